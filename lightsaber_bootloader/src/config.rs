@@ -0,0 +1,290 @@
+use core::{
+    fmt,
+    str
+};
+
+use log::LevelFilter;
+
+pub const DEFAULT_KERNEL_PATH: &str = r"\efi\kernel\lightsaber.elf";
+pub const DEFAULT_INITIAL_RAMDISK_PATH: &str = r"\efi\kernel\initrd";
+pub const DEFAULT_KERNEL_STACK_SIZE: u64 = 80 * 1024;
+
+// The kernel needs at least one page of stack besides the one it starts on.
+const MINIMUM_KERNEL_STACK_SIZE: u64 = 2 * 4096;
+// Far more than the kernel uses, and small enough that mapping it cannot overflow.
+pub const MAXIMUM_KERNEL_STACK_SIZE: u64 = 64 << 20;
+pub const MAXIMUM_TIMEOUT_SECONDS: u64 = 600;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Resolution {
+    pub width: usize,
+    pub height: usize
+}
+
+// Read from a file of `key = value` lines; lines starting with `#` are comments, and anything left out keeps its default.
+//
+//     kernel = \efi\kernel\lightsaber.elf
+//     initrd = none
+//     cmdline = loglevel=debug
+//     resolution = 1280x720
+//     log_level = info
+//     stack_size = 128K
+//     timeout = 3
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct BootConfig<'config> {
+    pub kernel_path: &'config str,
+    pub initial_ramdisk_path: Option<&'config str>,
+    pub command_line: &'config str,
+    pub resolution: Option<Resolution>,
+    pub log_level: LevelFilter,
+    pub kernel_stack_size: u64,
+    pub timeout_seconds: u64
+}
+
+impl Default for BootConfig<'_> {
+    fn default() -> Self {
+        Self {
+            kernel_path: DEFAULT_KERNEL_PATH,
+            initial_ramdisk_path: Some(DEFAULT_INITIAL_RAMDISK_PATH),
+            command_line: "",
+            resolution: None,
+            log_level: LevelFilter::Info,
+            kernel_stack_size: DEFAULT_KERNEL_STACK_SIZE,
+            timeout_seconds: 0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ConfigErrorKind<'config> {
+    NotUtf8,
+
+    MissingEquals,
+
+    UnknownKey(&'config str),
+
+    DuplicateKey(&'config str),
+
+    InvalidValue(&'config str),
+
+    EmptyValue(&'config str)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct ConfigError<'config> {
+    pub line: usize,
+    pub kind: ConfigErrorKind<'config>
+}
+
+impl fmt::Display for ConfigError<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.kind {
+            ConfigErrorKind::NotUtf8 => write!(formatter, "The boot configuration is not valid UTF-8."),
+            ConfigErrorKind::MissingEquals => write!(formatter, "Line {}: expected `key = value`.", self.line),
+            ConfigErrorKind::UnknownKey(key) => write!(formatter, "Line {}: unknown key `{}`.", self.line, key),
+            ConfigErrorKind::DuplicateKey(key) => write!(formatter, "Line {}: `{}` is set more than once.", self.line, key),
+            ConfigErrorKind::InvalidValue(key) => write!(formatter, "Line {}: invalid value for `{}`.", self.line, key),
+            ConfigErrorKind::EmptyValue(key) => write!(formatter, "Line {}: `{}` needs a value.", self.line, key)
+        }
+    }
+}
+
+const CONFIG_KEYS: [&str; 7] = ["kernel", "initrd", "cmdline", "resolution", "log_level", "stack_size", "timeout"];
+
+impl<'config> BootConfig<'config> {
+    pub fn parse(bytes: &'config [u8]) -> Result<Self, ConfigError<'config>> {
+        let text = str::from_utf8(bytes).map_err(|_| ConfigError {
+            line: 0,
+            kind: ConfigErrorKind::NotUtf8
+        })?;
+
+        let mut config = Self::default();
+        let mut seen = [false; CONFIG_KEYS.len()];
+
+        for (index, line) in text.lines().enumerate() {
+            let error = |kind| ConfigError {
+                line: index + 1,
+                kind
+            };
+
+            // The command line may well contain a `#` of its own, so comments only start a line.
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (key, value) = match line.find('=') {
+                Some(equals) => (line[..equals].trim(), line[equals + 1..].trim()),
+                None => return Err(error(ConfigErrorKind::MissingEquals))
+            };
+
+            let key_index = CONFIG_KEYS
+                .iter()
+                .position(|candidate| *candidate == key)
+                .ok_or_else(|| error(ConfigErrorKind::UnknownKey(key)))?;
+
+            if seen[key_index] {
+                return Err(error(ConfigErrorKind::DuplicateKey(key)));
+            }
+
+            seen[key_index] = true;
+
+            // Only the command line may be left empty, to clear it.
+            if value.is_empty() && key != "cmdline" {
+                return Err(error(ConfigErrorKind::EmptyValue(key)));
+            }
+
+            let invalid = || error(ConfigErrorKind::InvalidValue(key));
+
+            match key {
+                "kernel" => config.kernel_path = value,
+                "initrd" => config.initial_ramdisk_path = if value == "none" { None } else { Some(value) },
+                "cmdline" => config.command_line = value,
+                "resolution" => config.resolution = Some(lightsaber_parse_resolution(value).ok_or_else(invalid)?),
+                "log_level" => config.log_level = lightsaber_parse_log_level(value).ok_or_else(invalid)?,
+                "stack_size" => {
+                    config.kernel_stack_size = lightsaber_parse_size(value)
+                        .filter(|size| (MINIMUM_KERNEL_STACK_SIZE..=MAXIMUM_KERNEL_STACK_SIZE).contains(size))
+                        .ok_or_else(invalid)?
+                }
+                "timeout" => {
+                    config.timeout_seconds = value
+                        .parse()
+                        .ok()
+                        .filter(|seconds| *seconds <= MAXIMUM_TIMEOUT_SECONDS)
+                        .ok_or_else(invalid)?
+                }
+                _ => unreachable!()
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn lightsaber_parse_resolution(value: &str) -> Option<Resolution> {
+    let separator = value.find(&['x', 'X'][..])?;
+
+    let width = value[..separator].trim().parse().ok()?;
+    let height = value[separator + 1..].trim().parse().ok()?;
+
+    if width == 0 || height == 0 {
+        return None;
+    }
+
+    Some(Resolution {
+        width,
+        height
+    })
+}
+
+pub fn lightsaber_parse_log_level(value: &str) -> Option<LevelFilter> {
+    match value {
+        "off" => Some(LevelFilter::Off),
+        "error" => Some(LevelFilter::Error),
+        "warn" => Some(LevelFilter::Warn),
+        "info" => Some(LevelFilter::Info),
+        "debug" => Some(LevelFilter::Debug),
+        "trace" => Some(LevelFilter::Trace),
+        _ => None
+    }
+}
+
+// Bytes, or kibibytes and mebibytes with a `K` or `M` suffix.
+fn lightsaber_parse_size(value: &str) -> Option<u64> {
+    let (digits, multiplier) = match value.chars().last()? {
+        'k' | 'K' => (&value[..value.len() - 1], 1 << 10),
+        'm' | 'M' => (&value[..value.len() - 1], 1 << 20),
+        _ => (value, 1)
+    };
+
+    digits.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn an_empty_file_keeps_the_defaults() {
+        assert_eq!(BootConfig::parse(b""), Ok(BootConfig::default()));
+        assert_eq!(BootConfig::parse(b"# Nothing to see.\n\n   \n"), Ok(BootConfig::default()));
+    }
+
+    #[test]
+    fn parses_every_key() {
+        let config = BootConfig::parse(b"\
+kernel = \\efi\\test\\kernel.elf
+initrd = none
+cmdline = loglevel=debug init=/bin/sh # not a comment
+resolution = 1280x720
+log_level = trace
+stack_size = 128K
+timeout = 3
+").unwrap();
+
+        assert_eq!(config, BootConfig {
+            kernel_path: r"\efi\test\kernel.elf",
+            initial_ramdisk_path: None,
+            command_line: "loglevel=debug init=/bin/sh # not a comment",
+            resolution: Some(Resolution {
+                width: 1280,
+                height: 720
+            }),
+            log_level: LevelFilter::Trace,
+            kernel_stack_size: 128 * 1024,
+            timeout_seconds: 3
+        });
+    }
+
+    #[test]
+    fn accepts_crlf_and_surrounding_whitespace() {
+        let config = BootConfig::parse(b"  resolution=  800 X 600  \r\n\tlog_level = warn\r\n").unwrap();
+
+        assert_eq!(config.resolution, Some(Resolution {
+            width: 800,
+            height: 600
+        }));
+        assert_eq!(config.log_level, LevelFilter::Warn);
+    }
+
+    #[test]
+    fn reports_the_line_of_a_syntax_error() {
+        let error = BootConfig::parse(b"timeout = 1\n\nthis is not a setting\n").unwrap_err();
+
+        assert_eq!(error, ConfigError {
+            line: 3,
+            kind: ConfigErrorKind::MissingEquals
+        });
+    }
+
+    #[test]
+    fn rejects_unknown_and_repeated_keys() {
+        assert_eq!(BootConfig::parse(b"colour = blue").unwrap_err().kind, ConfigErrorKind::UnknownKey("colour"));
+        assert_eq!(BootConfig::parse(b"timeout = 1\ntimeout = 2").unwrap_err(), ConfigError {
+            line: 2,
+            kind: ConfigErrorKind::DuplicateKey("timeout")
+        });
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        for config in [&b"resolution = 1280"[..], b"resolution = 0x720", b"log_level = loud", b"stack_size = 1K", b"stack_size = 65M", b"stack_size = 999999999M", b"stack_size = lots", b"timeout = -1", b"timeout = 601"].iter() {
+            assert!(matches!(BootConfig::parse(config).unwrap_err().kind, ConfigErrorKind::InvalidValue(_)), "{:?} was accepted.", str::from_utf8(config));
+        }
+
+        assert_eq!(BootConfig::parse(b"stack_size = 64M").map(|config| config.kernel_stack_size), Ok(MAXIMUM_KERNEL_STACK_SIZE));
+        assert_eq!(BootConfig::parse(b"timeout = 600").map(|config| config.timeout_seconds), Ok(MAXIMUM_TIMEOUT_SECONDS));
+        assert_eq!(BootConfig::parse(b"kernel =").unwrap_err().kind, ConfigErrorKind::EmptyValue("kernel"));
+        assert_eq!(BootConfig::parse(b"cmdline =").map(|config| config.command_line), Ok(""));
+        assert_eq!(BootConfig::parse(&[0xFF, 0xFE]).unwrap_err().kind, ConfigErrorKind::NotUtf8);
+    }
+
+    #[test]
+    fn error_messages_name_the_line_and_key() {
+        let error = BootConfig::parse(b"\n\nstack_size = huge").unwrap_err();
+
+        assert_eq!(format!("{}", error), "Line 3: invalid value for `stack_size`.");
+    }
+}
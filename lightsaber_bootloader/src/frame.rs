@@ -0,0 +1,516 @@
+use core::{
+    fmt,
+    mem::MaybeUninit
+};
+
+use uefi::table::boot::{
+    MemoryDescriptor,
+    MemoryType
+};
+
+use x86_64::{
+    structures::paging::{
+        FrameAllocator,
+        PageSize,
+        PhysFrame,
+        Size4KiB
+    },
+    PhysAddr
+};
+
+use crate::{
+    MemoryRegion,
+    MemoryRegionType
+};
+
+// The UEFI memory types that are reclassified, numbered as in the UEFI specification.
+pub const UEFI_LOADER_CODE: u32 = 1;
+pub const UEFI_LOADER_DATA: u32 = 2;
+pub const UEFI_BOOT_SERVICES_CODE: u32 = 3;
+pub const UEFI_BOOT_SERVICES_DATA: u32 = 4;
+pub const UEFI_RUNTIME_SERVICES_CODE: u32 = 5;
+pub const UEFI_RUNTIME_SERVICES_DATA: u32 = 6;
+
+pub trait BootMemoryRegion: Copy + fmt::Debug {
+    fn start(&self) -> PhysAddr;
+
+    fn len(&self) -> u64;
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn region_type(&self) -> MemoryRegionType;
+}
+
+// The descriptors the firmware hands over when the bootloader exits boot services.
+impl BootMemoryRegion for MemoryDescriptor {
+    fn start(&self) -> PhysAddr {
+        PhysAddr::new(self.phys_start)
+    }
+
+    fn len(&self) -> u64 {
+        self.page_count * Size4KiB::SIZE
+    }
+
+    fn region_type(&self) -> MemoryRegionType {
+        match self.ty {
+            MemoryType::CONVENTIONAL => MemoryRegionType::Usable,
+            other => MemoryRegionType::UnknownUefi(other.0)
+        }
+    }
+}
+
+// Hands out frames in address order from the usable regions, whatever order the firmware lists
+// them in, so that everything below the next free frame is known to be taken.
+pub struct BootFrameAllocator<I, D> {
+    original: I,
+    current_descriptor: Option<D>,
+    next_frame: PhysFrame
+}
+
+impl<I, D> BootFrameAllocator<I, D>
+where
+    I: ExactSizeIterator<Item = D> + Clone,
+    I::Item: BootMemoryRegion {
+    pub fn new(memory_map: I) -> Self {
+        let start_frame = PhysFrame::containing_address(PhysAddr::new(0x1000));
+
+        Self {
+            original: memory_map,
+            current_descriptor: None,
+            next_frame: start_frame
+        }
+    }
+
+    // The frames that lie wholly inside the region, as a half-open range; none for a region smaller than a frame.
+    fn descriptor_frames(descriptor: &I::Item) -> Option<(PhysFrame, PhysFrame)> {
+        let start = descriptor.start().as_u64();
+        let end = start.checked_add(descriptor.len())?;
+
+        let start_frame = PhysFrame::containing_address(PhysAddr::new(start).align_up(Size4KiB::SIZE));
+        let end_frame = PhysFrame::containing_address(PhysAddr::new(end).align_down(Size4KiB::SIZE));
+
+        if start_frame < end_frame {
+            Some((start_frame, end_frame))
+        }
+        else {
+            None
+        }
+    }
+
+    fn allocate_frame_from_descriptor(&mut self, descriptor: I::Item) -> Option<PhysFrame> {
+        let (start_frame, end_frame) = Self::descriptor_frames(&descriptor)?;
+
+        if self.next_frame < start_frame {
+            self.next_frame = start_frame;
+        }
+
+        if self.next_frame < end_frame {
+            let frame = self.next_frame;
+            self.next_frame += 1;
+
+            return Some(frame);
+        }
+
+        None
+    }
+
+    // The usable region that holds the lowest frame not yet handed out.
+    fn next_descriptor(&self) -> Option<I::Item> {
+        let next_frame = self.next_frame;
+
+        self.original
+            .clone()
+            .filter(|descriptor| descriptor.region_type() == MemoryRegionType::Usable)
+            .filter(|descriptor| Self::descriptor_frames(descriptor).map_or(false, |(_, end_frame)| end_frame > next_frame))
+            .min_by_key(|descriptor| descriptor.start())
+    }
+
+    // Every descriptor comes out as at most two regions: the part the bootloader used and the part it did not.
+    pub fn construct_memory_map(self, regions: &mut [MaybeUninit<MemoryRegion>]) -> &mut [MemoryRegion] {
+        let mut next_index = 0;
+
+        for descriptor in self.original {
+            // Nothing can be placed in an empty region, and the kernel has no use for one.
+            if descriptor.is_empty() {
+                continue;
+            }
+
+            let mut start = descriptor.start();
+            let end = start + descriptor.len();
+            let next_free = self.next_frame.start_address();
+            let r#type = match descriptor.region_type() {
+                MemoryRegionType::Usable => {
+                    if end <= next_free {
+                        MemoryRegionType::Bootloader
+                    }
+                    else if descriptor.start() >= next_free {
+                        MemoryRegionType::Usable
+                    }
+                    else {
+                        let used_region = MemoryRegion {
+                            start: descriptor.start().as_u64(),
+                            end: next_free.as_u64(),
+                            r#type: MemoryRegionType::Bootloader
+                        };
+
+                        Self::add_region(used_region, regions, &mut next_index)
+                            .expect("Failed to add memory region.");
+
+                        start = next_free;
+                        MemoryRegionType::Usable
+                    }
+                }
+                MemoryRegionType::UnknownUefi(other) => lightsaber_reclassify_uefi_memory(other),
+                other => other
+            };
+
+            let region = MemoryRegion {
+                start: start.as_u64(),
+                end: end.as_u64(),
+                r#type
+            };
+
+            Self::add_region(region, regions, &mut next_index).unwrap();
+        }
+
+        let initialized = &mut regions[..next_index];
+
+        unsafe {
+            &mut *(initialized as *mut [MaybeUninit<MemoryRegion>] as *mut [MemoryRegion])
+        }
+    }
+
+    // Enough room for `construct_memory_map`, however many of the usable regions it has to split.
+    pub fn memory_map_capacity(&self) -> usize {
+        let usable = self
+            .original
+            .clone()
+            .filter(|descriptor| descriptor.region_type() == MemoryRegionType::Usable)
+            .count();
+
+        self.original.len() + usable
+    }
+
+    pub fn max_physical_address(&self) -> PhysAddr {
+        self
+            .original
+            .clone()
+            .map(|r| r.start() + r.len())
+            .max()
+            .unwrap()
+    }
+
+    fn add_region(region: MemoryRegion, regions: &mut [MaybeUninit<MemoryRegion>], next_index: &mut usize) -> Result<(), ()> {
+        unsafe {
+            regions
+                .get_mut(*next_index)
+                .ok_or(())?
+                .as_mut_ptr()
+                .write(region)
+        };
+
+        *next_index += 1;
+        Ok(())
+    }
+}
+
+unsafe impl<I, D> FrameAllocator<Size4KiB> for BootFrameAllocator<I, D>
+where
+    I: ExactSizeIterator<Item = D> + Clone,
+    I::Item: BootMemoryRegion {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        loop {
+            if let Some(current_descriptor) = self.current_descriptor {
+                if let Some(frame) = self.allocate_frame_from_descriptor(current_descriptor) {
+                    return Some(frame);
+                }
+            }
+
+            self.current_descriptor = Some(self.next_descriptor()?);
+        }
+    }
+}
+
+pub fn lightsaber_reclassify_uefi_memory(memory_type: u32) -> MemoryRegionType {
+    match memory_type {
+        // The kernel image is mapped straight out of loader memory, so it must never be handed out as usable.
+        UEFI_LOADER_CODE | UEFI_LOADER_DATA => MemoryRegionType::Bootloader,
+        UEFI_BOOT_SERVICES_CODE
+            | UEFI_BOOT_SERVICES_DATA
+            | UEFI_RUNTIME_SERVICES_CODE
+            | UEFI_RUNTIME_SERVICES_DATA => MemoryRegionType::Usable,
+        other => MemoryRegionType::UnknownUefi(other)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::BTreeSet,
+        mem::MaybeUninit,
+        vec::Vec
+    };
+
+    use x86_64::{
+        structures::paging::{
+            FrameAllocator,
+            PhysFrame
+        },
+        PhysAddr
+    };
+
+    use super::*;
+
+    const FRAME: u64 = 0x1000;
+
+    #[derive(Debug, Clone, Copy)]
+    struct TestRegion {
+        start: u64,
+        len: u64,
+        r#type: MemoryRegionType
+    }
+
+    impl BootMemoryRegion for TestRegion {
+        fn start(&self) -> PhysAddr {
+            PhysAddr::new(self.start)
+        }
+
+        fn len(&self) -> u64 {
+            self.len
+        }
+
+        fn region_type(&self) -> MemoryRegionType {
+            self.r#type
+        }
+    }
+
+    fn usable(start: u64, len: u64) -> TestRegion {
+        TestRegion {
+            start,
+            len,
+            r#type: MemoryRegionType::Usable
+        }
+    }
+
+    fn uefi(start: u64, len: u64, memory_type: u32) -> TestRegion {
+        TestRegion {
+            start,
+            len,
+            r#type: MemoryRegionType::UnknownUefi(memory_type)
+        }
+    }
+
+    fn region(start: u64, end: u64, r#type: MemoryRegionType) -> MemoryRegion {
+        MemoryRegion {
+            start,
+            end,
+            r#type
+        }
+    }
+
+    // Allocates `count` frames, then builds the memory map the kernel would be given.
+    fn allocate_then_map(map: &[TestRegion], count: usize) -> (Vec<PhysFrame>, Vec<MemoryRegion>) {
+        let mut allocator = BootFrameAllocator::new(map.iter().copied());
+
+        let frames = (0..count)
+            .map(|_| allocator.allocate_frame().expect("Ran out of frames."))
+            .collect();
+
+        let mut storage = vec![MaybeUninit::uninit(); allocator.memory_map_capacity()];
+        let regions = allocator.construct_memory_map(&mut storage).to_vec();
+
+        (frames, regions)
+    }
+
+    fn frame(address: u64) -> PhysFrame {
+        PhysFrame::containing_address(PhysAddr::new(address))
+    }
+
+    #[test]
+    fn allocates_every_frame_of_a_region_then_runs_out() {
+        let map = [usable(0x1000, 3 * FRAME)];
+        let mut allocator = BootFrameAllocator::new(map.iter().copied());
+
+        assert_eq!(allocator.allocate_frame(), Some(frame(0x1000)));
+        assert_eq!(allocator.allocate_frame(), Some(frame(0x2000)));
+        assert_eq!(allocator.allocate_frame(), Some(frame(0x3000)));
+        assert_eq!(allocator.allocate_frame(), None);
+    }
+
+    #[test]
+    fn never_allocates_the_first_frame() {
+        let (frames, _) = allocate_then_map(&[usable(0, 2 * FRAME)], 1);
+
+        assert_eq!(frames, [frame(0x1000)]);
+    }
+
+    #[test]
+    fn splits_the_used_prefix_of_a_region_into_bootloader() {
+        let map = [
+            usable(0x1000, 4 * FRAME),
+            uefi(0x5000, FRAME, 0x0B)
+        ];
+
+        let (_, regions) = allocate_then_map(&map, 2);
+
+        assert_eq!(regions, [
+            region(0x1000, 0x3000, MemoryRegionType::Bootloader),
+            region(0x3000, 0x5000, MemoryRegionType::Usable),
+            region(0x5000, 0x6000, MemoryRegionType::UnknownUefi(0x0B))
+        ]);
+    }
+
+    #[test]
+    fn marks_fully_used_regions_as_bootloader() {
+        let map = [
+            usable(0x1000, 2 * FRAME),
+            usable(0x10000, 2 * FRAME)
+        ];
+
+        let (frames, regions) = allocate_then_map(&map, 3);
+
+        assert_eq!(frames, [frame(0x1000), frame(0x2000), frame(0x10000)]);
+        assert_eq!(regions, [
+            region(0x1000, 0x3000, MemoryRegionType::Bootloader),
+            region(0x10000, 0x11000, MemoryRegionType::Bootloader),
+            region(0x11000, 0x12000, MemoryRegionType::Usable)
+        ]);
+    }
+
+    #[test]
+    fn reclassifies_uefi_memory_types() {
+        let map = [
+            uefi(0x100000, FRAME, UEFI_LOADER_CODE),
+            uefi(0x101000, FRAME, UEFI_LOADER_DATA),
+            uefi(0x102000, FRAME, UEFI_BOOT_SERVICES_CODE),
+            uefi(0x103000, FRAME, UEFI_BOOT_SERVICES_DATA),
+            uefi(0x104000, FRAME, UEFI_RUNTIME_SERVICES_CODE),
+            uefi(0x105000, FRAME, UEFI_RUNTIME_SERVICES_DATA),
+            // ACPI reclaim memory holds the tables the kernel has yet to read.
+            uefi(0x106000, FRAME, 9),
+            usable(0x200000, FRAME)
+        ];
+
+        let (_, regions) = allocate_then_map(&map, 0);
+        let types: Vec<MemoryRegionType> = regions.iter().map(|region| region.r#type).collect();
+
+        assert_eq!(types, [
+            MemoryRegionType::Bootloader,
+            MemoryRegionType::Bootloader,
+            MemoryRegionType::Usable,
+            MemoryRegionType::Usable,
+            MemoryRegionType::Usable,
+            MemoryRegionType::Usable,
+            MemoryRegionType::UnknownUefi(9),
+            MemoryRegionType::Usable
+        ]);
+    }
+
+    #[test]
+    fn reclaimable_uefi_memory_is_not_allocated_from() {
+        let map = [
+            uefi(0x1000, 4 * FRAME, UEFI_BOOT_SERVICES_DATA),
+            usable(0x5000, FRAME)
+        ];
+
+        let (frames, _) = allocate_then_map(&map, 1);
+
+        assert_eq!(frames, [frame(0x5000)]);
+    }
+
+    #[test]
+    fn allocates_in_address_order_from_an_unsorted_map() {
+        let map = [
+            usable(0x20000, 2 * FRAME),
+            usable(0x1000, 2 * FRAME),
+            usable(0x10000, FRAME)
+        ];
+
+        let (frames, regions) = allocate_then_map(&map, 4);
+
+        assert_eq!(frames, [frame(0x1000), frame(0x2000), frame(0x10000), frame(0x20000)]);
+        assert_eq!(regions, [
+            region(0x20000, 0x21000, MemoryRegionType::Bootloader),
+            region(0x21000, 0x22000, MemoryRegionType::Usable),
+            region(0x1000, 0x3000, MemoryRegionType::Bootloader),
+            region(0x10000, 0x11000, MemoryRegionType::Bootloader)
+        ]);
+    }
+
+    #[test]
+    fn overlapping_regions_never_hand_out_a_frame_twice() {
+        let map = [
+            usable(0x1000, 4 * FRAME),
+            usable(0x3000, 4 * FRAME)
+        ];
+
+        let (frames, regions) = allocate_then_map(&map, 5);
+        let unique: BTreeSet<PhysFrame> = frames.iter().copied().collect();
+
+        assert_eq!(unique.len(), 5);
+        assert_eq!(frames.last(), Some(&frame(0x5000)));
+
+        // Nothing that was allocated is reported as usable by either region.
+        for region in regions.iter().filter(|region| region.r#type == MemoryRegionType::Usable) {
+            assert!(frames.iter().all(|frame| frame.start_address().as_u64() < region.start || frame.start_address().as_u64() >= region.end));
+        }
+    }
+
+    #[test]
+    fn zero_length_regions_are_skipped() {
+        let map = [
+            usable(0, 0),
+            usable(0x1000, 0),
+            usable(0x2000, FRAME)
+        ];
+
+        let (frames, regions) = allocate_then_map(&map, 1);
+
+        assert_eq!(frames, [frame(0x2000)]);
+        assert_eq!(regions, [region(0x2000, 0x3000, MemoryRegionType::Bootloader)]);
+    }
+
+    #[test]
+    fn only_whole_frames_are_allocated_from_unaligned_regions() {
+        let map = [
+            usable(0x1800, 0x1000),
+            usable(0x4800, 0x2000)
+        ];
+
+        let mut allocator = BootFrameAllocator::new(map.iter().copied());
+
+        assert_eq!(allocator.allocate_frame(), Some(frame(0x5000)));
+        assert_eq!(allocator.allocate_frame(), None);
+    }
+
+    #[test]
+    fn splits_a_region_that_straddles_the_next_free_frame() {
+        let map = [usable(0x1800, 0x3000)];
+
+        let (frames, regions) = allocate_then_map(&map, 1);
+
+        assert_eq!(frames, [frame(0x2000)]);
+        assert_eq!(regions, [
+            region(0x1800, 0x3000, MemoryRegionType::Bootloader),
+            region(0x3000, 0x4800, MemoryRegionType::Usable)
+        ]);
+    }
+
+    #[test]
+    fn capacity_covers_a_split_of_every_usable_region() {
+        let map = [
+            usable(0x1000, 4 * FRAME),
+            usable(0x1000, 4 * FRAME),
+            usable(0x2000, 4 * FRAME),
+            uefi(0x10000, FRAME, UEFI_LOADER_DATA)
+        ];
+
+        let (_, regions) = allocate_then_map(&map, 2);
+
+        assert_eq!(regions.len(), 7);
+        assert_eq!(regions.iter().filter(|region| region.r#type == MemoryRegionType::Usable).count(), 3);
+    }
+}
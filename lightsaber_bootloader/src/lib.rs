@@ -1,19 +1,74 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 use core::{
     ops,
-    slice
+    slice,
+    str
 };
 
 use lightsaber_graphics::Framebuffer;
 
+pub mod config;
+pub mod frame;
+pub mod load_options;
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct BootInformation {
     pub rsdp_address: u64,
     pub phys_memory_offset: u64,
     pub framebuffer: Framebuffer,
-    pub memory_regions: MemoryRegions
+    pub memory_regions: MemoryRegions,
+    pub initial_ramdisk: InitialRamdisk,
+    pub command_line: CommandLine
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct InitialRamdisk {
+    pub start: u64,
+    pub len: u64
+}
+
+impl InitialRamdisk {
+    #[inline]
+    pub fn is_present(&self) -> bool {
+        self.start != 0 && self.len != 0
+    }
+
+    /// # Safety
+    ///
+    /// The ramdisk must still be mapped where the bootloader left it, and its memory must not be reused.
+    pub unsafe fn as_slice(&self) -> &'static [u8] {
+        slice::from_raw_parts(self.start as *const u8, self.len as usize)
+    }
+}
+
+// The kernel command line, copied in after the memory map so that it lives as long as the boot information.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct CommandLine {
+    pub(in crate) ptr: *const u8,
+    pub(in crate) len: usize
+}
+
+impl From<&'static str> for CommandLine {
+    fn from(command_line: &'static str) -> Self {
+        Self {
+            ptr: command_line.as_ptr(),
+            len: command_line.len()
+        }
+    }
+}
+
+impl ops::Deref for CommandLine {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            str::from_utf8_unchecked(slice::from_raw_parts(self.ptr, self.len))
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq)]
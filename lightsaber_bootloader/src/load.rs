@@ -3,7 +3,8 @@ use core::{
         self,
         MaybeUninit
     },
-    slice
+    slice,
+    str
 };
 
 use uefi::{
@@ -57,6 +58,7 @@ use xmas_elf::{
 
 use lightsaber_bootloader::{
     BootInformation,
+    InitialRamdisk,
     MemoryRegion
 };
 
@@ -78,6 +80,9 @@ use x86_64::structures::paging::Mapper;
 type Size4KiBPageArray = [u64; Size4KiB::SIZE as usize / 8];
 const SIZE_4_KIB_ZERO_ARRAY: Size4KiBPageArray = [0; Size4KiB::SIZE as usize / 8];
 
+// The lower half of the address space is left to user processes.
+const KERNEL_LEVEL_FOUR_START: usize = 256;
+
 #[derive(Debug)]
 pub struct LevelFourEntries {
     entries: [bool; 512]
@@ -110,6 +115,7 @@ impl LevelFourEntries {
             .entries
             .iter_mut()
             .enumerate()
+            .skip(KERNEL_LEVEL_FOUR_START)
             .find(|(_, &mut entry)| !entry)
             .expect("No usable Level Four Entries are found.");
 
@@ -129,30 +135,37 @@ pub struct Mappings {
     pub stack_end: Page,
     pub used_entries: LevelFourEntries,
     pub framebuffer: VirtAddr,
-    pub phys_memory_offset: VirtAddr
+    pub phys_memory_offset: VirtAddr,
+    pub initial_ramdisk: Option<VirtAddr>
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct SystemInformation {
     pub framebuffer_address: PhysAddr,
     pub framebuffer_information: FramebufferInformation,
-    pub rsdp_address: Option<PhysAddr>
+    pub rsdp_address: Option<PhysAddr>,
+    pub initial_ramdisk: Option<&'static [u8]>,
+    pub kernel_stack_size: u64,
+    pub command_line: &'static str
 }
 
 fn lightsaber_create_boot_information<I, D>(mut frame_allocator: BootFrameAllocator<I, D>, page_tables: &mut PageTables, mappings: &mut Mappings, system_information: SystemInformation) -> (&'static mut BootInformation, ReservedFrames)
 where
     I: ExactSizeIterator<Item = D> + Clone,
     I::Item: BootMemoryRegion {
-    let (boot_information, memory_regions_) = {
+    let (boot_information, memory_regions_, command_line) = {
         let boot_info_address = mappings.used_entries.get_free_address();
         let boot_info_end = boot_info_address + mem::size_of::<BootInformation>();
 
         let memory_map_regions_address = boot_info_end.align_up(mem::align_of::<MemoryRegion>() as u64);
-        let regions = frame_allocator.len() + 1;
+        let regions = frame_allocator.memory_map_capacity();
         let memory_map_regions_end = memory_map_regions_address + regions * mem::size_of::<MemoryRegion>();
 
+        let command_line_address = memory_map_regions_end;
+        let command_line_end = command_line_address + system_information.command_line.len();
+
         let start_page = Page::containing_address(boot_info_address);
-        let end_page = Page::containing_address(memory_map_regions_end - 1u64);
+        let end_page = Page::containing_address(command_line_end - 1u64);
 
         Page::range_inclusive(start_page, end_page).for_each(|page| {
             let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE;
@@ -183,7 +196,13 @@ where
             slice::from_raw_parts_mut(memory_map_regions_address.as_mut_ptr(), regions)
         };
 
-        (boot_info, memory_regions)
+        let command_line: &'static mut [u8] = unsafe {
+            slice::from_raw_parts_mut(command_line_address.as_mut_ptr(), system_information.command_line.len())
+        };
+
+        command_line.copy_from_slice(system_information.command_line.as_bytes());
+
+        (boot_info, memory_regions, command_line)
     };
 
     let reserved_frames = ReservedFrames::new(&mut frame_allocator);
@@ -203,7 +222,14 @@ where
             rsdp_address: system_information.rsdp_address.unwrap().as_u64(),
             phys_memory_offset: mappings.phys_memory_offset.as_u64(),
             framebuffer,
-            memory_regions: memory_regions.into()
+            memory_regions: memory_regions.into(),
+            initial_ramdisk: InitialRamdisk {
+                start: mappings.initial_ramdisk.map(|address| address.as_u64()).unwrap_or(0),
+                len: system_information.initial_ramdisk.map(|initial_ramdisk| initial_ramdisk.len() as u64).unwrap_or(0)
+            },
+            command_line: unsafe {
+                str::from_utf8_unchecked(command_line)
+            }.into()
         }),
         reserved_frames
     )
@@ -268,6 +294,10 @@ where
 }
 
 pub fn lightsaber_load_file(boot_services: &BootServices, path: &str) -> &'static [u8] {
+    lightsaber_try_load_file(boot_services, path).unwrap_or_else(|| panic!("Failed to load file `{}`.", path))
+}
+
+pub fn lightsaber_try_load_file(boot_services: &BootServices, path: &str) -> Option<&'static [u8]> {
     let mut information_buffer = [0u8; 0x100];
 
     let filesystem = unsafe {
@@ -289,9 +319,14 @@ pub fn lightsaber_load_file(boot_services: &BootServices, path: &str) -> &'stati
 
     log::info!("Found volume label: {}.", volume_label);
 
-    let file_handle = root
-        .open(path, FileMode::Read, FileAttribute::empty())
-        .expect_success("Failed to retrieve file handle.");
+    let file_handle = match root.open(path, FileMode::Read, FileAttribute::empty()) {
+        Ok(file_handle) => file_handle.log(),
+        Err(_) => {
+            log::warn!("File `{}` was not found.", path);
+
+            return None;
+        }
+    };
 
     let mut file_handle = unsafe {
         RegularFile::new(file_handle)
@@ -313,7 +348,7 @@ pub fn lightsaber_load_file(boot_services: &BootServices, path: &str) -> &'stati
     let length = file_handle.read(buffer)
         .expect_success("Failed to read file.");
 
-    buffer[..length].as_ref()
+    Some(buffer[..length].as_ref())
 }
 
 fn lightsaber_load_system_kernel(frame_allocator: &mut impl FrameAllocator<Size4KiB>, page_tables: &mut PageTables, kernel_bytes: &[u8]) -> (u64, LevelFourEntries) {
@@ -335,14 +370,13 @@ fn lightsaber_load_system_kernel(frame_allocator: &mut impl FrameAllocator<Size4
     kernel_elf.program_iter().for_each(|program_header| {
         program::sanity_check(program_header, &kernel_elf).expect("Failed program header sanity check.");
 
-        match program_header.get_type().expect("Could not get program header type.") {
-            Type::Load => lightsaber_map_segment(
+        if let Type::Load = program_header.get_type().expect("Could not get program header type.") {
+            lightsaber_map_segment(
                 &program_header,
                 kernel_offset,
                 frame_allocator,
                 &mut page_tables.kernel_page_table
-            ),
-            _ => ()
+            );
         }
     });
 
@@ -395,8 +429,6 @@ fn lightsaber_map_segment(segment: &ProgramHeader, kernel_offset: PhysAddr, fram
                 new_frame_ptr.write(SIZE_4_KIB_ZERO_ARRAY)
             };
 
-            drop(new_frame_ptr);
-
             let original_bytes_ptr = original_frame.start_address().as_u64() as *mut u8;
             let new_bytes_ptr = new_frame.start_address().as_u64() as *mut u8;
 
@@ -428,8 +460,6 @@ fn lightsaber_map_segment(segment: &ProgramHeader, kernel_offset: PhysAddr, fram
                 frame_ptr.write(SIZE_4_KIB_ZERO_ARRAY)
             };
 
-            drop(frame_ptr);
-
             unsafe {
                 page_table
                     .map_to(page, frame, page_table_flags, frame_allocator)
@@ -451,7 +481,7 @@ where
     let stack_start_address = used_entries.get_free_address();
     let stack_start: Page = Page::containing_address(stack_start_address);
 
-    let stack_end_address = stack_start_address + Size4KiB::SIZE * 20;
+    let stack_end_address = stack_start_address + align_up(system_information.kernel_stack_size, Size4KiB::SIZE);
     let stack_end: Page = Page::containing_address(stack_end_address - 1u64);
 
     Page::range_inclusive(stack_start, stack_end).for_each(|page| {
@@ -487,6 +517,30 @@ where
     });
 
     let framebuffer = framebuffer_start_page.start_address();
+
+    let initial_ramdisk = system_information.initial_ramdisk.filter(|initial_ramdisk| !initial_ramdisk.is_empty()).map(|initial_ramdisk| {
+        log::info!("Mapping the initial ramdisk.");
+
+        let start_frame: PhysFrame = PhysFrame::containing_address(PhysAddr::new(initial_ramdisk.as_ptr() as u64));
+        let end_frame: PhysFrame = PhysFrame::containing_address(PhysAddr::new(initial_ramdisk.as_ptr() as u64 + initial_ramdisk.len() as u64 - 1));
+
+        let start_page: Page = Page::containing_address(used_entries.get_free_address());
+
+        PhysFrame::range_inclusive(start_frame, end_frame).enumerate().for_each(|(index, frame)| {
+            let page = start_page + index as u64;
+
+            unsafe {
+                page_tables
+                    .kernel_page_table
+                    .map_to(page, frame, PageTableFlags::PRESENT | PageTableFlags::NO_EXECUTE, frame_allocator)
+                    .unwrap()
+                    .flush();
+            }
+        });
+
+        start_page.start_address()
+    });
+
     let physical_memory_offset = used_entries.get_free_address();
 
     let start_frame = PhysFrame::containing_address(PhysAddr::new(0));
@@ -510,6 +564,7 @@ where
         stack_end,
         used_entries,
         framebuffer,
-        phys_memory_offset: physical_memory_offset
+        phys_memory_offset: physical_memory_offset,
+        initial_ramdisk
     }
 }
@@ -0,0 +1,81 @@
+use core::str;
+
+// Load options arrive as UCS-2. Anything but printable text, such as the binary data a boot entry may
+// carry, is not taken for a command line, and nothing is allocated for it.
+pub fn lightsaber_decode_load_options<'buffer, I, A>(units: I, allocate: A) -> Option<&'buffer str>
+where
+    I: Iterator<Item = u16> + Clone,
+    A: FnOnce(usize) -> Option<&'buffer mut [u8]> {
+    let characters = units.map(|unit| char::from_u32(unit as u32).filter(|character| *character == '\t' || !character.is_control()));
+
+    let len = characters.clone().try_fold(0, |len, character| character.map(|character| len + character.len_utf8()))?;
+
+    if characters.clone().flatten().all(char::is_whitespace) {
+        return None;
+    }
+
+    let buffer = allocate(len)?;
+    let mut offset = 0;
+
+    for character in characters.flatten() {
+        offset += character.encode_utf8(&mut buffer[offset..]).len();
+    }
+
+    str::from_utf8(buffer).ok().map(str::trim)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        cell::Cell,
+        vec::Vec
+    };
+
+    use super::*;
+
+    fn decode(text: &[u16]) -> Option<String> {
+        let mut buffer = Vec::new();
+
+        lightsaber_decode_load_options(text.iter().copied(), |len| {
+            buffer.resize(len, 0);
+            Some(&mut buffer[..])
+        }).map(String::from)
+    }
+
+    fn units(text: &str) -> Vec<u16> {
+        text.encode_utf16().collect()
+    }
+
+    #[test]
+    fn decodes_and_trims_printable_text() {
+        assert_eq!(decode(&units("  loglevel=debug\tinit=/bin/sh  ")).as_deref(), Some("loglevel=debug\tinit=/bin/sh"));
+        assert_eq!(decode(&units("init=/bin/\u{E9}t\u{E9}")).as_deref(), Some("init=/bin/\u{E9}t\u{E9}"));
+    }
+
+    #[test]
+    fn allocates_exactly_the_decoded_length() {
+        let asked = Cell::new(0);
+        let mut buffer = [0; 16];
+
+        let options = lightsaber_decode_load_options(units("a \u{E9}").into_iter(), |len| {
+            asked.set(len);
+            Some(&mut buffer[..len])
+        });
+
+        assert_eq!(options, Some("a \u{E9}"));
+        assert_eq!(asked.get(), 4);
+    }
+
+    #[test]
+    fn rejects_anything_but_printable_text_without_allocating() {
+        for text in [&[0x0001, 0x0002][..], &[0x006C, 0x000A], &[0xD800], &[0x0061, 0x001B]].iter() {
+            assert_eq!(lightsaber_decode_load_options(text.iter().copied(), |_| panic!("Allocated for {:04X?}.", text)), None);
+        }
+    }
+
+    #[test]
+    fn empty_or_blank_options_are_none() {
+        assert_eq!(decode(&[]), None);
+        assert_eq!(decode(&units(" \t ")), None);
+    }
+}
@@ -38,6 +38,8 @@ use uefi::{
 
 use x86_64::PhysAddr;
 
+use lightsaber_bootloader::config::BootConfig;
+
 use lightsaber_graphics::{
     debug::renderer::DebugRenderer,
     FramebufferInformation
@@ -45,6 +47,7 @@ use lightsaber_graphics::{
 
 mod load;
 mod logger;
+mod options;
 mod paging;
 mod unwind;
 
@@ -54,9 +57,9 @@ use crate::{
     paging::BootFrameAllocator
 };
 
-pub const PROJECT_LIGHTSABER_SYSTEM_KERNEL_ELF_PATH: &'static str = r"\efi\kernel\lightsaber.elf";
+pub const PROJECT_LIGHTSABER_SYSTEM_BOOT_CONFIG_PATH: &str = r"\efi\lightsaber\boot.cfg";
 
-fn lightsaber_initialize_display(system_table: &SystemTable<Boot>) -> (PhysAddr, FramebufferInformation) {
+fn lightsaber_initialize_display(system_table: &SystemTable<Boot>, config: &BootConfig) -> (PhysAddr, FramebufferInformation) {
     let graphics_output_protocol = system_table
         .boot_services()
         .locate_protocol::<GraphicsOutput>()
@@ -66,6 +69,19 @@ fn lightsaber_initialize_display(system_table: &SystemTable<Boot>) -> (PhysAddr,
         &mut *graphics_output_protocol.get()
     };
 
+    let requested_mode = config.resolution.map(|resolution| {
+        graphics_output_protocol
+            .modes()
+            .map(|mode| mode.log())
+            .find(|mode| mode.info().resolution() == (resolution.width, resolution.height))
+    });
+
+    if let Some(Some(mode)) = &requested_mode {
+        graphics_output_protocol
+            .set_mode(mode)
+            .expect_success("Failed to set the requested display mode.");
+    }
+
     let mode_information = graphics_output_protocol.current_mode_info();
     let mut framebuffer = graphics_output_protocol.frame_buffer();
 
@@ -90,18 +106,58 @@ fn lightsaber_initialize_display(system_table: &SystemTable<Boot>) -> (PhysAddr,
     let mutexed_logger = logger::LOGGER.call_once(|| global_logger);
 
     log::set_logger(mutexed_logger).expect("Failed to set global logger.");
-    log::set_max_level(log::LevelFilter::Info);
+    log::set_max_level(config.log_level);
+
+    if let (Some(resolution), Some(None)) = (config.resolution, requested_mode) {
+        log::warn!("The display does not support {}x{}; keeping the current mode.", resolution.width, resolution.height);
+    }
 
     (PhysAddr::new(framebuffer.as_mut_ptr() as u64), framebuffer_information)
 }
 
+// Gives the user a chance to read the log before the kernel takes over the screen; any key skips the wait.
+fn lightsaber_wait_for_timeout(system_table: &SystemTable<Boot>, seconds: u64) {
+    if seconds == 0 {
+        return;
+    }
+
+    log::info!("Booting in {} seconds, press any key to boot now.", seconds);
+
+    let _ = system_table.stdin().reset(false);
+
+    for _ in 0..seconds.saturating_mul(10) {
+        if let Ok(completion) = system_table.stdin().read_key() {
+            if completion.log().is_some() {
+                return;
+            }
+        }
+
+        system_table.boot_services().stall(100_000);
+    }
+}
+
 #[entry]
 fn efi_main(image: Handle, system_table: SystemTable<Boot>) -> Status {
-    let (framebuffer_address, framebuffer_info) = lightsaber_initialize_display(&system_table);
+    // The configuration is read before the display is set up, so its errors can only be reported afterwards.
+    let config = load::lightsaber_try_load_file(system_table.boot_services(), PROJECT_LIGHTSABER_SYSTEM_BOOT_CONFIG_PATH)
+        .map_or(Ok(BootConfig::default()), BootConfig::parse);
+
+    let (framebuffer_address, framebuffer_info) = lightsaber_initialize_display(&system_table, config.as_ref().unwrap_or(&BootConfig::default()));
     log::info!("Initialized Graphics Output Protocol.");
     log::info!("Using framebuffer at address {:#x}.", framebuffer_address);
 
-    let kernel_bytes= load::lightsaber_load_file(system_table.boot_services(), PROJECT_LIGHTSABER_SYSTEM_KERNEL_ELF_PATH);
+    let config = config.unwrap_or_else(|error| panic!("Invalid boot configuration `{}`. {}", PROJECT_LIGHTSABER_SYSTEM_BOOT_CONFIG_PATH, error));
+
+    let kernel_bytes = load::lightsaber_load_file(system_table.boot_services(), config.kernel_path);
+    let initial_ramdisk = config
+        .initial_ramdisk_path
+        .and_then(|path| load::lightsaber_try_load_file(system_table.boot_services(), path));
+
+    // Options given at boot time take precedence over the configured ones.
+    let command_line = options::lightsaber_load_options(system_table.boot_services(), image).unwrap_or(config.command_line);
+    log::info!("Kernel command line: `{}`.", command_line);
+
+    lightsaber_wait_for_timeout(&system_table, config.timeout_seconds);
 
     let mmap_storage = {
         let max_mmap_size =
@@ -136,6 +192,9 @@ fn efi_main(image: Handle, system_table: SystemTable<Boot>) -> Status {
         framebuffer_address,
         framebuffer_information: framebuffer_info,
         rsdp_address,
+        initial_ramdisk,
+        kernel_stack_size: config.kernel_stack_size,
+        command_line
     };
 
     load::lightsaber_load_and_switch_to_system_kernel(frame_allocator, page_tables, kernel_bytes, system_info);
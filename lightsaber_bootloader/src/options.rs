@@ -0,0 +1,116 @@
+use core::{
+    ffi::c_void,
+    slice
+};
+
+use uefi::{
+    prelude::{
+        BootServices,
+        Handle
+    },
+    proto::Protocol,
+    table::boot::MemoryType,
+    CStr16,
+    Char16,
+    Guid,
+    Identify
+};
+
+use lightsaber_bootloader::load_options;
+
+// The start of EFI_LOADED_IMAGE_PROTOCOL, which the uefi crate does not give the size of the load options for.
+#[repr(C)]
+struct LoadedImageOptions {
+    revision: u32,
+    parent_handle: *const c_void,
+    system_table: *const c_void,
+    device_handle: *const c_void,
+    file_path: *const c_void,
+    reserved: *const c_void,
+    load_options_size: u32,
+    load_options: *const u16
+}
+
+// EFI_SHELL_PARAMETERS_PROTOCOL, which the UEFI shell installs on the images it starts, with the command line split up.
+#[repr(C)]
+struct ShellParameters {
+    argv: *const *const Char16,
+    argc: usize,
+    stdin: *const c_void,
+    stdout: *const c_void,
+    stderr: *const c_void
+}
+
+// The uefi crate's derives only work inside it.
+unsafe impl Identify for LoadedImageOptions {
+    const GUID: Guid = Guid::from_values(0x5B1B_31A1, 0x9562, 0x11D2, 0x8E3F, [0x00, 0xA0, 0xC9, 0x69, 0x72, 0x3B]);
+}
+
+impl Protocol for LoadedImageOptions { }
+
+unsafe impl Identify for ShellParameters {
+    const GUID: Guid = Guid::from_values(0x752F_3136, 0x4E16, 0x4FDC, 0xA22A, [0xE5, 0xF4, 0x68, 0x12, 0xF4, 0xCA]);
+}
+
+impl Protocol for ShellParameters { }
+
+// The buffer lives as long as the bootloader, as the boot information only copies the options once boot services are gone.
+fn lightsaber_allocate_options(boot_services: &BootServices, len: usize) -> Option<&'static mut [u8]> {
+    let buffer = boot_services
+        .allocate_pool(MemoryType::LOADER_DATA, len)
+        .ok()?
+        .log();
+
+    Some(unsafe {
+        slice::from_raw_parts_mut(buffer, len)
+    })
+}
+
+// Options the bootloader was started with. The shell passes the image's own path first, which is left out
+// through the arguments it splits up; a boot entry passes just its optional data.
+pub fn lightsaber_load_options(boot_services: &BootServices, image: Handle) -> Option<&'static str> {
+    let allocate = |len| lightsaber_allocate_options(boot_services, len);
+
+    if let Ok(shell_parameters) = boot_services.handle_protocol::<ShellParameters>(image) {
+        let shell_parameters = unsafe {
+            &*shell_parameters.log().get()
+        };
+
+        let arguments = unsafe {
+            slice::from_raw_parts(shell_parameters.argv, shell_parameters.argc)
+        };
+
+        let units = arguments.iter().skip(1).enumerate().flat_map(|(index, argument)| {
+            let separator = if index == 0 { None } else { Some(u16::from(b' ')) };
+            let argument = unsafe {
+                CStr16::from_ptr(*argument)
+            };
+
+            separator.into_iter().chain(argument.to_u16_slice().iter().copied())
+        });
+
+        return load_options::lightsaber_decode_load_options(units, allocate);
+    }
+
+    let loaded_image = boot_services
+        .handle_protocol::<LoadedImageOptions>(image)
+        .ok()?
+        .log();
+
+    let loaded_image = unsafe {
+        &*loaded_image.get()
+    };
+
+    if loaded_image.load_options.is_null() {
+        return None;
+    }
+
+    let units = unsafe {
+        slice::from_raw_parts(loaded_image.load_options, loaded_image.load_options_size as usize / 2)
+    };
+
+    // Text options end at a NUL when they do not fill the buffer.
+    let units = &units[..units.iter().position(|unit| *unit == 0).unwrap_or(units.len())];
+
+    load_options::lightsaber_decode_load_options(units.iter().copied(), allocate)
+}
@@ -1,13 +1,3 @@
-use core::{
-    fmt,
-    mem::MaybeUninit
-};
-
-use uefi::table::boot::{
-    MemoryDescriptor,
-    MemoryType
-};
-
 use x86_64::{
     registers::{
         control::{
@@ -24,205 +14,18 @@ use x86_64::{
     structures::paging::{
         FrameAllocator,
         OffsetPageTable,
-        PageSize,
         PageTable,
         PhysFrame,
         Size4KiB
     },
-    PhysAddr,
     VirtAddr
 };
 
-use lightsaber_bootloader::{
-    MemoryRegion,
-    MemoryRegionType
+pub use lightsaber_bootloader::frame::{
+    BootFrameAllocator,
+    BootMemoryRegion
 };
 
-pub struct BootFrameAllocator<I, D> {
-    original: I,
-    memory_map: I,
-    current_descriptor: Option<D>,
-    next_frame: PhysFrame
-}
-
-impl<I, D> BootFrameAllocator<I, D>
-where
-    I: ExactSizeIterator<Item = D> + Clone,
-    I::Item: BootMemoryRegion {
-    pub fn new(memory_map: I) -> Self {
-        let start_frame = PhysFrame::containing_address(PhysAddr::new(0x1000));
-
-        Self {
-            original: memory_map.clone(),
-            memory_map,
-            current_descriptor: None,
-            next_frame: start_frame
-        }
-   }
-
-    pub fn allocate_frame_from_descriptor(&mut self, descriptor: I::Item) -> Option<PhysFrame> {
-        let start_address = descriptor.start();
-        let start_frame = PhysFrame::containing_address(start_address);
-        let end_address = start_address + descriptor.len();
-        let end_frame = PhysFrame::containing_address(end_address - 1u64);
-
-        if self.next_frame < start_frame {
-            self.next_frame = start_frame;
-        }
-
-        if self.next_frame < end_frame {
-            let frame = self.next_frame;
-            self.next_frame += 1;
-
-            return Some(frame);
-        }
-
-        None
-    }
-
-    pub fn construct_memory_map(self, regions: &mut [MaybeUninit<MemoryRegion>]) -> &mut [MemoryRegion] {
-        let mut next_index = 0;
-
-        for descriptor in self.original {
-            let mut start = descriptor.start();
-            let end = start + descriptor.len();
-            let next_free = self.next_frame.start_address();
-            let r#type = match descriptor.region_type() {
-                MemoryRegionType::Usable => {
-                    if end <= next_free {
-                        MemoryRegionType::Bootloader
-                    }
-                    else if descriptor.start() >= next_free {
-                        MemoryRegionType::Usable
-                    }
-                    else {
-                        let used_region = MemoryRegion {
-                            start: descriptor.start().as_u64(),
-                            end: next_free.as_u64(),
-                            r#type: MemoryRegionType::Bootloader
-                        };
-
-                        Self::add_region(used_region, regions, &mut next_index)
-                            .expect("Failed to add memory region.");
-
-                        start = next_free;
-                        MemoryRegionType::Usable
-                    }
-                }
-                MemoryRegionType::UnknownUefi(other) => {
-                    match MemoryType(other) {
-                        MemoryType::LOADER_CODE
-                            | MemoryType::LOADER_DATA
-                            | MemoryType::BOOT_SERVICES_CODE
-                            | MemoryType::BOOT_SERVICES_DATA
-                            | MemoryType::RUNTIME_SERVICES_CODE
-                            | MemoryType::RUNTIME_SERVICES_DATA => {
-                            MemoryRegionType::Usable
-                        }
-                        other => MemoryRegionType::UnknownUefi(other.0)
-                    }
-                }
-                other => other
-            };
-
-            let region = MemoryRegion {
-                start: start.as_u64(),
-                end: end.as_u64(),
-                r#type
-            };
-
-            Self::add_region(region, regions, &mut next_index).unwrap();
-        }
-
-        let initialized = &mut regions[..next_index];
-
-        unsafe {
-            MaybeUninit::slice_assume_init_mut(initialized)
-        }
-    }
-
-    pub fn len(&self) -> usize {
-        self.original.len()
-    }
-
-    pub fn max_physical_address(&self) -> PhysAddr {
-        self
-            .original
-            .clone()
-            .map(|r| r.start() + r.len())
-            .max()
-            .unwrap()
-    }
-
-    fn add_region(region: MemoryRegion, regions: &mut [MaybeUninit<MemoryRegion>], next_index: &mut usize) -> Result<(), ()> {
-        unsafe {
-            regions
-                .get_mut(*next_index)
-                .ok_or(())?
-                .as_mut_ptr()
-                .write(region)
-        };
-
-        *next_index += 1;
-        Ok(())
-    }
-}
-
-unsafe impl<I, D> FrameAllocator<Size4KiB> for BootFrameAllocator<I, D>
-where
-    I: ExactSizeIterator<Item = D> + Clone,
-    I::Item: BootMemoryRegion {
-    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
-        if let Some(current_descriptor) = self.current_descriptor {
-            match self.allocate_frame_from_descriptor(current_descriptor) {
-                Some(frame) => return Some(frame),
-                None => {
-                    self.current_descriptor = None;
-                }
-            }
-        }
-
-        while let Some(descriptor) = self.memory_map.next() {
-            if descriptor.region_type() != MemoryRegionType::Usable {
-                continue;
-            }
-
-            if let Some(frame) = self.allocate_frame_from_descriptor(descriptor) {
-                self.current_descriptor = Some(descriptor);
-
-                return Some(frame)
-            }
-        }
-
-        None
-    }
-}
-
-pub trait BootMemoryRegion: Copy + fmt::Debug {
-    fn start(&self) -> PhysAddr;
-
-    fn len(&self) -> u64;
-
-    fn region_type(&self) -> MemoryRegionType;
-}
-
-impl BootMemoryRegion for MemoryDescriptor {
-    fn start(&self) -> PhysAddr {
-        PhysAddr::new(self.phys_start)
-    }
-
-    fn len(&self) -> u64 {
-        self.page_count * Size4KiB::SIZE
-    }
-
-    fn region_type(&self) -> MemoryRegionType {
-        match self.ty {
-            MemoryType::CONVENTIONAL => MemoryRegionType::Usable,
-            other => MemoryRegionType::UnknownUefi(other.0)
-        }
-    }
-}
-
 pub struct PageTables {
     pub boot_page_table: OffsetPageTable<'static>,
     pub kernel_page_table: OffsetPageTable<'static>,
@@ -70,3 +70,19 @@ impl ColourCode {
         self.1
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Colour;
+
+    #[test]
+    fn from_rgba_packs_red_into_the_lowest_byte() {
+        let colour = Colour::from_rgba(0x11, 0x22, 0x33, 0x44);
+
+        assert_eq!(colour.inner(), 0x4433_2211);
+        assert_eq!(
+            [colour.get_r_bit(), colour.get_g_bit(), colour.get_b_bit(), colour.get_a_bit()],
+            [0x11, 0x22, 0x33, 0x44]
+        );
+    }
+}
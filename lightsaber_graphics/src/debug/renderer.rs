@@ -7,9 +7,13 @@ use crate::{
         Colour,
         ColourCode
     },
-    FramebufferInformation
+    FramebufferInformation,
+    PixelColourFormat
 };
 
+// Drawn for anything the basic font has no glyph for, such as text decoded lossily from user memory.
+const PLACEHOLDER_CHAR: char = '?';
+
 pub struct DebugRenderer<'buffer> {
     buffer: &'buffer mut [u8],
     information: FramebufferInformation,
@@ -34,7 +38,12 @@ impl<'buffer> DebugRenderer<'buffer> {
         self.x_position = 0;
         self.y_position = 0;
 
-        self.buffer.fill(self.colour.background().inner() as u8);
+        let background = self.pixel_bytes(self.colour.background());
+        let bytes_per_pixel = self.information.bytes_per_pixel;
+
+        self.buffer
+            .chunks_exact_mut(bytes_per_pixel)
+            .for_each(|pixel| pixel.copy_from_slice(&background[..bytes_per_pixel]));
     }
 
     #[inline(always)]
@@ -72,15 +81,17 @@ impl<'buffer> DebugRenderer<'buffer> {
         self.information.horiz_resolution
     }
 
+    // The format names the order of the colour channels in memory, from the lowest address up.
+    fn pixel_bytes(&self, colour: Colour) -> [u8; 4] {
+        match self.information.pixel_colour_format {
+            PixelColourFormat::Bgr => [colour.get_b_bit(), colour.get_g_bit(), colour.get_r_bit(), colour.get_a_bit()],
+            PixelColourFormat::Rgb | PixelColourFormat::U8 => [colour.get_r_bit(), colour.get_g_bit(), colour.get_b_bit(), colour.get_a_bit()]
+        }
+    }
+
     pub fn put_pixel(&mut self, x: usize, y: usize, colour: Colour) {
         let pixel_offset = y * self.information.stride + x;
-
-        let colour = [
-            colour.get_r_bit(),
-            colour.get_g_bit(),
-            colour.get_b_bit(),
-            colour.get_a_bit()
-        ];
+        let colour = self.pixel_bytes(colour);
 
         let bytes_per_pixel = self.information.bytes_per_pixel;
         let byte_offset = pixel_offset * bytes_per_pixel;
@@ -93,14 +104,19 @@ impl<'buffer> DebugRenderer<'buffer> {
         match r#char {
             '\n' => self.newline(),
             '\r' => self.carriage_return(),
+            '\x08' => self.backspace(),
             _ => {
-                let char_from_basic_font = font8x8::BASIC_FONTS.get(r#char).unwrap();
+                let char_from_basic_font = font8x8::BASIC_FONTS
+                    .get(r#char)
+                    .or_else(|| font8x8::BASIC_FONTS.get(PLACEHOLDER_CHAR))
+                    .unwrap_or_default();
 
                 if self.x_position >= self.width() {
                     self.newline();
                 }
 
-                if self.y_position >= (self.height() - 16) {
+                // A line takes sixteen rows; the screen starts over once the next one would not fit.
+                if self.y_position + 16 > self.height() {
                     self.clear_screen();
                 }
 
@@ -115,6 +131,11 @@ impl<'buffer> DebugRenderer<'buffer> {
         });
     }
 
+    // Only moves the cursor back; the character is erased by whatever is written over it.
+    fn backspace(&mut self) {
+        self.x_position = self.x_position.saturating_sub(8);
+    }
+
     fn carriage_return(&mut self) {
         self.x_position = 0;
     }
@@ -134,3 +155,161 @@ impl<'buffer> fmt::Write for DebugRenderer<'buffer> {
 
 unsafe impl<'buffer> Send for DebugRenderer<'buffer> { }
 unsafe impl<'buffer> Sync for DebugRenderer<'buffer> { }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        debug::colour::{
+            Colour,
+            ColourCode
+        },
+        testing::MemoryFramebuffer,
+        PixelColourFormat
+    };
+
+    const WHITE: [u8; 4] = [0xFF, 0xFF, 0xFF, 0x00];
+    const BLACK: [u8; 4] = [0x00, 0x00, 0x00, 0x00];
+
+    const GLYPH_A: &str = "\
+..##....
+.####...
+##..##..
+##..##..
+######..
+##..##..
+##..##..
+........
+";
+
+    const GLYPH_B: &str = "\
+######..
+.##..##.
+.##..##.
+.#####..
+.##..##.
+.##..##.
+######..
+........
+";
+
+    // Eight rows of eight pixels nothing was drawn to.
+    fn cell_untouched() -> String {
+        "        \n".repeat(8)
+    }
+
+    #[test]
+    fn renders_a_glyph_in_the_top_left_corner() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 32, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("A");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert!(framebuffer.untouched(8, 0, 8, 32));
+        assert!(framebuffer.untouched(0, 8, 8, 24));
+    }
+
+    #[test]
+    fn rgb_and_bgr_store_the_channels_in_opposite_orders() {
+        let colour = ColourCode::new(Colour::from_rgba(0x11, 0x22, 0x33, 0x00), Colour::from_rgba(0x44, 0x55, 0x66, 0x00));
+
+        let mut rgb = MemoryFramebuffer::new(8, 16, 8, PixelColourFormat::Rgb);
+
+        {
+            let mut renderer = rgb.renderer();
+            renderer.set_colour_code(colour);
+            renderer.write_str("A");
+        }
+
+        let mut bgr = MemoryFramebuffer::new(8, 16, 8, PixelColourFormat::Bgr);
+
+        {
+            let mut renderer = bgr.renderer();
+            renderer.set_colour_code(colour);
+            renderer.write_str("A");
+        }
+
+        assert_eq!(rgb.picture(0, 0, 8, 8, &[0x11, 0x22, 0x33, 0x00], &[0x44, 0x55, 0x66, 0x00]), GLYPH_A);
+        assert_eq!(bgr.picture(0, 0, 8, 8, &[0x33, 0x22, 0x11, 0x00], &[0x66, 0x55, 0x44, 0x00]), GLYPH_A);
+    }
+
+    #[test]
+    fn rows_are_addressed_by_stride_not_width() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 16, 24, PixelColourFormat::Bgr);
+        framebuffer.renderer().write_str("AB");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), GLYPH_B);
+        assert!(framebuffer.padding_untouched());
+    }
+
+    #[test]
+    fn draws_a_placeholder_for_characters_outside_the_font() {
+        let mut placeholder = MemoryFramebuffer::new(8, 16, 8, PixelColourFormat::Rgb);
+        placeholder.renderer().write_str("?");
+
+        let mut framebuffer = MemoryFramebuffer::new(16, 16, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("\u{E9}\u{FFFD}");
+
+        let question_mark = placeholder.picture(0, 0, 8, 8, &WHITE, &BLACK);
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), question_mark);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), question_mark);
+    }
+
+    #[test]
+    fn wraps_onto_the_next_line_at_the_right_edge() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 32, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("ABA");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), GLYPH_B);
+        assert_eq!(framebuffer.picture(0, 16, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(8, 16, 8, 8, &WHITE, &BLACK), cell_untouched());
+    }
+
+    #[test]
+    fn newline_returns_to_the_start_of_the_next_line() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 32, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("A\nB");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), cell_untouched());
+        assert_eq!(framebuffer.picture(0, 16, 8, 8, &WHITE, &BLACK), GLYPH_B);
+    }
+
+    #[test]
+    fn carriage_return_draws_over_the_line() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 16, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("AA\rB");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_B);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+    }
+
+    #[test]
+    fn backspace_stops_at_the_start_of_the_line() {
+        let mut framebuffer = MemoryFramebuffer::new(16, 16, 16, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("AB\x08A\x08\x08\x08B");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_B);
+        assert_eq!(framebuffer.picture(8, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+    }
+
+    #[test]
+    fn uses_the_last_line_that_fits() {
+        let mut framebuffer = MemoryFramebuffer::new(8, 32, 8, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("A\nB");
+
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(0, 16, 8, 8, &WHITE, &BLACK), GLYPH_B);
+    }
+
+    #[test]
+    fn starts_over_at_the_top_once_the_screen_is_full() {
+        let mut framebuffer = MemoryFramebuffer::new(8, 32, 8, PixelColourFormat::Rgb);
+        framebuffer.renderer().write_str("A\nB\nA");
+
+        // The screen is cleared to the background before the third line is drawn at the top.
+        assert_eq!(framebuffer.picture(0, 0, 8, 8, &WHITE, &BLACK), GLYPH_A);
+        assert_eq!(framebuffer.picture(0, 8, 8, 24, &WHITE, &BLACK), "........\n".repeat(24));
+    }
+}
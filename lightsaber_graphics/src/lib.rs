@@ -1,4 +1,4 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 
 #![feature(const_fn)]
 
@@ -6,6 +6,9 @@ use core::slice;
 
 pub mod debug;
 
+#[cfg(test)]
+mod testing;
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct Framebuffer {
@@ -21,7 +24,7 @@ impl Framebuffer {
         }
     }
 
-    pub fn buffer_mut(&self) -> &mut [u8] {
+    pub fn buffer_mut(&mut self) -> &mut [u8] {
         unsafe {
             self.create_buffer()
         }
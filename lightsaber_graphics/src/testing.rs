@@ -0,0 +1,77 @@
+use crate::{
+    debug::renderer::DebugRenderer,
+    FramebufferInformation,
+    PixelColourFormat
+};
+
+pub const BYTES_PER_PIXEL: usize = 4;
+
+// What the buffer starts out as, so that pixels nothing was drawn to can be told apart from the background.
+pub const UNTOUCHED: u8 = 0xA5;
+
+// A framebuffer in ordinary memory, for rendering on the host.
+pub struct MemoryFramebuffer {
+    buffer: Vec<u8>,
+    information: FramebufferInformation
+}
+
+impl MemoryFramebuffer {
+    pub fn new(width: usize, height: usize, stride: usize, pixel_colour_format: PixelColourFormat) -> Self {
+        assert!(stride >= width, "The stride is narrower than the framebuffer.");
+
+        let len_bytes = stride * height * BYTES_PER_PIXEL;
+
+        Self {
+            buffer: vec![UNTOUCHED; len_bytes],
+            information: FramebufferInformation {
+                len_bytes,
+                horiz_resolution: width,
+                vert_resolution: height,
+                pixel_colour_format,
+                bytes_per_pixel: BYTES_PER_PIXEL,
+                stride
+            }
+        }
+    }
+
+    pub fn renderer(&mut self) -> DebugRenderer<'_> {
+        DebugRenderer::new(&mut self.buffer, self.information)
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &[u8] {
+        let offset = (y * self.information.stride + x) * BYTES_PER_PIXEL;
+
+        &self.buffer[offset..offset + BYTES_PER_PIXEL]
+    }
+
+    // A rectangle of the framebuffer as text, one line a row: `#` for the given foreground pixel,
+    // `.` for the given background pixel and a space for anything else.
+    pub fn picture(&self, x: usize, y: usize, width: usize, height: usize, foreground: &[u8], background: &[u8]) -> String {
+        let mut picture = String::new();
+
+        for row in y..y + height {
+            for column in x..x + width {
+                picture.push(match self.pixel(column, row) {
+                    pixel if pixel == foreground => '#',
+                    pixel if pixel == background => '.',
+                    _ => ' '
+                });
+            }
+
+            picture.push('\n');
+        }
+
+        picture
+    }
+
+    pub fn untouched(&self, x: usize, y: usize, width: usize, height: usize) -> bool {
+        (y..y + height).all(|row| {
+            (x..x + width).all(|column| self.pixel(column, row).iter().all(|byte| *byte == UNTOUCHED))
+        })
+    }
+
+    // Whether anything was written between the end of a row and the start of the next.
+    pub fn padding_untouched(&self) -> bool {
+        self.untouched(self.information.horiz_resolution, 0, self.information.stride - self.information.horiz_resolution, self.information.vert_resolution)
+    }
+}
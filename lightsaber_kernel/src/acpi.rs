@@ -0,0 +1,396 @@
+use alloc::{
+    vec,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    ptr,
+    slice,
+    str
+};
+
+use spin::Once;
+
+use x86_64::{
+    instructions::port::Port,
+    PhysAddr
+};
+
+use crate::{
+    architecture::apic,
+    cmdline::param,
+    memory
+};
+
+const RSDP_SIGNATURE: &[u8] = b"RSD PTR ";
+const RSDP_VERSION_1_SIZE: usize = 20;
+const SDT_HEADER_SIZE: usize = 36;
+
+const MADT_ENTRIES_OFFSET: usize = 44;
+const MADT_LOCAL_APIC: u8 = 0;
+const MADT_LOCAL_APIC_ENABLED: u32 = 1 << 0;
+
+const FADT_DSDT: usize = 40;
+const FADT_SMI_COMMAND: usize = 48;
+const FADT_ACPI_ENABLE: usize = 52;
+const FADT_PM1A_CONTROL: usize = 64;
+const FADT_PM1B_CONTROL: usize = 68;
+const FADT_FLAGS: usize = 112;
+const FADT_RESET_REGISTER: usize = 116;
+const FADT_RESET_VALUE: usize = 128;
+const FADT_EXTENDED_DSDT: usize = 140;
+
+const FADT_FLAGS_RESET_REGISTER_SUPPORTED: u32 = 1 << 10;
+
+const ADDRESS_SPACE_MEMORY: u8 = 0;
+const ADDRESS_SPACE_IO: u8 = 1;
+
+const PM1_CONTROL_SCI_ENABLE: u16 = 1 << 0;
+const PM1_CONTROL_SLEEP_TYPE_SHIFT: u16 = 10;
+const PM1_CONTROL_SLEEP_ENABLE: u16 = 1 << 13;
+
+const AML_NAME: u8 = 0x08;
+const AML_PACKAGE: u8 = 0x12;
+const AML_BYTE_PREFIX: u8 = 0x0A;
+const AML_ROOT: u8 = b'\\';
+
+const ACPI_ENABLE_ATTEMPTS: usize = 1_000_000;
+
+static ACPI_TABLES: Once<Vec<AcpiTable>> = Once::new();
+
+#[derive(Debug, Clone, Copy)]
+pub struct AcpiTable {
+    pub signature: [u8; 4],
+    pub address: PhysAddr,
+    pub length: u32,
+    pub revision: u8,
+    pub oem_id: [u8; 6]
+}
+
+impl AcpiTable {
+    #[inline]
+    pub fn signature(&self) -> &str {
+        str::from_utf8(&self.signature).unwrap_or("????")
+    }
+
+    #[inline]
+    pub fn oem_id(&self) -> &str {
+        str::from_utf8(&self.oem_id).unwrap_or("").trim_end()
+    }
+
+    // The whole table, header included; firmware keeps it in memory the kernel never reuses.
+    pub fn bytes(&self) -> &'static [u8] {
+        unsafe {
+            lightsaber_kernel_physical_bytes(self.address, self.length as usize)
+        }
+    }
+}
+
+unsafe fn lightsaber_kernel_physical_bytes(address: PhysAddr, length: usize) -> &'static [u8] {
+    slice::from_raw_parts(memory::lightsaber_kernel_physical_to_virtual(address).as_ptr(), length)
+}
+
+fn lightsaber_kernel_read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn lightsaber_kernel_read_u64(data: &[u8], offset: usize) -> u64 {
+    lightsaber_kernel_read_u32(data, offset) as u64 | (lightsaber_kernel_read_u32(data, offset + 4) as u64) << 32
+}
+
+#[inline]
+fn lightsaber_kernel_checksum_valid(data: &[u8]) -> bool {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+fn lightsaber_kernel_read_table(address: PhysAddr) -> Option<AcpiTable> {
+    let header = unsafe {
+        lightsaber_kernel_physical_bytes(address, SDT_HEADER_SIZE)
+    };
+
+    let length = lightsaber_kernel_read_u32(header, 4);
+
+    if (length as usize) < SDT_HEADER_SIZE {
+        return None;
+    }
+
+    let mut signature = [0; 4];
+    signature.copy_from_slice(&header[..4]);
+
+    let mut oem_id = [0; 6];
+    oem_id.copy_from_slice(&header[10..16]);
+
+    let table = AcpiTable {
+        signature,
+        address,
+        length,
+        revision: header[8],
+        oem_id
+    };
+
+    match lightsaber_kernel_checksum_valid(table.bytes()) {
+        true => Some(table),
+        false => {
+            log::warn!("Ignoring ACPI table {} at {:#x} with a bad checksum.", table.signature(), address.as_u64());
+
+            None
+        }
+    }
+}
+
+fn lightsaber_kernel_parse_tables(rsdp_address: PhysAddr) -> Vec<AcpiTable> {
+    let rsdp = unsafe {
+        lightsaber_kernel_physical_bytes(rsdp_address, RSDP_VERSION_1_SIZE)
+    };
+
+    if &rsdp[..8] != RSDP_SIGNATURE || !lightsaber_kernel_checksum_valid(rsdp) {
+        log::warn!("No valid ACPI root pointer at {:#x}.", rsdp_address.as_u64());
+
+        return Vec::new();
+    }
+
+    // ACPI 2.0 and later have a 64-bit XSDT, which takes precedence over the RSDT.
+    let (root_address, entry_size) = match rsdp[15] >= 2 {
+        true => {
+            let extended = unsafe {
+                lightsaber_kernel_physical_bytes(rsdp_address, lightsaber_kernel_read_u32(rsdp, 20) as usize)
+            };
+
+            match extended.len() >= 32 && lightsaber_kernel_checksum_valid(extended) && lightsaber_kernel_read_u64(extended, 24) != 0 {
+                true => (lightsaber_kernel_read_u64(extended, 24), 8),
+                false => (lightsaber_kernel_read_u32(rsdp, 16) as u64, 4)
+            }
+        }
+        false => (lightsaber_kernel_read_u32(rsdp, 16) as u64, 4)
+    };
+
+    let root = match lightsaber_kernel_read_table(PhysAddr::new(root_address)) {
+        Some(root) => root,
+        None => return Vec::new()
+    };
+
+    let mut tables = Vec::new();
+    let bytes = root.bytes();
+
+    for offset in (SDT_HEADER_SIZE..bytes.len()).step_by(entry_size) {
+        if offset + entry_size > bytes.len() {
+            break;
+        }
+
+        let address = match entry_size {
+            8 => lightsaber_kernel_read_u64(bytes, offset),
+            _ => lightsaber_kernel_read_u32(bytes, offset) as u64
+        };
+
+        if let Some(table) = lightsaber_kernel_read_table(PhysAddr::new(address)) {
+            tables.push(table);
+        }
+    }
+
+    tables.insert(0, root);
+    tables
+}
+
+pub fn lightsaber_kernel_initialize_acpi(rsdp_address: u64) {
+    let tables = ACPI_TABLES.call_once(|| match rsdp_address {
+        0 => Vec::new(),
+        address => lightsaber_kernel_parse_tables(PhysAddr::new(address))
+    });
+
+    for table in tables {
+        log::debug!("ACPI table {} at {:#x} ({} bytes, OEM {}).", table.signature(), table.address.as_u64(), table.length, table.oem_id());
+    }
+
+    log::info!("Initialized ACPI with {} tables.", tables.len());
+}
+
+pub fn lightsaber_kernel_acpi_tables() -> &'static [AcpiTable] {
+    ACPI_TABLES.get().map_or(&[], |tables| tables.as_slice())
+}
+
+pub fn lightsaber_kernel_find_acpi_table(signature: &[u8; 4]) -> Option<AcpiTable> {
+    lightsaber_kernel_acpi_tables()
+        .iter()
+        .find(|table| &table.signature == signature)
+        .copied()
+}
+
+// `nosmp` leaves every processor but the bootstrap one out of the list.
+param!(pub NO_SMP: bool = "nosmp");
+
+// Local APIC IDs of the usable processors as the MADT lists them, in firmware order.
+pub fn lightsaber_kernel_processor_apic_ids() -> Vec<u8> {
+    if NO_SMP.get().unwrap_or(false) {
+        return vec![apic::lightsaber_kernel_local_apic_id()];
+    }
+
+    let madt = match lightsaber_kernel_find_acpi_table(b"APIC") {
+        Some(madt) => madt.bytes(),
+        None => return Vec::new()
+    };
+
+    let mut processors = Vec::new();
+    let mut offset = MADT_ENTRIES_OFFSET;
+
+    while offset + 2 <= madt.len() {
+        let length = madt[offset + 1] as usize;
+
+        if length < 2 || offset + length > madt.len() {
+            break;
+        }
+
+        if madt[offset] == MADT_LOCAL_APIC && length >= 8 && lightsaber_kernel_read_u32(madt, offset + 4) & MADT_LOCAL_APIC_ENABLED != 0 {
+            processors.push(madt[offset + 3]);
+        }
+
+        offset += length;
+    }
+
+    processors
+}
+
+// The DSDT is only reachable through the FADT, so it is not among the tables the root lists.
+fn lightsaber_kernel_dsdt(fadt: &[u8]) -> Option<AcpiTable> {
+    let address = match fadt.len() >= FADT_EXTENDED_DSDT + 8 && lightsaber_kernel_read_u64(fadt, FADT_EXTENDED_DSDT) != 0 {
+        true => lightsaber_kernel_read_u64(fadt, FADT_EXTENDED_DSDT),
+        false => lightsaber_kernel_read_u32(fadt, FADT_DSDT) as u64
+    };
+
+    match address {
+        0 => None,
+        address => lightsaber_kernel_read_table(PhysAddr::new(address))
+    }
+}
+
+fn lightsaber_kernel_aml_integer(aml: &[u8], offset: &mut usize) -> Option<u16> {
+    let value = match *aml.get(*offset)? {
+        AML_BYTE_PREFIX => {
+            *offset += 1;
+
+            *aml.get(*offset)?
+        }
+        // Zero, One and Ones are the only other encodings firmware uses here.
+        value @ 0..=1 => value,
+        _ => return None
+    };
+
+    *offset += 1;
+
+    Some(value as u16)
+}
+
+// Finds the sleep types of the \_S5 soft-off state without an AML interpreter, the way most
+// small kernels do: firmware declares it as a plain named package of integers.
+fn lightsaber_kernel_soft_off_sleep_types(dsdt: &[u8]) -> Option<(u16, u16)> {
+    let position = dsdt.windows(4).position(|window| window == b"_S5_")?;
+
+    let named = match position {
+        0 => false,
+        1 => dsdt[0] == AML_NAME,
+        _ => dsdt[position - 1] == AML_NAME || (dsdt[position - 2] == AML_NAME && dsdt[position - 1] == AML_ROOT)
+    };
+
+    if !named || *dsdt.get(position + 4)? != AML_PACKAGE {
+        return None;
+    }
+
+    // The package length encodes how many bytes it takes in its top two bits; the element count follows.
+    let mut offset = position + 5;
+    offset += ((*dsdt.get(offset)? >> 6) & 0x3) as usize + 1;
+    offset += 1;
+
+    let sleep_type_a = lightsaber_kernel_aml_integer(dsdt, &mut offset)?;
+    let sleep_type_b = lightsaber_kernel_aml_integer(dsdt, &mut offset)?;
+
+    Some((sleep_type_a, sleep_type_b))
+}
+
+// Firmware only hands the power management registers over once ACPI mode is switched on.
+fn lightsaber_kernel_enable_acpi_mode(fadt: &[u8], pm1a_control: u16) {
+    let enabled = || unsafe {
+        Port::<u16>::new(pm1a_control).read() & PM1_CONTROL_SCI_ENABLE != 0
+    };
+
+    let smi_command = lightsaber_kernel_read_u32(fadt, FADT_SMI_COMMAND) as u16;
+    let acpi_enable = fadt[FADT_ACPI_ENABLE];
+
+    if enabled() || smi_command == 0 || acpi_enable == 0 {
+        return;
+    }
+
+    unsafe {
+        Port::<u8>::new(smi_command).write(acpi_enable);
+    }
+
+    for _ in 0..ACPI_ENABLE_ATTEMPTS {
+        if enabled() {
+            return;
+        }
+
+        hint::spin_loop();
+    }
+}
+
+// Enters S5. Only returns if the firmware does not describe it or ignores the request.
+pub fn lightsaber_kernel_acpi_shutdown() {
+    let fadt = match lightsaber_kernel_find_acpi_table(b"FACP") {
+        Some(fadt) if fadt.length as usize >= FADT_FLAGS => fadt.bytes(),
+        _ => return
+    };
+
+    let sleep_types = lightsaber_kernel_dsdt(fadt).and_then(|dsdt| lightsaber_kernel_soft_off_sleep_types(dsdt.bytes()));
+
+    let (sleep_type_a, sleep_type_b) = match sleep_types {
+        Some(sleep_types) => sleep_types,
+        None => return
+    };
+
+    let pm1a_control = lightsaber_kernel_read_u32(fadt, FADT_PM1A_CONTROL) as u16;
+    let pm1b_control = lightsaber_kernel_read_u32(fadt, FADT_PM1B_CONTROL) as u16;
+
+    if pm1a_control == 0 {
+        return;
+    }
+
+    lightsaber_kernel_enable_acpi_mode(fadt, pm1a_control);
+
+    unsafe {
+        let mut control = Port::<u16>::new(pm1a_control);
+        let value = control.read();
+
+        control.write(value | sleep_type_a << PM1_CONTROL_SLEEP_TYPE_SHIFT | PM1_CONTROL_SLEEP_ENABLE);
+
+        if pm1b_control != 0 {
+            let mut control = Port::<u16>::new(pm1b_control);
+            let value = control.read();
+
+            control.write(value | sleep_type_b << PM1_CONTROL_SLEEP_TYPE_SHIFT | PM1_CONTROL_SLEEP_ENABLE);
+        }
+    }
+}
+
+// Writes the FADT reset register. Only returns if there is none or it did not take.
+pub fn lightsaber_kernel_acpi_reset() {
+    let fadt = match lightsaber_kernel_find_acpi_table(b"FACP") {
+        Some(fadt) if fadt.revision >= 2 && fadt.length as usize > FADT_RESET_VALUE => fadt.bytes(),
+        _ => return
+    };
+
+    if lightsaber_kernel_read_u32(fadt, FADT_FLAGS) & FADT_FLAGS_RESET_REGISTER_SUPPORTED == 0 {
+        return;
+    }
+
+    let address = lightsaber_kernel_read_u64(fadt, FADT_RESET_REGISTER + 4);
+    let value = fadt[FADT_RESET_VALUE];
+
+    match fadt[FADT_RESET_REGISTER] {
+        ADDRESS_SPACE_IO => unsafe {
+            Port::<u8>::new(address as u16).write(value);
+        },
+        ADDRESS_SPACE_MEMORY => unsafe {
+            ptr::write_volatile(memory::lightsaber_kernel_physical_to_virtual(PhysAddr::new(address)).as_mut_ptr::<u8>(), value);
+        },
+        _ => { }
+    }
+}
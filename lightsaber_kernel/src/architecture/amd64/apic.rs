@@ -0,0 +1,75 @@
+use spin::Once;
+
+use x86_64::{
+    registers::model_specific::Msr,
+    PhysAddr
+};
+
+use crate::{
+    architecture::interrupts::idt,
+    memory::mmio::{
+        self,
+        MmioRegion
+    }
+};
+
+pub const LOCAL_APIC_SPURIOUS_VECTOR: u8 = 0xFF;
+
+const IA32_APIC_BASE: u32 = 0x1B;
+const IA32_APIC_BASE_ENABLE: u64 = 1 << 11;
+const IA32_APIC_BASE_ADDRESS_MASK: u64 = 0x000F_FFFF_FFFF_F000;
+
+const LOCAL_APIC_SIZE: u64 = 0x400;
+const LOCAL_APIC_ID: u64 = 0x20;
+const LOCAL_APIC_END_OF_INTERRUPT: u64 = 0xB0;
+const LOCAL_APIC_SPURIOUS_INTERRUPT: u64 = 0xF0;
+const LOCAL_APIC_SOFTWARE_ENABLE: u32 = 1 << 8;
+
+static LOCAL_APIC: Once<MmioRegion> = Once::new();
+
+extern "x86-interrupt" fn lightsaber_kernel_local_apic_spurious(_stack_frame: idt::InterruptStackFrame) { }
+
+// Legacy IRQs keep going through the PIC; the local APIC is only needed to receive message-signalled interrupts.
+pub fn lightsaber_kernel_initialize_local_apic() {
+    let mut base = Msr::new(IA32_APIC_BASE);
+
+    let address = unsafe {
+        let value = base.read() | IA32_APIC_BASE_ENABLE;
+        base.write(value);
+
+        PhysAddr::new(value & IA32_APIC_BASE_ADDRESS_MASK)
+    };
+
+    let local_apic = match mmio::lightsaber_kernel_map_mmio(address, LOCAL_APIC_SIZE) {
+        Ok(region) => LOCAL_APIC.call_once(|| region),
+        Err(error) => {
+            log::warn!("Failed to map the local APIC: {}", error);
+
+            return;
+        }
+    };
+
+    unsafe {
+        idt::lightsaber_kernel_set_interrupt_handler(LOCAL_APIC_SPURIOUS_VECTOR, lightsaber_kernel_local_apic_spurious as usize as u64, 0, false);
+    }
+
+    let spurious = local_apic.read_u32(LOCAL_APIC_SPURIOUS_INTERRUPT) & !0xFF;
+    local_apic.write_u32(LOCAL_APIC_SPURIOUS_INTERRUPT, spurious | LOCAL_APIC_SOFTWARE_ENABLE | LOCAL_APIC_SPURIOUS_VECTOR as u32);
+
+    log::info!("Initialized local APIC {} at {:#x}.", lightsaber_kernel_local_apic_id(), address.as_u64());
+}
+
+#[inline]
+pub fn lightsaber_kernel_local_apic_available() -> bool {
+    LOCAL_APIC.get().is_some()
+}
+
+pub fn lightsaber_kernel_local_apic_id() -> u8 {
+    LOCAL_APIC.get().map_or(0, |local_apic| (local_apic.read_u32(LOCAL_APIC_ID) >> 24) as u8)
+}
+
+pub fn lightsaber_kernel_local_apic_end_of_interrupt() {
+    if let Some(local_apic) = LOCAL_APIC.get() {
+        local_apic.write_u32(LOCAL_APIC_END_OF_INTERRUPT, 0);
+    }
+}
@@ -0,0 +1,74 @@
+use core::mem;
+
+pub const CONTEXT_INITIAL_RFLAGS: u64 = 0x202;
+
+// What `lightsaber_kernel_switch_context` leaves at the stack pointer of a thread that is switched out.
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct SavedContext {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbx: u64,
+    pub rbp: u64,
+    pub rflags: u64,
+    pub return_address: u64
+}
+
+pub unsafe fn lightsaber_kernel_initialize_context(stack_top: usize, entry: extern "C" fn(usize) -> !, argument: usize) -> usize {
+    let stack_top = stack_top & !0xF;
+    let context_address = stack_top - mem::size_of::<SavedContext>();
+
+    (context_address as *mut SavedContext).write(SavedContext {
+        r15: 0,
+        r14: 0,
+        r13: argument as u64,
+        r12: entry as usize as u64,
+        rbx: 0,
+        rbp: 0,
+        rflags: CONTEXT_INITIAL_RFLAGS,
+        return_address: lightsaber_kernel_context_trampoline as usize as u64
+    });
+
+    context_address
+}
+
+#[naked]
+pub unsafe extern "C" fn lightsaber_kernel_switch_context(_old_stack_pointer: *mut usize, _new_stack_pointer: usize) {
+    asm!("
+        pushfq
+        push rbp
+        push rbx
+        push r12
+        push r13
+        push r14
+        push r15
+
+        mov [rdi], rsp
+        mov rsp, rsi
+
+        pop r15
+        pop r14
+        pop r13
+        pop r12
+        pop rbx
+        pop rbp
+        popfq
+
+        ret
+        ",
+        options(noreturn)
+    )
+}
+
+#[naked]
+unsafe extern "C" fn lightsaber_kernel_context_trampoline() {
+    asm!("
+        mov rdi, r13
+        call r12
+        ud2
+        ",
+        options(noreturn)
+    )
+}
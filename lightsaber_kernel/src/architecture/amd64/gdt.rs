@@ -1,8 +1,23 @@
 use core::mem;
 
-const GLOBAL_DESCRIPTOR_TABLE_ENTRIES: usize = 6;
+pub const KERNEL_CODE_SELECTOR: u16 = 0x08;
+pub const KERNEL_DATA_SELECTOR: u16 = 0x10;
+pub const USER_DATA_SELECTOR: u16 = 0x18 | 3;
+pub const USER_CODE_SELECTOR: u16 = 0x20 | 3;
+pub const TASK_STATE_SEGMENT_SELECTOR: u16 = 0x28;
+
+pub const DOUBLE_FAULT_STACK_INDEX: u8 = 1;
+const DOUBLE_FAULT_STACK_SIZE: usize = 16 * 1024;
+
+const GLOBAL_DESCRIPTOR_TABLE_ENTRIES: usize = 7;
 static mut GLOBAL_DESCRIPTOR_TABLE: [GdtEntry; GLOBAL_DESCRIPTOR_TABLE_ENTRIES] = [GdtEntry::null(); GLOBAL_DESCRIPTOR_TABLE_ENTRIES];
 
+pub(in crate::architecture) static mut TASK_STATE_SEGMENT: TaskStateSegment = TaskStateSegment::null();
+static mut DOUBLE_FAULT_STACK: DoubleFaultStack = DoubleFaultStack([0; DOUBLE_FAULT_STACK_SIZE]);
+
+#[repr(C, align(16))]
+struct DoubleFaultStack([u8; DOUBLE_FAULT_STACK_SIZE]);
+
 #[repr(C, packed)]
 struct GdtDescriptor {
     size: u16,
@@ -47,78 +62,63 @@ impl GdtEntry {
     const fn null() -> Self {
         Self::new(0x00, 0x00, 0x00, 0x00, 0x00, 0x00)
     }
+
+    fn task_state_segment(task_state_segment: *const TaskStateSegment) -> [Self; 2] {
+        let base = task_state_segment as u64;
+        let limit = (mem::size_of::<TaskStateSegment>() - 1) as u64;
+
+        [
+            Self::new(
+                limit as u16,
+                base as u16,
+                (base >> 16) as u8,
+                0x89,
+                ((limit >> 16) & 0x0F) as u8,
+                (base >> 24) as u8
+            ),
+            Self::new((base >> 32) as u16, (base >> 48) as u16, 0x00, 0x00, 0x00, 0x00)
+        ]
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
-#[repr(C)]
-pub struct TssEntry {
-    previous_tss: u64,
-    esp0: u64,
-    ss0: u64,
-    esp1: u64,
-    ss1: u64,
-    esp2: u64,
-    ss2: u64,
-    cr3: u64,
-    eip: u64,
-    eflags: u64,
-    eax: u64,
-    ecx: u64,
-    edx: u64,
-    ebx: u64,
-    esp: u64,
-    ebp: u64,
-    esi: u64,
-    edi: u64,
-    es: u64,
-    cs: u64,
-    ss: u64,
-    ds: u64,
-    fs: u64,
-    gs: u64,
-    ldt: u64,
-    trap: u16,
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    reserved_0: u32,
+    rsp0: u64,
+    rsp1: u64,
+    rsp2: u64,
+    reserved_1: u64,
+    ist1: u64,
+    ist2: u64,
+    ist3: u64,
+    ist4: u64,
+    ist5: u64,
+    ist6: u64,
+    ist7: u64,
+    reserved_2: u64,
+    reserved_3: u16,
     iomap_base: u16
 }
 
-impl TssEntry {
-    pub fn new() -> GdtEntry {
-        let this = Self::null();
-        let base = (&this as *const Self) as usize;
-        let limit = base + mem::size_of::<Self>();
-
-        GdtEntry::new(0, 0, base as u8, limit as u8, 0xE9, 0x00)
-    }
-
+impl TaskStateSegment {
     #[inline]
     pub const fn null() -> Self {
         Self {
-            previous_tss: 0,
-            esp0: 0,
-            ss0: 0,
-            esp1: 0,
-            ss1: 0,
-            esp2: 0,
-            ss2: 0,
-            cr3: 0,
-            eip: 0,
-            eflags: 0,
-            eax: 0,
-            ecx: 0,
-            edx: 0,
-            ebx: 0,
-            esp: 0,
-            ebp: 0,
-            esi: 0,
-            edi: 0,
-            es: 0,
-            cs: 0,
-            ss: 0,
-            ds: 0,
-            fs: 0,
-            gs: 0,
-            ldt: 0,
-            trap: 0,
+            reserved_0: 0,
+            rsp0: 0,
+            rsp1: 0,
+            rsp2: 0,
+            reserved_1: 0,
+            ist1: 0,
+            ist2: 0,
+            ist3: 0,
+            ist4: 0,
+            ist5: 0,
+            ist6: 0,
+            ist7: 0,
+            reserved_2: 0,
+            reserved_3: 0,
             iomap_base: mem::size_of::<Self>() as u16
         }
     }
@@ -126,14 +126,18 @@ impl TssEntry {
 
 pub fn lightsaber_kernel_initialize_global_descriptor_table() {
     unsafe {
-        let task_state_segment = TssEntry::new();
+        TASK_STATE_SEGMENT.ist1 = &DOUBLE_FAULT_STACK as *const _ as u64 + DOUBLE_FAULT_STACK_SIZE as u64;
+
+        let [task_state_segment_low, task_state_segment_high] = GdtEntry::task_state_segment(&TASK_STATE_SEGMENT as *const _);
 
-        GLOBAL_DESCRIPTOR_TABLE[0] = GdtEntry::new(0, 0, 0, 0x00, 0x00, 0);
+        GLOBAL_DESCRIPTOR_TABLE[0] = GdtEntry::null();
         GLOBAL_DESCRIPTOR_TABLE[1] = GdtEntry::new(0, 0, 0, 0x9A, 0xA0, 0);
         GLOBAL_DESCRIPTOR_TABLE[2] = GdtEntry::new(0, 0, 0, 0x92, 0xA0, 0);
-        GLOBAL_DESCRIPTOR_TABLE[3] = GdtEntry::new(0, 0, 0, 0xFA, 0xA0, 0);
-        GLOBAL_DESCRIPTOR_TABLE[4] = GdtEntry::new(0, 0, 0, 0xF2, 0xA0, 0);
-        GLOBAL_DESCRIPTOR_TABLE[5] = task_state_segment;
+        // `sysret` expects the user data segment directly before the user code segment.
+        GLOBAL_DESCRIPTOR_TABLE[3] = GdtEntry::new(0, 0, 0, 0xF2, 0xA0, 0);
+        GLOBAL_DESCRIPTOR_TABLE[4] = GdtEntry::new(0, 0, 0, 0xFA, 0xA0, 0);
+        GLOBAL_DESCRIPTOR_TABLE[5] = task_state_segment_low;
+        GLOBAL_DESCRIPTOR_TABLE[6] = task_state_segment_high;
 
         let gdt_descriptor = GdtDescriptor::new(
             (mem::size_of::<[GdtEntry; GLOBAL_DESCRIPTOR_TABLE_ENTRIES]>() - 1) as u16,
@@ -141,36 +145,42 @@ pub fn lightsaber_kernel_initialize_global_descriptor_table() {
         );
 
         lightsaber_kernel_load_global_descriptor_table(&gdt_descriptor as *const _);
-        lightsaber_kernel_load_task_state_segment(&task_state_segment as *const _);
+        lightsaber_kernel_load_task_state_segment(TASK_STATE_SEGMENT_SELECTOR);
     };
 }
 
+pub fn lightsaber_kernel_set_kernel_stack(stack_top: u64) {
+    unsafe {
+        TASK_STATE_SEGMENT.rsp0 = stack_top;
+    }
+}
+
 unsafe fn lightsaber_kernel_load_global_descriptor_table(gdt_descriptor: *const GdtDescriptor) {
     asm!("
         lgdt [rdi]
 
-        mov ax, 0x10
-
         mov ds, ax
         mov es, ax
         mov fs, ax
         mov gs, ax
         mov ss, ax
 
-        pop rdi
-
-        mov rax, 0x08
-
+        push {}
+        lea rax, [rip + 2f]
         push rax
-        push rdi
+        retfq
+
+        2:
         ",
-        in("rdi") gdt_descriptor
+        in(reg) KERNEL_CODE_SELECTOR as u64,
+        in("rdi") gdt_descriptor,
+        inout("rax") KERNEL_DATA_SELECTOR as u64 => _
     )
 }
 
-unsafe fn lightsaber_kernel_load_task_state_segment(gdt_entry: *const GdtEntry) {
+unsafe fn lightsaber_kernel_load_task_state_segment(selector: u16) {
     asm!(
-        "ltr [rdi]",
-        in("rdi") gdt_entry
+        "ltr {:x}",
+        in(reg) selector
     )
 }
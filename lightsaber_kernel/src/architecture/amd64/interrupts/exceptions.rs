@@ -1,21 +1,144 @@
-use crate::architecture::interrupts::idt::InterruptStackFrame;
+use x86_64::registers::control::Cr2;
+
+use crate::{
+    architecture::interrupts::{
+        self,
+        idt::InterruptStackFrame,
+        trap::{
+            self,
+            trap_entry,
+            TrapFrame
+        }
+    },
+    gdb,
+    process::{
+        self,
+        ExitStatus
+    },
+    syscall::user
+};
+
+const PAGE_FAULT_PROTECTION_VIOLATION: u64 = 1 << 0;
+const PAGE_FAULT_WRITE: u64 = 1 << 1;
+
+fn lightsaber_kernel_user_fault(description: &str, signal: u8, stack_frame: &InterruptStackFrame) -> ! {
+    let process = process::lightsaber_kernel_current_process();
+
+    log::error!(
+        "Terminating process {} after a fault in user mode: {} at {:#x}.",
+        process.as_ref().map(|process| process.id().0).unwrap_or(0),
+        description,
+        stack_frame.instruction_pointer.as_u64()
+    );
+
+    drop(process);
+    process::lightsaber_kernel_exit_process(ExitStatus::Killed(signal))
+}
+
+pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_division_by_zero(stack_frame: InterruptStackFrame) {
+    if stack_frame.from_user_mode() {
+        lightsaber_kernel_user_fault("division by zero", ExitStatus::SIGNAL_FLOATING_POINT, &stack_frame);
+    }
 
-pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_division_by_zero(_stack_frame: InterruptStackFrame) {
     panic!("Division by zero. (`DIVISION_BY_ZERO`)");
 }
 
-pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_debug(_stack_frame: InterruptStackFrame) {
-    panic!("Debug. (`DEBUG`)");
+// The debug and breakpoint exceptions get every register, so the debugger can read and change them.
+extern "C" fn lightsaber_kernel_trap_debug(frame: &mut TrapFrame) {
+    let debug_status = trap::lightsaber_kernel_take_debug_status();
+
+    if gdb::lightsaber_kernel_gdb_debug_trap(frame, debug_status) {
+        return;
+    }
+
+    panic!("Debug exception at {:#x} with status {:#x}. (`DEBUG`)", frame.instruction_pointer, debug_status);
 }
 
+trap_entry!(lightsaber_kernel_trap_entry_debug, lightsaber_kernel_trap_debug);
+
 pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_non_maskable_interrupts(_stack_frame: InterruptStackFrame) {
     panic!("Non-maskable interrupt. (`NONMASKABLE_INTERRUPT`)");
 }
 
-pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_breakpoint(_stack_frame: InterruptStackFrame) {
-    panic!("Breakpoint. (`BREAKPOINT`)");
+extern "C" fn lightsaber_kernel_trap_breakpoint(frame: &mut TrapFrame) {
+    if frame.from_user_mode() {
+        lightsaber_kernel_user_fault("breakpoint", ExitStatus::SIGNAL_TRAP, &frame.interrupt_stack_frame());
+    }
+
+    if gdb::lightsaber_kernel_gdb_breakpoint_trap(frame) {
+        return;
+    }
+
+    panic!("Breakpoint at {:#x}. (`BREAKPOINT`)", frame.instruction_pointer - 1);
 }
 
-pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_overflow(_stack_frame: InterruptStackFrame) {
+trap_entry!(lightsaber_kernel_trap_entry_breakpoint, lightsaber_kernel_trap_breakpoint);
+
+pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_overflow(stack_frame: InterruptStackFrame) {
+    if stack_frame.from_user_mode() {
+        lightsaber_kernel_user_fault("overflow", ExitStatus::SIGNAL_SEGMENTATION_FAULT, &stack_frame);
+    }
+
     panic!("Overflow. (`OVERFLOW`)");
 }
+
+pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_invalid_opcode(stack_frame: InterruptStackFrame) {
+    if stack_frame.from_user_mode() {
+        lightsaber_kernel_user_fault("invalid opcode", ExitStatus::SIGNAL_ILLEGAL_INSTRUCTION, &stack_frame);
+    }
+
+    panic!("Invalid opcode at {:#x}. (`INVALID_OPCODE`)", stack_frame.instruction_pointer.as_u64());
+}
+
+pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_double_fault(stack_frame: InterruptStackFrame, _error_code: u64) -> ! {
+    panic!("Double fault at {:#x}. (`DOUBLE_FAULT`)", stack_frame.instruction_pointer.as_u64());
+}
+
+pub extern "x86-interrupt" fn lightsaber_kernel_x86_interrupt_general_protection_fault(stack_frame: InterruptStackFrame, error_code: u64) {
+    if stack_frame.from_user_mode() {
+        lightsaber_kernel_user_fault("general protection fault", ExitStatus::SIGNAL_SEGMENTATION_FAULT, &stack_frame);
+    }
+
+    panic!(
+        "General protection fault at {:#x} with error code {:#x}. (`GENERAL_PROTECTION_FAULT`)",
+        stack_frame.instruction_pointer.as_u64(),
+        error_code
+    );
+}
+
+extern "C" fn lightsaber_kernel_trap_page_fault(frame: &mut TrapFrame) {
+    let address = Cr2::read();
+    let copy_on_write = PAGE_FAULT_PROTECTION_VIOLATION | PAGE_FAULT_WRITE;
+
+    if frame.error_code & copy_on_write == copy_on_write && user::lightsaber_kernel_is_user_address(address.as_u64()) {
+        // Resolving the fault may sleep on the address space lock, which needs interrupts back on.
+        if frame.interrupt_stack_frame().interrupts_enabled() {
+            unsafe {
+                interrupts::lightsaber_kernel_enable_interrupts();
+            }
+        }
+
+        if process::lightsaber_kernel_resolve_write_fault(address) {
+            return;
+        }
+    }
+
+    if frame.from_user_mode() {
+        lightsaber_kernel_user_fault("page fault", ExitStatus::SIGNAL_SEGMENTATION_FAULT, &frame.interrupt_stack_frame());
+    }
+
+    if let Some(fixup) = user::lightsaber_kernel_user_access_fixup(frame.instruction_pointer) {
+        frame.instruction_pointer = fixup;
+
+        return;
+    }
+
+    panic!(
+        "Page fault accessing {:#x} at {:#x} with error code {:#x}. (`PAGE_FAULT`)",
+        address.as_u64(),
+        frame.instruction_pointer,
+        frame.error_code
+    );
+}
+
+trap_entry!(lightsaber_kernel_trap_entry_page_fault, lightsaber_kernel_trap_page_fault, error_code);
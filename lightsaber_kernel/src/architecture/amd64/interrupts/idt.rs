@@ -1,5 +1,33 @@
+use core::mem;
+
 use x86_64::VirtAddr;
 
+use crate::architecture::{
+    gdt::{
+        DOUBLE_FAULT_STACK_INDEX,
+        KERNEL_CODE_SELECTOR
+    },
+    interrupts::exceptions
+};
+
+pub const INTERRUPT_DESCRIPTOR_TABLE_ENTRIES: usize = 256;
+
+pub const DIVISION_BY_ZERO_VECTOR: u8 = 0;
+pub const DEBUG_VECTOR: u8 = 1;
+pub const NON_MASKABLE_INTERRUPT_VECTOR: u8 = 2;
+pub const BREAKPOINT_VECTOR: u8 = 3;
+pub const OVERFLOW_VECTOR: u8 = 4;
+pub const INVALID_OPCODE_VECTOR: u8 = 6;
+pub const DOUBLE_FAULT_VECTOR: u8 = 8;
+pub const GENERAL_PROTECTION_FAULT_VECTOR: u8 = 13;
+pub const PAGE_FAULT_VECTOR: u8 = 14;
+
+const INTERRUPT_GATE: u8 = 0x8E;
+const RFLAGS_INTERRUPT_FLAG: u64 = 1 << 9;
+const PRIVILEGE_LEVEL_USER: u8 = 3 << 5;
+
+static mut INTERRUPT_DESCRIPTOR_TABLE: [IdtEntry; INTERRUPT_DESCRIPTOR_TABLE_ENTRIES] = [IdtEntry::missing(); INTERRUPT_DESCRIPTOR_TABLE_ENTRIES];
+
 #[derive(Debug, Clone, Copy)]
 #[repr(C)]
 pub struct InterruptStackFrame {
@@ -9,3 +37,87 @@ pub struct InterruptStackFrame {
     pub stack_pointer: VirtAddr,
     pub stack_segment: u64,
 }
+
+impl InterruptStackFrame {
+    #[inline]
+    pub fn from_user_mode(&self) -> bool {
+        self.code_segment & 0x03 == 0x03
+    }
+
+    #[inline]
+    pub fn interrupts_enabled(&self) -> bool {
+        self.cpu_flags & RFLAGS_INTERRUPT_FLAG != 0
+    }
+}
+
+#[repr(C, packed)]
+struct IdtDescriptor {
+    size: u16,
+    offset: u64
+}
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct IdtEntry {
+    offset_low: u16,
+    selector: u16,
+    interrupt_stack_table: u8,
+    type_attributes: u8,
+    offset_middle: u16,
+    offset_high: u32,
+    reserved: u32
+}
+
+impl IdtEntry {
+    #[inline]
+    pub const fn new(handler: u64, selector: u16, interrupt_stack_table: u8, type_attributes: u8) -> Self {
+        Self {
+            offset_low: handler as u16,
+            selector,
+            interrupt_stack_table,
+            type_attributes,
+            offset_middle: (handler >> 16) as u16,
+            offset_high: (handler >> 32) as u32,
+            reserved: 0
+        }
+    }
+
+    #[inline]
+    const fn missing() -> Self {
+        Self::new(0, 0, 0, 0)
+    }
+}
+
+pub unsafe fn lightsaber_kernel_set_interrupt_handler(vector: u8, handler: u64, interrupt_stack_table: u8, user_accessible: bool) {
+    let type_attributes = match user_accessible {
+        true => INTERRUPT_GATE | PRIVILEGE_LEVEL_USER,
+        false => INTERRUPT_GATE
+    };
+
+    INTERRUPT_DESCRIPTOR_TABLE[vector as usize] = IdtEntry::new(handler, KERNEL_CODE_SELECTOR, interrupt_stack_table, type_attributes);
+}
+
+pub fn lightsaber_kernel_initialize_interrupt_descriptor_table() {
+    unsafe {
+        lightsaber_kernel_set_interrupt_handler(DIVISION_BY_ZERO_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_division_by_zero as usize as u64, 0, false);
+        lightsaber_kernel_set_interrupt_handler(DEBUG_VECTOR, exceptions::lightsaber_kernel_trap_entry_debug as usize as u64, 0, false);
+        lightsaber_kernel_set_interrupt_handler(NON_MASKABLE_INTERRUPT_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_non_maskable_interrupts as usize as u64, 0, false);
+        lightsaber_kernel_set_interrupt_handler(BREAKPOINT_VECTOR, exceptions::lightsaber_kernel_trap_entry_breakpoint as usize as u64, 0, true);
+        lightsaber_kernel_set_interrupt_handler(OVERFLOW_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_overflow as usize as u64, 0, true);
+        lightsaber_kernel_set_interrupt_handler(INVALID_OPCODE_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_invalid_opcode as usize as u64, 0, false);
+        lightsaber_kernel_set_interrupt_handler(DOUBLE_FAULT_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_double_fault as usize as u64, DOUBLE_FAULT_STACK_INDEX, false);
+        lightsaber_kernel_set_interrupt_handler(GENERAL_PROTECTION_FAULT_VECTOR, exceptions::lightsaber_kernel_x86_interrupt_general_protection_fault as usize as u64, 0, false);
+        lightsaber_kernel_set_interrupt_handler(PAGE_FAULT_VECTOR, exceptions::lightsaber_kernel_trap_entry_page_fault as usize as u64, 0, false);
+
+        let idt_descriptor = IdtDescriptor {
+            size: (mem::size_of::<[IdtEntry; INTERRUPT_DESCRIPTOR_TABLE_ENTRIES]>() - 1) as u16,
+            offset: &INTERRUPT_DESCRIPTOR_TABLE as *const _ as u64
+        };
+
+        asm!(
+            "lidt [{}]",
+            in(reg) &idt_descriptor as *const _,
+            options(readonly, nostack, preserves_flags)
+        );
+    }
+}
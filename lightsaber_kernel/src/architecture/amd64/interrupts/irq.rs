@@ -0,0 +1,124 @@
+use core::{
+    mem,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::{
+        interrupts::idt::{
+            self,
+            InterruptStackFrame
+        },
+        pic::{
+            self,
+            PIC_IRQ_COUNT,
+            PIC_MASTER_OFFSET
+        }
+    },
+    scheduler
+};
+
+pub const IRQ_TIMER: u8 = 0;
+pub const IRQ_KEYBOARD: u8 = 1;
+pub const IRQ_SERIAL_PRIMARY: u8 = 4;
+pub const IRQ_MOUSE: u8 = 12;
+
+const IRQ_SPURIOUS_MASTER: u8 = 7;
+const IRQ_SPURIOUS_SLAVE: u8 = 15;
+
+pub type IrqHandler = fn();
+
+// Only ever copied into the array below, never used as a value of its own.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_IRQ_HANDLER: AtomicUsize = AtomicUsize::new(0);
+static IRQ_HANDLERS: [AtomicUsize; PIC_IRQ_COUNT as usize] = [NO_IRQ_HANDLER; PIC_IRQ_COUNT as usize];
+
+macro_rules! irq_entry {
+    ($name:ident, $irq:expr) => {
+        extern "x86-interrupt" fn $name(stack_frame: InterruptStackFrame) {
+            lightsaber_kernel_dispatch_irq($irq, stack_frame.from_user_mode());
+        }
+    };
+}
+
+irq_entry!(lightsaber_kernel_irq_0, 0);
+irq_entry!(lightsaber_kernel_irq_1, 1);
+irq_entry!(lightsaber_kernel_irq_2, 2);
+irq_entry!(lightsaber_kernel_irq_3, 3);
+irq_entry!(lightsaber_kernel_irq_4, 4);
+irq_entry!(lightsaber_kernel_irq_5, 5);
+irq_entry!(lightsaber_kernel_irq_6, 6);
+irq_entry!(lightsaber_kernel_irq_7, 7);
+irq_entry!(lightsaber_kernel_irq_8, 8);
+irq_entry!(lightsaber_kernel_irq_9, 9);
+irq_entry!(lightsaber_kernel_irq_10, 10);
+irq_entry!(lightsaber_kernel_irq_11, 11);
+irq_entry!(lightsaber_kernel_irq_12, 12);
+irq_entry!(lightsaber_kernel_irq_13, 13);
+irq_entry!(lightsaber_kernel_irq_14, 14);
+irq_entry!(lightsaber_kernel_irq_15, 15);
+
+fn lightsaber_kernel_dispatch_irq(irq: u8, from_user_mode: bool) {
+    if (irq == IRQ_SPURIOUS_MASTER || irq == IRQ_SPURIOUS_SLAVE) && !pic::lightsaber_kernel_pic_in_service(irq) {
+        // A spurious slave IRQ still has to be acknowledged on the master.
+        if irq == IRQ_SPURIOUS_SLAVE {
+            pic::lightsaber_kernel_pic_end_of_interrupt(0);
+        }
+
+        return;
+    }
+
+    let handler = IRQ_HANDLERS[irq as usize].load(Ordering::Acquire);
+
+    if handler != 0 {
+        let handler: IrqHandler = unsafe {
+            mem::transmute(handler)
+        };
+
+        handler();
+    }
+
+    pic::lightsaber_kernel_pic_end_of_interrupt(irq);
+    scheduler::lightsaber_kernel_preempt();
+
+    if from_user_mode {
+        scheduler::lightsaber_kernel_exit_if_killed();
+    }
+}
+
+pub fn lightsaber_kernel_initialize_irqs() {
+    let entries: [extern "x86-interrupt" fn(InterruptStackFrame); PIC_IRQ_COUNT as usize] = [
+        lightsaber_kernel_irq_0,
+        lightsaber_kernel_irq_1,
+        lightsaber_kernel_irq_2,
+        lightsaber_kernel_irq_3,
+        lightsaber_kernel_irq_4,
+        lightsaber_kernel_irq_5,
+        lightsaber_kernel_irq_6,
+        lightsaber_kernel_irq_7,
+        lightsaber_kernel_irq_8,
+        lightsaber_kernel_irq_9,
+        lightsaber_kernel_irq_10,
+        lightsaber_kernel_irq_11,
+        lightsaber_kernel_irq_12,
+        lightsaber_kernel_irq_13,
+        lightsaber_kernel_irq_14,
+        lightsaber_kernel_irq_15
+    ];
+
+    for (irq, entry) in entries.iter().enumerate() {
+        unsafe {
+            idt::lightsaber_kernel_set_interrupt_handler(PIC_MASTER_OFFSET + irq as u8, *entry as usize as u64, 0, false);
+        }
+    }
+
+    pic::lightsaber_kernel_initialize_pic();
+}
+
+pub fn lightsaber_kernel_register_irq_handler(irq: u8, handler: IrqHandler) {
+    IRQ_HANDLERS[irq as usize].store(handler as usize, Ordering::Release);
+    pic::lightsaber_kernel_pic_unmask(irq);
+}
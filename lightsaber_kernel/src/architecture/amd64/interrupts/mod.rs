@@ -1,10 +1,55 @@
 pub mod exceptions;
 pub mod idt;
+pub mod irq;
+pub mod msi;
+pub mod trap;
+
+const RFLAGS_INTERRUPT_FLAG: u64 = 1 << 9;
 
 pub unsafe fn lightsaber_kernel_disable_interrupts() {
     asm!("cli");
 }
 
+pub unsafe fn lightsaber_kernel_enable_interrupts() {
+    asm!("sti");
+}
+
 pub unsafe fn lightsaber_kernel_halt() {
     asm!("hlt", options(nostack, nomem))
 }
+
+pub fn lightsaber_kernel_interrupts_enabled() -> bool {
+    let rflags: u64;
+
+    unsafe {
+        asm!("
+            pushfq
+            pop {}
+            ",
+            out(reg) rflags,
+            options(nomem, preserves_flags)
+        )
+    }
+
+    rflags & RFLAGS_INTERRUPT_FLAG != 0
+}
+
+pub fn lightsaber_kernel_save_and_disable_interrupts() -> bool {
+    let enabled = lightsaber_kernel_interrupts_enabled();
+
+    if enabled {
+        unsafe {
+            lightsaber_kernel_disable_interrupts();
+        }
+    }
+
+    enabled
+}
+
+pub fn lightsaber_kernel_restore_interrupts(enabled: bool) {
+    if enabled {
+        unsafe {
+            lightsaber_kernel_enable_interrupts();
+        }
+    }
+}
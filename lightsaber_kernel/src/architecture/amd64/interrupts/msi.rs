@@ -0,0 +1,182 @@
+use core::{
+    mem,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::{
+        apic,
+        interrupts::idt::{
+            self,
+            InterruptStackFrame
+        },
+        pic::PIC_SLAVE_OFFSET
+    },
+    scheduler
+};
+
+pub const MSI_VECTOR_BASE: u8 = PIC_SLAVE_OFFSET + 8;
+pub const MSI_VECTOR_COUNT: u8 = 32;
+
+const MSI_ADDRESS_BASE: u64 = 0xFEE0_0000;
+const MSI_ADDRESS_DESTINATION_SHIFT: u64 = 12;
+
+// Handlers get back the argument they were registered with, so one driver can serve several devices or queues.
+pub type MsiHandler = fn(usize);
+
+// Only ever copied into the arrays below, never used as a value of its own.
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_MSI_HANDLER: AtomicUsize = AtomicUsize::new(0);
+static MSI_HANDLERS: [AtomicUsize; MSI_VECTOR_COUNT as usize] = [NO_MSI_HANDLER; MSI_VECTOR_COUNT as usize];
+static MSI_ARGUMENTS: [AtomicUsize; MSI_VECTOR_COUNT as usize] = [NO_MSI_HANDLER; MSI_VECTOR_COUNT as usize];
+
+// What a device writes, and where, to raise an interrupt.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct MsiMessage {
+    pub address: u64,
+    pub data: u32,
+    pub vector: u8
+}
+
+macro_rules! msi_entry {
+    ($name:ident, $index:expr) => {
+        extern "x86-interrupt" fn $name(stack_frame: InterruptStackFrame) {
+            lightsaber_kernel_dispatch_msi($index, stack_frame.from_user_mode());
+        }
+    };
+}
+
+msi_entry!(lightsaber_kernel_msi_0, 0);
+msi_entry!(lightsaber_kernel_msi_1, 1);
+msi_entry!(lightsaber_kernel_msi_2, 2);
+msi_entry!(lightsaber_kernel_msi_3, 3);
+msi_entry!(lightsaber_kernel_msi_4, 4);
+msi_entry!(lightsaber_kernel_msi_5, 5);
+msi_entry!(lightsaber_kernel_msi_6, 6);
+msi_entry!(lightsaber_kernel_msi_7, 7);
+msi_entry!(lightsaber_kernel_msi_8, 8);
+msi_entry!(lightsaber_kernel_msi_9, 9);
+msi_entry!(lightsaber_kernel_msi_10, 10);
+msi_entry!(lightsaber_kernel_msi_11, 11);
+msi_entry!(lightsaber_kernel_msi_12, 12);
+msi_entry!(lightsaber_kernel_msi_13, 13);
+msi_entry!(lightsaber_kernel_msi_14, 14);
+msi_entry!(lightsaber_kernel_msi_15, 15);
+msi_entry!(lightsaber_kernel_msi_16, 16);
+msi_entry!(lightsaber_kernel_msi_17, 17);
+msi_entry!(lightsaber_kernel_msi_18, 18);
+msi_entry!(lightsaber_kernel_msi_19, 19);
+msi_entry!(lightsaber_kernel_msi_20, 20);
+msi_entry!(lightsaber_kernel_msi_21, 21);
+msi_entry!(lightsaber_kernel_msi_22, 22);
+msi_entry!(lightsaber_kernel_msi_23, 23);
+msi_entry!(lightsaber_kernel_msi_24, 24);
+msi_entry!(lightsaber_kernel_msi_25, 25);
+msi_entry!(lightsaber_kernel_msi_26, 26);
+msi_entry!(lightsaber_kernel_msi_27, 27);
+msi_entry!(lightsaber_kernel_msi_28, 28);
+msi_entry!(lightsaber_kernel_msi_29, 29);
+msi_entry!(lightsaber_kernel_msi_30, 30);
+msi_entry!(lightsaber_kernel_msi_31, 31);
+
+fn lightsaber_kernel_dispatch_msi(index: usize, from_user_mode: bool) {
+    let handler = MSI_HANDLERS[index].load(Ordering::Acquire);
+
+    // Zero is an empty slot and one a slot still being claimed.
+    if handler > 1 {
+        let handler: MsiHandler = unsafe {
+            mem::transmute(handler)
+        };
+
+        handler(MSI_ARGUMENTS[index].load(Ordering::Acquire));
+    }
+
+    apic::lightsaber_kernel_local_apic_end_of_interrupt();
+    scheduler::lightsaber_kernel_preempt();
+
+    if from_user_mode {
+        scheduler::lightsaber_kernel_exit_if_killed();
+    }
+}
+
+pub fn lightsaber_kernel_initialize_msi() {
+    let entries: [extern "x86-interrupt" fn(InterruptStackFrame); MSI_VECTOR_COUNT as usize] = [
+        lightsaber_kernel_msi_0,
+        lightsaber_kernel_msi_1,
+        lightsaber_kernel_msi_2,
+        lightsaber_kernel_msi_3,
+        lightsaber_kernel_msi_4,
+        lightsaber_kernel_msi_5,
+        lightsaber_kernel_msi_6,
+        lightsaber_kernel_msi_7,
+        lightsaber_kernel_msi_8,
+        lightsaber_kernel_msi_9,
+        lightsaber_kernel_msi_10,
+        lightsaber_kernel_msi_11,
+        lightsaber_kernel_msi_12,
+        lightsaber_kernel_msi_13,
+        lightsaber_kernel_msi_14,
+        lightsaber_kernel_msi_15,
+        lightsaber_kernel_msi_16,
+        lightsaber_kernel_msi_17,
+        lightsaber_kernel_msi_18,
+        lightsaber_kernel_msi_19,
+        lightsaber_kernel_msi_20,
+        lightsaber_kernel_msi_21,
+        lightsaber_kernel_msi_22,
+        lightsaber_kernel_msi_23,
+        lightsaber_kernel_msi_24,
+        lightsaber_kernel_msi_25,
+        lightsaber_kernel_msi_26,
+        lightsaber_kernel_msi_27,
+        lightsaber_kernel_msi_28,
+        lightsaber_kernel_msi_29,
+        lightsaber_kernel_msi_30,
+        lightsaber_kernel_msi_31
+    ];
+
+    for (index, entry) in entries.iter().enumerate() {
+        unsafe {
+            idt::lightsaber_kernel_set_interrupt_handler(MSI_VECTOR_BASE + index as u8, *entry as usize as u64, 0, false);
+        }
+    }
+
+    apic::lightsaber_kernel_initialize_local_apic();
+}
+
+// Claims a free vector aimed at this processor, or nothing if there is no local APIC to receive it.
+pub fn lightsaber_kernel_allocate_msi(handler: MsiHandler, argument: usize) -> Option<MsiMessage> {
+    if !apic::lightsaber_kernel_local_apic_available() {
+        return None;
+    }
+
+    for index in 0..MSI_VECTOR_COUNT as usize {
+        // A placeholder claims the slot so the argument is in place before the real handler becomes visible.
+        if MSI_HANDLERS[index].compare_exchange(0, 1, Ordering::AcqRel, Ordering::Acquire).is_err() {
+            continue;
+        }
+
+        MSI_ARGUMENTS[index].store(argument, Ordering::Release);
+        MSI_HANDLERS[index].store(handler as usize, Ordering::Release);
+
+        let vector = MSI_VECTOR_BASE + index as u8;
+
+        return Some(MsiMessage {
+            address: MSI_ADDRESS_BASE | (apic::lightsaber_kernel_local_apic_id() as u64) << MSI_ADDRESS_DESTINATION_SHIFT,
+            data: vector as u32,
+            vector
+        });
+    }
+
+    None
+}
+
+pub fn lightsaber_kernel_free_msi(vector: u8) {
+    if let Some(index) = vector.checked_sub(MSI_VECTOR_BASE).filter(|index| *index < MSI_VECTOR_COUNT) {
+        MSI_HANDLERS[index as usize].store(0, Ordering::Release);
+        MSI_ARGUMENTS[index as usize].store(0, Ordering::Release);
+    }
+}
@@ -0,0 +1,143 @@
+use x86_64::VirtAddr;
+
+use crate::architecture::interrupts::idt::InterruptStackFrame;
+
+pub const RFLAGS_TRAP_FLAG: u64 = 1 << 8;
+
+const DEBUG_STATUS_SINGLE_STEP: u64 = 1 << 14;
+
+// Every general purpose register of the interrupted code, below the frame the processor pushed,
+// for exception handlers that need to inspect or change more than `x86-interrupt` gives them.
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct TrapFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub r11: u64,
+    pub r10: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub rbp: u64,
+    pub rdi: u64,
+    pub rsi: u64,
+    pub rdx: u64,
+    pub rcx: u64,
+    pub rbx: u64,
+    pub rax: u64,
+    // Zero for the vectors the processor pushes none for.
+    pub error_code: u64,
+    pub instruction_pointer: u64,
+    pub code_segment: u64,
+    pub cpu_flags: u64,
+    pub stack_pointer: u64,
+    pub stack_segment: u64
+}
+
+impl TrapFrame {
+    #[inline]
+    pub fn from_user_mode(&self) -> bool {
+        self.code_segment & 0x03 == 0x03
+    }
+
+    pub fn interrupt_stack_frame(&self) -> InterruptStackFrame {
+        InterruptStackFrame {
+            instruction_pointer: VirtAddr::new(self.instruction_pointer),
+            code_segment: self.code_segment,
+            cpu_flags: self.cpu_flags,
+            stack_pointer: VirtAddr::new(self.stack_pointer),
+            stack_segment: self.stack_segment
+        }
+    }
+}
+
+// Vectors without an error code get a zero one, so that every handler sees the same frame. The
+// stack is aligned for the call by hand, as it depends on whether the processor pushed the code.
+pub macro trap_entry {
+    ($name:ident, $handler:path) => {
+        trap_entry!(@entry $name, $handler, "push 0");
+    },
+
+    ($name:ident, $handler:path, error_code) => {
+        trap_entry!(@entry $name, $handler, "");
+    },
+
+    (@entry $name:ident, $handler:path, $push_error_code:literal) => {
+        #[naked]
+        pub unsafe extern "C" fn $name() {
+            asm!(
+                $push_error_code,
+                "
+                push rax
+                push rbx
+                push rcx
+                push rdx
+                push rsi
+                push rdi
+                push rbp
+                push r8
+                push r9
+                push r10
+                push r11
+                push r12
+                push r13
+                push r14
+                push r15
+
+                mov rdi, rsp
+                mov rbx, rsp
+                and rsp, -16
+                cld
+                call {handler}
+                mov rsp, rbx
+
+                pop r15
+                pop r14
+                pop r13
+                pop r12
+                pop r11
+                pop r10
+                pop r9
+                pop r8
+                pop rbp
+                pop rdi
+                pop rsi
+                pop rdx
+                pop rcx
+                pop rbx
+                pop rax
+                add rsp, 8
+
+                iretq
+                ",
+                handler = sym $handler,
+                options(noreturn)
+            )
+        }
+    }
+}
+
+// Reads and clears DR6, which the processor never clears by itself.
+pub fn lightsaber_kernel_take_debug_status() -> u64 {
+    let status: u64;
+
+    unsafe {
+        asm!("
+            mov {status}, dr6
+            mov {zero}, 0
+            mov dr6, {zero}
+            ",
+            status = out(reg) status,
+            zero = out(reg) _,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+
+    status
+}
+
+#[inline]
+pub fn lightsaber_kernel_single_stepped(debug_status: u64) -> bool {
+    debug_status & DEBUG_STATUS_SINGLE_STEP != 0
+}
@@ -1,6 +1,12 @@
+pub mod apic;
+pub mod context;
 pub mod gdt;
 pub mod interrupts;
+pub mod pic;
+pub mod pit;
+pub mod power;
 pub mod processor;
+pub mod syscall;
 
 pub mod elf {
     pub use goblin::elf64::*;
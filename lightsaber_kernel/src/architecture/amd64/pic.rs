@@ -0,0 +1,96 @@
+use core::sync::atomic::{
+    AtomicU16,
+    Ordering
+};
+
+use x86_64::instructions::port::Port;
+
+pub const PIC_MASTER_OFFSET: u8 = 32;
+pub const PIC_SLAVE_OFFSET: u8 = PIC_MASTER_OFFSET + 8;
+pub const PIC_IRQ_COUNT: u8 = 16;
+
+const PIC_MASTER_COMMAND: u16 = 0x20;
+const PIC_MASTER_DATA: u16 = 0x21;
+const PIC_SLAVE_COMMAND: u16 = 0xA0;
+const PIC_SLAVE_DATA: u16 = 0xA1;
+
+const PIC_INITIALIZE: u8 = 0x11;
+const PIC_MODE_8086: u8 = 0x01;
+const PIC_END_OF_INTERRUPT: u8 = 0x20;
+const PIC_READ_IN_SERVICE: u8 = 0x0B;
+
+const PIC_CASCADE_IRQ: u8 = 2;
+
+static PIC_MASK: AtomicU16 = AtomicU16::new(!(1 << PIC_CASCADE_IRQ));
+
+unsafe fn lightsaber_kernel_pic_wait() {
+    Port::<u8>::new(0x80).write(0);
+}
+
+unsafe fn lightsaber_kernel_pic_write_mask(mask: u16) {
+    Port::<u8>::new(PIC_MASTER_DATA).write(mask as u8);
+    Port::<u8>::new(PIC_SLAVE_DATA).write((mask >> 8) as u8);
+}
+
+pub fn lightsaber_kernel_initialize_pic() {
+    unsafe {
+        let mut master_command = Port::<u8>::new(PIC_MASTER_COMMAND);
+        let mut master_data = Port::<u8>::new(PIC_MASTER_DATA);
+        let mut slave_command = Port::<u8>::new(PIC_SLAVE_COMMAND);
+        let mut slave_data = Port::<u8>::new(PIC_SLAVE_DATA);
+
+        master_command.write(PIC_INITIALIZE);
+        lightsaber_kernel_pic_wait();
+        slave_command.write(PIC_INITIALIZE);
+        lightsaber_kernel_pic_wait();
+
+        master_data.write(PIC_MASTER_OFFSET);
+        lightsaber_kernel_pic_wait();
+        slave_data.write(PIC_SLAVE_OFFSET);
+        lightsaber_kernel_pic_wait();
+
+        master_data.write(1 << PIC_CASCADE_IRQ);
+        lightsaber_kernel_pic_wait();
+        slave_data.write(PIC_CASCADE_IRQ);
+        lightsaber_kernel_pic_wait();
+
+        master_data.write(PIC_MODE_8086);
+        lightsaber_kernel_pic_wait();
+        slave_data.write(PIC_MODE_8086);
+        lightsaber_kernel_pic_wait();
+
+        lightsaber_kernel_pic_write_mask(PIC_MASK.load(Ordering::Acquire));
+    }
+}
+
+pub fn lightsaber_kernel_pic_unmask(irq: u8) {
+    let mask = PIC_MASK.fetch_and(!(1 << irq), Ordering::AcqRel) & !(1 << irq);
+
+    unsafe {
+        lightsaber_kernel_pic_write_mask(mask);
+    }
+}
+
+pub fn lightsaber_kernel_pic_in_service(irq: u8) -> bool {
+    let in_service = unsafe {
+        Port::<u8>::new(PIC_MASTER_COMMAND).write(PIC_READ_IN_SERVICE);
+        Port::<u8>::new(PIC_SLAVE_COMMAND).write(PIC_READ_IN_SERVICE);
+
+        let master = Port::<u8>::new(PIC_MASTER_COMMAND).read() as u16;
+        let slave = Port::<u8>::new(PIC_SLAVE_COMMAND).read() as u16;
+
+        slave << 8 | master
+    };
+
+    in_service & (1 << irq) != 0
+}
+
+pub fn lightsaber_kernel_pic_end_of_interrupt(irq: u8) {
+    unsafe {
+        if irq >= 8 {
+            Port::<u8>::new(PIC_SLAVE_COMMAND).write(PIC_END_OF_INTERRUPT);
+        }
+
+        Port::<u8>::new(PIC_MASTER_COMMAND).write(PIC_END_OF_INTERRUPT);
+    }
+}
@@ -0,0 +1,20 @@
+use x86_64::instructions::port::Port;
+
+pub const PIT_BASE_FREQUENCY: u32 = 1_193_182;
+
+const PIT_CHANNEL_0: u16 = 0x40;
+const PIT_COMMAND: u16 = 0x43;
+
+const PIT_CHANNEL_0_SQUARE_WAVE: u8 = 0x36;
+
+pub fn lightsaber_kernel_initialize_pit(frequency: u32) {
+    let divisor = (PIT_BASE_FREQUENCY / frequency).max(1).min(u16::MAX as u32) as u16;
+
+    unsafe {
+        Port::<u8>::new(PIT_COMMAND).write(PIT_CHANNEL_0_SQUARE_WAVE);
+
+        let mut channel = Port::<u8>::new(PIT_CHANNEL_0);
+        channel.write(divisor as u8);
+        channel.write((divisor >> 8) as u8);
+    }
+}
@@ -0,0 +1,108 @@
+use core::hint;
+
+use x86_64::instructions::port::Port;
+
+use crate::{
+    acpi,
+    architecture::interrupts,
+    time
+};
+
+const KEYBOARD_CONTROLLER_STATUS: u16 = 0x64;
+const KEYBOARD_CONTROLLER_COMMAND: u16 = 0x64;
+const KEYBOARD_CONTROLLER_INPUT_FULL: u8 = 1 << 1;
+const KEYBOARD_CONTROLLER_PULSE_RESET: u8 = 0xFE;
+const KEYBOARD_CONTROLLER_ATTEMPTS: usize = 100_000;
+
+// Power-off ports of the emulators that do not describe S5 in a way the kernel can find.
+const EMULATOR_SHUTDOWN_PORTS: &[(u16, u16)] = &[
+    // QEMU with the PIIX4 and ICH9 chipsets.
+    (0x604, 0x2000),
+    // Bochs and older QEMU.
+    (0xB004, 0x2000),
+    // VirtualBox.
+    (0x4004, 0x3400)
+];
+
+// Grace period for a reset or power-off request to take effect before the next way is tried.
+const POWER_REQUEST_WAIT: u64 = 100;
+
+fn lightsaber_kernel_power_wait() {
+    match interrupts::lightsaber_kernel_interrupts_enabled() {
+        true => time::lightsaber_kernel_sleep(POWER_REQUEST_WAIT),
+        false => {
+            for _ in 0..KEYBOARD_CONTROLLER_ATTEMPTS {
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+// Pulses the CPU reset line through the i8042, which nearly every PC still wires up.
+fn lightsaber_kernel_keyboard_controller_reset() {
+    unsafe {
+        for _ in 0..KEYBOARD_CONTROLLER_ATTEMPTS {
+            if Port::<u8>::new(KEYBOARD_CONTROLLER_STATUS).read() & KEYBOARD_CONTROLLER_INPUT_FULL == 0 {
+                break;
+            }
+
+            hint::spin_loop();
+        }
+
+        Port::<u8>::new(KEYBOARD_CONTROLLER_COMMAND).write(KEYBOARD_CONTROLLER_PULSE_RESET);
+    }
+}
+
+// With an empty IDT the breakpoint cannot be delivered, which escalates to a triple fault.
+fn lightsaber_kernel_triple_fault() -> ! {
+    let empty_table = [0u16; 5];
+
+    unsafe {
+        interrupts::lightsaber_kernel_disable_interrupts();
+
+        asm!("
+            lidt [{}]
+            int3
+            ",
+            in(reg) &empty_table,
+            options(noreturn)
+        )
+    }
+}
+
+pub fn lightsaber_kernel_reboot() -> ! {
+    log::info!("Rebooting.");
+
+    acpi::lightsaber_kernel_acpi_reset();
+    lightsaber_kernel_power_wait();
+
+    lightsaber_kernel_keyboard_controller_reset();
+    lightsaber_kernel_power_wait();
+
+    lightsaber_kernel_triple_fault()
+}
+
+pub fn lightsaber_kernel_shutdown() -> ! {
+    log::info!("Shutting down.");
+
+    acpi::lightsaber_kernel_acpi_shutdown();
+    lightsaber_kernel_power_wait();
+
+    for (port, value) in EMULATOR_SHUTDOWN_PORTS {
+        unsafe {
+            Port::<u16>::new(*port).write(*value);
+        }
+    }
+
+    lightsaber_kernel_power_wait();
+
+    log::warn!("Failed to power off; it is now safe to turn off the machine.");
+
+    unsafe {
+        interrupts::lightsaber_kernel_disable_interrupts();
+
+        loop {
+            interrupts::lightsaber_kernel_halt();
+        }
+    }
+}
@@ -1,36 +1,236 @@
-pub struct ProcessorState {
-    pub ax: usize,
-    pub bx: usize,
-    pub cx: usize,
-    pub dx: usize
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use core::{
+    arch::x86_64::{
+        __cpuid,
+        __cpuid_count
+    },
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use x86_64::registers::control::{
+    Cr4,
+    Cr4Flags
+};
+
+const CPUID_EXTENDED_FEATURES_SMEP: u32 = 1 << 7;
+const CPUID_EXTENDED_FEATURES_SMAP: u32 = 1 << 20;
+
+const CPUID_EXTENDED_MAXIMUM_LEAF: u32 = 0x8000_0000;
+const CPUID_EXTENDED_INFORMATION: u32 = 0x8000_0001;
+const CPUID_BRAND_STRING: u32 = 0x8000_0002;
+
+// Bit positions of the features worth reporting, by the register of the leaf that has them.
+const CPUID_FEATURES_EDX: &[(u32, &str)] = &[
+    (0, "fpu"),
+    (4, "tsc"),
+    (5, "msr"),
+    (9, "apic"),
+    (15, "cmov"),
+    (19, "clflush"),
+    (23, "mmx"),
+    (24, "fxsr"),
+    (25, "sse"),
+    (26, "sse2")
+];
+
+const CPUID_FEATURES_ECX: &[(u32, &str)] = &[
+    (0, "sse3"),
+    (9, "ssse3"),
+    (13, "cx16"),
+    (19, "sse4.1"),
+    (20, "sse4.2"),
+    (21, "x2apic"),
+    (23, "popcnt"),
+    (24, "tsc-deadline"),
+    (25, "aes"),
+    (26, "xsave"),
+    (28, "avx"),
+    (30, "rdrand"),
+    (31, "hypervisor")
+];
+
+const CPUID_EXTENDED_FEATURES_EBX: &[(u32, &str)] = &[
+    (0, "fsgsbase"),
+    (3, "bmi1"),
+    (5, "avx2"),
+    (7, "smep"),
+    (8, "bmi2"),
+    (18, "rdseed"),
+    (20, "smap")
+];
+
+const CPUID_EXTENDED_INFORMATION_EDX: &[(u32, &str)] = &[
+    (11, "syscall"),
+    (20, "nx"),
+    (26, "pdpe1gb"),
+    (27, "rdtscp"),
+    (29, "lm")
+];
+
+#[derive(Debug, Clone)]
+pub struct ProcessorIdentity {
+    pub vendor: String,
+    pub brand: String,
+    pub family: u32,
+    pub model: u32,
+    pub stepping: u32,
+    pub features: Vec<&'static str>
+}
+
+static SUPERVISOR_MODE_ACCESS_PREVENTION: AtomicBool = AtomicBool::new(false);
+
+pub fn lightsaber_kernel_initialize_processor_features() {
+    let maximum_leaf = unsafe {
+        __cpuid(0).eax
+    };
+
+    if maximum_leaf < 7 {
+        return;
+    }
+
+    let extended_features = unsafe {
+        __cpuid_count(7, 0).ebx
+    };
+
+    let smep = extended_features & CPUID_EXTENDED_FEATURES_SMEP != 0;
+    let smap = extended_features & CPUID_EXTENDED_FEATURES_SMAP != 0;
+
+    unsafe {
+        Cr4::update(|cr4_flags| {
+            if smep {
+                *cr4_flags |= Cr4Flags::SUPERVISOR_MODE_EXECUTION_PROTECTION;
+            }
+
+            if smap {
+                *cr4_flags |= Cr4Flags::SUPERVISOR_MODE_ACCESS_PREVENTION;
+            }
+        });
+    }
+
+    SUPERVISOR_MODE_ACCESS_PREVENTION.store(smap, Ordering::Release);
+
+    log::info!("Initialized processor features. (SMEP: {}, SMAP: {})", smep, smap);
+}
+
+fn lightsaber_kernel_cpuid_features(register: u32, features: &[(u32, &'static str)], found: &mut Vec<&'static str>) {
+    for (bit, name) in features {
+        if register & (1 << bit) != 0 {
+            found.push(name);
+        }
+    }
+}
+
+fn lightsaber_kernel_cpuid_string(registers: &[u32]) -> String {
+    let bytes: Vec<u8> = registers.iter().flat_map(|register| register.to_le_bytes().to_vec()).collect();
+
+    String::from_utf8_lossy(&bytes).trim_matches(|character: char| character == '\0' || character == ' ').into()
+}
+
+pub fn lightsaber_kernel_processor_identity() -> ProcessorIdentity {
+    let vendor = unsafe {
+        __cpuid(0)
+    };
+
+    let information = unsafe {
+        __cpuid(1)
+    };
+
+    // Extended family and model only count for the families that overflowed the base fields.
+    let base_family = (information.eax >> 8) & 0xF;
+    let base_model = (information.eax >> 4) & 0xF;
+
+    let family = match base_family {
+        0xF => base_family + ((information.eax >> 20) & 0xFF),
+        _ => base_family
+    };
+
+    let model = match base_family {
+        0x6 | 0xF => base_model | ((information.eax >> 16) & 0xF) << 4,
+        _ => base_model
+    };
+
+    let mut features = Vec::new();
+
+    lightsaber_kernel_cpuid_features(information.edx, CPUID_FEATURES_EDX, &mut features);
+    lightsaber_kernel_cpuid_features(information.ecx, CPUID_FEATURES_ECX, &mut features);
+
+    if vendor.eax >= 7 {
+        let extended_features = unsafe {
+            __cpuid_count(7, 0).ebx
+        };
+
+        lightsaber_kernel_cpuid_features(extended_features, CPUID_EXTENDED_FEATURES_EBX, &mut features);
+    }
+
+    let extended_maximum_leaf = unsafe {
+        __cpuid(CPUID_EXTENDED_MAXIMUM_LEAF).eax
+    };
+
+    if extended_maximum_leaf >= CPUID_EXTENDED_INFORMATION {
+        let extended_information = unsafe {
+            __cpuid(CPUID_EXTENDED_INFORMATION).edx
+        };
+
+        lightsaber_kernel_cpuid_features(extended_information, CPUID_EXTENDED_INFORMATION_EDX, &mut features);
+    }
+
+    let brand = match extended_maximum_leaf >= CPUID_BRAND_STRING + 2 {
+        true => {
+            let registers: Vec<u32> = (0..3)
+                .flat_map(|index| {
+                    let leaf = unsafe {
+                        __cpuid(CPUID_BRAND_STRING + index)
+                    };
+
+                    [leaf.eax, leaf.ebx, leaf.ecx, leaf.edx].to_vec()
+                })
+                .collect();
+
+            lightsaber_kernel_cpuid_string(&registers)
+        }
+        false => String::new()
+    };
+
+    ProcessorIdentity {
+        vendor: lightsaber_kernel_cpuid_string(&[vendor.ebx, vendor.edx, vendor.ecx]),
+        brand,
+        family,
+        model,
+        stepping: information.eax & 0xF,
+        features
+    }
+}
+
+#[inline]
+pub fn lightsaber_kernel_smap_enabled() -> bool {
+    SUPERVISOR_MODE_ACCESS_PREVENTION.load(Ordering::Acquire)
 }
 
-impl ProcessorState {
-    pub fn new() -> Self {
-        let ax;
-        let bx;
-        let cx;
-        let dx;
+pub fn lightsaber_kernel_with_user_access<F, R>(function: F) -> R
+where
+    F: FnOnce() -> R {
+    let smap = lightsaber_kernel_smap_enabled();
 
+    if smap {
         unsafe {
-            asm!("
-                mov {}, rax
-                mov {}, rbx
-                mov {}, rcx
-                mov {}, rdx
-                ",
-                out(reg) ax,
-                out(reg) bx,
-                out(reg) cx,
-                out(reg) dx
-            )
+            asm!("stac", options(nomem, nostack));
         }
+    }
 
-        Self {
-            ax,
-            bx,
-            cx,
-            dx
+    let result = function();
+
+    if smap {
+        unsafe {
+            asm!("clac", options(nomem, nostack));
         }
     }
+
+    result
 }
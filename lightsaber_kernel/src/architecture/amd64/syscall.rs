@@ -0,0 +1,208 @@
+use core::ptr;
+
+use x86_64::{
+    registers::model_specific::{
+        Efer,
+        EferFlags,
+        KernelGsBase,
+        Msr
+    },
+    VirtAddr
+};
+
+use crate::{
+    architecture::gdt::{
+        self,
+        TaskStateSegment,
+        KERNEL_CODE_SELECTOR,
+        USER_CODE_SELECTOR,
+        USER_DATA_SELECTOR
+    },
+    syscall
+};
+
+const IA32_STAR: u32 = 0xC000_0081;
+const IA32_LSTAR: u32 = 0xC000_0082;
+const IA32_FMASK: u32 = 0xC000_0084;
+
+// Clears TF, IF, DF, IOPL, NT and AC on entry.
+const SYSCALL_FLAG_MASK: u64 = 0x0004_7700;
+
+pub const USER_INITIAL_RFLAGS: u64 = 0x202;
+
+static mut BOOTSTRAP_PROCESSOR_LOCAL: ProcessorLocal = ProcessorLocal {
+    user_stack_pointer: 0,
+    task_state_segment: ptr::null()
+};
+
+// What the system call entry reaches through the kernel GS base, one per processor; only the
+// bootstrap processor runs so far. The entry code hard-codes the field offsets.
+#[repr(C)]
+struct ProcessorLocal {
+    user_stack_pointer: u64,
+    task_state_segment: *const TaskStateSegment
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+#[repr(C)]
+pub struct SyscallFrame {
+    pub r15: u64,
+    pub r14: u64,
+    pub r13: u64,
+    pub r12: u64,
+    pub rbp: u64,
+    pub rbx: u64,
+    pub r9: u64,
+    pub r8: u64,
+    pub r10: u64,
+    pub rdx: u64,
+    pub rsi: u64,
+    pub rdi: u64,
+    pub rax: u64,
+    pub instruction_pointer: u64,
+    pub cpu_flags: u64,
+    pub stack_pointer: u64
+}
+
+impl SyscallFrame {
+    #[inline]
+    pub fn number(&self) -> u64 {
+        self.rax
+    }
+
+    #[inline]
+    pub fn arguments(&self) -> [u64; 6] {
+        [self.rdi, self.rsi, self.rdx, self.r10, self.r8, self.r9]
+    }
+
+    #[inline]
+    pub fn set_return_value(&mut self, value: u64) {
+        self.rax = value;
+    }
+}
+
+pub fn lightsaber_kernel_initialize_syscalls() {
+    // `sysret` loads CS from STAR[63:48] + 16 and SS from STAR[63:48] + 8.
+    let sysret_base = (USER_DATA_SELECTOR - 8) as u64;
+    let star = sysret_base << 48 | (KERNEL_CODE_SELECTOR as u64) << 32;
+
+    debug_assert_eq!(sysret_base + 16, USER_CODE_SELECTOR as u64);
+
+    unsafe {
+        Msr::new(IA32_STAR).write(star);
+        Msr::new(IA32_LSTAR).write(lightsaber_kernel_syscall_entry as usize as u64);
+        Msr::new(IA32_FMASK).write(SYSCALL_FLAG_MASK);
+
+        BOOTSTRAP_PROCESSOR_LOCAL.task_state_segment = &gdt::TASK_STATE_SEGMENT as *const _;
+        KernelGsBase::write(VirtAddr::new(&BOOTSTRAP_PROCESSOR_LOCAL as *const _ as u64));
+
+        Efer::update(|efer_flags| *efer_flags |= EferFlags::SYSTEM_CALL_EXTENSIONS);
+    }
+
+    log::info!("Initialized system calls.");
+}
+
+// The GS base is swapped only for as long as it takes to switch stacks, so nothing else in the
+// kernel depends on it and interrupts need not swap it.
+#[naked]
+unsafe extern "C" fn lightsaber_kernel_syscall_entry() {
+    asm!("
+        swapgs
+        mov gs:[0], rsp
+        mov rsp, gs:[8]
+        mov rsp, [rsp + 4]
+
+        push qword ptr gs:[0]
+        swapgs
+
+        push r11
+        push rcx
+        push rax
+        push rdi
+        push rsi
+        push rdx
+        push r10
+        push r8
+        push r9
+        push rbx
+        push rbp
+        push r12
+        push r13
+        push r14
+        push r15
+
+        mov rdi, rsp
+        call {dispatch}
+
+        mov rdi, rsp
+        jmp {return_to_user}
+        ",
+        dispatch = sym syscall::lightsaber_kernel_syscall_dispatch,
+        return_to_user = sym lightsaber_kernel_return_to_user,
+        options(noreturn)
+    )
+}
+
+#[naked]
+pub unsafe extern "C" fn lightsaber_kernel_return_to_user(_frame: *const SyscallFrame) -> ! {
+    asm!("
+        cli
+        mov rsp, rdi
+
+        pop r15
+        pop r14
+        pop r13
+        pop r12
+        pop rbp
+        pop rbx
+        pop r9
+        pop r8
+        pop r10
+        pop rdx
+        pop rsi
+        pop rdi
+        pop rax
+        pop rcx
+        pop r11
+        pop rsp
+
+        sysretq
+        ",
+        options(noreturn)
+    )
+}
+
+pub unsafe fn lightsaber_kernel_enter_user_mode(entry: u64, stack_pointer: u64) -> ! {
+    asm!("
+        push {user_data}
+        push {stack_pointer}
+        push {rflags}
+        push {user_code}
+        push {entry}
+
+        xor eax, eax
+        xor ebx, ebx
+        xor ecx, ecx
+        xor edx, edx
+        xor esi, esi
+        xor edi, edi
+        xor ebp, ebp
+        xor r8d, r8d
+        xor r9d, r9d
+        xor r10d, r10d
+        xor r11d, r11d
+        xor r12d, r12d
+        xor r13d, r13d
+        xor r14d, r14d
+        xor r15d, r15d
+
+        iretq
+        ",
+        user_data = in(reg) USER_DATA_SELECTOR as u64,
+        stack_pointer = in(reg) stack_pointer,
+        rflags = in(reg) USER_INITIAL_RFLAGS,
+        user_code = in(reg) USER_CODE_SELECTOR as u64,
+        entry = in(reg) entry,
+        options(noreturn)
+    )
+}
@@ -0,0 +1,228 @@
+use alloc::{
+    collections::BTreeMap,
+    sync::Arc,
+    vec,
+    vec::Vec
+};
+
+use crate::{
+    block::{
+        BlockDevice,
+        BlockError,
+        BlockOperation,
+        BlockRequest,
+        BlockStatistics
+    },
+    sync::Mutex
+};
+
+pub const DEFAULT_CACHE_PAGES: usize = 1024;
+
+const CACHE_PAGE_SIZE: usize = 4096;
+
+struct CachePage {
+    data: Vec<u8>,
+    dirty: bool,
+    last_used: u64
+}
+
+struct CacheState {
+    pages: BTreeMap<u64, CachePage>,
+    // Pages ordered by when they were last used, oldest first.
+    recently_used: BTreeMap<u64, u64>,
+    clock: u64
+}
+
+// A write-back cache of whole pages in front of a device, evicting the least recently used page first.
+pub struct BufferCache {
+    device: Arc<dyn BlockDevice>,
+    capacity: usize,
+    page_size: usize,
+    state: Mutex<CacheState>,
+    statistics: Arc<BlockStatistics>
+}
+
+impl BufferCache {
+    pub fn new(device: Arc<dyn BlockDevice>, capacity: usize) -> Self {
+        Self {
+            page_size: CACHE_PAGE_SIZE.max(device.sector_size()),
+            device,
+            capacity: capacity.max(1),
+            state: Mutex::named("buffer_cache", CacheState {
+                pages: BTreeMap::new(),
+                recently_used: BTreeMap::new(),
+                clock: 0
+            }),
+            statistics: Arc::new(BlockStatistics::default())
+        }
+    }
+
+    fn sectors_per_page(&self) -> u64 {
+        (self.page_size / self.device.sector_size()) as u64
+    }
+
+    // The last page of a device may be cut short by its end.
+    fn page_sectors(&self, page: u64) -> u64 {
+        let first = page * self.sectors_per_page();
+
+        self.sectors_per_page().min(self.device.sector_count() - first)
+    }
+
+    fn write_back(&self, index: u64, page: &mut CachePage) -> Result<(), BlockError> {
+        if !page.dirty {
+            return Ok(());
+        }
+
+        let length = self.page_sectors(index) as usize * self.device.sector_size();
+        self.device.write_sectors(index * self.sectors_per_page(), &page.data[..length])?;
+
+        page.dirty = false;
+
+        Ok(())
+    }
+
+    fn touch(&self, state: &mut CacheState, index: u64) {
+        state.clock += 1;
+
+        let clock = state.clock;
+
+        if let Some(page) = state.pages.get_mut(&index) {
+            state.recently_used.remove(&page.last_used);
+            page.last_used = clock;
+            state.recently_used.insert(clock, index);
+        }
+    }
+
+    // Brings a page in; a page about to be overwritten entirely does not need reading first.
+    fn load(&self, state: &mut CacheState, index: u64, overwrite: bool) -> Result<(), BlockError> {
+        if state.pages.contains_key(&index) {
+            self.statistics.record_cache_hit();
+            self.touch(state, index);
+
+            return Ok(());
+        }
+
+        self.statistics.record_cache_miss();
+
+        let mut data = vec![0; self.page_size];
+
+        if !overwrite {
+            let length = self.page_sectors(index) as usize * self.device.sector_size();
+            self.device.read_sectors(index * self.sectors_per_page(), &mut data[..length])?;
+        }
+
+        state.pages.insert(index, CachePage {
+            data,
+            dirty: false,
+            last_used: 0
+        });
+
+        self.touch(state, index);
+
+        Ok(())
+    }
+
+    fn evict(&self, state: &mut CacheState) {
+        while state.pages.len() > self.capacity {
+            let (last_used, index) = match state.recently_used.iter().next() {
+                Some((last_used, index)) => (*last_used, *index),
+                None => return
+            };
+
+            let mut page = state.pages.remove(&index).expect("The page list and the usage list disagree.");
+            state.recently_used.remove(&last_used);
+
+            // A page that cannot be written out stays cached rather than losing data.
+            if let Err(error) = self.write_back(index, &mut page) {
+                log::warn!("Failed to write back page {} of {}: {}", index, self.device.name(), error);
+
+                state.pages.insert(index, page);
+                state.recently_used.insert(last_used, index);
+
+                return;
+            }
+        }
+    }
+
+    fn transfer(&self, request: &BlockRequest) -> Result<(), BlockError> {
+        let mut state = self.state.lock();
+
+        if request.operation() == BlockOperation::Flush {
+            let indices = state.pages.iter().filter(|(_, page)| page.dirty).map(|(index, _)| *index).collect::<Vec<u64>>();
+
+            for index in indices {
+                let mut page = state.pages.remove(&index).expect("The page was just listed.");
+                let result = self.write_back(index, &mut page);
+
+                state.pages.insert(index, page);
+                result?;
+            }
+
+            drop(state);
+
+            return self.device.flush();
+        }
+
+        let sector_size = self.device.sector_size() as u64;
+        let page_size = self.page_size as u64;
+        let start = request.sector() * sector_size;
+        let end = start + request.sector_count() * sector_size;
+        let mut data = request.data();
+        let mut position = start;
+
+        while position < end {
+            let index = position / page_size;
+            let offset = (position % page_size) as usize;
+            let chunk = ((page_size - offset as u64).min(end - position)) as usize;
+            let buffer_offset = (position - start) as usize;
+            let whole_page = offset == 0 && chunk as u64 == self.page_sectors(index) * sector_size;
+
+            self.load(&mut state, index, request.operation() == BlockOperation::Write && whole_page)?;
+
+            let page = state.pages.get_mut(&index).expect("The page was just loaded.");
+
+            match request.operation() {
+                BlockOperation::Read => data[buffer_offset..buffer_offset + chunk].copy_from_slice(&page.data[offset..offset + chunk]),
+                _ => {
+                    page.data[offset..offset + chunk].copy_from_slice(&data[buffer_offset..buffer_offset + chunk]);
+                    page.dirty = true;
+                }
+            }
+
+            position += chunk as u64;
+        }
+
+        self.evict(&mut state);
+
+        Ok(())
+    }
+}
+
+impl BlockDevice for BufferCache {
+    fn name(&self) -> &str {
+        self.device.name()
+    }
+
+    fn sector_size(&self) -> usize {
+        self.device.sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.device.sector_count()
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.device.is_read_only()
+    }
+
+    // Requests are served synchronously, from memory or by waiting on the device underneath.
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        let status = self.transfer(&request);
+
+        request.complete(status);
+    }
+}
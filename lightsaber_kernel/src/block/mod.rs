@@ -0,0 +1,472 @@
+use alloc::{
+    format,
+    sync::Arc,
+    vec,
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use crate::{
+    fs::FsError,
+    scheduler,
+    sync::{
+        RwLock,
+        Spinlock,
+        SpinlockGuard,
+        WaitQueue
+    },
+    time
+};
+
+pub mod cache;
+pub mod partition;
+pub mod queue;
+#[cfg(test)]
+pub mod ram_disk;
+
+pub use cache::BufferCache;
+pub use partition::{
+    Partition,
+    PartitionKind
+};
+pub use queue::RequestQueue;
+#[cfg(test)]
+pub use ram_disk::RamDisk;
+
+const WRITEBACK_INTERVAL_MILLISECONDS: u64 = 5000;
+
+static BLOCK_DEVICES: RwLock<Vec<Arc<dyn BlockDevice>>> = RwLock::named("block_devices", Vec::new());
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlockError {
+    OutOfRange,
+
+    Misaligned,
+
+    ReadOnly,
+
+    NotReady,
+
+    Io
+}
+
+impl fmt::Display for BlockError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::OutOfRange => "The request is past the end of the device.",
+            Self::Misaligned => "The request is not a whole number of sectors.",
+            Self::ReadOnly => "The device is read-only.",
+            Self::NotReady => "The device is not ready.",
+            Self::Io => "The device reported an I/O error."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+impl From<BlockError> for FsError {
+    fn from(error: BlockError) -> Self {
+        match error {
+            BlockError::ReadOnly => Self::ReadOnly,
+            BlockError::OutOfRange | BlockError::Misaligned => Self::InvalidArgument,
+            BlockError::NotReady | BlockError::Io => Self::Io
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BlockOperation {
+    Read,
+
+    Write,
+
+    Flush
+}
+
+#[derive(Debug, Default)]
+pub struct BlockStatistics {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    flushes: AtomicU64,
+    sectors_read: AtomicU64,
+    sectors_written: AtomicU64,
+    errors: AtomicU64,
+    in_flight: AtomicU64,
+    latency_milliseconds: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BlockStatisticsSnapshot {
+    pub reads: u64,
+    pub writes: u64,
+    pub flushes: u64,
+    pub sectors_read: u64,
+    pub sectors_written: u64,
+    pub errors: u64,
+    pub in_flight: u64,
+    pub latency_milliseconds: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64
+}
+
+impl BlockStatistics {
+    pub fn snapshot(&self) -> BlockStatisticsSnapshot {
+        BlockStatisticsSnapshot {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            sectors_read: self.sectors_read.load(Ordering::Relaxed),
+            sectors_written: self.sectors_written.load(Ordering::Relaxed),
+            errors: self.errors.load(Ordering::Relaxed),
+            in_flight: self.in_flight.load(Ordering::Relaxed),
+            latency_milliseconds: self.latency_milliseconds.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed)
+        }
+    }
+
+    #[inline]
+    pub fn record_cache_hit(&self) {
+        self.cache_hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn record_cache_miss(&self) {
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_completion(&self, operation: BlockOperation, sectors: u64, status: Result<(), BlockError>, latency: u64) {
+        self.in_flight.fetch_sub(1, Ordering::Relaxed);
+        self.latency_milliseconds.fetch_add(latency, Ordering::Relaxed);
+
+        if status.is_err() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+
+            return;
+        }
+
+        match operation {
+            BlockOperation::Read => {
+                self.reads.fetch_add(1, Ordering::Relaxed);
+                self.sectors_read.fetch_add(sectors, Ordering::Relaxed);
+            }
+            BlockOperation::Write => {
+                self.writes.fetch_add(1, Ordering::Relaxed);
+                self.sectors_written.fetch_add(sectors, Ordering::Relaxed);
+            }
+            BlockOperation::Flush => {
+                self.flushes.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+// A single transfer handed to a device; completion may come from an interrupt handler.
+pub struct BlockRequest {
+    operation: BlockOperation,
+    sector: AtomicU64,
+    sector_count: u64,
+    data: Spinlock<Vec<u8>>,
+    status: Spinlock<Option<Result<(), BlockError>>>,
+    completion: WaitQueue,
+    submitted: u64,
+    statistics: Spinlock<Vec<Arc<BlockStatistics>>>
+}
+
+impl BlockRequest {
+    pub fn new(operation: BlockOperation, sector: u64, sector_count: u64, data: Vec<u8>) -> Arc<Self> {
+        Arc::new(Self {
+            operation,
+            sector: AtomicU64::new(sector),
+            sector_count,
+            data: Spinlock::named("block_request_data", data),
+            status: Spinlock::named("block_request_status", None),
+            completion: WaitQueue::new(),
+            submitted: time::lightsaber_kernel_uptime_milliseconds(),
+            statistics: Spinlock::named("block_request_statistics", Vec::new())
+        })
+    }
+
+    pub fn read(sector: u64, sector_count: u64, sector_size: usize) -> Arc<Self> {
+        Self::new(BlockOperation::Read, sector, sector_count, vec![0; sector_count as usize * sector_size])
+    }
+
+    pub fn write(sector: u64, data: Vec<u8>, sector_size: usize) -> Arc<Self> {
+        let sector_count = (data.len() / sector_size) as u64;
+
+        Self::new(BlockOperation::Write, sector, sector_count, data)
+    }
+
+    pub fn flush() -> Arc<Self> {
+        Self::new(BlockOperation::Flush, 0, 0, Vec::new())
+    }
+
+    #[inline]
+    pub fn operation(&self) -> BlockOperation {
+        self.operation
+    }
+
+    #[inline]
+    pub fn sector(&self) -> u64 {
+        self.sector.load(Ordering::Relaxed)
+    }
+
+    #[inline]
+    pub fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    #[inline]
+    pub fn data(&self) -> SpinlockGuard<'_, Vec<u8>> {
+        self.data.lock()
+    }
+
+    // Partitions pass requests on to the whole disk after shifting them by their start.
+    pub(in crate::block) fn remap(&self, offset: u64) {
+        self.sector.fetch_add(offset, Ordering::Relaxed);
+    }
+
+    fn account(&self, statistics: &Arc<BlockStatistics>) {
+        statistics.in_flight.fetch_add(1, Ordering::Relaxed);
+        self.statistics.lock().push(statistics.clone());
+    }
+
+    pub fn complete(&self, status: Result<(), BlockError>) {
+        {
+            let mut current = self.status.lock();
+
+            if current.is_some() {
+                return;
+            }
+
+            *current = Some(status);
+        }
+
+        let latency = time::lightsaber_kernel_uptime_milliseconds().saturating_sub(self.submitted);
+
+        for statistics in self.statistics.lock().drain(..) {
+            statistics.record_completion(self.operation, self.sector_count, status, latency);
+        }
+
+        self.completion.wake_all();
+    }
+
+    #[inline]
+    pub fn status(&self) -> Option<Result<(), BlockError>> {
+        *self.status.lock()
+    }
+
+    pub fn wait(&self) -> Result<(), BlockError> {
+        self.completion.wait_until_uninterruptible(|| self.status.lock().is_some());
+
+        self.status().expect("The request completed without a status.")
+    }
+}
+
+pub trait BlockDevice: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn sector_size(&self) -> usize;
+
+    fn sector_count(&self) -> u64;
+
+    fn statistics(&self) -> &Arc<BlockStatistics>;
+
+    // Starts a request that has already been checked against the device; the driver completes it when done.
+    fn submit_request(&self, request: Arc<BlockRequest>);
+
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    // The whole disk, for devices that are only a part of one.
+    fn parent(&self) -> Option<&Arc<dyn BlockDevice>> {
+        None
+    }
+
+    // What the partition table says a partition holds; whole disks have none.
+    fn partition_kind(&self) -> Option<PartitionKind> {
+        None
+    }
+
+    fn size(&self) -> u64 {
+        self.sector_count() * self.sector_size() as u64
+    }
+
+    fn submit(&self, request: Arc<BlockRequest>) {
+        request.account(self.statistics());
+
+        let valid = match request.operation() {
+            BlockOperation::Flush => Ok(()),
+            BlockOperation::Write if self.is_read_only() => Err(BlockError::ReadOnly),
+            _ if request.data().len() as u64 != request.sector_count() * self.sector_size() as u64 => Err(BlockError::Misaligned),
+            _ if request.sector().checked_add(request.sector_count()).map_or(true, |end| end > self.sector_count()) => Err(BlockError::OutOfRange),
+            _ => Ok(())
+        };
+
+        match valid {
+            Ok(()) => self.submit_request(request),
+            Err(error) => request.complete(Err(error))
+        }
+    }
+
+    fn read_sectors(&self, sector: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        if buffer.len() % self.sector_size() != 0 {
+            return Err(BlockError::Misaligned);
+        }
+
+        let request = BlockRequest::read(sector, (buffer.len() / self.sector_size()) as u64, self.sector_size());
+
+        self.submit(request.clone());
+        request.wait()?;
+
+        buffer.copy_from_slice(&request.data());
+
+        Ok(())
+    }
+
+    fn write_sectors(&self, sector: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        if buffer.len() % self.sector_size() != 0 {
+            return Err(BlockError::Misaligned);
+        }
+
+        let request = BlockRequest::write(sector, buffer.to_vec(), self.sector_size());
+
+        self.submit(request.clone());
+        request.wait()
+    }
+
+    fn flush(&self) -> Result<(), BlockError> {
+        let request = BlockRequest::flush();
+
+        self.submit(request.clone());
+        request.wait()
+    }
+
+    // Reads at any byte offset, going through whole sectors underneath.
+    fn read_bytes(&self, offset: u64, buffer: &mut [u8]) -> Result<(), BlockError> {
+        let sector_size = self.sector_size();
+        let mut sector = vec![0; sector_size];
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let position = offset + done as u64;
+            let sector_offset = (position % sector_size as u64) as usize;
+            let chunk = (sector_size - sector_offset).min(buffer.len() - done);
+
+            // Whole sectors go straight into the caller's buffer.
+            if sector_offset == 0 && chunk == sector_size {
+                let whole = (buffer.len() - done) / sector_size * sector_size;
+
+                self.read_sectors(position / sector_size as u64, &mut buffer[done..done + whole])?;
+                done += whole;
+
+                continue;
+            }
+
+            self.read_sectors(position / sector_size as u64, &mut sector)?;
+            buffer[done..done + chunk].copy_from_slice(&sector[sector_offset..sector_offset + chunk]);
+
+            done += chunk;
+        }
+
+        Ok(())
+    }
+
+    fn write_bytes(&self, offset: u64, buffer: &[u8]) -> Result<(), BlockError> {
+        let sector_size = self.sector_size();
+        let mut sector = vec![0; sector_size];
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let position = offset + done as u64;
+            let sector_offset = (position % sector_size as u64) as usize;
+            let chunk = (sector_size - sector_offset).min(buffer.len() - done);
+
+            if sector_offset == 0 && chunk == sector_size {
+                let whole = (buffer.len() - done) / sector_size * sector_size;
+
+                self.write_sectors(position / sector_size as u64, &buffer[done..done + whole])?;
+                done += whole;
+
+                continue;
+            }
+
+            // Only partially overwritten sectors have to be read first.
+            self.read_sectors(position / sector_size as u64, &mut sector)?;
+            sector[sector_offset..sector_offset + chunk].copy_from_slice(&buffer[done..done + chunk]);
+            self.write_sectors(position / sector_size as u64, &sector)?;
+
+            done += chunk;
+        }
+
+        Ok(())
+    }
+}
+
+// Puts a cache in front of the device, registers it and every partition found on it.
+pub fn lightsaber_kernel_register_block_device(device: Arc<dyn BlockDevice>) -> Arc<dyn BlockDevice> {
+    log::info!("Registered block device {} ({} sectors of {} bytes).", device.name(), device.sector_count(), device.sector_size());
+
+    let disk: Arc<dyn BlockDevice> = Arc::new(BufferCache::new(device, cache::DEFAULT_CACHE_PAGES));
+    BLOCK_DEVICES.write().push(disk.clone());
+
+    match partition::lightsaber_kernel_scan_partitions(&disk) {
+        Ok(partitions) => {
+            for partition in partitions {
+                let label = partition.label().map(|label| format!(" \"{}\"", label)).unwrap_or_default();
+
+                log::info!("Found partition {}{} ({} sectors at {}, {}).", partition.name(), label, partition.sector_count(), partition.start(), partition.kind());
+
+                BLOCK_DEVICES.write().push(Arc::new(partition));
+            }
+        }
+        Err(error) => log::warn!("Failed to read the partition table of {}: {}", disk.name(), error)
+    }
+
+    disk
+}
+
+// Forgets a disk that went away, together with its partitions; anything still cached for it is lost.
+pub fn lightsaber_kernel_unregister_block_device(name: &str) {
+    BLOCK_DEVICES.write().retain(|device| device.name() != name && device.parent().map_or(true, |parent| parent.name() != name));
+
+    log::info!("Unregistered block device {}.", name);
+}
+
+pub fn lightsaber_kernel_find_block_device(name: &str) -> Option<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.read()
+        .iter()
+        .find(|device| device.name() == name)
+        .cloned()
+}
+
+pub fn lightsaber_kernel_block_devices() -> Vec<Arc<dyn BlockDevice>> {
+    BLOCK_DEVICES.read().clone()
+}
+
+pub fn lightsaber_kernel_sync_block_devices() {
+    for device in lightsaber_kernel_block_devices() {
+        if let Err(error) = device.flush() {
+            log::warn!("Failed to write back {}: {}", device.name(), error);
+        }
+    }
+}
+
+pub fn lightsaber_kernel_initialize_block_layer() {
+    scheduler::lightsaber_kernel_spawn("block_writeback", || loop {
+        time::lightsaber_kernel_sleep(WRITEBACK_INTERVAL_MILLISECONDS);
+        lightsaber_kernel_sync_block_devices();
+    });
+
+    log::info!("Initialized block layer.");
+}
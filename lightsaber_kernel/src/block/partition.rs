@@ -0,0 +1,536 @@
+use alloc::{
+    format,
+    string::String,
+    sync::Arc,
+    vec,
+    vec::Vec
+};
+
+use core::fmt;
+
+use crate::block::{
+    BlockDevice,
+    BlockError,
+    BlockRequest,
+    BlockStatistics
+};
+
+const MBR_SIGNATURE: u16 = 0xAA55;
+const MBR_PARTITION_TABLE_OFFSET: usize = 446;
+const MBR_PARTITION_ENTRY_SIZE: usize = 16;
+const MBR_PRIMARY_PARTITIONS: usize = 4;
+const MBR_TYPE_GPT_PROTECTIVE: u8 = 0xEE;
+const MBR_TYPE_EFI_SYSTEM: u8 = 0xEF;
+const MBR_EXTENDED_TYPES: [u8; 3] = [0x05, 0x0F, 0x85];
+const MAXIMUM_LOGICAL_PARTITIONS: usize = 128;
+
+const GPT_SIGNATURE: &[u8] = b"EFI PART";
+const GPT_HEADER_MINIMUM_SIZE: usize = 92;
+const GPT_ENTRY_MINIMUM_SIZE: usize = 128;
+const GPT_MAXIMUM_ENTRIES_SIZE: usize = 1 << 20;
+
+const GPT_EFI_SYSTEM_PARTITION: Guid = Guid([0x28, 0x73, 0x2A, 0xC1, 0x1F, 0xF8, 0xD2, 0x11, 0xBA, 0x4B, 0x00, 0xA0, 0xC9, 0x3E, 0xC9, 0x3B]);
+
+// (type, start, sector count) of one slot of an MBR or extended boot record.
+type BootRecordEntry = (u8, u64, u64);
+
+fn lightsaber_kernel_read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn lightsaber_kernel_read_u64(data: &[u8], offset: usize) -> u64 {
+    lightsaber_kernel_read_u32(data, offset) as u64 | (lightsaber_kernel_read_u32(data, offset + 4) as u64) << 32
+}
+
+fn lightsaber_kernel_crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| (crc >> 1) ^ (0xEDB8_8320 & (!(crc & 1)).wrapping_add(1)))
+    })
+}
+
+// Stored with the first three fields little-endian, as GPT does.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.0.iter().all(|byte| *byte == 0)
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let bytes = &self.0;
+
+        write!(
+            formatter,
+            "{:02X}{:02X}{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+            bytes[3], bytes[2], bytes[1], bytes[0], bytes[5], bytes[4], bytes[7], bytes[6],
+            bytes[8], bytes[9], bytes[10], bytes[11], bytes[12], bytes[13], bytes[14], bytes[15]
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PartitionKind {
+    Mbr(u8),
+
+    Gpt(Guid)
+}
+
+impl PartitionKind {
+    #[inline]
+    pub fn is_efi_system_partition(&self) -> bool {
+        match self {
+            Self::Mbr(partition_type) => *partition_type == MBR_TYPE_EFI_SYSTEM,
+            Self::Gpt(partition_type) => *partition_type == GPT_EFI_SYSTEM_PARTITION
+        }
+    }
+}
+
+impl fmt::Display for PartitionKind {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mbr(partition_type) => write!(formatter, "MBR type {:#04x}", partition_type),
+            Self::Gpt(partition_type) => write!(formatter, "GPT type {}", partition_type)
+        }
+    }
+}
+
+pub struct Partition {
+    disk: Arc<dyn BlockDevice>,
+    name: String,
+    start: u64,
+    sector_count: u64,
+    kind: PartitionKind,
+    label: Option<String>,
+    statistics: Arc<BlockStatistics>
+}
+
+impl Partition {
+    fn new(disk: &Arc<dyn BlockDevice>, number: u32, start: u64, sector_count: u64, kind: PartitionKind, label: Option<String>) -> Self {
+        // `sda` becomes `sda1`, while `nvme0n1` becomes `nvme0n1p1`.
+        let name = match disk.name().ends_with(|character: char| character.is_ascii_digit()) {
+            true => format!("{}p{}", disk.name(), number),
+            false => format!("{}{}", disk.name(), number)
+        };
+
+        Self {
+            disk: disk.clone(),
+            name,
+            start,
+            sector_count,
+            kind,
+            label,
+            statistics: Arc::new(BlockStatistics::default())
+        }
+    }
+
+    #[inline]
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    #[inline]
+    pub fn kind(&self) -> PartitionKind {
+        self.kind
+    }
+
+    #[inline]
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+}
+
+impl BlockDevice for Partition {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.disk.sector_size()
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.disk.is_read_only()
+    }
+
+    fn parent(&self) -> Option<&Arc<dyn BlockDevice>> {
+        Some(&self.disk)
+    }
+
+    fn partition_kind(&self) -> Option<PartitionKind> {
+        Some(self.kind)
+    }
+
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        request.remap(self.start);
+        self.disk.submit(request);
+    }
+}
+
+fn lightsaber_kernel_scan_gpt_header(disk: &Arc<dyn BlockDevice>, sector: u64) -> Result<Option<Vec<Partition>>, BlockError> {
+    let sector_size = disk.sector_size();
+
+    if sector_size < GPT_HEADER_MINIMUM_SIZE || sector >= disk.sector_count() {
+        return Ok(None);
+    }
+
+    let mut header = vec![0; sector_size];
+    disk.read_sectors(sector, &mut header)?;
+
+    let header_size = lightsaber_kernel_read_u32(&header, 12) as usize;
+
+    if &header[..8] != GPT_SIGNATURE || header_size < GPT_HEADER_MINIMUM_SIZE || header_size > sector_size || lightsaber_kernel_read_u64(&header, 24) != sector {
+        return Ok(None);
+    }
+
+    let checksum = lightsaber_kernel_read_u32(&header, 16);
+    header[16..20].copy_from_slice(&[0; 4]);
+
+    if lightsaber_kernel_crc32(&header[..header_size]) != checksum {
+        return Ok(None);
+    }
+
+    let entries_start = lightsaber_kernel_read_u64(&header, 72);
+    let entry_count = lightsaber_kernel_read_u32(&header, 80) as usize;
+    let entry_size = lightsaber_kernel_read_u32(&header, 84) as usize;
+    let entries_checksum = lightsaber_kernel_read_u32(&header, 88);
+    let entries_size = entry_count * entry_size;
+
+    if entry_size < GPT_ENTRY_MINIMUM_SIZE || !entry_size.is_power_of_two() || entries_size > GPT_MAXIMUM_ENTRIES_SIZE {
+        return Ok(None);
+    }
+
+    let mut entries = vec![0; (entries_size + sector_size - 1) / sector_size * sector_size];
+    disk.read_sectors(entries_start, &mut entries)?;
+
+    if lightsaber_kernel_crc32(&entries[..entries_size]) != entries_checksum {
+        return Ok(None);
+    }
+
+    let mut partitions = Vec::new();
+
+    for (index, entry) in entries[..entries_size].chunks(entry_size).enumerate() {
+        let mut type_guid = [0; 16];
+        type_guid.copy_from_slice(&entry[..16]);
+
+        let type_guid = Guid(type_guid);
+        let first = lightsaber_kernel_read_u64(entry, 32);
+        let last = lightsaber_kernel_read_u64(entry, 40);
+
+        if type_guid.is_zero() || last < first || last >= disk.sector_count() {
+            continue;
+        }
+
+        let name = entry[56..]
+            .chunks(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .take_while(|unit| *unit != 0)
+            .collect::<Vec<u16>>();
+
+        let label = Some(String::from_utf16_lossy(&name)).filter(|label| !label.is_empty());
+
+        partitions.push(Partition::new(disk, index as u32 + 1, first, last - first + 1, PartitionKind::Gpt(type_guid), label));
+    }
+
+    Ok(Some(partitions))
+}
+
+fn lightsaber_kernel_scan_gpt(disk: &Arc<dyn BlockDevice>) -> Result<Option<Vec<Partition>>, BlockError> {
+    if let Some(partitions) = lightsaber_kernel_scan_gpt_header(disk, 1)? {
+        return Ok(Some(partitions));
+    }
+
+    let backup = lightsaber_kernel_scan_gpt_header(disk, disk.sector_count().saturating_sub(1))?;
+
+    if backup.is_some() {
+        log::warn!("The primary GPT header of {} is damaged, using the backup.", disk.name());
+    }
+
+    Ok(backup)
+}
+
+// Yields every used slot of an MBR or extended boot record.
+fn lightsaber_kernel_read_boot_record(disk: &Arc<dyn BlockDevice>, sector: u64) -> Result<Option<Vec<BootRecordEntry>>, BlockError> {
+    let mut record = [0; 512];
+    disk.read_bytes(sector * disk.sector_size() as u64, &mut record)?;
+
+    if u16::from_le_bytes([record[510], record[511]]) != MBR_SIGNATURE {
+        return Ok(None);
+    }
+
+    Ok(Some((0..MBR_PRIMARY_PARTITIONS).map(|index| {
+        let entry = &record[MBR_PARTITION_TABLE_OFFSET + index * MBR_PARTITION_ENTRY_SIZE..];
+
+        (entry[4], lightsaber_kernel_read_u32(entry, 8) as u64, lightsaber_kernel_read_u32(entry, 12) as u64)
+    }).collect()))
+}
+
+pub fn lightsaber_kernel_scan_partitions(disk: &Arc<dyn BlockDevice>) -> Result<Vec<Partition>, BlockError> {
+    let entries = match lightsaber_kernel_read_boot_record(disk, 0)? {
+        Some(entries) => entries,
+        None => return Ok(Vec::new())
+    };
+
+    if entries.iter().any(|(partition_type, _, _)| *partition_type == MBR_TYPE_GPT_PROTECTIVE) {
+        if let Some(partitions) = lightsaber_kernel_scan_gpt(disk)? {
+            return Ok(partitions);
+        }
+    }
+
+    let in_range = |start: u64, count: u64| count != 0 && start.checked_add(count).map_or(false, |end| end <= disk.sector_count());
+    let mut partitions = Vec::new();
+    let mut extended = None;
+
+    for (index, (partition_type, start, count)) in entries.into_iter().enumerate() {
+        if partition_type == 0 || !in_range(start, count) {
+            continue;
+        }
+
+        match MBR_EXTENDED_TYPES.contains(&partition_type) {
+            true => extended = Some(start),
+            false => partitions.push(Partition::new(disk, index as u32 + 1, start, count, PartitionKind::Mbr(partition_type), None))
+        }
+    }
+
+    // Logical partitions form a chain of boot records inside the extended partition, numbered from 5.
+    if let Some(extended_start) = extended {
+        let mut record = extended_start;
+
+        for number in 5..5 + MAXIMUM_LOGICAL_PARTITIONS as u32 {
+            let entries = match lightsaber_kernel_read_boot_record(disk, record)? {
+                Some(entries) => entries,
+                None => break
+            };
+
+            let (partition_type, start, count) = entries[0];
+
+            if partition_type != 0 && in_range(record + start, count) {
+                partitions.push(Partition::new(disk, number, record + start, count, PartitionKind::Mbr(partition_type), None));
+            }
+
+            let (next_type, next_start, _) = entries[1];
+
+            if !MBR_EXTENDED_TYPES.contains(&next_type) || next_start == 0 {
+                break;
+            }
+
+            record = extended_start + next_start;
+        }
+    }
+
+    Ok(partitions)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    use crate::block::RamDisk;
+
+    const SECTOR_SIZE: usize = 512;
+    const GPT_TEST_SECTORS: usize = 256;
+    const GPT_TEST_ENTRIES: u32 = 128;
+    const GPT_TEST_ENTRIES_START: u64 = 2;
+
+    const GPT_LINUX_DATA: Guid = Guid([0xAF, 0x3D, 0xC6, 0x0F, 0x83, 0x84, 0x72, 0x47, 0x8E, 0x79, 0x3D, 0x69, 0xD8, 0x47, 0x7D, 0xE4]);
+
+    fn lightsaber_kernel_test_disk(name: &str, data: Vec<u8>) -> Arc<dyn BlockDevice> {
+        Arc::new(RamDisk::new(name, SECTOR_SIZE, data))
+    }
+
+    fn lightsaber_kernel_test_boot_record(disk: &mut [u8], sector: u64, entries: &[BootRecordEntry]) {
+        let record = &mut disk[sector as usize * SECTOR_SIZE..][..SECTOR_SIZE];
+
+        for (index, (partition_type, start, count)) in entries.iter().enumerate() {
+            let entry = &mut record[MBR_PARTITION_TABLE_OFFSET + index * MBR_PARTITION_ENTRY_SIZE..][..MBR_PARTITION_ENTRY_SIZE];
+
+            entry[4] = *partition_type;
+            entry[8..12].copy_from_slice(&(*start as u32).to_le_bytes());
+            entry[12..16].copy_from_slice(&(*count as u32).to_le_bytes());
+        }
+
+        record[510..].copy_from_slice(&MBR_SIGNATURE.to_le_bytes());
+    }
+
+    fn lightsaber_kernel_test_gpt_header(disk: &mut [u8], sector: u64, entries_checksum: u32) {
+        let header = &mut disk[sector as usize * SECTOR_SIZE..][..SECTOR_SIZE];
+
+        header[..8].copy_from_slice(GPT_SIGNATURE);
+        header[8..12].copy_from_slice(&0x0001_0000u32.to_le_bytes());
+        header[12..16].copy_from_slice(&(GPT_HEADER_MINIMUM_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&sector.to_le_bytes());
+        header[72..80].copy_from_slice(&GPT_TEST_ENTRIES_START.to_le_bytes());
+        header[80..84].copy_from_slice(&GPT_TEST_ENTRIES.to_le_bytes());
+        header[84..88].copy_from_slice(&(GPT_ENTRY_MINIMUM_SIZE as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&entries_checksum.to_le_bytes());
+
+        let checksum = lightsaber_kernel_crc32(&header[..GPT_HEADER_MINIMUM_SIZE]);
+        header[16..20].copy_from_slice(&checksum.to_le_bytes());
+    }
+
+    // A disk with a protective MBR, a primary and a backup GPT header, and one entry per (type, first, last, name).
+    fn lightsaber_kernel_test_gpt(partitions: &[(Guid, u64, u64, &str)]) -> Vec<u8> {
+        let mut disk = vec![0; GPT_TEST_SECTORS * SECTOR_SIZE];
+        lightsaber_kernel_test_boot_record(&mut disk, 0, &[(MBR_TYPE_GPT_PROTECTIVE, 1, GPT_TEST_SECTORS as u64 - 1)]);
+
+        let entries_offset = GPT_TEST_ENTRIES_START as usize * SECTOR_SIZE;
+        let entries_size = GPT_TEST_ENTRIES as usize * GPT_ENTRY_MINIMUM_SIZE;
+
+        for (index, (type_guid, first, last, name)) in partitions.iter().enumerate() {
+            let entry = &mut disk[entries_offset + index * GPT_ENTRY_MINIMUM_SIZE..][..GPT_ENTRY_MINIMUM_SIZE];
+
+            entry[..16].copy_from_slice(&type_guid.0);
+            entry[16] = index as u8 + 1;
+            entry[32..40].copy_from_slice(&first.to_le_bytes());
+            entry[40..48].copy_from_slice(&last.to_le_bytes());
+
+            for (unit, character) in name.encode_utf16().enumerate() {
+                entry[56 + unit * 2..58 + unit * 2].copy_from_slice(&character.to_le_bytes());
+            }
+        }
+
+        let entries_checksum = lightsaber_kernel_crc32(&disk[entries_offset..entries_offset + entries_size]);
+
+        lightsaber_kernel_test_gpt_header(&mut disk, 1, entries_checksum);
+        lightsaber_kernel_test_gpt_header(&mut disk, GPT_TEST_SECTORS as u64 - 1, entries_checksum);
+
+        disk
+    }
+
+    fn lightsaber_kernel_test_layout(partitions: &[Partition]) -> Vec<(String, u64, u64, PartitionKind)> {
+        partitions
+            .iter()
+            .map(|partition| (partition.name().to_string(), partition.start(), partition.sector_count(), partition.kind()))
+            .collect()
+    }
+
+    #[test_case]
+    fn partition_checksums_and_guids_match_their_specifications() {
+        assert_eq!(lightsaber_kernel_crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(format!("{}", GPT_EFI_SYSTEM_PARTITION), "C12A7328-F81F-11D2-BA4B-00A0C93EC93B");
+        assert_eq!(format!("{}", GPT_LINUX_DATA), "0FC63DAF-8483-4772-8E79-3D69D8477DE4");
+    }
+
+    #[test_case]
+    fn partition_scans_mbr_primaries_and_logicals() {
+        let mut data = vec![0; 4096 * SECTOR_SIZE];
+
+        // The last primary runs past the end of the disk and is left out.
+        lightsaber_kernel_test_boot_record(&mut data, 0, &[(0x0C, 64, 512), (MBR_TYPE_EFI_SYSTEM, 600, 100), (0x05, 1024, 2048), (0x83, 4000, 1000)]);
+
+        // Logical partitions start relative to their own record, the next record relative to the extended partition.
+        lightsaber_kernel_test_boot_record(&mut data, 1024, &[(0x83, 1, 500), (0x05, 600, 200)]);
+        lightsaber_kernel_test_boot_record(&mut data, 1624, &[(0x82, 2, 100)]);
+
+        data[64 * SECTOR_SIZE..65 * SECTOR_SIZE].copy_from_slice(&[0x5A; SECTOR_SIZE]);
+
+        let disk = lightsaber_kernel_test_disk("mbr_test", data);
+        let partitions = lightsaber_kernel_scan_partitions(&disk).unwrap();
+
+        assert_eq!(lightsaber_kernel_test_layout(&partitions), [
+            ("mbr_test1".to_string(), 64, 512, PartitionKind::Mbr(0x0C)),
+            ("mbr_test2".to_string(), 600, 100, PartitionKind::Mbr(MBR_TYPE_EFI_SYSTEM)),
+            ("mbr_test5".to_string(), 1025, 500, PartitionKind::Mbr(0x83)),
+            ("mbr_test6".to_string(), 1626, 100, PartitionKind::Mbr(0x82))
+        ]);
+
+        assert!(!partitions[0].kind().is_efi_system_partition());
+        assert!(partitions[1].kind().is_efi_system_partition());
+
+        // Requests to a partition land at its start on the disk, and stop at its end.
+        let mut sector = [0; SECTOR_SIZE];
+
+        assert_eq!(partitions[0].read_sectors(0, &mut sector), Ok(()));
+        assert_eq!(sector, [0x5A; SECTOR_SIZE]);
+        assert_eq!(partitions[0].read_sectors(512, &mut sector), Err(BlockError::OutOfRange));
+    }
+
+    #[test_case]
+    fn partition_scans_a_gpt_behind_a_protective_mbr() {
+        let data = lightsaber_kernel_test_gpt(&[
+            (GPT_EFI_SYSTEM_PARTITION, 34, 99, "EFI system"),
+            (Guid([0; 16]), 100, 109, "unused"),
+            (GPT_LINUX_DATA, 110, 254, ""),
+            (GPT_LINUX_DATA, 200, 300, "past the end")
+        ]);
+
+        let disk = lightsaber_kernel_test_disk("nvme0n1", data);
+        let partitions = lightsaber_kernel_scan_partitions(&disk).unwrap();
+
+        // Entries keep their slot number, empty ones included.
+        assert_eq!(lightsaber_kernel_test_layout(&partitions), [
+            ("nvme0n1p1".to_string(), 34, 66, PartitionKind::Gpt(GPT_EFI_SYSTEM_PARTITION)),
+            ("nvme0n1p3".to_string(), 110, 145, PartitionKind::Gpt(GPT_LINUX_DATA))
+        ]);
+
+        assert_eq!(partitions[0].label(), Some("EFI system"));
+        assert_eq!(partitions[1].label(), None);
+        assert!(partitions[0].kind().is_efi_system_partition());
+        assert!(!partitions[1].kind().is_efi_system_partition());
+    }
+
+    #[test_case]
+    fn partition_falls_back_to_the_backup_gpt_header() {
+        let mut data = lightsaber_kernel_test_gpt(&[(GPT_LINUX_DATA, 34, 254, "root")]);
+        data[SECTOR_SIZE + 32] ^= 0xFF;
+
+        let disk = lightsaber_kernel_test_disk("backup_test", data.clone());
+
+        assert_eq!(lightsaber_kernel_test_layout(&lightsaber_kernel_scan_partitions(&disk).unwrap()), [
+            ("backup_test1".to_string(), 34, 221, PartitionKind::Gpt(GPT_LINUX_DATA))
+        ]);
+
+        // With both headers damaged, all that is left is the protective entry of the MBR.
+        data[(GPT_TEST_SECTORS - 1) * SECTOR_SIZE + 32] ^= 0xFF;
+
+        let disk = lightsaber_kernel_test_disk("backup_test", data);
+
+        assert_eq!(lightsaber_kernel_test_layout(&lightsaber_kernel_scan_partitions(&disk).unwrap()), [
+            ("backup_test1".to_string(), 1, GPT_TEST_SECTORS as u64 - 1, PartitionKind::Mbr(MBR_TYPE_GPT_PROTECTIVE))
+        ]);
+    }
+
+    #[test_case]
+    fn partition_rejects_gpt_entries_with_a_bad_checksum() {
+        let mut data = lightsaber_kernel_test_gpt(&[(GPT_LINUX_DATA, 34, 254, "root")]);
+
+        // Both headers share the entry array, so neither can be used.
+        data[GPT_TEST_ENTRIES_START as usize * SECTOR_SIZE + 32] ^= 0xFF;
+
+        let disk = lightsaber_kernel_test_disk("crc_test", data);
+
+        assert_eq!(lightsaber_kernel_test_layout(&lightsaber_kernel_scan_partitions(&disk).unwrap()), [
+            ("crc_test1".to_string(), 1, GPT_TEST_SECTORS as u64 - 1, PartitionKind::Mbr(MBR_TYPE_GPT_PROTECTIVE))
+        ]);
+    }
+
+    #[test_case]
+    fn partition_handles_truncated_and_blank_disks() {
+        let blank = lightsaber_kernel_test_disk("blank_test", vec![0; 16 * SECTOR_SIZE]);
+        let short = lightsaber_kernel_test_disk("short_test", vec![0; SECTOR_SIZE / 2]);
+
+        assert_eq!(lightsaber_kernel_scan_partitions(&blank).map(|partitions| partitions.len()), Ok(0));
+        assert_eq!(lightsaber_kernel_scan_partitions(&short).err(), Some(BlockError::OutOfRange));
+
+        // A GPT disk cut short loses its entry array along with the backup header.
+        let mut data = lightsaber_kernel_test_gpt(&[(GPT_LINUX_DATA, 34, 254, "root")]);
+        data.truncate(GPT_TEST_ENTRIES_START as usize * SECTOR_SIZE + SECTOR_SIZE);
+
+        let truncated = lightsaber_kernel_test_disk("truncated_test", data);
+
+        assert_eq!(lightsaber_kernel_scan_partitions(&truncated).err(), Some(BlockError::OutOfRange));
+    }
+}
@@ -0,0 +1,70 @@
+use alloc::{
+    collections::VecDeque,
+    sync::Arc
+};
+
+use core::sync::atomic::{
+    AtomicUsize,
+    Ordering
+};
+
+use crate::{
+    block::{
+        BlockError,
+        BlockRequest
+    },
+    sync::Spinlock
+};
+
+// Holds requests until the hardware has a free slot; drivers call `next` whenever a slot frees up.
+pub struct RequestQueue {
+    depth: usize,
+    pending: Spinlock<VecDeque<Arc<BlockRequest>>>,
+    in_flight: AtomicUsize
+}
+
+impl RequestQueue {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            depth,
+            pending: Spinlock::named("request_queue", VecDeque::new()),
+            in_flight: AtomicUsize::new(0)
+        }
+    }
+
+    pub fn push(&self, request: Arc<BlockRequest>) {
+        self.pending.lock().push_back(request);
+    }
+
+    #[inline]
+    pub fn peek(&self) -> Option<Arc<BlockRequest>> {
+        self.pending.lock().front().cloned()
+    }
+
+    pub fn next(&self) -> Option<Arc<BlockRequest>> {
+        let mut pending = self.pending.lock();
+
+        if self.in_flight.load(Ordering::Acquire) >= self.depth {
+            return None;
+        }
+
+        let request = pending.pop_front()?;
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+
+        Some(request)
+    }
+
+    // Completes a request taken with `next` and frees its slot.
+    pub fn finish(&self, request: &BlockRequest, status: Result<(), BlockError>) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+        request.complete(status);
+    }
+
+    pub fn fail_all(&self, error: BlockError) {
+        let requests = self.pending.lock().drain(..).collect::<VecDeque<Arc<BlockRequest>>>();
+
+        for request in requests {
+            request.complete(Err(error));
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use alloc::{
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+
+use crate::{
+    block::{
+        BlockDevice,
+        BlockOperation,
+        BlockRequest,
+        BlockStatistics
+    },
+    sync::Spinlock
+};
+
+// Stands in for a disk in the file system and partition tests.
+pub struct RamDisk {
+    name: String,
+    sector_size: usize,
+    data: Spinlock<Vec<u8>>,
+    statistics: Arc<BlockStatistics>
+}
+
+impl RamDisk {
+    pub fn new(name: &str, sector_size: usize, data: Vec<u8>) -> Self {
+        Self {
+            name: name.to_string(),
+            sector_size,
+            data: Spinlock::named("ram_disk", data),
+            statistics: Arc::new(BlockStatistics::default())
+        }
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        (self.data.lock().len() / self.sector_size) as u64
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        let start = request.sector() as usize * self.sector_size;
+
+        match request.operation() {
+            BlockOperation::Read => {
+                let mut buffer = request.data();
+                let length = buffer.len();
+
+                buffer.copy_from_slice(&self.data.lock()[start..start + length]);
+            }
+            BlockOperation::Write => {
+                let buffer = request.data();
+
+                self.data.lock()[start..start + buffer.len()].copy_from_slice(&buffer);
+            }
+            BlockOperation::Flush => { }
+        }
+
+        request.complete(Ok(()));
+    }
+}
@@ -0,0 +1,246 @@
+use core::{
+    fmt,
+    mem,
+    slice
+};
+
+use log::LevelFilter;
+
+use spin::Once;
+
+use lightsaber_bootloader::config;
+
+static COMMAND_LINE: Once<&'static str> = Once::new();
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CommandLineError {
+    UnknownOption(&'static str),
+
+    InvalidValue(&'static str),
+
+    Repeated(&'static str)
+}
+
+impl fmt::Display for CommandLineError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownOption(name) => write!(formatter, "Unknown command line option `{}`.", name),
+            Self::InvalidValue(name) => write!(formatter, "Invalid value for command line option `{}`.", name),
+            Self::Repeated(name) => write!(formatter, "Command line option `{}` is given more than once; the first is used.", name)
+        }
+    }
+}
+
+// How an option's text becomes a value; `None` is an option given without `=`, such as `nosmp`.
+pub trait ParameterValue: Sized {
+    fn parse(value: Option<&'static str>) -> Option<Self>;
+}
+
+impl ParameterValue for bool {
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        match value {
+            None | Some("1") | Some("on") | Some("yes") | Some("true") => Some(true),
+            Some("0") | Some("off") | Some("no") | Some("false") => Some(false),
+            Some(_) => None
+        }
+    }
+}
+
+impl ParameterValue for &'static str {
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        value.filter(|value| !value.is_empty())
+    }
+}
+
+impl ParameterValue for LevelFilter {
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        // Spelled the same as `log_level` in the boot configuration.
+        config::lightsaber_parse_log_level(value?)
+    }
+}
+
+// Declared with `param!`, set at most once while the command line is parsed, and read wherever the option matters.
+pub struct Parameter<T> {
+    name: &'static str,
+    value: Once<T>
+}
+
+impl<T> Parameter<T> {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            value: Once::new()
+        }
+    }
+}
+
+impl<T: Copy> Parameter<T> {
+    // `None` if the option was not given.
+    pub fn get(&self) -> Option<T> {
+        self.value.get().copied()
+    }
+}
+
+pub trait CommandLineParameter: Sync {
+    fn name(&self) -> &'static str;
+
+    fn accepts(&self, value: Option<&'static str>) -> bool;
+
+    fn set(&self, value: Option<&'static str>);
+}
+
+impl<T: ParameterValue + Copy + Send + Sync> CommandLineParameter for Parameter<T> {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn accepts(&self, value: Option<&'static str>) -> bool {
+        T::parse(value).is_some()
+    }
+
+    fn set(&self, value: Option<&'static str>) {
+        if let Some(value) = T::parse(value) {
+            self.value.call_once(|| value);
+        }
+    }
+}
+
+// `param!(pub NAME: Type = "option");` declares a parameter next to the code it configures. The linker gathers
+// every declaration's entry in the `lightsaber_parameters` section, so the parser needs no list of its own.
+pub macro param {
+    ($visibility:vis $name:ident: $type:ty = $option:literal) => {
+        $visibility static $name: $crate::cmdline::Parameter<$type> = $crate::cmdline::Parameter::new($option);
+
+        const _: () = {
+            #[link_section = "lightsaber_parameters"]
+            #[used]
+            static REGISTRATION: &dyn $crate::cmdline::CommandLineParameter = &$name;
+        };
+    }
+}
+
+extern "C" {
+    static __start_lightsaber_parameters: u8;
+    static __stop_lightsaber_parameters: u8;
+}
+
+// Every option the kernel understands; anything else on the command line is reported and ignored.
+fn lightsaber_kernel_parameters() -> &'static [&'static dyn CommandLineParameter] {
+    // The linker defines both symbols around the section, which holds nothing but `param!` entries.
+    unsafe {
+        let start = &__start_lightsaber_parameters as *const u8;
+        let end = &__stop_lightsaber_parameters as *const u8;
+
+        slice::from_raw_parts(start as *const &'static dyn CommandLineParameter, (end as usize - start as usize) / mem::size_of::<&dyn CommandLineParameter>())
+    }
+}
+
+// Options are separated by whitespace, and are either `name` or `name=value`.
+fn lightsaber_kernel_options(command_line: &'static str) -> impl Iterator<Item = (&'static str, Option<&'static str>)> {
+    command_line.split_whitespace().map(|option| match option.find('=') {
+        Some(equals) => (&option[..equals], Some(&option[equals + 1..])),
+        None => (option, None)
+    })
+}
+
+// The first valid value of an option is the one used, so only a later valid one counts as repeated.
+fn lightsaber_kernel_check_option(command_line: &'static str, index: usize, name: &'static str, value: Option<&'static str>) -> Result<&'static dyn CommandLineParameter, CommandLineError> {
+    let parameter = lightsaber_kernel_parameters()
+        .iter()
+        .copied()
+        .find(|parameter| parameter.name() == name)
+        .ok_or(CommandLineError::UnknownOption(name))?;
+
+    if !parameter.accepts(value) {
+        return Err(CommandLineError::InvalidValue(name));
+    }
+
+    let repeated = lightsaber_kernel_options(command_line)
+        .take(index)
+        .any(|(earlier, value)| earlier == name && parameter.accepts(value));
+
+    match repeated {
+        true => Err(CommandLineError::Repeated(name)),
+        false => Ok(parameter)
+    }
+}
+
+// Runs before the serial line and logger are set up, as both take options; problems are reported once they are.
+pub fn lightsaber_kernel_parse_command_line(command_line: &'static str) {
+    let command_line = *COMMAND_LINE.call_once(|| command_line);
+
+    for (index, (name, value)) in lightsaber_kernel_options(command_line).enumerate() {
+        if let Ok(parameter) = lightsaber_kernel_check_option(command_line, index, name, value) {
+            parameter.set(value);
+        }
+    }
+}
+
+pub fn lightsaber_kernel_report_command_line() {
+    let command_line = lightsaber_kernel_command_line();
+
+    log::info!("Kernel command line: `{}`.", command_line);
+
+    for (index, (name, value)) in lightsaber_kernel_options(command_line).enumerate() {
+        if let Err(error) = lightsaber_kernel_check_option(command_line, index, name, value) {
+            log::warn!("{}", error);
+        }
+    }
+}
+
+pub fn lightsaber_kernel_command_line() -> &'static str {
+    COMMAND_LINE.get().copied().unwrap_or("")
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test_case]
+    fn command_line_splits_options_and_values() {
+        let mut options = lightsaber_kernel_options("  loglevel=debug nosmp\tinit=/bin/sh=x empty= ");
+
+        assert_eq!(options.next(), Some(("loglevel", Some("debug"))));
+        assert_eq!(options.next(), Some(("nosmp", None)));
+        assert_eq!(options.next(), Some(("init", Some("/bin/sh=x"))));
+        assert_eq!(options.next(), Some(("empty", Some(""))));
+        assert_eq!(options.next(), None);
+    }
+
+    #[test_case]
+    fn command_line_parses_typed_values() {
+        assert_eq!(bool::parse(None), Some(true));
+        assert_eq!(bool::parse(Some("off")), Some(false));
+        assert_eq!(bool::parse(Some("maybe")), None);
+        assert_eq!(<&str>::parse(Some("")), None);
+        assert_eq!(LevelFilter::parse(Some("trace")), Some(LevelFilter::Trace));
+        assert_eq!(LevelFilter::parse(None), None);
+    }
+
+    #[test_case]
+    fn command_line_finds_every_declared_parameter() {
+        let mut names = lightsaber_kernel_parameters().iter().map(|parameter| parameter.name()).collect::<Vec<_>>();
+        names.sort_unstable();
+
+        assert_eq!(names, ["init", "loglevel", "nosmp", "root", "serial"]);
+    }
+
+    #[test_case]
+    fn command_line_reports_unknown_invalid_and_repeated_options() {
+        let command_line = "quiet loglevel=loud loglevel=warn loglevel=error";
+
+        let errors = lightsaber_kernel_options(command_line)
+            .enumerate()
+            .map(|(index, (name, value))| lightsaber_kernel_check_option(command_line, index, name, value).err())
+            .collect::<Vec<_>>();
+
+        assert_eq!(errors, [
+            Some(CommandLineError::UnknownOption("quiet")),
+            Some(CommandLineError::InvalidValue("loglevel")),
+            None,
+            Some(CommandLineError::Repeated("loglevel"))
+        ]);
+    }
+}
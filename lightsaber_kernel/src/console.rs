@@ -0,0 +1,105 @@
+use alloc::string::String;
+
+use core::fmt;
+
+use crate::{
+    drivers::serial,
+    renderer,
+    sync::{
+        Interrupted,
+        Spinlock,
+        WaitQueue
+    }
+};
+
+const CONSOLE_INPUT_CAPACITY: usize = 1024;
+
+// Bytes typed on any input device, waiting for whoever reads the console.
+struct ConsoleInput {
+    buffer: [u8; CONSOLE_INPUT_CAPACITY],
+    start: usize,
+    length: usize
+}
+
+impl ConsoleInput {
+    const fn new() -> Self {
+        Self {
+            buffer: [0; CONSOLE_INPUT_CAPACITY],
+            start: 0,
+            length: 0
+        }
+    }
+
+    fn push(&mut self, byte: u8) -> bool {
+        if self.length == CONSOLE_INPUT_CAPACITY {
+            return false;
+        }
+
+        self.buffer[(self.start + self.length) % CONSOLE_INPUT_CAPACITY] = byte;
+        self.length += 1;
+
+        true
+    }
+
+    fn pop_into(&mut self, buffer: &mut [u8]) -> usize {
+        let count = self.length.min(buffer.len());
+
+        for byte in buffer.iter_mut().take(count) {
+            *byte = self.buffer[self.start];
+            self.start = (self.start + 1) % CONSOLE_INPUT_CAPACITY;
+        }
+
+        self.length -= count;
+        count
+    }
+}
+
+static CONSOLE_INPUT: Spinlock<ConsoleInput> = Spinlock::named("console_input", ConsoleInput::new());
+static CONSOLE_READERS: WaitQueue = WaitQueue::new();
+
+// Called from interrupt handlers; what does not fit is dropped, as with keys typed into a full buffer.
+pub fn lightsaber_kernel_console_input(bytes: &[u8]) {
+    {
+        let mut input = CONSOLE_INPUT.lock();
+
+        for byte in bytes {
+            if !input.push(*byte) {
+                break;
+            }
+        }
+    }
+
+    CONSOLE_READERS.wake_all();
+}
+
+// Blocks until something was typed and takes as much of it as fits.
+pub fn lightsaber_kernel_console_read(buffer: &mut [u8]) -> Result<usize, Interrupted> {
+    if buffer.is_empty() {
+        return Ok(0);
+    }
+
+    let mut read = 0;
+
+    CONSOLE_READERS.wait_until(|| {
+        read = CONSOLE_INPUT.lock().pop_into(buffer);
+        read > 0
+    })?;
+
+    Ok(read)
+}
+
+// Output goes to the framebuffer and the serial line alike.
+pub fn lightsaber_kernel_console_write(bytes: &[u8]) {
+    renderer::print!("{}", String::from_utf8_lossy(bytes));
+    serial::lightsaber_kernel_serial_write(bytes);
+}
+
+pub struct ConsoleWriter;
+
+impl fmt::Write for ConsoleWriter {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        lightsaber_kernel_console_write(string.as_bytes());
+
+        Ok(())
+    }
+}
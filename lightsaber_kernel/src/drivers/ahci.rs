@@ -0,0 +1,928 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    sync::atomic::{
+        fence,
+        AtomicBool,
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use spin::Once;
+
+use x86_64::PhysAddr;
+
+use crate::{
+    architecture::interrupts::{
+        self,
+        irq
+    },
+    block::{
+        self,
+        BlockDevice,
+        BlockError,
+        BlockOperation,
+        BlockRequest,
+        BlockStatistics,
+        RequestQueue
+    },
+    memory::{
+        dma::{
+            self,
+            DmaBuffer
+        },
+        mmio::MmioRegion
+    },
+    pci::{
+        self,
+        PciDevice,
+        PciDeviceId,
+        PciDriver,
+        PciError
+    },
+    scheduler,
+    sync::{
+        Spinlock,
+        WaitQueue
+    },
+    time
+};
+
+const AHCI_BASE_BAR: usize = 5;
+
+const HBA_CAPABILITIES: u64 = 0x00;
+const HBA_GLOBAL_CONTROL: u64 = 0x04;
+const HBA_INTERRUPT_STATUS: u64 = 0x08;
+const HBA_PORTS_IMPLEMENTED: u64 = 0x0C;
+const HBA_VERSION: u64 = 0x10;
+const HBA_CAPABILITIES_2: u64 = 0x24;
+const HBA_BIOS_HANDOFF: u64 = 0x28;
+const HBA_PORT_BASE: u64 = 0x100;
+const HBA_PORT_SIZE: u64 = 0x80;
+
+const HBA_CAPABILITIES_SLOTS_SHIFT: u32 = 8;
+const HBA_CAPABILITIES_SLOTS: u32 = 0x1F;
+const HBA_CAPABILITIES_STAGGERED_SPIN_UP: u32 = 1 << 27;
+const HBA_CAPABILITIES_NCQ: u32 = 1 << 30;
+const HBA_CAPABILITIES_64_BIT: u32 = 1 << 31;
+const HBA_CAPABILITIES_2_BIOS_HANDOFF: u32 = 1 << 0;
+
+const HBA_GLOBAL_CONTROL_RESET: u32 = 1 << 0;
+const HBA_GLOBAL_CONTROL_INTERRUPT_ENABLE: u32 = 1 << 1;
+const HBA_GLOBAL_CONTROL_AHCI_ENABLE: u32 = 1 << 31;
+
+const HBA_BIOS_HANDOFF_BIOS_OWNED: u32 = 1 << 0;
+const HBA_BIOS_HANDOFF_OS_OWNED: u32 = 1 << 1;
+
+const PORT_COMMAND_LIST_BASE: u64 = 0x00;
+const PORT_COMMAND_LIST_BASE_HIGH: u64 = 0x04;
+const PORT_FIS_BASE: u64 = 0x08;
+const PORT_FIS_BASE_HIGH: u64 = 0x0C;
+const PORT_INTERRUPT_STATUS: u64 = 0x10;
+const PORT_INTERRUPT_ENABLE: u64 = 0x14;
+const PORT_COMMAND: u64 = 0x18;
+const PORT_TASK_FILE: u64 = 0x20;
+const PORT_SIGNATURE: u64 = 0x24;
+const PORT_SATA_STATUS: u64 = 0x28;
+const PORT_SATA_ERROR: u64 = 0x30;
+const PORT_SATA_ACTIVE: u64 = 0x34;
+const PORT_COMMAND_ISSUE: u64 = 0x38;
+
+const PORT_COMMAND_START: u32 = 1 << 0;
+const PORT_COMMAND_SPIN_UP: u32 = 1 << 1;
+const PORT_COMMAND_POWER_ON: u32 = 1 << 2;
+const PORT_COMMAND_FIS_RECEIVE_ENABLE: u32 = 1 << 4;
+const PORT_COMMAND_FIS_RECEIVE_RUNNING: u32 = 1 << 14;
+const PORT_COMMAND_LIST_RUNNING: u32 = 1 << 15;
+
+const PORT_INTERRUPT_DEVICE_TO_HOST: u32 = 1 << 0;
+const PORT_INTERRUPT_PIO_SETUP: u32 = 1 << 1;
+const PORT_INTERRUPT_DMA_SETUP: u32 = 1 << 2;
+const PORT_INTERRUPT_SET_DEVICE_BITS: u32 = 1 << 3;
+const PORT_INTERRUPT_CONNECT_CHANGE: u32 = 1 << 6;
+const PORT_INTERRUPT_PHY_READY_CHANGE: u32 = 1 << 22;
+const PORT_INTERRUPT_INTERFACE_FATAL: u32 = 1 << 27;
+const PORT_INTERRUPT_HOST_BUS_DATA: u32 = 1 << 28;
+const PORT_INTERRUPT_HOST_BUS_FATAL: u32 = 1 << 29;
+const PORT_INTERRUPT_TASK_FILE_ERROR: u32 = 1 << 30;
+
+const PORT_INTERRUPT_HOTPLUG: u32 = PORT_INTERRUPT_CONNECT_CHANGE | PORT_INTERRUPT_PHY_READY_CHANGE;
+const PORT_INTERRUPT_ERRORS: u32 = PORT_INTERRUPT_INTERFACE_FATAL | PORT_INTERRUPT_HOST_BUS_DATA | PORT_INTERRUPT_HOST_BUS_FATAL | PORT_INTERRUPT_TASK_FILE_ERROR;
+const PORT_INTERRUPTS: u32 = PORT_INTERRUPT_DEVICE_TO_HOST | PORT_INTERRUPT_PIO_SETUP | PORT_INTERRUPT_DMA_SETUP | PORT_INTERRUPT_SET_DEVICE_BITS | PORT_INTERRUPT_HOTPLUG | PORT_INTERRUPT_ERRORS;
+
+const PORT_TASK_FILE_ERROR: u32 = 1 << 0;
+const PORT_TASK_FILE_DATA_REQUEST: u32 = 1 << 3;
+const PORT_TASK_FILE_BUSY: u32 = 1 << 7;
+
+const PORT_SATA_STATUS_DETECTION: u32 = 0x0F;
+const PORT_SATA_STATUS_DEVICE_PRESENT: u32 = 0x03;
+
+const SATA_SIGNATURE_ATA: u32 = 0x0000_0101;
+const SATA_SIGNATURE_ATAPI: u32 = 0xEB14_0101;
+
+// Per port: the command list, the received FIS area, then one command table per slot.
+const COMMAND_HEADER_SIZE: usize = 32;
+const RECEIVED_FIS_OFFSET: usize = 1024;
+const COMMAND_TABLE_OFFSET: usize = 4096;
+const COMMAND_TABLE_SIZE: usize = 512;
+const PRDT_OFFSET: usize = 0x80;
+const PRD_SIZE: usize = 16;
+const MAXIMUM_PRDS: usize = (COMMAND_TABLE_SIZE - PRDT_OFFSET) / PRD_SIZE;
+const PRD_MAXIMUM_BYTES: usize = 4 * 1024 * 1024;
+
+const COMMAND_HEADER_WRITE: u32 = 1 << 6;
+
+const FIS_TYPE_REGISTER_HOST_TO_DEVICE: u8 = 0x27;
+const FIS_COMMAND: u8 = 1 << 7;
+const FIS_SIZE: usize = 20;
+
+const ATA_DEVICE_LBA: u8 = 1 << 6;
+
+const ATA_READ_DMA: u8 = 0xC8;
+const ATA_WRITE_DMA: u8 = 0xCA;
+const ATA_READ_DMA_EXT: u8 = 0x25;
+const ATA_WRITE_DMA_EXT: u8 = 0x35;
+const ATA_READ_FPDMA_QUEUED: u8 = 0x60;
+const ATA_WRITE_FPDMA_QUEUED: u8 = 0x61;
+const ATA_FLUSH_CACHE: u8 = 0xE7;
+const ATA_FLUSH_CACHE_EXT: u8 = 0xEA;
+const ATA_IDENTIFY: u8 = 0xEC;
+
+const ATA_IDENTIFY_SIZE: usize = 512;
+const ATA_SECTOR_SIZE: usize = 512;
+
+// Roughly a second of register reads; the timer is not necessarily running when ports are brought up.
+const AHCI_SPIN_LIMIT: usize = 1_000_000;
+const HOTPLUG_POLL_MILLISECONDS: u64 = 1000;
+
+const AHCI_IDS: &[PciDeviceId] = &[
+    PciDeviceId::class(0x01, 0x06, Some(0x01))
+];
+
+static AHCI_DRIVER: PciDriver = PciDriver {
+    name: "ahci",
+    ids: AHCI_IDS,
+    probe: lightsaber_kernel_probe_ahci
+};
+
+static AHCI_CONTROLLERS: Spinlock<BTreeMap<usize, Arc<AhciController>>> = Spinlock::named("ahci_controllers", BTreeMap::new());
+static NEXT_CONTROLLER_INDEX: AtomicUsize = AtomicUsize::new(0);
+static NEXT_DISK_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+static AHCI_HOTPLUG_WORKER: Once<()> = Once::new();
+static AHCI_HOTPLUG: WaitQueue = WaitQueue::new();
+static AHCI_HOTPLUG_PENDING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum AhciInterrupt {
+    Msi,
+
+    Legacy,
+
+    Polled
+}
+
+struct AhciCommand {
+    request: Arc<BlockRequest>,
+    queued: bool
+}
+
+pub struct AhciPort {
+    number: usize,
+    hba: MmioRegion,
+    registers: MmioRegion,
+    memory: DmaBuffer,
+    slot_count: usize,
+    ncq_supported: bool,
+    polled: bool,
+    // Indexed by command slot, which is also the NCQ tag.
+    in_flight: Spinlock<Vec<Option<AhciCommand>>>,
+    disk: Spinlock<Option<Arc<AhciDisk>>>,
+    changed: AtomicBool
+}
+
+impl AhciPort {
+    #[inline]
+    fn read(&self, register: u64) -> u32 {
+        self.registers.read_u32(register)
+    }
+
+    #[inline]
+    fn write(&self, register: u64, value: u32) {
+        self.registers.write_u32(register, value)
+    }
+
+    fn wait_for(&self, register: u64, mask: u32, value: u32) -> bool {
+        for _ in 0..AHCI_SPIN_LIMIT {
+            if self.read(register) & mask == value {
+                return true;
+            }
+
+            hint::spin_loop();
+        }
+
+        false
+    }
+
+    #[inline]
+    pub fn device_present(&self) -> bool {
+        self.read(PORT_SATA_STATUS) & PORT_SATA_STATUS_DETECTION == PORT_SATA_STATUS_DEVICE_PRESENT
+    }
+
+    fn stop_engine(&self) -> bool {
+        self.write(PORT_COMMAND, self.read(PORT_COMMAND) & !PORT_COMMAND_START);
+
+        if !self.wait_for(PORT_COMMAND, PORT_COMMAND_LIST_RUNNING, 0) {
+            return false;
+        }
+
+        self.write(PORT_COMMAND, self.read(PORT_COMMAND) & !PORT_COMMAND_FIS_RECEIVE_ENABLE);
+        self.wait_for(PORT_COMMAND, PORT_COMMAND_FIS_RECEIVE_RUNNING, 0)
+    }
+
+    fn start_engine(&self) -> bool {
+        if !self.wait_for(PORT_COMMAND, PORT_COMMAND_LIST_RUNNING, 0) || !self.wait_for(PORT_TASK_FILE, PORT_TASK_FILE_BUSY | PORT_TASK_FILE_DATA_REQUEST, 0) {
+            return false;
+        }
+
+        self.write(PORT_COMMAND, self.read(PORT_COMMAND) | PORT_COMMAND_FIS_RECEIVE_ENABLE);
+        self.write(PORT_COMMAND, self.read(PORT_COMMAND) | PORT_COMMAND_START);
+
+        true
+    }
+
+    // Points the port at its memory and turns on its interrupts; the engine is started once a device shows up.
+    fn initialize(&self) -> bool {
+        if !self.stop_engine() {
+            return false;
+        }
+
+        let command_list = self.memory.physical().as_u64();
+        let received_fis = command_list + RECEIVED_FIS_OFFSET as u64;
+
+        self.write(PORT_COMMAND_LIST_BASE, command_list as u32);
+        self.write(PORT_COMMAND_LIST_BASE_HIGH, (command_list >> 32) as u32);
+        self.write(PORT_FIS_BASE, received_fis as u32);
+        self.write(PORT_FIS_BASE_HIGH, (received_fis >> 32) as u32);
+
+        self.write(PORT_SATA_ERROR, u32::MAX);
+        self.write(PORT_INTERRUPT_STATUS, u32::MAX);
+        self.write(PORT_COMMAND, self.read(PORT_COMMAND) | PORT_COMMAND_SPIN_UP | PORT_COMMAND_POWER_ON);
+        self.write(PORT_INTERRUPT_ENABLE, PORT_INTERRUPTS);
+
+        true
+    }
+
+    // Fills in a slot's command header and table; commands without data pass no segments.
+    fn prepare(&self, slot: usize, fis: &[u8; FIS_SIZE], segments: &[(PhysAddr, usize)], write: bool) -> Result<(), BlockError> {
+        let table = COMMAND_TABLE_OFFSET + slot * COMMAND_TABLE_SIZE;
+        let mut entries = 0;
+
+        for &(address, length) in segments {
+            if address.as_u64() & 1 != 0 || length & 1 != 0 {
+                return Err(BlockError::Misaligned);
+            }
+
+            let mut offset = 0;
+
+            while offset < length {
+                if entries == MAXIMUM_PRDS {
+                    log::warn!("A request to port {} needs more than {} PRDs.", self.number, MAXIMUM_PRDS);
+
+                    return Err(BlockError::Io);
+                }
+
+                let chunk = (length - offset).min(PRD_MAXIMUM_BYTES);
+                let entry = table + PRDT_OFFSET + entries * PRD_SIZE;
+
+                self.memory.write::<u64>(entry, (address + offset).as_u64());
+                self.memory.write::<u32>(entry + 8, 0);
+                self.memory.write::<u32>(entry + 12, (chunk - 1) as u32);
+
+                offset += chunk;
+                entries += 1;
+            }
+        }
+
+        self.memory.copy_from(table, fis);
+
+        let mut flags = (FIS_SIZE / 4) as u32 | (entries as u32) << 16;
+
+        if write {
+            flags |= COMMAND_HEADER_WRITE;
+        }
+
+        let header = slot * COMMAND_HEADER_SIZE;
+
+        self.memory.write::<u32>(header, flags);
+        self.memory.write::<u32>(header + 4, 0);
+        self.memory.write::<u64>(header + 8, (self.memory.physical() + table).as_u64());
+
+        Ok(())
+    }
+
+    fn issue(&self, slot: usize, queued: bool) {
+        // The command table has to be in memory before the port is told about it.
+        fence(Ordering::SeqCst);
+
+        if queued {
+            self.write(PORT_SATA_ACTIVE, 1 << slot);
+        }
+
+        self.write(PORT_COMMAND_ISSUE, 1 << slot);
+    }
+
+    // Runs IDENTIFY DEVICE in slot 0 and waits for it; only used while nothing else is in flight.
+    fn identify(&self) -> Option<Vec<u16>> {
+        let buffer = DmaBuffer::new(ATA_IDENTIFY_SIZE)?;
+        let fis = lightsaber_kernel_ahci_command_fis(ATA_IDENTIFY, 0, 0, 0, 0);
+
+        self.prepare(0, &fis, &[(buffer.physical(), ATA_IDENTIFY_SIZE)], false).ok()?;
+        self.issue(0, false);
+
+        if !self.wait_for(PORT_COMMAND_ISSUE, 1, 0) || self.read(PORT_TASK_FILE) & PORT_TASK_FILE_ERROR != 0 {
+            return None;
+        }
+
+        Some((0..ATA_IDENTIFY_SIZE / 2).map(|word| buffer.read::<u16>(word * 2)).collect())
+    }
+
+    fn recover(&self) {
+        // The engine stops itself on an error and has to be cycled before it takes new commands.
+        if !self.stop_engine() {
+            log::warn!("AHCI port {} did not stop after an error.", self.number);
+        }
+
+        self.write(PORT_SATA_ERROR, u32::MAX);
+        self.write(PORT_INTERRUPT_STATUS, u32::MAX);
+
+        if self.device_present() && !self.start_engine() {
+            log::warn!("AHCI port {} did not restart after an error.", self.number);
+        }
+    }
+
+    fn handle_interrupt(&self) {
+        let status = self.read(PORT_INTERRUPT_STATUS);
+
+        self.write(PORT_INTERRUPT_STATUS, status);
+        self.hba.write_u32(HBA_INTERRUPT_STATUS, 1 << self.number);
+
+        // The connection bits only clear along with the error register bits behind them.
+        if status & PORT_INTERRUPT_HOTPLUG != 0 {
+            self.write(PORT_SATA_ERROR, u32::MAX);
+            self.changed.store(true, Ordering::Release);
+
+            AHCI_HOTPLUG_PENDING.store(true, Ordering::Release);
+            AHCI_HOTPLUG.wake_all();
+        }
+
+        let disk = match self.disk.lock().clone() {
+            Some(disk) => disk,
+            None => return
+        };
+
+        let completed = {
+            let mut in_flight = self.in_flight.lock();
+            let mut completed = Vec::new();
+
+            if status & PORT_INTERRUPT_ERRORS != 0 {
+                log::warn!("AHCI port {} reported an error (interrupt status {:#x}, task file {:#x}).", self.number, status, self.read(PORT_TASK_FILE));
+
+                for command in in_flight.iter_mut() {
+                    if let Some(command) = command.take() {
+                        completed.push((command.request, Err(BlockError::Io)));
+                    }
+                }
+
+                self.recover();
+            } else {
+                let busy = self.read(PORT_COMMAND_ISSUE) | self.read(PORT_SATA_ACTIVE);
+
+                for (slot, command) in in_flight.iter_mut().enumerate() {
+                    if busy & (1 << slot) != 0 {
+                        continue;
+                    }
+
+                    if let Some(command) = command.take() {
+                        completed.push((command.request, Ok(())));
+                    }
+                }
+            }
+
+            completed
+        };
+
+        for (request, status) in completed {
+            disk.pending.finish(&request, status);
+        }
+
+        disk.start();
+    }
+
+    fn detach(&self) {
+        let disk = match self.disk.lock().take() {
+            Some(disk) => disk,
+            None => return
+        };
+
+        disk.present.store(false, Ordering::Release);
+
+        let in_flight = self
+            .in_flight
+            .lock()
+            .iter_mut()
+            .filter_map(Option::take)
+            .collect::<Vec<AhciCommand>>();
+
+        for command in in_flight {
+            disk.pending.finish(&command.request, Err(BlockError::NotReady));
+        }
+
+        disk.pending.fail_all(BlockError::NotReady);
+        self.stop_engine();
+
+        block::lightsaber_kernel_unregister_block_device(&disk.name);
+
+        log::info!("SATA disk {} was removed from port {}.", disk.name, self.number);
+    }
+}
+
+pub struct AhciDisk {
+    name: String,
+    model: String,
+    port: Arc<AhciPort>,
+    sector_size: usize,
+    sector_count: u64,
+    lba48: bool,
+    // Zero when commands are not queued.
+    queue_depth: usize,
+    flush_command: Option<u8>,
+    present: AtomicBool,
+    pending: RequestQueue,
+    statistics: Arc<BlockStatistics>
+}
+
+impl AhciDisk {
+    fn command(&self, request: &BlockRequest, slot: usize) -> Result<[u8; FIS_SIZE], BlockError> {
+        let sector = request.sector();
+        let count = request.sector_count();
+        let write = request.operation() == BlockOperation::Write;
+
+        if request.operation() == BlockOperation::Flush {
+            let command = self.flush_command.ok_or(BlockError::NotReady)?;
+
+            return Ok(lightsaber_kernel_ahci_command_fis(command, 0, 0, 0, ATA_DEVICE_LBA));
+        }
+
+        if self.queue_depth > 0 {
+            if count > u16::MAX as u64 {
+                return Err(BlockError::Io);
+            }
+
+            let command = match write {
+                true => ATA_WRITE_FPDMA_QUEUED,
+                false => ATA_READ_FPDMA_QUEUED
+            };
+
+            // Queued commands carry the sector count in the features registers and the tag in the count register.
+            Ok(lightsaber_kernel_ahci_command_fis(command, sector, (slot as u16) << 3, count as u16, ATA_DEVICE_LBA))
+        } else if self.lba48 {
+            if count > u16::MAX as u64 {
+                return Err(BlockError::Io);
+            }
+
+            let command = match write {
+                true => ATA_WRITE_DMA_EXT,
+                false => ATA_READ_DMA_EXT
+            };
+
+            Ok(lightsaber_kernel_ahci_command_fis(command, sector, count as u16, 0, ATA_DEVICE_LBA))
+        } else {
+            if count > u8::MAX as u64 || sector + count > 1 << 28 {
+                return Err(BlockError::OutOfRange);
+            }
+
+            let command = match write {
+                true => ATA_WRITE_DMA,
+                false => ATA_READ_DMA
+            };
+
+            Ok(lightsaber_kernel_ahci_command_fis(command, sector & 0x00FF_FFFF, count as u16, 0, ATA_DEVICE_LBA | (sector >> 24) as u8 & 0x0F))
+        }
+    }
+
+    // Issues pending requests into free slots; non-queued commands run alone.
+    fn start(&self) {
+        if !self.present.load(Ordering::Acquire) {
+            self.pending.fail_all(BlockError::NotReady);
+
+            return;
+        }
+
+        let mut in_flight = self.port.in_flight.lock();
+
+        while let Some(request) = self.pending.peek() {
+            let queued = self.queue_depth > 0 && request.operation() != BlockOperation::Flush;
+            let busy = in_flight.iter().any(Option::is_some);
+
+            if busy && (!queued || in_flight.iter().flatten().any(|command| !command.queued)) {
+                break;
+            }
+
+            let slot = match in_flight.iter().position(Option::is_none) {
+                Some(slot) => slot,
+                None => break
+            };
+
+            let request = match self.pending.next() {
+                Some(request) => request,
+                None => break
+            };
+
+            // A disk without a volatile cache has nothing to flush.
+            if request.operation() == BlockOperation::Flush && self.flush_command.is_none() {
+                self.pending.finish(&request, Ok(()));
+
+                continue;
+            }
+
+            let prepared = self.command(&request, slot).and_then(|fis| {
+                let data = request.data();
+
+                let segments = match request.operation() {
+                    BlockOperation::Flush => Vec::new(),
+                    _ => dma::lightsaber_kernel_physical_segments(&data).ok_or(BlockError::Io)?
+                };
+
+                self.port.prepare(slot, &fis, &segments, request.operation() == BlockOperation::Write)
+            });
+
+            match prepared {
+                Ok(()) => {
+                    in_flight[slot] = Some(AhciCommand {
+                        request,
+                        queued
+                    });
+
+                    self.port.issue(slot, queued);
+                }
+                Err(error) => self.pending.finish(&request, Err(error))
+            }
+        }
+    }
+}
+
+impl BlockDevice for AhciDisk {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        self.pending.push(request.clone());
+        self.start();
+
+        if self.port.polled || !interrupts::lightsaber_kernel_interrupts_enabled() {
+            while request.status().is_none() {
+                self.port.handle_interrupt();
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+pub struct AhciController {
+    hba: MmioRegion,
+    interrupt: AhciInterrupt,
+    ports: Vec<Arc<AhciPort>>
+}
+
+impl AhciController {
+    fn handle_interrupt(&self) -> bool {
+        let pending = self.hba.read_u32(HBA_INTERRUPT_STATUS);
+
+        for port in &self.ports {
+            if pending & (1 << port.number) != 0 {
+                port.handle_interrupt();
+            }
+        }
+
+        pending != 0
+    }
+}
+
+fn lightsaber_kernel_ahci_command_fis(command: u8, lba: u64, count: u16, features: u16, device: u8) -> [u8; FIS_SIZE] {
+    let mut fis = [0; FIS_SIZE];
+
+    fis[0] = FIS_TYPE_REGISTER_HOST_TO_DEVICE;
+    fis[1] = FIS_COMMAND;
+    fis[2] = command;
+    fis[3] = features as u8;
+    fis[4] = lba as u8;
+    fis[5] = (lba >> 8) as u8;
+    fis[6] = (lba >> 16) as u8;
+    fis[7] = device;
+    fis[8] = (lba >> 24) as u8;
+    fis[9] = (lba >> 32) as u8;
+    fis[10] = (lba >> 40) as u8;
+    fis[11] = (features >> 8) as u8;
+    fis[12] = count as u8;
+    fis[13] = (count >> 8) as u8;
+
+    fis
+}
+
+// IDENTIFY strings hold two characters per word, the first in the high byte.
+fn lightsaber_kernel_ata_string(words: &[u16]) -> String {
+    let bytes = words.iter().flat_map(|word| word.to_be_bytes().to_vec()).collect::<Vec<u8>>();
+
+    String::from_utf8_lossy(&bytes).trim().into()
+}
+
+fn lightsaber_kernel_ahci_attach(port: &Arc<AhciPort>) {
+    if !port.start_engine() {
+        log::warn!("AHCI port {} has a device that never became ready.", port.number);
+
+        return;
+    }
+
+    match port.read(PORT_SIGNATURE) {
+        SATA_SIGNATURE_ATA => { }
+        SATA_SIGNATURE_ATAPI => {
+            log::info!("AHCI port {} has an ATAPI device, which is not supported.", port.number);
+
+            return;
+        }
+        signature => {
+            log::info!("AHCI port {} has a device with unknown signature {:#x}.", port.number, signature);
+
+            return;
+        }
+    }
+
+    let identity = match port.identify() {
+        Some(identity) => identity,
+        None => {
+            log::warn!("IDENTIFY DEVICE failed on AHCI port {}.", port.number);
+
+            return;
+        }
+    };
+
+    let lba48 = identity[83] & (1 << 10) != 0;
+
+    let sector_count = match lba48 {
+        true => (0..4).fold(0, |count, word| count | (identity[100 + word] as u64) << (16 * word)),
+        false => identity[60] as u64 | (identity[61] as u64) << 16
+    };
+
+    // Word 106 is only meaningful when its top two bits read 01.
+    let sector_size = match identity[106] & 0xC000 == 0x4000 && identity[106] & (1 << 12) != 0 {
+        true => (identity[117] as usize | (identity[118] as usize) << 16) * 2,
+        false => ATA_SECTOR_SIZE
+    };
+
+    let queue_depth = match port.ncq_supported && identity[76] & (1 << 8) != 0 {
+        true => ((identity[75] & 0x1F) as usize + 1).min(port.slot_count),
+        false => 0
+    };
+
+    let flush_command = if lba48 && identity[83] & (1 << 13) != 0 {
+        Some(ATA_FLUSH_CACHE_EXT)
+    } else if identity[83] & (1 << 12) != 0 {
+        Some(ATA_FLUSH_CACHE)
+    } else {
+        None
+    };
+
+    let index = NEXT_DISK_INDEX.fetch_add(1, Ordering::AcqRel);
+
+    let disk = Arc::new(AhciDisk {
+        name: format!("sd{}", (b'a' + (index % 26) as u8) as char),
+        model: lightsaber_kernel_ata_string(&identity[27..47]),
+        port: port.clone(),
+        sector_size,
+        sector_count,
+        lba48,
+        queue_depth,
+        flush_command,
+        present: AtomicBool::new(true),
+        pending: RequestQueue::new(queue_depth.max(1)),
+        statistics: Arc::new(BlockStatistics::default())
+    });
+
+    *port.disk.lock() = Some(disk.clone());
+
+    log::info!(
+        "Found SATA disk {} ({}) on AHCI port {} with {} sectors of {} bytes ({}).",
+        disk.name,
+        disk.model,
+        port.number,
+        disk.sector_count,
+        disk.sector_size,
+        match queue_depth {
+            0 => String::from("no NCQ"),
+            depth => format!("NCQ depth {}", depth)
+        }
+    );
+
+    block::lightsaber_kernel_register_block_device(disk);
+}
+
+fn lightsaber_kernel_ahci_hotplug_worker() {
+    loop {
+        let controllers = AHCI_CONTROLLERS.lock().values().cloned().collect::<Vec<Arc<AhciController>>>();
+
+        // Without interrupts the ports have to be looked at now and then instead.
+        match controllers.iter().any(|controller| controller.interrupt == AhciInterrupt::Polled) {
+            true => {
+                time::lightsaber_kernel_sleep(HOTPLUG_POLL_MILLISECONDS);
+
+                for controller in controllers.iter().filter(|controller| controller.interrupt == AhciInterrupt::Polled) {
+                    controller.handle_interrupt();
+                }
+
+                AHCI_HOTPLUG_PENDING.store(false, Ordering::Release);
+            }
+            false => AHCI_HOTPLUG.wait_until_uninterruptible(|| AHCI_HOTPLUG_PENDING.swap(false, Ordering::AcqRel))
+        }
+
+        for port in controllers.iter().flat_map(|controller| controller.ports.iter()) {
+            if !port.changed.swap(false, Ordering::AcqRel) {
+                continue;
+            }
+
+            let attached = port.disk.lock().is_some();
+
+            match (port.device_present(), attached) {
+                (true, false) => lightsaber_kernel_ahci_attach(port),
+                (false, true) => port.detach(),
+                _ => { }
+            }
+        }
+    }
+}
+
+fn lightsaber_kernel_ahci_interrupt(index: usize) {
+    let controller = AHCI_CONTROLLERS.lock().get(&index).cloned();
+
+    if let Some(controller) = controller {
+        controller.handle_interrupt();
+    }
+}
+
+fn lightsaber_kernel_ahci_legacy_interrupt() {
+    let controllers = AHCI_CONTROLLERS.lock().values().cloned().collect::<Vec<Arc<AhciController>>>();
+
+    for controller in controllers {
+        if controller.interrupt == AhciInterrupt::Legacy {
+            controller.handle_interrupt();
+        }
+    }
+}
+
+// Takes the controller from the firmware if it asks to be asked, then resets it into AHCI mode.
+fn lightsaber_kernel_ahci_reset(hba: &MmioRegion) -> bool {
+    if hba.read_u32(HBA_CAPABILITIES_2) & HBA_CAPABILITIES_2_BIOS_HANDOFF != 0 {
+        hba.write_u32(HBA_BIOS_HANDOFF, hba.read_u32(HBA_BIOS_HANDOFF) | HBA_BIOS_HANDOFF_OS_OWNED);
+
+        for _ in 0..AHCI_SPIN_LIMIT {
+            if hba.read_u32(HBA_BIOS_HANDOFF) & HBA_BIOS_HANDOFF_BIOS_OWNED == 0 {
+                break;
+            }
+
+            hint::spin_loop();
+        }
+    }
+
+    hba.write_u32(HBA_GLOBAL_CONTROL, hba.read_u32(HBA_GLOBAL_CONTROL) | HBA_GLOBAL_CONTROL_AHCI_ENABLE);
+    hba.write_u32(HBA_GLOBAL_CONTROL, hba.read_u32(HBA_GLOBAL_CONTROL) | HBA_GLOBAL_CONTROL_RESET);
+
+    for _ in 0..AHCI_SPIN_LIMIT {
+        if hba.read_u32(HBA_GLOBAL_CONTROL) & HBA_GLOBAL_CONTROL_RESET == 0 {
+            hba.write_u32(HBA_GLOBAL_CONTROL, hba.read_u32(HBA_GLOBAL_CONTROL) | HBA_GLOBAL_CONTROL_AHCI_ENABLE);
+
+            return true;
+        }
+
+        hint::spin_loop();
+    }
+
+    false
+}
+
+fn lightsaber_kernel_probe_ahci(device: &Arc<PciDevice>) -> Result<(), PciError> {
+    device.enable_bus_mastering();
+
+    let hba = device.map_bar(AHCI_BASE_BAR)?;
+
+    if !lightsaber_kernel_ahci_reset(&hba) {
+        return Err(PciError::DeviceFailed);
+    }
+
+    let capabilities = hba.read_u32(HBA_CAPABILITIES);
+    let slot_count = ((capabilities >> HBA_CAPABILITIES_SLOTS_SHIFT) & HBA_CAPABILITIES_SLOTS) as usize + 1;
+    let ncq_supported = capabilities & HBA_CAPABILITIES_NCQ != 0;
+    let implemented = hba.read_u32(HBA_PORTS_IMPLEMENTED);
+    let index = NEXT_CONTROLLER_INDEX.fetch_add(1, Ordering::AcqRel);
+
+    if capabilities & HBA_CAPABILITIES_64_BIT == 0 {
+        log::warn!("AHCI controller {} can only reach the first 4 GiB of memory.", device.address);
+    }
+
+    let interrupt = match device.enable_msi(lightsaber_kernel_ahci_interrupt, index) {
+        Ok(_) => AhciInterrupt::Msi,
+        Err(_) if device.interrupt_pin != 0 && device.interrupt_line < 16 => AhciInterrupt::Legacy,
+        Err(_) => AhciInterrupt::Polled
+    };
+
+    let mut ports = Vec::new();
+
+    for number in (0..32).filter(|number| implemented & (1 << number) != 0) {
+        let registers = hba.subregion(HBA_PORT_BASE + number as u64 * HBA_PORT_SIZE, HBA_PORT_SIZE).ok_or(PciError::InvalidBar)?;
+        let memory = DmaBuffer::new(COMMAND_TABLE_OFFSET + slot_count * COMMAND_TABLE_SIZE).ok_or(PciError::DeviceFailed)?;
+
+        let port = Arc::new(AhciPort {
+            number,
+            hba,
+            registers,
+            memory,
+            slot_count,
+            ncq_supported,
+            polled: interrupt == AhciInterrupt::Polled,
+            in_flight: Spinlock::named("ahci_port", (0..slot_count).map(|_| None).collect()),
+            disk: Spinlock::named("ahci_port_disk", None),
+            changed: AtomicBool::new(false)
+        });
+
+        if !port.initialize() {
+            log::warn!("AHCI port {} on {} would not stop; leaving it alone.", number, device.address);
+
+            continue;
+        }
+
+        ports.push(port);
+    }
+
+    let controller = Arc::new(AhciController {
+        hba,
+        interrupt,
+        ports
+    });
+
+    AHCI_CONTROLLERS.lock().insert(index, controller.clone());
+
+    if interrupt == AhciInterrupt::Legacy {
+        irq::lightsaber_kernel_register_irq_handler(device.interrupt_line, lightsaber_kernel_ahci_legacy_interrupt);
+    }
+
+    hba.write_u32(HBA_INTERRUPT_STATUS, u32::MAX);
+    hba.write_u32(HBA_GLOBAL_CONTROL, hba.read_u32(HBA_GLOBAL_CONTROL) | HBA_GLOBAL_CONTROL_INTERRUPT_ENABLE);
+
+    let version = hba.read_u32(HBA_VERSION);
+
+    log::info!(
+        "Found AHCI {:x}.{:x} controller {} with {} ports, {} command slots{}{} ({:?} interrupts).",
+        version >> 16,
+        (version >> 8) & 0xFF,
+        device.address,
+        controller.ports.len(),
+        slot_count,
+        if ncq_supported { ", NCQ" } else { "" },
+        if capabilities & HBA_CAPABILITIES_STAGGERED_SPIN_UP != 0 { ", staggered spin-up" } else { "" },
+        interrupt
+    );
+
+    for port in controller.ports.iter().filter(|port| port.device_present()) {
+        lightsaber_kernel_ahci_attach(port);
+    }
+
+    AHCI_HOTPLUG_WORKER.call_once(|| {
+        scheduler::lightsaber_kernel_spawn("ahci_hotplug", lightsaber_kernel_ahci_hotplug_worker);
+    });
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_initialize_ahci() {
+    pci::lightsaber_kernel_register_pci_driver(&AHCI_DRIVER);
+}
@@ -0,0 +1,14 @@
+pub mod ahci;
+pub mod nvme;
+pub mod ps2;
+pub mod serial;
+pub mod virtio;
+
+// Drivers register with their bus here; the buses have to be scanned first.
+pub fn lightsaber_kernel_initialize_drivers() {
+    virtio::block::lightsaber_kernel_initialize_virtio_block();
+    ahci::lightsaber_kernel_initialize_ahci();
+    nvme::lightsaber_kernel_initialize_nvme();
+    ps2::lightsaber_kernel_initialize_ps2();
+    serial::lightsaber_kernel_initialize_serial_input();
+}
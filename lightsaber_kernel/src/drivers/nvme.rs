@@ -0,0 +1,744 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    sync::atomic::{
+        fence,
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use x86_64::{
+    structures::paging::{
+        PageSize,
+        Size4KiB
+    },
+    PhysAddr
+};
+
+use crate::{
+    acpi,
+    architecture::{
+        apic,
+        interrupts::{
+            self,
+            irq,
+            msi::MsiHandler
+        }
+    },
+    block::{
+        self,
+        BlockDevice,
+        BlockError,
+        BlockOperation,
+        BlockRequest,
+        BlockStatistics,
+        RequestQueue
+    },
+    memory::{
+        dma::{
+            self,
+            DmaBuffer
+        },
+        mmio::MmioRegion
+    },
+    pci::{
+        self,
+        PciDevice,
+        PciDeviceId,
+        PciDriver,
+        PciError
+    },
+    sync::Spinlock
+};
+
+const NVME_BASE_BAR: usize = 0;
+
+const NVME_CAPABILITIES: u64 = 0x00;
+const NVME_VERSION: u64 = 0x08;
+const NVME_CONFIGURATION: u64 = 0x14;
+const NVME_STATUS: u64 = 0x1C;
+const NVME_ADMIN_QUEUE_ATTRIBUTES: u64 = 0x24;
+const NVME_ADMIN_SUBMISSION_QUEUE: u64 = 0x28;
+const NVME_ADMIN_COMPLETION_QUEUE: u64 = 0x30;
+const NVME_DOORBELL_BASE: u64 = 0x1000;
+
+const NVME_CAPABILITIES_MAXIMUM_ENTRIES: u64 = 0xFFFF;
+const NVME_CAPABILITIES_TIMEOUT_SHIFT: u64 = 24;
+const NVME_CAPABILITIES_DOORBELL_STRIDE_SHIFT: u64 = 32;
+const NVME_CAPABILITIES_NVM_COMMAND_SET: u64 = 1 << 37;
+const NVME_CAPABILITIES_MINIMUM_PAGE_SIZE_SHIFT: u64 = 48;
+
+const NVME_CONFIGURATION_ENABLE: u32 = 1 << 0;
+const NVME_CONFIGURATION_SUBMISSION_ENTRY_SIZE: u32 = 6 << 16;
+const NVME_CONFIGURATION_COMPLETION_ENTRY_SIZE: u32 = 4 << 20;
+
+const NVME_STATUS_READY: u32 = 1 << 0;
+const NVME_STATUS_FATAL: u32 = 1 << 1;
+
+const NVME_ADMIN_CREATE_SUBMISSION_QUEUE: u8 = 0x01;
+const NVME_ADMIN_CREATE_COMPLETION_QUEUE: u8 = 0x05;
+const NVME_ADMIN_IDENTIFY: u8 = 0x06;
+const NVME_ADMIN_SET_FEATURES: u8 = 0x09;
+
+const NVME_IDENTIFY_NAMESPACE: u32 = 0x00;
+const NVME_IDENTIFY_CONTROLLER: u32 = 0x01;
+const NVME_IDENTIFY_ACTIVE_NAMESPACES: u32 = 0x02;
+
+const NVME_FEATURE_NUMBER_OF_QUEUES: u32 = 0x07;
+
+const NVME_QUEUE_PHYSICALLY_CONTIGUOUS: u32 = 1 << 0;
+const NVME_QUEUE_INTERRUPTS_ENABLED: u32 = 1 << 1;
+
+const NVME_COMMAND_FLUSH: u8 = 0x00;
+const NVME_COMMAND_WRITE: u8 = 0x01;
+const NVME_COMMAND_READ: u8 = 0x02;
+
+const NVME_SUBMISSION_ENTRY_SIZE: usize = 64;
+const NVME_COMPLETION_ENTRY_SIZE: usize = 16;
+const NVME_IDENTIFY_SIZE: usize = 4096;
+
+const NVME_ADMIN_QUEUE_SIZE: u16 = 32;
+const NVME_IO_QUEUE_SIZE: u16 = 32;
+// Bounds the queue index packed into the MSI-X handler argument.
+const NVME_MAXIMUM_QUEUES: usize = 64;
+
+const NVME_PAGE_SIZE: u64 = Size4KiB::SIZE;
+const NVME_PRP_LIST_ENTRIES: usize = NVME_PAGE_SIZE as usize / 8;
+
+// Register reads per 500 ms unit of the controller's advertised timeout; the timer may not be running yet.
+const NVME_SPINS_PER_TIMEOUT_UNIT: usize = 500_000;
+
+const NVME_IDS: &[PciDeviceId] = &[
+    PciDeviceId::class(0x01, 0x08, Some(0x02))
+];
+
+static NVME_DRIVER: PciDriver = PciDriver {
+    name: "nvme",
+    ids: NVME_IDS,
+    probe: lightsaber_kernel_probe_nvme
+};
+
+static NVME_CONTROLLERS: Spinlock<BTreeMap<usize, Arc<NvmeController>>> = Spinlock::named("nvme_controllers", BTreeMap::new());
+static NEXT_CONTROLLER_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum NvmeInterrupt {
+    Msix,
+
+    Legacy,
+
+    Polled
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NvmeCompletion {
+    identifier: u16,
+    status: u16,
+    result: u32
+}
+
+// A submission queue and the completion queue it posts to, both physically contiguous.
+struct NvmeQueue {
+    id: u16,
+    size: u16,
+    submission: DmaBuffer,
+    completion: DmaBuffer,
+    tail: u16,
+    head: u16,
+    phase: bool
+}
+
+impl NvmeQueue {
+    fn new(id: u16, size: u16) -> Option<Self> {
+        Some(Self {
+            id,
+            size,
+            submission: DmaBuffer::new(size as usize * NVME_SUBMISSION_ENTRY_SIZE)?,
+            completion: DmaBuffer::new(size as usize * NVME_COMPLETION_ENTRY_SIZE)?,
+            tail: 0,
+            head: 0,
+            phase: true
+        })
+    }
+
+    // Copies a command in and returns the new tail for the doorbell.
+    fn push(&mut self, command: &[u32; 16]) -> u16 {
+        let entry = self.tail as usize * NVME_SUBMISSION_ENTRY_SIZE;
+
+        for (index, dword) in command.iter().enumerate() {
+            self.submission.write::<u32>(entry + index * 4, *dword);
+        }
+
+        fence(Ordering::SeqCst);
+
+        self.tail = (self.tail + 1) % self.size;
+        self.tail
+    }
+
+    // A completion is new while its phase bit matches the one expected on this pass through the queue.
+    fn pop(&mut self) -> Option<NvmeCompletion> {
+        let entry = self.head as usize * NVME_COMPLETION_ENTRY_SIZE;
+        let status = self.completion.read::<u32>(entry + 12);
+
+        if (status >> 16) & 1 != self.phase as u32 {
+            return None;
+        }
+
+        fence(Ordering::SeqCst);
+
+        let completion = NvmeCompletion {
+            identifier: status as u16,
+            status: (status >> 17) as u16,
+            result: self.completion.read::<u32>(entry)
+        };
+
+        self.head = (self.head + 1) % self.size;
+
+        if self.head == 0 {
+            self.phase = !self.phase;
+        }
+
+        Some(completion)
+    }
+}
+
+struct NvmeCommand {
+    request: Arc<BlockRequest>,
+    namespace: usize
+}
+
+struct NvmeIoState {
+    queue: NvmeQueue,
+    // Indexed by command identifier; one entry short of the queue size so the queue never fills.
+    in_flight: Vec<Option<NvmeCommand>>
+}
+
+struct NvmeIoQueue {
+    state: Spinlock<NvmeIoState>,
+    // One page of PRP entries per command identifier.
+    prp_lists: DmaBuffer
+}
+
+pub struct NvmeController {
+    name: String,
+    registers: MmioRegion,
+    doorbell_stride: u64,
+    timeout_spins: usize,
+    interrupt: NvmeInterrupt,
+    model: String,
+    serial: String,
+    maximum_transfer: usize,
+    volatile_write_cache: bool,
+    admin: Spinlock<NvmeQueue>,
+    io_queues: Vec<NvmeIoQueue>,
+    processors: Vec<u8>,
+    namespaces: Spinlock<Vec<Arc<NvmeNamespace>>>
+}
+
+impl NvmeController {
+    #[inline]
+    fn ring_submission(&self, queue: u16, tail: u16) {
+        self.registers.write_u32(NVME_DOORBELL_BASE + (2 * queue as u64) * self.doorbell_stride, tail as u32);
+    }
+
+    #[inline]
+    fn ring_completion(&self, queue: u16, head: u16) {
+        self.registers.write_u32(NVME_DOORBELL_BASE + (2 * queue as u64 + 1) * self.doorbell_stride, head as u32);
+    }
+
+    // Admin commands are rare and run one at a time, so their completions are polled for.
+    fn admin_command(&self, mut command: [u32; 16]) -> Result<u32, PciError> {
+        let mut admin = self.admin.lock();
+
+        command[0] |= (admin.tail as u32) << 16;
+
+        let tail = admin.push(&command);
+        self.ring_submission(admin.id, tail);
+
+        for _ in 0..self.timeout_spins {
+            if let Some(completion) = admin.pop() {
+                self.ring_completion(admin.id, admin.head);
+
+                return match completion.status & 0x7FF {
+                    0 => Ok(completion.result),
+                    status => {
+                        log::warn!("{}: admin command {:#x} failed with status {:#x}.", self.name, command[0] & 0xFF, status);
+
+                        Err(PciError::DeviceFailed)
+                    }
+                };
+            }
+
+            hint::spin_loop();
+        }
+
+        log::warn!("{}: admin command {:#x} timed out.", self.name, command[0] & 0xFF);
+
+        Err(PciError::DeviceFailed)
+    }
+
+    fn identify(&self, structure: u32, namespace: u32) -> Result<DmaBuffer, PciError> {
+        let buffer = DmaBuffer::new(NVME_IDENTIFY_SIZE).ok_or(PciError::DeviceFailed)?;
+        let mut command = lightsaber_kernel_nvme_command(NVME_ADMIN_IDENTIFY, namespace);
+
+        lightsaber_kernel_nvme_set_prps(&mut command, buffer.physical(), PhysAddr::new(0));
+        command[10] = structure;
+
+        self.admin_command(command)?;
+
+        Ok(buffer)
+    }
+
+    // Requests are kept on the submitting processor's queue pair.
+    fn current_queue(&self) -> usize {
+        let apic_id = apic::lightsaber_kernel_local_apic_id();
+
+        self.processors.iter().position(|id| *id == apic_id).unwrap_or(0) % self.io_queues.len()
+    }
+
+    fn command(&self, namespace: &NvmeNamespace, request: &BlockRequest, identifier: usize, prp_lists: &DmaBuffer) -> Result<[u32; 16], BlockError> {
+        let opcode = match request.operation() {
+            BlockOperation::Read => NVME_COMMAND_READ,
+            BlockOperation::Write => NVME_COMMAND_WRITE,
+            BlockOperation::Flush => return Ok(lightsaber_kernel_nvme_command(NVME_COMMAND_FLUSH, namespace.id))
+        };
+
+        let count = request.sector_count();
+
+        if count == 0 || count > 1 << 16 || count as usize * namespace.sector_size > self.maximum_transfer {
+            return Err(BlockError::Io);
+        }
+
+        let data = request.data();
+        let segments = dma::lightsaber_kernel_physical_segments(&data).ok_or(BlockError::Io)?;
+        let mut pages = Vec::new();
+
+        // Heap buffers are virtually contiguous, so every piece after the first starts on a page boundary as PRPs require.
+        for (address, length) in segments {
+            let mut offset = 0;
+
+            while offset < length {
+                let page = address + offset;
+
+                pages.push(page);
+                offset += ((NVME_PAGE_SIZE - page.as_u64() % NVME_PAGE_SIZE) as usize).min(length - offset);
+            }
+        }
+
+        if pages[0].as_u64() & 0x03 != 0 {
+            return Err(BlockError::Misaligned);
+        }
+
+        let second = match pages.len() {
+            1 => PhysAddr::new(0),
+            2 => pages[1],
+            length if length - 1 <= NVME_PRP_LIST_ENTRIES => {
+                let list = identifier * NVME_PAGE_SIZE as usize;
+
+                for (index, page) in pages[1..].iter().enumerate() {
+                    prp_lists.write::<u64>(list + index * 8, page.as_u64());
+                }
+
+                prp_lists.physical() + list
+            }
+            _ => return Err(BlockError::Io)
+        };
+
+        let mut command = lightsaber_kernel_nvme_command(opcode, namespace.id);
+
+        lightsaber_kernel_nvme_set_prps(&mut command, pages[0], second);
+        command[10] = request.sector() as u32;
+        command[11] = (request.sector() >> 32) as u32;
+        command[12] = (count - 1) as u32;
+
+        Ok(command)
+    }
+
+    // Moves pending requests of every namespace onto one queue pair while it has free identifiers.
+    fn start(&self, queue: usize) {
+        let namespaces = self.namespaces.lock().clone();
+        let io = &self.io_queues[queue];
+        let mut state = io.state.lock();
+        let mut tail = None;
+
+        for (index, namespace) in namespaces.iter().enumerate() {
+            while let Some(identifier) = state.in_flight.iter().position(Option::is_none) {
+                let request = match namespace.pending[queue].next() {
+                    Some(request) => request,
+                    None => break
+                };
+
+                // Without a volatile write cache everything written is already durable.
+                if request.operation() == BlockOperation::Flush && !self.volatile_write_cache {
+                    namespace.pending[queue].finish(&request, Ok(()));
+
+                    continue;
+                }
+
+                match self.command(namespace, &request, identifier, &io.prp_lists) {
+                    Ok(mut command) => {
+                        command[0] |= (identifier as u32) << 16;
+
+                        state.in_flight[identifier] = Some(NvmeCommand {
+                            request,
+                            namespace: index
+                        });
+
+                        tail = Some(state.queue.push(&command));
+                    }
+                    Err(error) => namespace.pending[queue].finish(&request, Err(error))
+                }
+            }
+        }
+
+        if let Some(tail) = tail {
+            self.ring_submission(state.queue.id, tail);
+        }
+    }
+
+    fn complete(&self, queue: usize) {
+        let completed = {
+            let mut state = self.io_queues[queue].state.lock();
+            let mut completed = Vec::new();
+            let mut consumed = false;
+
+            while let Some(completion) = state.queue.pop() {
+                consumed = true;
+
+                let command = state.in_flight.get_mut(completion.identifier as usize).and_then(Option::take);
+
+                if let Some(command) = command {
+                    let status = match completion.status & 0x7FF {
+                        0 => Ok(()),
+                        _ => Err(BlockError::Io)
+                    };
+
+                    completed.push((command, status));
+                }
+            }
+
+            if consumed {
+                self.ring_completion(state.queue.id, state.queue.head);
+            }
+
+            completed
+        };
+
+        let namespaces = self.namespaces.lock().clone();
+
+        for (command, status) in completed {
+            namespaces[command.namespace].pending[queue].finish(&command.request, status);
+        }
+
+        self.start(queue);
+    }
+}
+
+pub struct NvmeNamespace {
+    name: String,
+    id: u32,
+    controller: Arc<NvmeController>,
+    sector_size: usize,
+    sector_count: u64,
+    // One per I/O queue pair of the controller.
+    pending: Vec<RequestQueue>,
+    statistics: Arc<BlockStatistics>
+}
+
+impl BlockDevice for NvmeNamespace {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        let queue = self.controller.current_queue();
+
+        self.pending[queue].push(request.clone());
+        self.controller.start(queue);
+
+        if self.controller.interrupt == NvmeInterrupt::Polled || !interrupts::lightsaber_kernel_interrupts_enabled() {
+            while request.status().is_none() {
+                self.controller.complete(queue);
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+fn lightsaber_kernel_nvme_command(opcode: u8, namespace: u32) -> [u32; 16] {
+    let mut command = [0; 16];
+
+    command[0] = opcode as u32;
+    command[1] = namespace;
+
+    command
+}
+
+fn lightsaber_kernel_nvme_set_prps(command: &mut [u32; 16], first: PhysAddr, second: PhysAddr) {
+    command[6] = first.as_u64() as u32;
+    command[7] = (first.as_u64() >> 32) as u32;
+    command[8] = second.as_u64() as u32;
+    command[9] = (second.as_u64() >> 32) as u32;
+}
+
+fn lightsaber_kernel_nvme_string(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes).trim().into()
+}
+
+// The handler argument packs the controller index with the queue; entry 0 belongs to the admin queue, which is polled.
+fn lightsaber_kernel_nvme_interrupt(argument: usize) {
+    let queue = argument % NVME_MAXIMUM_QUEUES;
+
+    if queue == 0 {
+        return;
+    }
+
+    let controller = NVME_CONTROLLERS.lock().get(&(argument / NVME_MAXIMUM_QUEUES)).cloned();
+
+    if let Some(controller) = controller {
+        controller.complete(queue - 1);
+    }
+}
+
+fn lightsaber_kernel_nvme_legacy_interrupt() {
+    let controllers = NVME_CONTROLLERS.lock().values().cloned().collect::<Vec<Arc<NvmeController>>>();
+
+    for controller in controllers.iter().filter(|controller| controller.interrupt == NvmeInterrupt::Legacy) {
+        for queue in 0..controller.io_queues.len() {
+            controller.complete(queue);
+        }
+    }
+}
+
+fn lightsaber_kernel_nvme_wait_ready(registers: &MmioRegion, ready: bool, spins: usize) -> Result<(), PciError> {
+    for _ in 0..spins {
+        let status = registers.read_u32(NVME_STATUS);
+
+        if status & NVME_STATUS_FATAL != 0 {
+            return Err(PciError::DeviceFailed);
+        }
+
+        if (status & NVME_STATUS_READY != 0) == ready {
+            return Ok(());
+        }
+
+        hint::spin_loop();
+    }
+
+    Err(PciError::DeviceFailed)
+}
+
+fn lightsaber_kernel_probe_nvme(device: &Arc<PciDevice>) -> Result<(), PciError> {
+    device.enable_bus_mastering();
+
+    let registers = device.map_bar(NVME_BASE_BAR)?;
+    let capabilities = registers.read_u64(NVME_CAPABILITIES);
+    let index = NEXT_CONTROLLER_INDEX.fetch_add(1, Ordering::AcqRel);
+    let name = format!("nvme{}", index);
+
+    if capabilities & NVME_CAPABILITIES_NVM_COMMAND_SET == 0 || (capabilities >> NVME_CAPABILITIES_MINIMUM_PAGE_SIZE_SHIFT) & 0x0F != 0 {
+        log::warn!("{}: the controller supports neither the NVM command set nor 4 KiB pages.", name);
+
+        return Err(PciError::Unsupported);
+    }
+
+    let maximum_entries = (capabilities & NVME_CAPABILITIES_MAXIMUM_ENTRIES) as u16 + 1;
+    let timeout_spins = (((capabilities >> NVME_CAPABILITIES_TIMEOUT_SHIFT) & 0xFF) as usize).max(1) * NVME_SPINS_PER_TIMEOUT_UNIT;
+    let doorbell_stride = 4 << ((capabilities >> NVME_CAPABILITIES_DOORBELL_STRIDE_SHIFT) & 0x0F);
+
+    // The controller has to be disabled before the admin queue can be given to it.
+    registers.write_u32(NVME_CONFIGURATION, registers.read_u32(NVME_CONFIGURATION) & !NVME_CONFIGURATION_ENABLE);
+    lightsaber_kernel_nvme_wait_ready(&registers, false, timeout_spins)?;
+
+    let admin_size = NVME_ADMIN_QUEUE_SIZE.min(maximum_entries);
+    let admin = NvmeQueue::new(0, admin_size).ok_or(PciError::DeviceFailed)?;
+
+    registers.write_u32(NVME_ADMIN_QUEUE_ATTRIBUTES, (admin_size as u32 - 1) << 16 | (admin_size as u32 - 1));
+    registers.write_u64(NVME_ADMIN_SUBMISSION_QUEUE, admin.submission.physical().as_u64());
+    registers.write_u64(NVME_ADMIN_COMPLETION_QUEUE, admin.completion.physical().as_u64());
+    registers.write_u32(NVME_CONFIGURATION, NVME_CONFIGURATION_ENABLE | NVME_CONFIGURATION_SUBMISSION_ENTRY_SIZE | NVME_CONFIGURATION_COMPLETION_ENTRY_SIZE);
+    lightsaber_kernel_nvme_wait_ready(&registers, true, timeout_spins)?;
+
+    let mut processors = acpi::lightsaber_kernel_processor_apic_ids();
+
+    if processors.is_empty() {
+        processors.push(apic::lightsaber_kernel_local_apic_id());
+    }
+
+    let mut controller = NvmeController {
+        name,
+        registers,
+        doorbell_stride,
+        timeout_spins,
+        interrupt: NvmeInterrupt::Polled,
+        model: String::new(),
+        serial: String::new(),
+        maximum_transfer: usize::MAX,
+        volatile_write_cache: false,
+        admin: Spinlock::named("nvme_admin_queue", admin),
+        io_queues: Vec::new(),
+        processors,
+        namespaces: Spinlock::named("nvme_namespaces", Vec::new())
+    };
+
+    let identity = controller.identify(NVME_IDENTIFY_CONTROLLER, 0)?;
+    let identity = identity.as_slice();
+
+    controller.serial = lightsaber_kernel_nvme_string(&identity[4..24]);
+    controller.model = lightsaber_kernel_nvme_string(&identity[24..64]);
+    controller.volatile_write_cache = identity[525] & 1 != 0;
+
+    // Zero means no limit; a single PRP list page bounds it anyway.
+    controller.maximum_transfer = match identity[77] {
+        0 => usize::MAX,
+        shift => (NVME_PAGE_SIZE as usize) << shift
+    }.min(NVME_PRP_LIST_ENTRIES * NVME_PAGE_SIZE as usize);
+
+    let namespace_count = u32::from_le_bytes([identity[516], identity[517], identity[518], identity[519]]);
+
+    // Ask for a queue pair per processor and take what the controller grants.
+    let wanted = controller.processors.len().min(NVME_MAXIMUM_QUEUES - 1) as u32;
+    let mut command = lightsaber_kernel_nvme_command(NVME_ADMIN_SET_FEATURES, 0);
+
+    command[10] = NVME_FEATURE_NUMBER_OF_QUEUES;
+    command[11] = (wanted - 1) << 16 | (wanted - 1);
+
+    let granted = controller.admin_command(command)?;
+    let mut queue_count = (wanted as usize).min((granted & 0xFFFF) as usize + 1).min((granted >> 16) as usize + 1);
+
+    if let Some(vectors) = device.msix_vector_count() {
+        queue_count = queue_count.min(vectors.saturating_sub(1)).max(1);
+    }
+
+    let handlers = (0..=queue_count)
+        .map(|queue| (lightsaber_kernel_nvme_interrupt as MsiHandler, index * NVME_MAXIMUM_QUEUES + queue))
+        .collect::<Vec<(MsiHandler, usize)>>();
+
+    controller.interrupt = match device.enable_msix(&handlers) {
+        Ok(_) => NvmeInterrupt::Msix,
+        Err(_) if device.interrupt_pin != 0 && device.interrupt_line < 16 => NvmeInterrupt::Legacy,
+        Err(_) => NvmeInterrupt::Polled
+    };
+
+    let queue_size = NVME_IO_QUEUE_SIZE.min(maximum_entries);
+
+    for queue in 1..=queue_count as u16 {
+        let io = NvmeQueue::new(queue, queue_size).ok_or(PciError::DeviceFailed)?;
+
+        let (vector, flags) = match controller.interrupt {
+            NvmeInterrupt::Msix => (queue as u32, NVME_QUEUE_INTERRUPTS_ENABLED),
+            NvmeInterrupt::Legacy => (0, NVME_QUEUE_INTERRUPTS_ENABLED),
+            NvmeInterrupt::Polled => (0, 0)
+        };
+
+        let mut command = lightsaber_kernel_nvme_command(NVME_ADMIN_CREATE_COMPLETION_QUEUE, 0);
+
+        lightsaber_kernel_nvme_set_prps(&mut command, io.completion.physical(), PhysAddr::new(0));
+        command[10] = (queue_size as u32 - 1) << 16 | queue as u32;
+        command[11] = vector << 16 | flags | NVME_QUEUE_PHYSICALLY_CONTIGUOUS;
+        controller.admin_command(command)?;
+
+        let mut command = lightsaber_kernel_nvme_command(NVME_ADMIN_CREATE_SUBMISSION_QUEUE, 0);
+
+        lightsaber_kernel_nvme_set_prps(&mut command, io.submission.physical(), PhysAddr::new(0));
+        command[10] = (queue_size as u32 - 1) << 16 | queue as u32;
+        command[11] = (queue as u32) << 16 | NVME_QUEUE_PHYSICALLY_CONTIGUOUS;
+        controller.admin_command(command)?;
+
+        controller.io_queues.push(NvmeIoQueue {
+            state: Spinlock::named("nvme_io_queue", NvmeIoState {
+                in_flight: (1..queue_size).map(|_| None).collect(),
+                queue: io
+            }),
+            prp_lists: DmaBuffer::new((queue_size as usize - 1) * NVME_PAGE_SIZE as usize).ok_or(PciError::DeviceFailed)?
+        });
+    }
+
+    // The active namespace list is a 1.1 addition; older controllers number theirs densely.
+    let namespace_ids = match controller.identify(NVME_IDENTIFY_ACTIVE_NAMESPACES, 0) {
+        Ok(list) => (0..NVME_IDENTIFY_SIZE / 4)
+            .map(|entry| list.read::<u32>(entry * 4))
+            .take_while(|id| *id != 0)
+            .collect::<Vec<u32>>(),
+        Err(_) => (1..=namespace_count).collect()
+    };
+
+    let controller = Arc::new(controller);
+
+    NVME_CONTROLLERS.lock().insert(index, controller.clone());
+
+    if controller.interrupt == NvmeInterrupt::Legacy {
+        irq::lightsaber_kernel_register_irq_handler(device.interrupt_line, lightsaber_kernel_nvme_legacy_interrupt);
+    }
+
+    let version = controller.registers.read_u32(NVME_VERSION);
+
+    log::info!(
+        "Found NVMe {}.{} controller {} ({}, serial {}) with {} I/O queue pairs ({:?} interrupts).",
+        version >> 16,
+        (version >> 8) & 0xFF,
+        controller.name,
+        controller.model,
+        controller.serial,
+        controller.io_queues.len(),
+        controller.interrupt
+    );
+
+    for id in namespace_ids {
+        let identity = match controller.identify(NVME_IDENTIFY_NAMESPACE, id) {
+            Ok(identity) => identity,
+            Err(_) => continue
+        };
+
+        let sector_count = identity.read::<u64>(0);
+
+        if sector_count == 0 {
+            continue;
+        }
+
+        let format = identity.read::<u8>(26) & 0x0F;
+        let sector_size = 1 << ((identity.read::<u32>(128 + 4 * format as usize) >> 16) & 0xFF);
+
+        let namespace = Arc::new(NvmeNamespace {
+            name: format!("{}n{}", controller.name, id),
+            id,
+            controller: controller.clone(),
+            sector_size,
+            sector_count,
+            pending: (0..controller.io_queues.len()).map(|_| RequestQueue::new(queue_size as usize - 1)).collect(),
+            statistics: Arc::new(BlockStatistics::default())
+        });
+
+        controller.namespaces.lock().push(namespace.clone());
+
+        log::info!("Found NVMe namespace {} with {} sectors of {} bytes.", namespace.name, sector_count, sector_size);
+
+        block::lightsaber_kernel_register_block_device(namespace);
+    }
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_initialize_nvme() {
+    pci::lightsaber_kernel_register_pci_driver(&NVME_DRIVER);
+}
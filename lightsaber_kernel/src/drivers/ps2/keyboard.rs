@@ -0,0 +1,412 @@
+use crate::{
+    architecture::interrupts::irq,
+    console,
+    drivers::ps2::{
+        self,
+        keymap::{
+            self,
+            Keymap,
+            KeymapLevel
+        },
+        scancode::{
+            KeyCode,
+            KeyEvent,
+            ScancodeDecoder,
+            ScancodeSet
+        },
+        Ps2Error,
+        Ps2Port,
+        PS2_DEVICE_ACKNOWLEDGE,
+        PS2_DEVICE_DISABLE_SCANNING,
+        PS2_DEVICE_ENABLE_SCANNING
+    },
+    input::{
+        self,
+        InputEvent
+    },
+    sync::Spinlock
+};
+
+const KEYBOARD_SET_LEDS: u8 = 0xED;
+const KEYBOARD_SCANCODE_SET: u8 = 0xF0;
+const KEYBOARD_GET_SCANCODE_SET: u8 = 0x00;
+
+const KEYBOARD_LED_SCROLL_LOCK: u8 = 1 << 0;
+const KEYBOARD_LED_NUM_LOCK: u8 = 1 << 1;
+const KEYBOARD_LED_CAPS_LOCK: u8 = 1 << 2;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Modifiers {
+    pub left_shift: bool,
+    pub right_shift: bool,
+    pub left_control: bool,
+    pub right_control: bool,
+    pub left_alt: bool,
+    pub right_alt: bool,
+    pub caps_lock: bool,
+    pub num_lock: bool,
+    pub scroll_lock: bool
+}
+
+impl Modifiers {
+    pub const fn new() -> Self {
+        Self {
+            left_shift: false,
+            right_shift: false,
+            left_control: false,
+            right_control: false,
+            left_alt: false,
+            right_alt: false,
+            caps_lock: false,
+            num_lock: false,
+            scroll_lock: false
+        }
+    }
+
+    #[inline]
+    pub fn shift(&self) -> bool {
+        self.left_shift || self.right_shift
+    }
+
+    #[inline]
+    pub fn control(&self) -> bool {
+        self.left_control || self.right_control
+    }
+
+    #[inline]
+    pub fn alt(&self) -> bool {
+        self.left_alt
+    }
+
+    // The right Alt key is AltGr on the layouts that have third-level characters.
+    #[inline]
+    pub fn alt_graph(&self) -> bool {
+        self.right_alt
+    }
+
+    fn leds(&self) -> u8 {
+        let mut leds = 0;
+
+        if self.scroll_lock {
+            leds |= KEYBOARD_LED_SCROLL_LOCK;
+        }
+
+        if self.num_lock {
+            leds |= KEYBOARD_LED_NUM_LOCK;
+        }
+
+        if self.caps_lock {
+            leds |= KEYBOARD_LED_CAPS_LOCK;
+        }
+
+        leds
+    }
+
+    // Returns whether a lock key changed, so the LEDs need updating.
+    fn update(&mut self, event: KeyEvent) -> bool {
+        match event.code {
+            KeyCode::LeftShift => self.left_shift = event.pressed,
+            KeyCode::RightShift => self.right_shift = event.pressed,
+            KeyCode::LeftControl => self.left_control = event.pressed,
+            KeyCode::RightControl => self.right_control = event.pressed,
+            KeyCode::LeftAlt => self.left_alt = event.pressed,
+            KeyCode::RightAlt => self.right_alt = event.pressed,
+            KeyCode::CapsLock if event.pressed => {
+                self.caps_lock = !self.caps_lock;
+
+                return true;
+            }
+            KeyCode::NumLock if event.pressed => {
+                self.num_lock = !self.num_lock;
+
+                return true;
+            }
+            KeyCode::ScrollLock if event.pressed => {
+                self.scroll_lock = !self.scroll_lock;
+
+                return true;
+            }
+            _ => { }
+        }
+
+        false
+    }
+}
+
+struct Keyboard {
+    decoder: ScancodeDecoder,
+    modifiers: Modifiers,
+    // LED state to send once the keyboard acknowledges the command announcing it.
+    pending_leds: Option<u8>
+}
+
+static KEYBOARD: Spinlock<Keyboard> = Spinlock::named("keyboard", Keyboard {
+    decoder: ScancodeDecoder::new(ScancodeSet::Set2),
+    modifiers: Modifiers::new(),
+    pending_leds: None
+});
+
+// Special keys are sent the way a VT100-style terminal would, so readers handle the keyboard and serial lines alike.
+fn lightsaber_kernel_key_sequence(code: KeyCode) -> Option<&'static [u8]> {
+    let sequence: &[u8] = match code {
+        KeyCode::Enter | KeyCode::KeypadEnter => b"\n",
+        KeyCode::Backspace => b"\x7F",
+        KeyCode::Tab => b"\t",
+        KeyCode::Escape => b"\x1B",
+        KeyCode::Up => b"\x1B[A",
+        KeyCode::Down => b"\x1B[B",
+        KeyCode::Right => b"\x1B[C",
+        KeyCode::Left => b"\x1B[D",
+        KeyCode::Home => b"\x1B[H",
+        KeyCode::End => b"\x1B[F",
+        KeyCode::Insert => b"\x1B[2~",
+        KeyCode::Delete => b"\x1B[3~",
+        KeyCode::PageUp => b"\x1B[5~",
+        KeyCode::PageDown => b"\x1B[6~",
+        _ => return None
+    };
+
+    Some(sequence)
+}
+
+fn lightsaber_kernel_keypad_character(code: KeyCode) -> Option<char> {
+    let character = match code {
+        KeyCode::KeypadDivide => '/',
+        KeyCode::KeypadMultiply => '*',
+        KeyCode::KeypadMinus => '-',
+        KeyCode::KeypadPlus => '+',
+        KeyCode::KeypadPeriod => '.',
+        KeyCode::Keypad0 => '0',
+        KeyCode::Keypad1 => '1',
+        KeyCode::Keypad2 => '2',
+        KeyCode::Keypad3 => '3',
+        KeyCode::Keypad4 => '4',
+        KeyCode::Keypad5 => '5',
+        KeyCode::Keypad6 => '6',
+        KeyCode::Keypad7 => '7',
+        KeyCode::Keypad8 => '8',
+        KeyCode::Keypad9 => '9',
+        _ => return None
+    };
+
+    Some(character)
+}
+
+// Without num lock the keypad doubles as the navigation block.
+fn lightsaber_kernel_keypad_navigation(code: KeyCode) -> KeyCode {
+    match code {
+        KeyCode::Keypad0 => KeyCode::Insert,
+        KeyCode::Keypad1 => KeyCode::End,
+        KeyCode::Keypad2 => KeyCode::Down,
+        KeyCode::Keypad3 => KeyCode::PageDown,
+        KeyCode::Keypad4 => KeyCode::Left,
+        KeyCode::Keypad6 => KeyCode::Right,
+        KeyCode::Keypad7 => KeyCode::Home,
+        KeyCode::Keypad8 => KeyCode::Up,
+        KeyCode::Keypad9 => KeyCode::PageUp,
+        KeyCode::KeypadPeriod => KeyCode::Delete,
+        code => code
+    }
+}
+
+fn lightsaber_kernel_key_character(keymap: &Keymap, code: KeyCode, modifiers: &Modifiers) -> Option<char> {
+    match lightsaber_kernel_keypad_character(code) {
+        Some(character) => Some(character),
+        None if modifiers.alt_graph() => keymap.character(code, KeymapLevel::AltGraph),
+        None => {
+            // Caps lock only affects letters, and shift undoes it.
+            let letter = keymap.character(code, KeymapLevel::Plain).map_or(false, char::is_alphabetic);
+
+            match modifiers.shift() != (modifiers.caps_lock && letter) {
+                true => keymap.character(code, KeymapLevel::Shift),
+                false => keymap.character(code, KeymapLevel::Plain)
+            }
+        }
+    }
+}
+
+fn lightsaber_kernel_keyboard_input(code: KeyCode, modifiers: &Modifiers) {
+    let code = match modifiers.num_lock {
+        true => code,
+        false => lightsaber_kernel_keypad_navigation(code)
+    };
+
+    if let Some(sequence) = lightsaber_kernel_key_sequence(code) {
+        console::lightsaber_kernel_console_input(sequence);
+
+        return;
+    }
+
+    let character = match lightsaber_kernel_key_character(&keymap::lightsaber_kernel_keymap(), code, modifiers) {
+        Some(character) => character,
+        None => return
+    };
+
+    // Alt puts an escape in front, the way terminals send meta.
+    if modifiers.alt() {
+        console::lightsaber_kernel_console_input(b"\x1B");
+    }
+
+    if modifiers.control() && character.is_ascii_alphabetic() {
+        console::lightsaber_kernel_console_input(&[character.to_ascii_uppercase() as u8 & 0x1F]);
+
+        return;
+    }
+
+    let mut buffer = [0; 4];
+
+    console::lightsaber_kernel_console_input(character.encode_utf8(&mut buffer).as_bytes());
+}
+
+fn lightsaber_kernel_keyboard_interrupt() {
+    // A late interrupt can find a mouse byte waiting instead, which the mouse handler will take.
+    if ps2::lightsaber_kernel_ps2_pending() != Some(Ps2Port::First) {
+        return;
+    }
+
+    let byte = ps2::lightsaber_kernel_ps2_read_data();
+
+    let (event, modifiers) = {
+        let mut keyboard = KEYBOARD.lock();
+
+        if byte == PS2_DEVICE_ACKNOWLEDGE {
+            if let Some(leds) = keyboard.pending_leds.take() {
+                let _ = ps2::lightsaber_kernel_ps2_write(Ps2Port::First, leds);
+            }
+
+            return;
+        }
+
+        let event = match keyboard.decoder.feed(byte) {
+            Some(event) => event,
+            None => return
+        };
+
+        // The LED byte itself goes out when this command is acknowledged.
+        if keyboard.modifiers.update(event) && ps2::lightsaber_kernel_ps2_write(Ps2Port::First, KEYBOARD_SET_LEDS).is_ok() {
+            keyboard.pending_leds = Some(keyboard.modifiers.leds());
+        }
+
+        (event, keyboard.modifiers)
+    };
+
+    input::lightsaber_kernel_push_input_event(InputEvent::Key {
+        code: event.code,
+        pressed: event.pressed
+    });
+
+    if event.pressed {
+        lightsaber_kernel_keyboard_input(event.code, &modifiers);
+    }
+}
+
+pub fn lightsaber_kernel_initialize_keyboard() -> Result<(), Ps2Error> {
+    ps2::lightsaber_kernel_ps2_reset_device(Ps2Port::First)?;
+
+    // Keys pressed while the keyboard is being set up would get mixed into its replies.
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::First, PS2_DEVICE_DISABLE_SCANNING)?;
+
+    // Set 2 is what keyboards speak natively; a keyboard that will not switch is asked which set it uses.
+    let _ = ps2::lightsaber_kernel_ps2_send(Ps2Port::First, KEYBOARD_SCANCODE_SET).and_then(|_| ps2::lightsaber_kernel_ps2_send(Ps2Port::First, 2));
+
+    let set = ps2::lightsaber_kernel_ps2_send(Ps2Port::First, KEYBOARD_SCANCODE_SET)
+        .and_then(|_| ps2::lightsaber_kernel_ps2_send(Ps2Port::First, KEYBOARD_GET_SCANCODE_SET))
+        .and_then(|_| ps2::lightsaber_kernel_ps2_read());
+
+    let set = match set {
+        Ok(1) => ScancodeSet::Set1,
+        _ => ScancodeSet::Set2
+    };
+
+    KEYBOARD.lock().decoder.set_set(set);
+
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::First, KEYBOARD_SET_LEDS)?;
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::First, 0)?;
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::First, PS2_DEVICE_ENABLE_SCANNING)?;
+
+    let keymap = keymap::lightsaber_kernel_keymap();
+
+    irq::lightsaber_kernel_register_irq_handler(irq::IRQ_KEYBOARD, lightsaber_kernel_keyboard_interrupt);
+
+    log::info!("Initialized PS/2 keyboard using scancode {:?} and the {} layout.", set, keymap.name());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lightsaber_kernel_test_press(modifiers: &mut Modifiers, code: KeyCode, pressed: bool) -> bool {
+        modifiers.update(KeyEvent {
+            code,
+            pressed
+        })
+    }
+
+    #[test_case]
+    fn keyboard_tracks_held_modifiers_on_both_sides() {
+        let mut modifiers = Modifiers::new();
+
+        assert!(!lightsaber_kernel_test_press(&mut modifiers, KeyCode::LeftShift, true));
+        assert!(!lightsaber_kernel_test_press(&mut modifiers, KeyCode::RightShift, true));
+        assert!(!lightsaber_kernel_test_press(&mut modifiers, KeyCode::LeftShift, false));
+        assert!(modifiers.shift());
+
+        lightsaber_kernel_test_press(&mut modifiers, KeyCode::RightShift, false);
+        lightsaber_kernel_test_press(&mut modifiers, KeyCode::RightControl, true);
+        lightsaber_kernel_test_press(&mut modifiers, KeyCode::RightAlt, true);
+
+        assert!(!modifiers.shift());
+        assert!(modifiers.control());
+        assert!(!modifiers.alt());
+        assert!(modifiers.alt_graph());
+    }
+
+    #[test_case]
+    fn keyboard_toggles_locks_on_press_only() {
+        let mut modifiers = Modifiers::new();
+
+        assert!(lightsaber_kernel_test_press(&mut modifiers, KeyCode::CapsLock, true));
+        assert!(!lightsaber_kernel_test_press(&mut modifiers, KeyCode::CapsLock, false));
+        assert!(lightsaber_kernel_test_press(&mut modifiers, KeyCode::NumLock, true));
+        assert_eq!(modifiers.leds(), KEYBOARD_LED_CAPS_LOCK | KEYBOARD_LED_NUM_LOCK);
+
+        assert!(lightsaber_kernel_test_press(&mut modifiers, KeyCode::CapsLock, true));
+        assert!(lightsaber_kernel_test_press(&mut modifiers, KeyCode::ScrollLock, true));
+        assert_eq!(modifiers.leds(), KEYBOARD_LED_NUM_LOCK | KEYBOARD_LED_SCROLL_LOCK);
+    }
+
+    #[test_case]
+    fn keyboard_picks_the_level_from_shift_caps_lock_and_altgr() {
+        let us = Keymap::built_in("us").unwrap();
+        let de = Keymap::built_in("de").unwrap();
+        let mut modifiers = Modifiers::new();
+
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::A, &modifiers), Some('a'));
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::Keypad5, &modifiers), Some('5'));
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::F1, &modifiers), None);
+
+        modifiers.left_shift = true;
+
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::A, &modifiers), Some('A'));
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::Digit1, &modifiers), Some('!'));
+
+        // Caps lock leaves everything but letters alone, and shift undoes it.
+        modifiers.caps_lock = true;
+
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::A, &modifiers), Some('a'));
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::Digit1, &modifiers), Some('!'));
+
+        modifiers.left_shift = false;
+
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::A, &modifiers), Some('A'));
+        assert_eq!(lightsaber_kernel_key_character(&us, KeyCode::Digit1, &modifiers), Some('1'));
+        assert_eq!(lightsaber_kernel_key_character(&de, KeyCode::Semicolon, &modifiers), Some('\u{D6}'));
+
+        modifiers.right_alt = true;
+
+        assert_eq!(lightsaber_kernel_key_character(&de, KeyCode::Q, &modifiers), Some('@'));
+        assert_eq!(lightsaber_kernel_key_character(&de, KeyCode::A, &modifiers), None);
+    }
+}
@@ -0,0 +1,403 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc
+};
+
+use core::{
+    fmt,
+    str
+};
+
+use crate::{
+    drivers::ps2::scancode::KeyCode,
+    fs::{
+        self,
+        FsError
+    },
+    sync::Spinlock
+};
+
+// One key per line: its name, then what it types plain, with shift and with AltGr.
+// `-` leaves a level empty, `space` stands for a space, and lines starting with `#` are comments.
+const KEYMAP_US: &str = r#"
+Backquote ` ~
+Digit1 1 !
+Digit2 2 @
+Digit3 3 #
+Digit4 4 $
+Digit5 5 %
+Digit6 6 ^
+Digit7 7 &
+Digit8 8 *
+Digit9 9 (
+Digit0 0 )
+Minus - _
+Equals = +
+Q q Q
+W w W
+E e E
+R r R
+T t T
+Y y Y
+U u U
+I i I
+O o O
+P p P
+LeftBracket [ {
+RightBracket ] }
+Backslash \ |
+A a A
+S s S
+D d D
+F f F
+G g G
+H h H
+J j J
+K k K
+L l L
+Semicolon ; :
+Quote ' "
+NonUsBackslash \ |
+Z z Z
+X x X
+C c C
+V v V
+B b B
+N n N
+M m M
+Comma , <
+Period . >
+Slash / ?
+Space space space
+"#;
+
+const KEYMAP_GB: &str = r#"
+Backquote ` ¬ ¦
+Digit1 1 !
+Digit2 2 "
+Digit3 3 £
+Digit4 4 $ €
+Digit5 5 %
+Digit6 6 ^
+Digit7 7 &
+Digit8 8 *
+Digit9 9 (
+Digit0 0 )
+Minus - _
+Equals = +
+Q q Q
+W w W
+E e E é
+R r R
+T t T
+Y y Y
+U u U ú
+I i I í
+O o O ó
+P p P
+LeftBracket [ {
+RightBracket ] }
+Backslash # ~
+A a A á
+S s S
+D d D
+F f F
+G g G
+H h H
+J j J
+K k K
+L l L
+Semicolon ; :
+Quote ' @
+NonUsBackslash \ |
+Z z Z
+X x X
+C c C
+V v V
+B b B
+N n N
+M m M
+Comma , <
+Period . >
+Slash / ?
+Space space space
+"#;
+
+const KEYMAP_DE: &str = r#"
+Backquote ^ °
+Digit1 1 !
+Digit2 2 " ²
+Digit3 3 § ³
+Digit4 4 $
+Digit5 5 %
+Digit6 6 &
+Digit7 7 / {
+Digit8 8 ( [
+Digit9 9 ) ]
+Digit0 0 = }
+Minus ß ? \
+Equals ´ `
+Q q Q @
+W w W
+E e E €
+R r R
+T t T
+Y z Z
+U u U
+I i I
+O o O
+P p P
+LeftBracket ü Ü
+RightBracket + * ~
+Backslash # '
+A a A
+S s S
+D d D
+F f F
+G g G
+H h H
+J j J
+K k K
+L l L
+Semicolon ö Ö
+Quote ä Ä
+NonUsBackslash < > |
+Z y Y
+X x X
+C c C
+V v V
+B b B
+N n N
+M m M µ
+Comma , ;
+Period . :
+Slash - _
+Space space space
+"#;
+
+const KEYMAPS: &[(&str, &str)] = &[
+    ("us", KEYMAP_US),
+    ("gb", KEYMAP_GB),
+    ("de", KEYMAP_DE)
+];
+
+static KEYMAP: Spinlock<Option<Arc<Keymap>>> = Spinlock::named("keymap", None);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeymapError {
+    UnknownLayout,
+
+    InvalidLine(usize),
+
+    Io(FsError)
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownLayout => write!(formatter, "No such keyboard layout."),
+            Self::InvalidLine(line) => write!(formatter, "Line {} of the keymap is invalid.", line),
+            Self::Io(error) => write!(formatter, "{}", error)
+        }
+    }
+}
+
+impl From<FsError> for KeymapError {
+    fn from(error: FsError) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum KeymapLevel {
+    Plain,
+
+    Shift,
+
+    AltGraph
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    name: String,
+    keys: BTreeMap<KeyCode, [Option<char>; 3]>
+}
+
+impl Keymap {
+    pub fn parse(name: &str, text: &str) -> Result<Self, KeymapError> {
+        let mut keys = BTreeMap::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut fields = line.split_whitespace();
+            let code = fields.next().and_then(KeyCode::from_name).ok_or(KeymapError::InvalidLine(index + 1))?;
+            let mut levels = [None; 3];
+
+            for level in levels.iter_mut() {
+                *level = match fields.next() {
+                    None | Some("-") => None,
+                    Some("space") => Some(' '),
+                    Some(field) => {
+                        let mut characters = field.chars();
+
+                        match (characters.next(), characters.next()) {
+                            (Some(character), None) => Some(character),
+                            _ => return Err(KeymapError::InvalidLine(index + 1))
+                        }
+                    }
+                };
+            }
+
+            if fields.next().is_some() {
+                return Err(KeymapError::InvalidLine(index + 1));
+            }
+
+            keys.insert(code, levels);
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            keys
+        })
+    }
+
+    pub fn built_in(name: &str) -> Result<Self, KeymapError> {
+        let (name, text) = KEYMAPS
+            .iter()
+            .find(|(layout, _)| *layout == name)
+            .ok_or(KeymapError::UnknownLayout)?;
+
+        Self::parse(name, text)
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    // Falls back to the plain level when the requested one is empty, as AltGr does on keys that have nothing there.
+    pub fn character(&self, code: KeyCode, level: KeymapLevel) -> Option<char> {
+        let levels = self.keys.get(&code)?;
+
+        match level {
+            KeymapLevel::Plain => levels[0],
+            KeymapLevel::Shift => levels[1].or(levels[0]),
+            KeymapLevel::AltGraph => levels[2]
+        }
+    }
+}
+
+pub fn lightsaber_kernel_built_in_keymaps() -> impl Iterator<Item = &'static str> {
+    KEYMAPS.iter().map(|(name, _)| *name)
+}
+
+pub fn lightsaber_kernel_keymap() -> Arc<Keymap> {
+    let mut keymap = KEYMAP.lock();
+
+    keymap
+        .get_or_insert_with(|| Arc::new(Keymap::built_in("us").expect("The built-in US keymap is invalid.")))
+        .clone()
+}
+
+pub fn lightsaber_kernel_set_keymap(keymap: Keymap) {
+    log::info!("Switched to the {} keyboard layout.", keymap.name());
+
+    *KEYMAP.lock() = Some(Arc::new(keymap));
+}
+
+// Keymap files use the same format as the built-in layouts and are named after the file.
+pub fn lightsaber_kernel_load_keymap(path: &str) -> Result<(), KeymapError> {
+    let data = fs::lightsaber_kernel_read_file(path)?;
+    let text = str::from_utf8(&data).map_err(|_| KeymapError::InvalidLine(1))?;
+    let name = path.rsplit('/').next().unwrap_or(path);
+    let name = name.split('.').next().unwrap_or(name);
+
+    lightsaber_kernel_set_keymap(Keymap::parse(name, text)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    use crate::fs::{
+        mount,
+        path,
+        InodeType
+    };
+
+    #[test_case]
+    fn keymap_parses_levels_comments_and_blanks() {
+        let keymap = Keymap::parse("test", "# A comment.\n\n  Q q Q @\nSpace space\nDigit1 1 - !\nA a\n").unwrap();
+
+        assert_eq!(keymap.name(), "test");
+        assert_eq!(keymap.character(KeyCode::Q, KeymapLevel::AltGraph), Some('@'));
+        assert_eq!(keymap.character(KeyCode::Space, KeymapLevel::Plain), Some(' '));
+        assert_eq!(keymap.character(KeyCode::Digit1, KeymapLevel::AltGraph), Some('!'));
+
+        // An empty shift level types the plain character, an empty AltGr level nothing.
+        assert_eq!(keymap.character(KeyCode::Digit1, KeymapLevel::Shift), Some('1'));
+        assert_eq!(keymap.character(KeyCode::A, KeymapLevel::Shift), Some('a'));
+        assert_eq!(keymap.character(KeyCode::A, KeymapLevel::AltGraph), None);
+        assert_eq!(keymap.character(KeyCode::B, KeymapLevel::Plain), None);
+    }
+
+    #[test_case]
+    fn keymap_reports_the_line_it_cannot_parse() {
+        assert_eq!(Keymap::parse("test", "A a A\nNoSuchKey x X").err(), Some(KeymapError::InvalidLine(2)));
+        assert_eq!(Keymap::parse("test", "# Comment\nA ab").err(), Some(KeymapError::InvalidLine(2)));
+        assert_eq!(Keymap::parse("test", "\n\nA a A \u{E1} x").err(), Some(KeymapError::InvalidLine(3)));
+        assert_eq!(Keymap::parse("test", "a a A").err(), Some(KeymapError::InvalidLine(1)));
+    }
+
+    #[test_case]
+    fn keymap_built_in_layouts_parse() {
+        for name in lightsaber_kernel_built_in_keymaps() {
+            assert_eq!(Keymap::built_in(name).map(|keymap| keymap.name().to_string()), Ok(name.to_string()));
+        }
+
+        assert_eq!(Keymap::built_in("xx").err(), Some(KeymapError::UnknownLayout));
+        assert_eq!(Keymap::built_in("gb").unwrap().character(KeyCode::Digit3, KeymapLevel::Shift), Some('\u{A3}'));
+    }
+
+    #[test_case]
+    fn keymap_loads_a_layout_file() {
+        // The mount table is global, so this borrows a writable directory from the running system.
+        let scratch = path::lightsaber_kernel_lookup("/tmp", true).or_else(|_| mount::lightsaber_kernel_root()).unwrap();
+        let directory = scratch.path();
+        let directory = directory.trim_end_matches('/');
+
+        scratch.inode().create("dvorak.map", InodeType::File, 0o644).unwrap().write_at(0, b"Q ' \"\nW , <\n").unwrap();
+        scratch.inode().create("broken.map", InodeType::File, 0o644).unwrap().write_at(0, b"Q \xFF\n").unwrap();
+
+        let missing = lightsaber_kernel_load_keymap(&format!("{}/missing.map", directory));
+        let broken = lightsaber_kernel_load_keymap(&format!("{}/broken.map", directory));
+        let loaded = lightsaber_kernel_load_keymap(&format!("{}/dvorak.map", directory));
+        let keymap = lightsaber_kernel_keymap();
+
+        lightsaber_kernel_set_keymap(Keymap::built_in("us").unwrap());
+
+        for name in ["dvorak.map", "broken.map"].iter() {
+            scratch.inode().unlink(name).unwrap();
+            scratch.forget(name);
+        }
+
+        assert_eq!(missing, Err(KeymapError::Io(FsError::NotFound)));
+        assert_eq!(broken, Err(KeymapError::InvalidLine(1)));
+        assert_eq!(loaded, Ok(()));
+        assert_eq!(keymap.name(), "dvorak");
+        assert_eq!(keymap.character(KeyCode::W, KeymapLevel::Shift), Some('<'));
+    }
+}
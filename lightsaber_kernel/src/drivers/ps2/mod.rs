@@ -0,0 +1,273 @@
+pub mod keyboard;
+pub mod keymap;
+pub mod mouse;
+pub mod scancode;
+
+use core::{
+    fmt,
+    hint
+};
+
+use x86_64::instructions::port::Port;
+
+const PS2_DATA: u16 = 0x60;
+const PS2_STATUS: u16 = 0x64;
+const PS2_COMMAND: u16 = 0x64;
+
+const PS2_STATUS_OUTPUT_FULL: u8 = 1 << 0;
+const PS2_STATUS_INPUT_FULL: u8 = 1 << 1;
+const PS2_STATUS_AUXILIARY: u8 = 1 << 5;
+
+const PS2_COMMAND_READ_CONFIGURATION: u8 = 0x20;
+const PS2_COMMAND_WRITE_CONFIGURATION: u8 = 0x60;
+const PS2_COMMAND_DISABLE_SECOND: u8 = 0xA7;
+const PS2_COMMAND_ENABLE_SECOND: u8 = 0xA8;
+const PS2_COMMAND_TEST_SECOND: u8 = 0xA9;
+const PS2_COMMAND_SELF_TEST: u8 = 0xAA;
+const PS2_COMMAND_TEST_FIRST: u8 = 0xAB;
+const PS2_COMMAND_DISABLE_FIRST: u8 = 0xAD;
+const PS2_COMMAND_ENABLE_FIRST: u8 = 0xAE;
+const PS2_COMMAND_WRITE_SECOND: u8 = 0xD4;
+
+const PS2_CONFIGURATION_FIRST_INTERRUPT: u8 = 1 << 0;
+const PS2_CONFIGURATION_SECOND_INTERRUPT: u8 = 1 << 1;
+const PS2_CONFIGURATION_SECOND_CLOCK_DISABLED: u8 = 1 << 5;
+const PS2_CONFIGURATION_TRANSLATION: u8 = 1 << 6;
+
+const PS2_SELF_TEST_PASSED: u8 = 0x55;
+const PS2_PORT_TEST_PASSED: u8 = 0x00;
+
+pub const PS2_DEVICE_ACKNOWLEDGE: u8 = 0xFA;
+pub const PS2_DEVICE_RESEND: u8 = 0xFE;
+pub const PS2_DEVICE_RESET: u8 = 0xFF;
+pub const PS2_DEVICE_SELF_TEST_PASSED: u8 = 0xAA;
+pub const PS2_DEVICE_ENABLE_SCANNING: u8 = 0xF4;
+pub const PS2_DEVICE_DISABLE_SCANNING: u8 = 0xF5;
+
+// Status polls before a byte is given up on; devices answer within a few milliseconds.
+const PS2_SPIN_LIMIT: usize = 200_000;
+const PS2_RETRIES: usize = 3;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Ps2Port {
+    First,
+
+    Second
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Ps2Error {
+    Timeout,
+
+    NotAcknowledged,
+
+    SelfTestFailed
+}
+
+impl fmt::Display for Ps2Error {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::Timeout => "The device did not respond.",
+            Self::NotAcknowledged => "The device refused a command.",
+            Self::SelfTestFailed => "The self test failed."
+        };
+
+        write!(formatter, "{}", description)
+    }
+}
+
+#[inline]
+fn lightsaber_kernel_ps2_status() -> u8 {
+    unsafe {
+        Port::<u8>::new(PS2_STATUS).read()
+    }
+}
+
+fn lightsaber_kernel_ps2_wait_for_input_empty() -> Result<(), Ps2Error> {
+    for _ in 0..PS2_SPIN_LIMIT {
+        if lightsaber_kernel_ps2_status() & PS2_STATUS_INPUT_FULL == 0 {
+            return Ok(());
+        }
+
+        hint::spin_loop();
+    }
+
+    Err(Ps2Error::Timeout)
+}
+
+fn lightsaber_kernel_ps2_controller_command(command: u8) -> Result<(), Ps2Error> {
+    lightsaber_kernel_ps2_wait_for_input_empty()?;
+
+    unsafe {
+        Port::<u8>::new(PS2_COMMAND).write(command);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_ps2_controller_query(command: u8) -> Result<u8, Ps2Error> {
+    lightsaber_kernel_ps2_controller_command(command)?;
+    lightsaber_kernel_ps2_read()
+}
+
+fn lightsaber_kernel_ps2_write_configuration(configuration: u8) -> Result<(), Ps2Error> {
+    lightsaber_kernel_ps2_controller_command(PS2_COMMAND_WRITE_CONFIGURATION)?;
+    lightsaber_kernel_ps2_wait_for_input_empty()?;
+
+    unsafe {
+        Port::<u8>::new(PS2_DATA).write(configuration);
+    }
+
+    Ok(())
+}
+
+// Whether the byte waiting in the output buffer came from the second port, if one is waiting at all.
+#[inline]
+pub fn lightsaber_kernel_ps2_pending() -> Option<Ps2Port> {
+    match lightsaber_kernel_ps2_status() {
+        status if status & PS2_STATUS_OUTPUT_FULL == 0 => None,
+        status if status & PS2_STATUS_AUXILIARY != 0 => Some(Ps2Port::Second),
+        _ => Some(Ps2Port::First)
+    }
+}
+
+// For interrupt handlers, which are only called once a byte is waiting.
+#[inline]
+pub fn lightsaber_kernel_ps2_read_data() -> u8 {
+    unsafe {
+        Port::<u8>::new(PS2_DATA).read()
+    }
+}
+
+pub fn lightsaber_kernel_ps2_read() -> Result<u8, Ps2Error> {
+    for _ in 0..PS2_SPIN_LIMIT {
+        if lightsaber_kernel_ps2_status() & PS2_STATUS_OUTPUT_FULL != 0 {
+            return Ok(lightsaber_kernel_ps2_read_data());
+        }
+
+        hint::spin_loop();
+    }
+
+    Err(Ps2Error::Timeout)
+}
+
+pub fn lightsaber_kernel_ps2_write(port: Ps2Port, byte: u8) -> Result<(), Ps2Error> {
+    if port == Ps2Port::Second {
+        lightsaber_kernel_ps2_controller_command(PS2_COMMAND_WRITE_SECOND)?;
+    }
+
+    lightsaber_kernel_ps2_wait_for_input_empty()?;
+
+    unsafe {
+        Port::<u8>::new(PS2_DATA).write(byte);
+    }
+
+    Ok(())
+}
+
+// Sends a byte to a device and waits for it to be acknowledged, resending when asked to.
+pub fn lightsaber_kernel_ps2_send(port: Ps2Port, byte: u8) -> Result<(), Ps2Error> {
+    for _ in 0..PS2_RETRIES {
+        lightsaber_kernel_ps2_write(port, byte)?;
+
+        match lightsaber_kernel_ps2_read()? {
+            PS2_DEVICE_ACKNOWLEDGE => return Ok(()),
+            PS2_DEVICE_RESEND => continue,
+            _ => return Err(Ps2Error::NotAcknowledged)
+        }
+    }
+
+    Err(Ps2Error::NotAcknowledged)
+}
+
+pub fn lightsaber_kernel_ps2_reset_device(port: Ps2Port) -> Result<(), Ps2Error> {
+    lightsaber_kernel_ps2_send(port, PS2_DEVICE_RESET)?;
+
+    match lightsaber_kernel_ps2_read()? {
+        PS2_DEVICE_SELF_TEST_PASSED => Ok(()),
+        _ => Err(Ps2Error::SelfTestFailed)
+    }
+}
+
+// Drops whatever the devices sent that nobody asked for.
+fn lightsaber_kernel_ps2_flush() {
+    for _ in 0..PS2_SPIN_LIMIT {
+        if lightsaber_kernel_ps2_status() & PS2_STATUS_OUTPUT_FULL == 0 {
+            return;
+        }
+
+        lightsaber_kernel_ps2_read_data();
+    }
+}
+
+fn lightsaber_kernel_ps2_initialize_controller() -> Result<(bool, bool), Ps2Error> {
+    lightsaber_kernel_ps2_controller_command(PS2_COMMAND_DISABLE_FIRST)?;
+    lightsaber_kernel_ps2_controller_command(PS2_COMMAND_DISABLE_SECOND)?;
+    lightsaber_kernel_ps2_flush();
+
+    // Interrupts stay off until the devices are set up, and scancodes arrive untranslated.
+    let configuration = lightsaber_kernel_ps2_controller_query(PS2_COMMAND_READ_CONFIGURATION)?
+        & !(PS2_CONFIGURATION_FIRST_INTERRUPT | PS2_CONFIGURATION_SECOND_INTERRUPT | PS2_CONFIGURATION_TRANSLATION);
+
+    lightsaber_kernel_ps2_write_configuration(configuration)?;
+
+    if lightsaber_kernel_ps2_controller_query(PS2_COMMAND_SELF_TEST)? != PS2_SELF_TEST_PASSED {
+        return Err(Ps2Error::SelfTestFailed);
+    }
+
+    // Some controllers come out of the self test with their configuration reset.
+    lightsaber_kernel_ps2_write_configuration(configuration)?;
+
+    lightsaber_kernel_ps2_controller_command(PS2_COMMAND_ENABLE_SECOND)?;
+    let dual_channel = lightsaber_kernel_ps2_controller_query(PS2_COMMAND_READ_CONFIGURATION)? & PS2_CONFIGURATION_SECOND_CLOCK_DISABLED == 0;
+    lightsaber_kernel_ps2_controller_command(PS2_COMMAND_DISABLE_SECOND)?;
+
+    let first = lightsaber_kernel_ps2_controller_query(PS2_COMMAND_TEST_FIRST)? == PS2_PORT_TEST_PASSED;
+    let second = dual_channel && lightsaber_kernel_ps2_controller_query(PS2_COMMAND_TEST_SECOND)? == PS2_PORT_TEST_PASSED;
+
+    if first {
+        lightsaber_kernel_ps2_controller_command(PS2_COMMAND_ENABLE_FIRST)?;
+    }
+
+    if second {
+        lightsaber_kernel_ps2_controller_command(PS2_COMMAND_ENABLE_SECOND)?;
+    }
+
+    Ok((first, second))
+}
+
+pub fn lightsaber_kernel_initialize_ps2() {
+    let (first, second) = match lightsaber_kernel_ps2_initialize_controller() {
+        Ok(ports) => ports,
+        Err(error) => {
+            log::warn!("No usable PS/2 controller: {}", error);
+
+            return;
+        }
+    };
+
+    let mut interrupts = 0;
+
+    if first {
+        match keyboard::lightsaber_kernel_initialize_keyboard() {
+            Ok(()) => interrupts |= PS2_CONFIGURATION_FIRST_INTERRUPT,
+            Err(error) => log::warn!("Failed to initialize the PS/2 keyboard: {}", error)
+        }
+    }
+
+    if second {
+        match mouse::lightsaber_kernel_initialize_mouse() {
+            Ok(()) => interrupts |= PS2_CONFIGURATION_SECOND_INTERRUPT,
+            Err(error) => log::warn!("Failed to initialize the PS/2 mouse: {}", error)
+        }
+    }
+
+    lightsaber_kernel_ps2_flush();
+
+    let configuration = lightsaber_kernel_ps2_controller_query(PS2_COMMAND_READ_CONFIGURATION)
+        .and_then(|configuration| lightsaber_kernel_ps2_write_configuration(configuration | interrupts));
+
+    if let Err(error) = configuration {
+        log::warn!("Failed to enable PS/2 interrupts: {}", error);
+    }
+}
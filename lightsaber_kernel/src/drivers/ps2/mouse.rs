@@ -0,0 +1,202 @@
+use crate::{
+    architecture::interrupts::irq,
+    drivers::ps2::{
+        self,
+        Ps2Error,
+        Ps2Port,
+        PS2_DEVICE_ENABLE_SCANNING
+    },
+    input::{
+        self,
+        InputEvent,
+        MouseButton
+    },
+    sync::Spinlock
+};
+
+const MOUSE_SET_RESOLUTION: u8 = 0xE8;
+const MOUSE_IDENTIFY: u8 = 0xF2;
+const MOUSE_SET_SAMPLE_RATE: u8 = 0xF3;
+
+const MOUSE_ID_STANDARD: u8 = 0x00;
+const MOUSE_ID_WHEEL: u8 = 0x03;
+const MOUSE_ID_FIVE_BUTTONS: u8 = 0x04;
+
+const MOUSE_SAMPLE_RATE: u8 = 100;
+// Four counts per millimetre.
+const MOUSE_RESOLUTION: u8 = 0x02;
+
+const PACKET_LEFT: u8 = 1 << 0;
+const PACKET_RIGHT: u8 = 1 << 1;
+const PACKET_MIDDLE: u8 = 1 << 2;
+const PACKET_ALWAYS_SET: u8 = 1 << 3;
+const PACKET_X_SIGN: u8 = 1 << 4;
+const PACKET_Y_SIGN: u8 = 1 << 5;
+const PACKET_X_OVERFLOW: u8 = 1 << 6;
+const PACKET_Y_OVERFLOW: u8 = 1 << 7;
+
+const PACKET_FOURTH: u8 = 1 << 4;
+const PACKET_FIFTH: u8 = 1 << 5;
+
+struct Mouse {
+    id: u8,
+    packet: [u8; 4],
+    received: usize,
+    buttons: u8
+}
+
+impl Mouse {
+    #[inline]
+    fn packet_size(&self) -> usize {
+        match self.id {
+            MOUSE_ID_STANDARD => 3,
+            _ => 4
+        }
+    }
+
+    // Turns a complete packet into events, reporting only the buttons that changed.
+    fn decode(&mut self) {
+        let flags = self.packet[0];
+
+        // Overflowed movement is garbage, so it is dropped rather than reported as a jump.
+        let dx = match flags & PACKET_X_OVERFLOW {
+            0 => self.packet[1] as i32 - (((flags & PACKET_X_SIGN) as i32) << 4),
+            _ => 0
+        };
+
+        let dy = match flags & PACKET_Y_OVERFLOW {
+            0 => self.packet[2] as i32 - (((flags & PACKET_Y_SIGN) as i32) << 3),
+            _ => 0
+        };
+
+        let (scroll, extra) = match self.id {
+            MOUSE_ID_WHEEL => (self.packet[3] as i8 as i32, 0),
+            // The wheel only has the low four bits here, sign extended.
+            MOUSE_ID_FIVE_BUTTONS => ((((self.packet[3] & 0x0F) << 4) as i8 >> 4) as i32, self.packet[3] & (PACKET_FOURTH | PACKET_FIFTH)),
+            _ => (0, 0)
+        };
+
+        if dx != 0 || dy != 0 {
+            // The mouse counts y upwards.
+            input::lightsaber_kernel_push_input_event(InputEvent::PointerMotion {
+                dx,
+                dy: -dy
+            });
+        }
+
+        let buttons = flags & (PACKET_LEFT | PACKET_RIGHT | PACKET_MIDDLE) | extra << 3;
+        let changed = buttons ^ self.buttons;
+
+        let mapping = [
+            (PACKET_LEFT, MouseButton::Left),
+            (PACKET_RIGHT, MouseButton::Right),
+            (PACKET_MIDDLE, MouseButton::Middle),
+            (PACKET_FOURTH << 3, MouseButton::Fourth),
+            (PACKET_FIFTH << 3, MouseButton::Fifth)
+        ];
+
+        for (mask, button) in mapping.iter() {
+            if changed & mask != 0 {
+                input::lightsaber_kernel_push_input_event(InputEvent::PointerButton {
+                    button: *button,
+                    pressed: buttons & mask != 0
+                });
+            }
+        }
+
+        self.buttons = buttons;
+
+        // The wheel counts towards the user.
+        if scroll != 0 {
+            input::lightsaber_kernel_push_input_event(InputEvent::PointerScroll {
+                delta: -scroll
+            });
+        }
+    }
+}
+
+static MOUSE: Spinlock<Mouse> = Spinlock::named("mouse", Mouse {
+    id: MOUSE_ID_STANDARD,
+    packet: [0; 4],
+    received: 0,
+    buttons: 0
+});
+
+fn lightsaber_kernel_mouse_interrupt() {
+    if ps2::lightsaber_kernel_ps2_pending() != Some(Ps2Port::Second) {
+        return;
+    }
+
+    let byte = ps2::lightsaber_kernel_ps2_read_data();
+    let mut mouse = MOUSE.lock();
+
+    // A first byte without its always-set bit means a byte was lost; wait for the next packet to start.
+    if mouse.received == 0 && byte & PACKET_ALWAYS_SET == 0 {
+        return;
+    }
+
+    let index = mouse.received;
+
+    mouse.packet[index] = byte;
+    mouse.received += 1;
+
+    if mouse.received == mouse.packet_size() {
+        mouse.received = 0;
+        mouse.decode();
+    }
+}
+
+fn lightsaber_kernel_mouse_identify() -> Result<u8, Ps2Error> {
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, MOUSE_IDENTIFY)?;
+    ps2::lightsaber_kernel_ps2_read()
+}
+
+fn lightsaber_kernel_mouse_set_sample_rate(rate: u8) -> Result<(), Ps2Error> {
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, MOUSE_SET_SAMPLE_RATE)?;
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, rate)
+}
+
+// IntelliMouse extensions are unlocked by knocking with particular sample rates.
+fn lightsaber_kernel_mouse_knock(rates: &[u8]) -> Result<u8, Ps2Error> {
+    for rate in rates {
+        lightsaber_kernel_mouse_set_sample_rate(*rate)?;
+    }
+
+    lightsaber_kernel_mouse_identify()
+}
+
+pub fn lightsaber_kernel_initialize_mouse() -> Result<(), Ps2Error> {
+    ps2::lightsaber_kernel_ps2_reset_device(Ps2Port::Second)?;
+
+    // After its self test a mouse also sends its ID.
+    ps2::lightsaber_kernel_ps2_read()?;
+
+    let mut id = lightsaber_kernel_mouse_knock(&[200, 100, 80])?;
+
+    if id == MOUSE_ID_WHEEL {
+        id = lightsaber_kernel_mouse_knock(&[200, 200, 80])?;
+    }
+
+    lightsaber_kernel_mouse_set_sample_rate(MOUSE_SAMPLE_RATE)?;
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, MOUSE_SET_RESOLUTION)?;
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, MOUSE_RESOLUTION)?;
+
+    MOUSE.lock().id = match id {
+        MOUSE_ID_WHEEL | MOUSE_ID_FIVE_BUTTONS => id,
+        _ => MOUSE_ID_STANDARD
+    };
+
+    ps2::lightsaber_kernel_ps2_send(Ps2Port::Second, PS2_DEVICE_ENABLE_SCANNING)?;
+    irq::lightsaber_kernel_register_irq_handler(irq::IRQ_MOUSE, lightsaber_kernel_mouse_interrupt);
+
+    log::info!(
+        "Initialized PS/2 mouse ({}).",
+        match id {
+            MOUSE_ID_WHEEL => "IntelliMouse with wheel",
+            MOUSE_ID_FIVE_BUTTONS => "IntelliMouse Explorer with wheel and five buttons",
+            _ => "standard"
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,699 @@
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum KeyCode {
+    Escape,
+
+    F1,
+
+    F2,
+
+    F3,
+
+    F4,
+
+    F5,
+
+    F6,
+
+    F7,
+
+    F8,
+
+    F9,
+
+    F10,
+
+    F11,
+
+    F12,
+
+    Backquote,
+
+    Digit1,
+
+    Digit2,
+
+    Digit3,
+
+    Digit4,
+
+    Digit5,
+
+    Digit6,
+
+    Digit7,
+
+    Digit8,
+
+    Digit9,
+
+    Digit0,
+
+    Minus,
+
+    Equals,
+
+    Backspace,
+
+    Tab,
+
+    Q,
+
+    W,
+
+    E,
+
+    R,
+
+    T,
+
+    Y,
+
+    U,
+
+    I,
+
+    O,
+
+    P,
+
+    LeftBracket,
+
+    RightBracket,
+
+    Backslash,
+
+    CapsLock,
+
+    A,
+
+    S,
+
+    D,
+
+    F,
+
+    G,
+
+    H,
+
+    J,
+
+    K,
+
+    L,
+
+    Semicolon,
+
+    Quote,
+
+    Enter,
+
+    LeftShift,
+
+    NonUsBackslash,
+
+    Z,
+
+    X,
+
+    C,
+
+    V,
+
+    B,
+
+    N,
+
+    M,
+
+    Comma,
+
+    Period,
+
+    Slash,
+
+    RightShift,
+
+    LeftControl,
+
+    LeftSuper,
+
+    LeftAlt,
+
+    Space,
+
+    RightAlt,
+
+    RightSuper,
+
+    Menu,
+
+    RightControl,
+
+    PrintScreen,
+
+    ScrollLock,
+
+    Pause,
+
+    Insert,
+
+    Home,
+
+    PageUp,
+
+    Delete,
+
+    End,
+
+    PageDown,
+
+    Up,
+
+    Left,
+
+    Down,
+
+    Right,
+
+    NumLock,
+
+    KeypadDivide,
+
+    KeypadMultiply,
+
+    KeypadMinus,
+
+    KeypadPlus,
+
+    KeypadEnter,
+
+    KeypadPeriod,
+
+    Keypad0,
+
+    Keypad1,
+
+    Keypad2,
+
+    Keypad3,
+
+    Keypad4,
+
+    Keypad5,
+
+    Keypad6,
+
+    Keypad7,
+
+    Keypad8,
+
+    Keypad9
+}
+
+impl KeyCode {
+    pub const ALL: &'static [KeyCode] = &[
+        Self::Escape, Self::F1, Self::F2, Self::F3, Self::F4, Self::F5, Self::F6, Self::F7, Self::F8, Self::F9, Self::F10, Self::F11, Self::F12,
+        Self::Backquote, Self::Digit1, Self::Digit2, Self::Digit3, Self::Digit4, Self::Digit5, Self::Digit6, Self::Digit7, Self::Digit8, Self::Digit9, Self::Digit0,
+        Self::Minus, Self::Equals, Self::Backspace, Self::Tab, Self::Q, Self::W, Self::E, Self::R, Self::T, Self::Y, Self::U, Self::I, Self::O, Self::P,
+        Self::LeftBracket, Self::RightBracket, Self::Backslash, Self::CapsLock, Self::A, Self::S, Self::D, Self::F, Self::G, Self::H, Self::J, Self::K, Self::L,
+        Self::Semicolon, Self::Quote, Self::Enter, Self::LeftShift, Self::NonUsBackslash, Self::Z, Self::X, Self::C, Self::V, Self::B, Self::N, Self::M,
+        Self::Comma, Self::Period, Self::Slash, Self::RightShift, Self::LeftControl, Self::LeftSuper, Self::LeftAlt, Self::Space, Self::RightAlt,
+        Self::RightSuper, Self::Menu, Self::RightControl, Self::PrintScreen, Self::ScrollLock, Self::Pause, Self::Insert, Self::Home, Self::PageUp,
+        Self::Delete, Self::End, Self::PageDown, Self::Up, Self::Left, Self::Down, Self::Right, Self::NumLock, Self::KeypadDivide, Self::KeypadMultiply,
+        Self::KeypadMinus, Self::KeypadPlus, Self::KeypadEnter, Self::KeypadPeriod, Self::Keypad0, Self::Keypad1, Self::Keypad2, Self::Keypad3,
+        Self::Keypad4, Self::Keypad5, Self::Keypad6, Self::Keypad7, Self::Keypad8, Self::Keypad9
+    ];
+
+    // Keys are named in keymaps exactly as the variants are.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|code| alloc::format!("{:?}", code) == name)
+    }
+}
+
+impl fmt::Display for KeyCode {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, formatter)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct KeyEvent {
+    pub code: KeyCode,
+    pub pressed: bool
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ScancodeSet {
+    Set1,
+
+    Set2
+}
+
+const SCANCODE_EXTENDED: u8 = 0xE0;
+const SCANCODE_PAUSE: u8 = 0xE1;
+const SCANCODE_SET_2_RELEASE: u8 = 0xF0;
+const SCANCODE_SET_1_RELEASE: u8 = 0x80;
+
+// Pause sends a fixed sequence with no release; these are the bytes that follow its first one.
+const SCANCODE_SET_1_PAUSE_LENGTH: u8 = 5;
+const SCANCODE_SET_2_PAUSE_LENGTH: u8 = 7;
+
+// Turns the bytes a keyboard sends into key presses and releases.
+pub struct ScancodeDecoder {
+    set: ScancodeSet,
+    extended: bool,
+    released: bool,
+    pause_remaining: u8
+}
+
+impl ScancodeDecoder {
+    pub const fn new(set: ScancodeSet) -> Self {
+        Self {
+            set,
+            extended: false,
+            released: false,
+            pause_remaining: 0
+        }
+    }
+
+    pub fn set_set(&mut self, set: ScancodeSet) {
+        *self = Self::new(set);
+    }
+
+    pub fn feed(&mut self, byte: u8) -> Option<KeyEvent> {
+        if self.pause_remaining > 0 {
+            self.pause_remaining -= 1;
+
+            return match self.pause_remaining {
+                0 => Some(KeyEvent {
+                    code: KeyCode::Pause,
+                    pressed: true
+                }),
+                _ => None
+            };
+        }
+
+        match byte {
+            SCANCODE_EXTENDED => {
+                self.extended = true;
+
+                return None;
+            }
+            SCANCODE_PAUSE => {
+                self.pause_remaining = match self.set {
+                    ScancodeSet::Set1 => SCANCODE_SET_1_PAUSE_LENGTH,
+                    ScancodeSet::Set2 => SCANCODE_SET_2_PAUSE_LENGTH
+                };
+
+                return None;
+            }
+            SCANCODE_SET_2_RELEASE if self.set == ScancodeSet::Set2 => {
+                self.released = true;
+
+                return None;
+            }
+            _ => { }
+        }
+
+        let extended = self.extended;
+        let released = self.released;
+
+        self.extended = false;
+        self.released = false;
+
+        match self.set {
+            ScancodeSet::Set1 => {
+                let code = match extended {
+                    true => lightsaber_kernel_set_1_extended_key(byte & !SCANCODE_SET_1_RELEASE),
+                    false => lightsaber_kernel_set_1_key(byte & !SCANCODE_SET_1_RELEASE)
+                }?;
+
+                Some(KeyEvent {
+                    code,
+                    pressed: byte & SCANCODE_SET_1_RELEASE == 0
+                })
+            }
+            ScancodeSet::Set2 => {
+                let code = match extended {
+                    true => lightsaber_kernel_set_2_extended_key(byte),
+                    false => lightsaber_kernel_set_2_key(byte)
+                }?;
+
+                Some(KeyEvent {
+                    code,
+                    pressed: !released
+                })
+            }
+        }
+    }
+}
+
+fn lightsaber_kernel_set_1_key(scancode: u8) -> Option<KeyCode> {
+    let code = match scancode {
+        0x01 => KeyCode::Escape,
+        0x02 => KeyCode::Digit1,
+        0x03 => KeyCode::Digit2,
+        0x04 => KeyCode::Digit3,
+        0x05 => KeyCode::Digit4,
+        0x06 => KeyCode::Digit5,
+        0x07 => KeyCode::Digit6,
+        0x08 => KeyCode::Digit7,
+        0x09 => KeyCode::Digit8,
+        0x0A => KeyCode::Digit9,
+        0x0B => KeyCode::Digit0,
+        0x0C => KeyCode::Minus,
+        0x0D => KeyCode::Equals,
+        0x0E => KeyCode::Backspace,
+        0x0F => KeyCode::Tab,
+        0x10 => KeyCode::Q,
+        0x11 => KeyCode::W,
+        0x12 => KeyCode::E,
+        0x13 => KeyCode::R,
+        0x14 => KeyCode::T,
+        0x15 => KeyCode::Y,
+        0x16 => KeyCode::U,
+        0x17 => KeyCode::I,
+        0x18 => KeyCode::O,
+        0x19 => KeyCode::P,
+        0x1A => KeyCode::LeftBracket,
+        0x1B => KeyCode::RightBracket,
+        0x1C => KeyCode::Enter,
+        0x1D => KeyCode::LeftControl,
+        0x1E => KeyCode::A,
+        0x1F => KeyCode::S,
+        0x20 => KeyCode::D,
+        0x21 => KeyCode::F,
+        0x22 => KeyCode::G,
+        0x23 => KeyCode::H,
+        0x24 => KeyCode::J,
+        0x25 => KeyCode::K,
+        0x26 => KeyCode::L,
+        0x27 => KeyCode::Semicolon,
+        0x28 => KeyCode::Quote,
+        0x29 => KeyCode::Backquote,
+        0x2A => KeyCode::LeftShift,
+        0x2B => KeyCode::Backslash,
+        0x2C => KeyCode::Z,
+        0x2D => KeyCode::X,
+        0x2E => KeyCode::C,
+        0x2F => KeyCode::V,
+        0x30 => KeyCode::B,
+        0x31 => KeyCode::N,
+        0x32 => KeyCode::M,
+        0x33 => KeyCode::Comma,
+        0x34 => KeyCode::Period,
+        0x35 => KeyCode::Slash,
+        0x36 => KeyCode::RightShift,
+        0x37 => KeyCode::KeypadMultiply,
+        0x38 => KeyCode::LeftAlt,
+        0x39 => KeyCode::Space,
+        0x3A => KeyCode::CapsLock,
+        0x3B => KeyCode::F1,
+        0x3C => KeyCode::F2,
+        0x3D => KeyCode::F3,
+        0x3E => KeyCode::F4,
+        0x3F => KeyCode::F5,
+        0x40 => KeyCode::F6,
+        0x41 => KeyCode::F7,
+        0x42 => KeyCode::F8,
+        0x43 => KeyCode::F9,
+        0x44 => KeyCode::F10,
+        0x45 => KeyCode::NumLock,
+        0x46 => KeyCode::ScrollLock,
+        0x47 => KeyCode::Keypad7,
+        0x48 => KeyCode::Keypad8,
+        0x49 => KeyCode::Keypad9,
+        0x4A => KeyCode::KeypadMinus,
+        0x4B => KeyCode::Keypad4,
+        0x4C => KeyCode::Keypad5,
+        0x4D => KeyCode::Keypad6,
+        0x4E => KeyCode::KeypadPlus,
+        0x4F => KeyCode::Keypad1,
+        0x50 => KeyCode::Keypad2,
+        0x51 => KeyCode::Keypad3,
+        0x52 => KeyCode::Keypad0,
+        0x53 => KeyCode::KeypadPeriod,
+        0x56 => KeyCode::NonUsBackslash,
+        0x57 => KeyCode::F11,
+        0x58 => KeyCode::F12,
+        _ => return None
+    };
+
+    Some(code)
+}
+
+// The shift codes some keys wrap themselves in (0x2A and 0x36 after 0xE0) are not keys.
+fn lightsaber_kernel_set_1_extended_key(scancode: u8) -> Option<KeyCode> {
+    let code = match scancode {
+        0x1C => KeyCode::KeypadEnter,
+        0x1D => KeyCode::RightControl,
+        0x35 => KeyCode::KeypadDivide,
+        0x37 => KeyCode::PrintScreen,
+        0x38 => KeyCode::RightAlt,
+        0x47 => KeyCode::Home,
+        0x48 => KeyCode::Up,
+        0x49 => KeyCode::PageUp,
+        0x4B => KeyCode::Left,
+        0x4D => KeyCode::Right,
+        0x4F => KeyCode::End,
+        0x50 => KeyCode::Down,
+        0x51 => KeyCode::PageDown,
+        0x52 => KeyCode::Insert,
+        0x53 => KeyCode::Delete,
+        0x5B => KeyCode::LeftSuper,
+        0x5C => KeyCode::RightSuper,
+        0x5D => KeyCode::Menu,
+        _ => return None
+    };
+
+    Some(code)
+}
+
+fn lightsaber_kernel_set_2_key(scancode: u8) -> Option<KeyCode> {
+    let code = match scancode {
+        0x01 => KeyCode::F9,
+        0x03 => KeyCode::F5,
+        0x04 => KeyCode::F3,
+        0x05 => KeyCode::F1,
+        0x06 => KeyCode::F2,
+        0x07 => KeyCode::F12,
+        0x09 => KeyCode::F10,
+        0x0A => KeyCode::F8,
+        0x0B => KeyCode::F6,
+        0x0C => KeyCode::F4,
+        0x0D => KeyCode::Tab,
+        0x0E => KeyCode::Backquote,
+        0x11 => KeyCode::LeftAlt,
+        0x12 => KeyCode::LeftShift,
+        0x14 => KeyCode::LeftControl,
+        0x15 => KeyCode::Q,
+        0x16 => KeyCode::Digit1,
+        0x1A => KeyCode::Z,
+        0x1B => KeyCode::S,
+        0x1C => KeyCode::A,
+        0x1D => KeyCode::W,
+        0x1E => KeyCode::Digit2,
+        0x21 => KeyCode::C,
+        0x22 => KeyCode::X,
+        0x23 => KeyCode::D,
+        0x24 => KeyCode::E,
+        0x25 => KeyCode::Digit4,
+        0x26 => KeyCode::Digit3,
+        0x29 => KeyCode::Space,
+        0x2A => KeyCode::V,
+        0x2B => KeyCode::F,
+        0x2C => KeyCode::T,
+        0x2D => KeyCode::R,
+        0x2E => KeyCode::Digit5,
+        0x31 => KeyCode::N,
+        0x32 => KeyCode::B,
+        0x33 => KeyCode::H,
+        0x34 => KeyCode::G,
+        0x35 => KeyCode::Y,
+        0x36 => KeyCode::Digit6,
+        0x3A => KeyCode::M,
+        0x3B => KeyCode::J,
+        0x3C => KeyCode::U,
+        0x3D => KeyCode::Digit7,
+        0x3E => KeyCode::Digit8,
+        0x41 => KeyCode::Comma,
+        0x42 => KeyCode::K,
+        0x43 => KeyCode::I,
+        0x44 => KeyCode::O,
+        0x45 => KeyCode::Digit0,
+        0x46 => KeyCode::Digit9,
+        0x49 => KeyCode::Period,
+        0x4A => KeyCode::Slash,
+        0x4B => KeyCode::L,
+        0x4C => KeyCode::Semicolon,
+        0x4D => KeyCode::P,
+        0x4E => KeyCode::Minus,
+        0x52 => KeyCode::Quote,
+        0x54 => KeyCode::LeftBracket,
+        0x55 => KeyCode::Equals,
+        0x58 => KeyCode::CapsLock,
+        0x59 => KeyCode::RightShift,
+        0x5A => KeyCode::Enter,
+        0x5B => KeyCode::RightBracket,
+        0x5D => KeyCode::Backslash,
+        0x61 => KeyCode::NonUsBackslash,
+        0x66 => KeyCode::Backspace,
+        0x69 => KeyCode::Keypad1,
+        0x6B => KeyCode::Keypad4,
+        0x6C => KeyCode::Keypad7,
+        0x70 => KeyCode::Keypad0,
+        0x71 => KeyCode::KeypadPeriod,
+        0x72 => KeyCode::Keypad2,
+        0x73 => KeyCode::Keypad5,
+        0x74 => KeyCode::Keypad6,
+        0x75 => KeyCode::Keypad8,
+        0x76 => KeyCode::Escape,
+        0x77 => KeyCode::NumLock,
+        0x78 => KeyCode::F11,
+        0x79 => KeyCode::KeypadPlus,
+        0x7A => KeyCode::Keypad3,
+        0x7B => KeyCode::KeypadMinus,
+        0x7C => KeyCode::KeypadMultiply,
+        0x7D => KeyCode::Keypad9,
+        0x7E => KeyCode::ScrollLock,
+        0x83 => KeyCode::F7,
+        _ => return None
+    };
+
+    Some(code)
+}
+
+// As in set 1, 0x12 and 0x59 after 0xE0 are fake shifts around some keys.
+fn lightsaber_kernel_set_2_extended_key(scancode: u8) -> Option<KeyCode> {
+    let code = match scancode {
+        0x11 => KeyCode::RightAlt,
+        0x14 => KeyCode::RightControl,
+        0x1F => KeyCode::LeftSuper,
+        0x27 => KeyCode::RightSuper,
+        0x2F => KeyCode::Menu,
+        0x4A => KeyCode::KeypadDivide,
+        0x5A => KeyCode::KeypadEnter,
+        0x69 => KeyCode::End,
+        0x6B => KeyCode::Left,
+        0x6C => KeyCode::Home,
+        0x70 => KeyCode::Insert,
+        0x71 => KeyCode::Delete,
+        0x72 => KeyCode::Down,
+        0x74 => KeyCode::Right,
+        0x75 => KeyCode::Up,
+        0x7A => KeyCode::PageDown,
+        0x7C => KeyCode::PrintScreen,
+        0x7D => KeyCode::PageUp,
+        _ => return None
+    };
+
+    Some(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    fn lightsaber_kernel_test_feed(decoder: &mut ScancodeDecoder, bytes: &[u8]) -> Vec<KeyEvent> {
+        bytes.iter().filter_map(|byte| decoder.feed(*byte)).collect()
+    }
+
+    fn lightsaber_kernel_test_event(code: KeyCode, pressed: bool) -> KeyEvent {
+        KeyEvent {
+            code,
+            pressed
+        }
+    }
+
+    #[test_case]
+    fn scancode_set_2_reads_releases_after_f0() {
+        let mut decoder = ScancodeDecoder::new(ScancodeSet::Set2);
+
+        assert_eq!(lightsaber_kernel_test_feed(&mut decoder, &[0x1C, 0xF0, 0x1C, 0x11, 0xF0, 0x11]), [
+            lightsaber_kernel_test_event(KeyCode::A, true),
+            lightsaber_kernel_test_event(KeyCode::A, false),
+            lightsaber_kernel_test_event(KeyCode::LeftAlt, true),
+            lightsaber_kernel_test_event(KeyCode::LeftAlt, false)
+        ]);
+    }
+
+    #[test_case]
+    fn scancode_set_2_reads_extended_keys_after_e0() {
+        let mut decoder = ScancodeDecoder::new(ScancodeSet::Set2);
+
+        // Print Screen wraps itself in a fake left shift, which is dropped.
+        assert_eq!(lightsaber_kernel_test_feed(&mut decoder, &[0xE0, 0x11, 0xE0, 0x75, 0xE0, 0xF0, 0x75, 0xE0, 0x12, 0xE0, 0x7C, 0xE0, 0xF0, 0x7C, 0xE0, 0xF0, 0x12, 0x75]), [
+            lightsaber_kernel_test_event(KeyCode::RightAlt, true),
+            lightsaber_kernel_test_event(KeyCode::Up, true),
+            lightsaber_kernel_test_event(KeyCode::Up, false),
+            lightsaber_kernel_test_event(KeyCode::PrintScreen, true),
+            lightsaber_kernel_test_event(KeyCode::PrintScreen, false),
+            lightsaber_kernel_test_event(KeyCode::Keypad8, true)
+        ]);
+    }
+
+    #[test_case]
+    fn scancode_set_1_reads_releases_from_the_high_bit() {
+        let mut decoder = ScancodeDecoder::new(ScancodeSet::Set1);
+
+        assert_eq!(lightsaber_kernel_test_feed(&mut decoder, &[0x1E, 0x9E, 0xE0, 0x48, 0xE0, 0xC8, 0xE0, 0x2A, 0xE0, 0x37, 0x48]), [
+            lightsaber_kernel_test_event(KeyCode::A, true),
+            lightsaber_kernel_test_event(KeyCode::A, false),
+            lightsaber_kernel_test_event(KeyCode::Up, true),
+            lightsaber_kernel_test_event(KeyCode::Up, false),
+            lightsaber_kernel_test_event(KeyCode::PrintScreen, true),
+            lightsaber_kernel_test_event(KeyCode::Keypad8, true)
+        ]);
+
+        // 0xF0 is only a prefix in set 2.
+        assert_eq!(decoder.feed(0xF0), None);
+        assert_eq!(decoder.feed(0x1E), Some(lightsaber_kernel_test_event(KeyCode::A, true)));
+    }
+
+    #[test_case]
+    fn scancode_pause_is_one_press_in_either_set() {
+        let mut decoder = ScancodeDecoder::new(ScancodeSet::Set1);
+
+        // Its sequence contains what would otherwise read as left control and num lock.
+        assert_eq!(lightsaber_kernel_test_feed(&mut decoder, &[0xE1, 0x1D, 0x45, 0xE1, 0x9D, 0xC5, 0x1E]), [
+            lightsaber_kernel_test_event(KeyCode::Pause, true),
+            lightsaber_kernel_test_event(KeyCode::A, true)
+        ]);
+
+        decoder.set_set(ScancodeSet::Set2);
+
+        assert_eq!(lightsaber_kernel_test_feed(&mut decoder, &[0xE1, 0x14, 0x77, 0xE1, 0xF0, 0x14, 0xF0, 0x77, 0x1C]), [
+            lightsaber_kernel_test_event(KeyCode::Pause, true),
+            lightsaber_kernel_test_event(KeyCode::A, true)
+        ]);
+    }
+
+    #[test_case]
+    fn scancode_switching_sets_drops_a_pending_prefix() {
+        let mut decoder = ScancodeDecoder::new(ScancodeSet::Set2);
+
+        assert_eq!(decoder.feed(0xE0), None);
+        assert_eq!(decoder.feed(0xF0), None);
+
+        decoder.set_set(ScancodeSet::Set1);
+
+        assert_eq!(decoder.set, ScancodeSet::Set1);
+        assert_eq!(decoder.feed(0x48), Some(lightsaber_kernel_test_event(KeyCode::Keypad8, true)));
+        assert_eq!(decoder.feed(0x54), None);
+    }
+
+    #[test_case]
+    fn scancode_key_names_match_the_variants() {
+        for code in KeyCode::ALL {
+            assert_eq!(KeyCode::from_name(&alloc::format!("{}", code)), Some(*code));
+        }
+
+        assert_eq!(KeyCode::from_name("a"), None);
+        assert_eq!(KeyCode::from_name(""), None);
+    }
+}
@@ -0,0 +1,233 @@
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use x86_64::instructions::port::Port;
+
+use crate::{
+    architecture::interrupts::irq,
+    cmdline::{
+        param,
+        ParameterValue
+    },
+    console,
+    gdb,
+    sync::TicketLock
+};
+
+const SERIAL_PRIMARY: u16 = 0x3F8;
+
+const SERIAL_DATA: u16 = 0;
+const SERIAL_INTERRUPT_ENABLE: u16 = 1;
+const SERIAL_FIFO_CONTROL: u16 = 2;
+const SERIAL_LINE_CONTROL: u16 = 3;
+const SERIAL_MODEM_CONTROL: u16 = 4;
+const SERIAL_LINE_STATUS: u16 = 5;
+
+// With the divisor latch set, the first two registers hold the baud rate divisor instead.
+const SERIAL_DIVISOR_LOW: u16 = 0;
+const SERIAL_DIVISOR_HIGH: u16 = 1;
+
+const SERIAL_INTERRUPT_DATA_AVAILABLE: u8 = 1 << 0;
+
+// Enabled and cleared, raising an interrupt once 14 bytes are waiting.
+const SERIAL_FIFO_ENABLE: u8 = 0xC7;
+
+const SERIAL_LINE_DIVISOR_LATCH: u8 = 1 << 7;
+// Eight data bits, no parity and one stop bit.
+const SERIAL_LINE_8N1: u8 = 0x03;
+
+const SERIAL_MODEM_DATA_TERMINAL_READY: u8 = 1 << 0;
+const SERIAL_MODEM_REQUEST_TO_SEND: u8 = 1 << 1;
+const SERIAL_MODEM_OUT1: u8 = 1 << 2;
+// Gates the UART's interrupt line on PC-compatible boards.
+const SERIAL_MODEM_OUT2: u8 = 1 << 3;
+const SERIAL_MODEM_LOOPBACK: u8 = 1 << 4;
+
+const SERIAL_STATUS_DATA_READY: u8 = 1 << 0;
+const SERIAL_STATUS_TRANSMITTER_EMPTY: u8 = 1 << 5;
+
+// The UART's clock divided by 16; the divisor brings it down to the baud rate.
+const SERIAL_BASE_BAUD_RATE: u32 = 115_200;
+const SERIAL_LOOPBACK_TEST: u8 = 0xAE;
+const SERIAL_TRANSMIT_ATTEMPTS: usize = 100_000;
+
+// What GDB sends to interrupt the running kernel.
+const SERIAL_DEBUGGER_INTERRUPT: u8 = 0x03;
+
+// `serial=off` leaves the line alone, and `serial=<baud>` picks a rate that divides 115200.
+param!(pub SERIAL: SerialSetting = "serial");
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SerialSetting {
+    Off,
+
+    BaudRate(u32)
+}
+
+impl ParameterValue for SerialSetting {
+    fn parse(value: Option<&'static str>) -> Option<Self> {
+        match value? {
+            "off" => Some(Self::Off),
+            baud_rate => baud_rate
+                .parse()
+                .ok()
+                .filter(|baud_rate| *baud_rate != 0 && SERIAL_BASE_BAUD_RATE % baud_rate == 0 && SERIAL_BASE_BAUD_RATE / baud_rate <= u16::MAX as u32)
+                .map(Self::BaudRate)
+        }
+    }
+}
+
+static SERIAL_PRESENT: AtomicBool = AtomicBool::new(false);
+// While a debugger owns the line, console and log output stay off it.
+static SERIAL_DEBUGGER: AtomicBool = AtomicBool::new(false);
+// Processors take turns on the line, so none of them has its output held back indefinitely.
+static SERIAL_TRANSMIT: TicketLock<()> = TicketLock::named("serial_transmit", ());
+
+#[inline]
+fn lightsaber_kernel_serial_read_register(register: u16) -> u8 {
+    unsafe {
+        Port::<u8>::new(SERIAL_PRIMARY + register).read()
+    }
+}
+
+#[inline]
+fn lightsaber_kernel_serial_write_register(register: u16, value: u8) {
+    unsafe {
+        Port::<u8>::new(SERIAL_PRIMARY + register).write(value);
+    }
+}
+
+#[inline]
+pub fn lightsaber_kernel_serial_present() -> bool {
+    SERIAL_PRESENT.load(Ordering::Acquire)
+}
+
+pub fn lightsaber_kernel_serial_set_debugger(attached: bool) {
+    SERIAL_DEBUGGER.store(attached, Ordering::Release);
+}
+
+// A line with nothing listening on it is dropped rather than left to stall the kernel.
+fn lightsaber_kernel_serial_transmit(byte: u8) {
+    for _ in 0..SERIAL_TRANSMIT_ATTEMPTS {
+        if lightsaber_kernel_serial_read_register(SERIAL_LINE_STATUS) & SERIAL_STATUS_TRANSMITTER_EMPTY != 0 {
+            lightsaber_kernel_serial_write_register(SERIAL_DATA, byte);
+
+            return;
+        }
+
+        hint::spin_loop();
+    }
+}
+
+// Sends bytes exactly as given, for the debugger's own framing. It skips the transmit lock, as the
+// debugger may have stopped the kernel while that was held and the output it interrupts is off anyway.
+pub fn lightsaber_kernel_serial_write_raw(bytes: &[u8]) {
+    if !lightsaber_kernel_serial_present() {
+        return;
+    }
+
+    for byte in bytes {
+        lightsaber_kernel_serial_transmit(*byte);
+    }
+}
+
+// Terminals expect a carriage return before every line feed.
+pub fn lightsaber_kernel_serial_write(bytes: &[u8]) {
+    if !lightsaber_kernel_serial_present() || SERIAL_DEBUGGER.load(Ordering::Acquire) {
+        return;
+    }
+
+    let _guard = SERIAL_TRANSMIT.lock();
+
+    for byte in bytes {
+        if *byte == b'\n' {
+            lightsaber_kernel_serial_transmit(b'\r');
+        }
+
+        lightsaber_kernel_serial_transmit(*byte);
+    }
+}
+
+pub fn lightsaber_kernel_serial_try_read() -> Option<u8> {
+    if !lightsaber_kernel_serial_present() || lightsaber_kernel_serial_read_register(SERIAL_LINE_STATUS) & SERIAL_STATUS_DATA_READY == 0 {
+        return None;
+    }
+
+    Some(lightsaber_kernel_serial_read_register(SERIAL_DATA))
+}
+
+fn lightsaber_kernel_serial_interrupt() {
+    while let Some(byte) = lightsaber_kernel_serial_try_read() {
+        // The debugger reads its packets itself once the kernel is stopped; until then it can only ask to stop.
+        if SERIAL_DEBUGGER.load(Ordering::Acquire) {
+            if byte == SERIAL_DEBUGGER_INTERRUPT {
+                gdb::lightsaber_kernel_gdb_interrupt();
+            }
+
+            continue;
+        }
+
+        // Terminals send a carriage return for Enter; readers see a line feed, as from the keyboard.
+        let byte = match byte {
+            b'\r' => b'\n',
+            byte => byte
+        };
+
+        console::lightsaber_kernel_console_input(&[byte]);
+    }
+}
+
+fn lightsaber_kernel_serial_baud_rate() -> u32 {
+    match SERIAL.get() {
+        Some(SerialSetting::BaudRate(baud_rate)) => baud_rate,
+        _ => SERIAL_BASE_BAUD_RATE
+    }
+}
+
+// Runs before anything else logs, so the whole boot log reaches the serial line.
+pub fn lightsaber_kernel_initialize_serial() {
+    if SERIAL.get() == Some(SerialSetting::Off) {
+        return;
+    }
+
+    let divisor = (SERIAL_BASE_BAUD_RATE / lightsaber_kernel_serial_baud_rate()) as u16;
+
+    lightsaber_kernel_serial_write_register(SERIAL_INTERRUPT_ENABLE, 0);
+    lightsaber_kernel_serial_write_register(SERIAL_LINE_CONTROL, SERIAL_LINE_DIVISOR_LATCH);
+    lightsaber_kernel_serial_write_register(SERIAL_DIVISOR_LOW, divisor as u8);
+    lightsaber_kernel_serial_write_register(SERIAL_DIVISOR_HIGH, (divisor >> 8) as u8);
+    lightsaber_kernel_serial_write_register(SERIAL_LINE_CONTROL, SERIAL_LINE_8N1);
+    lightsaber_kernel_serial_write_register(SERIAL_FIFO_CONTROL, SERIAL_FIFO_ENABLE);
+
+    // Without a UART the port reads back whatever the bus floats to, not the byte looped back.
+    lightsaber_kernel_serial_write_register(SERIAL_MODEM_CONTROL, SERIAL_MODEM_REQUEST_TO_SEND | SERIAL_MODEM_OUT1 | SERIAL_MODEM_OUT2 | SERIAL_MODEM_LOOPBACK);
+    lightsaber_kernel_serial_write_register(SERIAL_DATA, SERIAL_LOOPBACK_TEST);
+
+    if lightsaber_kernel_serial_read_register(SERIAL_DATA) != SERIAL_LOOPBACK_TEST {
+        return;
+    }
+
+    lightsaber_kernel_serial_write_register(
+        SERIAL_MODEM_CONTROL,
+        SERIAL_MODEM_DATA_TERMINAL_READY | SERIAL_MODEM_REQUEST_TO_SEND | SERIAL_MODEM_OUT1 | SERIAL_MODEM_OUT2
+    );
+
+    SERIAL_PRESENT.store(true, Ordering::Release);
+}
+
+// Input needs the interrupt controller, which is set up after the serial line.
+pub fn lightsaber_kernel_initialize_serial_input() {
+    if !lightsaber_kernel_serial_present() {
+        return;
+    }
+
+    irq::lightsaber_kernel_register_irq_handler(irq::IRQ_SERIAL_PRIMARY, lightsaber_kernel_serial_interrupt);
+    lightsaber_kernel_serial_write_register(SERIAL_INTERRUPT_ENABLE, SERIAL_INTERRUPT_DATA_AVAILABLE);
+
+    log::info!("Initialized serial console on COM1 at {} baud.", lightsaber_kernel_serial_baud_rate());
+}
@@ -0,0 +1,415 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::interrupts::{
+        self,
+        irq
+    },
+    block::{
+        self,
+        BlockDevice,
+        BlockError,
+        BlockOperation,
+        BlockRequest,
+        BlockStatistics,
+        RequestQueue
+    },
+    drivers::virtio::{
+        queue::{
+            Virtqueue,
+            VirtqueueBuffer
+        },
+        VirtioTransport,
+        VIRTIO_MSI_NO_VECTOR,
+        VIRTIO_VENDOR_ID
+    },
+    memory::dma::{
+        self,
+        DmaBuffer
+    },
+    pci::{
+        self,
+        PciDevice,
+        PciDeviceId,
+        PciDriver,
+        PciError
+    },
+    sync::Spinlock
+};
+
+const VIRTIO_BLOCK_LEGACY_DEVICE_ID: u16 = 0x1001;
+const VIRTIO_BLOCK_MODERN_DEVICE_ID: u16 = 0x1042;
+
+const VIRTIO_BLOCK_F_SIZE_MAX: u64 = 1 << 1;
+const VIRTIO_BLOCK_F_SEG_MAX: u64 = 1 << 2;
+const VIRTIO_BLOCK_F_RO: u64 = 1 << 5;
+const VIRTIO_BLOCK_F_BLK_SIZE: u64 = 1 << 6;
+const VIRTIO_BLOCK_F_FLUSH: u64 = 1 << 9;
+
+const VIRTIO_BLOCK_CONFIG_CAPACITY: u16 = 0;
+const VIRTIO_BLOCK_CONFIG_SIZE_MAX: u16 = 8;
+const VIRTIO_BLOCK_CONFIG_SEG_MAX: u16 = 12;
+const VIRTIO_BLOCK_CONFIG_BLK_SIZE: u16 = 20;
+
+const VIRTIO_BLOCK_T_IN: u32 = 0;
+const VIRTIO_BLOCK_T_OUT: u32 = 1;
+const VIRTIO_BLOCK_T_FLUSH: u32 = 4;
+
+const VIRTIO_BLOCK_S_OK: u8 = 0;
+const VIRTIO_BLOCK_S_PENDING: u8 = 0xFF;
+
+// Capacity and request sectors are always in these units, whatever the logical block size.
+const VIRTIO_SECTOR_SIZE: usize = 512;
+
+const PREFERRED_QUEUE_SIZE: u16 = 128;
+const MAXIMUM_SEGMENTS: usize = 16;
+const REQUEST_HEADER_SIZE: usize = 16;
+const REQUEST_SLOT_SIZE: usize = 32;
+const REQUEST_STATUS_OFFSET: usize = 16;
+
+const VIRTIO_BLOCK_IDS: &[PciDeviceId] = &[
+    PciDeviceId::device(VIRTIO_VENDOR_ID, VIRTIO_BLOCK_LEGACY_DEVICE_ID),
+    PciDeviceId::device(VIRTIO_VENDOR_ID, VIRTIO_BLOCK_MODERN_DEVICE_ID)
+];
+
+static VIRTIO_BLOCK_DRIVER: PciDriver = PciDriver {
+    name: "virtio-blk",
+    ids: VIRTIO_BLOCK_IDS,
+    probe: lightsaber_kernel_probe_virtio_block
+};
+
+static VIRTIO_BLOCK_DEVICES: Spinlock<BTreeMap<usize, Arc<VirtioBlock>>> = Spinlock::named("virtio_block_devices", BTreeMap::new());
+static NEXT_DISK_INDEX: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum VirtioBlockInterrupt {
+    Msix,
+
+    Legacy,
+
+    Polled
+}
+
+struct VirtioBlockRing {
+    queue: Virtqueue,
+    in_flight: Vec<Option<Arc<BlockRequest>>>
+}
+
+pub struct VirtioBlock {
+    name: String,
+    transport: VirtioTransport,
+    interrupt: VirtioBlockInterrupt,
+    sector_size: usize,
+    sector_count: u64,
+    read_only: bool,
+    flush_supported: bool,
+    maximum_segments: usize,
+    maximum_segment_size: usize,
+    ring: Spinlock<VirtioBlockRing>,
+    // One header and status byte per descriptor, used by the request whose chain starts there.
+    slots: DmaBuffer,
+    pending: RequestQueue,
+    statistics: Arc<BlockStatistics>
+}
+
+impl VirtioBlock {
+    fn buffers(&self, request: &BlockRequest, slot: usize) -> Result<Vec<VirtqueueBuffer>, BlockError> {
+        let kind = match request.operation() {
+            BlockOperation::Read => VIRTIO_BLOCK_T_IN,
+            BlockOperation::Write if self.read_only => return Err(BlockError::ReadOnly),
+            BlockOperation::Write => VIRTIO_BLOCK_T_OUT,
+            BlockOperation::Flush if !self.flush_supported => return Err(BlockError::NotReady),
+            BlockOperation::Flush => VIRTIO_BLOCK_T_FLUSH
+        };
+
+        let header = slot * REQUEST_SLOT_SIZE;
+
+        self.slots.write::<u32>(header, kind);
+        self.slots.write::<u32>(header + 4, 0);
+        self.slots.write::<u64>(header + 8, request.sector() * (self.sector_size / VIRTIO_SECTOR_SIZE) as u64);
+        self.slots.write::<u8>(header + REQUEST_STATUS_OFFSET, VIRTIO_BLOCK_S_PENDING);
+
+        let mut buffers = alloc::vec![VirtqueueBuffer {
+            address: self.slots.physical() + header,
+            length: REQUEST_HEADER_SIZE as u32,
+            device_writable: false
+        }];
+
+        if request.operation() != BlockOperation::Flush {
+            let data = request.data();
+            let segments = dma::lightsaber_kernel_physical_segments(&data).ok_or(BlockError::Io)?;
+
+            for (address, length) in segments {
+                let mut offset = 0;
+
+                while offset < length {
+                    let chunk = (length - offset).min(self.maximum_segment_size);
+
+                    buffers.push(VirtqueueBuffer {
+                        address: address + offset,
+                        length: chunk as u32,
+                        device_writable: request.operation() == BlockOperation::Read
+                    });
+
+                    offset += chunk;
+                }
+            }
+
+            if buffers.len() - 1 > self.maximum_segments {
+                log::warn!("A request to {} needs {} segments, more than the {} supported.", self.name, buffers.len() - 1, self.maximum_segments);
+
+                return Err(BlockError::Io);
+            }
+        }
+
+        buffers.push(VirtqueueBuffer {
+            address: self.slots.physical() + header + REQUEST_STATUS_OFFSET,
+            length: 1,
+            device_writable: true
+        });
+
+        Ok(buffers)
+    }
+
+    // Moves pending requests onto the ring for as long as there is room.
+    fn start(&self) {
+        let mut ring = self.ring.lock();
+        let mut started = false;
+
+        while let Some(request) = self.pending.next() {
+            let slot = ring.queue.next_head() as usize;
+
+            let head = match self.buffers(&request, slot) {
+                Ok(buffers) => ring.queue.push(&buffers),
+                Err(error) => {
+                    self.pending.finish(&request, Err(error));
+
+                    continue;
+                }
+            };
+
+            match head {
+                Some(head) => {
+                    ring.in_flight[head as usize] = Some(request);
+                    started = true;
+                }
+                None => self.pending.finish(&request, Err(BlockError::Io))
+            }
+        }
+
+        if started {
+            self.transport.notify(&ring.queue);
+        }
+    }
+
+    fn complete(&self) {
+        let completed = {
+            let mut ring = self.ring.lock();
+            let mut completed = Vec::new();
+
+            while let Some((head, _)) = ring.queue.pop_used() {
+                if let Some(request) = ring.in_flight[head as usize].take() {
+                    let status = match self.slots.read::<u8>(head as usize * REQUEST_SLOT_SIZE + REQUEST_STATUS_OFFSET) {
+                        VIRTIO_BLOCK_S_OK => Ok(()),
+                        _ => Err(BlockError::Io)
+                    };
+
+                    completed.push((request, status));
+                }
+            }
+
+            completed
+        };
+
+        for (request, status) in completed {
+            self.pending.finish(&request, status);
+        }
+
+        self.start();
+    }
+}
+
+impl BlockDevice for VirtioBlock {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn sector_count(&self) -> u64 {
+        self.sector_count
+    }
+
+    fn statistics(&self) -> &Arc<BlockStatistics> {
+        &self.statistics
+    }
+
+    fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    fn submit_request(&self, request: Arc<BlockRequest>) {
+        self.pending.push(request.clone());
+        self.start();
+
+        // Before interrupts are on, for example while partitions are scanned at boot, completion has to be polled for.
+        if self.interrupt == VirtioBlockInterrupt::Polled || !interrupts::lightsaber_kernel_interrupts_enabled() {
+            while request.status().is_none() {
+                self.complete();
+                hint::spin_loop();
+            }
+        }
+    }
+}
+
+fn lightsaber_kernel_virtio_block_interrupt(index: usize) {
+    let device = VIRTIO_BLOCK_DEVICES.lock().get(&index).cloned();
+
+    if let Some(device) = device {
+        device.complete();
+    }
+}
+
+// Legacy interrupt lines may be shared, so every device on one is asked whether it raised it.
+fn lightsaber_kernel_virtio_block_legacy_interrupt() {
+    let devices = VIRTIO_BLOCK_DEVICES.lock().values().cloned().collect::<Vec<Arc<VirtioBlock>>>();
+
+    for device in devices {
+        if device.interrupt == VirtioBlockInterrupt::Legacy && device.transport.read_isr() & 1 != 0 {
+            device.complete();
+        }
+    }
+}
+
+fn lightsaber_kernel_probe_virtio_block(device: &Arc<PciDevice>) -> Result<(), PciError> {
+    device.enable_bus_mastering();
+
+    let mut transport = VirtioTransport::new(device)?;
+    let features = transport.negotiate(VIRTIO_BLOCK_F_SIZE_MAX | VIRTIO_BLOCK_F_SEG_MAX | VIRTIO_BLOCK_F_RO | VIRTIO_BLOCK_F_BLK_SIZE | VIRTIO_BLOCK_F_FLUSH)?;
+    let index = NEXT_DISK_INDEX.fetch_add(1, Ordering::AcqRel);
+
+    // MSI-X is preferred, then the legacy interrupt line, and polling when neither can be had.
+    let (interrupt, vectors) = match device.enable_msix(&[(lightsaber_kernel_virtio_block_interrupt, index)]) {
+        Ok(vectors) => {
+            transport.set_msix_enabled(true);
+            transport.set_config_vector(VIRTIO_MSI_NO_VECTOR);
+
+            (VirtioBlockInterrupt::Msix, vectors)
+        }
+        Err(_) if device.interrupt_pin != 0 && device.interrupt_line < 16 => (VirtioBlockInterrupt::Legacy, Vec::new()),
+        Err(_) => (VirtioBlockInterrupt::Polled, Vec::new())
+    };
+
+    let fail = |transport: &VirtioTransport, error: PciError| {
+        transport.fail();
+        device.disable_msix(&vectors);
+
+        Err(error)
+    };
+
+    let queue_size = transport.queue_size(0, PREFERRED_QUEUE_SIZE);
+
+    if queue_size <= 2 {
+        return fail(&transport, PciError::DeviceFailed);
+    }
+
+    let mut queue = match Virtqueue::new(0, queue_size) {
+        Some(queue) => queue,
+        None => return fail(&transport, PciError::DeviceFailed)
+    };
+
+    let vector = match interrupt {
+        VirtioBlockInterrupt::Msix => 0,
+        _ => VIRTIO_MSI_NO_VECTOR
+    };
+
+    if let Err(error) = transport.setup_queue(&mut queue, vector) {
+        return fail(&transport, error);
+    }
+
+    let slots = match DmaBuffer::new(queue_size as usize * REQUEST_SLOT_SIZE) {
+        Some(slots) => slots,
+        None => return fail(&transport, PciError::DeviceFailed)
+    };
+
+    let sector_size = match features & VIRTIO_BLOCK_F_BLK_SIZE {
+        0 => VIRTIO_SECTOR_SIZE,
+        _ => match transport.read_config_u32(VIRTIO_BLOCK_CONFIG_BLK_SIZE) as usize {
+            size if size >= VIRTIO_SECTOR_SIZE && size % VIRTIO_SECTOR_SIZE == 0 => size,
+            _ => VIRTIO_SECTOR_SIZE
+        }
+    };
+
+    let maximum_segments = match features & VIRTIO_BLOCK_F_SEG_MAX {
+        0 => MAXIMUM_SEGMENTS,
+        _ => (transport.read_config_u32(VIRTIO_BLOCK_CONFIG_SEG_MAX) as usize).clamp(1, MAXIMUM_SEGMENTS)
+    }.min(queue_size as usize - 2);
+
+    let maximum_segment_size = match features & VIRTIO_BLOCK_F_SIZE_MAX {
+        0 => usize::MAX,
+        _ => (transport.read_config_u32(VIRTIO_BLOCK_CONFIG_SIZE_MAX) as usize).max(VIRTIO_SECTOR_SIZE)
+    };
+
+    let capacity = transport.read_config_u64(VIRTIO_BLOCK_CONFIG_CAPACITY);
+
+    let block = Arc::new(VirtioBlock {
+        name: format!("vd{}", (b'a' + (index % 26) as u8) as char),
+        interrupt,
+        sector_size,
+        sector_count: capacity * VIRTIO_SECTOR_SIZE as u64 / sector_size as u64,
+        read_only: features & VIRTIO_BLOCK_F_RO != 0,
+        flush_supported: features & VIRTIO_BLOCK_F_FLUSH != 0,
+        maximum_segments,
+        maximum_segment_size,
+        ring: Spinlock::named("virtio_block_ring", VirtioBlockRing {
+            in_flight: (0..queue_size).map(|_| None).collect(),
+            queue
+        }),
+        slots,
+        pending: RequestQueue::new((queue_size as usize / (maximum_segments + 2)).max(1)),
+        statistics: Arc::new(BlockStatistics::default()),
+        transport
+    });
+
+    VIRTIO_BLOCK_DEVICES.lock().insert(index, block.clone());
+
+    if interrupt == VirtioBlockInterrupt::Legacy {
+        irq::lightsaber_kernel_register_irq_handler(device.interrupt_line, lightsaber_kernel_virtio_block_legacy_interrupt);
+    }
+
+    block.transport.finish_initialization();
+
+    log::info!(
+        "Found virtio block device {} with {} sectors of {} bytes ({} transport, {:?} interrupts{}).",
+        block.name,
+        block.sector_count,
+        block.sector_size,
+        if block.transport.is_legacy() { "legacy" } else { "modern" },
+        interrupt,
+        if block.read_only { ", read-only" } else { "" }
+    );
+
+    block::lightsaber_kernel_register_block_device(block);
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_initialize_virtio_block() {
+    pci::lightsaber_kernel_register_pci_driver(&VIRTIO_BLOCK_DRIVER);
+}
@@ -0,0 +1,384 @@
+use x86_64::instructions::port::Port;
+
+use crate::{
+    drivers::virtio::queue::Virtqueue,
+    memory::mmio::MmioRegion,
+    pci::{
+        PciBar,
+        PciDevice,
+        PciError,
+        PCI_CAPABILITY_VENDOR,
+        PCI_COMMAND_IO_SPACE
+    }
+};
+
+pub mod block;
+pub mod queue;
+
+pub const VIRTIO_VENDOR_ID: u16 = 0x1AF4;
+
+pub const VIRTIO_STATUS_ACKNOWLEDGE: u8 = 1 << 0;
+pub const VIRTIO_STATUS_DRIVER: u8 = 1 << 1;
+pub const VIRTIO_STATUS_DRIVER_OK: u8 = 1 << 2;
+pub const VIRTIO_STATUS_FEATURES_OK: u8 = 1 << 3;
+pub const VIRTIO_STATUS_FAILED: u8 = 1 << 7;
+
+pub const VIRTIO_F_VERSION_1: u64 = 1 << 32;
+
+pub const VIRTIO_MSI_NO_VECTOR: u16 = 0xFFFF;
+
+const VIRTIO_PCI_CAPABILITY_COMMON: u8 = 1;
+const VIRTIO_PCI_CAPABILITY_NOTIFY: u8 = 2;
+const VIRTIO_PCI_CAPABILITY_ISR: u8 = 3;
+const VIRTIO_PCI_CAPABILITY_DEVICE: u8 = 4;
+
+const LEGACY_DEVICE_FEATURES: u16 = 0x00;
+const LEGACY_DRIVER_FEATURES: u16 = 0x04;
+const LEGACY_QUEUE_ADDRESS: u16 = 0x08;
+const LEGACY_QUEUE_SIZE: u16 = 0x0C;
+const LEGACY_QUEUE_SELECT: u16 = 0x0E;
+const LEGACY_QUEUE_NOTIFY: u16 = 0x10;
+const LEGACY_DEVICE_STATUS: u16 = 0x12;
+const LEGACY_ISR_STATUS: u16 = 0x13;
+const LEGACY_CONFIG_VECTOR: u16 = 0x14;
+const LEGACY_QUEUE_VECTOR: u16 = 0x16;
+const LEGACY_DEVICE_CONFIG: u16 = 0x14;
+const LEGACY_DEVICE_CONFIG_MSIX: u16 = 0x18;
+const LEGACY_QUEUE_ADDRESS_SHIFT: u64 = 12;
+
+const COMMON_DEVICE_FEATURE_SELECT: u64 = 0x00;
+const COMMON_DEVICE_FEATURE: u64 = 0x04;
+const COMMON_DRIVER_FEATURE_SELECT: u64 = 0x08;
+const COMMON_DRIVER_FEATURE: u64 = 0x0C;
+const COMMON_CONFIG_VECTOR: u64 = 0x10;
+const COMMON_DEVICE_STATUS: u64 = 0x14;
+const COMMON_QUEUE_SELECT: u64 = 0x16;
+const COMMON_QUEUE_SIZE: u64 = 0x18;
+const COMMON_QUEUE_VECTOR: u64 = 0x1A;
+const COMMON_QUEUE_ENABLE: u64 = 0x1C;
+const COMMON_QUEUE_NOTIFY_OFFSET: u64 = 0x1E;
+const COMMON_QUEUE_DESCRIPTORS: u64 = 0x20;
+const COMMON_QUEUE_DRIVER: u64 = 0x28;
+const COMMON_QUEUE_DEVICE: u64 = 0x30;
+
+// Transitional devices offer both interfaces; the modern one is used whenever it is there.
+pub enum VirtioTransport {
+    Legacy {
+        base: u16,
+        msix: bool
+    },
+
+    Modern {
+        common: MmioRegion,
+        notify: MmioRegion,
+        notify_multiplier: u32,
+        isr: MmioRegion,
+        device: MmioRegion
+    }
+}
+
+impl VirtioTransport {
+    pub fn new(device: &PciDevice) -> Result<Self, PciError> {
+        match Self::modern(device) {
+            Ok(transport) => Ok(transport),
+            Err(_) => match device.bar(0) {
+                Some(PciBar::Io { port, .. }) => {
+                    device.enable(PCI_COMMAND_IO_SPACE);
+
+                    Ok(Self::Legacy {
+                        base: port,
+                        msix: false
+                    })
+                }
+                _ => Err(PciError::Unsupported)
+            }
+        }
+    }
+
+    fn modern(device: &PciDevice) -> Result<Self, PciError> {
+        let mut common = None;
+        let mut notify = None;
+        let mut isr = None;
+        let mut device_config = None;
+        let mut mapped: [Option<MmioRegion>; 6] = [None; 6];
+
+        for (id, offset) in device.capabilities() {
+            if id != PCI_CAPABILITY_VENDOR {
+                continue;
+            }
+
+            let kind = device.read_u8(offset + 3);
+            let bar = device.read_u8(offset + 4) as usize;
+            let start = device.read_u32(offset + 8) as u64;
+            let length = device.read_u32(offset + 12) as u64;
+
+            let slot = match kind {
+                VIRTIO_PCI_CAPABILITY_COMMON => &mut common,
+                VIRTIO_PCI_CAPABILITY_NOTIFY => &mut notify,
+                VIRTIO_PCI_CAPABILITY_ISR => &mut isr,
+                VIRTIO_PCI_CAPABILITY_DEVICE => &mut device_config,
+                _ => continue
+            };
+
+            // The first capability of each kind is the preferred one.
+            if slot.is_none() {
+                let bar_region = match mapped.get(bar).copied().ok_or(PciError::InvalidBar)? {
+                    Some(region) => region,
+                    None => device.map_bar(bar)?
+                };
+
+                mapped[bar] = Some(bar_region);
+
+                let region = bar_region.subregion(start, length).ok_or(PciError::InvalidBar)?;
+
+                *slot = Some((region, offset));
+            }
+        }
+
+        match (common, notify, isr, device_config) {
+            (Some((common, _)), Some((notify, notify_capability)), Some((isr, _)), Some((device_config, _))) => Ok(Self::Modern {
+                common,
+                notify,
+                notify_multiplier: device.read_u32(notify_capability + 16),
+                isr,
+                device: device_config
+            }),
+            _ => Err(PciError::CapabilityMissing)
+        }
+    }
+
+    #[inline]
+    pub fn is_legacy(&self) -> bool {
+        matches!(self, Self::Legacy { .. })
+    }
+
+    unsafe fn legacy_port<T>(base: u16, offset: u16) -> Port<T> {
+        Port::new(base + offset)
+    }
+
+    pub fn status(&self) -> u8 {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u8>(*base, LEGACY_DEVICE_STATUS).read()
+            },
+            Self::Modern { common, .. } => common.read_u8(COMMON_DEVICE_STATUS)
+        }
+    }
+
+    pub fn set_status(&self, status: u8) {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u8>(*base, LEGACY_DEVICE_STATUS).write(status)
+            },
+            Self::Modern { common, .. } => common.write_u8(COMMON_DEVICE_STATUS, status)
+        }
+    }
+
+    #[inline]
+    pub fn add_status(&self, status: u8) {
+        self.set_status(self.status() | status);
+    }
+
+    pub fn reset(&self) {
+        self.set_status(0);
+
+        // The device acknowledges the reset by reading back zero.
+        while self.status() != 0 {
+            core::hint::spin_loop();
+        }
+    }
+
+    pub fn device_features(&self) -> u64 {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u32>(*base, LEGACY_DEVICE_FEATURES).read() as u64
+            },
+            Self::Modern { common, .. } => {
+                common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 0);
+                let low = common.read_u32(COMMON_DEVICE_FEATURE) as u64;
+
+                common.write_u32(COMMON_DEVICE_FEATURE_SELECT, 1);
+                let high = common.read_u32(COMMON_DEVICE_FEATURE) as u64;
+
+                high << 32 | low
+            }
+        }
+    }
+
+    fn set_driver_features(&self, features: u64) {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u32>(*base, LEGACY_DRIVER_FEATURES).write(features as u32)
+            },
+            Self::Modern { common, .. } => {
+                common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 0);
+                common.write_u32(COMMON_DRIVER_FEATURE, features as u32);
+
+                common.write_u32(COMMON_DRIVER_FEATURE_SELECT, 1);
+                common.write_u32(COMMON_DRIVER_FEATURE, (features >> 32) as u32);
+            }
+        }
+    }
+
+    // Resets the device and agrees on the wanted features it offers, leaving it ready for queue setup.
+    pub fn negotiate(&self, wanted: u64) -> Result<u64, PciError> {
+        self.reset();
+        self.add_status(VIRTIO_STATUS_ACKNOWLEDGE | VIRTIO_STATUS_DRIVER);
+
+        let features = match self {
+            Self::Legacy { .. } => self.device_features() & wanted & 0xFFFF_FFFF,
+            Self::Modern { .. } => self.device_features() & (wanted | VIRTIO_F_VERSION_1)
+        };
+
+        self.set_driver_features(features);
+
+        if let Self::Modern { .. } = self {
+            self.add_status(VIRTIO_STATUS_FEATURES_OK);
+
+            if features & VIRTIO_F_VERSION_1 == 0 || self.status() & VIRTIO_STATUS_FEATURES_OK == 0 {
+                self.fail();
+
+                return Err(PciError::Unsupported);
+            }
+        }
+
+        Ok(features)
+    }
+
+    #[inline]
+    pub fn finish_initialization(&self) {
+        self.add_status(VIRTIO_STATUS_DRIVER_OK);
+    }
+
+    #[inline]
+    pub fn fail(&self) {
+        self.add_status(VIRTIO_STATUS_FAILED);
+    }
+
+    // The legacy interface moves the device configuration once MSI-X is switched on.
+    pub fn set_msix_enabled(&mut self, enabled: bool) {
+        if let Self::Legacy { msix, .. } = self {
+            *msix = enabled;
+        }
+    }
+
+    pub fn set_config_vector(&self, vector: u16) -> bool {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u16>(*base, LEGACY_CONFIG_VECTOR).write(vector);
+                Self::legacy_port::<u16>(*base, LEGACY_CONFIG_VECTOR).read() == vector
+            },
+            Self::Modern { common, .. } => {
+                common.write_u16(COMMON_CONFIG_VECTOR, vector);
+                common.read_u16(COMMON_CONFIG_VECTOR) == vector
+            }
+        }
+    }
+
+    pub fn read_config_u32(&self, offset: u16) -> u32 {
+        match self {
+            Self::Legacy { base, msix } => unsafe {
+                Self::legacy_port::<u32>(*base, Self::legacy_config(*msix) + offset).read()
+            },
+            Self::Modern { device, .. } => device.read_u32(offset as u64)
+        }
+    }
+
+    pub fn read_config_u64(&self, offset: u16) -> u64 {
+        self.read_config_u32(offset) as u64 | (self.read_config_u32(offset + 4) as u64) << 32
+    }
+
+    #[inline]
+    fn legacy_config(msix: bool) -> u16 {
+        match msix {
+            true => LEGACY_DEVICE_CONFIG_MSIX,
+            false => LEGACY_DEVICE_CONFIG
+        }
+    }
+
+    // Zero means the queue does not exist.
+    pub fn maximum_queue_size(&self, index: u16) -> u16 {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u16>(*base, LEGACY_QUEUE_SELECT).write(index);
+                Self::legacy_port::<u16>(*base, LEGACY_QUEUE_SIZE).read()
+            },
+            Self::Modern { common, .. } => {
+                common.write_u16(COMMON_QUEUE_SELECT, index);
+                common.read_u16(COMMON_QUEUE_SIZE)
+            }
+        }
+    }
+
+    // Legacy devices only take a queue of exactly their own size; modern ones accept anything up to it.
+    pub fn queue_size(&self, index: u16, preferred: u16) -> u16 {
+        let maximum = self.maximum_queue_size(index);
+
+        match self {
+            Self::Legacy { .. } => maximum,
+            Self::Modern { .. } => maximum.min(preferred)
+        }
+    }
+
+    pub fn setup_queue(&self, queue: &mut Virtqueue, vector: u16) -> Result<(), PciError> {
+        match self {
+            Self::Legacy { base, msix } => unsafe {
+                Self::legacy_port::<u16>(*base, LEGACY_QUEUE_SELECT).write(queue.index());
+
+                if *msix {
+                    Self::legacy_port::<u16>(*base, LEGACY_QUEUE_VECTOR).write(vector);
+
+                    if Self::legacy_port::<u16>(*base, LEGACY_QUEUE_VECTOR).read() != vector {
+                        return Err(PciError::NoFreeVectors);
+                    }
+                }
+
+                Self::legacy_port::<u32>(*base, LEGACY_QUEUE_ADDRESS).write((queue.descriptor_address().as_u64() >> LEGACY_QUEUE_ADDRESS_SHIFT) as u32);
+            },
+            Self::Modern { common, notify_multiplier, .. } => {
+                common.write_u16(COMMON_QUEUE_SELECT, queue.index());
+                common.write_u16(COMMON_QUEUE_SIZE, queue.size());
+                common.write_u16(COMMON_QUEUE_VECTOR, vector);
+
+                if common.read_u16(COMMON_QUEUE_VECTOR) != vector {
+                    return Err(PciError::NoFreeVectors);
+                }
+
+                Self::write_common_u64(common, COMMON_QUEUE_DESCRIPTORS, queue.descriptor_address().as_u64());
+                Self::write_common_u64(common, COMMON_QUEUE_DRIVER, queue.available_address().as_u64());
+                Self::write_common_u64(common, COMMON_QUEUE_DEVICE, queue.used_address().as_u64());
+
+                queue.set_notify_offset(common.read_u16(COMMON_QUEUE_NOTIFY_OFFSET) as u64 * *notify_multiplier as u64);
+                common.write_u16(COMMON_QUEUE_ENABLE, 1);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Split in two, since devices need not support 64-bit accesses to the common configuration.
+    #[inline]
+    fn write_common_u64(common: &MmioRegion, offset: u64, value: u64) {
+        common.write_u32(offset, value as u32);
+        common.write_u32(offset + 4, (value >> 32) as u32);
+    }
+
+    pub fn notify(&self, queue: &Virtqueue) {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u16>(*base, LEGACY_QUEUE_NOTIFY).write(queue.index())
+            },
+            Self::Modern { notify, .. } => notify.write_u16(queue.notify_offset(), queue.index())
+        }
+    }
+
+    // Reading the status also acknowledges the interrupt.
+    pub fn read_isr(&self) -> u8 {
+        match self {
+            Self::Legacy { base, .. } => unsafe {
+                Self::legacy_port::<u8>(*base, LEGACY_ISR_STATUS).read()
+            },
+            Self::Modern { isr, .. } => isr.read_u8(0)
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use alloc::vec::Vec;
+
+use core::sync::atomic::{
+    fence,
+    Ordering
+};
+
+use x86_64::PhysAddr;
+
+use crate::memory::dma::DmaBuffer;
+
+pub const VIRTQ_DESCRIPTOR_NEXT: u16 = 1 << 0;
+pub const VIRTQ_DESCRIPTOR_WRITE: u16 = 1 << 1;
+
+const VIRTQ_DESCRIPTOR_SIZE: usize = 16;
+const VIRTQ_USED_ELEMENT_SIZE: usize = 8;
+const VIRTQ_LEGACY_ALIGNMENT: usize = 4096;
+
+// One piece of a request: where it is, how long, and whether the device writes to it.
+#[derive(Debug, Clone, Copy)]
+pub struct VirtqueueBuffer {
+    pub address: PhysAddr,
+    pub length: u32,
+    pub device_writable: bool
+}
+
+// A split virtqueue laid out as the legacy interface wants it, which also suits the modern one.
+pub struct Virtqueue {
+    index: u16,
+    size: u16,
+    memory: DmaBuffer,
+    available_offset: usize,
+    used_offset: usize,
+    free_head: u16,
+    free_count: u16,
+    available_index: u16,
+    last_used_index: u16,
+    chain_lengths: Vec<u16>,
+    notify_offset: u64
+}
+
+impl Virtqueue {
+    pub fn new(index: u16, size: u16) -> Option<Self> {
+        let entries = size as usize;
+        let available_offset = entries * VIRTQ_DESCRIPTOR_SIZE;
+        let used_offset = (available_offset + 6 + 2 * entries + VIRTQ_LEGACY_ALIGNMENT - 1) / VIRTQ_LEGACY_ALIGNMENT * VIRTQ_LEGACY_ALIGNMENT;
+        let memory = DmaBuffer::new(used_offset + 6 + VIRTQ_USED_ELEMENT_SIZE * entries)?;
+
+        let queue = Self {
+            index,
+            size,
+            memory,
+            available_offset,
+            used_offset,
+            free_head: 0,
+            free_count: size,
+            available_index: 0,
+            last_used_index: 0,
+            chain_lengths: alloc::vec![0; entries],
+            notify_offset: 0
+        };
+
+        // Every descriptor starts out on the free list, linked in order.
+        for descriptor in 0..size {
+            queue.memory.write::<u16>(queue.descriptor_offset(descriptor) + 14, descriptor.wrapping_add(1));
+        }
+
+        Some(queue)
+    }
+
+    #[inline]
+    pub fn index(&self) -> u16 {
+        self.index
+    }
+
+    #[inline]
+    pub fn size(&self) -> u16 {
+        self.size
+    }
+
+    // The head the next chain will start at, so per-request data can be placed before pushing.
+    #[inline]
+    pub fn next_head(&self) -> u16 {
+        self.free_head
+    }
+
+    #[inline]
+    pub fn descriptor_address(&self) -> PhysAddr {
+        self.memory.physical()
+    }
+
+    #[inline]
+    pub fn available_address(&self) -> PhysAddr {
+        self.memory.physical() + self.available_offset
+    }
+
+    #[inline]
+    pub fn used_address(&self) -> PhysAddr {
+        self.memory.physical() + self.used_offset
+    }
+
+    #[inline]
+    pub fn notify_offset(&self) -> u64 {
+        self.notify_offset
+    }
+
+    #[inline]
+    pub(in crate::drivers::virtio) fn set_notify_offset(&mut self, offset: u64) {
+        self.notify_offset = offset;
+    }
+
+    #[inline]
+    fn descriptor_offset(&self, descriptor: u16) -> usize {
+        descriptor as usize * VIRTQ_DESCRIPTOR_SIZE
+    }
+
+    // Chains the buffers together and offers them to the device, returning the head descriptor.
+    pub fn push(&mut self, buffers: &[VirtqueueBuffer]) -> Option<u16> {
+        if buffers.is_empty() || buffers.len() > self.free_count as usize {
+            return None;
+        }
+
+        let head = self.free_head;
+        let mut descriptor = head;
+
+        for (index, buffer) in buffers.iter().enumerate() {
+            let offset = self.descriptor_offset(descriptor);
+            let next = self.memory.read::<u16>(offset + 14);
+
+            let mut flags = match buffer.device_writable {
+                true => VIRTQ_DESCRIPTOR_WRITE,
+                false => 0
+            };
+
+            if index + 1 < buffers.len() {
+                flags |= VIRTQ_DESCRIPTOR_NEXT;
+            }
+
+            self.memory.write::<u64>(offset, buffer.address.as_u64());
+            self.memory.write::<u32>(offset + 8, buffer.length);
+            self.memory.write::<u16>(offset + 12, flags);
+
+            if index + 1 < buffers.len() {
+                descriptor = next;
+            } else {
+                self.free_head = next;
+            }
+        }
+
+        self.free_count -= buffers.len() as u16;
+        self.chain_lengths[head as usize] = buffers.len() as u16;
+
+        let slot = self.available_offset + 4 + 2 * (self.available_index % self.size) as usize;
+        self.memory.write::<u16>(slot, head);
+
+        // The device must see the descriptors and the ring entry before the new index.
+        fence(Ordering::SeqCst);
+
+        self.available_index = self.available_index.wrapping_add(1);
+        self.memory.write::<u16>(self.available_offset + 2, self.available_index);
+
+        fence(Ordering::SeqCst);
+
+        Some(head)
+    }
+
+    // Takes the next chain the device has finished with, freeing its descriptors.
+    pub fn pop_used(&mut self) -> Option<(u16, u32)> {
+        fence(Ordering::SeqCst);
+
+        if self.last_used_index == self.memory.read::<u16>(self.used_offset + 2) {
+            return None;
+        }
+
+        let element = self.used_offset + 4 + VIRTQ_USED_ELEMENT_SIZE * (self.last_used_index % self.size) as usize;
+        let head = self.memory.read::<u32>(element) as u16;
+        let length = self.memory.read::<u32>(element + 4);
+
+        self.last_used_index = self.last_used_index.wrapping_add(1);
+
+        let mut tail = head;
+
+        for _ in 1..self.chain_lengths[head as usize] {
+            tail = self.memory.read::<u16>(self.descriptor_offset(tail) + 14);
+        }
+
+        self.memory.write::<u16>(self.descriptor_offset(tail) + 14, self.free_head);
+        self.free_head = head;
+        self.free_count += self.chain_lengths[head as usize];
+
+        Some((head, length))
+    }
+}
@@ -0,0 +1,156 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::{
+        Arc,
+        Weak
+    },
+    vec::Vec
+};
+
+use crate::{
+    fs::{
+        mount::Mount,
+        FsError,
+        Inode,
+        InodeType
+    },
+    sync::Spinlock
+};
+
+pub struct Dentry {
+    name: String,
+    inode: Arc<dyn Inode>,
+    parent: Option<Arc<Dentry>>,
+    covered: Option<Arc<Dentry>>,
+    children: Spinlock<BTreeMap<String, Weak<Dentry>>>,
+    mounted: Spinlock<Option<Arc<Mount>>>
+}
+
+impl Dentry {
+    // The root of a mounted file system records the dentry it covers, so that `..` can climb back out of it.
+    pub fn new_root(inode: Arc<dyn Inode>, covered: Option<Arc<Dentry>>) -> Arc<Self> {
+        let name = covered
+            .as_ref()
+            .map(|covered| covered.name.clone())
+            .unwrap_or_else(|| "/".to_string());
+
+        Arc::new(Self {
+            name,
+            inode,
+            parent: None,
+            covered,
+            children: Spinlock::named("dentry_children", BTreeMap::new()),
+            mounted: Spinlock::named("dentry_mounted", None)
+        })
+    }
+
+    #[inline]
+    pub fn inode(&self) -> &Arc<dyn Inode> {
+        &self.inode
+    }
+
+    pub fn inode_type(&self) -> Result<InodeType, FsError> {
+        Ok(self.inode.metadata()?.inode_type)
+    }
+
+    pub fn lookup(self: &Arc<Self>, name: &str) -> Result<Arc<Dentry>, FsError> {
+        if let Some(child) = self.children.lock().get(name).and_then(Weak::upgrade) {
+            return Ok(child);
+        }
+
+        let inode = self.inode.lookup(name)?;
+
+        let mut children = self.children.lock();
+
+        // Someone else may have looked the same name up in the meantime.
+        if let Some(child) = children.get(name).and_then(Weak::upgrade) {
+            return Ok(child);
+        }
+
+        let child = Arc::new(Self {
+            name: name.to_string(),
+            inode,
+            parent: Some(self.clone()),
+            covered: None,
+            children: Spinlock::named("dentry_children", BTreeMap::new()),
+            mounted: Spinlock::named("dentry_mounted", None)
+        });
+
+        children.retain(|_, child| child.strong_count() != 0);
+        children.insert(name.to_string(), Arc::downgrade(&child));
+
+        Ok(child)
+    }
+
+    // The tests remove names behind the cache's back and drop what it still holds for them.
+    #[cfg(test)]
+    pub fn forget(&self, name: &str) {
+        self.children.lock().remove(name);
+    }
+
+    pub fn mounted(&self) -> Option<Arc<Mount>> {
+        self.mounted.lock().clone()
+    }
+
+    pub(in crate::fs) fn set_mounted(&self, mount: Option<Arc<Mount>>) -> Option<Arc<Mount>> {
+        core::mem::replace(&mut *self.mounted.lock(), mount)
+    }
+
+    pub fn follow_mounts(self: &Arc<Self>) -> Arc<Dentry> {
+        let mut dentry = self.clone();
+
+        while let Some(mount) = dentry.mounted() {
+            dentry = mount.root().clone();
+        }
+
+        dentry
+    }
+
+    pub fn parent(self: &Arc<Self>) -> Arc<Dentry> {
+        let mut dentry = self.clone();
+
+        while let Some(covered) = dentry.covered.clone() {
+            dentry = covered;
+        }
+
+        match &dentry.parent {
+            Some(parent) => parent.follow_mounts(),
+            None => dentry.follow_mounts()
+        }
+    }
+
+    pub fn path(self: &Arc<Self>) -> String {
+        let mut components = Vec::new();
+        let mut dentry = self.clone();
+
+        loop {
+            while let Some(covered) = dentry.covered.clone() {
+                dentry = covered;
+            }
+
+            match dentry.parent.clone() {
+                Some(parent) => {
+                    components.push(dentry.name.clone());
+                    dentry = parent;
+                }
+                None => break
+            }
+        }
+
+        let mut path = String::new();
+
+        for component in components.iter().rev() {
+            path.push('/');
+            path.push_str(component);
+        }
+
+        match path.is_empty() {
+            true => "/".to_string(),
+            false => path
+        }
+    }
+}
@@ -0,0 +1,2242 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{
+        Arc,
+        Weak
+    },
+    vec,
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use crate::{
+    block::{
+        self,
+        BlockDevice
+    },
+    cmdline::param,
+    fs::{
+        self,
+        inode::MODE_PERMISSION_MASK,
+        mount,
+        path,
+        DirectoryEntry,
+        FileSystem,
+        FsError,
+        FsUsage,
+        Inode,
+        InodeType,
+        Metadata
+    },
+    sync::{
+        Mutex,
+        Spinlock
+    }
+};
+
+const SUPERBLOCK_OFFSET: u64 = 1024;
+const SUPERBLOCK_SIZE: usize = 1024;
+const EXT2_MAGIC: u16 = 0xEF53;
+
+const STATE_VALID: u16 = 0x1;
+const STATE_ERRORS: u16 = 0x2;
+
+const GOOD_OLD_REVISION: u32 = 0;
+const GOOD_OLD_INODE_SIZE: u64 = 128;
+const GOOD_OLD_FIRST_INODE: u32 = 11;
+
+const INCOMPAT_FILETYPE: u32 = 0x2;
+const SUPPORTED_INCOMPAT: u32 = INCOMPAT_FILETYPE;
+
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+const RO_COMPAT_BTREE_DIRECTORY: u32 = 0x4;
+const SUPPORTED_RO_COMPAT: u32 = RO_COMPAT_SPARSE_SUPER | RO_COMPAT_LARGE_FILE | RO_COMPAT_BTREE_DIRECTORY;
+
+const ROOT_INODE: u32 = 2;
+const GROUP_DESCRIPTOR_SIZE: u64 = 32;
+
+const DIRECT_BLOCKS: usize = 12;
+const INDIRECT_BLOCK: usize = 12;
+const DOUBLE_INDIRECT_BLOCK: usize = 13;
+const TRIPLE_INDIRECT_BLOCK: usize = 14;
+const BLOCK_POINTERS: usize = 15;
+
+const INODE_FLAG_INDEX: u32 = 0x1000;
+const FAST_SYMLINK_MAXIMUM: usize = 60;
+const MAXIMUM_LINKS: u16 = 65000;
+const SMALL_FILE_MAXIMUM: u64 = (1 << 31) - 1;
+
+const DIRECTORY_ENTRY_HEADER_SIZE: usize = 8;
+
+const FILE_TYPE_REGULAR: u8 = 1;
+const FILE_TYPE_DIRECTORY: u8 = 2;
+const FILE_TYPE_CHARACTER_DEVICE: u8 = 3;
+const FILE_TYPE_BLOCK_DEVICE: u8 = 4;
+const FILE_TYPE_SYMLINK: u8 = 7;
+
+const MAXIMUM_REPORTED_INCONSISTENCIES: usize = 32;
+
+// `root=<device>`, a block device such as `sda2` whose ext2 file system is mounted over the initial root.
+param!(pub ROOT: &'static str = "root");
+
+fn lightsaber_kernel_read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn lightsaber_kernel_read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn lightsaber_kernel_write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn lightsaber_kernel_write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+fn lightsaber_kernel_record_length(name_length: usize) -> usize {
+    (DIRECTORY_ENTRY_HEADER_SIZE + name_length + 3) & !3
+}
+
+fn lightsaber_kernel_file_type(inode_type: InodeType) -> u8 {
+    match inode_type {
+        InodeType::File => FILE_TYPE_REGULAR,
+        InodeType::Directory => FILE_TYPE_DIRECTORY,
+        InodeType::Symlink => FILE_TYPE_SYMLINK,
+        InodeType::CharacterDevice => FILE_TYPE_CHARACTER_DEVICE,
+        InodeType::BlockDevice => FILE_TYPE_BLOCK_DEVICE
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Ext2Inconsistency {
+    FreeBlockCount {
+        recorded: u32,
+        actual: u32
+    },
+
+    FreeInodeCount {
+        recorded: u32,
+        actual: u32
+    },
+
+    GroupFreeBlocks {
+        group: u32,
+        recorded: u16,
+        actual: u32
+    },
+
+    GroupFreeInodes {
+        group: u32,
+        recorded: u16,
+        actual: u32
+    },
+
+    GroupMetadataOutOfRange {
+        group: u32
+    },
+
+    BlockOutOfRange {
+        inode: u32,
+        block: u32
+    },
+
+    BlockReferencedTwice {
+        inode: u32,
+        block: u32
+    },
+
+    BlockNotMarkedUsed {
+        inode: u32,
+        block: u32
+    },
+
+    BadDirectoryBlock {
+        directory: u32,
+        block: u32
+    },
+
+    EntryToFreeInode {
+        directory: u32,
+        inode: u32
+    },
+
+    LinkCount {
+        inode: u32,
+        recorded: u16,
+        actual: u32
+    },
+
+    UnreachableInode {
+        inode: u32
+    }
+}
+
+impl fmt::Display for Ext2Inconsistency {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::FreeBlockCount { recorded, actual } => write!(formatter, "The superblock records {} free blocks, the groups add up to {}.", recorded, actual),
+            Self::FreeInodeCount { recorded, actual } => write!(formatter, "The superblock records {} free inodes, the groups add up to {}.", recorded, actual),
+            Self::GroupFreeBlocks { group, recorded, actual } => write!(formatter, "Group {} records {} free blocks, its bitmap has {}.", group, recorded, actual),
+            Self::GroupFreeInodes { group, recorded, actual } => write!(formatter, "Group {} records {} free inodes, its bitmap has {}.", group, recorded, actual),
+            Self::GroupMetadataOutOfRange { group } => write!(formatter, "Group {} points its bitmaps or inode table past the end of the file system.", group),
+            Self::BlockOutOfRange { inode, block } => write!(formatter, "Inode {} refers to block {}, which is out of range.", inode, block),
+            Self::BlockReferencedTwice { inode, block } => write!(formatter, "Inode {} refers to block {}, which is already in use.", inode, block),
+            Self::BlockNotMarkedUsed { inode, block } => write!(formatter, "Inode {} refers to block {}, which is marked free.", inode, block),
+            Self::BadDirectoryBlock { directory, block } => write!(formatter, "Directory {} has a malformed entry in block {}.", directory, block),
+            Self::EntryToFreeInode { directory, inode } => write!(formatter, "Directory {} has an entry for inode {}, which is not in use.", directory, inode),
+            Self::LinkCount { inode, recorded, actual } => write!(formatter, "Inode {} records {} links, but {} entries refer to it.", inode, recorded, actual),
+            Self::UnreachableInode { inode } => write!(formatter, "Inode {} is in use but no directory refers to it.", inode)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct GroupDescriptor {
+    block_bitmap: u32,
+    inode_bitmap: u32,
+    inode_table: u32,
+    free_blocks: u16,
+    free_inodes: u16,
+    used_directories: u16
+}
+
+impl GroupDescriptor {
+    fn parse(data: &[u8]) -> Self {
+        Self {
+            block_bitmap: lightsaber_kernel_read_u32(data, 0),
+            inode_bitmap: lightsaber_kernel_read_u32(data, 4),
+            inode_table: lightsaber_kernel_read_u32(data, 8),
+            free_blocks: lightsaber_kernel_read_u16(data, 12),
+            free_inodes: lightsaber_kernel_read_u16(data, 14),
+            used_directories: lightsaber_kernel_read_u16(data, 16)
+        }
+    }
+}
+
+struct Ext2Allocation {
+    groups: Vec<GroupDescriptor>,
+    free_blocks: u32,
+    free_inodes: u32
+}
+
+// An on-disk inode, kept as raw bytes so fields this driver does not know about survive a rewrite.
+#[derive(Clone)]
+struct RawInode {
+    data: Vec<u8>
+}
+
+impl RawInode {
+    fn mode(&self) -> u16 {
+        lightsaber_kernel_read_u16(&self.data, 0)
+    }
+
+    fn set_mode(&mut self, mode: u16) {
+        lightsaber_kernel_write_u16(&mut self.data, 0, mode);
+    }
+
+    fn inode_type(&self) -> Option<InodeType> {
+        InodeType::from_mode_bits(self.mode() as u32)
+    }
+
+    fn is_directory(&self) -> bool {
+        self.inode_type() == Some(InodeType::Directory)
+    }
+
+    fn uid(&self) -> u32 {
+        lightsaber_kernel_read_u16(&self.data, 2) as u32 | (lightsaber_kernel_read_u16(&self.data, 120) as u32) << 16
+    }
+
+    fn gid(&self) -> u32 {
+        lightsaber_kernel_read_u16(&self.data, 24) as u32 | (lightsaber_kernel_read_u16(&self.data, 122) as u32) << 16
+    }
+
+    // The high half of the size only means that for regular files; directories used it for ACLs.
+    fn size(&self) -> u64 {
+        let low = lightsaber_kernel_read_u32(&self.data, 4) as u64;
+
+        match self.inode_type() {
+            Some(InodeType::File) => low | (lightsaber_kernel_read_u32(&self.data, 108) as u64) << 32,
+            _ => low
+        }
+    }
+
+    fn set_size(&mut self, size: u64) {
+        lightsaber_kernel_write_u32(&mut self.data, 4, size as u32);
+
+        if self.inode_type() == Some(InodeType::File) {
+            lightsaber_kernel_write_u32(&mut self.data, 108, (size >> 32) as u32);
+        }
+    }
+
+    fn accessed(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 8)
+    }
+
+    fn changed(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 12)
+    }
+
+    fn modified(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 16)
+    }
+
+    fn set_deleted(&mut self, time: u32) {
+        lightsaber_kernel_write_u32(&mut self.data, 20, time);
+    }
+
+    fn links(&self) -> u16 {
+        lightsaber_kernel_read_u16(&self.data, 26)
+    }
+
+    fn set_links(&mut self, links: u16) {
+        lightsaber_kernel_write_u16(&mut self.data, 26, links);
+    }
+
+    // Counted in 512-byte units, whatever the block size.
+    fn sectors(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 28)
+    }
+
+    fn set_sectors(&mut self, sectors: u32) {
+        lightsaber_kernel_write_u32(&mut self.data, 28, sectors);
+    }
+
+    fn flags(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 32)
+    }
+
+    fn set_flags(&mut self, flags: u32) {
+        lightsaber_kernel_write_u32(&mut self.data, 32, flags);
+    }
+
+    fn block(&self, index: usize) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 40 + index * 4)
+    }
+
+    fn set_block(&mut self, index: usize, block: u32) {
+        lightsaber_kernel_write_u32(&mut self.data, 40 + index * 4, block);
+    }
+
+    fn file_acl(&self) -> u32 {
+        lightsaber_kernel_read_u32(&self.data, 104)
+    }
+
+    fn is_fast_symlink(&self, block_size: u64) -> bool {
+        let acl_sectors = if self.file_acl() != 0 { (block_size / 512) as u32 } else { 0 };
+
+        self.inode_type() == Some(InodeType::Symlink) && self.sectors() == acl_sectors
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DirectoryRecord {
+    offset: usize,
+    inode: u32,
+    record_length: usize,
+    name: Vec<u8>,
+    file_type: u8
+}
+
+impl DirectoryRecord {
+    fn used_length(&self) -> usize {
+        match self.inode {
+            0 => 0,
+            _ => lightsaber_kernel_record_length(self.name.len())
+        }
+    }
+
+    fn is_dot(&self) -> bool {
+        self.name == b"." || self.name == b".."
+    }
+}
+
+// There is no wall clock, so deleted inodes are stamped with when the file system was last written.
+// A deletion time below the inode count would be taken for a link in the orphan list.
+struct Ext2Volume {
+    device: Arc<dyn BlockDevice>,
+    device_number: u64,
+    block_size: u64,
+    blocks_count: u32,
+    inodes_count: u32,
+    first_data_block: u32,
+    blocks_per_group: u32,
+    inodes_per_group: u32,
+    inode_size: u64,
+    first_inode: u32,
+    group_count: u32,
+    group_table_position: u64,
+    deletion_time: u32,
+    file_types: bool,
+    large_files: bool,
+    read_only: AtomicBool,
+    allocation: Mutex<Ext2Allocation>,
+    metadata: Mutex<()>,
+    inodes: Spinlock<BTreeMap<u32, Weak<Ext2Inode>>>
+}
+
+impl Ext2Volume {
+    fn check_writable(&self) -> Result<(), FsError> {
+        match self.read_only.load(Ordering::Relaxed) {
+            true => Err(FsError::ReadOnly),
+            false => Ok(())
+        }
+    }
+
+    fn pointers_per_block(&self) -> u64 {
+        self.block_size / 4
+    }
+
+    fn maximum_file_size(&self) -> u64 {
+        let pointers = self.pointers_per_block();
+        let blocks = DIRECT_BLOCKS as u64 + pointers + pointers * pointers + pointers * pointers * pointers;
+
+        match self.large_files {
+            true => blocks * self.block_size,
+            false => (blocks * self.block_size).min(SMALL_FILE_MAXIMUM)
+        }
+    }
+
+    fn read_block(&self, block: u32) -> Result<Vec<u8>, FsError> {
+        let mut data = vec![0; self.block_size as usize];
+        self.device.read_bytes(block as u64 * self.block_size, &mut data)?;
+
+        Ok(data)
+    }
+
+    fn write_block(&self, block: u32, data: &[u8]) -> Result<(), FsError> {
+        Ok(self.device.write_bytes(block as u64 * self.block_size, data)?)
+    }
+
+    fn is_valid_block(&self, block: u32) -> bool {
+        block >= self.first_data_block && block < self.blocks_count
+    }
+
+    fn blocks_in_group(&self, group: u32) -> u32 {
+        (self.blocks_count - self.first_data_block - group * self.blocks_per_group).min(self.blocks_per_group)
+    }
+
+    fn inode_position(&self, allocation: &Ext2Allocation, number: u32) -> Result<u64, FsError> {
+        if number == 0 || number > self.inodes_count {
+            return Err(FsError::Io);
+        }
+
+        let group = (number - 1) / self.inodes_per_group;
+        let index = (number - 1) % self.inodes_per_group;
+
+        Ok(allocation.groups[group as usize].inode_table as u64 * self.block_size + index as u64 * self.inode_size)
+    }
+
+    fn read_inode(&self, number: u32) -> Result<RawInode, FsError> {
+        let position = self.inode_position(&self.allocation.lock(), number)?;
+
+        let mut data = vec![0; self.inode_size as usize];
+        self.device.read_bytes(position, &mut data)?;
+
+        Ok(RawInode {
+            data
+        })
+    }
+
+    // Inodes share inode table sectors, so their writes are serialized.
+    fn write_inode(&self, number: u32, raw: &RawInode) -> Result<(), FsError> {
+        let position = self.inode_position(&self.allocation.lock(), number)?;
+        let _guard = self.metadata.lock();
+
+        Ok(self.device.write_bytes(position, &raw.data)?)
+    }
+
+    fn write_group(&self, allocation: &Ext2Allocation, group: u32) -> Result<(), FsError> {
+        let descriptor = allocation.groups[group as usize];
+
+        let mut data = [0; 6];
+        lightsaber_kernel_write_u16(&mut data, 0, descriptor.free_blocks);
+        lightsaber_kernel_write_u16(&mut data, 2, descriptor.free_inodes);
+        lightsaber_kernel_write_u16(&mut data, 4, descriptor.used_directories);
+
+        self.device.write_bytes(self.group_table_position + group as u64 * GROUP_DESCRIPTOR_SIZE + 12, &data)?;
+
+        let mut counts = [0; 8];
+        lightsaber_kernel_write_u32(&mut counts, 0, allocation.free_blocks);
+        lightsaber_kernel_write_u32(&mut counts, 4, allocation.free_inodes);
+
+        Ok(self.device.write_bytes(SUPERBLOCK_OFFSET + 12, &counts)?)
+    }
+
+    fn update_bitmap_bit(&self, bitmap: u32, bit: u32, set: bool) -> Result<bool, FsError> {
+        let position = bitmap as u64 * self.block_size + bit as u64 / 8;
+
+        let mut byte = [0; 1];
+        self.device.read_bytes(position, &mut byte)?;
+
+        let mask = 1 << (bit % 8);
+        let was_set = byte[0] & mask != 0;
+
+        byte[0] = if set { byte[0] | mask } else { byte[0] & !mask };
+        self.device.write_bytes(position, &byte)?;
+
+        Ok(was_set)
+    }
+
+    fn find_clear_bit(&self, bitmap: u32, limit: u32) -> Result<Option<u32>, FsError> {
+        let data = self.read_block(bitmap)?;
+
+        Ok((0..limit).find(|bit| data[(bit / 8) as usize] & (1 << (bit % 8)) == 0))
+    }
+
+    // Allocates a zeroed block, preferring the given group.
+    fn allocate_block(&self, goal: u32) -> Result<u32, FsError> {
+        self.check_writable()?;
+
+        let mut allocation = self.allocation.lock();
+
+        for offset in 0..self.group_count {
+            let group = (goal + offset) % self.group_count;
+            let descriptor = allocation.groups[group as usize];
+
+            if descriptor.free_blocks == 0 {
+                continue;
+            }
+
+            let bit = match self.find_clear_bit(descriptor.block_bitmap, self.blocks_in_group(group))? {
+                Some(bit) => bit,
+                None => continue
+            };
+
+            self.update_bitmap_bit(descriptor.block_bitmap, bit, true)?;
+
+            allocation.groups[group as usize].free_blocks -= 1;
+            allocation.free_blocks = allocation.free_blocks.saturating_sub(1);
+            self.write_group(&allocation, group)?;
+
+            let block = self.first_data_block + group * self.blocks_per_group + bit;
+            self.write_block(block, &vec![0; self.block_size as usize])?;
+
+            return Ok(block);
+        }
+
+        Err(FsError::NoSpace)
+    }
+
+    fn free_blocks(&self, blocks: &[u32]) -> Result<(), FsError> {
+        let mut allocation = self.allocation.lock();
+
+        for block in blocks {
+            if !self.is_valid_block(*block) {
+                log::warn!("Refusing to free out of range ext2 block {}.", block);
+                continue;
+            }
+
+            let group = (block - self.first_data_block) / self.blocks_per_group;
+            let bit = (block - self.first_data_block) % self.blocks_per_group;
+
+            if self.update_bitmap_bit(allocation.groups[group as usize].block_bitmap, bit, false)? {
+                allocation.groups[group as usize].free_blocks += 1;
+                allocation.free_blocks += 1;
+                self.write_group(&allocation, group)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn allocate_inode(&self, goal: u32, directory: bool) -> Result<u32, FsError> {
+        self.check_writable()?;
+
+        let mut allocation = self.allocation.lock();
+
+        for offset in 0..self.group_count {
+            let group = (goal + offset) % self.group_count;
+            let descriptor = allocation.groups[group as usize];
+
+            if descriptor.free_inodes == 0 {
+                continue;
+            }
+
+            let data = self.read_block(descriptor.inode_bitmap)?;
+
+            // Reserved inodes are normally marked in use already, but do not rely on it.
+            let bit = (0..self.inodes_per_group).find(|bit| {
+                group * self.inodes_per_group + bit + 1 >= self.first_inode && data[(bit / 8) as usize] & (1 << (bit % 8)) == 0
+            });
+
+            let bit = match bit {
+                Some(bit) => bit,
+                None => continue
+            };
+
+            self.update_bitmap_bit(descriptor.inode_bitmap, bit, true)?;
+
+            let descriptor = &mut allocation.groups[group as usize];
+            descriptor.free_inodes -= 1;
+
+            if directory {
+                descriptor.used_directories += 1;
+            }
+
+            allocation.free_inodes = allocation.free_inodes.saturating_sub(1);
+            self.write_group(&allocation, group)?;
+
+            return Ok(group * self.inodes_per_group + bit + 1);
+        }
+
+        Err(FsError::NoSpace)
+    }
+
+    fn free_inode(&self, number: u32, directory: bool) -> Result<(), FsError> {
+        let mut allocation = self.allocation.lock();
+
+        let group = (number - 1) / self.inodes_per_group;
+        let bit = (number - 1) % self.inodes_per_group;
+
+        if self.update_bitmap_bit(allocation.groups[group as usize].inode_bitmap, bit, false)? {
+            let descriptor = &mut allocation.groups[group as usize];
+            descriptor.free_inodes += 1;
+
+            if directory {
+                descriptor.used_directories = descriptor.used_directories.saturating_sub(1);
+            }
+
+            allocation.free_inodes += 1;
+            self.write_group(&allocation, group)?;
+        }
+
+        Ok(())
+    }
+
+    // Splits a logical block number into the pointer slot in the inode and the offsets within each indirect block.
+    fn block_path(&self, index: u64) -> Option<(usize, Vec<u64>)> {
+        let pointers = self.pointers_per_block();
+        let mut index = index;
+
+        if index < DIRECT_BLOCKS as u64 {
+            return Some((index as usize, Vec::new()));
+        }
+
+        index -= DIRECT_BLOCKS as u64;
+
+        if index < pointers {
+            return Some((INDIRECT_BLOCK, vec![index]));
+        }
+
+        index -= pointers;
+
+        if index < pointers * pointers {
+            return Some((DOUBLE_INDIRECT_BLOCK, vec![index / pointers, index % pointers]));
+        }
+
+        index -= pointers * pointers;
+
+        if index < pointers * pointers * pointers {
+            return Some((TRIPLE_INDIRECT_BLOCK, vec![index / (pointers * pointers), (index / pointers) % pointers, index % pointers]));
+        }
+
+        None
+    }
+
+    fn read_pointer(&self, block: u32, index: u64) -> Result<u32, FsError> {
+        let mut data = [0; 4];
+        self.device.read_bytes(block as u64 * self.block_size + index * 4, &mut data)?;
+
+        Ok(u32::from_le_bytes(data))
+    }
+
+    fn write_pointer(&self, block: u32, index: u64, value: u32) -> Result<(), FsError> {
+        Ok(self.device.write_bytes(block as u64 * self.block_size + index * 4, &value.to_le_bytes())?)
+    }
+
+    // Returns the physical block backing a logical block, or zero for a hole.
+    fn lookup_block(&self, raw: &RawInode, index: u64) -> Result<u32, FsError> {
+        let (slot, path) = self.block_path(index).ok_or(FsError::FileTooLarge)?;
+        let mut block = raw.block(slot);
+
+        for offset in path {
+            if block == 0 {
+                break;
+            }
+
+            if !self.is_valid_block(block) {
+                return Err(FsError::Io);
+            }
+
+            block = self.read_pointer(block, offset)?;
+        }
+
+        match block {
+            0 => Ok(0),
+            block if self.is_valid_block(block) => Ok(block),
+            _ => Err(FsError::Io)
+        }
+    }
+
+    fn map_block(&self, raw: &mut RawInode, goal: u32, index: u64) -> Result<u32, FsError> {
+        let (slot, path) = self.block_path(index).ok_or(FsError::FileTooLarge)?;
+        let sectors_per_block = (self.block_size / 512) as u32;
+        let mut block = raw.block(slot);
+
+        if block == 0 {
+            block = self.allocate_block(goal)?;
+
+            raw.set_block(slot, block);
+            raw.set_sectors(raw.sectors() + sectors_per_block);
+        }
+
+        for offset in path {
+            if !self.is_valid_block(block) {
+                return Err(FsError::Io);
+            }
+
+            let mut next = self.read_pointer(block, offset)?;
+
+            if next == 0 {
+                next = self.allocate_block(goal)?;
+
+                self.write_pointer(block, offset, next)?;
+                raw.set_sectors(raw.sectors() + sectors_per_block);
+            }
+
+            block = next;
+        }
+
+        Ok(block)
+    }
+
+    // Collects every block at or past logical block `first` in the tree below `block`; returns whether the whole tree goes.
+    fn truncate_tree(&self, block: u32, depth: u32, first: u64, freed: &mut Vec<u32>) -> Result<bool, FsError> {
+        if depth == 0 {
+            if first == 0 {
+                freed.push(block);
+            }
+
+            return Ok(first == 0);
+        }
+
+        if !self.is_valid_block(block) {
+            return Err(FsError::Io);
+        }
+
+        let span = self.pointers_per_block().pow(depth - 1);
+        let mut data = self.read_block(block)?;
+        let mut changed = false;
+
+        for index in 0..self.pointers_per_block() {
+            let pointer = lightsaber_kernel_read_u32(&data, index as usize * 4);
+            let start = index * span;
+
+            if pointer == 0 || start + span <= first {
+                continue;
+            }
+
+            if self.truncate_tree(pointer, depth - 1, first.saturating_sub(start), freed)? {
+                lightsaber_kernel_write_u32(&mut data, index as usize * 4, 0);
+                changed = true;
+            }
+        }
+
+        if data.iter().all(|byte| *byte == 0) {
+            freed.push(block);
+
+            return Ok(true);
+        }
+
+        if changed {
+            self.write_block(block, &data)?;
+        }
+
+        Ok(false)
+    }
+
+    fn truncate_blocks(&self, raw: &mut RawInode, first: u64) -> Result<(), FsError> {
+        let pointers = self.pointers_per_block();
+        let mut freed = Vec::new();
+
+        for index in (first as usize).min(DIRECT_BLOCKS)..DIRECT_BLOCKS {
+            if raw.block(index) != 0 {
+                freed.push(raw.block(index));
+                raw.set_block(index, 0);
+            }
+        }
+
+        let mut base = DIRECT_BLOCKS as u64;
+
+        for (depth, slot) in [(1, INDIRECT_BLOCK), (2, DOUBLE_INDIRECT_BLOCK), (3, TRIPLE_INDIRECT_BLOCK)].iter() {
+            let span = pointers.pow(*depth);
+
+            if raw.block(*slot) != 0 && first < base + span && self.truncate_tree(raw.block(*slot), *depth, first.saturating_sub(base), &mut freed)? {
+                raw.set_block(*slot, 0);
+            }
+
+            base += span;
+        }
+
+        raw.set_sectors(raw.sectors().saturating_sub(freed.len() as u32 * (self.block_size / 512) as u32));
+
+        self.free_blocks(&freed)
+    }
+
+    fn parse_directory_block(&self, data: &[u8]) -> Result<Vec<DirectoryRecord>, FsError> {
+        let mut records = Vec::new();
+        let mut offset = 0;
+
+        while offset < data.len() {
+            if offset + DIRECTORY_ENTRY_HEADER_SIZE > data.len() {
+                return Err(FsError::Io);
+            }
+
+            let inode = lightsaber_kernel_read_u32(data, offset);
+            let record_length = lightsaber_kernel_read_u16(data, offset + 4) as usize;
+
+            let (name_length, file_type) = match self.file_types {
+                true => (data[offset + 6] as usize, data[offset + 7]),
+                false => (lightsaber_kernel_read_u16(data, offset + 6) as usize, 0)
+            };
+
+            if record_length < DIRECTORY_ENTRY_HEADER_SIZE || record_length % 4 != 0 || offset + record_length > data.len() || DIRECTORY_ENTRY_HEADER_SIZE + name_length > record_length {
+                return Err(FsError::Io);
+            }
+
+            let name_start = offset + DIRECTORY_ENTRY_HEADER_SIZE;
+
+            records.push(DirectoryRecord {
+                offset,
+                inode,
+                record_length,
+                name: data[name_start..name_start + name_length].to_vec(),
+                file_type
+            });
+
+            offset += record_length;
+        }
+
+        Ok(records)
+    }
+
+    fn encode_directory_record(&self, data: &mut [u8], offset: usize, inode: u32, record_length: usize, name: &[u8], file_type: u8) {
+        lightsaber_kernel_write_u32(data, offset, inode);
+        lightsaber_kernel_write_u16(data, offset + 4, record_length as u16);
+
+        match self.file_types {
+            true => {
+                data[offset + 6] = name.len() as u8;
+                data[offset + 7] = file_type;
+            }
+            false => lightsaber_kernel_write_u16(data, offset + 6, name.len() as u16)
+        }
+
+        data[offset + DIRECTORY_ENTRY_HEADER_SIZE..offset + DIRECTORY_ENTRY_HEADER_SIZE + name.len()].copy_from_slice(name);
+    }
+}
+
+struct Ext2InodeState {
+    raw: RawInode
+}
+
+pub struct Ext2Inode {
+    number: u32,
+    volume: Arc<Ext2Volume>,
+    state: Mutex<Ext2InodeState>
+}
+
+impl Ext2Inode {
+    fn load(volume: &Arc<Ext2Volume>, number: u32) -> Result<Arc<Self>, FsError> {
+        if let Some(inode) = volume.inodes.lock().get(&number).and_then(Weak::upgrade) {
+            return Ok(inode);
+        }
+
+        let raw = volume.read_inode(number)?;
+
+        if raw.links() == 0 || raw.inode_type().is_none() {
+            return Err(FsError::Io);
+        }
+
+        let mut inodes = volume.inodes.lock();
+
+        // Someone else may have loaded the same inode in the meantime.
+        if let Some(inode) = inodes.get(&number).and_then(Weak::upgrade) {
+            return Ok(inode);
+        }
+
+        let inode = Arc::new(Self {
+            number,
+            volume: volume.clone(),
+            state: Mutex::named("ext2_inode", Ext2InodeState {
+                raw
+            })
+        });
+
+        inodes.insert(number, Arc::downgrade(&inode));
+
+        Ok(inode)
+    }
+
+    fn group(&self) -> u32 {
+        (self.number - 1) / self.volume.inodes_per_group
+    }
+
+    fn read_data(&self, raw: &RawInode, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let size = raw.size();
+
+        if offset >= size {
+            return Ok(0);
+        }
+
+        let block_size = self.volume.block_size;
+        let end = size.min(offset + buffer.len() as u64);
+        let mut position = offset;
+
+        while position < end {
+            let block_offset = position % block_size;
+            let chunk = (block_size - block_offset).min(end - position) as usize;
+            let destination = (position - offset) as usize;
+
+            match self.volume.lookup_block(raw, position / block_size)? {
+                0 => buffer[destination..destination + chunk].iter_mut().for_each(|byte| *byte = 0),
+                block => self.volume.device.read_bytes(block as u64 * block_size + block_offset, &mut buffer[destination..destination + chunk])?
+            }
+
+            position += chunk as u64;
+        }
+
+        Ok((end - offset) as usize)
+    }
+
+    fn write_data(&self, raw: &mut RawInode, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        let block_size = self.volume.block_size;
+        let mut position = offset;
+        let end = offset + buffer.len() as u64;
+
+        while position < end {
+            let block_offset = position % block_size;
+            let chunk = (block_size - block_offset).min(end - position) as usize;
+            let source = (position - offset) as usize;
+
+            let block = match self.volume.map_block(raw, self.group(), position / block_size) {
+                Ok(block) => block,
+                Err(FsError::NoSpace) if position > offset => break,
+                Err(error) => {
+                    self.volume.write_inode(self.number, raw)?;
+                    return Err(error);
+                }
+            };
+
+            self.volume.device.write_bytes(block as u64 * block_size + block_offset, &buffer[source..source + chunk])?;
+            position += chunk as u64;
+        }
+
+        if position > raw.size() {
+            raw.set_size(position);
+        }
+
+        self.volume.write_inode(self.number, raw)?;
+
+        Ok((position - offset) as usize)
+    }
+
+    fn records(&self, raw: &RawInode) -> Result<Vec<(u64, Vec<DirectoryRecord>)>, FsError> {
+        let block_size = self.volume.block_size;
+        let mut blocks = Vec::new();
+
+        for index in 0..raw.size() / block_size {
+            let block = self.volume.lookup_block(raw, index)?;
+
+            if block == 0 {
+                continue;
+            }
+
+            blocks.push((index, self.volume.parse_directory_block(&self.volume.read_block(block)?)?));
+        }
+
+        Ok(blocks)
+    }
+
+    fn find_record(&self, raw: &RawInode, name: &str) -> Result<DirectoryRecord, FsError> {
+        self.records(raw)?
+            .into_iter()
+            .flat_map(|(_, records)| records)
+            .find(|record| record.inode != 0 && record.name == name.as_bytes())
+            .ok_or(FsError::NotFound)
+    }
+
+    fn add_record(&self, raw: &mut RawInode, name: &str, inode: u32, inode_type: InodeType) -> Result<(), FsError> {
+        let block_size = self.volume.block_size;
+        let needed = lightsaber_kernel_record_length(name.len());
+        let file_type = lightsaber_kernel_file_type(inode_type);
+
+        // Hashed directory indexes are not kept up to date, so they are dropped on the first change.
+        raw.set_flags(raw.flags() & !INODE_FLAG_INDEX);
+
+        for (index, records) in self.records(raw)? {
+            let record = match records.iter().find(|record| record.record_length - record.used_length() >= needed) {
+                Some(record) => record,
+                None => continue
+            };
+
+            let block = self.volume.lookup_block(raw, index)?;
+            let mut data = self.volume.read_block(block)?;
+
+            match record.used_length() {
+                0 => self.volume.encode_directory_record(&mut data, record.offset, inode, record.record_length, name.as_bytes(), file_type),
+                used => {
+                    lightsaber_kernel_write_u16(&mut data, record.offset + 4, used as u16);
+                    self.volume.encode_directory_record(&mut data, record.offset + used, inode, record.record_length - used, name.as_bytes(), file_type);
+                }
+            }
+
+            self.volume.write_block(block, &data)?;
+
+            return self.volume.write_inode(self.number, raw);
+        }
+
+        let index = raw.size() / block_size;
+        let block = self.volume.map_block(raw, self.group(), index)?;
+
+        let mut data = vec![0; block_size as usize];
+        self.volume.encode_directory_record(&mut data, 0, inode, block_size as usize, name.as_bytes(), file_type);
+        self.volume.write_block(block, &data)?;
+
+        raw.set_size((index + 1) * block_size);
+
+        self.volume.write_inode(self.number, raw)
+    }
+
+    fn remove_record(&self, raw: &mut RawInode, name: &str) -> Result<(), FsError> {
+        raw.set_flags(raw.flags() & !INODE_FLAG_INDEX);
+
+        for (index, records) in self.records(raw)? {
+            let position = match records.iter().position(|record| record.inode != 0 && record.name == name.as_bytes()) {
+                Some(position) => position,
+                None => continue
+            };
+
+            let block = self.volume.lookup_block(raw, index)?;
+            let mut data = self.volume.read_block(block)?;
+            let record = &records[position];
+
+            // The space goes to the previous record; the first record in a block just loses its inode.
+            match position {
+                0 => lightsaber_kernel_write_u32(&mut data, record.offset, 0),
+                _ => {
+                    let previous = &records[position - 1];
+                    lightsaber_kernel_write_u16(&mut data, previous.offset + 4, (previous.record_length + record.record_length) as u16);
+                }
+            }
+
+            self.volume.write_block(block, &data)?;
+
+            return self.volume.write_inode(self.number, raw);
+        }
+
+        Err(FsError::NotFound)
+    }
+
+    fn is_empty_directory(&self, raw: &RawInode) -> Result<bool, FsError> {
+        Ok(self.records(raw)?
+            .iter()
+            .flat_map(|(_, records)| records.iter())
+            .all(|record| record.inode == 0 || record.is_dot()))
+    }
+
+    fn check_name(name: &str) -> Result<(), FsError> {
+        match name {
+            "" | "." | ".." => Err(FsError::InvalidArgument),
+            _ if name.contains('/') || name.contains('\0') => Err(FsError::InvalidArgument),
+            _ if name.len() > path::MAXIMUM_NAME_LENGTH => Err(FsError::NameTooLong),
+            _ => Ok(())
+        }
+    }
+
+    // Allocates and writes out a fresh inode; times stay zero as there is no wall clock yet.
+    fn allocate(&self, inode_type: InodeType, mode: u16) -> Result<(u32, RawInode), FsError> {
+        let directory = inode_type == InodeType::Directory;
+        let number = self.volume.allocate_inode(self.group(), directory)?;
+
+        let mut raw = RawInode {
+            data: vec![0; self.volume.inode_size as usize]
+        };
+
+        raw.set_mode(inode_type.mode_bits() as u16 | (mode & MODE_PERMISSION_MASK));
+        raw.set_links(if directory { 2 } else { 1 });
+
+        Ok((number, raw))
+    }
+
+    fn insert(&self, name: &str, inode_type: InodeType, mode: u16, contents: impl FnOnce(&Self, u32, &mut RawInode) -> Result<(), FsError>) -> Result<Arc<dyn Inode>, FsError> {
+        self.volume.check_writable()?;
+        Self::check_name(name)?;
+
+        let mut state = self.state.lock();
+
+        if !state.raw.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        match self.find_record(&state.raw, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::NotFound) => { }
+            Err(error) => return Err(error)
+        }
+
+        if inode_type == InodeType::Directory && state.raw.links() >= MAXIMUM_LINKS {
+            return Err(FsError::NoSpace);
+        }
+
+        let (number, mut raw) = self.allocate(inode_type, mode)?;
+
+        let result = contents(self, number, &mut raw)
+            .and_then(|_| self.volume.write_inode(number, &raw))
+            .and_then(|_| self.add_record(&mut state.raw, name, number, inode_type));
+
+        if let Err(error) = result {
+            let _ = self.volume.truncate_blocks(&mut raw, 0);
+            let _ = self.volume.free_inode(number, inode_type == InodeType::Directory);
+
+            return Err(error);
+        }
+
+        if inode_type == InodeType::Directory {
+            let links = state.raw.links();
+            state.raw.set_links(links + 1);
+            self.volume.write_inode(self.number, &state.raw)?;
+        }
+
+        drop(state);
+
+        Ok(Ext2Inode::load(&self.volume, number)?)
+    }
+}
+
+impl Inode for Ext2Inode {
+    fn metadata(&self) -> Result<Metadata, FsError> {
+        let state = self.state.lock();
+        let raw = &state.raw;
+
+        Ok(Metadata {
+            device: self.volume.device_number,
+            inode: self.number as u64,
+            inode_type: raw.inode_type().unwrap_or(InodeType::File),
+            mode: raw.mode() & MODE_PERMISSION_MASK,
+            links: raw.links() as u32,
+            uid: raw.uid(),
+            gid: raw.gid(),
+            size: raw.size(),
+            block_size: self.volume.block_size,
+            blocks: raw.sectors() as u64,
+            accessed: raw.accessed() as u64,
+            modified: raw.modified() as u64,
+            changed: raw.changed() as u64
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let state = self.state.lock();
+
+        match state.raw.inode_type() {
+            Some(InodeType::Directory) => Err(FsError::IsDirectory),
+            Some(InodeType::File) => self.read_data(&state.raw, offset, buffer),
+            _ => Err(FsError::InvalidArgument)
+        }
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        self.volume.check_writable()?;
+
+        let mut state = self.state.lock();
+
+        match state.raw.inode_type() {
+            Some(InodeType::Directory) => return Err(FsError::IsDirectory),
+            Some(InodeType::File) => { }
+            _ => return Err(FsError::InvalidArgument)
+        }
+
+        let end = offset.checked_add(buffer.len() as u64).ok_or(FsError::FileTooLarge)?;
+
+        if end > self.volume.maximum_file_size() {
+            return Err(FsError::FileTooLarge);
+        }
+
+        self.write_data(&mut state.raw, offset, buffer)
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), FsError> {
+        self.volume.check_writable()?;
+
+        let mut state = self.state.lock();
+        let block_size = self.volume.block_size;
+
+        match state.raw.inode_type() {
+            Some(InodeType::Directory) => return Err(FsError::IsDirectory),
+            Some(InodeType::File) => { }
+            _ => return Err(FsError::InvalidArgument)
+        }
+
+        if size > self.volume.maximum_file_size() {
+            return Err(FsError::FileTooLarge);
+        }
+
+        if size < state.raw.size() {
+            self.volume.truncate_blocks(&mut state.raw, (size + block_size - 1) / block_size)?;
+
+            // The tail of the last block has to read back as zeroes should the file grow again.
+            if size % block_size != 0 {
+                let block = self.volume.lookup_block(&state.raw, size / block_size)?;
+
+                if block != 0 {
+                    let zeroes = vec![0; (block_size - size % block_size) as usize];
+                    self.volume.device.write_bytes(block as u64 * block_size + size % block_size, &zeroes)?;
+                }
+            }
+        }
+
+        state.raw.set_size(size);
+
+        self.volume.write_inode(self.number, &state.raw)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        let state = self.state.lock();
+
+        if !state.raw.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let record = self.find_record(&state.raw, name)?;
+        drop(state);
+
+        Ok(Ext2Inode::load(&self.volume, record.inode)?)
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType, mode: u16) -> Result<Arc<dyn Inode>, FsError> {
+        match inode_type {
+            InodeType::File => self.insert(name, inode_type, mode, |_, _, _| Ok(())),
+            InodeType::Directory => self.insert(name, inode_type, mode, |parent, number, raw| {
+                let block_size = parent.volume.block_size;
+                let block = parent.volume.map_block(raw, parent.group(), 0)?;
+
+                let mut data = vec![0; block_size as usize];
+                parent.volume.encode_directory_record(&mut data, 0, number, lightsaber_kernel_record_length(1), b".", FILE_TYPE_DIRECTORY);
+                parent.volume.encode_directory_record(&mut data, lightsaber_kernel_record_length(1), parent.number, block_size as usize - lightsaber_kernel_record_length(1), b"..", FILE_TYPE_DIRECTORY);
+                parent.volume.write_block(block, &data)?;
+
+                raw.set_size(block_size);
+
+                Ok(())
+            }),
+            _ => Err(FsError::NotSupported)
+        }
+    }
+
+    fn link(&self, name: &str, target: &Arc<dyn Inode>) -> Result<(), FsError> {
+        self.volume.check_writable()?;
+        Self::check_name(name)?;
+
+        let metadata = target.metadata()?;
+
+        if metadata.device != self.volume.device_number {
+            return Err(FsError::CrossDevice);
+        }
+
+        if metadata.is_directory() {
+            return Err(FsError::NotSupported);
+        }
+
+        let target = Ext2Inode::load(&self.volume, metadata.inode as u32)?;
+        let mut state = self.state.lock();
+
+        if !state.raw.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        match self.find_record(&state.raw, name) {
+            Ok(_) => return Err(FsError::AlreadyExists),
+            Err(FsError::NotFound) => { }
+            Err(error) => return Err(error)
+        }
+
+        let mut target_state = target.state.lock();
+
+        if target_state.raw.links() >= MAXIMUM_LINKS {
+            return Err(FsError::NoSpace);
+        }
+
+        self.add_record(&mut state.raw, name, target.number, metadata.inode_type)?;
+
+        let links = target_state.raw.links();
+        target_state.raw.set_links(links + 1);
+
+        self.volume.write_inode(target.number, &target_state.raw)
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FsError> {
+        self.volume.check_writable()?;
+        Self::check_name(name)?;
+
+        let mut state = self.state.lock();
+
+        if !state.raw.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let record = self.find_record(&state.raw, name)?;
+        let child = Ext2Inode::load(&self.volume, record.inode)?;
+        let mut child_state = child.state.lock();
+        let directory = child_state.raw.is_directory();
+
+        if directory && !child.is_empty_directory(&child_state.raw)? {
+            return Err(FsError::NotEmpty);
+        }
+
+        self.remove_record(&mut state.raw, name)?;
+
+        // The inode and its blocks are released once the last user lets go of it.
+        if directory {
+            child_state.raw.set_links(0);
+
+            let links = state.raw.links();
+            state.raw.set_links(links.saturating_sub(1));
+            self.volume.write_inode(self.number, &state.raw)?;
+        } else {
+            let links = child_state.raw.links();
+            child_state.raw.set_links(links.saturating_sub(1));
+        }
+
+        self.volume.write_inode(child.number, &child_state.raw)
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>, FsError> {
+        if target.is_empty() || target.len() > path::MAXIMUM_PATH_LENGTH {
+            return Err(FsError::InvalidArgument);
+        }
+
+        self.insert(name, InodeType::Symlink, 0o777, |parent, _, raw| {
+            // Short targets live in the block pointers themselves.
+            if target.len() < FAST_SYMLINK_MAXIMUM {
+                raw.data[40..40 + target.len()].copy_from_slice(target.as_bytes());
+                raw.set_size(target.len() as u64);
+
+                return Ok(());
+            }
+
+            let block = parent.volume.map_block(raw, parent.group(), 0)?;
+            parent.volume.device.write_bytes(block as u64 * parent.volume.block_size, target.as_bytes())?;
+            raw.set_size(target.len() as u64);
+
+            Ok(())
+        })
+    }
+
+    fn read_link(&self) -> Result<String, FsError> {
+        let state = self.state.lock();
+        let raw = &state.raw;
+
+        if raw.inode_type() != Some(InodeType::Symlink) {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let size = raw.size() as usize;
+
+        if size > path::MAXIMUM_PATH_LENGTH {
+            return Err(FsError::Io);
+        }
+
+        if raw.is_fast_symlink(self.volume.block_size) {
+            return Ok(String::from_utf8_lossy(&raw.data[40..40 + size.min(FAST_SYMLINK_MAXIMUM)]).into());
+        }
+
+        let mut buffer = vec![0; size];
+        let read = self.read_data(raw, 0, &mut buffer)?;
+
+        Ok(String::from_utf8_lossy(&buffer[..read]).into())
+    }
+
+    fn read_directory(&self, index: usize) -> Result<Option<DirectoryEntry>, FsError> {
+        let state = self.state.lock();
+
+        if !state.raw.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let record = self.records(&state.raw)?
+            .into_iter()
+            .flat_map(|(_, records)| records)
+            .filter(|record| record.inode != 0 && !record.is_dot())
+            .nth(index);
+
+        let record = match record {
+            Some(record) => record,
+            None => return Ok(None)
+        };
+
+        drop(state);
+
+        let inode_type = match record.file_type {
+            FILE_TYPE_DIRECTORY => InodeType::Directory,
+            FILE_TYPE_SYMLINK => InodeType::Symlink,
+            FILE_TYPE_CHARACTER_DEVICE => InodeType::CharacterDevice,
+            FILE_TYPE_BLOCK_DEVICE => InodeType::BlockDevice,
+            FILE_TYPE_REGULAR => InodeType::File,
+            _ => Ext2Inode::load(&self.volume, record.inode)?.state.lock().raw.inode_type().unwrap_or(InodeType::File)
+        };
+
+        Ok(Some(DirectoryEntry {
+            name: String::from_utf8_lossy(&record.name).into(),
+            inode: record.inode as u64,
+            inode_type
+        }))
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(self.volume.device.flush()?)
+    }
+}
+
+impl Drop for Ext2Inode {
+    fn drop(&mut self) {
+        let mut inodes = self.volume.inodes.lock();
+
+        // A newer inode may already have been registered for the same number.
+        if inodes.get(&self.number).map_or(false, |inode| inode.strong_count() == 0) {
+            inodes.remove(&self.number);
+        }
+
+        drop(inodes);
+
+        let volume = &self.volume;
+        let number = self.number;
+        let raw = &mut self.state.get_mut().raw;
+
+        if raw.links() != 0 || volume.read_only.load(Ordering::Relaxed) {
+            return;
+        }
+
+        let directory = raw.is_directory();
+
+        let result = match raw.is_fast_symlink(volume.block_size) {
+            true => Ok(()),
+            false => volume.truncate_blocks(raw, 0)
+        };
+
+        raw.set_deleted(volume.deletion_time);
+
+        let result = result
+            .and_then(|_| volume.write_inode(number, raw))
+            .and_then(|_| volume.free_inode(number, directory));
+
+        if let Err(error) = result {
+            log::warn!("Failed to release ext2 inode {}: {}", number, error);
+        }
+    }
+}
+
+pub struct Ext2FileSystem {
+    volume: Arc<Ext2Volume>,
+    root: Arc<Ext2Inode>
+}
+
+impl Ext2FileSystem {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        let mut superblock = [0; SUPERBLOCK_SIZE];
+        device.read_bytes(SUPERBLOCK_OFFSET, &mut superblock)?;
+
+        if lightsaber_kernel_read_u16(&superblock, 56) != EXT2_MAGIC {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let inodes_count = lightsaber_kernel_read_u32(&superblock, 0);
+        let blocks_count = lightsaber_kernel_read_u32(&superblock, 4);
+        let free_blocks = lightsaber_kernel_read_u32(&superblock, 12);
+        let free_inodes = lightsaber_kernel_read_u32(&superblock, 16);
+        let first_data_block = lightsaber_kernel_read_u32(&superblock, 20);
+        let log_block_size = lightsaber_kernel_read_u32(&superblock, 24);
+        let blocks_per_group = lightsaber_kernel_read_u32(&superblock, 32);
+        let inodes_per_group = lightsaber_kernel_read_u32(&superblock, 40);
+        let last_written = lightsaber_kernel_read_u32(&superblock, 48);
+        let state = lightsaber_kernel_read_u16(&superblock, 58);
+        let revision = lightsaber_kernel_read_u32(&superblock, 76);
+
+        let (inode_size, first_inode, incompatible, read_only_compatible) = match revision {
+            GOOD_OLD_REVISION => (GOOD_OLD_INODE_SIZE, GOOD_OLD_FIRST_INODE, 0, 0),
+            _ => (
+                lightsaber_kernel_read_u16(&superblock, 88) as u64,
+                lightsaber_kernel_read_u32(&superblock, 84),
+                lightsaber_kernel_read_u32(&superblock, 96),
+                lightsaber_kernel_read_u32(&superblock, 100)
+            )
+        };
+
+        if log_block_size > 6 {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let block_size = 1024 << log_block_size;
+
+        let valid = blocks_per_group != 0
+            && inodes_per_group != 0
+            && blocks_per_group as u64 <= block_size * 8
+            && inodes_per_group as u64 <= block_size * 8
+            && first_data_block < blocks_count
+            && inode_size >= GOOD_OLD_INODE_SIZE
+            && inode_size.is_power_of_two()
+            && inode_size <= block_size
+            && first_inode > ROOT_INODE
+            && blocks_count as u64 * block_size <= device.size();
+
+        if !valid {
+            return Err(FsError::InvalidArgument);
+        }
+
+        if incompatible & !SUPPORTED_INCOMPAT != 0 {
+            log::warn!("The ext2 file system on {} uses unsupported features {:#x}.", device.name(), incompatible & !SUPPORTED_INCOMPAT);
+
+            return Err(FsError::NotSupported);
+        }
+
+        let mut read_only = device.is_read_only();
+
+        if read_only_compatible & !SUPPORTED_RO_COMPAT != 0 {
+            log::warn!("Mounting the ext2 file system on {} read-only, it uses features {:#x}.", device.name(), read_only_compatible & !SUPPORTED_RO_COMPAT);
+
+            read_only = true;
+        }
+
+        let group_count = (blocks_count - first_data_block + blocks_per_group - 1) / blocks_per_group;
+        let group_table_position = (first_data_block as u64 + 1) * block_size;
+
+        let mut table = vec![0; group_count as usize * GROUP_DESCRIPTOR_SIZE as usize];
+        device.read_bytes(group_table_position, &mut table)?;
+
+        let groups = table
+            .chunks(GROUP_DESCRIPTOR_SIZE as usize)
+            .map(GroupDescriptor::parse)
+            .collect::<Vec<GroupDescriptor>>();
+
+        if groups.iter().any(|group| group.inode_table >= blocks_count) {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let volume = Arc::new(Ext2Volume {
+            device_number: fs::lightsaber_kernel_allocate_device_number(),
+            block_size,
+            blocks_count,
+            inodes_count,
+            first_data_block,
+            blocks_per_group,
+            inodes_per_group,
+            inode_size,
+            first_inode,
+            group_count,
+            group_table_position,
+            deletion_time: last_written.max(inodes_count),
+            file_types: incompatible & INCOMPAT_FILETYPE != 0,
+            large_files: read_only_compatible & RO_COMPAT_LARGE_FILE != 0,
+            read_only: AtomicBool::new(read_only),
+            allocation: Mutex::named("ext2_allocation", Ext2Allocation {
+                groups,
+                free_blocks,
+                free_inodes
+            }),
+            metadata: Mutex::named("ext2_metadata", ()),
+            inodes: Spinlock::named("ext2_inodes", BTreeMap::new()),
+            device
+        });
+
+        let file_system = Self {
+            root: Ext2Inode::load(&volume, ROOT_INODE)?,
+            volume
+        };
+
+        log::info!("Found an ext2 file system on {} with {} blocks of {} bytes.", file_system.volume.device.name(), blocks_count, block_size);
+
+        // An unclean file system is checked before anything gets written to it.
+        if state & STATE_VALID == 0 || state & STATE_ERRORS != 0 {
+            let inconsistencies = file_system.check()?;
+
+            for inconsistency in inconsistencies.iter().take(MAXIMUM_REPORTED_INCONSISTENCIES) {
+                log::warn!("ext2: {}", inconsistency);
+            }
+
+            if !inconsistencies.is_empty() {
+                log::warn!("Found {} inconsistencies, mounting read-only.", inconsistencies.len());
+
+                file_system.volume.read_only.store(true, Ordering::Relaxed);
+            }
+        }
+
+        Ok(file_system)
+    }
+
+    // Cross-checks bitmaps, counters, block pointers, directories and link counts; meant for a quiescent file system.
+    pub fn check(&self) -> Result<Vec<Ext2Inconsistency>, FsError> {
+        let volume = &self.volume;
+        let allocation = volume.allocation.lock();
+        let mut inconsistencies = Vec::new();
+
+        let mut block_bitmaps = Vec::new();
+        let mut inode_bitmaps = Vec::new();
+        let mut free_blocks = 0;
+        let mut free_inodes = 0;
+
+        for (group, descriptor) in allocation.groups.iter().enumerate() {
+            let group = group as u32;
+
+            if !volume.is_valid_block(descriptor.block_bitmap) || !volume.is_valid_block(descriptor.inode_bitmap) || !volume.is_valid_block(descriptor.inode_table) {
+                inconsistencies.push(Ext2Inconsistency::GroupMetadataOutOfRange {
+                    group
+                });
+
+                block_bitmaps.push(vec![0xFF; volume.block_size as usize]);
+                inode_bitmaps.push(vec![0; volume.block_size as usize]);
+                continue;
+            }
+
+            let block_bitmap = volume.read_block(descriptor.block_bitmap)?;
+            let inode_bitmap = volume.read_block(descriptor.inode_bitmap)?;
+
+            let clear = |bitmap: &[u8], limit: u32| (0..limit).filter(|bit| bitmap[(bit / 8) as usize] & (1 << (bit % 8)) == 0).count() as u32;
+
+            let actual = clear(&block_bitmap, volume.blocks_in_group(group));
+
+            if actual != descriptor.free_blocks as u32 {
+                inconsistencies.push(Ext2Inconsistency::GroupFreeBlocks {
+                    group,
+                    recorded: descriptor.free_blocks,
+                    actual
+                });
+            }
+
+            let actual = clear(&inode_bitmap, volume.inodes_per_group);
+
+            if actual != descriptor.free_inodes as u32 {
+                inconsistencies.push(Ext2Inconsistency::GroupFreeInodes {
+                    group,
+                    recorded: descriptor.free_inodes,
+                    actual
+                });
+            }
+
+            free_blocks += descriptor.free_blocks as u32;
+            free_inodes += descriptor.free_inodes as u32;
+
+            block_bitmaps.push(block_bitmap);
+            inode_bitmaps.push(inode_bitmap);
+        }
+
+        if free_blocks != allocation.free_blocks {
+            inconsistencies.push(Ext2Inconsistency::FreeBlockCount {
+                recorded: allocation.free_blocks,
+                actual: free_blocks
+            });
+        }
+
+        if free_inodes != allocation.free_inodes {
+            inconsistencies.push(Ext2Inconsistency::FreeInodeCount {
+                recorded: allocation.free_inodes,
+                actual: free_inodes
+            });
+        }
+
+        drop(allocation);
+
+        let is_block_marked = |block: u32| {
+            let index = block - volume.first_data_block;
+            let bitmap = &block_bitmaps[(index / volume.blocks_per_group) as usize];
+            let bit = index % volume.blocks_per_group;
+
+            bitmap[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+        };
+
+        let is_inode_marked = |inode: u32| {
+            let bitmap = &inode_bitmaps[((inode - 1) / volume.inodes_per_group) as usize];
+            let bit = (inode - 1) % volume.inodes_per_group;
+
+            bitmap[(bit / 8) as usize] & (1 << (bit % 8)) != 0
+        };
+
+        let mut referenced = vec![0u64; (volume.blocks_count as usize + 63) / 64];
+        let mut in_use = BTreeMap::new();
+        let mut directories = Vec::new();
+
+        for number in 1..=volume.inodes_count {
+            if (number < volume.first_inode && number != ROOT_INODE) || !is_inode_marked(number) {
+                continue;
+            }
+
+            let raw = volume.read_inode(number)?;
+
+            if raw.links() == 0 || raw.mode() == 0 {
+                continue;
+            }
+
+            in_use.insert(number, (raw.links(), 0u32));
+
+            if raw.is_directory() {
+                directories.push((number, raw.clone()));
+            }
+
+            if raw.is_fast_symlink(volume.block_size) {
+                continue;
+            }
+
+            // Walks a pointer tree, checking each block before descending into it.
+            let mut pending = (0..BLOCK_POINTERS)
+                .map(|slot| (raw.block(slot), if slot < DIRECT_BLOCKS { 0 } else { slot - DIRECT_BLOCKS + 1 }))
+                .collect::<Vec<(u32, usize)>>();
+
+            while let Some((block, depth)) = pending.pop() {
+                if block == 0 {
+                    continue;
+                }
+
+                if !volume.is_valid_block(block) {
+                    inconsistencies.push(Ext2Inconsistency::BlockOutOfRange {
+                        inode: number,
+                        block
+                    });
+
+                    continue;
+                }
+
+                let (word, bit) = (block as usize / 64, block % 64);
+
+                if referenced[word] & (1 << bit) != 0 {
+                    inconsistencies.push(Ext2Inconsistency::BlockReferencedTwice {
+                        inode: number,
+                        block
+                    });
+
+                    continue;
+                }
+
+                referenced[word] |= 1 << bit;
+
+                if !is_block_marked(block) {
+                    inconsistencies.push(Ext2Inconsistency::BlockNotMarkedUsed {
+                        inode: number,
+                        block
+                    });
+                }
+
+                if depth > 0 {
+                    let data = volume.read_block(block)?;
+
+                    pending.extend(data.chunks(4).map(|pointer| (lightsaber_kernel_read_u32(pointer, 0), depth - 1)));
+                }
+            }
+        }
+
+        for (directory, raw) in directories.iter() {
+            for index in 0..raw.size() / volume.block_size {
+                let block = match volume.lookup_block(raw, index) {
+                    Ok(0) | Err(_) => continue,
+                    Ok(block) => block
+                };
+
+                let records = match volume.parse_directory_block(&volume.read_block(block)?) {
+                    Ok(records) => records,
+                    Err(_) => {
+                        inconsistencies.push(Ext2Inconsistency::BadDirectoryBlock {
+                            directory: *directory,
+                            block
+                        });
+
+                        continue;
+                    }
+                };
+
+                for record in records.iter().filter(|record| record.inode != 0) {
+                    match in_use.get_mut(&record.inode) {
+                        Some((_, references)) => *references += 1,
+                        None => inconsistencies.push(Ext2Inconsistency::EntryToFreeInode {
+                            directory: *directory,
+                            inode: record.inode
+                        })
+                    }
+                }
+            }
+        }
+
+        for (inode, (links, references)) in in_use {
+            match references {
+                0 => inconsistencies.push(Ext2Inconsistency::UnreachableInode {
+                    inode
+                }),
+                references if references != links as u32 => inconsistencies.push(Ext2Inconsistency::LinkCount {
+                    inode,
+                    recorded: links,
+                    actual: references
+                }),
+                _ => { }
+            }
+        }
+
+        Ok(inconsistencies)
+    }
+}
+
+impl FileSystem for Ext2FileSystem {
+    fn name(&self) -> &'static str {
+        "ext2"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(self.volume.device.flush()?)
+    }
+
+    fn usage(&self) -> Result<FsUsage, FsError> {
+        let free_blocks = self.volume.allocation.lock().free_blocks;
+
+        Ok(FsUsage {
+            size: (self.volume.blocks_count - self.volume.first_data_block) as u64 * self.volume.block_size,
+            free: free_blocks as u64 * self.volume.block_size
+        })
+    }
+}
+
+// Mounts the ext2 file system on a registered block device, such as `sda2`, on `path`.
+pub fn lightsaber_kernel_mount_device(name: &str, path: &str) -> Result<(), FsError> {
+    let device = block::lightsaber_kernel_find_block_device(name).ok_or(FsError::NotFound)?;
+
+    mount::lightsaber_kernel_mount(path, Arc::new(Ext2FileSystem::new(device)?))
+}
+
+// Runs once the disks are known; the initial ramdisk or tmpfs stays underneath, out of sight.
+pub fn lightsaber_kernel_mount_root_device() {
+    if let Some(name) = ROOT.get() {
+        let name = name.trim_start_matches("/dev/");
+
+        if let Err(error) = lightsaber_kernel_mount_device(name, "/") {
+            log::error!("Failed to mount {} as the root file system: {}", name, error);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        format,
+        string::ToString
+    };
+
+    use super::*;
+
+    use crate::block::RamDisk;
+
+    // Made with `mke2fs` by `cargo xtask images`, which also lists what is on them.
+    const EXT2_IMAGE: &[u8] = include_bytes!("../../testdata/ext2-1k.img");
+    const EXT2_REVISION_0_IMAGE: &[u8] = include_bytes!("../../testdata/ext2-rev0.img");
+
+    const PATTERN_SIZE: usize = 20000;
+    const LONG_LINK_TARGET: &str = "docs/nested/note.txt/../../../xxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+
+    fn lightsaber_kernel_test_disk(image: &[u8]) -> Arc<dyn BlockDevice> {
+        Arc::new(RamDisk::new("ext2_test", 512, image.to_vec()))
+    }
+
+    // The image with `bytes` written over it at `offset`.
+    fn lightsaber_kernel_test_patched(image: &[u8], offset: usize, bytes: &[u8]) -> Arc<dyn BlockDevice> {
+        let mut image = image.to_vec();
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        Arc::new(RamDisk::new("ext2_test", 512, image))
+    }
+
+    fn lightsaber_kernel_test_pattern(size: usize) -> Vec<u8> {
+        (0..size).map(|index| (index * 7 % 251) as u8).collect()
+    }
+
+    fn lightsaber_kernel_test_lookup(file_system: &Ext2FileSystem, path: &str) -> Result<Arc<dyn Inode>, FsError> {
+        path.split('/').try_fold(file_system.root(), |directory, name| directory.lookup(name))
+    }
+
+    fn lightsaber_kernel_test_inode(file_system: &Ext2FileSystem, path: &str) -> u32 {
+        lightsaber_kernel_test_lookup(file_system, path).unwrap().metadata().unwrap().inode as u32
+    }
+
+    fn lightsaber_kernel_test_read(file_system: &Ext2FileSystem, path: &str) -> Vec<u8> {
+        let inode = lightsaber_kernel_test_lookup(file_system, path).unwrap();
+        let mut buffer = vec![0; inode.metadata().unwrap().size as usize];
+
+        assert_eq!(inode.read_at(0, &mut buffer), Ok(buffer.len()));
+
+        buffer
+    }
+
+    fn lightsaber_kernel_test_entries(directory: &Arc<dyn Inode>) -> Vec<(String, InodeType)> {
+        let mut entries = Vec::new();
+
+        while let Some(entry) = directory.read_directory(entries.len()).unwrap() {
+            entries.push((entry.name, entry.inode_type));
+        }
+
+        entries.sort_by(|first, second| first.0.cmp(&second.0));
+
+        entries
+    }
+
+    // Mounts the image, damages it through the volume, and mounts it again so nothing is served from memory.
+    fn lightsaber_kernel_test_corrupted<F>(image: &[u8], corrupt: F) -> Ext2FileSystem
+    where
+        F: FnOnce(&Ext2FileSystem) {
+        let disk = lightsaber_kernel_test_disk(image);
+
+        corrupt(&Ext2FileSystem::new(disk.clone()).unwrap());
+
+        Ext2FileSystem::new(disk).unwrap()
+    }
+
+    #[test_case]
+    fn ext2_reads_mke2fs_images() {
+        for (image, block_size) in [(EXT2_IMAGE, 1024), (EXT2_REVISION_0_IMAGE, 4096)].iter() {
+            let file_system = Ext2FileSystem::new(lightsaber_kernel_test_disk(image)).unwrap();
+
+            assert_eq!(file_system.volume.block_size, *block_size);
+            assert!(!file_system.volume.read_only.load(Ordering::Relaxed));
+
+            // The revision 0 image has no file types in its directories, so those come from the inodes.
+            assert_eq!(lightsaber_kernel_test_entries(&file_system.root()), [
+                ("docs".to_string(), InodeType::Directory),
+                ("hello.txt".to_string(), InodeType::File),
+                ("link".to_string(), InodeType::Symlink),
+                ("long-link".to_string(), InodeType::Symlink),
+                ("lost+found".to_string(), InodeType::Directory)
+            ]);
+
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "hello.txt"), b"Hello, ext2!\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "docs/nested/note.txt"), b"nested\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "docs/pattern.bin"), lightsaber_kernel_test_pattern(PATTERN_SIZE));
+
+            let hello = lightsaber_kernel_test_lookup(&file_system, "hello.txt").unwrap().metadata().unwrap();
+
+            assert_eq!(hello.links, 2);
+            assert_eq!(lightsaber_kernel_test_inode(&file_system, "docs/hello-again.txt"), hello.inode as u32);
+
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "link").unwrap().read_link().unwrap(), "hello.txt");
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "long-link").unwrap().read_link().unwrap(), LONG_LINK_TARGET);
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "docs/missing").err(), Some(FsError::NotFound));
+
+            assert_eq!(file_system.check(), Ok(Vec::new()));
+        }
+    }
+
+    #[test_case]
+    fn ext2_writes_survive_a_remount_and_check_clean() {
+        let disk = lightsaber_kernel_test_disk(EXT2_IMAGE);
+        let pattern = lightsaber_kernel_test_pattern(40000);
+
+        // Past the direct and single indirect blocks, so the write goes through a double indirect block.
+        let far = 300 * 1024;
+
+        {
+            let file_system = Ext2FileSystem::new(disk.clone()).unwrap();
+            let root = file_system.root();
+
+            let file = root.create("new.bin", InodeType::File, 0o644).unwrap();
+            assert_eq!(file.write_at(0, &pattern), Ok(pattern.len()));
+            assert_eq!(file.write_at(far, b"far"), Ok(3));
+
+            let directory = root.create("made", InodeType::Directory, 0o755).unwrap();
+            directory.create("inner", InodeType::File, 0o600).unwrap();
+
+            root.symlink("short", "new.bin").unwrap();
+            root.symlink("longer", LONG_LINK_TARGET).unwrap();
+            root.link("hard", &file).unwrap();
+            root.unlink("hello.txt").unwrap();
+
+            root.create("gone", InodeType::Directory, 0o755).unwrap();
+            assert_eq!(root.unlink("made"), Err(FsError::NotEmpty));
+            root.unlink("gone").unwrap();
+
+            lightsaber_kernel_test_lookup(&file_system, "docs/pattern.bin").unwrap().truncate(5000).unwrap();
+
+            assert_eq!(file_system.check(), Ok(Vec::new()));
+        }
+
+        let file_system = Ext2FileSystem::new(disk).unwrap();
+        let contents = lightsaber_kernel_test_read(&file_system, "new.bin");
+
+        assert_eq!(contents.len() as u64, far + 3);
+        assert_eq!(&contents[..pattern.len()], &pattern[..]);
+        assert!(contents[pattern.len()..far as usize].iter().all(|byte| *byte == 0));
+        assert_eq!(&contents[far as usize..], b"far");
+
+        assert_eq!(lightsaber_kernel_test_inode(&file_system, "hard"), lightsaber_kernel_test_inode(&file_system, "new.bin"));
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "hard").unwrap().metadata().unwrap().links, 2);
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "made/inner").unwrap().metadata().unwrap().mode, 0o600);
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "made").unwrap().metadata().unwrap().links, 2);
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "short").unwrap().read_link().unwrap(), "new.bin");
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "longer").unwrap().read_link().unwrap(), LONG_LINK_TARGET);
+
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "hello.txt").err(), Some(FsError::NotFound));
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "gone").err(), Some(FsError::NotFound));
+        assert_eq!(lightsaber_kernel_test_read(&file_system, "docs/hello-again.txt"), b"Hello, ext2!\n");
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "docs/hello-again.txt").unwrap().metadata().unwrap().links, 1);
+        assert_eq!(lightsaber_kernel_test_read(&file_system, "docs/pattern.bin"), &lightsaber_kernel_test_pattern(PATTERN_SIZE)[..5000]);
+
+        assert_eq!(file_system.check(), Ok(Vec::new()));
+    }
+
+    #[test_case]
+    fn ext2_gives_back_everything_a_deleted_file_held() {
+        let file_system = Ext2FileSystem::new(lightsaber_kernel_test_disk(EXT2_IMAGE)).unwrap();
+        let free = |file_system: &Ext2FileSystem| {
+            let allocation = file_system.volume.allocation.lock();
+
+            (allocation.free_blocks, allocation.free_inodes)
+        };
+
+        let before = free(&file_system);
+        let root = file_system.root();
+
+        {
+            let file = root.create("sparse", InodeType::File, 0o644).unwrap();
+            file.write_at(0, &[1; 2048]).unwrap();
+            file.write_at(300 * 1024, &[2; 2048]).unwrap();
+
+            root.create("empty", InodeType::Directory, 0o755).unwrap();
+        }
+
+        assert_ne!(free(&file_system), before);
+
+        root.unlink("sparse").unwrap();
+        root.unlink("empty").unwrap();
+
+        assert_eq!(free(&file_system), before);
+        assert_eq!(file_system.check(), Ok(Vec::new()));
+    }
+
+    #[test_case]
+    fn ext2_refuses_superblocks_it_cannot_trust() {
+        let mount = |disk| Ext2FileSystem::new(disk).err();
+
+        // The magic number, a block count past the end of the device, and the extents feature.
+        assert_eq!(mount(lightsaber_kernel_test_patched(EXT2_IMAGE, 1024 + 56, &[0, 0])), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(EXT2_IMAGE, 1024 + 4, &1025u32.to_le_bytes())), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_disk(&EXT2_IMAGE[..EXT2_IMAGE.len() / 2])), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(EXT2_IMAGE, 1024 + 96, &0x42u32.to_le_bytes())), Some(FsError::NotSupported));
+
+        // An unknown read-only compatible feature still lets the file system be read.
+        let file_system = Ext2FileSystem::new(lightsaber_kernel_test_patched(EXT2_IMAGE, 1024 + 100, &0x3Bu32.to_le_bytes())).unwrap();
+
+        assert!(file_system.volume.read_only.load(Ordering::Relaxed));
+        assert_eq!(lightsaber_kernel_test_read(&file_system, "hello.txt"), b"Hello, ext2!\n");
+        assert_eq!(file_system.root().create("new", InodeType::File, 0o644).err(), Some(FsError::ReadOnly));
+    }
+
+    #[test_case]
+    fn ext2_check_reports_bitmap_and_counter_damage() {
+        let mut hello = (0, 0, 0);
+
+        let file_system = lightsaber_kernel_test_corrupted(EXT2_IMAGE, |file_system| {
+            let volume = &file_system.volume;
+            let number = lightsaber_kernel_test_inode(file_system, "hello.txt");
+            let block = volume.read_inode(number).unwrap().block(0);
+            let group = (block - volume.first_data_block) / volume.blocks_per_group;
+            let bitmap = volume.allocation.lock().groups[group as usize].block_bitmap;
+
+            volume.update_bitmap_bit(bitmap, (block - volume.first_data_block) % volume.blocks_per_group, false).unwrap();
+            volume.device.write_bytes(SUPERBLOCK_OFFSET + 16, &1u32.to_le_bytes()).unwrap();
+
+            hello = (number, block, group);
+        });
+
+        let (inode, block, group) = hello;
+        let free_inodes = file_system.volume.allocation.lock().groups.iter().map(|group| group.free_inodes as u32).sum();
+        let recorded = file_system.volume.allocation.lock().groups[group as usize].free_blocks;
+
+        let inconsistencies = file_system.check().unwrap();
+
+        assert!(inconsistencies.contains(&Ext2Inconsistency::GroupFreeBlocks {
+            group,
+            recorded,
+            actual: recorded as u32 + 1
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::FreeInodeCount {
+            recorded: 1,
+            actual: free_inodes
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::BlockNotMarkedUsed {
+            inode,
+            block
+        }));
+        assert_eq!(inconsistencies.len(), 3);
+    }
+
+    #[test_case]
+    fn ext2_check_reports_bad_block_pointers() {
+        let mut pattern = 0;
+
+        let file_system = lightsaber_kernel_test_corrupted(EXT2_IMAGE, |file_system| {
+            let volume = &file_system.volume;
+            pattern = lightsaber_kernel_test_inode(file_system, "docs/pattern.bin");
+
+            let mut raw = volume.read_inode(pattern).unwrap();
+            raw.set_block(1, raw.block(0));
+            raw.set_block(2, volume.blocks_count + 10);
+            volume.write_inode(pattern, &raw).unwrap();
+        });
+
+        let raw = file_system.volume.read_inode(pattern).unwrap();
+        let inconsistencies = file_system.check().unwrap();
+
+        assert!(inconsistencies.contains(&Ext2Inconsistency::BlockReferencedTwice {
+            inode: pattern,
+            block: raw.block(0)
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::BlockOutOfRange {
+            inode: pattern,
+            block: file_system.volume.blocks_count + 10
+        }));
+
+        // The blocks before the bad pointer still read; the one it points at does not.
+        let inode = lightsaber_kernel_test_lookup(&file_system, "docs/pattern.bin").unwrap();
+        let mut buffer = vec![0; 2048];
+
+        assert_eq!(inode.read_at(0, &mut buffer), Ok(2048));
+        assert_eq!(inode.read_at(2048, &mut buffer), Err(FsError::Io));
+    }
+
+    #[test_case]
+    fn ext2_check_reports_directory_and_link_damage() {
+        let mut numbers = (0, 0, 0);
+
+        let file_system = lightsaber_kernel_test_corrupted(EXT2_IMAGE, |file_system| {
+            let volume = &file_system.volume;
+            let docs = lightsaber_kernel_test_inode(file_system, "docs");
+            let hello = lightsaber_kernel_test_inode(file_system, "hello.txt");
+            let link = lightsaber_kernel_test_inode(file_system, "link");
+
+            let mut raw = volume.read_inode(hello).unwrap();
+            raw.set_links(5);
+            volume.write_inode(hello, &raw).unwrap();
+
+            // Dropping the entry without the inode leaves the inode in use with nothing referring to it.
+            let root = Ext2Inode::load(volume, ROOT_INODE).unwrap();
+            let mut raw = volume.read_inode(ROOT_INODE).unwrap();
+            root.remove_record(&mut raw, "link").unwrap();
+            volume.write_inode(ROOT_INODE, &raw).unwrap();
+
+            // A zero record length makes the first block of `docs` unreadable.
+            let block = volume.read_inode(docs).unwrap().block(0);
+            volume.device.write_bytes(block as u64 * volume.block_size + 4, &[0, 0]).unwrap();
+
+            numbers = (docs, hello, link);
+        });
+
+        let (docs, hello, link) = numbers;
+        let docs_block = file_system.volume.read_inode(docs).unwrap().block(0);
+        let inconsistencies = file_system.check().unwrap();
+
+        assert!(inconsistencies.contains(&Ext2Inconsistency::BadDirectoryBlock {
+            directory: docs,
+            block: docs_block
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::UnreachableInode {
+            inode: link
+        }));
+
+        // The second link to `hello.txt` lives in the unreadable block, so only one is counted.
+        assert!(inconsistencies.contains(&Ext2Inconsistency::LinkCount {
+            inode: hello,
+            recorded: 5,
+            actual: 1
+        }));
+
+        assert_eq!(lightsaber_kernel_test_lookup(&file_system, "docs/pattern.bin").err(), Some(FsError::Io));
+        assert_eq!(lightsaber_kernel_test_read(&file_system, "hello.txt"), b"Hello, ext2!\n");
+    }
+
+    #[test_case]
+    fn ext2_check_reports_entries_to_free_inodes_and_bad_groups() {
+        let mut hello = 0;
+
+        let file_system = lightsaber_kernel_test_corrupted(EXT2_IMAGE, |file_system| {
+            let volume = &file_system.volume;
+            hello = lightsaber_kernel_test_inode(file_system, "hello.txt");
+
+            let group = (hello - 1) / volume.inodes_per_group;
+            let bitmap = volume.allocation.lock().groups[group as usize].inode_bitmap;
+            volume.update_bitmap_bit(bitmap, (hello - 1) % volume.inodes_per_group, false).unwrap();
+
+            // The second group's block bitmap is pointed past the end of the file system.
+            volume.device.write_bytes(volume.group_table_position + GROUP_DESCRIPTOR_SIZE, &volume.blocks_count.to_le_bytes()).unwrap();
+        });
+
+        let inconsistencies = file_system.check().unwrap();
+        let docs = lightsaber_kernel_test_inode(&file_system, "docs");
+
+        assert!(inconsistencies.contains(&Ext2Inconsistency::EntryToFreeInode {
+            directory: ROOT_INODE,
+            inode: hello
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::EntryToFreeInode {
+            directory: docs,
+            inode: hello
+        }));
+        assert!(inconsistencies.contains(&Ext2Inconsistency::GroupMetadataOutOfRange {
+            group: 1
+        }));
+    }
+
+    #[test_case]
+    fn ext2_mounts_a_damaged_unclean_file_system_read_only() {
+        // Only an unclean file system is checked when it is mounted, and only a damaged one is kept read-only.
+        for (state, damaged, read_only) in [(STATE_VALID, true, false), (STATE_ERRORS, false, false), (STATE_ERRORS, true, true)].iter() {
+            let disk = lightsaber_kernel_test_disk(EXT2_IMAGE);
+
+            if *damaged {
+                let file_system = Ext2FileSystem::new(disk.clone()).unwrap();
+                let hello = lightsaber_kernel_test_inode(&file_system, "hello.txt");
+                let mut raw = file_system.volume.read_inode(hello).unwrap();
+
+                raw.set_links(1);
+                file_system.volume.write_inode(hello, &raw).unwrap();
+            }
+
+            disk.write_bytes(SUPERBLOCK_OFFSET + 58, &state.to_le_bytes()).unwrap();
+
+            let file_system = Ext2FileSystem::new(disk).unwrap();
+            let created = file_system.root().create("new", InodeType::File, 0o644).err();
+
+            assert_eq!(file_system.volume.read_only.load(Ordering::Relaxed), *read_only);
+            assert_eq!(created, if *read_only { Some(FsError::ReadOnly) } else { None });
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "hello.txt"), b"Hello, ext2!\n");
+        }
+    }
+
+    #[test_case]
+    fn ext2_mounts_a_registered_block_device() {
+        block::lightsaber_kernel_register_block_device(Arc::new(RamDisk::new("ext2_mount_test", 512, EXT2_IMAGE.to_vec())));
+
+        // The mount table is global, so this borrows a writable directory from the running system.
+        let scratch = path::lightsaber_kernel_lookup("/tmp", true).or_else(|_| mount::lightsaber_kernel_root()).unwrap();
+        let mountpoint = format!("{}/ext2_mount_test", scratch.path().trim_end_matches('/'));
+
+        mount::lightsaber_kernel_create_mountpoint(&mountpoint).unwrap();
+
+        let missing = lightsaber_kernel_mount_device("ext2_missing_test", &mountpoint);
+        let mounted = lightsaber_kernel_mount_device("ext2_mount_test", &mountpoint);
+        let hello = fs::lightsaber_kernel_read_file(&format!("{}/hello.txt", mountpoint));
+
+        mount::lightsaber_kernel_unmount(&mountpoint).unwrap();
+        block::lightsaber_kernel_unregister_block_device("ext2_mount_test");
+        scratch.inode().unlink("ext2_mount_test").unwrap();
+        scratch.forget("ext2_mount_test");
+
+        assert_eq!(missing, Err(FsError::NotFound));
+        assert_eq!(mounted, Ok(()));
+        assert_eq!(hello.as_deref(), Ok(&b"Hello, ext2!\n"[..]));
+    }
+}
@@ -0,0 +1,2019 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::String,
+    sync::{
+        Arc,
+        Weak
+    },
+    vec,
+    vec::Vec
+};
+
+use core::fmt;
+
+use crate::{
+    block::{
+        self,
+        BlockDevice
+    },
+    fs::{
+        self,
+        mount,
+        path,
+        DirectoryEntry,
+        FileSystem,
+        FsError,
+        FsUsage,
+        Inode,
+        InodeType,
+        Metadata
+    },
+    sync::{
+        Mutex,
+        Spinlock
+    }
+};
+
+const BOOT_SIGNATURE: u16 = 0xAA55;
+
+const FS_INFO_LEAD_SIGNATURE: u32 = 0x4161_5252;
+const FS_INFO_STRUCTURE_SIGNATURE: u32 = 0x6141_7272;
+const FS_INFO_TRAIL_SIGNATURE: u32 = 0xAA55_0000;
+const FS_INFO_UNKNOWN: u32 = 0xFFFF_FFFF;
+
+const FAT12_MAXIMUM_CLUSTERS: u32 = 4085;
+const FAT16_MAXIMUM_CLUSTERS: u32 = 65525;
+
+const FREE_CLUSTER: u32 = 0;
+const FIRST_DATA_CLUSTER: u32 = 2;
+const FAT_SCAN_BATCH: u32 = 1024;
+
+const DIRECTORY_ENTRY_SIZE: usize = 32;
+const MAXIMUM_DIRECTORY_ENTRIES: usize = 65536;
+
+const ATTRIBUTE_READ_ONLY: u8 = 0x01;
+const ATTRIBUTE_VOLUME_ID: u8 = 0x08;
+const ATTRIBUTE_DIRECTORY: u8 = 0x10;
+const ATTRIBUTE_ARCHIVE: u8 = 0x20;
+const ATTRIBUTE_LONG_NAME: u8 = 0x0F;
+
+const ENTRY_END_OF_DIRECTORY: u8 = 0x00;
+const ENTRY_DELETED: u8 = 0xE5;
+const ENTRY_KANJI_E5: u8 = 0x05;
+
+const CASE_LOWER_BASE: u8 = 0x08;
+const CASE_LOWER_EXTENSION: u8 = 0x10;
+
+const LONG_NAME_LAST_ENTRY: u8 = 0x40;
+const LONG_NAME_ORDINAL_MASK: u8 = 0x1F;
+const LONG_NAME_CHARACTERS: usize = 13;
+const LONG_NAME_OFFSETS: [usize; LONG_NAME_CHARACTERS] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+const SHORT_NAME_SPECIAL_CHARACTERS: &[u8] = b"$%'-_@~`!(){}^#&";
+const LONG_NAME_INVALID_CHARACTERS: &str = "\"*/:<>?\\|";
+
+// There is no wall clock yet, so new entries are stamped with the FAT epoch.
+const FAT_EPOCH_DATE: u16 = (1 << 5) | 1;
+const FAT_EPOCH_TIME: u16 = 0;
+
+const ROOT_INODE: u64 = 1;
+
+const EFI_SYSTEM_PARTITION_MOUNTPOINT: &str = "/boot/efi";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FatType {
+    Fat12,
+
+    Fat16,
+
+    Fat32
+}
+
+impl FatType {
+    fn end_of_chain(self) -> u32 {
+        match self {
+            Self::Fat12 => 0xFFF,
+            Self::Fat16 => 0xFFFF,
+            Self::Fat32 => 0x0FFF_FFFF
+        }
+    }
+
+    fn is_end_of_chain(self, value: u32) -> bool {
+        value >= self.end_of_chain() - 7
+    }
+}
+
+impl fmt::Display for FatType {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Fat12 => formatter.write_str("FAT12"),
+            Self::Fat16 => formatter.write_str("FAT16"),
+            Self::Fat32 => formatter.write_str("FAT32")
+        }
+    }
+}
+
+fn lightsaber_kernel_read_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+fn lightsaber_kernel_read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]])
+}
+
+fn lightsaber_kernel_write_u16(data: &mut [u8], offset: usize, value: u16) {
+    data[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+fn lightsaber_kernel_write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+// Converts a FAT date and time to seconds since the Unix epoch.
+fn lightsaber_kernel_fat_timestamp(date: u16, time: u16) -> u64 {
+    let year = 1980 + (date >> 9) as i64;
+    let month = ((date >> 5) & 0xF).max(1).min(12) as i64;
+    let day = (date & 0x1F).max(1) as i64;
+
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year / 400;
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = (time >> 11) as i64 * 3600 + ((time >> 5) & 0x3F) as i64 * 60 + (time & 0x1F) as i64 * 2;
+
+    (days * 86400 + seconds) as u64
+}
+
+fn lightsaber_kernel_short_name_checksum(short_name: &[u8; 11]) -> u8 {
+    short_name.iter().fold(0u8, |sum, byte| ((sum & 1) << 7).wrapping_add(sum >> 1).wrapping_add(*byte))
+}
+
+fn lightsaber_kernel_is_short_name_character(byte: u8) -> bool {
+    byte.is_ascii_uppercase() || byte.is_ascii_digit() || SHORT_NAME_SPECIAL_CHARACTERS.contains(&byte)
+}
+
+// Returns the 8.3 form and case flags if `name` can be stored without a long name.
+fn lightsaber_kernel_exact_short_name(name: &str) -> Option<([u8; 11], u8)> {
+    let (base, extension) = match name.find('.') {
+        Some(index) => (&name[..index], &name[index + 1..]),
+        None => (name, "")
+    };
+
+    if base.is_empty() || base.len() > 8 || extension.len() > 3 || extension.contains('.') || (name.contains('.') && extension.is_empty()) {
+        return None;
+    }
+
+    let mut short_name = [b' '; 11];
+    let mut case = 0;
+
+    for &(part, offset, flag) in [(base, 0, CASE_LOWER_BASE), (extension, 8, CASE_LOWER_EXTENSION)].iter() {
+        let has_lower = part.bytes().any(|byte| byte.is_ascii_lowercase());
+        let has_upper = part.bytes().any(|byte| byte.is_ascii_uppercase());
+
+        if has_lower && has_upper {
+            return None;
+        }
+
+        if has_lower {
+            case |= flag;
+        }
+
+        for (index, byte) in part.bytes().enumerate() {
+            let byte = byte.to_ascii_uppercase();
+
+            if !lightsaber_kernel_is_short_name_character(byte) {
+                return None;
+            }
+
+            short_name[offset + index] = byte;
+        }
+    }
+
+    Some((short_name, case))
+}
+
+// Derives a unique `BASIS~N.EXT` alias for a name that needs a long name entry.
+fn lightsaber_kernel_generate_short_name(name: &str, existing: &[[u8; 11]]) -> Result<[u8; 11], FsError> {
+    let trimmed = name.trim_start_matches('.');
+
+    let (base, extension) = match trimmed.rfind('.') {
+        Some(index) => (&trimmed[..index], &trimmed[index + 1..]),
+        None => (trimmed, "")
+    };
+
+    let convert = |part: &str, limit: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|character| *character != ' ' && *character != '.')
+            .map(|character| match character.is_ascii() {
+                true if lightsaber_kernel_is_short_name_character(character.to_ascii_uppercase() as u8) => character.to_ascii_uppercase() as u8,
+                _ => b'_'
+            })
+            .take(limit)
+            .collect()
+    };
+
+    let mut basis = convert(base, 8);
+    let extension = convert(extension, 3);
+
+    if basis.is_empty() {
+        basis.push(b'_');
+    }
+
+    for number in 1..1_000_000 {
+        let suffix = format!("~{}", number);
+        let length = basis.len().min(8 - suffix.len());
+
+        let mut short_name = [b' '; 11];
+        short_name[..length].copy_from_slice(&basis[..length]);
+        short_name[length..length + suffix.len()].copy_from_slice(suffix.as_bytes());
+        short_name[8..8 + extension.len()].copy_from_slice(&extension);
+
+        if !existing.contains(&short_name) {
+            return Ok(short_name);
+        }
+    }
+
+    Err(FsError::NoSpace)
+}
+
+fn lightsaber_kernel_display_short_name(short_name: &[u8; 11], case: u8) -> String {
+    let convert = |bytes: &[u8], lower: bool| -> String {
+        bytes.iter()
+            .enumerate()
+            .map(|(index, byte)| match (index, *byte) {
+                (0, ENTRY_KANJI_E5) => ENTRY_DELETED,
+                (_, byte) => byte
+            })
+            .map(|byte| match lower {
+                true => byte.to_ascii_lowercase() as char,
+                false => byte as char
+            })
+            .collect::<String>()
+            .trim_end_matches(' ')
+            .into()
+    };
+
+    let base = convert(&short_name[..8], case & CASE_LOWER_BASE != 0);
+    let extension = convert(&short_name[8..], case & CASE_LOWER_EXTENSION != 0);
+
+    match extension.is_empty() {
+        true => base,
+        false => format!("{}.{}", base, extension)
+    }
+}
+
+fn lightsaber_kernel_check_name(name: &str) -> Result<(), FsError> {
+    if name.is_empty() || name == "." || name == ".." || name.ends_with('.') || name.ends_with(' ') {
+        return Err(FsError::InvalidArgument);
+    }
+
+    if name.chars().any(|character| (character as u32) < 0x20 || LONG_NAME_INVALID_CHARACTERS.contains(character)) {
+        return Err(FsError::InvalidArgument);
+    }
+
+    if name.encode_utf16().count() > path::MAXIMUM_NAME_LENGTH {
+        return Err(FsError::NameTooLong);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_encode_short_entry(short_name: &[u8; 11], case: u8, attributes: u8, first_cluster: u32) -> [u8; DIRECTORY_ENTRY_SIZE] {
+    let mut entry = [0; DIRECTORY_ENTRY_SIZE];
+
+    entry[..11].copy_from_slice(short_name);
+    entry[11] = attributes;
+    entry[12] = case;
+    lightsaber_kernel_write_u16(&mut entry, 14, FAT_EPOCH_TIME);
+    lightsaber_kernel_write_u16(&mut entry, 16, FAT_EPOCH_DATE);
+    lightsaber_kernel_write_u16(&mut entry, 18, FAT_EPOCH_DATE);
+    lightsaber_kernel_write_u16(&mut entry, 20, (first_cluster >> 16) as u16);
+    lightsaber_kernel_write_u16(&mut entry, 22, FAT_EPOCH_TIME);
+    lightsaber_kernel_write_u16(&mut entry, 24, FAT_EPOCH_DATE);
+    lightsaber_kernel_write_u16(&mut entry, 26, first_cluster as u16);
+
+    entry
+}
+
+// Long name entries are stored last part first, directly in front of the short entry.
+fn lightsaber_kernel_encode_long_name_entries(name: &str, checksum: u8) -> Vec<[u8; DIRECTORY_ENTRY_SIZE]> {
+    let units = name.encode_utf16().collect::<Vec<u16>>();
+    let count = (units.len() + LONG_NAME_CHARACTERS - 1) / LONG_NAME_CHARACTERS;
+
+    (1..=count).rev().map(|ordinal| {
+        let mut entry = [0; DIRECTORY_ENTRY_SIZE];
+
+        entry[0] = ordinal as u8 | if ordinal == count { LONG_NAME_LAST_ENTRY } else { 0 };
+        entry[11] = ATTRIBUTE_LONG_NAME;
+        entry[13] = checksum;
+
+        for (character, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+            let index = (ordinal - 1) * LONG_NAME_CHARACTERS + character;
+
+            let unit = match index {
+                index if index < units.len() => units[index],
+                index if index == units.len() => 0,
+                _ => 0xFFFF
+            };
+
+            lightsaber_kernel_write_u16(&mut entry, *offset, unit);
+        }
+
+        entry
+    }).collect()
+}
+
+#[derive(Debug, Clone)]
+struct FatDirectoryEntry {
+    name: String,
+    short_name: [u8; 11],
+    attributes: u8,
+    first_cluster: u32,
+    size: u32,
+    accessed_date: u16,
+    modified_date: u16,
+    modified_time: u16,
+    created_date: u16,
+    created_time: u16,
+    first_slot: usize,
+    slot: usize
+}
+
+impl FatDirectoryEntry {
+    fn is_directory(&self) -> bool {
+        self.attributes & ATTRIBUTE_DIRECTORY != 0
+    }
+
+    fn is_dot(&self) -> bool {
+        self.short_name == *b".          " || self.short_name == *b"..         "
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name) || lightsaber_kernel_display_short_name(&self.short_name, 0).eq_ignore_ascii_case(name)
+    }
+}
+
+// A directory's raw slots together with where each cluster of them lives on disk.
+struct RawDirectory {
+    data: Vec<u8>,
+    extents: Vec<u64>,
+    extent_size: u64
+}
+
+impl RawDirectory {
+    fn slot_count(&self) -> usize {
+        self.data.len() / DIRECTORY_ENTRY_SIZE
+    }
+
+    fn slot(&self, slot: usize) -> &[u8] {
+        &self.data[slot * DIRECTORY_ENTRY_SIZE..(slot + 1) * DIRECTORY_ENTRY_SIZE]
+    }
+
+    fn position(&self, slot: usize) -> u64 {
+        let offset = (slot * DIRECTORY_ENTRY_SIZE) as u64;
+
+        self.extents[(offset / self.extent_size) as usize] + offset % self.extent_size
+    }
+
+    fn entries(&self) -> Vec<FatDirectoryEntry> {
+        let mut entries = Vec::new();
+        let mut long_name: Vec<u16> = Vec::new();
+        let mut expected = 0;
+        let mut checksum = 0;
+        let mut first_slot = 0;
+
+        for slot in 0..self.slot_count() {
+            let data = self.slot(slot);
+
+            match data[0] {
+                ENTRY_END_OF_DIRECTORY => break,
+                ENTRY_DELETED => {
+                    expected = 0;
+                    continue;
+                }
+                _ => { }
+            }
+
+            if data[11] & 0x3F == ATTRIBUTE_LONG_NAME {
+                let ordinal = data[0] & LONG_NAME_ORDINAL_MASK;
+
+                if data[0] & LONG_NAME_LAST_ENTRY != 0 {
+                    long_name = vec![0xFFFF; ordinal as usize * LONG_NAME_CHARACTERS];
+                    checksum = data[13];
+                    first_slot = slot;
+                } else if ordinal + 1 != expected || data[13] != checksum {
+                    expected = 0;
+                    continue;
+                }
+
+                if ordinal == 0 {
+                    expected = 0;
+                    continue;
+                }
+
+                for (character, offset) in LONG_NAME_OFFSETS.iter().enumerate() {
+                    long_name[(ordinal as usize - 1) * LONG_NAME_CHARACTERS + character] = lightsaber_kernel_read_u16(data, *offset);
+                }
+
+                expected = ordinal;
+                continue;
+            }
+
+            if data[11] & ATTRIBUTE_VOLUME_ID != 0 {
+                expected = 0;
+                continue;
+            }
+
+            let mut short_name = [0; 11];
+            short_name.copy_from_slice(&data[..11]);
+
+            // A long name only counts if it is complete and belongs to this short entry.
+            let long_name_valid = expected == 1 && checksum == lightsaber_kernel_short_name_checksum(&short_name);
+
+            let name = match long_name_valid {
+                true => {
+                    let length = long_name.iter().position(|unit| *unit == 0 || *unit == 0xFFFF).unwrap_or(long_name.len());
+
+                    String::from_utf16_lossy(&long_name[..length])
+                }
+                false => lightsaber_kernel_display_short_name(&short_name, data[12])
+            };
+
+            entries.push(FatDirectoryEntry {
+                name,
+                short_name,
+                attributes: data[11],
+                first_cluster: (lightsaber_kernel_read_u16(data, 20) as u32) << 16 | lightsaber_kernel_read_u16(data, 26) as u32,
+                size: lightsaber_kernel_read_u32(data, 28),
+                accessed_date: lightsaber_kernel_read_u16(data, 18),
+                modified_date: lightsaber_kernel_read_u16(data, 24),
+                modified_time: lightsaber_kernel_read_u16(data, 22),
+                created_date: lightsaber_kernel_read_u16(data, 16),
+                created_time: lightsaber_kernel_read_u16(data, 14),
+                first_slot: if long_name_valid { first_slot } else { slot },
+                slot
+            });
+
+            expected = 0;
+        }
+
+        entries
+    }
+
+    fn find_free_slots(&self, count: usize) -> Option<usize> {
+        let mut run = 0;
+
+        for slot in 0..self.slot_count() {
+            match self.slot(slot)[0] {
+                ENTRY_END_OF_DIRECTORY | ENTRY_DELETED => run += 1,
+                _ => run = 0
+            }
+
+            if run == count {
+                return Some(slot + 1 - count);
+            }
+        }
+
+        None
+    }
+}
+
+struct FatAllocation {
+    free_count: Option<u32>,
+    next_free: u32,
+    dirty: bool
+}
+
+struct FatVolume {
+    device: Arc<dyn BlockDevice>,
+    device_number: u64,
+    fat_type: FatType,
+    cluster_size: u64,
+    fat_start: u64,
+    fat_size: u64,
+    fat_count: u64,
+    active_fat: Option<u64>,
+    root_directory_position: u64,
+    root_directory_size: u64,
+    root_cluster: u32,
+    data_start: u64,
+    cluster_count: u32,
+    fs_info_position: Option<u64>,
+    allocation: Mutex<FatAllocation>,
+    entries: Mutex<()>,
+    inodes: Spinlock<BTreeMap<u64, Weak<FatInode>>>
+}
+
+impl FatVolume {
+    fn check_writable(&self) -> Result<(), FsError> {
+        match self.device.is_read_only() {
+            true => Err(FsError::ReadOnly),
+            false => Ok(())
+        }
+    }
+
+    fn cluster_position(&self, cluster: u32) -> u64 {
+        self.data_start + (cluster - FIRST_DATA_CLUSTER) as u64 * self.cluster_size
+    }
+
+    fn is_valid_cluster(&self, cluster: u32) -> bool {
+        cluster >= FIRST_DATA_CLUSTER && cluster < self.cluster_count + FIRST_DATA_CLUSTER
+    }
+
+    fn fat_offset(&self, cluster: u32) -> u64 {
+        match self.fat_type {
+            FatType::Fat12 => cluster as u64 + cluster as u64 / 2,
+            FatType::Fat16 => cluster as u64 * 2,
+            FatType::Fat32 => cluster as u64 * 4
+        }
+    }
+
+    fn read_fat_entries(&self, first: u32, count: u32) -> Result<Vec<u32>, FsError> {
+        let start = self.fat_offset(first);
+        let end = self.fat_offset(first + count - 1) + if self.fat_type == FatType::Fat32 { 4 } else { 2 };
+        let base = self.fat_start + self.active_fat.unwrap_or(0) * self.fat_size;
+
+        let mut data = vec![0; (end - start) as usize];
+        self.device.read_bytes(base + start, &mut data)?;
+
+        Ok((first..first + count).map(|cluster| {
+            let offset = (self.fat_offset(cluster) - start) as usize;
+
+            match self.fat_type {
+                FatType::Fat12 if cluster % 2 == 1 => lightsaber_kernel_read_u16(&data, offset) as u32 >> 4,
+                FatType::Fat12 => lightsaber_kernel_read_u16(&data, offset) as u32 & 0xFFF,
+                FatType::Fat16 => lightsaber_kernel_read_u16(&data, offset) as u32,
+                FatType::Fat32 => lightsaber_kernel_read_u32(&data, offset) & 0x0FFF_FFFF
+            }
+        }).collect())
+    }
+
+    // Entries share bytes and sectors, so every update happens with the allocation lock held.
+    fn write_fat_entry(&self, _allocation: &mut FatAllocation, cluster: u32, value: u32) -> Result<(), FsError> {
+        let offset = self.fat_offset(cluster);
+
+        let copies = match self.active_fat {
+            Some(active) => active..active + 1,
+            None => 0..self.fat_count
+        };
+
+        for copy in copies {
+            let position = self.fat_start + copy * self.fat_size + offset;
+
+            match self.fat_type {
+                FatType::Fat12 => {
+                    let mut data = [0; 2];
+                    self.device.read_bytes(position, &mut data)?;
+
+                    let current = u16::from_le_bytes(data);
+                    let updated = match cluster % 2 {
+                        1 => (current & 0x000F) | ((value as u16) << 4),
+                        _ => (current & 0xF000) | (value as u16 & 0x0FFF)
+                    };
+
+                    self.device.write_bytes(position, &updated.to_le_bytes())?;
+                }
+                FatType::Fat16 => self.device.write_bytes(position, &(value as u16).to_le_bytes())?,
+                FatType::Fat32 => {
+                    let mut data = [0; 4];
+                    self.device.read_bytes(position, &mut data)?;
+
+                    let updated = (u32::from_le_bytes(data) & 0xF000_0000) | (value & 0x0FFF_FFFF);
+                    self.device.write_bytes(position, &updated.to_le_bytes())?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_chain(&self, first: u32) -> Result<Vec<u32>, FsError> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+
+        if cluster < FIRST_DATA_CLUSTER {
+            return Ok(chain);
+        }
+
+        loop {
+            if !self.is_valid_cluster(cluster) || chain.len() >= self.cluster_count as usize {
+                return Err(FsError::Io);
+            }
+
+            chain.push(cluster);
+
+            let next = self.read_fat_entries(cluster, 1)?[0];
+
+            if self.fat_type.is_end_of_chain(next) {
+                return Ok(chain);
+            }
+
+            cluster = next;
+        }
+    }
+
+    fn allocate_cluster(&self, previous: Option<u32>) -> Result<u32, FsError> {
+        let mut allocation = self.allocation.lock();
+
+        if allocation.free_count == Some(0) {
+            return Err(FsError::NoSpace);
+        }
+
+        let end = self.cluster_count + FIRST_DATA_CLUSTER;
+        let mut cluster = allocation.next_free;
+        let mut searched = 0;
+
+        while searched < self.cluster_count {
+            let batch = FAT_SCAN_BATCH.min(end - cluster).min(self.cluster_count - searched);
+            let entries = self.read_fat_entries(cluster, batch)?;
+
+            if let Some(index) = entries.iter().position(|entry| *entry == FREE_CLUSTER) {
+                let found = cluster + index as u32;
+
+                self.write_fat_entry(&mut allocation, found, self.fat_type.end_of_chain())?;
+
+                if let Some(previous) = previous {
+                    self.write_fat_entry(&mut allocation, previous, found)?;
+                }
+
+                allocation.next_free = if found + 1 < end { found + 1 } else { FIRST_DATA_CLUSTER };
+                allocation.free_count = allocation.free_count.map(|count| count - 1);
+                allocation.dirty = true;
+
+                return Ok(found);
+            }
+
+            searched += batch;
+            cluster += batch;
+
+            if cluster >= end {
+                cluster = FIRST_DATA_CLUSTER;
+            }
+        }
+
+        allocation.free_count = Some(0);
+
+        Err(FsError::NoSpace)
+    }
+
+    fn free_clusters(&self, clusters: &[u32]) -> Result<(), FsError> {
+        let mut allocation = self.allocation.lock();
+
+        for cluster in clusters {
+            self.write_fat_entry(&mut allocation, *cluster, FREE_CLUSTER)?;
+        }
+
+        allocation.free_count = allocation.free_count.map(|count| count + clusters.len() as u32);
+        allocation.dirty = true;
+
+        Ok(())
+    }
+
+    fn terminate_chain(&self, cluster: u32) -> Result<(), FsError> {
+        let mut allocation = self.allocation.lock();
+
+        self.write_fat_entry(&mut allocation, cluster, self.fat_type.end_of_chain())
+    }
+
+    fn zero_cluster(&self, cluster: u32) -> Result<(), FsError> {
+        let zeroes = vec![0; self.cluster_size as usize];
+
+        Ok(self.device.write_bytes(self.cluster_position(cluster), &zeroes)?)
+    }
+
+    fn free_cluster_count(&self) -> Result<u32, FsError> {
+        let mut allocation = self.allocation.lock();
+
+        if let Some(count) = allocation.free_count {
+            return Ok(count);
+        }
+
+        let mut count = 0;
+        let mut cluster = FIRST_DATA_CLUSTER;
+
+        while cluster < self.cluster_count + FIRST_DATA_CLUSTER {
+            let batch = FAT_SCAN_BATCH.min(self.cluster_count + FIRST_DATA_CLUSTER - cluster);
+
+            count += self.read_fat_entries(cluster, batch)?.iter().filter(|entry| **entry == FREE_CLUSTER).count() as u32;
+            cluster += batch;
+        }
+
+        allocation.free_count = Some(count);
+        allocation.dirty = true;
+
+        Ok(count)
+    }
+
+    fn write_fs_info(&self) -> Result<(), FsError> {
+        let position = match self.fs_info_position {
+            Some(position) => position,
+            None => return Ok(())
+        };
+
+        let mut allocation = self.allocation.lock();
+
+        if !allocation.dirty {
+            return Ok(());
+        }
+
+        let mut data = [0; 8];
+        lightsaber_kernel_write_u32(&mut data, 0, allocation.free_count.unwrap_or(FS_INFO_UNKNOWN));
+        lightsaber_kernel_write_u32(&mut data, 4, allocation.next_free);
+
+        self.device.write_bytes(position + 488, &data)?;
+        allocation.dirty = false;
+
+        Ok(())
+    }
+
+    fn write_slots(&self, raw: &mut RawDirectory, first_slot: usize, slots: &[[u8; DIRECTORY_ENTRY_SIZE]]) -> Result<(), FsError> {
+        let _guard = self.entries.lock();
+
+        for (index, slot) in slots.iter().enumerate() {
+            let offset = (first_slot + index) * DIRECTORY_ENTRY_SIZE;
+
+            self.device.write_bytes(raw.position(first_slot + index), slot)?;
+            raw.data[offset..offset + DIRECTORY_ENTRY_SIZE].copy_from_slice(slot);
+        }
+
+        Ok(())
+    }
+
+    fn update_entry(&self, position: u64, first_cluster: u32, size: u32, attributes: u8) -> Result<(), FsError> {
+        let _guard = self.entries.lock();
+
+        let mut entry = [0; DIRECTORY_ENTRY_SIZE];
+        self.device.read_bytes(position, &mut entry)?;
+
+        entry[11] = attributes;
+        lightsaber_kernel_write_u16(&mut entry, 20, (first_cluster >> 16) as u16);
+        lightsaber_kernel_write_u16(&mut entry, 26, first_cluster as u16);
+        lightsaber_kernel_write_u32(&mut entry, 28, size);
+
+        Ok(self.device.write_bytes(position, &entry)?)
+    }
+}
+
+struct FatInodeState {
+    first_cluster: u32,
+    size: u32,
+    attributes: u8,
+    accessed_date: u16,
+    modified_date: u16,
+    modified_time: u16,
+    created_date: u16,
+    created_time: u16,
+    chain: Option<Vec<u32>>,
+    entry: Option<u64>,
+    unlinked: bool
+}
+
+pub struct FatInode {
+    inode: u64,
+    volume: Arc<FatVolume>,
+    fixed_root: bool,
+    state: Mutex<FatInodeState>
+}
+
+impl FatInode {
+    fn from_entry(volume: &Arc<FatVolume>, position: u64, entry: &FatDirectoryEntry) -> Arc<Self> {
+        let mut inodes = volume.inodes.lock();
+
+        if let Some(inode) = inodes.get(&position).and_then(Weak::upgrade) {
+            return inode;
+        }
+
+        let inode = Arc::new(Self {
+            inode: position,
+            volume: volume.clone(),
+            fixed_root: false,
+            state: Mutex::named("fat_inode", FatInodeState {
+                first_cluster: entry.first_cluster,
+                size: if entry.is_directory() { 0 } else { entry.size },
+                attributes: entry.attributes,
+                accessed_date: entry.accessed_date,
+                modified_date: entry.modified_date,
+                modified_time: entry.modified_time,
+                created_date: entry.created_date,
+                created_time: entry.created_time,
+                chain: None,
+                entry: Some(position),
+                unlinked: false
+            })
+        });
+
+        inodes.insert(position, Arc::downgrade(&inode));
+
+        inode
+    }
+
+    fn is_directory(&self) -> bool {
+        self.fixed_root || self.state.lock().attributes & ATTRIBUTE_DIRECTORY != 0
+    }
+
+    fn chain<'a>(&self, state: &'a mut FatInodeState) -> Result<&'a mut Vec<u32>, FsError> {
+        if state.chain.is_none() {
+            state.chain = Some(self.volume.read_chain(state.first_cluster)?);
+        }
+
+        Ok(state.chain.as_mut().expect("The cluster chain was just read."))
+    }
+
+    fn read_raw_directory(&self, state: &mut FatInodeState) -> Result<RawDirectory, FsError> {
+        if self.fixed_root {
+            let mut data = vec![0; self.volume.root_directory_size as usize];
+            self.volume.device.read_bytes(self.volume.root_directory_position, &mut data)?;
+
+            return Ok(RawDirectory {
+                data,
+                extents: vec![self.volume.root_directory_position],
+                extent_size: self.volume.root_directory_size
+            });
+        }
+
+        let cluster_size = self.volume.cluster_size;
+        let chain = self.chain(state)?.clone();
+        let mut data = vec![0; chain.len() * cluster_size as usize];
+
+        for (index, cluster) in chain.iter().enumerate() {
+            let start = index * cluster_size as usize;
+            self.volume.device.read_bytes(self.volume.cluster_position(*cluster), &mut data[start..start + cluster_size as usize])?;
+        }
+
+        Ok(RawDirectory {
+            data,
+            extents: chain.iter().map(|cluster| self.volume.cluster_position(*cluster)).collect(),
+            extent_size: cluster_size
+        })
+    }
+
+    fn directory_entries(&self) -> Result<Vec<FatDirectoryEntry>, FsError> {
+        if !self.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let mut state = self.state.lock();
+
+        Ok(self.read_raw_directory(&mut state)?
+            .entries()
+            .into_iter()
+            .filter(|entry| !entry.is_dot())
+            .collect())
+    }
+
+    fn grow_directory(&self, state: &mut FatInodeState, raw: &mut RawDirectory) -> Result<(), FsError> {
+        if self.fixed_root || raw.slot_count() + self.volume.cluster_size as usize / DIRECTORY_ENTRY_SIZE > MAXIMUM_DIRECTORY_ENTRIES {
+            return Err(FsError::NoSpace);
+        }
+
+        let last = self.chain(state)?.last().copied();
+        let cluster = self.volume.allocate_cluster(last)?;
+
+        self.volume.zero_cluster(cluster)?;
+        self.chain(state)?.push(cluster);
+
+        raw.data.resize(raw.data.len() + self.volume.cluster_size as usize, 0);
+        raw.extents.push(self.volume.cluster_position(cluster));
+
+        Ok(())
+    }
+
+    fn insert_entry(&self, name: &str, attributes: u8, first_cluster: u32) -> Result<Arc<FatInode>, FsError> {
+        lightsaber_kernel_check_name(name)?;
+
+        let mut state = self.state.lock();
+        let mut raw = self.read_raw_directory(&mut state)?;
+        let entries = raw.entries();
+
+        if entries.iter().any(|entry| entry.matches(name)) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        let mut slots = Vec::new();
+
+        let short_name = match lightsaber_kernel_exact_short_name(name) {
+            Some((short_name, case)) => {
+                slots.push(lightsaber_kernel_encode_short_entry(&short_name, case, attributes, first_cluster));
+                short_name
+            }
+            None => {
+                let existing = entries.iter().map(|entry| entry.short_name).collect::<Vec<[u8; 11]>>();
+                let short_name = lightsaber_kernel_generate_short_name(name, &existing)?;
+
+                slots.extend(lightsaber_kernel_encode_long_name_entries(name, lightsaber_kernel_short_name_checksum(&short_name)));
+                slots.push(lightsaber_kernel_encode_short_entry(&short_name, 0, attributes, first_cluster));
+                short_name
+            }
+        };
+
+        let first_slot = loop {
+            match raw.find_free_slots(slots.len()) {
+                Some(slot) => break slot,
+                None => self.grow_directory(&mut state, &mut raw)?
+            }
+        };
+
+        self.volume.write_slots(&mut raw, first_slot, &slots)?;
+
+        let slot = first_slot + slots.len() - 1;
+        let entry = raw.entries()
+            .into_iter()
+            .find(|entry| entry.slot == slot && entry.short_name == short_name)
+            .ok_or(FsError::Io)?;
+
+        Ok(FatInode::from_entry(&self.volume, raw.position(slot), &entry))
+    }
+
+    fn update_entry(&self, state: &FatInodeState) -> Result<(), FsError> {
+        match state.entry {
+            Some(position) => self.volume.update_entry(position, state.first_cluster, state.size, state.attributes),
+            None => Ok(())
+        }
+    }
+
+    // Makes sure the chain covers `clusters` clusters, stopping early when the volume is full.
+    fn extend_chain(&self, state: &mut FatInodeState, clusters: usize) -> Result<usize, FsError> {
+        while self.chain(state)?.len() < clusters {
+            let last = self.chain(state)?.last().copied();
+
+            let cluster = match self.volume.allocate_cluster(last) {
+                Ok(cluster) => cluster,
+                Err(FsError::NoSpace) => break,
+                Err(error) => return Err(error)
+            };
+
+            if last.is_none() {
+                state.first_cluster = cluster;
+            }
+
+            self.chain(state)?.push(cluster);
+        }
+
+        Ok(self.chain(state)?.len())
+    }
+
+    fn write_data(&self, state: &mut FatInodeState, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        let cluster_size = self.volume.cluster_size;
+        let end = offset + buffer.len() as u64;
+        let clusters = self.extend_chain(state, ((end + cluster_size - 1) / cluster_size) as usize)?;
+        let end = end.min(clusters as u64 * cluster_size);
+
+        if end <= offset && !buffer.is_empty() {
+            self.update_entry(state)?;
+
+            return Err(FsError::NoSpace);
+        }
+
+        let chain = self.chain(state)?.clone();
+        let mut position = offset;
+
+        while position < end {
+            let cluster_offset = position % cluster_size;
+            let chunk = (cluster_size - cluster_offset).min(end - position);
+            let source = (position - offset) as usize;
+
+            let disk_position = self.volume.cluster_position(chain[(position / cluster_size) as usize]) + cluster_offset;
+            self.volume.device.write_bytes(disk_position, &buffer[source..source + chunk as usize])?;
+
+            position += chunk;
+        }
+
+        state.size = state.size.max(end as u32);
+        state.attributes |= ATTRIBUTE_ARCHIVE;
+        self.update_entry(state)?;
+
+        Ok((end - offset) as usize)
+    }
+
+    fn fill_zeroes(&self, state: &mut FatInodeState, end: u64) -> Result<(), FsError> {
+        let zeroes = vec![0; self.volume.cluster_size as usize];
+
+        while (state.size as u64) < end {
+            let chunk = (end - state.size as u64).min(zeroes.len() as u64) as usize;
+            let size = state.size as u64;
+
+            self.write_data(state, size, &zeroes[..chunk])?;
+        }
+
+        Ok(())
+    }
+
+    fn release_chain(&self, state: &mut FatInodeState, keep: usize) -> Result<(), FsError> {
+        let chain = self.chain(state)?.clone();
+
+        if chain.len() <= keep {
+            return Ok(());
+        }
+
+        match keep {
+            0 => state.first_cluster = 0,
+            keep => self.volume.terminate_chain(chain[keep - 1])?
+        }
+
+        self.volume.free_clusters(&chain[keep..])?;
+        self.chain(state)?.truncate(keep);
+
+        Ok(())
+    }
+}
+
+impl Inode for FatInode {
+    fn metadata(&self) -> Result<Metadata, FsError> {
+        let directory = self.is_directory();
+        let mut state = self.state.lock();
+
+        let clusters = match self.fixed_root {
+            true => 0,
+            false => self.chain(&mut state)?.len() as u64
+        };
+
+        let (inode_type, mut mode) = match directory {
+            true => (InodeType::Directory, 0o755),
+            false => (InodeType::File, 0o644)
+        };
+
+        if state.attributes & ATTRIBUTE_READ_ONLY != 0 || self.volume.device.is_read_only() {
+            mode &= !0o222;
+        }
+
+        let size = match directory {
+            true if self.fixed_root => self.volume.root_directory_size,
+            true => clusters * self.volume.cluster_size,
+            false => state.size as u64
+        };
+
+        let modified = lightsaber_kernel_fat_timestamp(state.modified_date, state.modified_time);
+
+        Ok(Metadata {
+            device: self.volume.device_number,
+            inode: self.inode,
+            inode_type,
+            mode,
+            links: if state.unlinked { 0 } else { 1 },
+            uid: 0,
+            gid: 0,
+            size,
+            block_size: self.volume.cluster_size,
+            blocks: clusters * self.volume.cluster_size / 512,
+            accessed: lightsaber_kernel_fat_timestamp(state.accessed_date, 0),
+            modified,
+            changed: modified.max(lightsaber_kernel_fat_timestamp(state.created_date, state.created_time))
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        if self.is_directory() {
+            return Err(FsError::IsDirectory);
+        }
+
+        let mut state = self.state.lock();
+
+        if offset >= state.size as u64 {
+            return Ok(0);
+        }
+
+        let cluster_size = self.volume.cluster_size;
+        let end = (state.size as u64).min(offset + buffer.len() as u64);
+        let chain = self.chain(&mut state)?.clone();
+
+        if ((end + cluster_size - 1) / cluster_size) as usize > chain.len() {
+            return Err(FsError::Io);
+        }
+
+        let mut position = offset;
+
+        while position < end {
+            let cluster_offset = position % cluster_size;
+            let chunk = (cluster_size - cluster_offset).min(end - position);
+            let destination = (position - offset) as usize;
+
+            let disk_position = self.volume.cluster_position(chain[(position / cluster_size) as usize]) + cluster_offset;
+            self.volume.device.read_bytes(disk_position, &mut buffer[destination..destination + chunk as usize])?;
+
+            position += chunk;
+        }
+
+        Ok((end - offset) as usize)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        self.volume.check_writable()?;
+
+        if self.is_directory() {
+            return Err(FsError::IsDirectory);
+        }
+
+        let end = offset.checked_add(buffer.len() as u64).ok_or(FsError::FileTooLarge)?;
+
+        if end > u32::MAX as u64 {
+            return Err(FsError::FileTooLarge);
+        }
+
+        let mut state = self.state.lock();
+
+        // FAT has no holes, so the gap in front of the write is filled in.
+        self.fill_zeroes(&mut state, offset)?;
+        self.write_data(&mut state, offset, buffer)
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), FsError> {
+        self.volume.check_writable()?;
+
+        if self.is_directory() {
+            return Err(FsError::IsDirectory);
+        }
+
+        if size > u32::MAX as u64 {
+            return Err(FsError::FileTooLarge);
+        }
+
+        let mut state = self.state.lock();
+
+        if size > state.size as u64 {
+            return self.fill_zeroes(&mut state, size);
+        }
+
+        let cluster_size = self.volume.cluster_size;
+        self.release_chain(&mut state, ((size + cluster_size - 1) / cluster_size) as usize)?;
+
+        state.size = size as u32;
+        state.attributes |= ATTRIBUTE_ARCHIVE;
+
+        self.update_entry(&state)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        if !self.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let mut state = self.state.lock();
+        let raw = self.read_raw_directory(&mut state)?;
+
+        let entry = raw.entries()
+            .into_iter()
+            .find(|entry| !entry.is_dot() && entry.matches(name))
+            .ok_or(FsError::NotFound)?;
+
+        Ok(FatInode::from_entry(&self.volume, raw.position(entry.slot), &entry))
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType, mode: u16) -> Result<Arc<dyn Inode>, FsError> {
+        self.volume.check_writable()?;
+
+        if !self.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let read_only = if mode & 0o222 == 0 { ATTRIBUTE_READ_ONLY } else { 0 };
+
+        match inode_type {
+            InodeType::File => Ok(self.insert_entry(name, ATTRIBUTE_ARCHIVE | read_only, 0)?),
+            InodeType::Directory => {
+                let cluster = self.volume.allocate_cluster(None)?;
+
+                let parent_cluster = match self.fixed_root {
+                    true => 0,
+                    false if self.state.lock().first_cluster == self.volume.root_cluster => 0,
+                    false => self.state.lock().first_cluster
+                };
+
+                let dots = [
+                    lightsaber_kernel_encode_short_entry(b".          ", 0, ATTRIBUTE_DIRECTORY, cluster),
+                    lightsaber_kernel_encode_short_entry(b"..         ", 0, ATTRIBUTE_DIRECTORY, parent_cluster)
+                ];
+
+                let result = self.volume.zero_cluster(cluster)
+                    .and_then(|_| Ok(self.volume.device.write_bytes(self.volume.cluster_position(cluster), &dots.concat())?))
+                    .and_then(|_| self.insert_entry(name, ATTRIBUTE_DIRECTORY | read_only, cluster));
+
+                if result.is_err() {
+                    self.volume.free_clusters(&[cluster])?;
+                }
+
+                Ok(result?)
+            }
+            _ => Err(FsError::NotSupported)
+        }
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FsError> {
+        self.volume.check_writable()?;
+
+        if !self.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let mut state = self.state.lock();
+        let mut raw = self.read_raw_directory(&mut state)?;
+
+        let entry = raw.entries()
+            .into_iter()
+            .find(|entry| !entry.is_dot() && entry.matches(name))
+            .ok_or(FsError::NotFound)?;
+
+        let child = FatInode::from_entry(&self.volume, raw.position(entry.slot), &entry);
+
+        if entry.is_directory() && !child.directory_entries()?.is_empty() {
+            return Err(FsError::NotEmpty);
+        }
+
+        let mut deleted = Vec::new();
+
+        for slot in entry.first_slot..=entry.slot {
+            let mut data = [0; DIRECTORY_ENTRY_SIZE];
+
+            data.copy_from_slice(raw.slot(slot));
+            data[0] = ENTRY_DELETED;
+            deleted.push(data);
+        }
+
+        // The clusters stay allocated until the last user of the inode lets go of it.
+        let mut child_state = child.state.lock();
+
+        self.volume.write_slots(&mut raw, entry.first_slot, &deleted)?;
+
+        child_state.entry = None;
+        child_state.unlinked = true;
+
+        self.volume.inodes.lock().remove(&child.inode);
+
+        Ok(())
+    }
+
+    fn read_directory(&self, index: usize) -> Result<Option<DirectoryEntry>, FsError> {
+        if !self.is_directory() {
+            return Err(FsError::NotDirectory);
+        }
+
+        let mut state = self.state.lock();
+        let raw = self.read_raw_directory(&mut state)?;
+
+        Ok(raw.entries().into_iter().filter(|entry| !entry.is_dot()).nth(index).map(|entry| DirectoryEntry {
+            inode: raw.position(entry.slot),
+            inode_type: if entry.is_directory() { InodeType::Directory } else { InodeType::File },
+            name: entry.name
+        }))
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(self.volume.device.flush()?)
+    }
+}
+
+impl Drop for FatInode {
+    fn drop(&mut self) {
+        let mut state = self.state.lock();
+
+        if state.unlinked {
+            if let Err(error) = self.release_chain(&mut state, 0) {
+                log::warn!("Failed to free the clusters of an unlinked FAT file: {}", error);
+            }
+
+            return;
+        }
+
+        drop(state);
+
+        let mut inodes = self.volume.inodes.lock();
+
+        // A newer inode may already have been registered for the same entry.
+        if inodes.get(&self.inode).map_or(false, |inode| inode.strong_count() == 0) {
+            inodes.remove(&self.inode);
+        }
+    }
+}
+
+pub struct FatFileSystem {
+    volume: Arc<FatVolume>,
+    root: Arc<FatInode>
+}
+
+impl FatFileSystem {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Result<Self, FsError> {
+        let mut boot_sector = [0; 512];
+        device.read_bytes(0, &mut boot_sector)?;
+
+        let bytes_per_sector = lightsaber_kernel_read_u16(&boot_sector, 11) as u64;
+        let sectors_per_cluster = boot_sector[13] as u64;
+        let reserved_sectors = lightsaber_kernel_read_u16(&boot_sector, 14) as u64;
+        let fat_count = boot_sector[16] as u64;
+        let root_entry_count = lightsaber_kernel_read_u16(&boot_sector, 17) as u64;
+
+        let total_sectors = match lightsaber_kernel_read_u16(&boot_sector, 19) {
+            0 => lightsaber_kernel_read_u32(&boot_sector, 32) as u64,
+            count => count as u64
+        };
+
+        let fat_size = match lightsaber_kernel_read_u16(&boot_sector, 22) {
+            0 => lightsaber_kernel_read_u32(&boot_sector, 36) as u64,
+            size => size as u64
+        };
+
+        let valid = lightsaber_kernel_read_u16(&boot_sector, 510) == BOOT_SIGNATURE
+            && [512, 1024, 2048, 4096].contains(&bytes_per_sector)
+            && sectors_per_cluster.is_power_of_two()
+            && reserved_sectors != 0
+            && fat_count != 0
+            && fat_size != 0
+            && total_sectors * bytes_per_sector <= device.size();
+
+        if !valid {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let root_directory_sectors = (root_entry_count * DIRECTORY_ENTRY_SIZE as u64 + bytes_per_sector - 1) / bytes_per_sector;
+        let data_start_sector = reserved_sectors + fat_count * fat_size + root_directory_sectors;
+
+        if data_start_sector >= total_sectors {
+            return Err(FsError::InvalidArgument);
+        }
+
+        let cluster_count = ((total_sectors - data_start_sector) / sectors_per_cluster) as u32;
+
+        let fat_type = match cluster_count {
+            count if count < FAT12_MAXIMUM_CLUSTERS => FatType::Fat12,
+            count if count < FAT16_MAXIMUM_CLUSTERS => FatType::Fat16,
+            _ => FatType::Fat32
+        };
+
+        let mut free_count = None;
+        let mut next_free = FIRST_DATA_CLUSTER;
+        let mut fs_info_position = None;
+        let mut active_fat = None;
+        let mut root_cluster = 0;
+
+        if fat_type == FatType::Fat32 {
+            let extended_flags = lightsaber_kernel_read_u16(&boot_sector, 40);
+            root_cluster = lightsaber_kernel_read_u32(&boot_sector, 44);
+
+            if root_entry_count != 0 || root_cluster < FIRST_DATA_CLUSTER || root_cluster >= cluster_count + FIRST_DATA_CLUSTER {
+                return Err(FsError::InvalidArgument);
+            }
+
+            // Bit 7 turns mirroring off, leaving only the FAT named in the low bits in use.
+            if extended_flags & 0x80 != 0 {
+                active_fat = Some((extended_flags & 0xF) as u64).filter(|active| *active < fat_count);
+            }
+
+            let fs_info_sector = lightsaber_kernel_read_u16(&boot_sector, 48) as u64;
+
+            if fs_info_sector != 0 && fs_info_sector < reserved_sectors {
+                let mut fs_info = [0; 512];
+                device.read_bytes(fs_info_sector * bytes_per_sector, &mut fs_info)?;
+
+                if lightsaber_kernel_read_u32(&fs_info, 0) == FS_INFO_LEAD_SIGNATURE
+                    && lightsaber_kernel_read_u32(&fs_info, 484) == FS_INFO_STRUCTURE_SIGNATURE
+                    && lightsaber_kernel_read_u32(&fs_info, 508) == FS_INFO_TRAIL_SIGNATURE {
+                    fs_info_position = Some(fs_info_sector * bytes_per_sector);
+
+                    free_count = Some(lightsaber_kernel_read_u32(&fs_info, 488)).filter(|count| *count <= cluster_count);
+                    next_free = Some(lightsaber_kernel_read_u32(&fs_info, 492))
+                        .filter(|cluster| *cluster >= FIRST_DATA_CLUSTER && *cluster < cluster_count + FIRST_DATA_CLUSTER)
+                        .unwrap_or(FIRST_DATA_CLUSTER);
+                }
+            }
+        }
+
+        let volume = Arc::new(FatVolume {
+            device_number: fs::lightsaber_kernel_allocate_device_number(),
+            fat_type,
+            cluster_size: sectors_per_cluster * bytes_per_sector,
+            fat_start: reserved_sectors * bytes_per_sector,
+            fat_size: fat_size * bytes_per_sector,
+            fat_count,
+            active_fat,
+            root_directory_position: (reserved_sectors + fat_count * fat_size) * bytes_per_sector,
+            root_directory_size: root_entry_count * DIRECTORY_ENTRY_SIZE as u64,
+            root_cluster,
+            data_start: data_start_sector * bytes_per_sector,
+            cluster_count,
+            fs_info_position,
+            allocation: Mutex::named("fat_allocation", FatAllocation {
+                free_count,
+                next_free,
+                dirty: false
+            }),
+            entries: Mutex::named("fat_entries", ()),
+            inodes: Spinlock::named("fat_inodes", BTreeMap::new()),
+            device
+        });
+
+        let root = Arc::new(FatInode {
+            inode: ROOT_INODE,
+            volume: volume.clone(),
+            fixed_root: fat_type != FatType::Fat32,
+            state: Mutex::named("fat_inode", FatInodeState {
+                first_cluster: root_cluster,
+                size: 0,
+                attributes: ATTRIBUTE_DIRECTORY,
+                accessed_date: FAT_EPOCH_DATE,
+                modified_date: FAT_EPOCH_DATE,
+                modified_time: FAT_EPOCH_TIME,
+                created_date: FAT_EPOCH_DATE,
+                created_time: FAT_EPOCH_TIME,
+                chain: None,
+                entry: None,
+                unlinked: false
+            })
+        });
+
+        log::info!("Found a {} volume on {} with {} clusters of {} bytes.", fat_type, volume.device.name(), cluster_count, volume.cluster_size);
+
+        Ok(Self {
+            volume,
+            root
+        })
+    }
+}
+
+impl FileSystem for FatFileSystem {
+    fn name(&self) -> &'static str {
+        "vfat"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        if !self.volume.device.is_read_only() {
+            self.volume.write_fs_info()?;
+        }
+
+        Ok(self.volume.device.flush()?)
+    }
+
+    fn usage(&self) -> Result<FsUsage, FsError> {
+        Ok(FsUsage {
+            size: self.volume.cluster_count as u64 * self.volume.cluster_size,
+            free: self.volume.free_cluster_count()? as u64 * self.volume.cluster_size
+        })
+    }
+}
+
+// Makes the kernel, initial ramdisk and boot configuration the bootloader read reachable once the disks are known.
+// Should several disks carry one, the first registered is taken.
+pub fn lightsaber_kernel_mount_efi_system_partition() {
+    let partition = block::lightsaber_kernel_block_devices()
+        .into_iter()
+        .find(|device| device.partition_kind().map_or(false, |kind| kind.is_efi_system_partition()));
+
+    let partition = match partition {
+        Some(partition) => partition,
+        None => {
+            log::info!("No EFI system partition was found.");
+            return;
+        }
+    };
+
+    let result = FatFileSystem::new(partition.clone())
+        .and_then(|file_system| {
+            mount::lightsaber_kernel_create_mountpoint(EFI_SYSTEM_PARTITION_MOUNTPOINT)?;
+            mount::lightsaber_kernel_mount(EFI_SYSTEM_PARTITION_MOUNTPOINT, Arc::new(file_system))
+        });
+
+    if let Err(error) = result {
+        log::warn!("Failed to mount the EFI system partition {} on {}: {}", partition.name(), EFI_SYSTEM_PARTITION_MOUNTPOINT, error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    use crate::block::RamDisk;
+
+    // Made with the fatfs crate by `cargo xtask images`, which also lists what is on them.
+    const FAT12_IMAGE: &[u8] = include_bytes!("../../testdata/fat12.img");
+    const FAT16_IMAGE: &[u8] = include_bytes!("../../testdata/fat16.img");
+    const FAT32_IMAGE: &[u8] = include_bytes!("../../testdata/fat32.img");
+
+    const FAT_IMAGES: [(&[u8], FatType); 3] = [
+        (FAT12_IMAGE, FatType::Fat12),
+        (FAT16_IMAGE, FatType::Fat16),
+        (FAT32_IMAGE, FatType::Fat32)
+    ];
+
+    const CLUSTER_SIZE: u64 = 512;
+    const PATTERN_SIZE: usize = 20000;
+    const MANY_FILES: usize = 40;
+    const FRAGMENTS: u8 = 8;
+    const FAT12_ROOT_ENTRIES: usize = 32;
+    const FAT12_ROOT_ENTRIES_USED: usize = 15;
+    const UNICODE_NAME: &str = "\u{DC}n\u{EF}c\u{F6}d\u{E9}.txt";
+
+    // The images are stored without the zeroes at their end, which the boot sector still counts.
+    fn lightsaber_kernel_test_image(image: &[u8]) -> Vec<u8> {
+        let total_sectors = match lightsaber_kernel_read_u16(image, 19) {
+            0 => lightsaber_kernel_read_u32(image, 32) as usize,
+            count => count as usize
+        };
+
+        let mut data = image.to_vec();
+        data.resize(total_sectors * lightsaber_kernel_read_u16(image, 11) as usize, 0);
+
+        data
+    }
+
+    fn lightsaber_kernel_test_disk(image: &[u8]) -> Arc<dyn BlockDevice> {
+        Arc::new(RamDisk::new("fat_test", 512, lightsaber_kernel_test_image(image)))
+    }
+
+    // The image with `bytes` written over it at `offset`.
+    fn lightsaber_kernel_test_patched(image: &[u8], offset: usize, bytes: &[u8]) -> Arc<dyn BlockDevice> {
+        let mut image = lightsaber_kernel_test_image(image);
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+
+        Arc::new(RamDisk::new("fat_test", 512, image))
+    }
+
+    fn lightsaber_kernel_test_mount(image: &[u8]) -> FatFileSystem {
+        FatFileSystem::new(lightsaber_kernel_test_disk(image)).unwrap()
+    }
+
+    fn lightsaber_kernel_test_pattern(size: usize) -> Vec<u8> {
+        (0..size).map(|index| (index * 7 % 251) as u8).collect()
+    }
+
+    fn lightsaber_kernel_test_fragments(fill: u8) -> Vec<u8> {
+        (0..FRAGMENTS).flat_map(|fragment| vec![fill | fragment; CLUSTER_SIZE as usize]).collect()
+    }
+
+    fn lightsaber_kernel_test_lookup(file_system: &FatFileSystem, path: &str) -> Result<Arc<dyn Inode>, FsError> {
+        path.split('/').try_fold(file_system.root(), |directory, name| directory.lookup(name))
+    }
+
+    fn lightsaber_kernel_test_read(file_system: &FatFileSystem, path: &str) -> Vec<u8> {
+        let inode = lightsaber_kernel_test_lookup(file_system, path).unwrap();
+        let mut buffer = vec![0; inode.metadata().unwrap().size as usize];
+
+        assert_eq!(inode.read_at(0, &mut buffer), Ok(buffer.len()));
+
+        buffer
+    }
+
+    fn lightsaber_kernel_test_entries(directory: &Arc<dyn Inode>) -> Vec<(String, InodeType)> {
+        let mut entries = Vec::new();
+
+        while let Some(entry) = directory.read_directory(entries.len()).unwrap() {
+            entries.push((entry.name, entry.inode_type));
+        }
+
+        entries.sort_by(|first, second| first.0.cmp(&second.0));
+
+        entries
+    }
+
+    // An entry of the root directory as it is on disk, with the slots of its long name.
+    fn lightsaber_kernel_test_root_entry(file_system: &FatFileSystem, name: &str) -> FatDirectoryEntry {
+        let mut state = file_system.root.state.lock();
+
+        file_system.root.read_raw_directory(&mut state).unwrap().entries().into_iter().find(|entry| entry.name == name).unwrap()
+    }
+
+    fn lightsaber_kernel_test_raw_directory(slots: &[[u8; DIRECTORY_ENTRY_SIZE]]) -> RawDirectory {
+        let data = slots.concat();
+
+        RawDirectory {
+            extents: vec![0],
+            extent_size: data.len() as u64,
+            data
+        }
+    }
+
+    #[test_case]
+    fn fat_reads_each_width() {
+        for (image, fat_type) in FAT_IMAGES.iter() {
+            let file_system = lightsaber_kernel_test_mount(image);
+
+            assert_eq!(file_system.volume.fat_type, *fat_type);
+
+            assert_eq!(lightsaber_kernel_test_entries(&file_system.root()), [
+                ("A long file name.txt".to_string(), InodeType::File),
+                ("Docs".to_string(), InodeType::Directory),
+                ("HELLO.TXT".to_string(), InodeType::File),
+                ("Many".to_string(), InodeType::Directory),
+                ("frag-a.bin".to_string(), InodeType::File),
+                ("frag-b.bin".to_string(), InodeType::File),
+                (UNICODE_NAME.to_string(), InodeType::File)
+            ]);
+
+            // Names match without regard to case, and by their short alias too.
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "hello.txt"), b"Hello, FAT!\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "A long file name.txt"), b"long\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "alongf~1.txt"), b"long\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, UNICODE_NAME), b"unicode\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "DOCS/nested/Note.TXT"), b"nested\n");
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "Docs/pattern.bin"), lightsaber_kernel_test_pattern(PATTERN_SIZE));
+
+            let many = lightsaber_kernel_test_lookup(&file_system, "Many").unwrap();
+
+            // Two slots a file and the dot entries take up six clusters.
+            assert_eq!(many.metadata().unwrap().size, 6 * CLUSTER_SIZE);
+            assert_eq!(lightsaber_kernel_test_entries(&many).len(), MANY_FILES);
+
+            for index in 0..MANY_FILES {
+                let name = format!("file-{:02}.txt", index);
+
+                assert_eq!(lightsaber_kernel_test_read(&file_system, &format!("Many/{}", name)), name.as_bytes());
+            }
+
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "frag-a.bin"), lightsaber_kernel_test_fragments(0));
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "frag-b.bin"), lightsaber_kernel_test_fragments(0x80));
+
+            let chain = file_system.volume.read_chain(lightsaber_kernel_test_root_entry(&file_system, "frag-a.bin").first_cluster).unwrap();
+
+            assert_eq!(chain.len(), FRAGMENTS as usize);
+            assert!(chain.windows(2).all(|pair| pair[1] == pair[0] + 2));
+
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "Docs/missing").err(), Some(FsError::NotFound));
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "HELLO.TXT/inner").err(), Some(FsError::NotDirectory));
+        }
+    }
+
+    #[test_case]
+    fn fat_short_names() {
+        assert_eq!(lightsaber_kernel_exact_short_name("HELLO.TXT"), Some((*b"HELLO   TXT", 0)));
+        assert_eq!(lightsaber_kernel_exact_short_name("NOEXT"), Some((*b"NOEXT      ", 0)));
+        assert_eq!(lightsaber_kernel_exact_short_name("readme.md"), Some((*b"README  MD ", CASE_LOWER_BASE | CASE_LOWER_EXTENSION)));
+        assert_eq!(lightsaber_kernel_exact_short_name("KERNEL.elf"), Some((*b"KERNEL  ELF", CASE_LOWER_EXTENSION)));
+
+        // Mixed case, too long a part, more than one dot, a trailing dot and characters 8.3 names cannot hold.
+        for name in ["Mixed.txt", "ninechars.txt", "name.text", "a.b.c", "name.", "a+b.txt", "a b.txt", UNICODE_NAME].iter() {
+            assert_eq!(lightsaber_kernel_exact_short_name(name), None, "{}", name);
+        }
+
+        assert_eq!(lightsaber_kernel_generate_short_name("A long file name.txt", &[]), Ok(*b"ALONGF~1TXT"));
+        assert_eq!(lightsaber_kernel_generate_short_name("A long file name.txt", &[*b"ALONGF~1TXT"]), Ok(*b"ALONGF~2TXT"));
+        assert_eq!(lightsaber_kernel_generate_short_name(".hidden", &[]), Ok(*b"HIDDEN~1   "));
+        assert_eq!(lightsaber_kernel_generate_short_name("archive.tar.gz", &[]), Ok(*b"ARCHIV~1GZ "));
+        assert_eq!(lightsaber_kernel_generate_short_name(UNICODE_NAME, &[]), Ok(*b"_N_C_D~1TXT"));
+
+        assert_eq!(lightsaber_kernel_display_short_name(b"README  MD ", CASE_LOWER_BASE | CASE_LOWER_EXTENSION), "readme.md");
+        assert_eq!(lightsaber_kernel_display_short_name(b"NOEXT      ", 0), "NOEXT");
+        assert_eq!(lightsaber_kernel_display_short_name(b"\x05BC     TXT", 0), "\u{E5}BC.TXT");
+
+        // Worked out independently, and matching what the fatfs crate wrote into the images.
+        assert_eq!(lightsaber_kernel_short_name_checksum(b"ALONGF~1TXT"), 0x02);
+        assert_eq!(lightsaber_kernel_short_name_checksum(b"HELLO   TXT"), 0xF1);
+
+        assert_eq!(lightsaber_kernel_check_name("A long file name.txt"), Ok(()));
+
+        for name in ["", ".", "..", "name.", "name ", "a:b", "a*b", "tab\there"].iter() {
+            assert_eq!(lightsaber_kernel_check_name(name), Err(FsError::InvalidArgument), "{:?}", name);
+        }
+
+        assert_eq!(lightsaber_kernel_check_name(&"x".repeat(256)), Err(FsError::NameTooLong));
+    }
+
+    #[test_case]
+    fn fat_long_names() {
+        let short_entry = lightsaber_kernel_encode_short_entry(b"ALONGF~1TXT", 0, ATTRIBUTE_ARCHIVE, 0);
+
+        // Two full parts end with the name itself, with neither a terminator nor padding.
+        for name in ["A long file name.txt", "abcdefghijklmnopqrstuvwxyz", UNICODE_NAME].iter() {
+            let mut slots = lightsaber_kernel_encode_long_name_entries(name, lightsaber_kernel_short_name_checksum(b"ALONGF~1TXT"));
+            let count = slots.len();
+
+            assert_eq!(count, (name.encode_utf16().count() + LONG_NAME_CHARACTERS - 1) / LONG_NAME_CHARACTERS);
+            assert_eq!(slots[0][0], count as u8 | LONG_NAME_LAST_ENTRY);
+
+            slots.push(short_entry);
+
+            let entries = lightsaber_kernel_test_raw_directory(&slots).entries();
+
+            assert_eq!(entries.len(), 1);
+            assert_eq!(entries[0].name, *name);
+            assert_eq!((entries[0].first_slot, entries[0].slot), (0, count));
+        }
+
+        // A long name that does not belong to the short entry after it, or misses a part, is left out.
+        let mut slots = lightsaber_kernel_encode_long_name_entries("A long file name.txt", 0);
+        slots.push(short_entry);
+
+        assert_eq!(lightsaber_kernel_test_raw_directory(&slots).entries()[0].name, "ALONGF~1.TXT");
+
+        let mut slots = lightsaber_kernel_encode_long_name_entries("A long file name.txt", lightsaber_kernel_short_name_checksum(b"ALONGF~1TXT"));
+        slots.remove(0);
+        slots.push(short_entry);
+
+        let entries = lightsaber_kernel_test_raw_directory(&slots).entries();
+
+        assert_eq!(entries[0].name, "ALONGF~1.TXT");
+        assert_eq!(entries[0].first_slot, entries[0].slot);
+    }
+
+    #[test_case]
+    fn fat_creates_long_names_only_when_needed() {
+        let disk = lightsaber_kernel_test_disk(FAT16_IMAGE);
+
+        {
+            let file_system = FatFileSystem::new(disk.clone()).unwrap();
+            let root = file_system.root();
+
+            for name in ["NEW.TXT", "readme.md", "Mixed.Txt", "A long file name 2.txt"].iter() {
+                root.create(name, InodeType::File, 0o644).unwrap();
+            }
+
+            // The short name, the case flags and the number of slots each entry took.
+            for (name, short_name, case, slots) in [
+                ("NEW.TXT", b"NEW     TXT", 0, 1),
+                ("readme.md", b"README  MD ", CASE_LOWER_BASE | CASE_LOWER_EXTENSION, 1),
+                ("Mixed.Txt", b"MIXED~1 TXT", 0, 2),
+                ("A long file name 2.txt", b"ALONGF~2TXT", 0, 3)
+            ].iter() {
+                let entry = lightsaber_kernel_test_root_entry(&file_system, name);
+                let raw = file_system.root.read_raw_directory(&mut file_system.root.state.lock()).unwrap();
+
+                assert_eq!(&entry.short_name, *short_name, "{}", name);
+                assert_eq!(raw.slot(entry.slot)[12], *case, "{}", name);
+                assert_eq!(entry.slot + 1 - entry.first_slot, *slots, "{}", name);
+            }
+
+            // Names clash without regard to case, and with short aliases.
+            assert_eq!(root.create("new.txt", InodeType::File, 0o644).err(), Some(FsError::AlreadyExists));
+            assert_eq!(root.create("ALONGF~1.TXT", InodeType::File, 0o644).err(), Some(FsError::AlreadyExists));
+            assert_eq!(root.create("a:b", InodeType::File, 0o644).err(), Some(FsError::InvalidArgument));
+            assert_eq!(root.create(&"x".repeat(256), InodeType::File, 0o644).err(), Some(FsError::NameTooLong));
+        }
+
+        let file_system = FatFileSystem::new(disk).unwrap();
+        let names = lightsaber_kernel_test_entries(&file_system.root()).into_iter().map(|entry| entry.0).collect::<Vec<String>>();
+
+        for name in ["NEW.TXT", "readme.md", "Mixed.Txt", "A long file name 2.txt", "A long file name.txt"].iter() {
+            assert!(names.iter().any(|entry| entry == name), "{}", name);
+        }
+    }
+
+    #[test_case]
+    fn fat_files_grow_and_shrink_their_cluster_chains() {
+        for (image, _) in FAT_IMAGES.iter() {
+            let file_system = lightsaber_kernel_test_mount(image);
+            let root = file_system.root();
+            let before = file_system.usage().map(|usage| usage.free).unwrap();
+            let pattern = lightsaber_kernel_test_pattern(PATTERN_SIZE);
+
+            {
+                let file = root.create("grown.bin", InodeType::File, 0o644).unwrap();
+
+                assert_eq!(file.write_at(0, &pattern), Ok(pattern.len()));
+
+                // FAT has no holes, so the gap in front of a write past the end is zeroes.
+                assert_eq!(file.write_at(30000, b"far"), Ok(3));
+
+                let metadata = file.metadata().unwrap();
+
+                assert_eq!(metadata.size, 30003);
+                assert_eq!(metadata.blocks, 59);
+                assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before - 59 * CLUSTER_SIZE));
+
+                let contents = lightsaber_kernel_test_read(&file_system, "grown.bin");
+
+                assert_eq!(&contents[..PATTERN_SIZE], &pattern[..]);
+                assert!(contents[PATTERN_SIZE..30000].iter().all(|byte| *byte == 0));
+                assert_eq!(&contents[30000..], b"far");
+
+                file.truncate(1000).unwrap();
+
+                assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before - 2 * CLUSTER_SIZE));
+                assert_eq!(lightsaber_kernel_test_read(&file_system, "grown.bin"), &pattern[..1000]);
+
+                file.truncate(0).unwrap();
+
+                assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before));
+                assert_eq!(lightsaber_kernel_test_root_entry(&file_system, "grown.bin").first_cluster, 0);
+            }
+
+            root.unlink("grown.bin").unwrap();
+
+            assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before));
+        }
+    }
+
+    #[test_case]
+    fn fat_directories_grow_past_a_cluster() {
+        // Four slots for each name, so that 24 of them and the dot entries fill seven clusters.
+        let names = (0..24).map(|index| format!("a rather long file name {:02}.txt", index)).collect::<Vec<String>>();
+
+        for image in [FAT16_IMAGE, FAT32_IMAGE].iter() {
+            let disk = lightsaber_kernel_test_disk(image);
+
+            {
+                let file_system = FatFileSystem::new(disk.clone()).unwrap();
+                let root = file_system.root();
+                let mut directories = vec![(root.create("Grown", InodeType::Directory, 0o755).unwrap(), 7)];
+
+                // Only the FAT32 root directory is a cluster chain; with its seventeen slots in use it needs one more.
+                if file_system.volume.fat_type == FatType::Fat32 {
+                    directories.push((root, 8));
+                }
+
+                for (directory, clusters) in directories.iter() {
+                    for name in names.iter() {
+                        directory.create(name, InodeType::File, 0o644).unwrap().write_at(0, name.as_bytes()).unwrap();
+                    }
+
+                    assert_eq!(directory.metadata().unwrap().size, clusters * CLUSTER_SIZE);
+                }
+            }
+
+            let file_system = FatFileSystem::new(disk).unwrap();
+
+            assert_eq!(lightsaber_kernel_test_entries(&lightsaber_kernel_test_lookup(&file_system, "Grown").unwrap()).len(), names.len());
+
+            for name in names.iter() {
+                assert_eq!(lightsaber_kernel_test_read(&file_system, &format!("Grown/{}", name)), name.as_bytes());
+
+                if file_system.volume.fat_type == FatType::Fat32 {
+                    assert_eq!(lightsaber_kernel_test_read(&file_system, name), name.as_bytes());
+                }
+            }
+        }
+    }
+
+    #[test_case]
+    fn fat_fixed_root_directory_fills_up() {
+        let file_system = lightsaber_kernel_test_mount(FAT12_IMAGE);
+        let root = file_system.root();
+        let before = file_system.usage().map(|usage| usage.free).unwrap();
+        let mut created = 0;
+
+        let error = loop {
+            match root.create(&format!("FILL{:02}.TXT", created), InodeType::File, 0o644) {
+                Ok(_) => created += 1,
+                Err(error) => break error
+            }
+        };
+
+        assert_eq!(error, FsError::NoSpace);
+        assert_eq!(created, FAT12_ROOT_ENTRIES - FAT12_ROOT_ENTRIES_USED);
+
+        // A new directory's cluster is given back when there is no room for its entry.
+        assert_eq!(root.create("MORE", InodeType::Directory, 0o755).err(), Some(FsError::NoSpace));
+        assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before));
+
+        // Directories below the root are chains, so they still grow.
+        let docs = lightsaber_kernel_test_lookup(&file_system, "Docs").unwrap();
+
+        for index in 0..FAT12_ROOT_ENTRIES {
+            docs.create(&format!("FILL{:02}.TXT", index), InodeType::File, 0o644).unwrap();
+        }
+
+        root.unlink("FILL00.TXT").unwrap();
+        root.create("AGAIN.TXT", InodeType::File, 0o644).unwrap();
+    }
+
+    #[test_case]
+    fn fat_writes_survive_a_remount() {
+        for (image, _) in FAT_IMAGES.iter() {
+            let disk = lightsaber_kernel_test_disk(image);
+            let pattern = lightsaber_kernel_test_pattern(PATTERN_SIZE);
+
+            let free = {
+                let file_system = FatFileSystem::new(disk.clone()).unwrap();
+                let root = file_system.root();
+
+                root.create("new.bin", InodeType::File, 0o644).unwrap().write_at(0, &pattern[..5000]).unwrap();
+                root.create("Made Here", InodeType::Directory, 0o755).unwrap().create("inner.txt", InodeType::File, 0o644).unwrap().write_at(0, b"inner").unwrap();
+                root.unlink("HELLO.TXT").unwrap();
+
+                lightsaber_kernel_test_lookup(&file_system, "Docs/pattern.bin").unwrap().truncate(5000).unwrap();
+                lightsaber_kernel_test_lookup(&file_system, "frag-a.bin").unwrap().write_at(FRAGMENTS as u64 * CLUSTER_SIZE, b"tail").unwrap();
+
+                file_system.sync().unwrap();
+                file_system.usage().map(|usage| usage.free).unwrap()
+            };
+
+            let file_system = FatFileSystem::new(disk).unwrap();
+
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "new.bin"), &pattern[..5000]);
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "Made Here/inner.txt"), b"inner");
+            assert_eq!(lightsaber_kernel_test_entries(&lightsaber_kernel_test_lookup(&file_system, "Made Here").unwrap()), [
+                ("inner.txt".to_string(), InodeType::File)
+            ]);
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "HELLO.TXT").err(), Some(FsError::NotFound));
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "Docs/pattern.bin"), &pattern[..5000]);
+
+            let mut fragments = lightsaber_kernel_test_fragments(0);
+            fragments.extend_from_slice(b"tail");
+
+            assert_eq!(lightsaber_kernel_test_read(&file_system, "frag-a.bin"), fragments);
+            assert_eq!(file_system.usage().map(|usage| usage.free), Ok(free));
+
+            // FAT32 keeps the free count in its FS information sector, which has to agree with the FAT itself.
+            file_system.volume.allocation.lock().free_count = None;
+
+            assert_eq!(file_system.usage().map(|usage| usage.free), Ok(free));
+
+            // Both copies of the FAT were kept the same.
+            let volume = &file_system.volume;
+            let mut copies = vec![vec![0; volume.fat_size as usize]; volume.fat_count as usize];
+
+            for (copy, data) in copies.iter_mut().enumerate() {
+                volume.device.read_bytes(volume.fat_start + copy as u64 * volume.fat_size, data).unwrap();
+            }
+
+            assert!(copies.windows(2).all(|pair| pair[0] == pair[1]));
+        }
+    }
+
+    #[test_case]
+    fn fat_unlinked_files_keep_their_clusters_until_closed() {
+        let file_system = lightsaber_kernel_test_mount(FAT16_IMAGE);
+        let docs = lightsaber_kernel_test_lookup(&file_system, "Docs").unwrap();
+        let before = file_system.usage().map(|usage| usage.free).unwrap();
+
+        {
+            let file = docs.lookup("pattern.bin").unwrap();
+
+            docs.unlink("pattern.bin").unwrap();
+
+            assert_eq!(docs.lookup("pattern.bin").err(), Some(FsError::NotFound));
+            assert_eq!(file.metadata().unwrap().links, 0);
+            assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before));
+
+            let mut buffer = vec![0; PATTERN_SIZE];
+
+            assert_eq!(file.read_at(0, &mut buffer), Ok(PATTERN_SIZE));
+            assert_eq!(buffer, lightsaber_kernel_test_pattern(PATTERN_SIZE));
+        }
+
+        let clusters = (PATTERN_SIZE as u64 + CLUSTER_SIZE - 1) / CLUSTER_SIZE;
+
+        assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before + clusters * CLUSTER_SIZE));
+        assert_eq!(file_system.root().unlink("Docs"), Err(FsError::NotEmpty));
+
+        docs.lookup("Nested").unwrap().unlink("note.txt").unwrap();
+        docs.unlink("Nested").unwrap();
+
+        // The note and the directory that held it each had a cluster.
+        assert_eq!(file_system.usage().map(|usage| usage.free), Ok(before + (clusters + 2) * CLUSTER_SIZE));
+    }
+
+    #[test_case]
+    fn fat_refuses_boot_sectors_it_cannot_trust() {
+        let mount = |disk| FatFileSystem::new(disk).err();
+
+        // The signature, the sector size, the cluster size and the number of FATs.
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT16_IMAGE, 510, &[0, 0])), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT16_IMAGE, 11, &1000u16.to_le_bytes())), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT16_IMAGE, 13, &[3])), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT16_IMAGE, 16, &[0])), Some(FsError::InvalidArgument));
+
+        // The images as stored are shorter than their boot sectors say.
+        assert_eq!(mount(Arc::new(RamDisk::new("fat_test", 512, FAT16_IMAGE.to_vec()))), Some(FsError::InvalidArgument));
+
+        // A FAT32 root directory outside the volume, or a fixed one as well.
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT32_IMAGE, 44, &0u32.to_le_bytes())), Some(FsError::InvalidArgument));
+        assert_eq!(mount(lightsaber_kernel_test_patched(FAT32_IMAGE, 17, &512u16.to_le_bytes())), Some(FsError::InvalidArgument));
+    }
+
+    #[test_case]
+    fn fat_reports_broken_cluster_chains() {
+        let file_system = lightsaber_kernel_test_mount(FAT12_IMAGE);
+        let first = lightsaber_kernel_test_root_entry(&file_system, "frag-a.bin").first_cluster;
+        let chain = file_system.volume.read_chain(first).unwrap();
+        let last = *chain.last().unwrap();
+
+        // A chain that loops back on itself, and one that leaves the volume.
+        for next in [first, file_system.volume.cluster_count + FIRST_DATA_CLUSTER].iter() {
+            let file_system = lightsaber_kernel_test_mount(FAT12_IMAGE);
+            let volume = &file_system.volume;
+
+            volume.write_fat_entry(&mut volume.allocation.lock(), last, *next).unwrap();
+
+            let mut buffer = [0; 16];
+
+            assert_eq!(lightsaber_kernel_test_lookup(&file_system, "frag-a.bin").unwrap().read_at(0, &mut buffer), Err(FsError::Io));
+        }
+    }
+
+    #[test_case]
+    fn fat_mounts_on_a_mountpoint_it_creates() {
+        // The mount table is global, so this borrows a writable directory from the running system.
+        let scratch = path::lightsaber_kernel_lookup("/tmp", true).or_else(|_| mount::lightsaber_kernel_root()).unwrap();
+        let mountpoint = format!("{}/fat_test/efi", scratch.path().trim_end_matches('/'));
+
+        mount::lightsaber_kernel_create_mountpoint(&mountpoint).unwrap();
+        mount::lightsaber_kernel_mount(&mountpoint, Arc::new(lightsaber_kernel_test_mount(FAT32_IMAGE))).unwrap();
+
+        let hello = fs::lightsaber_kernel_read_file(&format!("{}/hello.txt", mountpoint));
+
+        mount::lightsaber_kernel_unmount(&mountpoint).unwrap();
+
+        let parent = scratch.lookup("fat_test").unwrap();
+        parent.inode().unlink("efi").unwrap();
+        parent.forget("efi");
+        scratch.inode().unlink("fat_test").unwrap();
+        scratch.forget("fat_test");
+
+        assert_eq!(hello.as_deref(), Ok(&b"Hello, FAT!\n"[..]));
+    }
+}
@@ -0,0 +1,187 @@
+use alloc::sync::Arc;
+
+use crate::{
+    fs::{
+        path,
+        Dentry,
+        DirectoryEntry,
+        FsError,
+        InodeType,
+        Metadata
+    },
+    process::files::FileDescription,
+    sync::Mutex,
+    syscall::SyscallError
+};
+
+pub const OPEN_READ_ONLY: u64 = 0x0;
+pub const OPEN_WRITE_ONLY: u64 = 0x1;
+pub const OPEN_READ_WRITE: u64 = 0x2;
+pub const OPEN_ACCESS_MODE: u64 = 0x3;
+pub const OPEN_CREATE: u64 = 0x40;
+pub const OPEN_EXCLUSIVE: u64 = 0x80;
+pub const OPEN_TRUNCATE: u64 = 0x200;
+pub const OPEN_APPEND: u64 = 0x400;
+pub const OPEN_DIRECTORY: u64 = 0x10000;
+pub const OPEN_NO_FOLLOW: u64 = 0x20000;
+
+const OPEN_FLAGS: u64 = OPEN_ACCESS_MODE | OPEN_CREATE | OPEN_EXCLUSIVE | OPEN_TRUNCATE | OPEN_APPEND | OPEN_DIRECTORY | OPEN_NO_FOLLOW;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SeekWhence {
+    Start,
+
+    Current,
+
+    End
+}
+
+impl SeekWhence {
+    pub fn from_u64(value: u64) -> Option<Self> {
+        match value {
+            0 => Some(Self::Start),
+            1 => Some(Self::Current),
+            2 => Some(Self::End),
+            _ => None
+        }
+    }
+}
+
+pub struct OpenFile {
+    dentry: Arc<Dentry>,
+    flags: u64,
+    offset: Mutex<u64>
+}
+
+impl OpenFile {
+    pub fn new(dentry: Arc<Dentry>, flags: u64) -> Self {
+        Self {
+            dentry,
+            flags,
+            offset: Mutex::named("open_file_offset", 0)
+        }
+    }
+
+    #[inline]
+    pub fn is_readable(&self) -> bool {
+        self.flags & OPEN_ACCESS_MODE != OPEN_WRITE_ONLY
+    }
+
+    #[inline]
+    pub fn is_writable(&self) -> bool {
+        self.flags & OPEN_ACCESS_MODE != OPEN_READ_ONLY
+    }
+}
+
+impl FileDescription for OpenFile {
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, SyscallError> {
+        if !self.is_readable() {
+            return Err(SyscallError::BadDescriptor);
+        }
+
+        let mut offset = self.offset.lock();
+        let count = self.dentry.inode().read_at(*offset, buffer)?;
+
+        *offset += count as u64;
+
+        Ok(count)
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<usize, SyscallError> {
+        if !self.is_writable() {
+            return Err(SyscallError::BadDescriptor);
+        }
+
+        let mut offset = self.offset.lock();
+
+        if self.flags & OPEN_APPEND != 0 {
+            *offset = self.dentry.inode().metadata()?.size;
+        }
+
+        let count = self.dentry.inode().write_at(*offset, buffer)?;
+
+        *offset += count as u64;
+
+        Ok(count)
+    }
+
+    fn seek(&self, offset: i64, whence: SeekWhence) -> Result<u64, SyscallError> {
+        let mut current = self.offset.lock();
+
+        let base = match whence {
+            SeekWhence::Start => 0,
+            SeekWhence::Current => *current as i64,
+            SeekWhence::End => self.dentry.inode().metadata()?.size as i64
+        };
+
+        let target = base
+            .checked_add(offset)
+            .filter(|target| *target >= 0)
+            .ok_or(SyscallError::InvalidArgument)?;
+
+        *current = target as u64;
+
+        Ok(*current)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SyscallError> {
+        Ok(self.dentry.inode().metadata()?)
+    }
+
+    fn read_directory(&self, fill: &mut dyn FnMut(&DirectoryEntry) -> bool) -> Result<usize, SyscallError> {
+        // For directories the offset is the index of the next entry to hand out.
+        let mut offset = self.offset.lock();
+        let mut count = 0;
+
+        while let Some(entry) = self.dentry.inode().read_directory(*offset as usize)? {
+            if !fill(&entry) {
+                break;
+            }
+
+            *offset += 1;
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+pub fn lightsaber_kernel_open(path: &str, flags: u64, mode: u16) -> Result<Arc<OpenFile>, FsError> {
+    let access_mode = flags & OPEN_ACCESS_MODE;
+
+    if flags & !OPEN_FLAGS != 0 || ![OPEN_READ_ONLY, OPEN_WRITE_ONLY, OPEN_READ_WRITE].contains(&access_mode) {
+        return Err(FsError::InvalidArgument);
+    }
+
+    let follow = flags & OPEN_NO_FOLLOW == 0;
+
+    let dentry = match flags & OPEN_CREATE {
+        0 => path::lightsaber_kernel_lookup(path, follow)?,
+        _ => {
+            let (parent, name) = path::lightsaber_kernel_lookup_parent(path)?;
+
+            match parent.lookup(&name) {
+                Ok(_) if flags & OPEN_EXCLUSIVE != 0 => return Err(FsError::AlreadyExists),
+                Ok(_) => path::lightsaber_kernel_lookup(path, follow)?,
+                Err(FsError::NotFound) => {
+                    parent.inode().create(&name, InodeType::File, mode)?;
+                    parent.lookup(&name)?
+                }
+                Err(error) => return Err(error)
+            }
+        }
+    };
+
+    let open_file = OpenFile::new(dentry, flags);
+
+    match open_file.dentry.inode_type()? {
+        InodeType::Symlink => return Err(FsError::TooManyLinks),
+        InodeType::Directory if open_file.is_writable() => return Err(FsError::IsDirectory),
+        InodeType::Directory => { }
+        _ if flags & OPEN_DIRECTORY != 0 => return Err(FsError::NotDirectory),
+        InodeType::File if flags & OPEN_TRUNCATE != 0 && open_file.is_writable() => open_file.dentry.inode().truncate(0)?,
+        _ => { }
+    }
+
+    Ok(Arc::new(open_file))
+}
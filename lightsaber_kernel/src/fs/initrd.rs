@@ -0,0 +1,761 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    str,
+    sync::atomic::{
+        AtomicU32,
+        AtomicU64,
+        Ordering
+    }
+};
+
+use lightsaber_bootloader::InitialRamdisk;
+
+use crate::{
+    fs::{
+        inode::MODE_PERMISSION_MASK,
+        mount,
+        DirectoryEntry,
+        FileSystem,
+        FsError,
+        Inode,
+        InodeType,
+        Metadata
+    },
+    sync::Spinlock
+};
+
+const CPIO_NEWC_MAGIC: &[u8] = b"070701";
+const CPIO_NEWC_CRC_MAGIC: &[u8] = b"070702";
+const CPIO_HEADER_SIZE: usize = 110;
+const CPIO_TRAILER: &str = "TRAILER!!!";
+
+const TAR_BLOCK_SIZE: usize = 512;
+const TAR_MAGIC: &[u8] = b"ustar";
+const TAR_MAGIC_OFFSET: usize = 257;
+const TAR_CHECKSUM_OFFSET: usize = 148;
+const TAR_CHECKSUM_SIZE: usize = 8;
+
+const DEFAULT_DIRECTORY_MODE: u16 = 0o755;
+const BLOCK_SIZE: u64 = 512;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ArchiveError {
+    UnknownFormat,
+
+    Truncated,
+
+    BadHeader,
+
+    BadChecksum,
+
+    BadPath,
+
+    MissingLinkTarget
+}
+
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::UnknownFormat => "The archive is neither a cpio (newc) nor a tar archive.",
+            Self::Truncated => "The archive is truncated.",
+            Self::BadHeader => "The archive contains a malformed header.",
+            Self::BadChecksum => "The archive contains a header whose checksum does not match.",
+            Self::BadPath => "The archive contains an invalid path.",
+            Self::MissingLinkTarget => "The archive contains a hard link to a missing file."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+enum InitialRamdiskContent {
+    File(Spinlock<&'static [u8]>),
+
+    Directory(Spinlock<BTreeMap<String, Arc<InitialRamdiskInode>>>),
+
+    Symlink(String)
+}
+
+pub struct InitialRamdiskInode {
+    inode: u64,
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    modified: u64,
+    links: AtomicU32,
+    content: InitialRamdiskContent
+}
+
+impl InitialRamdiskInode {
+    fn inode_type(&self) -> InodeType {
+        match self.content {
+            InitialRamdiskContent::File(_) => InodeType::File,
+            InitialRamdiskContent::Directory(_) => InodeType::Directory,
+            InitialRamdiskContent::Symlink(_) => InodeType::Symlink
+        }
+    }
+
+    fn children(&self) -> Result<&Spinlock<BTreeMap<String, Arc<InitialRamdiskInode>>>, FsError> {
+        match &self.content {
+            InitialRamdiskContent::Directory(children) => Ok(children),
+            _ => Err(FsError::NotDirectory)
+        }
+    }
+}
+
+impl Inode for InitialRamdiskInode {
+    fn metadata(&self) -> Result<Metadata, FsError> {
+        let size = match &self.content {
+            InitialRamdiskContent::File(data) => data.lock().len() as u64,
+            InitialRamdiskContent::Directory(children) => children.lock().len() as u64,
+            InitialRamdiskContent::Symlink(target) => target.len() as u64
+        };
+
+        Ok(Metadata {
+            device: 0,
+            inode: self.inode,
+            inode_type: self.inode_type(),
+            mode: self.mode,
+            links: self.links.load(Ordering::Relaxed),
+            uid: self.uid,
+            gid: self.gid,
+            size,
+            block_size: BLOCK_SIZE,
+            blocks: (size + BLOCK_SIZE - 1) / BLOCK_SIZE,
+            accessed: self.modified,
+            modified: self.modified,
+            changed: self.modified
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let data = match &self.content {
+            InitialRamdiskContent::File(data) => *data.lock(),
+            InitialRamdiskContent::Directory(_) => return Err(FsError::IsDirectory),
+            InitialRamdiskContent::Symlink(_) => return Err(FsError::InvalidArgument)
+        };
+
+        if offset >= data.len() as u64 {
+            return Ok(0);
+        }
+
+        let available = &data[offset as usize..];
+        let count = available.len().min(buffer.len());
+
+        buffer[..count].copy_from_slice(&available[..count]);
+
+        Ok(count)
+    }
+
+    fn write_at(&self, _offset: u64, _buffer: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn truncate(&self, _size: u64) -> Result<(), FsError> {
+        Err(FsError::ReadOnly)
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        let child = self.children()?
+            .lock()
+            .get(name)
+            .cloned()
+            .ok_or(FsError::NotFound)?;
+
+        Ok(child)
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType, _mode: u16) -> Result<Arc<dyn Inode>, FsError> {
+        self.children()?;
+
+        Err(FsError::ReadOnly)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<dyn Inode>) -> Result<(), FsError> {
+        self.children()?;
+
+        Err(FsError::ReadOnly)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), FsError> {
+        self.children()?;
+
+        Err(FsError::ReadOnly)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>, FsError> {
+        self.children()?;
+
+        Err(FsError::ReadOnly)
+    }
+
+    fn read_link(&self) -> Result<String, FsError> {
+        match &self.content {
+            InitialRamdiskContent::Symlink(target) => Ok(target.clone()),
+            _ => Err(FsError::InvalidArgument)
+        }
+    }
+
+    fn read_directory(&self, index: usize) -> Result<Option<DirectoryEntry>, FsError> {
+        let children = self.children()?.lock();
+
+        Ok(children.iter().nth(index).map(|(name, child)| DirectoryEntry {
+            name: name.clone(),
+            inode: child.inode,
+            inode_type: child.inode_type()
+        }))
+    }
+}
+
+pub struct InitialRamdiskFileSystem {
+    root: Arc<InitialRamdiskInode>,
+    next_inode: AtomicU64
+}
+
+struct ArchiveEntry<'a> {
+    path: &'a str,
+    inode_type: InodeType,
+    mode: u16,
+    uid: u32,
+    gid: u32,
+    modified: u64,
+    data: &'static [u8],
+    hard_link: Option<&'a str>
+}
+
+impl InitialRamdiskFileSystem {
+    pub fn new(archive: &'static [u8]) -> Result<Self, ArchiveError> {
+        let file_system = Self {
+            root: Arc::new(InitialRamdiskInode {
+                inode: 1,
+                mode: DEFAULT_DIRECTORY_MODE,
+                uid: 0,
+                gid: 0,
+                modified: 0,
+                links: AtomicU32::new(2),
+                content: InitialRamdiskContent::Directory(Spinlock::named("initrd_directory", BTreeMap::new()))
+            }),
+            next_inode: AtomicU64::new(2)
+        };
+
+        if archive.starts_with(CPIO_NEWC_MAGIC) || archive.starts_with(CPIO_NEWC_CRC_MAGIC) {
+            file_system.parse_cpio(archive)?;
+        }
+        else if archive.get(TAR_MAGIC_OFFSET..TAR_MAGIC_OFFSET + TAR_MAGIC.len()) == Some(TAR_MAGIC) {
+            file_system.parse_tar(archive)?;
+        }
+        else {
+            return Err(ArchiveError::UnknownFormat);
+        }
+
+        Ok(file_system)
+    }
+
+    fn parse_cpio(&self, archive: &'static [u8]) -> Result<(), ArchiveError> {
+        let mut offset = 0;
+        let mut hard_links: BTreeMap<u64, Arc<InitialRamdiskInode>> = BTreeMap::new();
+
+        loop {
+            let header = archive.get(offset..offset + CPIO_HEADER_SIZE).ok_or(ArchiveError::Truncated)?;
+
+            if !header.starts_with(CPIO_NEWC_MAGIC) && !header.starts_with(CPIO_NEWC_CRC_MAGIC) {
+                return Err(ArchiveError::BadHeader);
+            }
+
+            let field = |index: usize| -> Result<u64, ArchiveError> {
+                let start = 6 + index * 8;
+
+                str::from_utf8(&header[start..start + 8])
+                    .ok()
+                    .and_then(|digits| u64::from_str_radix(digits, 16).ok())
+                    .ok_or(ArchiveError::BadHeader)
+            };
+
+            let (inode, mode, uid, gid, links, modified, size) = (field(0)?, field(1)?, field(2)?, field(3)?, field(4)?, field(5)?, field(6)? as usize);
+            let name_size = field(11)? as usize;
+
+            let name_start = offset + CPIO_HEADER_SIZE;
+            let name = archive.get(name_start..name_start + name_size).ok_or(ArchiveError::Truncated)?;
+            let name = str::from_utf8(name.split(|byte| *byte == 0).next().unwrap_or(&[])).map_err(|_| ArchiveError::BadPath)?;
+
+            let data_start = (name_start + name_size + 3) & !3;
+            let data = archive.get(data_start..data_start + size).ok_or(ArchiveError::Truncated)?;
+
+            offset = (data_start + size + 3) & !3;
+
+            if name == CPIO_TRAILER {
+                return Ok(());
+            }
+
+            let inode_type = match mode as u32 & 0o170000 {
+                0o100000 => InodeType::File,
+                0o040000 => InodeType::Directory,
+                0o120000 => InodeType::Symlink,
+                _ => {
+                    log::warn!("Skipping unsupported initial ramdisk entry `{}`.", name);
+                    continue;
+                }
+            };
+
+            // Hard linked files share an inode number, and only the last of them carries the data.
+            if inode_type == InodeType::File && links > 1 {
+                if let Some(existing) = hard_links.get(&inode).cloned() {
+                    if let InitialRamdiskContent::File(existing_data) = &existing.content {
+                        if !data.is_empty() {
+                            *existing_data.lock() = data;
+                        }
+                    }
+
+                    self.insert_existing(name, existing)?;
+                    continue;
+                }
+            }
+
+            let created = self.insert(ArchiveEntry {
+                path: name,
+                inode_type,
+                mode: mode as u16 & MODE_PERMISSION_MASK,
+                uid: uid as u32,
+                gid: gid as u32,
+                modified,
+                data,
+                hard_link: None
+            })?;
+
+            if let Some(created) = created {
+                if inode_type == InodeType::File && links > 1 {
+                    hard_links.insert(inode, created);
+                }
+            }
+        }
+    }
+
+    fn parse_tar(&self, archive: &'static [u8]) -> Result<(), ArchiveError> {
+        let mut offset = 0;
+
+        while let Some(header) = archive.get(offset..offset + TAR_BLOCK_SIZE) {
+            if header.iter().all(|byte| *byte == 0) {
+                return Ok(());
+            }
+
+            let string = |start: usize, length: usize| -> Result<&'static str, ArchiveError> {
+                let bytes = &archive[offset + start..offset + start + length];
+                let bytes = bytes.split(|byte| *byte == 0).next().unwrap_or(&[]);
+
+                str::from_utf8(bytes).map_err(|_| ArchiveError::BadPath)
+            };
+
+            let octal = |start: usize, length: usize| -> Result<u64, ArchiveError> {
+                let digits = string(start, length).map_err(|_| ArchiveError::BadHeader)?.trim_matches(|character| character == ' ' || character == '\0');
+
+                match digits.is_empty() {
+                    true => Ok(0),
+                    false => u64::from_str_radix(digits, 8).map_err(|_| ArchiveError::BadHeader)
+                }
+            };
+
+            // The checksum is taken over the header with its own field counted as spaces.
+            let sum = header
+                .iter()
+                .enumerate()
+                .map(|(index, byte)| match (TAR_CHECKSUM_OFFSET..TAR_CHECKSUM_OFFSET + TAR_CHECKSUM_SIZE).contains(&index) {
+                    true => b' ' as u64,
+                    false => *byte as u64
+                })
+                .sum::<u64>();
+
+            if octal(TAR_CHECKSUM_OFFSET, TAR_CHECKSUM_SIZE)? != sum {
+                return Err(ArchiveError::BadChecksum);
+            }
+
+            let (name, prefix) = (string(0, 100)?, string(345, 155)?);
+            let (mode, uid, gid, size, modified) = (octal(100, 8)?, octal(108, 8)?, octal(116, 8)?, octal(124, 12)? as usize, octal(136, 12)?);
+            let link_name = string(157, 100)?;
+
+            let data_start = offset + TAR_BLOCK_SIZE;
+            let data = archive.get(data_start..data_start + size).ok_or(ArchiveError::Truncated)?;
+
+            offset = data_start + (size + TAR_BLOCK_SIZE - 1) / TAR_BLOCK_SIZE * TAR_BLOCK_SIZE;
+
+            let path = match prefix.is_empty() {
+                true => name.to_string(),
+                false => [prefix, name].join("/")
+            };
+
+            let (inode_type, hard_link) = match header[156] {
+                b'0' | 0 | b'7' => (InodeType::File, None),
+                b'1' => (InodeType::File, Some(link_name)),
+                b'2' => (InodeType::Symlink, None),
+                b'5' => (InodeType::Directory, None),
+                _ => {
+                    log::warn!("Skipping unsupported initial ramdisk entry `{}`.", path);
+                    continue;
+                }
+            };
+
+            let data = match inode_type {
+                InodeType::Symlink => link_name.as_bytes(),
+                _ => data
+            };
+
+            self.insert(ArchiveEntry {
+                path: &path,
+                inode_type,
+                mode: mode as u16 & MODE_PERMISSION_MASK,
+                uid: uid as u32,
+                gid: gid as u32,
+                modified,
+                data,
+                hard_link
+            })?;
+        }
+
+        Err(ArchiveError::Truncated)
+    }
+
+    fn allocate_inode(&self) -> u64 {
+        self.next_inode.fetch_add(1, Ordering::Relaxed)
+    }
+
+    fn split_path(path: &str) -> Result<(Vec<&str>, &str), ArchiveError> {
+        let mut components = path
+            .split('/')
+            .filter(|component| !component.is_empty() && *component != ".")
+            .collect::<Vec<&str>>();
+
+        if components.iter().any(|component| *component == "..") {
+            return Err(ArchiveError::BadPath);
+        }
+
+        match components.pop() {
+            Some(name) => Ok((components, name)),
+            None => Ok((components, ""))
+        }
+    }
+
+    fn directory(&self, components: &[&str]) -> Result<Arc<InitialRamdiskInode>, ArchiveError> {
+        let mut directory = self.root.clone();
+
+        for component in components {
+            let next = {
+                let mut children = directory.children().map_err(|_| ArchiveError::BadPath)?.lock();
+
+                match children.get(*component) {
+                    Some(child) => child.clone(),
+                    None => {
+                        let child = Arc::new(InitialRamdiskInode {
+                            inode: self.allocate_inode(),
+                            mode: DEFAULT_DIRECTORY_MODE,
+                            uid: 0,
+                            gid: 0,
+                            modified: 0,
+                            links: AtomicU32::new(2),
+                            content: InitialRamdiskContent::Directory(Spinlock::named("initrd_directory", BTreeMap::new()))
+                        });
+
+                        directory.links.fetch_add(1, Ordering::Relaxed);
+                        children.insert(component.to_string(), child.clone());
+
+                        child
+                    }
+                }
+            };
+
+            directory = next;
+        }
+
+        Ok(directory)
+    }
+
+    fn insert_existing(&self, path: &str, inode: Arc<InitialRamdiskInode>) -> Result<(), ArchiveError> {
+        let (components, name) = Self::split_path(path)?;
+        let directory = self.directory(&components)?;
+
+        inode.links.fetch_add(1, Ordering::Relaxed);
+        directory.children().map_err(|_| ArchiveError::BadPath)?.lock().insert(name.to_string(), inode);
+
+        Ok(())
+    }
+
+    fn insert(&self, entry: ArchiveEntry) -> Result<Option<Arc<InitialRamdiskInode>>, ArchiveError> {
+        if let Some(target) = entry.hard_link {
+            let (components, name) = Self::split_path(target)?;
+
+            let existing = self.directory(&components)?
+                .children()
+                .ok()
+                .and_then(|children| children.lock().get(name).cloned())
+                .ok_or(ArchiveError::MissingLinkTarget)?;
+
+            self.insert_existing(entry.path, existing)?;
+
+            return Ok(None);
+        }
+
+        let (components, name) = Self::split_path(entry.path)?;
+
+        // The archive root itself, usually listed as `.`.
+        if name.is_empty() {
+            return Ok(None);
+        }
+
+        let directory = self.directory(&components)?;
+        let mut children = directory.children().map_err(|_| ArchiveError::BadPath)?.lock();
+
+        // Directories may already exist because a file inside them was listed first.
+        if entry.inode_type == InodeType::Directory {
+            if let Some(existing) = children.get(name) {
+                if existing.inode_type() == InodeType::Directory {
+                    return Ok(None);
+                }
+            }
+        }
+
+        let content = match entry.inode_type {
+            InodeType::File => InitialRamdiskContent::File(Spinlock::named("initrd_file", entry.data)),
+            InodeType::Directory => InitialRamdiskContent::Directory(Spinlock::named("initrd_directory", BTreeMap::new())),
+            _ => InitialRamdiskContent::Symlink(str::from_utf8(entry.data).map_err(|_| ArchiveError::BadPath)?.to_string())
+        };
+
+        let links = match entry.inode_type {
+            InodeType::Directory => 2,
+            _ => 1
+        };
+
+        let inode = Arc::new(InitialRamdiskInode {
+            inode: self.allocate_inode(),
+            mode: entry.mode,
+            uid: entry.uid,
+            gid: entry.gid,
+            modified: entry.modified,
+            links: AtomicU32::new(links),
+            content
+        });
+
+        if entry.inode_type == InodeType::Directory {
+            directory.links.fetch_add(1, Ordering::Relaxed);
+        }
+
+        children.insert(name.to_string(), inode.clone());
+
+        Ok(Some(inode))
+    }
+}
+
+impl FileSystem for InitialRamdiskFileSystem {
+    fn name(&self) -> &'static str {
+        "initrd"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
+
+pub fn lightsaber_kernel_mount_initial_ramdisk(initial_ramdisk: &InitialRamdisk) {
+    if !initial_ramdisk.is_present() {
+        log::warn!("No initial ramdisk was provided by the bootloader.");
+        return;
+    }
+
+    let archive = unsafe {
+        initial_ramdisk.as_slice()
+    };
+
+    let file_system = match InitialRamdiskFileSystem::new(archive) {
+        Ok(file_system) => file_system,
+        Err(error) => {
+            log::error!("Failed to parse the initial ramdisk: {}", error);
+            return;
+        }
+    };
+
+    if let Err(error) = mount::lightsaber_kernel_mount_root(Arc::new(file_system)) {
+        log::error!("Failed to mount the initial ramdisk: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{
+        format,
+        vec,
+        vec::Vec
+    };
+
+    use super::*;
+
+    // Path, mode, inode number, link count and data of one cpio (newc) member.
+    type CpioMember<'a> = (&'a str, u32, u64, u64, &'a [u8]);
+
+    // Name, prefix, type flag, link name and data of one ustar member.
+    type TarMember<'a> = (&'a str, &'a str, u8, &'a str, &'a [u8]);
+
+    fn lightsaber_kernel_test_pad(archive: &mut Vec<u8>, alignment: usize) {
+        archive.resize((archive.len() + alignment - 1) / alignment * alignment, 0);
+    }
+
+    fn lightsaber_kernel_test_cpio(members: &[CpioMember]) -> Vec<u8> {
+        let mut archive = Vec::new();
+
+        for (path, mode, inode, links, data) in members.iter().chain(&[(CPIO_TRAILER, 0, 0, 1, &[][..])]) {
+            let fields = [*inode, *mode as u64, 0, 0, *links, 1620648000, data.len() as u64, 0, 0, 0, 0, path.len() as u64 + 1, 0];
+
+            archive.extend_from_slice(CPIO_NEWC_MAGIC);
+
+            for field in &fields {
+                archive.extend_from_slice(format!("{:08X}", field).as_bytes());
+            }
+
+            archive.extend_from_slice(path.as_bytes());
+            archive.push(0);
+            lightsaber_kernel_test_pad(&mut archive, 4);
+            archive.extend_from_slice(data);
+            lightsaber_kernel_test_pad(&mut archive, 4);
+        }
+
+        archive
+    }
+
+    fn lightsaber_kernel_test_tar(members: &[TarMember]) -> Vec<u8> {
+        let mut archive = Vec::new();
+
+        for (name, prefix, type_flag, link_name, data) in members {
+            let mut header = [0; TAR_BLOCK_SIZE];
+
+            let mut field = |start: usize, value: &[u8]| header[start..start + value.len()].copy_from_slice(value);
+
+            field(0, name.as_bytes());
+            field(100, b"0000644\0");
+            field(108, b"0000000\0");
+            field(116, b"0000000\0");
+            field(124, format!("{:011o}\0", data.len()).as_bytes());
+            field(136, b"14044621500\0");
+            field(156, &[*type_flag]);
+            field(157, link_name.as_bytes());
+            field(TAR_MAGIC_OFFSET, b"ustar\0");
+            field(263, b"00");
+            field(345, prefix.as_bytes());
+
+            header[TAR_CHECKSUM_OFFSET..TAR_CHECKSUM_OFFSET + TAR_CHECKSUM_SIZE].copy_from_slice(b"        ");
+
+            let sum = header.iter().map(|byte| *byte as u64).sum::<u64>();
+            header[TAR_CHECKSUM_OFFSET..TAR_CHECKSUM_OFFSET + TAR_CHECKSUM_SIZE].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+
+            archive.extend_from_slice(&header);
+            archive.extend_from_slice(data);
+            lightsaber_kernel_test_pad(&mut archive, TAR_BLOCK_SIZE);
+        }
+
+        archive.extend_from_slice(&[0; 2 * TAR_BLOCK_SIZE]);
+
+        archive
+    }
+
+    // The file system keeps borrowing the archive, as the bootloader's stays mapped for good.
+    fn lightsaber_kernel_test_parse(archive: &[u8]) -> Result<InitialRamdiskFileSystem, ArchiveError> {
+        InitialRamdiskFileSystem::new(Vec::leak(archive.to_vec()))
+    }
+
+    fn lightsaber_kernel_test_open(file_system: &InitialRamdiskFileSystem, path: &str) -> Arc<dyn Inode> {
+        path.split('/').fold(file_system.root(), |directory, name| directory.lookup(name).unwrap())
+    }
+
+    fn lightsaber_kernel_test_read(inode: &Arc<dyn Inode>) -> Vec<u8> {
+        let mut buffer = vec![0; 64];
+        let count = inode.read_at(0, &mut buffer).unwrap();
+
+        buffer.truncate(count);
+        buffer
+    }
+
+    #[test_case]
+    fn initrd_cpio_hard_links_take_the_data_of_the_last_link() {
+        let archive = lightsaber_kernel_test_cpio(&[
+            (".", 0o040755, 1, 2, b""),
+            ("bin", 0o040755, 2, 2, b""),
+            ("bin/first", 0o100755, 3, 2, b""),
+            ("bin/second", 0o100755, 3, 2, b"shared"),
+            ("bin/link", 0o120777, 4, 1, b"second")
+        ]);
+
+        let file_system = lightsaber_kernel_test_parse(&archive).unwrap();
+        let first = lightsaber_kernel_test_open(&file_system, "bin/first");
+        let second = lightsaber_kernel_test_open(&file_system, "bin/second");
+
+        assert_eq!(lightsaber_kernel_test_read(&first), b"shared");
+        assert_eq!(first.metadata().unwrap().inode, second.metadata().unwrap().inode);
+        assert_eq!(first.metadata().unwrap().links, 2);
+        assert_eq!(first.metadata().unwrap().mode, 0o755);
+        assert_eq!(lightsaber_kernel_test_open(&file_system, "bin/link").read_link().unwrap(), "second");
+    }
+
+    #[test_case]
+    fn initrd_tar_joins_the_prefix_and_resolves_hard_links() {
+        let archive = lightsaber_kernel_test_tar(&[
+            ("file", "a/long/directory", b'0', "", b"hello"),
+            ("copy", "", b'1', "a/long/directory/file", b""),
+            ("empty/", "", b'5', "", b""),
+            ("link", "", b'2', "copy", b"")
+        ]);
+
+        let file_system = lightsaber_kernel_test_parse(&archive).unwrap();
+        let file = lightsaber_kernel_test_open(&file_system, "a/long/directory/file");
+        let copy = lightsaber_kernel_test_open(&file_system, "copy");
+
+        assert_eq!(lightsaber_kernel_test_read(&file), b"hello");
+        assert_eq!(copy.metadata().unwrap().inode, file.metadata().unwrap().inode);
+        assert_eq!(copy.metadata().unwrap().links, 2);
+        assert_eq!(lightsaber_kernel_test_open(&file_system, "empty").metadata().unwrap().inode_type, InodeType::Directory);
+        assert_eq!(lightsaber_kernel_test_open(&file_system, "link").read_link().unwrap(), "copy");
+    }
+
+    #[test_case]
+    fn initrd_rejects_paths_that_climb_out() {
+        let cpio = lightsaber_kernel_test_cpio(&[("../escape", 0o100644, 2, 1, b"")]);
+        let tar = lightsaber_kernel_test_tar(&[("escape", "directory/..", b'0', "", b"")]);
+        let tar_link = lightsaber_kernel_test_tar(&[("file", "", b'0', "", b""), ("copy", "", b'1', "../file", b"")]);
+
+        assert_eq!(lightsaber_kernel_test_parse(&cpio).err(), Some(ArchiveError::BadPath));
+        assert_eq!(lightsaber_kernel_test_parse(&tar).err(), Some(ArchiveError::BadPath));
+        assert_eq!(lightsaber_kernel_test_parse(&tar_link).err(), Some(ArchiveError::BadPath));
+    }
+
+    #[test_case]
+    fn initrd_rejects_truncated_archives() {
+        let data = [0x5A; 600];
+        let cpio = lightsaber_kernel_test_cpio(&[("file", 0o100644, 2, 1, &data)]);
+        let tar = lightsaber_kernel_test_tar(&[("file", "", b'0', "", &data)]);
+
+        assert_eq!(lightsaber_kernel_test_parse(&cpio[..CPIO_HEADER_SIZE - 1]).err(), Some(ArchiveError::Truncated));
+        assert_eq!(lightsaber_kernel_test_parse(&cpio[..CPIO_HEADER_SIZE + 100]).err(), Some(ArchiveError::Truncated));
+        // Without its trailer, a cpio archive runs out where the next header should be.
+        assert_eq!(lightsaber_kernel_test_parse(&cpio[..cpio.len() - 124]).err(), Some(ArchiveError::Truncated));
+        assert_eq!(lightsaber_kernel_test_parse(&tar[..TAR_BLOCK_SIZE + 100]).err(), Some(ArchiveError::Truncated));
+        // And a tar archive without its two zero blocks.
+        assert_eq!(lightsaber_kernel_test_parse(&tar[..tar.len() - 2 * TAR_BLOCK_SIZE]).err(), Some(ArchiveError::Truncated));
+    }
+
+    #[test_case]
+    fn initrd_rejects_unknown_formats_and_bad_checksums() {
+        let mut tar = lightsaber_kernel_test_tar(&[("file", "", b'0', "", b"data")]);
+
+        assert_eq!(lightsaber_kernel_test_parse(b"070707 is an old cpio format").err(), Some(ArchiveError::UnknownFormat));
+        assert_eq!(lightsaber_kernel_test_parse(&[0; TAR_BLOCK_SIZE]).err(), Some(ArchiveError::UnknownFormat));
+        assert!(lightsaber_kernel_test_parse(&tar).is_ok());
+
+        tar[0] = b'g';
+
+        assert_eq!(lightsaber_kernel_test_parse(&tar).err(), Some(ArchiveError::BadChecksum));
+    }
+}
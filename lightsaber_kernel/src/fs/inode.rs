@@ -0,0 +1,164 @@
+use alloc::{
+    string::String,
+    sync::Arc
+};
+
+use crate::fs::FsError;
+
+pub const MODE_TYPE_MASK: u32 = 0o170000;
+pub const MODE_PERMISSION_MASK: u16 = 0o7777;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InodeType {
+    File,
+
+    Directory,
+
+    Symlink,
+
+    CharacterDevice,
+
+    BlockDevice
+}
+
+impl InodeType {
+    #[inline]
+    pub fn mode_bits(self) -> u32 {
+        match self {
+            Self::File => 0o100000,
+            Self::Directory => 0o040000,
+            Self::Symlink => 0o120000,
+            Self::CharacterDevice => 0o020000,
+            Self::BlockDevice => 0o060000
+        }
+    }
+
+    pub fn from_mode_bits(mode: u32) -> Option<Self> {
+        match mode & MODE_TYPE_MASK {
+            0o100000 => Some(Self::File),
+            0o040000 => Some(Self::Directory),
+            0o120000 => Some(Self::Symlink),
+            0o020000 => Some(Self::CharacterDevice),
+            0o060000 => Some(Self::BlockDevice),
+            _ => None
+        }
+    }
+
+    // The `d_type` values reported by `readdir`.
+    #[inline]
+    pub fn directory_entry_type(self) -> u8 {
+        match self {
+            Self::File => 8,
+            Self::Directory => 4,
+            Self::Symlink => 10,
+            Self::CharacterDevice => 2,
+            Self::BlockDevice => 6
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Metadata {
+    pub device: u64,
+    pub inode: u64,
+    pub inode_type: InodeType,
+    pub mode: u16,
+    pub links: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub block_size: u64,
+    pub blocks: u64,
+    pub accessed: u64,
+    pub modified: u64,
+    pub changed: u64
+}
+
+impl Metadata {
+    #[inline]
+    pub fn is_directory(&self) -> bool {
+        self.inode_type == InodeType::Directory
+    }
+
+    #[inline]
+    pub fn full_mode(&self) -> u32 {
+        self.inode_type.mode_bits() | (self.mode & MODE_PERMISSION_MASK) as u32
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DirectoryEntry {
+    pub name: String,
+    pub inode: u64,
+    pub inode_type: InodeType
+}
+
+pub trait Inode: Send + Sync {
+    fn metadata(&self) -> Result<Metadata, FsError>;
+
+    fn read_at(&self, _offset: u64, _buffer: &mut [u8]) -> Result<usize, FsError> {
+        Err(FsError::NotSupported)
+    }
+
+    fn write_at(&self, _offset: u64, _buffer: &[u8]) -> Result<usize, FsError> {
+        Err(FsError::NotSupported)
+    }
+
+    fn truncate(&self, _size: u64) -> Result<(), FsError> {
+        Err(FsError::NotSupported)
+    }
+
+    fn lookup(&self, _name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType, _mode: u16) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn link(&self, _name: &str, _target: &Arc<dyn Inode>) -> Result<(), FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn unlink(&self, _name: &str) -> Result<(), FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn symlink(&self, _name: &str, _target: &str) -> Result<Arc<dyn Inode>, FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn read_link(&self) -> Result<String, FsError> {
+        Err(FsError::InvalidArgument)
+    }
+
+    // Entries are addressed by a cursor that stays valid for as long as the directory is not modified.
+    fn read_directory(&self, _index: usize) -> Result<Option<DirectoryEntry>, FsError> {
+        Err(FsError::NotDirectory)
+    }
+
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FsUsage {
+    pub size: u64,
+    pub free: u64
+}
+
+pub trait FileSystem: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn root(&self) -> Arc<dyn Inode>;
+
+    fn sync(&self) -> Result<(), FsError> {
+        Ok(())
+    }
+
+    // In bytes, for file systems that keep track of their free space.
+    fn usage(&self) -> Result<FsUsage, FsError> {
+        Err(FsError::NotSupported)
+    }
+}
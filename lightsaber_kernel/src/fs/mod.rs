@@ -0,0 +1,147 @@
+use alloc::{
+    vec,
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use crate::syscall::SyscallError;
+
+pub mod dentry;
+pub mod ext2;
+pub mod fat;
+pub mod file;
+pub mod initrd;
+pub mod inode;
+pub mod mount;
+pub mod path;
+pub mod tmpfs;
+
+pub use dentry::Dentry;
+pub use file::OpenFile;
+pub use inode::{
+    DirectoryEntry,
+    FileSystem,
+    FsUsage,
+    Inode,
+    InodeType,
+    Metadata
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FsError {
+    NotFound,
+
+    NotDirectory,
+
+    IsDirectory,
+
+    AlreadyExists,
+
+    NotEmpty,
+
+    InvalidArgument,
+
+    NameTooLong,
+
+    TooManyLinks,
+
+    ReadOnly,
+
+    NoSpace,
+
+    FileTooLarge,
+
+    Busy,
+
+    CrossDevice,
+
+    NotSupported,
+
+    Io
+}
+
+impl fmt::Display for FsError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::NotFound => "No such file or directory.",
+            Self::NotDirectory => "Not a directory.",
+            Self::IsDirectory => "Is a directory.",
+            Self::AlreadyExists => "File exists.",
+            Self::NotEmpty => "Directory not empty.",
+            Self::InvalidArgument => "Invalid argument.",
+            Self::NameTooLong => "File name too long.",
+            Self::TooManyLinks => "Too many levels of symbolic links.",
+            Self::ReadOnly => "Read-only file system.",
+            Self::NoSpace => "No space left on device.",
+            Self::FileTooLarge => "File too large.",
+            Self::Busy => "Device or resource busy.",
+            Self::CrossDevice => "Invalid cross-device link.",
+            Self::NotSupported => "Operation not supported.",
+            Self::Io => "Input/output error."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+impl From<FsError> for SyscallError {
+    fn from(error: FsError) -> Self {
+        match error {
+            FsError::NotFound => Self::NoEntry,
+            FsError::NotDirectory => Self::NotDirectory,
+            FsError::IsDirectory => Self::IsDirectory,
+            FsError::AlreadyExists => Self::Exists,
+            FsError::NotEmpty => Self::NotEmpty,
+            FsError::InvalidArgument => Self::InvalidArgument,
+            FsError::NameTooLong => Self::NameTooLong,
+            FsError::TooManyLinks => Self::SymlinkLoop,
+            FsError::ReadOnly => Self::ReadOnlyFilesystem,
+            FsError::NoSpace => Self::NoSpace,
+            FsError::FileTooLarge => Self::FileTooLarge,
+            FsError::Busy => Self::Busy,
+            FsError::CrossDevice => Self::CrossDevice,
+            FsError::NotSupported => Self::NotSupported,
+            FsError::Io => Self::Io
+        }
+    }
+}
+
+// Device number 0 belongs to the initial ramdisk.
+static NEXT_DEVICE_NUMBER: AtomicU64 = AtomicU64::new(1);
+
+pub fn lightsaber_kernel_allocate_device_number() -> u64 {
+    NEXT_DEVICE_NUMBER.fetch_add(1, Ordering::Relaxed)
+}
+
+pub fn lightsaber_kernel_read_file(path: &str) -> Result<Vec<u8>, FsError> {
+    let dentry = path::lightsaber_kernel_lookup(path, true)?;
+    let metadata = dentry.inode().metadata()?;
+
+    if metadata.is_directory() {
+        return Err(FsError::IsDirectory);
+    }
+
+    let mut buffer = vec![0; metadata.size as usize];
+    let mut read = 0;
+
+    while read < buffer.len() {
+        let count = dentry.inode().read_at(read as u64, &mut buffer[read..])?;
+
+        if count == 0 {
+            break;
+        }
+
+        read += count;
+    }
+
+    buffer.truncate(read);
+
+    Ok(buffer)
+}
@@ -0,0 +1,173 @@
+use alloc::{
+    format,
+    string::String,
+    sync::Arc,
+    vec::Vec
+};
+
+use core::iter;
+
+use crate::{
+    fs::{
+        path,
+        Dentry,
+        FileSystem,
+        FsError,
+        InodeType
+    },
+    sync::{
+        RwLock,
+        Spinlock
+    }
+};
+
+static MOUNTS: RwLock<Vec<Arc<Mount>>> = RwLock::named("mounts", Vec::new());
+static ROOT: Spinlock<Option<Arc<Dentry>>> = Spinlock::named("root_dentry", None);
+
+pub struct Mount {
+    path: String,
+    file_system: Arc<dyn FileSystem>,
+    root: Arc<Dentry>,
+    mountpoint: Option<Arc<Dentry>>
+}
+
+impl Mount {
+    #[inline]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    #[inline]
+    pub fn file_system(&self) -> &Arc<dyn FileSystem> {
+        &self.file_system
+    }
+
+    #[inline]
+    pub fn root(&self) -> &Arc<Dentry> {
+        &self.root
+    }
+}
+
+pub fn lightsaber_kernel_root() -> Result<Arc<Dentry>, FsError> {
+    ROOT.lock()
+        .as_ref()
+        .map(|root| root.follow_mounts())
+        .ok_or(FsError::NotFound)
+}
+
+pub fn lightsaber_kernel_mount_root(file_system: Arc<dyn FileSystem>) -> Result<(), FsError> {
+    let mut root = ROOT.lock();
+
+    if root.is_some() {
+        return Err(FsError::Busy);
+    }
+
+    let dentry = Dentry::new_root(file_system.root(), None);
+
+    MOUNTS.write().push(Arc::new(Mount {
+        path: String::from("/"),
+        file_system: file_system.clone(),
+        root: dentry.clone(),
+        mountpoint: None
+    }));
+
+    *root = Some(dentry);
+
+    log::info!("Mounted {} as the root file system.", file_system.name());
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_mount(path: &str, file_system: Arc<dyn FileSystem>) -> Result<(), FsError> {
+    let mountpoint = path::lightsaber_kernel_lookup(path, true)?;
+
+    if mountpoint.inode_type()? != InodeType::Directory {
+        return Err(FsError::NotDirectory);
+    }
+
+    let mount = Arc::new(Mount {
+        path: mountpoint.path(),
+        file_system: file_system.clone(),
+        root: Dentry::new_root(file_system.root(), Some(mountpoint.clone())),
+        mountpoint: Some(mountpoint.clone())
+    });
+
+    let mut mounts = MOUNTS.write();
+
+    if mountpoint.mounted().is_some() {
+        return Err(FsError::Busy);
+    }
+
+    mountpoint.set_mounted(Some(mount.clone()));
+    mounts.push(mount.clone());
+
+    log::info!("Mounted {} on {}.", file_system.name(), mount.path());
+
+    Ok(())
+}
+
+// Creates `path` and any directory missing above it, for file systems the kernel mounts on its own.
+pub fn lightsaber_kernel_create_mountpoint(path: &str) -> Result<(), FsError> {
+    let ends = path
+        .match_indices('/')
+        .map(|(index, _)| index)
+        .filter(|index| *index != 0)
+        .chain(iter::once(path.len()));
+
+    for end in ends {
+        match path::lightsaber_kernel_lookup(&path[..end], true) {
+            Err(FsError::NotFound) => {
+                let (parent, name) = path::lightsaber_kernel_lookup_parent(&path[..end])?;
+                parent.inode().create(&name, InodeType::Directory, 0o755)?;
+            }
+            result => {
+                result?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_unmount(path: &str) -> Result<(), FsError> {
+    let root = path::lightsaber_kernel_lookup(path, true)?;
+    let mut mounts = MOUNTS.write();
+
+    let index = mounts
+        .iter()
+        .position(|mount| Arc::ptr_eq(&mount.root, &root) && mount.mountpoint.is_some())
+        .ok_or(FsError::InvalidArgument)?;
+
+    // Anything mounted below this file system would become unreachable.
+    let prefix = format!("{}/", mounts[index].path);
+    let nested = mounts.iter().any(|mount| mount.path.starts_with(prefix.as_str()));
+
+    if nested {
+        return Err(FsError::Busy);
+    }
+
+    let mount = mounts.remove(index);
+
+    if let Some(mountpoint) = &mount.mountpoint {
+        mountpoint.set_mounted(None);
+    }
+
+    drop(mounts);
+    mount.file_system.sync()?;
+
+    log::info!("Unmounted {} from {}.", mount.file_system.name(), mount.path());
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_mounts() -> Vec<Arc<Mount>> {
+    MOUNTS.read().clone()
+}
+
+pub fn lightsaber_kernel_sync_all() -> Result<(), FsError> {
+    for mount in lightsaber_kernel_mounts() {
+        mount.file_system.sync()?;
+    }
+
+    Ok(())
+}
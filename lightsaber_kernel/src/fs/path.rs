@@ -0,0 +1,246 @@
+use alloc::{
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+
+use crate::fs::{
+    mount,
+    Dentry,
+    FsError,
+    InodeType
+};
+
+pub const MAXIMUM_PATH_LENGTH: usize = 4096;
+pub const MAXIMUM_NAME_LENGTH: usize = 255;
+
+const MAXIMUM_SYMLINK_DEPTH: usize = 40;
+
+pub fn lightsaber_kernel_lookup(path: &str, follow: bool) -> Result<Arc<Dentry>, FsError> {
+    lightsaber_kernel_lookup_at(&mount::lightsaber_kernel_root()?, path, follow)
+}
+
+pub fn lightsaber_kernel_lookup_at(start: &Arc<Dentry>, path: &str, follow: bool) -> Result<Arc<Dentry>, FsError> {
+    let mut depth = 0;
+
+    lightsaber_kernel_walk(start, path, follow, &mut depth)
+}
+
+// Splits `path` into the directory that would contain it and the final component, for operations that create or remove names.
+pub fn lightsaber_kernel_lookup_parent(path: &str) -> Result<(Arc<Dentry>, String), FsError> {
+    let trimmed = path.trim_end_matches('/');
+
+    if trimmed.is_empty() {
+        return Err(FsError::InvalidArgument);
+    }
+
+    let (directory, name) = match trimmed.rfind('/') {
+        Some(0) => ("/", &trimmed[1..]),
+        Some(index) => (&trimmed[..index], &trimmed[index + 1..]),
+        None => ("", trimmed)
+    };
+
+    if name == "." || name == ".." {
+        return Err(FsError::InvalidArgument);
+    }
+
+    if name.len() > MAXIMUM_NAME_LENGTH {
+        return Err(FsError::NameTooLong);
+    }
+
+    let parent = lightsaber_kernel_lookup(directory, true)?;
+
+    if parent.inode_type()? != InodeType::Directory {
+        return Err(FsError::NotDirectory);
+    }
+
+    Ok((parent, name.to_string()))
+}
+
+fn lightsaber_kernel_walk(start: &Arc<Dentry>, path: &str, follow: bool, depth: &mut usize) -> Result<Arc<Dentry>, FsError> {
+    if path.len() > MAXIMUM_PATH_LENGTH {
+        return Err(FsError::NameTooLong);
+    }
+
+    let mut current = match path.starts_with('/') {
+        true => mount::lightsaber_kernel_root()?,
+        false => start.follow_mounts()
+    };
+
+    let components = path
+        .split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<&str>>();
+
+    for (index, component) in components.iter().enumerate() {
+        let last = index + 1 == components.len();
+
+        if current.inode_type()? != InodeType::Directory {
+            return Err(FsError::NotDirectory);
+        }
+
+        match *component {
+            "." => { }
+            ".." => current = current.parent(),
+            name => {
+                if name.len() > MAXIMUM_NAME_LENGTH {
+                    return Err(FsError::NameTooLong);
+                }
+
+                let next = current.lookup(name)?.follow_mounts();
+
+                if next.inode_type()? == InodeType::Symlink && (follow || !last) {
+                    *depth += 1;
+
+                    if *depth > MAXIMUM_SYMLINK_DEPTH {
+                        return Err(FsError::TooManyLinks);
+                    }
+
+                    // Relative targets are resolved from the directory holding the link.
+                    let target = next.inode().read_link()?;
+                    current = lightsaber_kernel_walk(&current, &target, true, depth)?;
+                }
+                else {
+                    current = next;
+                }
+            }
+        }
+    }
+
+    if path.ends_with('/') && current.inode_type()? != InodeType::Directory {
+        return Err(FsError::NotDirectory);
+    }
+
+    Ok(current)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    use crate::fs::{
+        tmpfs::{
+            TmpfsFileSystem,
+            TmpfsOptions
+        },
+        FileSystem
+    };
+
+    // A tmpfs of its own, not mounted anywhere, so that relative walks from its root stay inside it.
+    fn lightsaber_kernel_test_tree() -> Arc<Dentry> {
+        let file_system = TmpfsFileSystem::new(TmpfsOptions::parse("").unwrap());
+        let root = Dentry::new_root(file_system.root(), None);
+
+        let directory = root.inode().create("directory", InodeType::Directory, 0o755).unwrap();
+        directory.create("file", InodeType::File, 0o644).unwrap();
+        directory.symlink("link", "file").unwrap();
+        directory.symlink("parent_link", "../directory/file").unwrap();
+        root.inode().symlink("loop", "loop").unwrap();
+        root.inode().symlink("ping", "pong").unwrap();
+        root.inode().symlink("pong", "./ping").unwrap();
+
+        root
+    }
+
+    fn lightsaber_kernel_test_identity(dentry: &Arc<Dentry>) -> (u64, u64) {
+        let metadata = dentry.inode().metadata().unwrap();
+
+        (metadata.device, metadata.inode)
+    }
+
+    #[test_case]
+    fn path_resolves_dot_and_dot_dot() {
+        let root = lightsaber_kernel_test_tree();
+        let directory = lightsaber_kernel_lookup_at(&root, "directory", true).unwrap();
+
+        for path in &["directory/.", "./directory/", "directory/./.././directory", "directory/file/.."] {
+            let resolved = lightsaber_kernel_lookup_at(&root, path, true);
+
+            match *path {
+                // `..` cannot back out of a file.
+                "directory/file/.." => assert_eq!(resolved.err(), Some(FsError::NotDirectory)),
+                _ => assert_eq!(lightsaber_kernel_test_identity(&resolved.unwrap()), lightsaber_kernel_test_identity(&directory))
+            }
+        }
+
+        // `..` at the root stays at the root.
+        let above = lightsaber_kernel_lookup_at(&root, "../../..", true).unwrap();
+        assert_eq!(lightsaber_kernel_test_identity(&above), lightsaber_kernel_test_identity(&root));
+    }
+
+    #[test_case]
+    fn path_follows_relative_symlinks_from_their_directory() {
+        let root = lightsaber_kernel_test_tree();
+        let file = lightsaber_kernel_lookup_at(&root, "directory/file", true).unwrap();
+
+        let link = lightsaber_kernel_lookup_at(&root, "directory/link", true).unwrap();
+        let parent_link = lightsaber_kernel_lookup_at(&root, "directory/parent_link", true).unwrap();
+        let unfollowed = lightsaber_kernel_lookup_at(&root, "directory/link", false).unwrap();
+
+        assert_eq!(lightsaber_kernel_test_identity(&link), lightsaber_kernel_test_identity(&file));
+        assert_eq!(lightsaber_kernel_test_identity(&parent_link), lightsaber_kernel_test_identity(&file));
+        assert_eq!(unfollowed.inode_type(), Ok(InodeType::Symlink));
+    }
+
+    #[test_case]
+    fn path_gives_up_on_symlink_loops() {
+        let root = lightsaber_kernel_test_tree();
+
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "loop", true).err(), Some(FsError::TooManyLinks));
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "ping", true).err(), Some(FsError::TooManyLinks));
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "ping/file", false).err(), Some(FsError::TooManyLinks));
+
+        // Without following, the link itself is found.
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "loop", false).and_then(|dentry| dentry.inode_type()), Ok(InodeType::Symlink));
+    }
+
+    #[test_case]
+    fn path_rejects_a_trailing_slash_on_a_file() {
+        let root = lightsaber_kernel_test_tree();
+
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "directory/file/", true).err(), Some(FsError::NotDirectory));
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "directory/link/", true).err(), Some(FsError::NotDirectory));
+        assert_eq!(lightsaber_kernel_lookup_at(&root, "directory/file/name", true).err(), Some(FsError::NotDirectory));
+        assert!(lightsaber_kernel_lookup_at(&root, "directory/", true).is_ok());
+    }
+
+    #[test_case]
+    fn path_climbs_out_of_a_mount_with_dot_dot() {
+        // The mount table is global, so this borrows a writable directory from the running system.
+        let scratch = lightsaber_kernel_lookup("/tmp", true).or_else(|_| mount::lightsaber_kernel_root()).unwrap();
+        scratch.inode().create("path_test_mountpoint", InodeType::Directory, 0o755).unwrap();
+
+        let mountpoint = format!("{}/path_test_mountpoint", scratch.path().trim_end_matches('/'));
+        let file_system = TmpfsFileSystem::new(TmpfsOptions::parse("").unwrap());
+        file_system.root().create("inside", InodeType::File, 0o644).unwrap();
+
+        mount::lightsaber_kernel_mount(&mountpoint, Arc::new(file_system)).unwrap();
+
+        let inside = lightsaber_kernel_lookup(&format!("{}/inside", mountpoint), true);
+        let mounted_root = lightsaber_kernel_lookup(&mountpoint, true).unwrap();
+        let above = lightsaber_kernel_lookup_at(&mounted_root, "..", true).unwrap();
+        let (parent, name) = lightsaber_kernel_lookup_parent(&format!("{}/inside/", mountpoint)).unwrap();
+
+        mount::lightsaber_kernel_unmount(&mountpoint).unwrap();
+        scratch.inode().unlink("path_test_mountpoint").unwrap();
+        scratch.forget("path_test_mountpoint");
+
+        assert!(inside.is_ok());
+        assert_eq!(lightsaber_kernel_test_identity(&above), lightsaber_kernel_test_identity(&scratch));
+        assert_eq!(lightsaber_kernel_test_identity(&parent), lightsaber_kernel_test_identity(&mounted_root));
+        assert_eq!(name, "inside");
+    }
+
+    #[test_case]
+    fn path_parent_lookup_rejects_unnamed_paths() {
+        assert_eq!(lightsaber_kernel_lookup_parent("/").err(), Some(FsError::InvalidArgument));
+        assert_eq!(lightsaber_kernel_lookup_parent("/tmp/..").err(), Some(FsError::InvalidArgument));
+        assert_eq!(lightsaber_kernel_lookup_parent("/tmp/.").err(), Some(FsError::InvalidArgument));
+        assert_eq!(lightsaber_kernel_lookup_parent(&format!("/{}", "x".repeat(MAXIMUM_NAME_LENGTH + 1))).err(), Some(FsError::NameTooLong));
+    }
+}
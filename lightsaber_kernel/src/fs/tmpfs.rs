@@ -0,0 +1,596 @@
+use alloc::{
+    boxed::Box,
+    collections::{
+        btree_map::Entry,
+        BTreeMap
+    },
+    string::{
+        String,
+        ToString
+    },
+    sync::{
+        Arc,
+        Weak
+    }
+};
+
+use core::sync::atomic::{
+    AtomicU64,
+    Ordering
+};
+
+use crate::{
+    fs::{
+        self,
+        inode::MODE_PERMISSION_MASK,
+        mount,
+        DirectoryEntry,
+        FileSystem,
+        FsError,
+        FsUsage,
+        Inode,
+        InodeType,
+        Metadata
+    },
+    memory::frame,
+    sync::{
+        Mutex,
+        Spinlock
+    },
+    time
+};
+
+const TMPFS_PAGE_SIZE: u64 = 4096;
+const TMPFS_BLOCK_SIZE: u64 = 512;
+
+const DEFAULT_ROOT_MODE: u16 = 0o1777;
+const MAXIMUM_FILE_SIZE: u64 = 1 << 44;
+
+type TmpfsPage = Box<[u8; TMPFS_PAGE_SIZE as usize]>;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct TmpfsOptions {
+    pub size_limit: u64,
+    pub root_mode: u16
+}
+
+impl TmpfsOptions {
+    // Accepts a comma separated list such as `size=16M,mode=755`; the size defaults to half of physical memory.
+    pub fn parse(options: &str) -> Result<Self, FsError> {
+        let total_memory = frame::lightsaber_kernel_frame_statistics().total_frames * TMPFS_PAGE_SIZE;
+
+        let mut parsed = Self {
+            size_limit: total_memory / 2,
+            root_mode: DEFAULT_ROOT_MODE
+        };
+
+        for option in options.split(',').filter(|option| !option.is_empty()) {
+            let (key, value) = match option.find('=') {
+                Some(index) => (&option[..index], &option[index + 1..]),
+                None => return Err(FsError::InvalidArgument)
+            };
+
+            match key {
+                "size" => parsed.size_limit = Self::parse_size(value, total_memory)?,
+                "mode" => parsed.root_mode = u16::from_str_radix(value, 8).map_err(|_| FsError::InvalidArgument)? & MODE_PERMISSION_MASK,
+                _ => return Err(FsError::InvalidArgument)
+            }
+        }
+
+        Ok(parsed)
+    }
+
+    fn parse_size(value: &str, total_memory: u64) -> Result<u64, FsError> {
+        if let Some(percentage) = value.strip_suffix('%') {
+            let percentage = percentage.parse::<u64>().map_err(|_| FsError::InvalidArgument)?;
+
+            return Ok(total_memory / 100 * percentage.min(100));
+        }
+
+        let (digits, multiplier) = match value.chars().last() {
+            Some('k') | Some('K') => (&value[..value.len() - 1], 1 << 10),
+            Some('m') | Some('M') => (&value[..value.len() - 1], 1 << 20),
+            Some('g') | Some('G') => (&value[..value.len() - 1], 1 << 30),
+            _ => (value, 1)
+        };
+
+        digits
+            .parse::<u64>()
+            .ok()
+            .and_then(|size| size.checked_mul(multiplier))
+            .ok_or(FsError::InvalidArgument)
+    }
+}
+
+struct TmpfsShared {
+    device: u64,
+    size_limit: u64,
+    used: AtomicU64,
+    next_inode: AtomicU64,
+    inodes: Spinlock<BTreeMap<u64, Weak<TmpfsInode>>>
+}
+
+impl TmpfsShared {
+    fn reserve_page(&self) -> Result<(), FsError> {
+        let mut used = self.used.load(Ordering::Relaxed);
+
+        loop {
+            if used + TMPFS_PAGE_SIZE > self.size_limit {
+                return Err(FsError::NoSpace);
+            }
+
+            match self.used.compare_exchange_weak(used, used + TMPFS_PAGE_SIZE, Ordering::AcqRel, Ordering::Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(current) => used = current
+            }
+        }
+    }
+
+    fn release_pages(&self, count: u64) {
+        self.used.fetch_sub(count * TMPFS_PAGE_SIZE, Ordering::AcqRel);
+    }
+}
+
+#[derive(Default)]
+struct TmpfsFileData {
+    size: u64,
+    pages: BTreeMap<u64, TmpfsPage>
+}
+
+enum TmpfsContent {
+    File(Mutex<TmpfsFileData>),
+
+    Directory(Mutex<BTreeMap<String, Arc<TmpfsInode>>>),
+
+    Symlink(String)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TmpfsAttributes {
+    mode: u16,
+    links: u32,
+    accessed: u64,
+    modified: u64,
+    changed: u64
+}
+
+pub struct TmpfsInode {
+    inode: u64,
+    shared: Arc<TmpfsShared>,
+    attributes: Spinlock<TmpfsAttributes>,
+    content: TmpfsContent
+}
+
+fn lightsaber_kernel_tmpfs_now() -> u64 {
+    time::lightsaber_kernel_uptime_milliseconds() / 1000
+}
+
+impl TmpfsInode {
+    fn new(shared: &Arc<TmpfsShared>, mode: u16, content: TmpfsContent) -> Arc<Self> {
+        let now = lightsaber_kernel_tmpfs_now();
+
+        let links = match content {
+            TmpfsContent::Directory(_) => 2,
+            _ => 1
+        };
+
+        let inode = Arc::new(Self {
+            inode: shared.next_inode.fetch_add(1, Ordering::Relaxed),
+            shared: shared.clone(),
+            attributes: Spinlock::named("tmpfs_attributes", TmpfsAttributes {
+                mode: mode & MODE_PERMISSION_MASK,
+                links,
+                accessed: now,
+                modified: now,
+                changed: now
+            }),
+            content
+        });
+
+        shared.inodes.lock().insert(inode.inode, Arc::downgrade(&inode));
+
+        inode
+    }
+
+    fn inode_type(&self) -> InodeType {
+        match self.content {
+            TmpfsContent::File(_) => InodeType::File,
+            TmpfsContent::Directory(_) => InodeType::Directory,
+            TmpfsContent::Symlink(_) => InodeType::Symlink
+        }
+    }
+
+    fn children(&self) -> Result<&Mutex<BTreeMap<String, Arc<TmpfsInode>>>, FsError> {
+        match &self.content {
+            TmpfsContent::Directory(children) => Ok(children),
+            _ => Err(FsError::NotDirectory)
+        }
+    }
+
+    fn file_data(&self) -> Result<&Mutex<TmpfsFileData>, FsError> {
+        match &self.content {
+            TmpfsContent::File(data) => Ok(data),
+            TmpfsContent::Directory(_) => Err(FsError::IsDirectory),
+            TmpfsContent::Symlink(_) => Err(FsError::InvalidArgument)
+        }
+    }
+
+    fn touch(&self, modified: bool) {
+        let now = lightsaber_kernel_tmpfs_now();
+        let mut attributes = self.attributes.lock();
+
+        attributes.accessed = now;
+
+        if modified {
+            attributes.modified = now;
+            attributes.changed = now;
+        }
+    }
+
+    fn adjust_links(&self, delta: i32) {
+        let mut attributes = self.attributes.lock();
+
+        attributes.links = (attributes.links as i32 + delta).max(0) as u32;
+        attributes.changed = lightsaber_kernel_tmpfs_now();
+    }
+
+    fn check_name(name: &str) -> Result<(), FsError> {
+        match name {
+            "" | "." | ".." => Err(FsError::InvalidArgument),
+            _ if name.contains('/') => Err(FsError::InvalidArgument),
+            _ if name.len() > fs::path::MAXIMUM_NAME_LENGTH => Err(FsError::NameTooLong),
+            _ => Ok(())
+        }
+    }
+
+    fn insert_child(&self, name: &str, child: Arc<TmpfsInode>) -> Result<(), FsError> {
+        Self::check_name(name)?;
+
+        let mut children = self.children()?.lock();
+
+        if children.contains_key(name) {
+            return Err(FsError::AlreadyExists);
+        }
+
+        if child.inode_type() == InodeType::Directory {
+            self.adjust_links(1);
+        }
+
+        children.insert(name.to_string(), child);
+        drop(children);
+
+        self.touch(true);
+
+        Ok(())
+    }
+}
+
+impl Inode for TmpfsInode {
+    fn metadata(&self) -> Result<Metadata, FsError> {
+        let attributes = *self.attributes.lock();
+
+        let (size, pages) = match &self.content {
+            TmpfsContent::File(data) => {
+                let data = data.lock();
+
+                (data.size, data.pages.len() as u64)
+            }
+            TmpfsContent::Directory(children) => (children.lock().len() as u64, 0),
+            TmpfsContent::Symlink(target) => (target.len() as u64, 0)
+        };
+
+        Ok(Metadata {
+            device: self.shared.device,
+            inode: self.inode,
+            inode_type: self.inode_type(),
+            mode: attributes.mode,
+            links: attributes.links,
+            uid: 0,
+            gid: 0,
+            size,
+            block_size: TMPFS_PAGE_SIZE,
+            blocks: pages * (TMPFS_PAGE_SIZE / TMPFS_BLOCK_SIZE),
+            accessed: attributes.accessed,
+            modified: attributes.modified,
+            changed: attributes.changed
+        })
+    }
+
+    fn read_at(&self, offset: u64, buffer: &mut [u8]) -> Result<usize, FsError> {
+        let data = self.file_data()?.lock();
+
+        if offset >= data.size {
+            return Ok(0);
+        }
+
+        let length = (data.size - offset).min(buffer.len() as u64) as usize;
+        let mut done = 0;
+
+        // Pages that were never written are holes and read back as zeroes.
+        while done < length {
+            let position = offset + done as u64;
+            let page_offset = (position % TMPFS_PAGE_SIZE) as usize;
+            let chunk = (TMPFS_PAGE_SIZE as usize - page_offset).min(length - done);
+
+            match data.pages.get(&(position / TMPFS_PAGE_SIZE)) {
+                Some(page) => buffer[done..done + chunk].copy_from_slice(&page[page_offset..page_offset + chunk]),
+                None => buffer[done..done + chunk].iter_mut().for_each(|byte| *byte = 0)
+            }
+
+            done += chunk;
+        }
+
+        drop(data);
+        self.touch(false);
+
+        Ok(length)
+    }
+
+    fn write_at(&self, offset: u64, buffer: &[u8]) -> Result<usize, FsError> {
+        let end = offset
+            .checked_add(buffer.len() as u64)
+            .filter(|end| *end <= MAXIMUM_FILE_SIZE)
+            .ok_or(FsError::FileTooLarge)?;
+
+        let mut data = self.file_data()?.lock();
+        let mut done = 0;
+
+        while done < buffer.len() {
+            let position = offset + done as u64;
+            let page_offset = (position % TMPFS_PAGE_SIZE) as usize;
+            let chunk = (TMPFS_PAGE_SIZE as usize - page_offset).min(buffer.len() - done);
+            let index = position / TMPFS_PAGE_SIZE;
+
+            let page = match data.pages.entry(index) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    // A partial write is still reported, as long as something made it in.
+                    if let Err(error) = self.shared.reserve_page() {
+                        if done == 0 {
+                            return Err(error);
+                        }
+
+                        break;
+                    }
+
+                    entry.insert(Box::new([0; TMPFS_PAGE_SIZE as usize]))
+                }
+            };
+
+            page[page_offset..page_offset + chunk].copy_from_slice(&buffer[done..done + chunk]);
+
+            done += chunk;
+        }
+
+        data.size = data.size.max(end.min(offset + done as u64));
+        drop(data);
+
+        self.touch(true);
+
+        Ok(done)
+    }
+
+    fn truncate(&self, size: u64) -> Result<(), FsError> {
+        if size > MAXIMUM_FILE_SIZE {
+            return Err(FsError::FileTooLarge);
+        }
+
+        let mut data = self.file_data()?.lock();
+
+        if size < data.size {
+            let first_removed = (size + TMPFS_PAGE_SIZE - 1) / TMPFS_PAGE_SIZE;
+            let removed = data.pages.split_off(&first_removed);
+
+            self.shared.release_pages(removed.len() as u64);
+
+            // Bytes past the new end must read back as zeroes if the file grows again.
+            if size % TMPFS_PAGE_SIZE != 0 {
+                if let Some(page) = data.pages.get_mut(&(size / TMPFS_PAGE_SIZE)) {
+                    page[(size % TMPFS_PAGE_SIZE) as usize..].iter_mut().for_each(|byte| *byte = 0);
+                }
+            }
+        }
+
+        data.size = size;
+        drop(data);
+
+        self.touch(true);
+
+        Ok(())
+    }
+
+    fn lookup(&self, name: &str) -> Result<Arc<dyn Inode>, FsError> {
+        let child = self.children()?
+            .lock()
+            .get(name)
+            .cloned()
+            .ok_or(FsError::NotFound)?;
+
+        Ok(child)
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType, mode: u16) -> Result<Arc<dyn Inode>, FsError> {
+        let content = match inode_type {
+            InodeType::File => TmpfsContent::File(Mutex::named("tmpfs_file", TmpfsFileData::default())),
+            InodeType::Directory => TmpfsContent::Directory(Mutex::named("tmpfs_directory", BTreeMap::new())),
+            _ => return Err(FsError::NotSupported)
+        };
+
+        self.children()?;
+
+        let child = TmpfsInode::new(&self.shared, mode, content);
+        self.insert_child(name, child.clone())?;
+
+        Ok(child)
+    }
+
+    fn link(&self, name: &str, target: &Arc<dyn Inode>) -> Result<(), FsError> {
+        let metadata = target.metadata()?;
+
+        if metadata.device != self.shared.device {
+            return Err(FsError::CrossDevice);
+        }
+
+        if metadata.is_directory() {
+            return Err(FsError::NotSupported);
+        }
+
+        let target = self.shared.inodes
+            .lock()
+            .get(&metadata.inode)
+            .and_then(Weak::upgrade)
+            .ok_or(FsError::NotFound)?;
+
+        self.insert_child(name, target.clone())?;
+        target.adjust_links(1);
+
+        Ok(())
+    }
+
+    fn unlink(&self, name: &str) -> Result<(), FsError> {
+        Self::check_name(name)?;
+
+        let mut children = self.children()?.lock();
+        let child = children.get(name).cloned().ok_or(FsError::NotFound)?;
+
+        if let TmpfsContent::Directory(grandchildren) = &child.content {
+            if !grandchildren.lock().is_empty() {
+                return Err(FsError::NotEmpty);
+            }
+
+            self.adjust_links(-1);
+            child.adjust_links(-1);
+        }
+
+        children.remove(name);
+        drop(children);
+
+        child.adjust_links(-1);
+        self.touch(true);
+
+        Ok(())
+    }
+
+    fn symlink(&self, name: &str, target: &str) -> Result<Arc<dyn Inode>, FsError> {
+        if target.is_empty() || target.len() > fs::path::MAXIMUM_PATH_LENGTH {
+            return Err(FsError::InvalidArgument);
+        }
+
+        self.children()?;
+
+        let child = TmpfsInode::new(&self.shared, 0o777, TmpfsContent::Symlink(target.to_string()));
+        self.insert_child(name, child.clone())?;
+
+        Ok(child)
+    }
+
+    fn read_link(&self) -> Result<String, FsError> {
+        match &self.content {
+            TmpfsContent::Symlink(target) => Ok(target.clone()),
+            _ => Err(FsError::InvalidArgument)
+        }
+    }
+
+    fn read_directory(&self, index: usize) -> Result<Option<DirectoryEntry>, FsError> {
+        let children = self.children()?.lock();
+
+        Ok(children.iter().nth(index).map(|(name, child)| DirectoryEntry {
+            name: name.clone(),
+            inode: child.inode,
+            inode_type: child.inode_type()
+        }))
+    }
+}
+
+impl Drop for TmpfsInode {
+    fn drop(&mut self) {
+        if let TmpfsContent::File(data) = &self.content {
+            let pages = data.lock().pages.len() as u64;
+            self.shared.release_pages(pages);
+        }
+
+        self.shared.inodes.lock().remove(&self.inode);
+    }
+}
+
+pub struct TmpfsFileSystem {
+    shared: Arc<TmpfsShared>,
+    root: Arc<TmpfsInode>
+}
+
+impl TmpfsFileSystem {
+    pub fn new(options: TmpfsOptions) -> Self {
+        let shared = Arc::new(TmpfsShared {
+            device: fs::lightsaber_kernel_allocate_device_number(),
+            size_limit: options.size_limit,
+            used: AtomicU64::new(0),
+            next_inode: AtomicU64::new(1),
+            inodes: Spinlock::named("tmpfs_inodes", BTreeMap::new())
+        });
+
+        let root = TmpfsInode::new(&shared, options.root_mode, TmpfsContent::Directory(Mutex::named("tmpfs_directory", BTreeMap::new())));
+
+        Self {
+            shared,
+            root
+        }
+    }
+}
+
+impl FileSystem for TmpfsFileSystem {
+    fn name(&self) -> &'static str {
+        "tmpfs"
+    }
+
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+
+    fn usage(&self) -> Result<FsUsage, FsError> {
+        let used = self.shared.used.load(Ordering::Relaxed);
+
+        Ok(FsUsage {
+            size: self.shared.size_limit,
+            free: self.shared.size_limit.saturating_sub(used)
+        })
+    }
+}
+
+pub fn lightsaber_kernel_initialize_tmpfs() {
+    let options = TmpfsOptions::parse("").expect("The default tmpfs options are invalid.");
+
+    // Without an initial ramdisk early user space still needs somewhere to live.
+    if mount::lightsaber_kernel_root().is_err() {
+        if let Err(error) = mount::lightsaber_kernel_mount_root(Arc::new(TmpfsFileSystem::new(options))) {
+            log::error!("Failed to mount tmpfs as the root file system: {}", error);
+        }
+
+        return;
+    }
+
+    if let Err(error) = mount::lightsaber_kernel_mount("/tmp", Arc::new(TmpfsFileSystem::new(options))) {
+        log::warn!("Failed to mount tmpfs on /tmp: {}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn tmpfs_options_parse_sizes_and_modes() {
+        let options = TmpfsOptions::parse("size=16M,mode=755").unwrap();
+
+        assert_eq!(options.size_limit, 16 << 20);
+        assert_eq!(options.root_mode, 0o755);
+        assert_eq!(TmpfsOptions::parse("size=4k").unwrap().size_limit, 4 << 10);
+    }
+
+    #[test_case]
+    fn tmpfs_options_reject_malformed_input() {
+        assert_eq!(TmpfsOptions::parse("size"), Err(FsError::InvalidArgument));
+        assert_eq!(TmpfsOptions::parse("size=lots"), Err(FsError::InvalidArgument));
+        assert_eq!(TmpfsOptions::parse("colour=blue"), Err(FsError::InvalidArgument));
+        assert_eq!(TmpfsOptions::parse("mode=999"), Err(FsError::InvalidArgument));
+    }
+}
@@ -0,0 +1,709 @@
+pub mod packet;
+
+use core::{
+    fmt::{
+        self,
+        Write
+    },
+    ptr,
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use x86_64::{
+    registers::control::{
+        Cr0,
+        Cr0Flags
+    },
+    VirtAddr
+};
+
+use crate::{
+    architecture::interrupts::trap::{
+        self,
+        TrapFrame,
+        RFLAGS_TRAP_FLAG
+    },
+    drivers::serial,
+    memory::paging,
+    scheduler::{
+        self,
+        thread::ThreadState
+    },
+    sync::Spinlock
+};
+
+use packet::{
+    Packet,
+    PACKET_CAPACITY
+};
+
+const GDB_SIGNAL_INTERRUPT: u8 = 2;
+const GDB_SIGNAL_TRAP: u8 = 5;
+
+const GDB_BREAKPOINT_CAPACITY: usize = 64;
+const GDB_BREAKPOINT_SOFTWARE: u8 = b'0';
+const INSTRUCTION_BREAKPOINT: u8 = 0xCC;
+
+// Memory is sent as two hex digits a byte, and the reply has to fit in one packet.
+const GDB_MEMORY_CHUNK: usize = PACKET_CAPACITY / 2 - 8;
+
+// GDB's numbering for amd64: the sixteen general purpose registers, rip, then eflags and the segment selectors.
+const GDB_REGISTER_COUNT: usize = 24;
+const GDB_REGISTER_RIP: usize = 16;
+const GDB_REGISTER_EFLAGS: usize = 17;
+
+static GDB_ATTACHED: AtomicBool = AtomicBool::new(false);
+static GDB_INTERRUPT_REQUESTED: AtomicBool = AtomicBool::new(false);
+static GDB_STUB: Spinlock<GdbStub> = Spinlock::named("gdb_stub", GdbStub::new());
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum GdbError {
+    NoSerialLine,
+
+    AlreadyAttached
+}
+
+impl fmt::Display for GdbError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSerialLine => write!(formatter, "There is no serial line to debug over."),
+            Self::AlreadyAttached => write!(formatter, "A debugger is already attached.")
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Resume {
+    Continue,
+
+    Step
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Action {
+    Reply,
+
+    Resume(Resume),
+
+    Detach
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Breakpoint {
+    address: u64,
+    original: u8
+}
+
+struct GdbState {
+    breakpoints: [Option<Breakpoint>; GDB_BREAKPOINT_CAPACITY],
+    // Set while single-stepping for GDB; continuing from a breakpoint steps over it first.
+    stepping: Option<Resume>,
+    // The thread `g` and `G` refer to, as GDB numbers them; the stopped thread when unset.
+    register_thread: Option<u64>,
+    signal: u8
+}
+
+struct GdbStub {
+    state: GdbState,
+    input: Packet,
+    output: Packet
+}
+
+impl GdbStub {
+    const fn new() -> Self {
+        Self {
+            state: GdbState {
+                breakpoints: [None; GDB_BREAKPOINT_CAPACITY],
+                stepping: None,
+                register_thread: None,
+                signal: GDB_SIGNAL_TRAP
+            },
+            input: Packet::new(),
+            output: Packet::new()
+        }
+    }
+}
+
+// GDB counts threads from one, where the scheduler starts at zero.
+#[inline]
+fn lightsaber_kernel_gdb_thread_id(id: u64) -> u64 {
+    id + 1
+}
+
+fn lightsaber_kernel_segment_selectors() -> [u64; 4] {
+    let (ds, es, fs, gs): (u16, u16, u16, u16);
+
+    unsafe {
+        asm!("
+            mov {0:x}, ds
+            mov {1:x}, es
+            mov {2:x}, fs
+            mov {3:x}, gs
+            ",
+            out(reg) ds,
+            out(reg) es,
+            out(reg) fs,
+            out(reg) gs,
+            options(nomem, nostack, preserves_flags)
+        );
+    }
+
+    [ds as u64, es as u64, fs as u64, gs as u64]
+}
+
+fn lightsaber_kernel_frame_registers(frame: &TrapFrame) -> [u64; GDB_REGISTER_COUNT] {
+    let [ds, es, fs, gs] = lightsaber_kernel_segment_selectors();
+
+    [
+        frame.rax, frame.rbx, frame.rcx, frame.rdx, frame.rsi, frame.rdi, frame.rbp, frame.stack_pointer,
+        frame.r8, frame.r9, frame.r10, frame.r11, frame.r12, frame.r13, frame.r14, frame.r15,
+        frame.instruction_pointer, frame.cpu_flags, frame.code_segment, frame.stack_segment,
+        ds, es, fs, gs
+    ]
+}
+
+// Segment registers are left alone; changing them under the kernel would only make it fault.
+fn lightsaber_kernel_set_frame_register(frame: &mut TrapFrame, register: usize, value: u64) {
+    match register {
+        0 => frame.rax = value,
+        1 => frame.rbx = value,
+        2 => frame.rcx = value,
+        3 => frame.rdx = value,
+        4 => frame.rsi = value,
+        5 => frame.rdi = value,
+        6 => frame.rbp = value,
+        7 => frame.stack_pointer = value,
+        8 => frame.r8 = value,
+        9 => frame.r9 = value,
+        10 => frame.r10 = value,
+        11 => frame.r11 = value,
+        12 => frame.r12 = value,
+        13 => frame.r13 = value,
+        14 => frame.r14 = value,
+        15 => frame.r15 = value,
+        GDB_REGISTER_RIP => frame.instruction_pointer = value,
+        GDB_REGISTER_EFLAGS => frame.cpu_flags = value,
+        _ => { }
+    }
+}
+
+#[inline]
+fn lightsaber_kernel_register_size(register: usize) -> usize {
+    match register {
+        0..=GDB_REGISTER_RIP => 8,
+        _ => 4
+    }
+}
+
+fn lightsaber_kernel_current_thread_id() -> Option<u64> {
+    let mut current = None;
+
+    scheduler::lightsaber_kernel_try_for_each_thread(|thread, is_current| {
+        if is_current {
+            current = Some(lightsaber_kernel_gdb_thread_id(thread.id().0));
+        }
+    });
+
+    current
+}
+
+// A thread that is switched out only has the registers the context switch saved; the rest read as zero.
+fn lightsaber_kernel_thread_registers(id: u64, frame: &TrapFrame) -> Option<[u64; GDB_REGISTER_COUNT]> {
+    let mut registers = None;
+
+    scheduler::lightsaber_kernel_try_for_each_thread(|thread, is_current| {
+        if lightsaber_kernel_gdb_thread_id(thread.id().0) != id {
+            return;
+        }
+
+        registers = match is_current {
+            true => Some(lightsaber_kernel_frame_registers(frame)),
+            false => thread.saved_context().map(|(context, stack_pointer)| {
+                let mut registers = [0; GDB_REGISTER_COUNT];
+
+                registers[1] = context.rbx;
+                registers[6] = context.rbp;
+                registers[7] = stack_pointer;
+                registers[12] = context.r12;
+                registers[13] = context.r13;
+                registers[14] = context.r14;
+                registers[15] = context.r15;
+                registers[GDB_REGISTER_RIP] = context.return_address;
+                registers[GDB_REGISTER_EFLAGS] = context.rflags;
+                registers[18] = frame.code_segment;
+                registers[19] = frame.stack_segment;
+
+                registers
+            })
+        };
+    });
+
+    registers
+}
+
+fn lightsaber_kernel_mapped(address: u64) -> bool {
+    VirtAddr::try_new(address)
+        .ok()
+        .and_then(paging::lightsaber_kernel_translate_flags)
+        .is_some()
+}
+
+// Stops at the first unmapped byte rather than faulting on it.
+fn lightsaber_kernel_read_memory(address: u64, buffer: &mut [u8]) -> usize {
+    for (offset, byte) in buffer.iter_mut().enumerate() {
+        let address = match address.checked_add(offset as u64) {
+            Some(address) if lightsaber_kernel_mapped(address) => address,
+            _ => return offset
+        };
+
+        *byte = unsafe {
+            ptr::read_volatile(address as *const u8)
+        };
+    }
+
+    buffer.len()
+}
+
+// Kernel text is mapped read-only, so write protection is lifted for as long as the write takes.
+fn lightsaber_kernel_write_memory(address: u64, bytes: &[u8]) -> bool {
+    let end = match address.checked_add(bytes.len() as u64) {
+        Some(end) => end,
+        None => return false
+    };
+
+    if !(address..end).all(lightsaber_kernel_mapped) {
+        return false;
+    }
+
+    let flags = Cr0::read();
+
+    unsafe {
+        Cr0::write(flags - Cr0Flags::WRITE_PROTECT);
+
+        for (offset, byte) in bytes.iter().enumerate() {
+            ptr::write_volatile((address + offset as u64) as *mut u8, *byte);
+        }
+
+        Cr0::write(flags);
+    }
+
+    true
+}
+
+impl GdbState {
+    fn breakpoint_at(&self, address: u64) -> bool {
+        self.breakpoints.iter().flatten().any(|breakpoint| breakpoint.address == address)
+    }
+
+    fn add_breakpoint(&mut self, address: u64) -> bool {
+        if self.breakpoint_at(address) {
+            return true;
+        }
+
+        let mut original = [0];
+
+        if lightsaber_kernel_read_memory(address, &mut original) != 1 {
+            return false;
+        }
+
+        match self.breakpoints.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(Breakpoint {
+                    address,
+                    original: original[0]
+                });
+
+                true
+            }
+            None => false
+        }
+    }
+
+    fn remove_breakpoint(&mut self, address: u64) {
+        for slot in self.breakpoints.iter_mut() {
+            if slot.map_or(false, |breakpoint| breakpoint.address == address) {
+                *slot = None;
+            }
+        }
+    }
+
+    // Breakpoints are only in memory while the kernel runs, so GDB never reads back its own int3s.
+    fn insert_breakpoints(&self) {
+        for breakpoint in self.breakpoints.iter().flatten() {
+            lightsaber_kernel_write_memory(breakpoint.address, &[INSTRUCTION_BREAKPOINT]);
+        }
+    }
+
+    fn restore_breakpoints(&self) {
+        for breakpoint in self.breakpoints.iter().flatten() {
+            lightsaber_kernel_write_memory(breakpoint.address, &[breakpoint.original]);
+        }
+    }
+
+    fn stop_reply(&self, output: &mut Packet) {
+        output.clear();
+
+        let _ = match lightsaber_kernel_current_thread_id() {
+            Some(thread) => write!(output, "T{:02x}thread:{:x};", self.signal, thread),
+            None => write!(output, "S{:02x}", self.signal)
+        };
+    }
+
+    fn query(&mut self, query: &[u8], output: &mut Packet) {
+        if query.starts_with(b"Supported") {
+            let _ = write!(output, "PacketSize={:x}", PACKET_CAPACITY);
+        }
+        else if query == b"Attached" {
+            output.push_bytes(b"1");
+        }
+        else if query == b"C" {
+            if let Some(thread) = lightsaber_kernel_current_thread_id() {
+                let _ = write!(output, "QC{:x}", thread);
+            }
+        }
+        else if query == b"fThreadInfo" {
+            let mut separator = b'm';
+
+            let listed = scheduler::lightsaber_kernel_try_for_each_thread(|thread, _| {
+                if thread.state() != ThreadState::Exited {
+                    output.push(separator);
+                    separator = b',';
+
+                    let _ = write!(output, "{:x}", lightsaber_kernel_gdb_thread_id(thread.id().0));
+                }
+            });
+
+            // Without the scheduler there is only the stopped thread to report.
+            if !listed || separator == b'm' {
+                output.clear();
+                output.push_bytes(b"m1");
+            }
+        }
+        else if query == b"sThreadInfo" {
+            output.push(b'l');
+        }
+        else if let Some(thread) = query.strip_prefix(b"ThreadExtraInfo,") {
+            let thread = packet::lightsaber_kernel_parse_hex(thread).unwrap_or(0);
+
+            scheduler::lightsaber_kernel_try_for_each_thread(|candidate, _| {
+                if lightsaber_kernel_gdb_thread_id(candidate.id().0) == thread {
+                    let mut description = Packet::new();
+                    let _ = write!(description, "{} ({:?})", candidate.name(), candidate.state());
+
+                    output.push_hex(description.as_bytes());
+                }
+            });
+        }
+        else if query.starts_with(b"Symbol") {
+            output.push_bytes(b"OK");
+        }
+    }
+
+    fn handle(&mut self, input: &[u8], output: &mut Packet, frame: &mut TrapFrame) -> Action {
+        output.clear();
+
+        let (command, arguments) = match input.split_first() {
+            Some((command, arguments)) => (*command, arguments),
+            None => return Action::Reply
+        };
+
+        match command {
+            b'?' => self.stop_reply(output),
+            b'g' => {
+                let registers = match self.register_thread {
+                    Some(thread) => lightsaber_kernel_thread_registers(thread, frame),
+                    None => Some(lightsaber_kernel_frame_registers(frame))
+                };
+
+                match registers {
+                    Some(registers) => {
+                        for (register, value) in registers.iter().enumerate() {
+                            output.push_register(*value, lightsaber_kernel_register_size(register));
+                        }
+                    }
+                    None => output.push_bytes(b"E01")
+                }
+            }
+            b'G' => {
+                // Only the stopped thread has its registers where they can be changed.
+                if self.register_thread.map_or(false, |thread| Some(thread) != lightsaber_kernel_current_thread_id()) {
+                    output.push_bytes(b"E01");
+
+                    return Action::Reply;
+                }
+
+                let mut offset = 0;
+
+                for register in 0..GDB_REGISTER_COUNT {
+                    let size = lightsaber_kernel_register_size(register) * 2;
+
+                    if let Some(value) = arguments.get(offset..offset + size).and_then(packet::lightsaber_kernel_parse_register) {
+                        lightsaber_kernel_set_frame_register(frame, register, value);
+                    }
+
+                    offset += size;
+                }
+
+                output.push_bytes(b"OK");
+            }
+            b'p' => {
+                let register = packet::lightsaber_kernel_parse_hex(arguments).map(|register| register as usize);
+
+                // Floating point and the rest are not captured; an empty reply tells GDB to use `g`.
+                if let Some(register) = register.filter(|register| *register < GDB_REGISTER_COUNT) {
+                    let registers = lightsaber_kernel_frame_registers(frame);
+
+                    output.push_register(registers[register], lightsaber_kernel_register_size(register));
+                }
+            }
+            b'P' => {
+                let mut fields = arguments.splitn(2, |byte| *byte == b'=');
+                let register = fields.next().and_then(packet::lightsaber_kernel_parse_hex);
+                let value = fields.next().and_then(packet::lightsaber_kernel_parse_register);
+
+                match (register, value) {
+                    (Some(register), Some(value)) if (register as usize) < GDB_REGISTER_COUNT => {
+                        lightsaber_kernel_set_frame_register(frame, register as usize, value);
+                        output.push_bytes(b"OK");
+                    }
+                    _ => output.push_bytes(b"E01")
+                }
+            }
+            b'm' => {
+                let mut fields = arguments.splitn(2, |byte| *byte == b',');
+                let address = fields.next().and_then(packet::lightsaber_kernel_parse_hex);
+                let length = fields.next().and_then(packet::lightsaber_kernel_parse_hex);
+
+                match (address, length) {
+                    (Some(address), Some(length)) => {
+                        let mut buffer = [0; GDB_MEMORY_CHUNK];
+                        let length = (length as usize).min(GDB_MEMORY_CHUNK);
+                        let read = lightsaber_kernel_read_memory(address, &mut buffer[..length]);
+
+                        match read {
+                            0 if length > 0 => output.push_bytes(b"E14"),
+                            read => output.push_hex(&buffer[..read])
+                        }
+                    }
+                    _ => output.push_bytes(b"E01")
+                }
+            }
+            b'M' => {
+                let mut fields = arguments.splitn(2, |byte| *byte == b':');
+                let mut location = fields.next().unwrap_or(&[]).splitn(2, |byte| *byte == b',');
+                let address = location.next().and_then(packet::lightsaber_kernel_parse_hex);
+                let data = fields.next().unwrap_or(&[]);
+                let mut buffer = [0; GDB_MEMORY_CHUNK];
+
+                match (address, packet::lightsaber_kernel_decode_hex(data, &mut buffer)) {
+                    (Some(address), Some(length)) if lightsaber_kernel_write_memory(address, &buffer[..length]) => output.push_bytes(b"OK"),
+                    (Some(_), Some(_)) => output.push_bytes(b"E14"),
+                    _ => output.push_bytes(b"E01")
+                }
+            }
+            b'Z' | b'z' => {
+                let mut fields = arguments.split(|byte| *byte == b',');
+                let kind = fields.next();
+                let address = fields.next().and_then(packet::lightsaber_kernel_parse_hex);
+
+                // Hardware breakpoints and watchpoints are left to GDB to emulate or refuse.
+                if kind != Some(&[GDB_BREAKPOINT_SOFTWARE][..]) {
+                    return Action::Reply;
+                }
+
+                match (command, address) {
+                    (b'Z', Some(address)) if self.add_breakpoint(address) => output.push_bytes(b"OK"),
+                    (b'z', Some(address)) => {
+                        self.remove_breakpoint(address);
+                        output.push_bytes(b"OK");
+                    }
+                    _ => output.push_bytes(b"E01")
+                }
+            }
+            b'c' | b's' => {
+                if let Some(address) = packet::lightsaber_kernel_parse_hex(arguments) {
+                    frame.instruction_pointer = address;
+                }
+
+                return match command {
+                    b'c' => Action::Resume(Resume::Continue),
+                    _ => Action::Resume(Resume::Step)
+                };
+            }
+            b'H' => {
+                if let Some((b'g', thread)) = arguments.split_first() {
+                    // Zero means any thread and -1 all of them; either way the stopped one.
+                    self.register_thread = match thread {
+                        b"0" | b"-1" => None,
+                        thread => packet::lightsaber_kernel_parse_hex(thread)
+                    };
+                }
+
+                output.push_bytes(b"OK");
+            }
+            b'T' => {
+                let thread = packet::lightsaber_kernel_parse_hex(arguments).unwrap_or(0);
+                let mut alive = false;
+
+                scheduler::lightsaber_kernel_try_for_each_thread(|candidate, _| {
+                    alive |= lightsaber_kernel_gdb_thread_id(candidate.id().0) == thread && candidate.state() != ThreadState::Exited;
+                });
+
+                output.push_bytes(if alive { b"OK" } else { b"E01" });
+            }
+            b'q' => self.query(arguments, output),
+            b'D' => {
+                output.push_bytes(b"OK");
+
+                return Action::Detach;
+            }
+            b'k' => return Action::Detach,
+            _ => { }
+        }
+
+        Action::Reply
+    }
+
+    fn resume(&mut self, resume: Resume, frame: &mut TrapFrame) {
+        self.register_thread = None;
+
+        match resume {
+            Resume::Step => {
+                self.stepping = Some(Resume::Step);
+                frame.cpu_flags |= RFLAGS_TRAP_FLAG;
+            }
+            // The breakpoint under the instruction pointer goes in after that instruction has run.
+            Resume::Continue if self.breakpoint_at(frame.instruction_pointer) => {
+                self.stepping = Some(Resume::Continue);
+                frame.cpu_flags |= RFLAGS_TRAP_FLAG;
+            }
+            Resume::Continue => self.insert_breakpoints()
+        }
+    }
+}
+
+impl GdbStub {
+    // Serves GDB until it resumes or detaches; the kernel stays stopped, with interrupts off, until then.
+    fn stop(&mut self, frame: &mut TrapFrame, signal: u8) {
+        let GdbStub { state, input, output } = self;
+
+        state.restore_breakpoints();
+        state.signal = signal;
+        state.stepping = None;
+        state.stop_reply(output);
+        packet::lightsaber_kernel_send_packet(output);
+
+        loop {
+            packet::lightsaber_kernel_receive_packet(input);
+
+            match state.handle(input.as_bytes(), output, frame) {
+                Action::Reply => packet::lightsaber_kernel_send_packet(output),
+                Action::Resume(resume) => {
+                    state.resume(resume, frame);
+
+                    return;
+                }
+                Action::Detach => {
+                    if !output.as_bytes().is_empty() {
+                        packet::lightsaber_kernel_send_packet(output);
+                    }
+
+                    state.breakpoints = [None; GDB_BREAKPOINT_CAPACITY];
+                    state.register_thread = None;
+                    frame.cpu_flags &= !RFLAGS_TRAP_FLAG;
+
+                    GDB_ATTACHED.store(false, Ordering::Release);
+                    serial::lightsaber_kernel_serial_set_debugger(false);
+
+                    return;
+                }
+            }
+        }
+    }
+}
+
+#[inline]
+pub fn lightsaber_kernel_gdb_attached() -> bool {
+    GDB_ATTACHED.load(Ordering::Acquire)
+}
+
+// Called from the breakpoint exception; returns whether the debugger took it.
+pub fn lightsaber_kernel_gdb_breakpoint_trap(frame: &mut TrapFrame) -> bool {
+    if !lightsaber_kernel_gdb_attached() {
+        return false;
+    }
+
+    let mut stub = GDB_STUB.lock();
+
+    // GDB expects to find the program counter on a breakpoint it planted, not just past it.
+    let address = frame.instruction_pointer.wrapping_sub(1);
+
+    if stub.state.breakpoint_at(address) {
+        frame.instruction_pointer = address;
+    }
+
+    let signal = match GDB_INTERRUPT_REQUESTED.swap(false, Ordering::AcqRel) {
+        true => GDB_SIGNAL_INTERRUPT,
+        false => GDB_SIGNAL_TRAP
+    };
+
+    stub.stop(frame, signal);
+
+    true
+}
+
+// Called from the debug exception; only single steps the debugger asked for are its business.
+pub fn lightsaber_kernel_gdb_debug_trap(frame: &mut TrapFrame, debug_status: u64) -> bool {
+    if !lightsaber_kernel_gdb_attached() || !trap::lightsaber_kernel_single_stepped(debug_status) {
+        return false;
+    }
+
+    let mut stub = GDB_STUB.lock();
+
+    let stepping = match stub.state.stepping.take() {
+        Some(stepping) => stepping,
+        None => return false
+    };
+
+    frame.cpu_flags &= !RFLAGS_TRAP_FLAG;
+
+    match stepping {
+        Resume::Continue => stub.state.insert_breakpoints(),
+        Resume::Step => stub.stop(frame, GDB_SIGNAL_TRAP)
+    }
+
+    true
+}
+
+// GDB sent Ctrl+C while the kernel was running.
+pub fn lightsaber_kernel_gdb_interrupt() {
+    GDB_INTERRUPT_REQUESTED.store(true, Ordering::Release);
+
+    unsafe {
+        asm!("int3", options(nomem, nostack));
+    }
+}
+
+// Hands the serial line over to GDB and stops the kernel until it connects.
+pub fn lightsaber_kernel_gdb_attach() -> Result<(), GdbError> {
+    if !serial::lightsaber_kernel_serial_present() {
+        return Err(GdbError::NoSerialLine);
+    }
+
+    if GDB_ATTACHED.swap(true, Ordering::AcqRel) {
+        return Err(GdbError::AlreadyAttached);
+    }
+
+    log::info!("Waiting for GDB on COM1.");
+
+    serial::lightsaber_kernel_serial_set_debugger(true);
+
+    unsafe {
+        asm!("int3", options(nomem, nostack));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,239 @@
+use core::fmt;
+
+use crate::drivers::serial;
+
+// Advertised to GDB as the largest packet it may send.
+pub const PACKET_CAPACITY: usize = 4096;
+
+const PACKET_SEND_ATTEMPTS: usize = 10;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+// Packets live in fixed buffers: the stub may have stopped the kernel inside the allocator.
+pub struct Packet {
+    buffer: [u8; PACKET_CAPACITY],
+    length: usize
+}
+
+impl Packet {
+    pub const fn new() -> Self {
+        Self {
+            buffer: [0; PACKET_CAPACITY],
+            length: 0
+        }
+    }
+
+    #[inline]
+    pub fn clear(&mut self) {
+        self.length = 0;
+    }
+
+    #[inline]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer[..self.length]
+    }
+
+    // Whatever does not fit is dropped; replies are sized so that it never comes to that.
+    pub fn push(&mut self, byte: u8) -> bool {
+        if self.length == PACKET_CAPACITY {
+            return false;
+        }
+
+        self.buffer[self.length] = byte;
+        self.length += 1;
+
+        true
+    }
+
+    pub fn push_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.push(*byte);
+        }
+    }
+
+    pub fn push_hex(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.push(HEX_DIGITS[(byte >> 4) as usize]);
+            self.push(HEX_DIGITS[(byte & 0xF) as usize]);
+        }
+    }
+
+    // Register values go over the wire in target byte order.
+    pub fn push_register(&mut self, value: u64, size: usize) {
+        self.push_hex(&value.to_le_bytes()[..size]);
+    }
+}
+
+impl fmt::Write for Packet {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.push_bytes(string.as_bytes());
+
+        Ok(())
+    }
+}
+
+#[inline]
+pub fn lightsaber_kernel_hex_value(digit: u8) -> Option<u8> {
+    match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        b'A'..=b'F' => Some(digit - b'A' + 10),
+        _ => None
+    }
+}
+
+// A big-endian hexadecimal number, as addresses, lengths and thread IDs are sent.
+pub fn lightsaber_kernel_parse_hex(digits: &[u8]) -> Option<u64> {
+    if digits.is_empty() || digits.len() > 16 {
+        return None;
+    }
+
+    digits
+        .iter()
+        .try_fold(0u64, |value, digit| Some(value << 4 | lightsaber_kernel_hex_value(*digit)? as u64))
+}
+
+pub fn lightsaber_kernel_decode_hex(digits: &[u8], bytes: &mut [u8]) -> Option<usize> {
+    if digits.len() % 2 != 0 || digits.len() / 2 > bytes.len() {
+        return None;
+    }
+
+    for (index, pair) in digits.chunks(2).enumerate() {
+        bytes[index] = lightsaber_kernel_hex_value(pair[0])? << 4 | lightsaber_kernel_hex_value(pair[1])?;
+    }
+
+    Some(digits.len() / 2)
+}
+
+// The inverse of `push_register`.
+pub fn lightsaber_kernel_parse_register(digits: &[u8]) -> Option<u64> {
+    let mut bytes = [0; 8];
+    lightsaber_kernel_decode_hex(digits, &mut bytes)?;
+
+    Some(u64::from_le_bytes(bytes))
+}
+
+fn lightsaber_kernel_read_byte() -> u8 {
+    loop {
+        if let Some(byte) = serial::lightsaber_kernel_serial_try_read() {
+            return byte;
+        }
+
+        core::hint::spin_loop();
+    }
+}
+
+// Waits for a packet with a valid checksum, asking GDB to retransmit the ones that are not.
+pub fn lightsaber_kernel_receive_packet(packet: &mut Packet) {
+    loop {
+        while lightsaber_kernel_read_byte() != b'$' { }
+
+        'packet: loop {
+            let mut checksum = 0u8;
+            let mut complete = true;
+
+            packet.clear();
+
+            loop {
+                match lightsaber_kernel_read_byte() {
+                    b'#' => break,
+                    // GDB gave up on the packet and started over.
+                    b'$' => continue 'packet,
+                    byte => {
+                        checksum = checksum.wrapping_add(byte);
+                        complete &= packet.push(byte);
+                    }
+                }
+            }
+
+            let high = lightsaber_kernel_hex_value(lightsaber_kernel_read_byte());
+            let low = lightsaber_kernel_hex_value(lightsaber_kernel_read_byte());
+
+            match (high, low) {
+                (Some(high), Some(low)) if complete && high << 4 | low == checksum => {
+                    serial::lightsaber_kernel_serial_write_raw(b"+");
+
+                    return;
+                }
+                _ => {
+                    serial::lightsaber_kernel_serial_write_raw(b"-");
+
+                    break;
+                }
+            }
+        }
+    }
+}
+
+pub fn lightsaber_kernel_send_packet(packet: &Packet) {
+    let checksum = packet.as_bytes().iter().fold(0u8, |checksum, byte| checksum.wrapping_add(*byte));
+    let trailer = [b'#', HEX_DIGITS[(checksum >> 4) as usize], HEX_DIGITS[(checksum & 0xF) as usize]];
+
+    for _ in 0..PACKET_SEND_ATTEMPTS {
+        serial::lightsaber_kernel_serial_write_raw(b"$");
+        serial::lightsaber_kernel_serial_write_raw(packet.as_bytes());
+        serial::lightsaber_kernel_serial_write_raw(&trailer);
+
+        // Anything but an acknowledgement, such as a stray interrupt request, is skipped.
+        loop {
+            match lightsaber_kernel_read_byte() {
+                b'+' => return,
+                b'-' => break,
+                _ => { }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::fmt::Write;
+
+    use super::*;
+
+    #[test_case]
+    fn parse_hex_reads_big_endian_numbers() {
+        assert_eq!(lightsaber_kernel_parse_hex(b"ffffffff8000a0c0"), Some(0xFFFF_FFFF_8000_A0C0));
+        assert_eq!(lightsaber_kernel_parse_hex(b"1F"), Some(0x1F));
+        assert_eq!(lightsaber_kernel_parse_hex(b""), None);
+        assert_eq!(lightsaber_kernel_parse_hex(b"12g"), None);
+        assert_eq!(lightsaber_kernel_parse_hex(b"10000000000000000"), None);
+    }
+
+    #[test_case]
+    fn decode_hex_rejects_odd_and_oversized_input() {
+        let mut bytes = [0; 2];
+
+        assert_eq!(lightsaber_kernel_decode_hex(b"c3", &mut bytes), Some(1));
+        assert_eq!(bytes[0], 0xC3);
+        assert_eq!(lightsaber_kernel_decode_hex(b"c", &mut bytes), None);
+        assert_eq!(lightsaber_kernel_decode_hex(b"aabbcc", &mut bytes), None);
+    }
+
+    #[test_case]
+    fn registers_round_trip_in_target_byte_order() {
+        let mut packet = Packet::new();
+        packet.push_register(0x1122_3344_5566_7788, 8);
+
+        assert_eq!(packet.as_bytes(), b"8877665544332211");
+        assert_eq!(lightsaber_kernel_parse_register(packet.as_bytes()), Some(0x1122_3344_5566_7788));
+
+        packet.clear();
+        packet.push_register(0x246, 4);
+
+        assert_eq!(packet.as_bytes(), b"46020000");
+    }
+
+    #[test_case]
+    fn packet_drops_what_does_not_fit() {
+        let mut packet = Packet::new();
+
+        for _ in 0..PACKET_CAPACITY {
+            assert!(packet.push(b'x'));
+        }
+
+        assert!(!packet.push(b'y'));
+        assert!(write!(packet, "z").is_ok());
+        assert_eq!(packet.as_bytes().len(), PACKET_CAPACITY);
+    }
+}
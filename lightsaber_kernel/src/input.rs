@@ -0,0 +1,144 @@
+use crate::{
+    drivers::ps2::scancode::KeyCode,
+    sync::{
+        Interrupted,
+        Spinlock,
+        WaitQueue
+    }
+};
+
+const INPUT_QUEUE_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum MouseButton {
+    Left,
+
+    Right,
+
+    Middle,
+
+    Fourth,
+
+    Fifth
+}
+
+// Pointer motion is relative and already in screen orientation, with y growing downwards.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum InputEvent {
+    Key {
+        code: KeyCode,
+        pressed: bool
+    },
+
+    PointerMotion {
+        dx: i32,
+        dy: i32
+    },
+
+    PointerButton {
+        button: MouseButton,
+        pressed: bool
+    },
+
+    // Positive when the wheel is turned away from the user.
+    PointerScroll {
+        delta: i32
+    }
+}
+
+struct InputQueue {
+    events: [Option<InputEvent>; INPUT_QUEUE_CAPACITY],
+    start: usize,
+    length: usize,
+    dropped: usize
+}
+
+impl InputQueue {
+    const fn new() -> Self {
+        Self {
+            events: [None; INPUT_QUEUE_CAPACITY],
+            start: 0,
+            length: 0,
+            dropped: 0
+        }
+    }
+
+    // A consumer that falls behind loses the oldest events rather than the newest.
+    fn push(&mut self, event: InputEvent) {
+        if self.length == INPUT_QUEUE_CAPACITY {
+            self.start = (self.start + 1) % INPUT_QUEUE_CAPACITY;
+            self.length -= 1;
+            self.dropped += 1;
+        }
+
+        self.events[(self.start + self.length) % INPUT_QUEUE_CAPACITY] = Some(event);
+        self.length += 1;
+    }
+
+    fn pop(&mut self) -> Option<InputEvent> {
+        if self.length == 0 {
+            return None;
+        }
+
+        let event = self.events[self.start].take();
+
+        self.start = (self.start + 1) % INPUT_QUEUE_CAPACITY;
+        self.length -= 1;
+
+        event
+    }
+}
+
+static INPUT_QUEUE: Spinlock<InputQueue> = Spinlock::named("input_queue", InputQueue::new());
+static INPUT_WAITERS: WaitQueue = WaitQueue::new();
+
+// Called from the input drivers' interrupt handlers.
+pub fn lightsaber_kernel_push_input_event(event: InputEvent) {
+    INPUT_QUEUE.lock().push(event);
+    INPUT_WAITERS.wake_all();
+}
+
+pub fn lightsaber_kernel_wait_input_event() -> Result<InputEvent, Interrupted> {
+    let mut event = None;
+
+    INPUT_WAITERS.wait_until(|| {
+        event = INPUT_QUEUE.lock().pop();
+        event.is_some()
+    })?;
+
+    Ok(event.expect("Woke up without an input event."))
+}
+
+pub fn lightsaber_kernel_dropped_input_events() -> usize {
+    INPUT_QUEUE.lock().dropped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn input_queue_drops_the_oldest_events() {
+        let mut queue = InputQueue::new();
+
+        for delta in 0..INPUT_QUEUE_CAPACITY as i32 + 2 {
+            queue.push(InputEvent::PointerScroll {
+                delta
+            });
+        }
+
+        assert_eq!(queue.dropped, 2);
+        assert_eq!(queue.pop(), Some(InputEvent::PointerScroll {
+            delta: 2
+        }));
+
+        while queue.length > 1 {
+            queue.pop();
+        }
+
+        assert_eq!(queue.pop(), Some(InputEvent::PointerScroll {
+            delta: INPUT_QUEUE_CAPACITY as i32 + 1
+        }));
+        assert_eq!(queue.pop(), None);
+    }
+}
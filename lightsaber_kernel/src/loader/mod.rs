@@ -0,0 +1,690 @@
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    mem,
+    ptr,
+    str
+};
+
+use x86_64::{
+    align_down,
+    align_up,
+    structures::paging::{
+        Page,
+        PageSize,
+        PageTableFlags,
+        Size4KiB
+    },
+    VirtAddr
+};
+
+use crate::{
+    architecture::elf::{
+        dynamic::{
+            Dyn,
+            DT_NULL,
+            DT_RELA,
+            DT_RELAENT,
+            DT_RELASZ
+        },
+        header::{
+            Header,
+            EI_CLASS,
+            EI_DATA,
+            EI_VERSION,
+            ELFCLASS64,
+            ELFDATA2LSB,
+            ELFMAG,
+            EM_X86_64,
+            ET_DYN,
+            ET_EXEC,
+            EV_CURRENT,
+            SELFMAG,
+            SIZEOF_EHDR
+        },
+        program_header::{
+            ProgramHeader,
+            PF_W,
+            PF_X,
+            PT_DYNAMIC,
+            PT_GNU_STACK,
+            PT_INTERP,
+            PT_LOAD,
+            PT_PHDR,
+            SIZEOF_PHDR
+        },
+        reloc::{
+            self,
+            Rela,
+            R_X86_64_NONE,
+            R_X86_64_RELATIVE,
+            SIZEOF_RELA
+        }
+    },
+    memory::{
+        address_space::{
+            AddressSpace,
+            AddressSpaceError
+        },
+        frame
+    },
+    syscall::user::USER_SPACE_END
+};
+
+pub mod stack;
+
+use stack::{
+    AT_BASE,
+    AT_ENTRY,
+    AT_PAGESZ,
+    AT_PHDR,
+    AT_PHENT,
+    AT_PHNUM
+};
+
+pub const DYNAMIC_EXECUTABLE_BASE: u64 = 0x0000_5555_5555_0000;
+pub const INTERPRETER_BASE: u64 = 0x0000_7F00_0000_0000;
+
+const MAXIMUM_INTERPRETER_PATH: u64 = 4096;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ElfError {
+    Truncated,
+
+    BadMagic,
+
+    UnsupportedClass(u8),
+
+    UnsupportedEncoding(u8),
+
+    UnsupportedVersion(u8),
+
+    UnsupportedMachine(u16),
+
+    UnsupportedType(u16),
+
+    BadProgramHeaderTable,
+
+    BadSegment(usize, &'static str),
+
+    NoLoadableSegments,
+
+    BadEntryPoint(u64),
+
+    ImageTooLarge(u64),
+
+    BadInterpreter(&'static str),
+
+    InterpreterNotFound(String),
+
+    BadDynamicSection(&'static str),
+
+    UnsupportedRelocation(u32),
+
+    ArgumentsTooLarge,
+
+    AddressSpace(AddressSpaceError)
+}
+
+impl fmt::Display for ElfError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Truncated => write!(formatter, "The file is too small to hold an ELF header."),
+            Self::BadMagic => write!(formatter, "The file does not start with the ELF magic number."),
+            Self::UnsupportedClass(class) => write!(formatter, "Unsupported ELF class {}; only 64-bit files can be loaded.", class),
+            Self::UnsupportedEncoding(encoding) => write!(formatter, "Unsupported data encoding {}; only little-endian files can be loaded.", encoding),
+            Self::UnsupportedVersion(version) => write!(formatter, "Unsupported ELF version {}.", version),
+            Self::UnsupportedMachine(machine) => write!(formatter, "Unsupported machine {}; only x86_64 files can be loaded.", machine),
+            Self::UnsupportedType(r#type) => write!(formatter, "Unsupported ELF type {}; only executables and shared objects can be loaded.", r#type),
+            Self::BadProgramHeaderTable => write!(formatter, "The program header table is malformed or lies outside the file."),
+            Self::BadSegment(index, reason) => write!(formatter, "Program header {} is invalid: {}.", index, reason),
+            Self::NoLoadableSegments => write!(formatter, "The file has no loadable segments."),
+            Self::BadEntryPoint(entry) => write!(formatter, "The entry point {:#x} does not lie in an executable segment.", entry),
+            Self::ImageTooLarge(size) => write!(formatter, "The segments need {} KiB of memory, more than is free.", size / 1024),
+            Self::BadInterpreter(reason) => write!(formatter, "The program interpreter is invalid: {}.", reason),
+            Self::InterpreterNotFound(path) => write!(formatter, "The program interpreter `{}` could not be found.", path),
+            Self::BadDynamicSection(reason) => write!(formatter, "The dynamic section is invalid: {}.", reason),
+            Self::UnsupportedRelocation(r#type) => write!(formatter, "Unsupported relocation type {}.", r#type),
+            Self::ArgumentsTooLarge => write!(formatter, "The arguments and environment do not fit on the user stack."),
+            Self::AddressSpace(error) => write!(formatter, "Failed to map the image: {}", error)
+        }
+    }
+}
+
+impl From<AddressSpaceError> for ElfError {
+    fn from(error: AddressSpaceError) -> Self {
+        Self::AddressSpace(error)
+    }
+}
+
+pub struct Executable {
+    pub address_space: AddressSpace,
+    pub entry: VirtAddr,
+    pub stack_pointer: VirtAddr,
+    pub program_break: VirtAddr
+}
+
+struct LoadedImage {
+    base: u64,
+    entry: u64,
+    program_headers: u64,
+    program_header_count: u16,
+    end: u64,
+    interpreter: Option<String>,
+    executable_stack: bool
+}
+
+fn lightsaber_kernel_read<T: Copy>(image: &[u8], offset: u64) -> Option<T> {
+    let end = offset.checked_add(mem::size_of::<T>() as u64)?;
+
+    if end > image.len() as u64 {
+        return None;
+    }
+
+    Some(unsafe {
+        ptr::read_unaligned(image.as_ptr().add(offset as usize) as *const T)
+    })
+}
+
+fn lightsaber_kernel_parse_header(image: &[u8]) -> Result<Header, ElfError> {
+    if image.len() < SIZEOF_EHDR {
+        return Err(ElfError::Truncated);
+    }
+
+    let header: Header = lightsaber_kernel_read(image, 0).ok_or(ElfError::Truncated)?;
+
+    if header.e_ident[..SELFMAG] != ELFMAG[..] {
+        return Err(ElfError::BadMagic);
+    }
+
+    if header.e_ident[EI_CLASS] != ELFCLASS64 {
+        return Err(ElfError::UnsupportedClass(header.e_ident[EI_CLASS]));
+    }
+
+    if header.e_ident[EI_DATA] != ELFDATA2LSB {
+        return Err(ElfError::UnsupportedEncoding(header.e_ident[EI_DATA]));
+    }
+
+    if header.e_ident[EI_VERSION] != EV_CURRENT {
+        return Err(ElfError::UnsupportedVersion(header.e_ident[EI_VERSION]));
+    }
+
+    if header.e_machine != EM_X86_64 {
+        return Err(ElfError::UnsupportedMachine(header.e_machine));
+    }
+
+    if header.e_type != ET_EXEC && header.e_type != ET_DYN {
+        return Err(ElfError::UnsupportedType(header.e_type));
+    }
+
+    Ok(header)
+}
+
+fn lightsaber_kernel_parse_program_headers(image: &[u8], header: &Header) -> Result<Vec<ProgramHeader>, ElfError> {
+    if header.e_phnum == 0 || header.e_phentsize as usize != SIZEOF_PHDR {
+        return Err(ElfError::BadProgramHeaderTable);
+    }
+
+    (0..header.e_phnum as u64)
+        .map(|index| {
+            header.e_phoff
+                .checked_add(index * SIZEOF_PHDR as u64)
+                .and_then(|offset| lightsaber_kernel_read(image, offset))
+                .ok_or(ElfError::BadProgramHeaderTable)
+        })
+        .collect()
+}
+
+fn lightsaber_kernel_segment_flags(program_header: &ProgramHeader) -> PageTableFlags {
+    let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+
+    if program_header.p_flags & PF_W != 0 {
+        flags |= PageTableFlags::WRITABLE;
+    }
+
+    if program_header.p_flags & PF_X == 0 {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    flags
+}
+
+fn lightsaber_kernel_validate_segment(image: &[u8], index: usize, program_header: &ProgramHeader, base: u64) -> Result<(), ElfError> {
+    if program_header.p_filesz > program_header.p_memsz {
+        return Err(ElfError::BadSegment(index, "the file size exceeds the memory size"));
+    }
+
+    match program_header.p_offset.checked_add(program_header.p_filesz) {
+        Some(end) if end <= image.len() as u64 => { }
+        _ => return Err(ElfError::BadSegment(index, "the segment data lies outside the file"))
+    }
+
+    if program_header.p_align > 1 {
+        if !program_header.p_align.is_power_of_two() {
+            return Err(ElfError::BadSegment(index, "the alignment is not a power of two"));
+        }
+
+        if program_header.p_vaddr % program_header.p_align != program_header.p_offset % program_header.p_align {
+            return Err(ElfError::BadSegment(index, "the virtual address and file offset are not congruent"));
+        }
+    }
+
+    let start = base.checked_add(program_header.p_vaddr);
+    let end = start.and_then(|start| start.checked_add(program_header.p_memsz));
+
+    match (start, end) {
+        (Some(start), Some(end)) if start >= Size4KiB::SIZE && end <= USER_SPACE_END => Ok(()),
+        _ => Err(ElfError::BadSegment(index, "the segment lies outside user space"))
+    }
+}
+
+fn lightsaber_kernel_map_segment(address_space: &mut AddressSpace, image: &[u8], program_header: &ProgramHeader, base: u64) -> Result<(), ElfError> {
+    let start = base + program_header.p_vaddr;
+    let end = start + program_header.p_memsz;
+    let flags = lightsaber_kernel_segment_flags(program_header);
+
+    let start_page: Page = Page::containing_address(VirtAddr::new(align_down(start, Size4KiB::SIZE)));
+    let end_page: Page = Page::containing_address(VirtAddr::new(align_up(end, Size4KiB::SIZE)));
+
+    for page in Page::range(start_page, end_page) {
+        match address_space.flags(page) {
+            // Segments may share a page at their boundaries; such a page gets the union of both permissions.
+            Some(existing) => {
+                let no_execute = existing & flags & PageTableFlags::NO_EXECUTE;
+                address_space.update_flags(page, ((existing | flags) - PageTableFlags::NO_EXECUTE) | no_execute)?;
+            }
+            None => address_space.map_zeroed(page, 1, flags)?
+        }
+    }
+
+    let offset = program_header.p_offset as usize;
+    let data = &image[offset..offset + program_header.p_filesz as usize];
+
+    address_space.write(VirtAddr::new(start), data)?;
+
+    Ok(())
+}
+
+fn lightsaber_kernel_read_interpreter(image: &[u8], index: usize, program_header: &ProgramHeader) -> Result<String, ElfError> {
+    if program_header.p_filesz == 0 || program_header.p_filesz > MAXIMUM_INTERPRETER_PATH {
+        return Err(ElfError::BadInterpreter("the path length is out of range"));
+    }
+
+    let start = program_header.p_offset as usize;
+    let end = program_header.p_offset
+        .checked_add(program_header.p_filesz)
+        .filter(|end| *end <= image.len() as u64)
+        .ok_or(ElfError::BadSegment(index, "the interpreter path lies outside the file"))? as usize;
+
+    let path = match image[start..end].split_last() {
+        Some((0, path)) => path,
+        _ => return Err(ElfError::BadInterpreter("the path is not null-terminated"))
+    };
+
+    str::from_utf8(path)
+        .map(String::from)
+        .map_err(|_| ElfError::BadInterpreter("the path is not valid UTF-8"))
+}
+
+fn lightsaber_kernel_apply_relocations(address_space: &mut AddressSpace, dynamic: &ProgramHeader, base: u64, strict: bool) -> Result<(), ElfError> {
+    let mut rela = None;
+    let mut rela_size = 0;
+    let mut rela_entry_size = SIZEOF_RELA as u64;
+
+    let count = dynamic.p_memsz / mem::size_of::<Dyn>() as u64;
+
+    for index in 0..count {
+        let mut bytes = [0; mem::size_of::<Dyn>()];
+        let address = VirtAddr::new(base + dynamic.p_vaddr + index * mem::size_of::<Dyn>() as u64);
+
+        address_space.read(address, &mut bytes)?;
+
+        let entry: Dyn = lightsaber_kernel_read(&bytes, 0).ok_or(ElfError::BadDynamicSection("truncated entry"))?;
+
+        match entry.d_tag {
+            DT_NULL => break,
+            DT_RELA => rela = Some(entry.d_val),
+            DT_RELASZ => rela_size = entry.d_val,
+            DT_RELAENT => rela_entry_size = entry.d_val,
+            _ => { }
+        }
+    }
+
+    let rela = match rela {
+        Some(rela) => rela,
+        None => return Ok(())
+    };
+
+    if rela_entry_size != SIZEOF_RELA as u64 {
+        return Err(ElfError::BadDynamicSection("unexpected relocation entry size"));
+    }
+
+    for index in 0..rela_size / rela_entry_size {
+        let mut bytes = [0; SIZEOF_RELA];
+        let address = base
+            .checked_add(rela)
+            .and_then(|address| address.checked_add(index * rela_entry_size))
+            .filter(|address| *address < USER_SPACE_END)
+            .ok_or(ElfError::BadDynamicSection("the relocation table lies outside user space"))?;
+
+        address_space
+            .read(VirtAddr::new(address), &mut bytes)
+            .map_err(|_| ElfError::BadDynamicSection("the relocation table is not mapped"))?;
+
+        let relocation: Rela = lightsaber_kernel_read(&bytes, 0).ok_or(ElfError::BadDynamicSection("truncated relocation"))?;
+
+        match reloc::r_type(relocation.r_info) {
+            R_X86_64_NONE => { }
+            R_X86_64_RELATIVE => {
+                let target = base
+                    .checked_add(relocation.r_offset)
+                    .filter(|target| *target < USER_SPACE_END)
+                    .ok_or(ElfError::BadDynamicSection("a relocation targets memory outside user space"))?;
+                let value = base.wrapping_add(relocation.r_addend as u64);
+
+                address_space
+                    .write(VirtAddr::new(target), &value.to_le_bytes())
+                    .map_err(|_| ElfError::BadDynamicSection("a relocation targets unmapped memory"))?;
+            }
+            // Anything symbolic is left to the dynamic linker, if there is one.
+            other if strict => return Err(ElfError::UnsupportedRelocation(other)),
+            _ => { }
+        }
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_load_image(address_space: &mut AddressSpace, image: &[u8], dynamic_base: u64, is_interpreter: bool) -> Result<LoadedImage, ElfError> {
+    let header = lightsaber_kernel_parse_header(image)?;
+    let program_headers = lightsaber_kernel_parse_program_headers(image, &header)?;
+
+    let base = match header.e_type {
+        ET_DYN => dynamic_base,
+        _ => 0
+    };
+
+    let mut interpreter = None;
+    let mut executable_stack = false;
+    let mut loadable = 0;
+
+    for (index, program_header) in program_headers.iter().enumerate() {
+        match program_header.p_type {
+            PT_LOAD => {
+                lightsaber_kernel_validate_segment(image, index, program_header, base)?;
+                loadable += 1;
+            }
+            PT_INTERP if is_interpreter => return Err(ElfError::BadInterpreter("the interpreter requests an interpreter of its own")),
+            PT_INTERP if interpreter.is_some() => return Err(ElfError::BadInterpreter("there is more than one PT_INTERP segment")),
+            PT_INTERP => interpreter = Some(lightsaber_kernel_read_interpreter(image, index, program_header)?),
+            PT_DYNAMIC => lightsaber_kernel_validate_segment(image, index, program_header, base)?,
+            PT_GNU_STACK => executable_stack = program_header.p_flags & PF_X != 0,
+            _ => { }
+        }
+    }
+
+    if loadable == 0 {
+        return Err(ElfError::NoLoadableSegments);
+    }
+
+    let loads = || program_headers.iter().filter(|program_header| program_header.p_type == PT_LOAD && program_header.p_memsz != 0);
+
+    let entry = base.wrapping_add(header.e_entry);
+    let entry_is_executable = loads().any(|program_header| {
+        let start = base + program_header.p_vaddr;
+
+        program_header.p_flags & PF_X != 0 && entry >= start && entry < start + program_header.p_memsz
+    });
+
+    if !entry_is_executable {
+        return Err(ElfError::BadEntryPoint(entry));
+    }
+
+    // Every page is mapped up front, so a segment claiming more memory than there is fails here rather than exhausting it.
+    let pages = loads()
+        .map(|program_header| {
+            let start = base + program_header.p_vaddr;
+
+            (align_up(start + program_header.p_memsz, Size4KiB::SIZE) - align_down(start, Size4KiB::SIZE)) / Size4KiB::SIZE
+        })
+        .sum::<u64>();
+
+    if pages > frame::lightsaber_kernel_frame_statistics().free_frames {
+        return Err(ElfError::ImageTooLarge(pages * Size4KiB::SIZE));
+    }
+
+    for program_header in loads() {
+        lightsaber_kernel_map_segment(address_space, image, program_header, base)?;
+    }
+
+    if header.e_type == ET_DYN {
+        if let Some(dynamic) = program_headers.iter().find(|program_header| program_header.p_type == PT_DYNAMIC) {
+            let strict = interpreter.is_none() && !is_interpreter;
+
+            lightsaber_kernel_apply_relocations(address_space, dynamic, base, strict)?;
+        }
+    }
+
+    let program_header_address = program_headers
+        .iter()
+        .find(|program_header| program_header.p_type == PT_PHDR)
+        .map(|program_header| program_header.p_vaddr)
+        .or_else(|| {
+            loads()
+                .find(|program_header| header.e_phoff >= program_header.p_offset && header.e_phoff < program_header.p_offset + program_header.p_filesz)
+                .map(|program_header| program_header.p_vaddr + (header.e_phoff - program_header.p_offset))
+        })
+        .map(|address| base + address)
+        .unwrap_or(0);
+
+    let end = loads()
+        .map(|program_header| base + program_header.p_vaddr + program_header.p_memsz)
+        .max()
+        .unwrap_or(base);
+
+    Ok(LoadedImage {
+        base,
+        entry,
+        program_headers: program_header_address,
+        program_header_count: header.e_phnum,
+        end,
+        interpreter,
+        executable_stack
+    })
+}
+
+pub fn lightsaber_kernel_load_executable<F>(image: &[u8], arguments: &[&str], environment: &[&str], mut open_interpreter: F) -> Result<Executable, ElfError>
+where
+    F: FnMut(&str) -> Option<Vec<u8>> {
+    let mut address_space = AddressSpace::new()?;
+
+    let executable = lightsaber_kernel_load_image(&mut address_space, image, DYNAMIC_EXECUTABLE_BASE, false)?;
+
+    let (entry, interpreter_base) = match executable.interpreter.as_deref() {
+        Some(path) => {
+            let interpreter_image = open_interpreter(path).ok_or_else(|| ElfError::InterpreterNotFound(String::from(path)))?;
+            let interpreter = lightsaber_kernel_load_image(&mut address_space, &interpreter_image, INTERPRETER_BASE, true)?;
+
+            (interpreter.entry, interpreter.base)
+        }
+        None => (executable.entry, 0)
+    };
+
+    let auxiliary_vector = [
+        (AT_PHDR, executable.program_headers),
+        (AT_PHENT, SIZEOF_PHDR as u64),
+        (AT_PHNUM, executable.program_header_count as u64),
+        (AT_PAGESZ, Size4KiB::SIZE),
+        (AT_BASE, interpreter_base),
+        (AT_ENTRY, executable.entry)
+    ];
+
+    let stack_pointer = stack::lightsaber_kernel_build_user_stack(
+        &mut address_space,
+        arguments,
+        environment,
+        &auxiliary_vector,
+        executable.executable_stack
+    )?;
+
+    Ok(Executable {
+        address_space,
+        entry: VirtAddr::new(entry),
+        stack_pointer,
+        program_break: VirtAddr::new(align_up(executable.end, Size4KiB::SIZE))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use core::slice;
+
+    use super::*;
+
+    use crate::architecture::elf::{
+        header::{
+            ELFCLASS32,
+            EM_386
+        },
+        program_header::PF_R
+    };
+
+    const TEST_IMAGE_SIZE: usize = 0x2000;
+    const TEST_VIRTUAL_ADDRESS: u64 = 0x40_0000;
+    const TEST_ENTRY: u64 = TEST_VIRTUAL_ADDRESS + 0x100;
+
+    // A static executable with one readable and executable segment covering the whole file.
+    fn lightsaber_kernel_test_image<F>(customize: F) -> Vec<u8>
+    where
+        F: FnOnce(&mut Header, &mut ProgramHeader) {
+        let mut header = Header::default();
+        header.e_ident[..SELFMAG].copy_from_slice(&ELFMAG[..]);
+        header.e_ident[EI_CLASS] = ELFCLASS64;
+        header.e_ident[EI_DATA] = ELFDATA2LSB;
+        header.e_ident[EI_VERSION] = EV_CURRENT;
+        header.e_type = ET_EXEC;
+        header.e_machine = EM_X86_64;
+        header.e_entry = TEST_ENTRY;
+        header.e_phoff = SIZEOF_EHDR as u64;
+        header.e_phentsize = SIZEOF_PHDR as u16;
+        header.e_phnum = 1;
+
+        let mut program_header = ProgramHeader {
+            p_type: PT_LOAD,
+            p_flags: PF_R | PF_X,
+            p_offset: 0,
+            p_vaddr: TEST_VIRTUAL_ADDRESS,
+            p_paddr: TEST_VIRTUAL_ADDRESS,
+            p_filesz: TEST_IMAGE_SIZE as u64,
+            p_memsz: TEST_IMAGE_SIZE as u64,
+            p_align: Size4KiB::SIZE
+        };
+
+        customize(&mut header, &mut program_header);
+
+        let mut image = vec![0; TEST_IMAGE_SIZE];
+
+        unsafe {
+            image[..SIZEOF_EHDR].copy_from_slice(slice::from_raw_parts(&header as *const Header as *const u8, SIZEOF_EHDR));
+            image[SIZEOF_EHDR..SIZEOF_EHDR + SIZEOF_PHDR].copy_from_slice(slice::from_raw_parts(&program_header as *const ProgramHeader as *const u8, SIZEOF_PHDR));
+        }
+
+        image
+    }
+
+    fn lightsaber_kernel_test_load<F>(customize: F) -> Result<u64, ElfError>
+    where
+        F: FnOnce(&mut Header, &mut ProgramHeader) {
+        let mut address_space = AddressSpace::new()?;
+
+        lightsaber_kernel_load_image(&mut address_space, &lightsaber_kernel_test_image(customize), DYNAMIC_EXECUTABLE_BASE, false).map(|image| image.entry)
+    }
+
+    fn lightsaber_kernel_test_validate<F>(customize: F) -> Result<(), ElfError>
+    where
+        F: FnOnce(&mut ProgramHeader) {
+        let image = lightsaber_kernel_test_image(|_, program_header| customize(program_header));
+        let program_header: ProgramHeader = lightsaber_kernel_read(&image, SIZEOF_EHDR as u64).unwrap();
+
+        lightsaber_kernel_validate_segment(&image, 0, &program_header, 0)
+    }
+
+    #[test_case]
+    fn loader_loads_a_well_formed_image() {
+        let image = lightsaber_kernel_test_image(|_, _| { });
+        let mut address_space = AddressSpace::new().unwrap();
+        let loaded = lightsaber_kernel_load_image(&mut address_space, &image, DYNAMIC_EXECUTABLE_BASE, false).unwrap();
+
+        let mut bytes = [0; SIZEOF_EHDR];
+        address_space.read(VirtAddr::new(TEST_VIRTUAL_ADDRESS), &mut bytes).unwrap();
+
+        assert_eq!(loaded.entry, TEST_ENTRY);
+        assert_eq!(loaded.end, TEST_VIRTUAL_ADDRESS + TEST_IMAGE_SIZE as u64);
+        assert_eq!(bytes[..], image[..SIZEOF_EHDR]);
+    }
+
+    #[test_case]
+    fn loader_rejects_malformed_headers() {
+        let image = lightsaber_kernel_test_image(|_, _| { });
+
+        assert_eq!(lightsaber_kernel_parse_header(&image[..SIZEOF_EHDR - 1]).err(), Some(ElfError::Truncated));
+        assert_eq!(lightsaber_kernel_test_load(|header, _| header.e_ident[0] = 0), Err(ElfError::BadMagic));
+        assert_eq!(lightsaber_kernel_test_load(|header, _| header.e_ident[EI_CLASS] = ELFCLASS32), Err(ElfError::UnsupportedClass(ELFCLASS32)));
+        assert_eq!(lightsaber_kernel_test_load(|header, _| header.e_machine = EM_386), Err(ElfError::UnsupportedMachine(EM_386)));
+        assert_eq!(lightsaber_kernel_test_load(|header, _| header.e_phentsize = 0), Err(ElfError::BadProgramHeaderTable));
+    }
+
+    #[test_case]
+    fn loader_rejects_malformed_segments() {
+        assert_eq!(lightsaber_kernel_test_validate(|_| { }), Ok(()));
+
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_memsz = program_header.p_filesz - 1),
+            Err(ElfError::BadSegment(0, "the file size exceeds the memory size"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_offset = Size4KiB::SIZE),
+            Err(ElfError::BadSegment(0, "the segment data lies outside the file"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_offset = u64::MAX),
+            Err(ElfError::BadSegment(0, "the segment data lies outside the file"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_align = 0x1800),
+            Err(ElfError::BadSegment(0, "the alignment is not a power of two"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_vaddr += 0x100),
+            Err(ElfError::BadSegment(0, "the virtual address and file offset are not congruent"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_vaddr = 0xFFFF_8000_0000_0000),
+            Err(ElfError::BadSegment(0, "the segment lies outside user space"))
+        );
+        assert_eq!(
+            lightsaber_kernel_test_validate(|program_header| program_header.p_vaddr = 0),
+            Err(ElfError::BadSegment(0, "the segment lies outside user space"))
+        );
+    }
+
+    #[test_case]
+    fn loader_rejects_an_entry_outside_executable_segments() {
+        assert_eq!(lightsaber_kernel_test_load(|_, program_header| program_header.p_flags = PF_R), Err(ElfError::BadEntryPoint(TEST_ENTRY)));
+        assert_eq!(lightsaber_kernel_test_load(|header, _| header.e_entry = TEST_VIRTUAL_ADDRESS + TEST_IMAGE_SIZE as u64), Err(ElfError::BadEntryPoint(TEST_VIRTUAL_ADDRESS + TEST_IMAGE_SIZE as u64)));
+    }
+
+    #[test_case]
+    fn loader_rejects_more_memory_than_is_free() {
+        let free = frame::lightsaber_kernel_frame_statistics().free_frames;
+
+        assert_eq!(lightsaber_kernel_test_load(|_, program_header| program_header.p_memsz = 1 << 42), Err(ElfError::ImageTooLarge(1 << 42)));
+        assert_eq!(frame::lightsaber_kernel_frame_statistics().free_frames, free);
+    }
+}
@@ -0,0 +1,128 @@
+use alloc::{
+    vec,
+    vec::Vec
+};
+
+use core::{
+    arch::x86_64::_rdtsc,
+    mem
+};
+
+use x86_64::{
+    align_down,
+    structures::paging::{
+        Page,
+        PageSize,
+        PageTableFlags,
+        Size4KiB
+    },
+    VirtAddr
+};
+
+use crate::{
+    loader::ElfError,
+    memory::address_space::AddressSpace
+};
+
+pub const USER_STACK_TOP: u64 = 0x0000_7FFF_FFFF_F000;
+pub const USER_STACK_SIZE: u64 = 256 * 1024;
+
+pub const AT_NULL: u64 = 0;
+pub const AT_PHDR: u64 = 3;
+pub const AT_PHENT: u64 = 4;
+pub const AT_PHNUM: u64 = 5;
+pub const AT_PAGESZ: u64 = 6;
+pub const AT_BASE: u64 = 7;
+pub const AT_ENTRY: u64 = 9;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_RANDOM: u64 = 25;
+pub const AT_EXECFN: u64 = 31;
+
+const PLATFORM: &str = "x86_64";
+const RANDOM_BYTES: usize = 16;
+
+// `AT_RANDOM` is only used to seed stack protectors and the like; timestamp counter noise is enough for that here.
+fn lightsaber_kernel_random_bytes() -> [u8; RANDOM_BYTES] {
+    let mut state = unsafe {
+        _rdtsc()
+    };
+
+    let mut bytes = [0; RANDOM_BYTES];
+
+    for chunk in bytes.chunks_mut(8) {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+
+        let mut value = state;
+        value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        value ^= value >> 31;
+
+        chunk.copy_from_slice(&value.to_le_bytes()[..chunk.len()]);
+    }
+
+    bytes
+}
+
+pub fn lightsaber_kernel_build_user_stack(address_space: &mut AddressSpace, arguments: &[&str], environment: &[&str], auxiliary_vector: &[(u64, u64)], executable: bool) -> Result<VirtAddr, ElfError> {
+    let mut flags = PageTableFlags::WRITABLE;
+
+    if !executable {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    let stack_bottom = Page::containing_address(VirtAddr::new(USER_STACK_TOP - USER_STACK_SIZE));
+    address_space.map_zeroed(stack_bottom, USER_STACK_SIZE / Size4KiB::SIZE, flags)?;
+
+    // The strings sit at the very top of the stack, followed downwards by the pointer vectors.
+    let mut strings = Vec::new();
+    let mut offsets = Vec::new();
+
+    for string in [PLATFORM].iter().chain(arguments.iter()).chain(environment.iter()) {
+        offsets.push(strings.len() as u64);
+        strings.extend_from_slice(string.as_bytes());
+        strings.push(0);
+    }
+
+    let random_offset = strings.len() as u64;
+    strings.extend_from_slice(&lightsaber_kernel_random_bytes());
+
+    let strings_start = align_down(USER_STACK_TOP - strings.len() as u64, 16);
+    let string_address = |index: usize| strings_start + offsets[index];
+
+    let argument_addresses = (0..arguments.len()).map(|index| string_address(1 + index));
+    let environment_addresses = (0..environment.len()).map(|index| string_address(1 + arguments.len() + index));
+
+    let mut vector = vec![arguments.len() as u64];
+    vector.extend(argument_addresses);
+    vector.push(0);
+    vector.extend(environment_addresses);
+    vector.push(0);
+
+    for (key, value) in auxiliary_vector {
+        vector.push(*key);
+        vector.push(*value);
+    }
+
+    vector.extend_from_slice(&[AT_PLATFORM, string_address(0)]);
+    vector.extend_from_slice(&[AT_RANDOM, strings_start + random_offset]);
+
+    if !arguments.is_empty() {
+        vector.extend_from_slice(&[AT_EXECFN, string_address(1)]);
+    }
+
+    vector.extend_from_slice(&[AT_NULL, 0]);
+
+    let vector_size = (vector.len() * mem::size_of::<u64>()) as u64;
+    let stack_pointer = align_down(strings_start - vector_size, 16);
+
+    if USER_STACK_TOP - stack_pointer > USER_STACK_SIZE / 2 {
+        return Err(ElfError::ArgumentsTooLarge);
+    }
+
+    let vector_bytes = vector.iter().flat_map(|value| value.to_le_bytes().to_vec()).collect::<Vec<u8>>();
+
+    address_space.write(VirtAddr::new(strings_start), &strings)?;
+    address_space.write(VirtAddr::new(stack_pointer), &vector_bytes)?;
+
+    Ok(VirtAddr::new(stack_pointer))
+}
@@ -1,3 +1,10 @@
+use alloc::vec::Vec;
+
+use core::fmt::{
+    self,
+    Write
+};
+
 use log::{
     Level,
     LevelFilter,
@@ -10,12 +17,64 @@ use lightsaber_graphics::debug::colour::{
     ColourCode
 };
 
-use crate::renderer::{
-    self,
-    print,
-    println
+use crate::{
+    cmdline::param,
+    drivers::serial,
+    renderer::{
+        self,
+        print,
+        println
+    },
+    sync::Spinlock
 };
 
+const LOG_BUFFER_CAPACITY: usize = 64 * 1024;
+
+// `loglevel=`, the most verbose level that is printed and kept.
+param!(pub LOG_LEVEL: LevelFilter = "loglevel");
+
+// The most recent log output, kept for dmesg; the oldest lines are overwritten first.
+struct LogBuffer {
+    buffer: [u8; LOG_BUFFER_CAPACITY],
+    start: usize,
+    length: usize
+}
+
+impl LogBuffer {
+    const fn new() -> Self {
+        Self {
+            buffer: [0; LOG_BUFFER_CAPACITY],
+            start: 0,
+            length: 0
+        }
+    }
+
+    fn push(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.buffer[(self.start + self.length) % LOG_BUFFER_CAPACITY] = *byte;
+
+            match self.length == LOG_BUFFER_CAPACITY {
+                true => self.start = (self.start + 1) % LOG_BUFFER_CAPACITY,
+                false => self.length += 1
+            }
+        }
+    }
+}
+
+static LOG_BUFFER: Spinlock<LogBuffer> = Spinlock::named("log_buffer", LogBuffer::new());
+
+// Copies of each record that do not need the heap, which the first records predate.
+struct LogRecorder;
+
+impl fmt::Write for LogRecorder {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        LOG_BUFFER.lock().push(string.as_bytes());
+        serial::lightsaber_kernel_serial_write(string.as_bytes());
+
+        Ok(())
+    }
+}
+
 pub static LOGGER: LightsaberKernelLogger = LightsaberKernelLogger;
 
 pub struct LightsaberKernelLogger;
@@ -53,14 +112,27 @@ impl log::Log for LightsaberKernelLogger {
             renderer::lightsaber_kernel_set_colour_code(ColourCode::new(Colour::WHITE, Colour::BLACK));
 
             println!(" ]    - {}", record.args());
+
+            let _ = writeln!(LogRecorder, "[ {} ]    - {}", record.level(), record.args());
         }
     }
 
     fn flush(&self) { }
 }
 
+pub fn lightsaber_kernel_kernel_log() -> Vec<u8> {
+    let buffer = LOG_BUFFER.lock();
+    let mut contents = Vec::with_capacity(buffer.length);
+
+    for index in 0..buffer.length {
+        contents.push(buffer.buffer[(buffer.start + index) % LOG_BUFFER_CAPACITY]);
+    }
+
+    contents
+}
+
 pub fn lightsaber_kernel_initialize_logger() {
     log::set_logger(&LOGGER)
-        .map(|()| log::set_max_level(LevelFilter::Info))
+        .map(|()| log::set_max_level(LOG_LEVEL.get().unwrap_or(LevelFilter::Info)))
         .unwrap();
 }
@@ -2,34 +2,95 @@
 #![no_main]
 
 #![feature(abi_x86_interrupt)]
+#![feature(alloc_error_handler)]
 #![feature(asm)]
+#![feature(const_btree_new)]
 #![feature(const_fn)]
+#![feature(custom_test_frameworks)]
 #![feature(decl_macro)]
+#![feature(global_asm)]
 #![feature(lang_items)]
+#![feature(naked_functions)]
 #![feature(panic_info_message)]
 
+#![test_runner(crate::testing::lightsaber_kernel_test_runner)]
+#![reexport_test_harness_main = "lightsaber_kernel_test_main"]
+
+extern crate alloc;
 extern crate rlibc;
 
 use lightsaber_bootloader::BootInformation;
 
+mod acpi;
 mod architecture;
+mod block;
+mod cmdline;
+mod console;
+mod drivers;
+mod fs;
+mod gdb;
+mod input;
+mod loader;
 mod logger;
+mod memory;
+mod pci;
+mod process;
 mod unwind;
 mod renderer;
+mod scheduler;
+mod shell;
+mod sync;
+mod syscall;
+#[cfg(test)]
+mod testing;
+mod time;
 
 #[export_name = "_start"]
 extern "C" fn lightsaber_kernel_main(boot_information: &'static mut BootInformation) -> ! {
     let framebuffer = &mut boot_information.framebuffer;
     renderer::lightsaber_kernel_initialize_renderer(framebuffer);
+    cmdline::lightsaber_kernel_parse_command_line(&boot_information.command_line);
+    drivers::serial::lightsaber_kernel_initialize_serial();
     logger::lightsaber_kernel_initialize_logger();
 
     log::info!("Initialized kernel debug renderer and logger.");
+    cmdline::lightsaber_kernel_report_command_line();
 
-    unsafe {
-        architecture::interrupts::lightsaber_kernel_disable_interrupts();
+    architecture::gdt::lightsaber_kernel_initialize_global_descriptor_table();
+    architecture::interrupts::idt::lightsaber_kernel_initialize_interrupt_descriptor_table();
+    architecture::interrupts::irq::lightsaber_kernel_initialize_irqs();
+    architecture::processor::lightsaber_kernel_initialize_processor_features();
+
+    memory::lightsaber_kernel_initialize_memory(boot_information.phys_memory_offset, &boot_information.memory_regions);
+    acpi::lightsaber_kernel_initialize_acpi(boot_information.rsdp_address);
+    architecture::interrupts::msi::lightsaber_kernel_initialize_msi();
+    scheduler::lightsaber_kernel_initialize_scheduler();
+    fs::initrd::lightsaber_kernel_mount_initial_ramdisk(&boot_information.initial_ramdisk);
+    fs::tmpfs::lightsaber_kernel_initialize_tmpfs();
+    time::lightsaber_kernel_initialize_timer();
 
-        loop {
-            architecture::interrupts::lightsaber_kernel_halt();
+    // Tests need no more than the core of the kernel, and do not return.
+    #[cfg(test)]
+    {
+        unsafe {
+            architecture::interrupts::lightsaber_kernel_enable_interrupts();
         }
+
+        lightsaber_kernel_test_main();
     }
+
+    block::lightsaber_kernel_initialize_block_layer();
+    pci::lightsaber_kernel_initialize_pci();
+    drivers::lightsaber_kernel_initialize_drivers();
+    fs::ext2::lightsaber_kernel_mount_root_device();
+    fs::fat::lightsaber_kernel_mount_efi_system_partition();
+    architecture::syscall::lightsaber_kernel_initialize_syscalls();
+    process::lightsaber_kernel_start_init();
+    shell::lightsaber_kernel_start_shell();
+
+    unsafe {
+        architecture::interrupts::lightsaber_kernel_enable_interrupts();
+    }
+
+    scheduler::lightsaber_kernel_exit_thread()
 }
@@ -0,0 +1,514 @@
+use alloc::vec::Vec;
+
+use core::{
+    fmt,
+    ptr
+};
+
+use spin::Once;
+
+use x86_64::{
+    registers::control::Cr3,
+    structures::paging::{
+        mapper::{
+            MapToError,
+            TranslateResult
+        },
+        page_table::PageTableEntry,
+        Mapper,
+        OffsetPageTable,
+        Page,
+        PageSize,
+        PageTable,
+        PageTableFlags,
+        PageTableIndex,
+        PhysFrame,
+        Size4KiB,
+        Translate
+    },
+    PhysAddr,
+    VirtAddr
+};
+
+use crate::memory::{
+    self,
+    frame::{
+        self,
+        GlobalFrameAllocator
+    },
+    paging
+};
+
+pub const KERNEL_LEVEL_FOUR_START: usize = 256;
+
+pub const PAGE_COPY_ON_WRITE: PageTableFlags = PageTableFlags::BIT_9;
+pub const PAGE_SHARED: PageTableFlags = PageTableFlags::BIT_10;
+
+static KERNEL_LEVEL_FOUR_FRAME: Once<PhysFrame> = Once::new();
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AddressSpaceError {
+    OutOfMemory,
+
+    AlreadyMapped,
+
+    NotMapped
+}
+
+impl fmt::Display for AddressSpaceError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::OutOfMemory => "Out of physical memory.",
+            Self::AlreadyMapped => "The page is already mapped.",
+            Self::NotMapped => "The page is not mapped."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+impl From<MapToError<Size4KiB>> for AddressSpaceError {
+    fn from(error: MapToError<Size4KiB>) -> Self {
+        match error {
+            MapToError::FrameAllocationFailed => Self::OutOfMemory,
+            MapToError::ParentEntryHugePage | MapToError::PageAlreadyMapped(_) => Self::AlreadyMapped
+        }
+    }
+}
+
+unsafe fn lightsaber_kernel_table_at(frame: PhysFrame) -> &'static mut PageTable {
+    &mut *memory::lightsaber_kernel_physical_to_virtual(frame.start_address()).as_mut_ptr::<PageTable>()
+}
+
+pub fn lightsaber_kernel_initialize_kernel_address_space() {
+    let (level_four_frame, _) = Cr3::read();
+
+    let level_four_table = unsafe {
+        lightsaber_kernel_table_at(level_four_frame)
+    };
+
+    // Every address space shares the kernel's level three tables, so they all have to exist up front.
+    let mut reserved = 0;
+
+    for entry in level_four_table.iter_mut().skip(KERNEL_LEVEL_FOUR_START) {
+        if entry.is_unused() {
+            let frame = frame::lightsaber_kernel_allocate_zeroed_frame().expect("Failed to allocate a kernel page table.");
+            entry.set_frame(frame, PageTableFlags::PRESENT | PageTableFlags::WRITABLE);
+
+            reserved += 1;
+        }
+    }
+
+    KERNEL_LEVEL_FOUR_FRAME.call_once(|| level_four_frame);
+
+    log::info!("Initialized kernel address space with {} reserved level four entries.", reserved);
+}
+
+pub fn lightsaber_kernel_kernel_level_four_frame() -> PhysFrame {
+    *KERNEL_LEVEL_FOUR_FRAME
+        .get()
+        .expect("The kernel address space is not initialized.")
+}
+
+#[derive(Debug)]
+pub struct AddressSpace {
+    level_four_frame: PhysFrame
+}
+
+impl AddressSpace {
+    pub fn new() -> Result<Self, AddressSpaceError> {
+        let level_four_frame = frame::lightsaber_kernel_allocate_zeroed_frame().ok_or(AddressSpaceError::OutOfMemory)?;
+
+        unsafe {
+            let kernel_table = lightsaber_kernel_table_at(lightsaber_kernel_kernel_level_four_frame());
+            let table = lightsaber_kernel_table_at(level_four_frame);
+
+            for index in KERNEL_LEVEL_FOUR_START..512 {
+                table[index] = kernel_table[index].clone();
+            }
+        }
+
+        Ok(Self {
+            level_four_frame
+        })
+    }
+
+    #[inline]
+    pub fn level_four_frame(&self) -> PhysFrame {
+        self.level_four_frame
+    }
+
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        Cr3::read().0 == self.level_four_frame
+    }
+
+    fn page_table(&self) -> OffsetPageTable<'static> {
+        unsafe {
+            paging::lightsaber_kernel_page_table_at(self.level_four_frame)
+        }
+    }
+
+    // Every frame is allocated before anything is mapped, so running out of memory leaves the range untouched.
+    fn allocate_zeroed_frames(count: u64) -> Result<Vec<PhysFrame>, AddressSpaceError> {
+        if count > frame::lightsaber_kernel_frame_statistics().free_frames {
+            return Err(AddressSpaceError::OutOfMemory);
+        }
+
+        let mut frames = Vec::with_capacity(count as usize);
+
+        for _ in 0..count {
+            match frame::lightsaber_kernel_allocate_zeroed_frame() {
+                Some(frame) => frames.push(frame),
+                None => {
+                    for frame in frames {
+                        unsafe {
+                            frame::lightsaber_kernel_deallocate_frame(frame);
+                        }
+                    }
+
+                    return Err(AddressSpaceError::OutOfMemory);
+                }
+            }
+        }
+
+        Ok(frames)
+    }
+
+    // A page table that cannot be allocated undoes what was mapped so far.
+    fn map_frames(&mut self, start_page: Page, frames: Vec<PhysFrame>, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let flags = flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let active = self.is_active();
+        let mut page_table = self.page_table();
+        let mut frames = frames.into_iter();
+        let mut mapped = 0;
+
+        while let Some(frame) = frames.next() {
+            let result = unsafe {
+                page_table.map_to(start_page + mapped, frame, flags, &mut GlobalFrameAllocator)
+            };
+
+            match result {
+                Ok(flush) if active => flush.flush(),
+                Ok(flush) => flush.ignore(),
+                Err(error) => {
+                    for frame in Some(frame).into_iter().chain(frames) {
+                        unsafe {
+                            frame::lightsaber_kernel_deallocate_frame(frame);
+                        }
+                    }
+
+                    self.unmap(start_page, mapped);
+
+                    return Err(error.into());
+                }
+            }
+
+            mapped += 1;
+        }
+
+        Ok(())
+    }
+
+    pub fn map_zeroed(&mut self, start_page: Page, count: u64, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let frames = Self::allocate_zeroed_frames(count)?;
+
+        self.map_frames(start_page, frames, flags)
+    }
+
+    // Whatever the range held is only unmapped once the new frames are in hand.
+    pub fn replace_zeroed(&mut self, start_page: Page, count: u64, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let frames = Self::allocate_zeroed_frames(count)?;
+
+        self.unmap(start_page, count);
+        self.map_frames(start_page, frames, flags)
+    }
+
+    pub fn flags(&self, page: Page) -> Option<PageTableFlags> {
+        match self.page_table().translate(page.start_address()) {
+            TranslateResult::Mapped { flags, .. } => Some(flags),
+            TranslateResult::NotMapped | TranslateResult::InvalidFrameAddress(_) => None
+        }
+    }
+
+    pub fn update_flags(&mut self, page: Page, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let flags = flags | PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+        let active = self.is_active();
+
+        let result = unsafe {
+            self.page_table().update_flags(page, flags)
+        };
+
+        match result {
+            Ok(flush) if active => flush.flush(),
+            Ok(flush) => flush.ignore(),
+            Err(_) => return Err(AddressSpaceError::NotMapped)
+        }
+
+        Ok(())
+    }
+
+    pub fn unmap(&mut self, start_page: Page, count: u64) {
+        let active = self.is_active();
+        let mut page_table = self.page_table();
+
+        for page in Page::range(start_page, start_page + count) {
+            if let Ok((frame, flush)) = page_table.unmap(page) {
+                match active {
+                    true => flush.flush(),
+                    false => flush.ignore()
+                }
+
+                unsafe {
+                    frame::lightsaber_kernel_release_frame(frame);
+                }
+            }
+        }
+    }
+
+    pub fn protect(&mut self, start_page: Page, count: u64, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let pages = Page::range(start_page, start_page + count);
+
+        if pages.clone().any(|page| self.flags(page).is_none()) {
+            return Err(AddressSpaceError::NotMapped);
+        }
+
+        for page in pages {
+            let old_flags = self.flags(page).ok_or(AddressSpaceError::NotMapped)?;
+            let mut new_flags = (flags - PAGE_COPY_ON_WRITE) | (old_flags & PAGE_SHARED);
+
+            // A private frame still referenced by another address space may only become writable through a fault.
+            if new_flags.contains(PageTableFlags::WRITABLE) && !new_flags.contains(PAGE_SHARED) && self.frame_reference_count(page) > 1 {
+                new_flags.remove(PageTableFlags::WRITABLE);
+                new_flags.insert(PAGE_COPY_ON_WRITE);
+            }
+
+            self.update_flags(page, new_flags)?;
+        }
+
+        Ok(())
+    }
+
+    fn frame_reference_count(&self, page: Page) -> u64 {
+        self.translate(page.start_address())
+            .map(|address| frame::lightsaber_kernel_frame_reference_count(PhysFrame::containing_address(address)))
+            .unwrap_or(0)
+    }
+
+    pub fn is_range_free(&self, start_page: Page, count: u64) -> bool {
+        Page::range(start_page, start_page + count).all(|page| self.flags(page).is_none())
+    }
+
+    pub fn try_clone(&mut self) -> Result<Self, AddressSpaceError> {
+        let mut clone = Self::new()?;
+        let mut result = Ok(());
+
+        self.for_each_user_page(|page, entry| {
+            if result.is_err() {
+                return;
+            }
+
+            let frame = PhysFrame::containing_address(entry.addr());
+            let mut flags = entry.flags();
+
+            // Both sides lose write access to private pages until one of them faults and takes a copy.
+            if !flags.contains(PAGE_SHARED) && flags.contains(PageTableFlags::WRITABLE) {
+                flags.remove(PageTableFlags::WRITABLE);
+                flags.insert(PAGE_COPY_ON_WRITE);
+
+                entry.set_flags(flags);
+            }
+
+            result = clone.map_frame(page, frame, flags);
+        });
+
+        if self.is_active() {
+            let (level_four_frame, cr3_flags) = Cr3::read();
+
+            unsafe {
+                Cr3::write(level_four_frame, cr3_flags);
+            }
+        }
+
+        result.map(|_| clone)
+    }
+
+    fn map_frame(&mut self, page: Page, frame: PhysFrame, flags: PageTableFlags) -> Result<(), AddressSpaceError> {
+        let result = unsafe {
+            self.page_table().map_to(page, frame, flags, &mut GlobalFrameAllocator)
+        };
+
+        match result {
+            Ok(flush) => {
+                flush.ignore();
+                frame::lightsaber_kernel_reference_frame(frame);
+
+                Ok(())
+            }
+            Err(error) => Err(error.into())
+        }
+    }
+
+    pub fn resolve_copy_on_write(&mut self, page: Page) -> Result<bool, AddressSpaceError> {
+        let flags = match self.flags(page) {
+            Some(flags) if flags.contains(PAGE_COPY_ON_WRITE) => flags,
+            _ => return Ok(false)
+        };
+
+        let new_flags = (flags - PAGE_COPY_ON_WRITE) | PageTableFlags::WRITABLE;
+        let source = PhysFrame::containing_address(self.translate(page.start_address()).ok_or(AddressSpaceError::NotMapped)?);
+
+        if frame::lightsaber_kernel_frame_reference_count(source) == 1 {
+            self.update_flags(page, new_flags)?;
+
+            return Ok(true);
+        }
+
+        let frame = frame::lightsaber_kernel_allocate_frame().ok_or(AddressSpaceError::OutOfMemory)?;
+
+        unsafe {
+            ptr::copy_nonoverlapping(
+                memory::lightsaber_kernel_physical_to_virtual(source.start_address()).as_ptr::<u8>(),
+                memory::lightsaber_kernel_physical_to_virtual(frame.start_address()).as_mut_ptr::<u8>(),
+                Size4KiB::SIZE as usize
+            );
+        }
+
+        let active = self.is_active();
+        let mut page_table = self.page_table();
+
+        match page_table.unmap(page) {
+            Ok((_, flush)) if active => flush.flush(),
+            Ok((_, flush)) => flush.ignore(),
+            Err(_) => {
+                unsafe {
+                    frame::lightsaber_kernel_deallocate_frame(frame);
+                }
+
+                return Err(AddressSpaceError::NotMapped);
+            }
+        }
+
+        // The intermediate tables are still in place, so remapping cannot fail for lack of memory.
+        unsafe {
+            page_table
+                .map_to(page, frame, new_flags, &mut GlobalFrameAllocator)?
+                .ignore();
+
+            frame::lightsaber_kernel_release_frame(source);
+        }
+
+        Ok(true)
+    }
+
+    fn for_each_user_page<F>(&mut self, mut function: F)
+    where
+        F: FnMut(Page, &mut PageTableEntry) {
+        let present = |entry: &PageTableEntry| entry.flags().contains(PageTableFlags::PRESENT);
+
+        unsafe {
+            let level_four_table = lightsaber_kernel_table_at(self.level_four_frame);
+
+            for (level_four_index, level_four_entry) in level_four_table.iter().enumerate().take(KERNEL_LEVEL_FOUR_START).filter(|(_, entry)| present(entry)) {
+                let level_three_table = lightsaber_kernel_table_at(PhysFrame::containing_address(level_four_entry.addr()));
+
+                for (level_three_index, level_three_entry) in level_three_table.iter().enumerate().filter(|(_, entry)| present(entry)) {
+                    let level_two_table = lightsaber_kernel_table_at(PhysFrame::containing_address(level_three_entry.addr()));
+
+                    for (level_two_index, level_two_entry) in level_two_table.iter().enumerate().filter(|(_, entry)| present(entry)) {
+                        let level_one_table = lightsaber_kernel_table_at(PhysFrame::containing_address(level_two_entry.addr()));
+
+                        for (level_one_index, level_one_entry) in level_one_table.iter_mut().enumerate().filter(|(_, entry)| present(entry)) {
+                            let page = Page::from_page_table_indices(
+                                PageTableIndex::new(level_four_index as u16),
+                                PageTableIndex::new(level_three_index as u16),
+                                PageTableIndex::new(level_two_index as u16),
+                                PageTableIndex::new(level_one_index as u16)
+                            );
+
+                            function(page, level_one_entry);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn translate(&self, address: VirtAddr) -> Option<PhysAddr> {
+        self.page_table().translate_addr(address)
+    }
+
+    fn for_each_chunk<F>(&self, address: VirtAddr, length: usize, mut function: F) -> Result<(), AddressSpaceError>
+    where
+        F: FnMut(*mut u8, usize, usize) {
+        let mut done = 0;
+
+        while done < length {
+            let current = address + done as u64;
+            let physical = self.translate(current).ok_or(AddressSpaceError::NotMapped)?;
+            let chunk = (Size4KiB::SIZE - u64::from(current.page_offset())).min((length - done) as u64) as usize;
+
+            function(memory::lightsaber_kernel_physical_to_virtual(physical).as_mut_ptr(), done, chunk);
+            done += chunk;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(&self, address: VirtAddr, buffer: &mut [u8]) -> Result<(), AddressSpaceError> {
+        let destination = buffer.as_mut_ptr();
+
+        self.for_each_chunk(address, buffer.len(), |source, offset, length| unsafe {
+            ptr::copy_nonoverlapping(source, destination.add(offset), length);
+        })
+    }
+
+    pub fn write(&mut self, address: VirtAddr, bytes: &[u8]) -> Result<(), AddressSpaceError> {
+        self.for_each_chunk(address, bytes.len(), |destination, offset, length| unsafe {
+            ptr::copy_nonoverlapping(bytes.as_ptr().add(offset), destination, length);
+        })
+    }
+}
+
+unsafe fn lightsaber_kernel_free_page_table(table_frame: PhysFrame, level: u8) {
+    let table = lightsaber_kernel_table_at(table_frame);
+
+    for entry in table.iter_mut() {
+        if entry.is_unused() || !entry.flags().contains(PageTableFlags::PRESENT) {
+            continue;
+        }
+
+        let frame = PhysFrame::containing_address(entry.addr());
+
+        match level {
+            1 => {
+                frame::lightsaber_kernel_release_frame(frame);
+            }
+            _ if entry.flags().contains(PageTableFlags::HUGE_PAGE) => { }
+            _ => lightsaber_kernel_free_page_table(frame, level - 1)
+        }
+
+        entry.set_unused();
+    }
+
+    frame::lightsaber_kernel_deallocate_frame(table_frame);
+}
+
+impl Drop for AddressSpace {
+    fn drop(&mut self) {
+        debug_assert!(!self.is_active(), "Dropping the active address space.");
+
+        unsafe {
+            let table = lightsaber_kernel_table_at(self.level_four_frame);
+
+            for entry in table.iter_mut().take(KERNEL_LEVEL_FOUR_START) {
+                if !entry.is_unused() {
+                    lightsaber_kernel_free_page_table(PhysFrame::containing_address(entry.addr()), 3);
+                    entry.set_unused();
+                }
+            }
+
+            frame::lightsaber_kernel_deallocate_frame(self.level_four_frame);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use alloc::vec::Vec;
+
+use core::{
+    ptr,
+    slice
+};
+
+use x86_64::{
+    structures::paging::{
+        PageSize,
+        PhysFrame,
+        Size4KiB
+    },
+    PhysAddr,
+    VirtAddr
+};
+
+use crate::memory::{
+    self,
+    frame
+};
+
+// Zeroed, physically contiguous memory that devices can read and write directly.
+#[derive(Debug)]
+pub struct DmaBuffer {
+    physical: PhysAddr,
+    size: usize,
+    frames: u64
+}
+
+impl DmaBuffer {
+    pub fn new(size: usize) -> Option<Self> {
+        let frames = ((size.max(1) as u64) + Size4KiB::SIZE - 1) / Size4KiB::SIZE;
+        let first = frame::lightsaber_kernel_allocate_contiguous_frames(frames)?;
+
+        let buffer = Self {
+            physical: first.start_address(),
+            size,
+            frames
+        };
+
+        unsafe {
+            buffer.pointer::<u8>(0).write_bytes(0, (frames * Size4KiB::SIZE) as usize);
+        }
+
+        Some(buffer)
+    }
+
+    #[inline]
+    pub fn physical(&self) -> PhysAddr {
+        self.physical
+    }
+
+    #[inline]
+    pub fn virtual_address(&self) -> VirtAddr {
+        memory::lightsaber_kernel_physical_to_virtual(self.physical)
+    }
+
+    #[inline]
+    fn pointer<T>(&self, offset: usize) -> *mut T {
+        assert!(offset + core::mem::size_of::<T>() <= self.size, "DMA access at {:#x} is outside the buffer.", offset);
+
+        (self.virtual_address() + offset).as_mut_ptr()
+    }
+
+    // Volatile, because the device may change the memory at any time.
+    #[inline]
+    pub fn read<T: Copy>(&self, offset: usize) -> T {
+        unsafe {
+            ptr::read_volatile(self.pointer::<T>(offset))
+        }
+    }
+
+    #[inline]
+    pub fn write<T: Copy>(&self, offset: usize, value: T) {
+        unsafe {
+            ptr::write_volatile(self.pointer::<T>(offset), value)
+        }
+    }
+
+    pub fn copy_from(&self, offset: usize, buffer: &[u8]) {
+        assert!(offset + buffer.len() <= self.size, "DMA copy at {:#x} is outside the buffer.", offset);
+
+        unsafe {
+            ptr::copy_nonoverlapping(buffer.as_ptr(), self.pointer::<u8>(offset), buffer.len());
+        }
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        unsafe {
+            slice::from_raw_parts(self.pointer::<u8>(0), self.size)
+        }
+    }
+}
+
+impl Drop for DmaBuffer {
+    fn drop(&mut self) {
+        for index in 0..self.frames {
+            unsafe {
+                frame::lightsaber_kernel_deallocate_frame(PhysFrame::containing_address(self.physical + index * Size4KiB::SIZE));
+            }
+        }
+    }
+}
+
+// Splits a buffer into the physically contiguous pieces backing it, merging neighbouring pages.
+pub fn lightsaber_kernel_physical_segments(buffer: &[u8]) -> Option<Vec<(PhysAddr, usize)>> {
+    let mut segments: Vec<(PhysAddr, usize)> = Vec::new();
+    let start = buffer.as_ptr() as u64;
+    let mut offset = 0;
+
+    while offset < buffer.len() {
+        let address = VirtAddr::new(start + offset as u64);
+        let length = ((Size4KiB::SIZE - address.as_u64() % Size4KiB::SIZE) as usize).min(buffer.len() - offset);
+        let physical = memory::paging::lightsaber_kernel_translate(address)?;
+
+        match segments.last_mut() {
+            Some((previous, previous_length)) if *previous + *previous_length as u64 == physical => *previous_length += length,
+            _ => segments.push((physical, length))
+        }
+
+        offset += length;
+    }
+
+    Some(segments)
+}
@@ -0,0 +1,284 @@
+use alloc::collections::BTreeMap;
+
+use spin::Once;
+
+use x86_64::{
+    structures::paging::{
+        FrameAllocator,
+        FrameDeallocator,
+        PageSize,
+        PhysFrame,
+        Size4KiB
+    },
+    align_down,
+    align_up,
+    PhysAddr
+};
+
+use lightsaber_bootloader::{
+    MemoryRegion,
+    MemoryRegionType
+};
+
+use crate::{
+    memory,
+    sync::Spinlock
+};
+
+static FRAME_ALLOCATOR: Once<Spinlock<KernelFrameAllocator>> = Once::new();
+
+// Only frames mapped more than once are tracked; a missing entry means a single owner.
+static FRAME_REFERENCES: Once<Spinlock<BTreeMap<PhysFrame, u64>>> = Once::new();
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct FrameStatistics {
+    pub total_frames: u64,
+    pub free_frames: u64
+}
+
+pub struct KernelFrameAllocator {
+    regions: &'static [MemoryRegion],
+    region_index: usize,
+    next_address: u64,
+    free_list: Option<PhysFrame>,
+    total_frames: u64,
+    free_frames: u64
+}
+
+impl KernelFrameAllocator {
+    pub fn new(regions: &'static [MemoryRegion]) -> Self {
+        let total_frames = regions
+            .iter()
+            .filter(|region| region.r#type == MemoryRegionType::Usable)
+            .map(|region| Self::usable_range(region))
+            .map(|(start, end)| end.saturating_sub(start) / Size4KiB::SIZE)
+            .sum();
+
+        Self {
+            regions,
+            region_index: 0,
+            next_address: 0,
+            free_list: None,
+            total_frames,
+            free_frames: total_frames
+        }
+    }
+
+    pub fn statistics(&self) -> FrameStatistics {
+        FrameStatistics {
+            total_frames: self.total_frames,
+            free_frames: self.free_frames
+        }
+    }
+
+    fn usable_range(region: &MemoryRegion) -> (u64, u64) {
+        // The first frame is never handed out so that a null physical address always means "no frame".
+        let start = align_up(region.start.max(Size4KiB::SIZE), Size4KiB::SIZE);
+        let end = align_down(region.end, Size4KiB::SIZE);
+
+        (start, end.max(start))
+    }
+
+    fn next_free_list_frame(frame: PhysFrame) -> Option<PhysFrame> {
+        let next = unsafe {
+            memory::lightsaber_kernel_physical_to_virtual(frame.start_address()).as_ptr::<u64>().read()
+        };
+
+        match next {
+            0 => None,
+            address => Some(PhysFrame::containing_address(PhysAddr::new(address)))
+        }
+    }
+
+    fn allocate_from_regions(&mut self) -> Option<PhysFrame> {
+        while let Some(region) = self.regions.get(self.region_index) {
+            if region.r#type == MemoryRegionType::Usable {
+                let (start, end) = Self::usable_range(region);
+                let address = self.next_address.max(start);
+
+                if address + Size4KiB::SIZE <= end {
+                    self.next_address = address + Size4KiB::SIZE;
+
+                    return Some(PhysFrame::containing_address(PhysAddr::new(address)));
+                }
+            }
+
+            self.region_index += 1;
+            self.next_address = 0;
+        }
+
+        None
+    }
+
+    // Only memory that has never been handed out is known to be contiguous, as the free list is in no particular order.
+    fn allocate_contiguous(&mut self, count: u64) -> Option<PhysFrame> {
+        while let Some(region) = self.regions.get(self.region_index) {
+            if region.r#type == MemoryRegionType::Usable {
+                let (start, end) = Self::usable_range(region);
+                let address = self.next_address.max(start);
+
+                if address + count * Size4KiB::SIZE <= end {
+                    self.next_address = address + count * Size4KiB::SIZE;
+                    self.free_frames -= count;
+
+                    return Some(PhysFrame::containing_address(PhysAddr::new(address)));
+                }
+
+                // The tail of the region is too short, so it goes to the free list instead of being skipped.
+                for frame in (address..end).step_by(Size4KiB::SIZE as usize) {
+                    unsafe {
+                        self.push_free_frame(PhysFrame::containing_address(PhysAddr::new(frame)));
+                    }
+                }
+            }
+
+            self.region_index += 1;
+            self.next_address = 0;
+        }
+
+        None
+    }
+
+    unsafe fn push_free_frame(&mut self, frame: PhysFrame) {
+        let next = self.free_list.map(|next| next.start_address().as_u64()).unwrap_or(0);
+
+        memory::lightsaber_kernel_physical_to_virtual(frame.start_address())
+            .as_mut_ptr::<u64>()
+            .write(next);
+
+        self.free_list = Some(frame);
+    }
+}
+
+unsafe impl FrameAllocator<Size4KiB> for KernelFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        let frame = match self.free_list {
+            Some(frame) => {
+                self.free_list = Self::next_free_list_frame(frame);
+
+                Some(frame)
+            }
+            None => self.allocate_from_regions()
+        }?;
+
+        self.free_frames -= 1;
+
+        Some(frame)
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for KernelFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        self.push_free_frame(frame);
+        self.free_frames += 1;
+    }
+}
+
+pub struct GlobalFrameAllocator;
+
+unsafe impl FrameAllocator<Size4KiB> for GlobalFrameAllocator {
+    fn allocate_frame(&mut self) -> Option<PhysFrame<Size4KiB>> {
+        lightsaber_kernel_allocate_frame()
+    }
+}
+
+impl FrameDeallocator<Size4KiB> for GlobalFrameAllocator {
+    unsafe fn deallocate_frame(&mut self, frame: PhysFrame<Size4KiB>) {
+        lightsaber_kernel_deallocate_frame(frame);
+    }
+}
+
+pub fn lightsaber_kernel_initialize_frame_allocator(memory_regions: &'static [MemoryRegion]) {
+    let frame_allocator = KernelFrameAllocator::new(memory_regions);
+
+    log::info!(
+        "Initialized physical frame allocator with {} usable frames ({} KiB).",
+        frame_allocator.total_frames,
+        frame_allocator.total_frames * Size4KiB::SIZE / 1024
+    );
+
+    FRAME_ALLOCATOR.call_once(|| Spinlock::named("frame_allocator", frame_allocator));
+}
+
+pub fn lightsaber_kernel_allocate_frame() -> Option<PhysFrame> {
+    FRAME_ALLOCATOR
+        .get()
+        .expect("The physical frame allocator is not initialized.")
+        .lock()
+        .allocate_frame()
+}
+
+pub fn lightsaber_kernel_allocate_zeroed_frame() -> Option<PhysFrame> {
+    let frame = lightsaber_kernel_allocate_frame()?;
+
+    unsafe {
+        memory::lightsaber_kernel_physical_to_virtual(frame.start_address())
+            .as_mut_ptr::<u8>()
+            .write_bytes(0, Size4KiB::SIZE as usize);
+    }
+
+    Some(frame)
+}
+
+// Returns the first of `count` physically adjacent frames; they are freed one at a time like any other frame.
+pub fn lightsaber_kernel_allocate_contiguous_frames(count: u64) -> Option<PhysFrame> {
+    FRAME_ALLOCATOR
+        .get()
+        .expect("The physical frame allocator is not initialized.")
+        .lock()
+        .allocate_contiguous(count)
+}
+
+pub unsafe fn lightsaber_kernel_deallocate_frame(frame: PhysFrame) {
+    FRAME_ALLOCATOR
+        .get()
+        .expect("The physical frame allocator is not initialized.")
+        .lock()
+        .deallocate_frame(frame)
+}
+
+fn lightsaber_kernel_frame_references() -> &'static Spinlock<BTreeMap<PhysFrame, u64>> {
+    FRAME_REFERENCES.call_once(|| Spinlock::named("frame_references", BTreeMap::new()))
+}
+
+pub fn lightsaber_kernel_reference_frame(frame: PhysFrame) {
+    *lightsaber_kernel_frame_references().lock().entry(frame).or_insert(1) += 1;
+}
+
+pub fn lightsaber_kernel_frame_reference_count(frame: PhysFrame) -> u64 {
+    lightsaber_kernel_frame_references()
+        .lock()
+        .get(&frame)
+        .copied()
+        .unwrap_or(1)
+}
+
+pub unsafe fn lightsaber_kernel_release_frame(frame: PhysFrame) -> bool {
+    {
+        let mut references = lightsaber_kernel_frame_references().lock();
+
+        if let Some(count) = references.get_mut(&frame) {
+            *count -= 1;
+
+            if *count == 1 {
+                references.remove(&frame);
+            }
+
+            return false;
+        }
+    }
+
+    lightsaber_kernel_deallocate_frame(frame);
+
+    true
+}
+
+pub fn lightsaber_kernel_frame_statistics() -> FrameStatistics {
+    FRAME_ALLOCATOR
+        .get()
+        .map(|frame_allocator| frame_allocator.lock().statistics())
+        .unwrap_or(FrameStatistics {
+            total_frames: 0,
+            free_frames: 0
+        })
+}
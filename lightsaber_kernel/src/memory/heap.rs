@@ -0,0 +1,234 @@
+use core::{
+    alloc::{
+        GlobalAlloc,
+        Layout
+    },
+    mem,
+    ptr
+};
+
+use x86_64::{
+    structures::paging::{
+        Page,
+        PageSize,
+        PageTableFlags,
+        Size4KiB
+    },
+    align_up,
+    VirtAddr
+};
+
+use crate::{
+    memory::paging,
+    sync::Spinlock
+};
+
+pub const KERNEL_HEAP_START: u64 = 0xFFFF_A000_0000_0000;
+pub const KERNEL_HEAP_INITIAL_SIZE: u64 = 4 * 1024 * 1024;
+pub const KERNEL_HEAP_MAXIMUM_SIZE: u64 = 1024 * 1024 * 1024;
+
+const KERNEL_HEAP_GROWTH_GRANULARITY: u64 = 256 * 1024;
+const FREE_BLOCK_ALIGNMENT: usize = mem::align_of::<FreeBlock>() * 2;
+const FREE_BLOCK_MINIMUM_SIZE: usize = mem::size_of::<FreeBlock>();
+
+#[global_allocator]
+static KERNEL_HEAP: KernelHeap = KernelHeap(Spinlock::named("kernel_heap", LinkedListHeap::empty()));
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct HeapStatistics {
+    pub size: usize,
+    pub used: usize
+}
+
+struct FreeBlock {
+    size: usize,
+    next: *mut FreeBlock
+}
+
+pub struct LinkedListHeap {
+    head: *mut FreeBlock,
+    size: usize,
+    used: usize
+}
+
+unsafe impl Send for LinkedListHeap { }
+
+impl LinkedListHeap {
+    pub const fn empty() -> Self {
+        Self {
+            head: ptr::null_mut(),
+            size: 0,
+            used: 0
+        }
+    }
+
+    pub unsafe fn extend(&mut self, start: usize, size: usize) {
+        self.size += size;
+        self.insert_free_region(start, size);
+    }
+
+    pub fn statistics(&self) -> HeapStatistics {
+        HeapStatistics {
+            size: self.size,
+            used: self.used
+        }
+    }
+
+    fn adjust_layout(layout: Layout) -> (usize, usize) {
+        let size = align_up(layout.size().max(FREE_BLOCK_MINIMUM_SIZE) as u64, FREE_BLOCK_ALIGNMENT as u64) as usize;
+        let align = layout.align().max(FREE_BLOCK_ALIGNMENT);
+
+        (size, align)
+    }
+
+    pub fn allocate(&mut self, layout: Layout) -> Option<*mut u8> {
+        let (size, align) = Self::adjust_layout(layout);
+
+        let mut previous: *mut *mut FreeBlock = &mut self.head;
+
+        unsafe {
+            while !(*previous).is_null() {
+                let block = *previous;
+                let block_start = block as usize;
+                let block_end = block_start + (*block).size;
+
+                let mut start = align_up(block_start as u64, align as u64) as usize;
+
+                // Any gap left in front of the allocation must be large enough to be tracked as a free block.
+                if start != block_start && start - block_start < FREE_BLOCK_MINIMUM_SIZE {
+                    start = align_up((block_start + FREE_BLOCK_MINIMUM_SIZE) as u64, align as u64) as usize;
+                }
+
+                let end = start + size;
+
+                if end > block_end {
+                    previous = &mut (*block).next;
+                    continue;
+                }
+
+                *previous = (*block).next;
+
+                if start != block_start {
+                    self.insert_free_region(block_start, start - block_start);
+                }
+
+                if block_end != end {
+                    self.insert_free_region(end, block_end - end);
+                }
+
+                self.used += size;
+
+                return Some(start as *mut u8);
+            }
+        }
+
+        None
+    }
+
+    pub unsafe fn deallocate(&mut self, pointer: *mut u8, layout: Layout) {
+        let (size, _) = Self::adjust_layout(layout);
+
+        self.used -= size;
+        self.insert_free_region(pointer as usize, size);
+    }
+
+    unsafe fn insert_free_region(&mut self, start: usize, size: usize) {
+        let mut previous: *mut FreeBlock = ptr::null_mut();
+        let mut current = self.head;
+
+        while !current.is_null() && (current as usize) < start {
+            previous = current;
+            current = (*current).next;
+        }
+
+        let block = start as *mut FreeBlock;
+        block.write(FreeBlock {
+            size,
+            next: current
+        });
+
+        if !current.is_null() && start + size == current as usize {
+            (*block).size += (*current).size;
+            (*block).next = (*current).next;
+        }
+
+        if previous.is_null() {
+            self.head = block;
+        }
+        else if previous as usize + (*previous).size == start {
+            (*previous).size += (*block).size;
+            (*previous).next = (*block).next;
+        }
+        else {
+            (*previous).next = block;
+        }
+    }
+}
+
+pub struct KernelHeap(Spinlock<LinkedListHeap>);
+
+impl KernelHeap {
+    fn grow(heap: &mut LinkedListHeap, layout: Layout) -> bool {
+        let (size, align) = LinkedListHeap::adjust_layout(layout);
+        let growth = align_up((size + align) as u64, KERNEL_HEAP_GROWTH_GRANULARITY);
+
+        if heap.size as u64 + growth > KERNEL_HEAP_MAXIMUM_SIZE {
+            return false;
+        }
+
+        let heap_end = KERNEL_HEAP_START + heap.size as u64;
+        let start_page = Page::containing_address(VirtAddr::new(heap_end));
+
+        if paging::lightsaber_kernel_map_pages(start_page, growth / Size4KiB::SIZE, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE).is_err() {
+            return false;
+        }
+
+        unsafe {
+            heap.extend(heap_end as usize, growth as usize);
+        }
+
+        true
+    }
+}
+
+unsafe impl GlobalAlloc for KernelHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.0.lock();
+
+        if let Some(pointer) = heap.allocate(layout) {
+            return pointer;
+        }
+
+        if !Self::grow(&mut heap, layout) {
+            return ptr::null_mut();
+        }
+
+        heap.allocate(layout).unwrap_or(ptr::null_mut())
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        self.0.lock().deallocate(pointer, layout)
+    }
+}
+
+#[alloc_error_handler]
+fn lightsaber_kernel_allocation_error(layout: Layout) -> ! {
+    panic!("Failed to allocate {} bytes with alignment {} from the kernel heap.", layout.size(), layout.align());
+}
+
+pub fn lightsaber_kernel_initialize_heap() {
+    let start_page = Page::containing_address(VirtAddr::new(KERNEL_HEAP_START));
+
+    paging::lightsaber_kernel_map_pages(start_page, KERNEL_HEAP_INITIAL_SIZE / Size4KiB::SIZE, PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_EXECUTE)
+        .expect("Failed to map the kernel heap.");
+
+    unsafe {
+        KERNEL_HEAP.0.lock().extend(KERNEL_HEAP_START as usize, KERNEL_HEAP_INITIAL_SIZE as usize);
+    }
+
+    log::info!("Initialized kernel heap at {:#x} ({} KiB).", KERNEL_HEAP_START, KERNEL_HEAP_INITIAL_SIZE / 1024);
+}
+
+pub fn lightsaber_kernel_heap_statistics() -> HeapStatistics {
+    KERNEL_HEAP.0.lock().statistics()
+}
@@ -0,0 +1,173 @@
+use core::{
+    ptr,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use x86_64::{
+    structures::paging::{
+        Mapper,
+        Page,
+        PageSize,
+        PageTableFlags,
+        PhysFrame,
+        Size4KiB
+    },
+    align_down,
+    align_up,
+    PhysAddr,
+    VirtAddr
+};
+
+use crate::memory::{
+    address_space::AddressSpaceError,
+    frame::GlobalFrameAllocator,
+    paging
+};
+
+pub const MMIO_WINDOW_START: u64 = 0xFFFF_B000_0000_0000;
+pub const MMIO_WINDOW_SIZE: u64 = 64 * 1024 * 1024 * 1024;
+
+static MMIO_WINDOW_NEXT: AtomicU64 = AtomicU64::new(MMIO_WINDOW_START);
+
+// Device registers mapped uncached; all accesses are volatile.
+#[derive(Debug, Clone, Copy)]
+pub struct MmioRegion {
+    physical: PhysAddr,
+    virtual_address: VirtAddr,
+    size: u64
+}
+
+macro_rules! mmio_accessors {
+    ($read:ident, $write:ident, $type:ty) => {
+        #[inline]
+        pub fn $read(&self, offset: u64) -> $type {
+            unsafe {
+                ptr::read_volatile(self.pointer::<$type>(offset))
+            }
+        }
+
+        #[inline]
+        pub fn $write(&self, offset: u64, value: $type) {
+            unsafe {
+                ptr::write_volatile(self.pointer::<$type>(offset), value)
+            }
+        }
+    };
+}
+
+impl MmioRegion {
+    #[inline]
+    pub fn physical(&self) -> PhysAddr {
+        self.physical
+    }
+
+    // Narrows the region to a window inside it, for registers that live at an offset into a BAR.
+    pub fn subregion(&self, offset: u64, size: u64) -> Option<Self> {
+        if offset.checked_add(size)? > self.size {
+            return None;
+        }
+
+        Some(Self {
+            physical: self.physical + offset,
+            virtual_address: self.virtual_address + offset,
+            size
+        })
+    }
+
+    #[inline]
+    fn pointer<T>(&self, offset: u64) -> *mut T {
+        assert!(offset + core::mem::size_of::<T>() as u64 <= self.size, "MMIO access at {:#x} is outside the region.", offset);
+
+        (self.virtual_address + offset).as_mut_ptr()
+    }
+
+    mmio_accessors!(read_u8, write_u8, u8);
+    mmio_accessors!(read_u16, write_u16, u16);
+    mmio_accessors!(read_u32, write_u32, u32);
+    mmio_accessors!(read_u64, write_u64, u64);
+}
+
+// Mappings are never torn down; devices are not expected to go away often enough for it to matter.
+pub fn lightsaber_kernel_map_mmio(physical: PhysAddr, size: u64) -> Result<MmioRegion, AddressSpaceError> {
+    let start = align_down(physical.as_u64(), Size4KiB::SIZE);
+    let end = align_up(physical.as_u64() + size.max(1), Size4KiB::SIZE);
+    let pages = (end - start) / Size4KiB::SIZE;
+
+    let base = MMIO_WINDOW_NEXT.fetch_add(end - start, Ordering::AcqRel);
+
+    if base + (end - start) > MMIO_WINDOW_START + MMIO_WINDOW_SIZE {
+        return Err(AddressSpaceError::OutOfMemory);
+    }
+
+    let mut page_table = unsafe {
+        paging::lightsaber_kernel_active_page_table()
+    };
+
+    let flags = PageTableFlags::PRESENT | PageTableFlags::WRITABLE | PageTableFlags::NO_CACHE | PageTableFlags::WRITE_THROUGH | PageTableFlags::NO_EXECUTE;
+
+    for index in 0..pages {
+        let page = Page::<Size4KiB>::containing_address(VirtAddr::new(base + index * Size4KiB::SIZE));
+        let frame = PhysFrame::containing_address(PhysAddr::new(start + index * Size4KiB::SIZE));
+
+        unsafe {
+            page_table.map_to(page, frame, flags, &mut GlobalFrameAllocator)?.flush();
+        }
+    }
+
+    Ok(MmioRegion {
+        physical,
+        virtual_address: VirtAddr::new(base + (physical.as_u64() - start)),
+        size
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    use crate::testing::should_panic;
+
+    // Ordinary memory stands in for the device; only the bounds checks are under test.
+    fn lightsaber_kernel_test_region(backing: &mut [u8]) -> MmioRegion {
+        let virtual_address = VirtAddr::from_ptr(backing.as_mut_ptr());
+
+        MmioRegion {
+            physical: PhysAddr::new(0),
+            virtual_address,
+            size: backing.len() as u64
+        }
+    }
+
+    #[test_case]
+    fn mmio_accesses_inside_the_region() {
+        let mut backing = vec![0u8; 16];
+        let region = lightsaber_kernel_test_region(&mut backing);
+
+        region.write_u32(12, 0xDEAD_BEEF);
+
+        assert_eq!(region.read_u32(12), 0xDEAD_BEEF);
+        assert_eq!(region.read_u8(12), 0xEF);
+    }
+
+    #[test_case]
+    fn mmio_subregion_stays_inside_the_region() {
+        let mut backing = vec![0u8; 16];
+        let region = lightsaber_kernel_test_region(&mut backing);
+
+        assert!(region.subregion(8, 8).is_some());
+        assert!(region.subregion(8, 9).is_none());
+        assert!(region.subregion(u64::MAX, 2).is_none());
+    }
+
+    should_panic!(mmio_access_past_the_end_panics, {
+        let mut backing = vec![0u8; 16];
+        let region = lightsaber_kernel_test_region(&mut backing);
+
+        region.read_u32(13);
+    });
+}
@@ -0,0 +1,47 @@
+use core::sync::atomic::{
+    AtomicU64,
+    Ordering
+};
+
+use spin::Once;
+
+use x86_64::{
+    PhysAddr,
+    VirtAddr
+};
+
+use lightsaber_bootloader::MemoryRegion;
+
+pub mod address_space;
+pub mod dma;
+pub mod frame;
+pub mod heap;
+pub mod mmio;
+pub mod paging;
+
+static PHYSICAL_MEMORY_OFFSET: AtomicU64 = AtomicU64::new(0);
+static MEMORY_REGIONS: Once<&'static [MemoryRegion]> = Once::new();
+
+pub fn lightsaber_kernel_initialize_memory(physical_memory_offset: u64, memory_regions: &'static [MemoryRegion]) {
+    PHYSICAL_MEMORY_OFFSET.store(physical_memory_offset, Ordering::Release);
+    MEMORY_REGIONS.call_once(|| memory_regions);
+
+    frame::lightsaber_kernel_initialize_frame_allocator(memory_regions);
+    heap::lightsaber_kernel_initialize_heap();
+    address_space::lightsaber_kernel_initialize_kernel_address_space();
+}
+
+// The memory map as the bootloader handed it over.
+pub fn lightsaber_kernel_memory_regions() -> &'static [MemoryRegion] {
+    MEMORY_REGIONS.get().copied().unwrap_or(&[])
+}
+
+#[inline]
+pub fn lightsaber_kernel_physical_memory_offset() -> VirtAddr {
+    VirtAddr::new(PHYSICAL_MEMORY_OFFSET.load(Ordering::Acquire))
+}
+
+#[inline]
+pub fn lightsaber_kernel_physical_to_virtual(address: PhysAddr) -> VirtAddr {
+    lightsaber_kernel_physical_memory_offset() + address.as_u64()
+}
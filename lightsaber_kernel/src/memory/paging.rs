@@ -0,0 +1,83 @@
+use x86_64::{
+    registers::control::Cr3,
+    structures::paging::{
+        mapper::{
+            MapToError,
+            TranslateResult
+        },
+        Mapper,
+        OffsetPageTable,
+        Page,
+        PageTable,
+        PageTableFlags,
+        PhysFrame,
+        Size4KiB,
+        Translate
+    },
+    PhysAddr,
+    VirtAddr
+};
+
+use crate::memory::{
+    self,
+    frame::{
+        self,
+        GlobalFrameAllocator
+    }
+};
+
+pub unsafe fn lightsaber_kernel_page_table_at(level_four_frame: PhysFrame) -> OffsetPageTable<'static> {
+    let level_four_table: *mut PageTable = memory::lightsaber_kernel_physical_to_virtual(level_four_frame.start_address()).as_mut_ptr();
+
+    OffsetPageTable::new(&mut *level_four_table, memory::lightsaber_kernel_physical_memory_offset())
+}
+
+pub unsafe fn lightsaber_kernel_active_page_table() -> OffsetPageTable<'static> {
+    lightsaber_kernel_page_table_at(Cr3::read().0)
+}
+
+pub fn lightsaber_kernel_map_pages(start_page: Page, count: u64, flags: PageTableFlags) -> Result<(), MapToError<Size4KiB>> {
+    let mut page_table = unsafe {
+        lightsaber_kernel_active_page_table()
+    };
+
+    for page in Page::range(start_page, start_page + count) {
+        let frame = frame::lightsaber_kernel_allocate_frame().ok_or(MapToError::FrameAllocationFailed)?;
+
+        let result = unsafe {
+            page_table.map_to(page, frame, flags, &mut GlobalFrameAllocator)
+        };
+
+        match result {
+            Ok(flush) => flush.flush(),
+            Err(error) => {
+                unsafe {
+                    frame::lightsaber_kernel_deallocate_frame(frame);
+                }
+
+                return Err(error);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_translate_flags(address: VirtAddr) -> Option<PageTableFlags> {
+    let page_table = unsafe {
+        lightsaber_kernel_active_page_table()
+    };
+
+    match page_table.translate(address) {
+        TranslateResult::Mapped { flags, .. } => Some(flags),
+        TranslateResult::NotMapped | TranslateResult::InvalidFrameAddress(_) => None
+    }
+}
+
+pub fn lightsaber_kernel_translate(address: VirtAddr) -> Option<PhysAddr> {
+    let page_table = unsafe {
+        lightsaber_kernel_active_page_table()
+    };
+
+    page_table.translate_addr(address)
+}
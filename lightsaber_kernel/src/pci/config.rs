@@ -0,0 +1,192 @@
+use alloc::vec::Vec;
+
+use spin::Once;
+
+use x86_64::{
+    instructions::port::Port,
+    PhysAddr
+};
+
+use crate::{
+    acpi,
+    memory::mmio::{
+        self,
+        MmioRegion
+    },
+    pci::PciAddress,
+    sync::Spinlock
+};
+
+pub const PCI_CONFIG_SPACE_SIZE: u16 = 256;
+
+const PCI_CONFIG_ADDRESS: u16 = 0xCF8;
+const PCI_CONFIG_DATA: u16 = 0xCFC;
+const PCI_CONFIG_ENABLE: u32 = 1 << 31;
+
+const MCFG_ENTRIES_OFFSET: usize = 44;
+const MCFG_ENTRY_SIZE: usize = 16;
+
+static CONFIG_ACCESS: Once<ConfigAccess> = Once::new();
+static LEGACY_CONFIG_LOCK: Spinlock<()> = Spinlock::named("pci_legacy_config", ());
+
+// One ECAM window from the MCFG table, covering a range of buses in a segment.
+pub struct EcamRegion {
+    pub segment: u16,
+    pub start_bus: u8,
+    pub end_bus: u8,
+    region: MmioRegion
+}
+
+impl EcamRegion {
+    #[inline]
+    fn contains(&self, address: PciAddress) -> bool {
+        address.segment == self.segment && (self.start_bus..=self.end_bus).contains(&address.bus)
+    }
+
+    #[inline]
+    fn offset(&self, address: PciAddress, offset: u16) -> u64 {
+        ((address.bus - self.start_bus) as u64) << 20 | (address.device as u64) << 15 | (address.function as u64) << 12 | offset as u64
+    }
+}
+
+pub enum ConfigAccess {
+    Legacy,
+
+    Ecam(Vec<EcamRegion>)
+}
+
+impl ConfigAccess {
+    // Segments that can be scanned, with the first bus of each.
+    pub fn roots(&self) -> Vec<(u16, u8)> {
+        match self {
+            Self::Legacy => alloc::vec![(0, 0)],
+            Self::Ecam(regions) => regions.iter().map(|region| (region.segment, region.start_bus)).collect()
+        }
+    }
+}
+
+fn lightsaber_kernel_parse_mcfg() -> Option<Vec<EcamRegion>> {
+    let table = acpi::lightsaber_kernel_find_acpi_table(b"MCFG")?;
+    let bytes = table.bytes();
+    let mut regions = Vec::new();
+
+    for entry in bytes[MCFG_ENTRIES_OFFSET.min(bytes.len())..].chunks_exact(MCFG_ENTRY_SIZE) {
+        let mut base = [0; 8];
+        base.copy_from_slice(&entry[..8]);
+
+        let base = u64::from_le_bytes(base);
+        let segment = u16::from_le_bytes([entry[8], entry[9]]);
+        let start_bus = entry[10];
+        let end_bus = entry[11];
+
+        if end_bus < start_bus {
+            continue;
+        }
+
+        // The base address is where bus zero would be, even when the range starts later.
+        let start = PhysAddr::new(base + ((start_bus as u64) << 20));
+        let size = ((end_bus - start_bus) as u64 + 1) << 20;
+
+        match mmio::lightsaber_kernel_map_mmio(start, size) {
+            Ok(region) => regions.push(EcamRegion {
+                segment,
+                start_bus,
+                end_bus,
+                region
+            }),
+            Err(error) => log::warn!("Failed to map ECAM for segment {} at {:#x}: {}", segment, start.as_u64(), error)
+        }
+    }
+
+    Some(regions).filter(|regions| !regions.is_empty())
+}
+
+pub fn lightsaber_kernel_initialize_config_access() -> &'static ConfigAccess {
+    CONFIG_ACCESS.call_once(|| match lightsaber_kernel_parse_mcfg() {
+        Some(regions) => {
+            for region in regions.iter() {
+                log::info!("Using ECAM for PCI segment {} buses {}-{} at {:#x}.", region.segment, region.start_bus, region.end_bus, region.region.physical().as_u64());
+            }
+
+            ConfigAccess::Ecam(regions)
+        }
+        None => {
+            log::info!("No MCFG table, using legacy PCI configuration ports.");
+
+            ConfigAccess::Legacy
+        }
+    })
+}
+
+fn lightsaber_kernel_config_access() -> &'static ConfigAccess {
+    CONFIG_ACCESS.get().unwrap_or(&ConfigAccess::Legacy)
+}
+
+fn lightsaber_kernel_ecam_region(address: PciAddress) -> Option<&'static EcamRegion> {
+    match lightsaber_kernel_config_access() {
+        ConfigAccess::Ecam(regions) => regions.iter().find(|region| region.contains(address)),
+        ConfigAccess::Legacy => None
+    }
+}
+
+#[inline]
+fn lightsaber_kernel_legacy_address(address: PciAddress, offset: u16) -> u32 {
+    PCI_CONFIG_ENABLE | (address.bus as u32) << 16 | (address.device as u32) << 11 | (address.function as u32) << 8 | (offset as u32 & 0xFC)
+}
+
+// Offsets past the first 256 bytes only exist through ECAM and read as all ones otherwise.
+pub fn lightsaber_kernel_config_read_u32(address: PciAddress, offset: u16) -> u32 {
+    let offset = offset & !0x03;
+
+    if let Some(region) = lightsaber_kernel_ecam_region(address) {
+        return region.region.read_u32(region.offset(address, offset));
+    }
+
+    if address.segment != 0 || offset >= PCI_CONFIG_SPACE_SIZE {
+        return !0;
+    }
+
+    let _guard = LEGACY_CONFIG_LOCK.lock();
+
+    unsafe {
+        Port::<u32>::new(PCI_CONFIG_ADDRESS).write(lightsaber_kernel_legacy_address(address, offset));
+        Port::<u32>::new(PCI_CONFIG_DATA).read()
+    }
+}
+
+pub fn lightsaber_kernel_config_write_u32(address: PciAddress, offset: u16, value: u32) {
+    let offset = offset & !0x03;
+
+    if let Some(region) = lightsaber_kernel_ecam_region(address) {
+        region.region.write_u32(region.offset(address, offset), value);
+
+        return;
+    }
+
+    if address.segment != 0 || offset >= PCI_CONFIG_SPACE_SIZE {
+        return;
+    }
+
+    let _guard = LEGACY_CONFIG_LOCK.lock();
+
+    unsafe {
+        Port::<u32>::new(PCI_CONFIG_ADDRESS).write(lightsaber_kernel_legacy_address(address, offset));
+        Port::<u32>::new(PCI_CONFIG_DATA).write(value);
+    }
+}
+
+pub fn lightsaber_kernel_config_read_u16(address: PciAddress, offset: u16) -> u16 {
+    (lightsaber_kernel_config_read_u32(address, offset) >> ((offset & 0x02) * 8)) as u16
+}
+
+pub fn lightsaber_kernel_config_read_u8(address: PciAddress, offset: u16) -> u8 {
+    (lightsaber_kernel_config_read_u32(address, offset) >> ((offset & 0x03) * 8)) as u8
+}
+
+// Narrow writes are done as read-modify-write of the surrounding dword.
+pub fn lightsaber_kernel_config_write_u16(address: PciAddress, offset: u16, value: u16) {
+    let shift = (offset & 0x02) * 8;
+    let dword = lightsaber_kernel_config_read_u32(address, offset) & !(0xFFFF << shift);
+
+    lightsaber_kernel_config_write_u32(address, offset, dword | (value as u32) << shift);
+}
@@ -0,0 +1,594 @@
+use alloc::{
+    collections::BTreeSet,
+    sync::Arc,
+    vec::Vec
+};
+
+use core::fmt;
+
+use x86_64::PhysAddr;
+
+use crate::{
+    memory::mmio::{
+        self,
+        MmioRegion
+    },
+    sync::Spinlock
+};
+
+pub mod config;
+pub mod msi;
+
+pub const PCI_DEVICES_PER_BUS: u8 = 32;
+pub const PCI_FUNCTIONS_PER_DEVICE: u8 = 8;
+
+pub const PCI_VENDOR_ID: u16 = 0x00;
+pub const PCI_DEVICE_ID: u16 = 0x02;
+pub const PCI_COMMAND: u16 = 0x04;
+pub const PCI_STATUS: u16 = 0x06;
+pub const PCI_REVISION: u16 = 0x08;
+pub const PCI_INTERFACE: u16 = 0x09;
+pub const PCI_SUBCLASS: u16 = 0x0A;
+pub const PCI_CLASS: u16 = 0x0B;
+pub const PCI_HEADER_TYPE: u16 = 0x0E;
+pub const PCI_BAR_0: u16 = 0x10;
+pub const PCI_SECONDARY_BUS: u16 = 0x19;
+pub const PCI_SUBSYSTEM_VENDOR_ID: u16 = 0x2C;
+pub const PCI_SUBSYSTEM_ID: u16 = 0x2E;
+pub const PCI_CAPABILITIES_POINTER: u16 = 0x34;
+pub const PCI_INTERRUPT_LINE: u16 = 0x3C;
+pub const PCI_INTERRUPT_PIN: u16 = 0x3D;
+
+pub const PCI_COMMAND_IO_SPACE: u16 = 1 << 0;
+pub const PCI_COMMAND_MEMORY_SPACE: u16 = 1 << 1;
+pub const PCI_COMMAND_BUS_MASTER: u16 = 1 << 2;
+pub const PCI_COMMAND_INTERRUPT_DISABLE: u16 = 1 << 10;
+
+pub const PCI_STATUS_CAPABILITIES: u16 = 1 << 4;
+
+pub const PCI_CAPABILITY_MSI: u8 = 0x05;
+pub const PCI_CAPABILITY_VENDOR: u8 = 0x09;
+pub const PCI_CAPABILITY_MSIX: u8 = 0x11;
+
+const PCI_HEADER_TYPE_MASK: u8 = 0x7F;
+const PCI_HEADER_TYPE_MULTIFUNCTION: u8 = 1 << 7;
+const PCI_HEADER_TYPE_GENERAL: u8 = 0x00;
+const PCI_HEADER_TYPE_BRIDGE: u8 = 0x01;
+
+const PCI_BAR_IO: u32 = 1 << 0;
+const PCI_BAR_MEMORY_64: u32 = 0x02 << 1;
+const PCI_BAR_PREFETCHABLE: u32 = 1 << 3;
+const PCI_BAR_COUNT: usize = 6;
+
+const PCI_NO_VENDOR: u16 = 0xFFFF;
+const PCI_MAXIMUM_CAPABILITIES: usize = 48;
+
+static PCI_DEVICES: Spinlock<Vec<Arc<PciDevice>>> = Spinlock::named("pci_devices", Vec::new());
+static PCI_DRIVERS: Spinlock<Vec<&'static PciDriver>> = Spinlock::named("pci_drivers", Vec::new());
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PciError {
+    InvalidBar,
+
+    CapabilityMissing,
+
+    NoFreeVectors,
+
+    MappingFailed,
+
+    Unsupported,
+
+    DeviceFailed
+}
+
+impl fmt::Display for PciError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::InvalidBar => "The base address register is missing or of the wrong kind.",
+            Self::CapabilityMissing => "The device does not have the capability.",
+            Self::NoFreeVectors => "No interrupt vectors are free.",
+            Self::MappingFailed => "Failed to map the device's registers.",
+            Self::Unsupported => "The device is not supported.",
+            Self::DeviceFailed => "The device did not respond as expected."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub struct PciAddress {
+    pub segment: u16,
+    pub bus: u8,
+    pub device: u8,
+    pub function: u8
+}
+
+impl PciAddress {
+    #[inline]
+    pub const fn new(segment: u16, bus: u8, device: u8, function: u8) -> Self {
+        Self {
+            segment,
+            bus,
+            device,
+            function
+        }
+    }
+}
+
+impl fmt::Display for PciAddress {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{:04x}:{:02x}:{:02x}.{}", self.segment, self.bus, self.device, self.function)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum PciBar {
+    Memory {
+        address: u64,
+        size: u64,
+        prefetchable: bool
+    },
+
+    Io {
+        port: u16,
+        size: u32
+    }
+}
+
+impl fmt::Display for PciBar {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Memory { address, size, prefetchable } => {
+                write!(formatter, "memory at {:#x} ({} KiB{})", address, size / 1024, if *prefetchable { ", prefetchable" } else { "" })
+            }
+            Self::Io { port, size } => write!(formatter, "I/O ports at {:#x} ({} bytes)", port, size)
+        }
+    }
+}
+
+pub struct PciDevice {
+    pub address: PciAddress,
+    pub vendor_id: u16,
+    pub device_id: u16,
+    pub class: u8,
+    pub subclass: u8,
+    pub interface: u8,
+    pub revision: u8,
+    pub header_type: u8,
+    pub subsystem_vendor_id: u16,
+    pub subsystem_id: u16,
+    pub interrupt_line: u8,
+    pub interrupt_pin: u8,
+    bars: [Option<PciBar>; PCI_BAR_COUNT],
+    driver: Spinlock<Option<&'static str>>
+}
+
+impl PciDevice {
+    fn probe(address: PciAddress) -> Option<Self> {
+        let vendor_id = config::lightsaber_kernel_config_read_u16(address, PCI_VENDOR_ID);
+
+        if vendor_id == PCI_NO_VENDOR {
+            return None;
+        }
+
+        let header_type = config::lightsaber_kernel_config_read_u8(address, PCI_HEADER_TYPE) & PCI_HEADER_TYPE_MASK;
+
+        let (subsystem_vendor_id, subsystem_id) = match header_type {
+            PCI_HEADER_TYPE_GENERAL => (
+                config::lightsaber_kernel_config_read_u16(address, PCI_SUBSYSTEM_VENDOR_ID),
+                config::lightsaber_kernel_config_read_u16(address, PCI_SUBSYSTEM_ID)
+            ),
+            _ => (0, 0)
+        };
+
+        let mut device = Self {
+            address,
+            vendor_id,
+            device_id: config::lightsaber_kernel_config_read_u16(address, PCI_DEVICE_ID),
+            class: config::lightsaber_kernel_config_read_u8(address, PCI_CLASS),
+            subclass: config::lightsaber_kernel_config_read_u8(address, PCI_SUBCLASS),
+            interface: config::lightsaber_kernel_config_read_u8(address, PCI_INTERFACE),
+            revision: config::lightsaber_kernel_config_read_u8(address, PCI_REVISION),
+            header_type,
+            subsystem_vendor_id,
+            subsystem_id,
+            interrupt_line: config::lightsaber_kernel_config_read_u8(address, PCI_INTERRUPT_LINE),
+            interrupt_pin: config::lightsaber_kernel_config_read_u8(address, PCI_INTERRUPT_PIN),
+            bars: [None; PCI_BAR_COUNT],
+            driver: Spinlock::named("pci_device_driver", None)
+        };
+
+        device.size_bars();
+
+        Some(device)
+    }
+
+    // Sizing overwrites the BARs, so decoding is switched off meanwhile to keep the device off the bus.
+    fn size_bars(&mut self) {
+        let count = match self.header_type {
+            PCI_HEADER_TYPE_GENERAL => PCI_BAR_COUNT,
+            PCI_HEADER_TYPE_BRIDGE => 2,
+            _ => return
+        };
+
+        let command = self.command();
+        self.set_command(command & !(PCI_COMMAND_IO_SPACE | PCI_COMMAND_MEMORY_SPACE));
+
+        let mut index = 0;
+
+        while index < count {
+            let offset = PCI_BAR_0 + index as u16 * 4;
+            let original = self.read_u32(offset);
+
+            self.write_u32(offset, !0);
+            let mask = self.read_u32(offset);
+            self.write_u32(offset, original);
+
+            if mask == 0 {
+                index += 1;
+
+                continue;
+            }
+
+            if original & PCI_BAR_IO != 0 {
+                let size = !(mask & !0x03) & 0xFFFF;
+
+                self.bars[index] = Some(PciBar::Io {
+                    port: (original & !0x03) as u16,
+                    size: size + 1
+                });
+
+                index += 1;
+
+                continue;
+            }
+
+            let mut address = (original & !0x0F) as u64;
+            let mut size_mask = (mask & !0x0F) as u64 | 0xFFFF_FFFF_0000_0000;
+            let wide = original & 0x06 == PCI_BAR_MEMORY_64 && index + 1 < count;
+
+            if wide {
+                let upper_offset = offset + 4;
+                let upper = self.read_u32(upper_offset);
+
+                self.write_u32(upper_offset, !0);
+                let upper_mask = self.read_u32(upper_offset);
+                self.write_u32(upper_offset, upper);
+
+                address |= (upper as u64) << 32;
+                size_mask = size_mask & 0xFFFF_FFFF | (upper_mask as u64) << 32;
+            }
+
+            let size = (!size_mask).wrapping_add(1);
+
+            if size != 0 {
+                self.bars[index] = Some(PciBar::Memory {
+                    address,
+                    size,
+                    prefetchable: original & PCI_BAR_PREFETCHABLE != 0
+                });
+            }
+
+            index += if wide { 2 } else { 1 };
+        }
+
+        self.set_command(command);
+    }
+
+    #[inline]
+    pub fn read_u8(&self, offset: u16) -> u8 {
+        config::lightsaber_kernel_config_read_u8(self.address, offset)
+    }
+
+    #[inline]
+    pub fn read_u16(&self, offset: u16) -> u16 {
+        config::lightsaber_kernel_config_read_u16(self.address, offset)
+    }
+
+    #[inline]
+    pub fn read_u32(&self, offset: u16) -> u32 {
+        config::lightsaber_kernel_config_read_u32(self.address, offset)
+    }
+
+    #[inline]
+    pub fn write_u16(&self, offset: u16, value: u16) {
+        config::lightsaber_kernel_config_write_u16(self.address, offset, value)
+    }
+
+    #[inline]
+    pub fn write_u32(&self, offset: u16, value: u32) {
+        config::lightsaber_kernel_config_write_u32(self.address, offset, value)
+    }
+
+    #[inline]
+    pub fn command(&self) -> u16 {
+        self.read_u16(PCI_COMMAND)
+    }
+
+    // Written as a whole dword with a zero status half, since status bits are cleared by writing ones.
+    #[inline]
+    pub fn set_command(&self, command: u16) {
+        self.write_u32(PCI_COMMAND, command as u32);
+    }
+
+    pub fn enable(&self, bits: u16) {
+        self.set_command(self.command() | bits);
+    }
+
+    pub fn enable_bus_mastering(&self) {
+        self.enable(PCI_COMMAND_MEMORY_SPACE | PCI_COMMAND_IO_SPACE | PCI_COMMAND_BUS_MASTER);
+    }
+
+    #[inline]
+    pub fn is_bridge(&self) -> bool {
+        self.header_type == PCI_HEADER_TYPE_BRIDGE
+    }
+
+    #[inline]
+    pub fn bar(&self, index: usize) -> Option<PciBar> {
+        self.bars.get(index).copied().flatten()
+    }
+
+    pub fn bars(&self) -> impl Iterator<Item = (usize, PciBar)> + '_ {
+        self.bars.iter().enumerate().filter_map(|(index, bar)| bar.map(|bar| (index, bar)))
+    }
+
+    pub fn map_bar(&self, index: usize) -> Result<MmioRegion, PciError> {
+        match self.bar(index) {
+            Some(PciBar::Memory { address, size, .. }) => {
+                self.enable(PCI_COMMAND_MEMORY_SPACE);
+
+                mmio::lightsaber_kernel_map_mmio(PhysAddr::new(address), size).map_err(|_| PciError::MappingFailed)
+            }
+            _ => Err(PciError::InvalidBar)
+        }
+    }
+
+    // Yields (id, offset) for every entry of the standard capability list.
+    pub fn capabilities(&self) -> Vec<(u8, u16)> {
+        let mut capabilities = Vec::new();
+
+        if self.read_u16(PCI_STATUS) & PCI_STATUS_CAPABILITIES == 0 {
+            return capabilities;
+        }
+
+        let mut offset = (self.read_u8(PCI_CAPABILITIES_POINTER) & !0x03) as u16;
+
+        // The bound guards against a looped list on broken hardware.
+        while offset != 0 && capabilities.len() < PCI_MAXIMUM_CAPABILITIES {
+            capabilities.push((self.read_u8(offset), offset));
+            offset = (self.read_u8(offset + 1) & !0x03) as u16;
+        }
+
+        capabilities
+    }
+
+    pub fn find_capability(&self, id: u8) -> Option<u16> {
+        self.capabilities()
+            .into_iter()
+            .find(|(capability, _)| *capability == id)
+            .map(|(_, offset)| offset)
+    }
+
+    #[inline]
+    pub fn driver(&self) -> Option<&'static str> {
+        *self.driver.lock()
+    }
+
+    pub fn class_name(&self) -> &'static str {
+        lightsaber_kernel_pci_class_name(self.class, self.subclass)
+    }
+}
+
+impl fmt::Display for PciDevice {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "{} [{:04x}:{:04x}] {} ({:02x}.{:02x}.{:02x}, revision {})",
+            self.address, self.vendor_id, self.device_id, self.class_name(), self.class, self.subclass, self.interface, self.revision
+        )
+    }
+}
+
+pub fn lightsaber_kernel_pci_class_name(class: u8, subclass: u8) -> &'static str {
+    match (class, subclass) {
+        (0x01, 0x01) => "IDE controller",
+        (0x01, 0x06) => "SATA controller",
+        (0x01, 0x08) => "NVM controller",
+        (0x01, 0x00) => "SCSI controller",
+        (0x01, _) => "Storage controller",
+        (0x02, 0x00) => "Ethernet controller",
+        (0x02, _) => "Network controller",
+        (0x03, _) => "Display controller",
+        (0x04, _) => "Multimedia controller",
+        (0x05, _) => "Memory controller",
+        (0x06, 0x00) => "Host bridge",
+        (0x06, 0x01) => "ISA bridge",
+        (0x06, 0x04) => "PCI bridge",
+        (0x06, _) => "Bridge",
+        (0x07, _) => "Communication controller",
+        (0x08, _) => "System peripheral",
+        (0x09, _) => "Input controller",
+        (0x0C, 0x03) => "USB controller",
+        (0x0C, 0x05) => "SMBus controller",
+        (0x0C, _) => "Serial bus controller",
+        (0xFF, _) => "Vendor specific device",
+        _ => "Unknown device"
+    }
+}
+
+// Any field left as `None` matches every device.
+#[derive(Debug, Clone, Copy)]
+pub struct PciDeviceId {
+    pub vendor_id: Option<u16>,
+    pub device_id: Option<u16>,
+    pub class: Option<u8>,
+    pub subclass: Option<u8>,
+    pub interface: Option<u8>
+}
+
+impl PciDeviceId {
+    #[inline]
+    pub const fn device(vendor_id: u16, device_id: u16) -> Self {
+        Self {
+            vendor_id: Some(vendor_id),
+            device_id: Some(device_id),
+            class: None,
+            subclass: None,
+            interface: None
+        }
+    }
+
+    #[inline]
+    pub const fn class(class: u8, subclass: u8, interface: Option<u8>) -> Self {
+        Self {
+            vendor_id: None,
+            device_id: None,
+            class: Some(class),
+            subclass: Some(subclass),
+            interface
+        }
+    }
+
+    pub fn matches(&self, device: &PciDevice) -> bool {
+        self.vendor_id.map_or(true, |vendor_id| vendor_id == device.vendor_id)
+            && self.device_id.map_or(true, |device_id| device_id == device.device_id)
+            && self.class.map_or(true, |class| class == device.class)
+            && self.subclass.map_or(true, |subclass| subclass == device.subclass)
+            && self.interface.map_or(true, |interface| interface == device.interface)
+    }
+}
+
+pub struct PciDriver {
+    pub name: &'static str,
+    pub ids: &'static [PciDeviceId],
+    pub probe: fn(&Arc<PciDevice>) -> Result<(), PciError>
+}
+
+impl PciDriver {
+    #[inline]
+    pub fn matches(&self, device: &PciDevice) -> bool {
+        self.ids.iter().any(|id| id.matches(device))
+    }
+}
+
+fn lightsaber_kernel_bind(device: &Arc<PciDevice>, driver: &'static PciDriver) -> bool {
+    // Claimed before probing, so a probe that registers more drivers cannot bind the device twice.
+    {
+        let mut bound = device.driver.lock();
+
+        if bound.is_some() || !driver.matches(device) {
+            return false;
+        }
+
+        *bound = Some(driver.name);
+    }
+
+    match (driver.probe)(device) {
+        Ok(()) => {
+            log::info!("Bound PCI device {} to driver {}.", device.address, driver.name);
+
+            true
+        }
+        Err(error) => {
+            log::warn!("Driver {} failed to probe PCI device {}: {}", driver.name, device.address, error);
+            *device.driver.lock() = None;
+
+            false
+        }
+    }
+}
+
+fn lightsaber_kernel_scan_bus(segment: u16, bus: u8, scanned: &mut BTreeSet<u8>, devices: &mut Vec<Arc<PciDevice>>) {
+    if !scanned.insert(bus) {
+        return;
+    }
+
+    for slot in 0..PCI_DEVICES_PER_BUS {
+        let first = PciAddress::new(segment, bus, slot, 0);
+
+        if config::lightsaber_kernel_config_read_u16(first, PCI_VENDOR_ID) == PCI_NO_VENDOR {
+            continue;
+        }
+
+        let functions = match config::lightsaber_kernel_config_read_u8(first, PCI_HEADER_TYPE) & PCI_HEADER_TYPE_MULTIFUNCTION {
+            0 => 1,
+            _ => PCI_FUNCTIONS_PER_DEVICE
+        };
+
+        for function in 0..functions {
+            let device = match PciDevice::probe(PciAddress::new(segment, bus, slot, function)) {
+                Some(device) => Arc::new(device),
+                None => continue
+            };
+
+            log::info!("Found PCI device {}.", device);
+
+            for (index, bar) in device.bars() {
+                log::debug!("  BAR {}: {}", index, bar);
+            }
+
+            devices.push(device.clone());
+
+            if device.is_bridge() {
+                let secondary = device.read_u8(PCI_SECONDARY_BUS);
+
+                if secondary > bus {
+                    lightsaber_kernel_scan_bus(segment, secondary, scanned, devices);
+                }
+            }
+        }
+    }
+}
+
+pub fn lightsaber_kernel_initialize_pci() {
+    let access = config::lightsaber_kernel_initialize_config_access();
+    let mut devices = Vec::new();
+
+    for (segment, root_bus) in access.roots() {
+        let mut scanned = BTreeSet::new();
+
+        lightsaber_kernel_scan_bus(segment, root_bus, &mut scanned, &mut devices);
+
+        // A multi-function host bridge has one root bus per function.
+        let host = PciAddress::new(segment, root_bus, 0, 0);
+
+        if config::lightsaber_kernel_config_read_u8(host, PCI_HEADER_TYPE) & PCI_HEADER_TYPE_MULTIFUNCTION != 0 {
+            for function in 1..PCI_FUNCTIONS_PER_DEVICE {
+                let address = PciAddress::new(segment, root_bus, 0, function);
+
+                if config::lightsaber_kernel_config_read_u16(address, PCI_VENDOR_ID) != PCI_NO_VENDOR {
+                    lightsaber_kernel_scan_bus(segment, root_bus.wrapping_add(function), &mut scanned, &mut devices);
+                }
+            }
+        }
+    }
+
+    log::info!("Initialized PCI with {} devices.", devices.len());
+
+    PCI_DEVICES.lock().extend(devices.iter().cloned());
+
+    for driver in lightsaber_kernel_pci_drivers() {
+        for device in devices.iter() {
+            lightsaber_kernel_bind(device, driver);
+        }
+    }
+}
+
+// Drivers registered after the scan are offered every device that is still unbound.
+pub fn lightsaber_kernel_register_pci_driver(driver: &'static PciDriver) {
+    PCI_DRIVERS.lock().push(driver);
+
+    for device in lightsaber_kernel_pci_devices() {
+        lightsaber_kernel_bind(&device, driver);
+    }
+}
+
+pub fn lightsaber_kernel_pci_devices() -> Vec<Arc<PciDevice>> {
+    PCI_DEVICES.lock().clone()
+}
+
+pub fn lightsaber_kernel_pci_drivers() -> Vec<&'static PciDriver> {
+    PCI_DRIVERS.lock().clone()
+}
@@ -0,0 +1,137 @@
+use alloc::vec::Vec;
+
+use crate::{
+    architecture::interrupts::msi::{
+        self,
+        MsiHandler,
+        MsiMessage
+    },
+    pci::{
+        PciDevice,
+        PciError,
+        PCI_CAPABILITY_MSI,
+        PCI_CAPABILITY_MSIX,
+        PCI_COMMAND_INTERRUPT_DISABLE
+    }
+};
+
+const MSI_CONTROL: u16 = 0x02;
+const MSI_ADDRESS_LOW: u16 = 0x04;
+const MSI_ADDRESS_HIGH: u16 = 0x08;
+const MSI_DATA_32: u16 = 0x08;
+const MSI_DATA_64: u16 = 0x0C;
+const MSI_CONTROL_ENABLE: u16 = 1 << 0;
+const MSI_CONTROL_MULTIPLE_ENABLE: u16 = 0x07 << 4;
+const MSI_CONTROL_64_BIT: u16 = 1 << 7;
+
+const MSIX_CONTROL: u16 = 0x02;
+const MSIX_TABLE: u16 = 0x04;
+const MSIX_CONTROL_TABLE_SIZE: u16 = 0x07FF;
+const MSIX_CONTROL_FUNCTION_MASK: u16 = 1 << 14;
+const MSIX_CONTROL_ENABLE: u16 = 1 << 15;
+const MSIX_BAR_INDICATOR: u32 = 0x07;
+const MSIX_ENTRY_SIZE: u64 = 16;
+const MSIX_ENTRY_ADDRESS_LOW: u64 = 0x00;
+const MSIX_ENTRY_ADDRESS_HIGH: u64 = 0x04;
+const MSIX_ENTRY_DATA: u64 = 0x08;
+const MSIX_ENTRY_VECTOR_CONTROL: u64 = 0x0C;
+const MSIX_ENTRY_MASKED: u32 = 1 << 0;
+
+impl PciDevice {
+    fn disable_legacy_interrupts(&self) {
+        self.enable(PCI_COMMAND_INTERRUPT_DISABLE);
+    }
+
+    // Uses a single message; multiple-message MSI needs aligned blocks of vectors the allocator does not hand out.
+    pub fn enable_msi(&self, handler: MsiHandler, argument: usize) -> Result<u8, PciError> {
+        let capability = self.find_capability(PCI_CAPABILITY_MSI).ok_or(PciError::CapabilityMissing)?;
+        let message = msi::lightsaber_kernel_allocate_msi(handler, argument).ok_or(PciError::NoFreeVectors)?;
+        let control = self.read_u16(capability + MSI_CONTROL);
+
+        self.write_u32(capability + MSI_ADDRESS_LOW, message.address as u32);
+
+        match control & MSI_CONTROL_64_BIT {
+            0 => self.write_u16(capability + MSI_DATA_32, message.data as u16),
+            _ => {
+                self.write_u32(capability + MSI_ADDRESS_HIGH, (message.address >> 32) as u32);
+                self.write_u16(capability + MSI_DATA_64, message.data as u16);
+            }
+        }
+
+        self.write_u16(capability + MSI_CONTROL, control & !MSI_CONTROL_MULTIPLE_ENABLE | MSI_CONTROL_ENABLE);
+        self.disable_legacy_interrupts();
+
+        Ok(message.vector)
+    }
+
+    pub fn msix_vector_count(&self) -> Option<usize> {
+        let capability = self.find_capability(PCI_CAPABILITY_MSIX)?;
+
+        Some((self.read_u16(capability + MSIX_CONTROL) & MSIX_CONTROL_TABLE_SIZE) as usize + 1)
+    }
+
+    // Programs one table entry per handler, in order, and returns the vectors they were given.
+    pub fn enable_msix(&self, handlers: &[(MsiHandler, usize)]) -> Result<Vec<u8>, PciError> {
+        let capability = self.find_capability(PCI_CAPABILITY_MSIX).ok_or(PciError::CapabilityMissing)?;
+        let control = self.read_u16(capability + MSIX_CONTROL);
+        let table_size = (control & MSIX_CONTROL_TABLE_SIZE) as usize + 1;
+
+        if handlers.is_empty() || handlers.len() > table_size {
+            return Err(PciError::Unsupported);
+        }
+
+        let table_location = self.read_u32(capability + MSIX_TABLE);
+        let table = self
+            .map_bar((table_location & MSIX_BAR_INDICATOR) as usize)?
+            .subregion((table_location & !MSIX_BAR_INDICATOR) as u64, table_size as u64 * MSIX_ENTRY_SIZE)
+            .ok_or(PciError::InvalidBar)?;
+
+        let mut messages: Vec<MsiMessage> = Vec::new();
+
+        for (handler, argument) in handlers {
+            match msi::lightsaber_kernel_allocate_msi(*handler, *argument) {
+                Some(message) => messages.push(message),
+                None => {
+                    for message in messages {
+                        msi::lightsaber_kernel_free_msi(message.vector);
+                    }
+
+                    return Err(PciError::NoFreeVectors);
+                }
+            }
+        }
+
+        // The whole function stays masked while its table is being filled in.
+        self.write_u16(capability + MSIX_CONTROL, control | MSIX_CONTROL_ENABLE | MSIX_CONTROL_FUNCTION_MASK);
+
+        for entry in 0..table_size {
+            let base = entry as u64 * MSIX_ENTRY_SIZE;
+
+            match messages.get(entry) {
+                Some(message) => {
+                    table.write_u32(base + MSIX_ENTRY_ADDRESS_LOW, message.address as u32);
+                    table.write_u32(base + MSIX_ENTRY_ADDRESS_HIGH, (message.address >> 32) as u32);
+                    table.write_u32(base + MSIX_ENTRY_DATA, message.data);
+                    table.write_u32(base + MSIX_ENTRY_VECTOR_CONTROL, table.read_u32(base + MSIX_ENTRY_VECTOR_CONTROL) & !MSIX_ENTRY_MASKED);
+                }
+                None => table.write_u32(base + MSIX_ENTRY_VECTOR_CONTROL, table.read_u32(base + MSIX_ENTRY_VECTOR_CONTROL) | MSIX_ENTRY_MASKED)
+            }
+        }
+
+        self.write_u16(capability + MSIX_CONTROL, (control | MSIX_CONTROL_ENABLE) & !MSIX_CONTROL_FUNCTION_MASK);
+        self.disable_legacy_interrupts();
+
+        Ok(messages.iter().map(|message| message.vector).collect())
+    }
+
+    pub fn disable_msix(&self, vectors: &[u8]) {
+        if let Some(capability) = self.find_capability(PCI_CAPABILITY_MSIX) {
+            let control = self.read_u16(capability + MSIX_CONTROL);
+            self.write_u16(capability + MSIX_CONTROL, control & !MSIX_CONTROL_ENABLE);
+        }
+
+        for vector in vectors {
+            msi::lightsaber_kernel_free_msi(*vector);
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use alloc::{
+    sync::Arc,
+    vec::Vec
+};
+
+use crate::{
+    console,
+    fs::{
+        file::SeekWhence,
+        DirectoryEntry,
+        Metadata
+    },
+    syscall::SyscallError
+};
+
+pub const STANDARD_INPUT: usize = 0;
+pub const STANDARD_OUTPUT: usize = 1;
+pub const STANDARD_ERROR: usize = 2;
+
+const MAXIMUM_FILE_DESCRIPTORS: usize = 256;
+
+pub trait FileDescription: Send + Sync {
+    fn read(&self, _buffer: &mut [u8]) -> Result<usize, SyscallError> {
+        Err(SyscallError::BadDescriptor)
+    }
+
+    fn write(&self, _buffer: &[u8]) -> Result<usize, SyscallError> {
+        Err(SyscallError::BadDescriptor)
+    }
+
+    fn seek(&self, _offset: i64, _whence: SeekWhence) -> Result<u64, SyscallError> {
+        Err(SyscallError::IllegalSeek)
+    }
+
+    fn metadata(&self) -> Result<Metadata, SyscallError> {
+        Err(SyscallError::BadDescriptor)
+    }
+
+    // Hands entries to `fill` until it refuses one, which is then returned again by the next call.
+    fn read_directory(&self, _fill: &mut dyn FnMut(&DirectoryEntry) -> bool) -> Result<usize, SyscallError> {
+        Err(SyscallError::NotDirectory)
+    }
+}
+
+pub struct Console;
+
+impl FileDescription for Console {
+    fn read(&self, buffer: &mut [u8]) -> Result<usize, SyscallError> {
+        Ok(console::lightsaber_kernel_console_read(buffer)?)
+    }
+
+    fn write(&self, buffer: &[u8]) -> Result<usize, SyscallError> {
+        console::lightsaber_kernel_console_write(buffer);
+
+        Ok(buffer.len())
+    }
+}
+
+#[derive(Clone)]
+pub struct FileDescriptorTable {
+    descriptors: Vec<Option<Arc<dyn FileDescription>>>
+}
+
+impl FileDescriptorTable {
+    #[inline]
+    pub fn new() -> Self {
+        Self {
+            descriptors: Vec::new()
+        }
+    }
+
+    pub fn with_console() -> Self {
+        let console: Arc<dyn FileDescription> = Arc::new(Console);
+        let mut table = Self::new();
+
+        for descriptor in &[STANDARD_INPUT, STANDARD_OUTPUT, STANDARD_ERROR] {
+            table
+                .install_at(*descriptor, console.clone())
+                .expect("Failed to install the console file descriptors.");
+        }
+
+        table
+    }
+
+    pub fn get(&self, descriptor: usize) -> Result<Arc<dyn FileDescription>, SyscallError> {
+        self.descriptors
+            .get(descriptor)
+            .and_then(|file| file.clone())
+            .ok_or(SyscallError::BadDescriptor)
+    }
+
+    pub fn install(&mut self, file: Arc<dyn FileDescription>) -> Result<usize, SyscallError> {
+        let descriptor = self.descriptors
+            .iter()
+            .position(|file| file.is_none())
+            .unwrap_or_else(|| self.descriptors.len());
+
+        self.install_at(descriptor, file)?;
+
+        Ok(descriptor)
+    }
+
+    pub fn install_at(&mut self, descriptor: usize, file: Arc<dyn FileDescription>) -> Result<Option<Arc<dyn FileDescription>>, SyscallError> {
+        if descriptor >= MAXIMUM_FILE_DESCRIPTORS {
+            return Err(SyscallError::TooManyFiles);
+        }
+
+        if descriptor >= self.descriptors.len() {
+            self.descriptors.resize(descriptor + 1, None);
+        }
+
+        Ok(self.descriptors[descriptor].replace(file))
+    }
+
+    pub fn remove(&mut self, descriptor: usize) -> Result<Arc<dyn FileDescription>, SyscallError> {
+        self.descriptors
+            .get_mut(descriptor)
+            .and_then(|file| file.take())
+            .ok_or(SyscallError::BadDescriptor)
+    }
+
+    pub fn clear(&mut self) {
+        self.descriptors.clear();
+    }
+
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.descriptors.iter().filter(|file| file.is_some()).count()
+    }
+}
@@ -0,0 +1,414 @@
+use alloc::{
+    collections::BTreeMap,
+    string::{
+        String,
+        ToString
+    },
+    sync::{
+        Arc,
+        Weak
+    },
+    vec::Vec
+};
+
+use core::{
+    fmt,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use spin::Once;
+
+use x86_64::{
+    structures::paging::Page,
+    VirtAddr
+};
+
+use crate::{
+    architecture::syscall::{
+        self,
+        SyscallFrame
+    },
+    cmdline::param,
+    fs,
+    loader::{
+        self,
+        ElfError
+    },
+    memory::address_space::{
+        AddressSpace,
+        AddressSpaceError
+    },
+    scheduler::{
+        self,
+        thread::Thread
+    },
+    sync::{
+        Mutex,
+        Spinlock,
+        WaitQueue
+    },
+    syscall::SyscallError
+};
+
+pub mod files;
+
+use files::FileDescriptorTable;
+
+pub const INIT_PROCESS_ID: ProcessId = ProcessId(1);
+
+// `init=<path>`, a program to start as the first process.
+param!(pub INIT: &'static str = "init");
+
+static PROCESSES: Once<Spinlock<BTreeMap<ProcessId, Arc<Process>>>> = Once::new();
+static NEXT_PROCESS_ID: AtomicU64 = AtomicU64::new(INIT_PROCESS_ID.0);
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ProcessId(pub u64);
+
+impl fmt::Display for ProcessId {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExitStatus {
+    Exited(i32),
+
+    Killed(u8)
+}
+
+impl ExitStatus {
+    pub const SIGNAL_ILLEGAL_INSTRUCTION: u8 = 4;
+    pub const SIGNAL_TRAP: u8 = 5;
+    pub const SIGNAL_FLOATING_POINT: u8 = 8;
+    pub const SIGNAL_SEGMENTATION_FAULT: u8 = 11;
+
+    // Encoded the way `wait` reports it, so user space can use the usual `WIFEXITED` family of macros.
+    #[inline]
+    pub fn as_wait_status(self) -> i32 {
+        match self {
+            Self::Exited(code) => (code & 0xFF) << 8,
+            Self::Killed(signal) => signal as i32 & 0x7F
+        }
+    }
+}
+
+pub struct Process {
+    id: ProcessId,
+    name: String,
+    parent: Spinlock<Option<Weak<Process>>>,
+    children: Spinlock<Vec<Arc<Process>>>,
+    threads: Spinlock<Vec<Weak<Thread>>>,
+    address_space: Spinlock<Option<Arc<Mutex<AddressSpace>>>>,
+    files: Mutex<FileDescriptorTable>,
+    exit_status: Spinlock<Option<ExitStatus>>,
+    child_exited: WaitQueue
+}
+
+impl Process {
+    fn new(name: String, parent: Option<&Arc<Process>>, address_space: AddressSpace, files: FileDescriptorTable) -> Arc<Self> {
+        let process = Arc::new(Self {
+            id: ProcessId(NEXT_PROCESS_ID.fetch_add(1, Ordering::Relaxed)),
+            name,
+            parent: Spinlock::named("process_parent", parent.map(Arc::downgrade)),
+            children: Spinlock::named("process_children", Vec::new()),
+            threads: Spinlock::named("process_threads", Vec::new()),
+            address_space: Spinlock::named("process_address_space", Some(Arc::new(Mutex::named("address_space", address_space)))),
+            files: Mutex::named("file_descriptor_table", files),
+            exit_status: Spinlock::named("process_exit_status", None),
+            child_exited: WaitQueue::new()
+        });
+
+        if let Some(parent) = parent {
+            parent.children.lock().push(process.clone());
+        }
+
+        lightsaber_kernel_processes_table()
+            .lock()
+            .insert(process.id, process.clone());
+
+        process
+    }
+
+    #[inline]
+    pub fn id(&self) -> ProcessId {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn parent(&self) -> Option<Arc<Process>> {
+        self.parent.lock().as_ref().and_then(Weak::upgrade)
+    }
+
+    pub fn children(&self) -> Vec<Arc<Process>> {
+        self.children.lock().clone()
+    }
+
+    pub fn threads(&self) -> Vec<Arc<Thread>> {
+        self.threads
+            .lock()
+            .iter()
+            .filter_map(Weak::upgrade)
+            .collect()
+    }
+
+    pub fn address_space(&self) -> Option<Arc<Mutex<AddressSpace>>> {
+        self.address_space.lock().clone()
+    }
+
+    #[inline]
+    pub fn files(&self) -> &Mutex<FileDescriptorTable> {
+        &self.files
+    }
+
+    pub fn exit_status(&self) -> Option<ExitStatus> {
+        *self.exit_status.lock()
+    }
+
+    #[inline]
+    pub fn is_zombie(&self) -> bool {
+        self.exit_status().is_some()
+    }
+
+    fn spawn_thread<F>(self: &Arc<Self>, function: F) -> Arc<Thread>
+    where
+        F: FnOnce() + Send + 'static {
+        let thread = scheduler::lightsaber_kernel_spawn_in_process(self.name(), self.clone(), function);
+
+        self.threads.lock().push(Arc::downgrade(&thread));
+
+        thread
+    }
+}
+
+impl fmt::Debug for Process {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Process")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("exit_status", &self.exit_status())
+            .finish()
+    }
+}
+
+fn lightsaber_kernel_processes_table() -> &'static Spinlock<BTreeMap<ProcessId, Arc<Process>>> {
+    PROCESSES.call_once(|| Spinlock::named("processes", BTreeMap::new()))
+}
+
+pub fn lightsaber_kernel_current_process() -> Option<Arc<Process>> {
+    scheduler::lightsaber_kernel_current_thread().and_then(|thread| thread.process().cloned())
+}
+
+pub fn lightsaber_kernel_find_process(id: ProcessId) -> Option<Arc<Process>> {
+    lightsaber_kernel_processes_table().lock().get(&id).cloned()
+}
+
+pub fn lightsaber_kernel_processes() -> Vec<Arc<Process>> {
+    lightsaber_kernel_processes_table().lock().values().cloned().collect()
+}
+
+pub fn lightsaber_kernel_resolve_write_fault(address: VirtAddr) -> bool {
+    let address_space = match lightsaber_kernel_current_process().and_then(|process| process.address_space()) {
+        Some(address_space) => address_space,
+        None => return false
+    };
+
+    let result = address_space
+        .lock()
+        .resolve_copy_on_write(Page::containing_address(address));
+
+    matches!(result, Ok(true))
+}
+
+pub fn lightsaber_kernel_spawn_process(name: &str, image: &[u8], arguments: &[&str], environment: &[&str], parent: Option<&Arc<Process>>) -> Result<Arc<Process>, ElfError> {
+    let executable = loader::lightsaber_kernel_load_executable(image, arguments, environment, |path| fs::lightsaber_kernel_read_file(path).ok())?;
+
+    let files = match parent {
+        Some(parent) => parent.files.lock().clone(),
+        None => FileDescriptorTable::with_console()
+    };
+
+    let process = Process::new(name.to_string(), parent, executable.address_space, files);
+    let (entry, stack_pointer) = (executable.entry, executable.stack_pointer);
+
+    process.spawn_thread(move || unsafe {
+        syscall::lightsaber_kernel_enter_user_mode(entry.as_u64(), stack_pointer.as_u64())
+    });
+
+    log::debug!("Spawned process {} (`{}`).", process.id(), process.name());
+
+    Ok(process)
+}
+
+pub fn lightsaber_kernel_fork(frame: &SyscallFrame) -> Result<Arc<Process>, AddressSpaceError> {
+    let parent = lightsaber_kernel_current_process().expect("Forking outside of a process.");
+
+    let address_space = parent
+        .address_space()
+        .ok_or(AddressSpaceError::NotMapped)?
+        .lock()
+        .try_clone()?;
+
+    let files = parent.files.lock().clone();
+    let child = Process::new(parent.name().to_string(), Some(&parent), address_space, files);
+
+    let mut child_frame = *frame;
+    child_frame.set_return_value(0);
+
+    child.spawn_thread(move || unsafe {
+        syscall::lightsaber_kernel_return_to_user(&child_frame)
+    });
+
+    Ok(child)
+}
+
+// The first process spawned gets the init process ID; the debug shell keeps running alongside it.
+pub fn lightsaber_kernel_start_init() {
+    let path = match INIT.get() {
+        Some(path) => path,
+        None => return
+    };
+
+    let image = match fs::lightsaber_kernel_read_file(path) {
+        Ok(image) => image,
+        Err(error) => {
+            log::error!("Failed to read init `{}`: {}", path, error);
+
+            return;
+        }
+    };
+
+    match lightsaber_kernel_spawn_process(path, &image, &[path], &[], None) {
+        Ok(process) => log::info!("Started init `{}` as process {}.", path, process.id()),
+        Err(error) => log::error!("Failed to start init `{}`: {}", path, error)
+    }
+}
+
+fn lightsaber_kernel_reparent_children(process: &Arc<Process>) {
+    let children = core::mem::take(&mut *process.children.lock());
+
+    let init = lightsaber_kernel_find_process(INIT_PROCESS_ID)
+        .filter(|init| !Arc::ptr_eq(init, process) && !init.is_zombie());
+
+    for child in children {
+        match &init {
+            Some(init) => {
+                *child.parent.lock() = Some(Arc::downgrade(init));
+                init.children.lock().push(child.clone());
+
+                if child.is_zombie() {
+                    init.child_exited.wake_all();
+                }
+            }
+            None => {
+                *child.parent.lock() = None;
+
+                // Nobody is left to wait for an orphaned zombie, so it is reaped right away.
+                if child.is_zombie() {
+                    lightsaber_kernel_processes_table().lock().remove(&child.id);
+                }
+            }
+        }
+    }
+}
+
+pub fn lightsaber_kernel_exit_process(status: ExitStatus) -> ! {
+    let process = match lightsaber_kernel_current_process() {
+        Some(process) => process,
+        None => scheduler::lightsaber_kernel_exit_thread()
+    };
+
+    {
+        let mut exit_status = process.exit_status.lock();
+
+        if exit_status.is_some() {
+            drop(exit_status);
+            scheduler::lightsaber_kernel_exit_thread();
+        }
+
+        *exit_status = Some(status);
+    }
+
+    let current = scheduler::lightsaber_kernel_current_thread();
+
+    for thread in process.threads() {
+        if current.as_ref().map(|current| !Arc::ptr_eq(current, &thread)).unwrap_or(true) {
+            scheduler::lightsaber_kernel_kill(&thread);
+        }
+    }
+
+    process.files.lock().clear();
+
+    // The exiting threads keep the page tables alive until they are reaped, which happens only after they are switched away from.
+    process.address_space.lock().take();
+
+    lightsaber_kernel_reparent_children(&process);
+
+    log::debug!("Process {} (`{}`) exited with {:?}.", process.id(), process.name(), status);
+
+    match process.parent() {
+        Some(parent) => {
+            parent.child_exited.wake_all();
+        }
+        None => {
+            lightsaber_kernel_processes_table().lock().remove(&process.id);
+        }
+    }
+
+    drop(process);
+    scheduler::lightsaber_kernel_exit_thread()
+}
+
+pub fn lightsaber_kernel_wait(id: Option<ProcessId>, no_hang: bool) -> Result<Option<(ProcessId, ExitStatus)>, SyscallError> {
+    let process = lightsaber_kernel_current_process().ok_or(SyscallError::NoChild)?;
+
+    let matches = |child: &Arc<Process>| id.map(|id| child.id == id).unwrap_or(true);
+    let mut reaped = None;
+
+    let mut try_reap = || -> Result<bool, SyscallError> {
+        let mut children = process.children.lock();
+
+        if !children.iter().any(|child| matches(child)) {
+            return Err(SyscallError::NoChild);
+        }
+
+        if let Some(index) = children.iter().position(|child| matches(child) && child.is_zombie()) {
+            let child = children.remove(index);
+            lightsaber_kernel_processes_table().lock().remove(&child.id);
+
+            reaped = Some((child.id, child.exit_status().unwrap()));
+
+            return Ok(true);
+        }
+
+        Ok(false)
+    };
+
+    if try_reap()? || no_hang {
+        return Ok(reaped);
+    }
+
+    let mut error = None;
+
+    process.child_exited.wait_until(|| match try_reap() {
+        Ok(done) => done,
+        Err(failure) => {
+            error = Some(failure);
+            true
+        }
+    })?;
+
+    match error {
+        Some(error) => Err(error),
+        None => Ok(reaped)
+    }
+}
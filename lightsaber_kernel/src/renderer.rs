@@ -3,10 +3,7 @@ use core::fmt::{
     Write
 };
 
-use spin::{
-    Mutex,
-    Once
-};
+use spin::Once;
 
 use lightsaber_graphics::{
     debug::{
@@ -16,7 +13,9 @@ use lightsaber_graphics::{
     Framebuffer
 };
 
-static DEBUG_RENDERER: Once<Mutex<DebugRenderer>> = Once::new();
+use crate::sync::Spinlock;
+
+static DEBUG_RENDERER: Once<Spinlock<DebugRenderer>> = Once::new();
 
 pub fn lightsaber_kernel_initialize_renderer(framebuffer: &'static mut Framebuffer) {
     let information = framebuffer.information();
@@ -25,7 +24,13 @@ pub fn lightsaber_kernel_initialize_renderer(framebuffer: &'static mut Framebuff
     let mut renderer = DebugRenderer::new(buffer, information);
     renderer.clear_screen();
 
-    DEBUG_RENDERER.call_once(|| Mutex::new(renderer));
+    DEBUG_RENDERER.call_once(|| Spinlock::named("debug_renderer", renderer));
+}
+
+pub unsafe fn lightsaber_kernel_force_unlock_renderer() {
+    if let Some(renderer) = DEBUG_RENDERER.get() {
+        renderer.force_unlock();
+    }
 }
 
 pub fn lightsaber_kernel_set_colour_code(colour_code: ColourCode) {
@@ -0,0 +1,330 @@
+use alloc::{
+    boxed::Box,
+    collections::{
+        BTreeMap,
+        VecDeque
+    },
+    string::{
+        String,
+        ToString
+    },
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicBool,
+        AtomicU64,
+        Ordering
+    }
+};
+
+use spin::Once;
+
+use x86_64::registers::control::{
+    Cr3,
+    Cr3Flags
+};
+
+use crate::{
+    architecture::{
+        context,
+        gdt,
+        interrupts
+    },
+    memory::address_space,
+    process::Process,
+    sync::Spinlock
+};
+
+pub mod thread;
+
+use thread::{
+    KernelStack,
+    Thread,
+    ThreadId,
+    ThreadState,
+    KERNEL_STACK_SIZE
+};
+
+static SCHEDULER: Once<Spinlock<Scheduler>> = Once::new();
+static NEXT_THREAD_ID: AtomicU64 = AtomicU64::new(0);
+// Readable without the scheduler lock, for lock tracking; it changes only once that lock is released.
+static CURRENT_THREAD_ID: AtomicU64 = AtomicU64::new(0);
+
+const TIME_SLICE_TICKS: u64 = 5;
+
+static TIME_SLICE_REMAINING: AtomicU64 = AtomicU64::new(TIME_SLICE_TICKS);
+static RESCHEDULE_PENDING: AtomicBool = AtomicBool::new(false);
+
+type ThreadFunction = Box<dyn FnOnce() + Send + 'static>;
+
+struct Scheduler {
+    current: Arc<Thread>,
+    idle: Arc<Thread>,
+    run_queue: VecDeque<Arc<Thread>>,
+    threads: BTreeMap<ThreadId, Arc<Thread>>,
+    exited: Vec<Arc<Thread>>
+}
+
+impl Scheduler {
+    fn reap_exited_threads(&mut self) {
+        let current = self.current.id();
+        let threads = &mut self.threads;
+
+        self.exited.retain(|thread| {
+            if thread.id() == current {
+                return true;
+            }
+
+            threads.remove(&thread.id());
+            false
+        });
+    }
+}
+
+fn lightsaber_kernel_allocate_thread_id() -> ThreadId {
+    ThreadId(NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed))
+}
+
+fn lightsaber_kernel_create_thread(name: String, process: Option<Arc<Process>>, function: ThreadFunction) -> Arc<Thread> {
+    let kernel_stack = KernelStack::new(KERNEL_STACK_SIZE);
+    let stack_top = kernel_stack.top();
+
+    let thread = Arc::new(Thread::new(lightsaber_kernel_allocate_thread_id(), name, Some(kernel_stack), process));
+    let argument = Box::into_raw(Box::new(function)) as usize;
+
+    unsafe {
+        *thread.stack_pointer() = context::lightsaber_kernel_initialize_context(stack_top, lightsaber_kernel_thread_entry, argument);
+    }
+
+    thread
+}
+
+extern "C" fn lightsaber_kernel_thread_entry(argument: usize) -> ! {
+    let function = unsafe {
+        Box::from_raw(argument as *mut ThreadFunction)
+    };
+
+    function();
+
+    lightsaber_kernel_exit_thread()
+}
+
+fn lightsaber_kernel_idle_thread() {
+    loop {
+        lightsaber_kernel_schedule();
+
+        if interrupts::lightsaber_kernel_interrupts_enabled() {
+            unsafe {
+                interrupts::lightsaber_kernel_halt();
+            }
+        }
+        else {
+            hint::spin_loop();
+        }
+    }
+}
+
+pub fn lightsaber_kernel_initialize_scheduler() {
+    let boot_thread = Arc::new(Thread::new(lightsaber_kernel_allocate_thread_id(), "kernel".to_string(), None, None));
+    boot_thread.set_state(ThreadState::Running);
+
+    let idle_thread = lightsaber_kernel_create_thread("idle".to_string(), None, Box::new(lightsaber_kernel_idle_thread));
+
+    let mut threads = BTreeMap::new();
+    threads.insert(boot_thread.id(), boot_thread.clone());
+    threads.insert(idle_thread.id(), idle_thread.clone());
+
+    SCHEDULER.call_once(|| Spinlock::named("scheduler", Scheduler {
+        current: boot_thread,
+        idle: idle_thread,
+        run_queue: VecDeque::new(),
+        threads,
+        exited: Vec::new()
+    }));
+
+    log::info!("Initialized scheduler.");
+}
+
+fn lightsaber_kernel_enqueue_new_thread(thread: Arc<Thread>) -> Arc<Thread> {
+    let mut scheduler = SCHEDULER
+        .get()
+        .expect("The scheduler is not initialized.")
+        .lock();
+
+    scheduler.threads.insert(thread.id(), thread.clone());
+    scheduler.run_queue.push_back(thread.clone());
+
+    thread
+}
+
+pub fn lightsaber_kernel_spawn<F>(name: &str, function: F) -> Arc<Thread>
+where
+    F: FnOnce() + Send + 'static {
+    lightsaber_kernel_enqueue_new_thread(lightsaber_kernel_create_thread(name.to_string(), None, Box::new(function)))
+}
+
+pub fn lightsaber_kernel_spawn_in_process<F>(name: &str, process: Arc<Process>, function: F) -> Arc<Thread>
+where
+    F: FnOnce() + Send + 'static {
+    lightsaber_kernel_enqueue_new_thread(lightsaber_kernel_create_thread(name.to_string(), Some(process), Box::new(function)))
+}
+
+pub fn lightsaber_kernel_current_thread() -> Option<Arc<Thread>> {
+    SCHEDULER.get().map(|scheduler| scheduler.lock().current.clone())
+}
+
+// The boot thread, whose identifier is zero, until the scheduler first switches away from it.
+#[cfg(any(test, feature = "lockdep"))]
+pub fn lightsaber_kernel_current_thread_id() -> ThreadId {
+    ThreadId(CURRENT_THREAD_ID.load(Ordering::Relaxed))
+}
+
+pub fn lightsaber_kernel_threads() -> Vec<Arc<Thread>> {
+    SCHEDULER
+        .get()
+        .map(|scheduler| scheduler.lock().threads.values().cloned().collect())
+        .unwrap_or_default()
+}
+
+// For the debugger, which can stop the kernel while the scheduler lock is held and must not allocate.
+pub fn lightsaber_kernel_try_for_each_thread<F>(mut function: F) -> bool
+where
+    F: FnMut(&Arc<Thread>, bool) {
+    let scheduler = match SCHEDULER.get().and_then(|scheduler| scheduler.try_lock()) {
+        Some(scheduler) => scheduler,
+        None => return false
+    };
+
+    for thread in scheduler.threads.values() {
+        function(thread, Arc::ptr_eq(thread, &scheduler.current));
+    }
+
+    true
+}
+
+pub fn lightsaber_kernel_wake(thread: &Arc<Thread>) -> bool {
+    if !thread.transition_state(ThreadState::Blocked, ThreadState::Runnable) {
+        return false;
+    }
+
+    SCHEDULER
+        .get()
+        .expect("The scheduler is not initialized.")
+        .lock()
+        .run_queue
+        .push_back(thread.clone());
+
+    true
+}
+
+// A killed thread is not torn down on the spot; it exits the next time it is about to return to user mode.
+pub fn lightsaber_kernel_kill(thread: &Arc<Thread>) {
+    thread.kill();
+    lightsaber_kernel_wake(thread);
+}
+
+pub fn lightsaber_kernel_exit_if_killed() {
+    if lightsaber_kernel_current_thread().map(|current| current.is_killed()).unwrap_or(false) {
+        lightsaber_kernel_exit_thread();
+    }
+}
+
+pub fn lightsaber_kernel_schedule() {
+    let scheduler = match SCHEDULER.get() {
+        Some(scheduler) => scheduler,
+        None => return
+    };
+
+    let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+
+    let switch = {
+        let mut scheduler = scheduler.lock();
+        scheduler.reap_exited_threads();
+
+        let current = scheduler.current.clone();
+        let current_is_idle = Arc::ptr_eq(&current, &scheduler.idle);
+
+        match current.state() {
+            ThreadState::Running if !current_is_idle => {
+                current.set_state(ThreadState::Runnable);
+                scheduler.run_queue.push_back(current.clone());
+            }
+            ThreadState::Running => current.set_state(ThreadState::Runnable),
+            ThreadState::Exited => scheduler.exited.push(current.clone()),
+            ThreadState::Runnable | ThreadState::Blocked => { }
+        }
+
+        let next = scheduler.run_queue.pop_front().unwrap_or_else(|| scheduler.idle.clone());
+        next.set_state(ThreadState::Running);
+
+        if Arc::ptr_eq(&next, &current) {
+            None
+        }
+        else {
+            if let Some(kernel_stack) = next.kernel_stack() {
+                gdt::lightsaber_kernel_set_kernel_stack(kernel_stack.top() as u64);
+            }
+
+            let level_four_frame = next.level_four_frame().unwrap_or_else(address_space::lightsaber_kernel_kernel_level_four_frame);
+
+            if Cr3::read().0 != level_four_frame {
+                unsafe {
+                    Cr3::write(level_four_frame, Cr3Flags::empty());
+                }
+            }
+
+            let switch = (current.stack_pointer(), unsafe { *next.stack_pointer() }, next.id());
+            scheduler.current = next;
+
+            Some(switch)
+        }
+    };
+
+    TIME_SLICE_REMAINING.store(TIME_SLICE_TICKS, Ordering::Relaxed);
+    RESCHEDULE_PENDING.store(false, Ordering::Relaxed);
+
+    if let Some((old_stack_pointer, new_stack_pointer, next_id)) = switch {
+        CURRENT_THREAD_ID.store(next_id.0, Ordering::Relaxed);
+
+        unsafe {
+            context::lightsaber_kernel_switch_context(old_stack_pointer, new_stack_pointer);
+        }
+    }
+
+    interrupts::lightsaber_kernel_restore_interrupts(interrupts_enabled);
+}
+
+pub fn lightsaber_kernel_tick() {
+    let remaining = TIME_SLICE_REMAINING.load(Ordering::Relaxed);
+
+    if remaining <= 1 {
+        RESCHEDULE_PENDING.store(true, Ordering::Relaxed);
+    }
+    else {
+        TIME_SLICE_REMAINING.store(remaining - 1, Ordering::Relaxed);
+    }
+}
+
+pub fn lightsaber_kernel_preempt() {
+    if RESCHEDULE_PENDING.swap(false, Ordering::AcqRel) {
+        lightsaber_kernel_schedule();
+    }
+}
+
+pub fn lightsaber_kernel_yield() {
+    lightsaber_kernel_schedule();
+}
+
+pub fn lightsaber_kernel_exit_thread() -> ! {
+    if let Some(current) = lightsaber_kernel_current_thread() {
+        current.set_state(ThreadState::Exited);
+    }
+
+    lightsaber_kernel_schedule();
+
+    unreachable!("An exited thread was scheduled again.");
+}
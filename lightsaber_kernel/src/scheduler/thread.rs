@@ -0,0 +1,225 @@
+use alloc::{
+    alloc::{
+        self as allocator,
+        Layout
+    },
+    string::String,
+    sync::Arc
+};
+
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    mem,
+    sync::atomic::{
+        AtomicBool,
+        AtomicU8,
+        Ordering
+    }
+};
+
+use x86_64::structures::paging::PhysFrame;
+
+use crate::{
+    architecture::context::SavedContext,
+    memory::address_space::AddressSpace,
+    process::Process,
+    sync::Mutex
+};
+
+pub const KERNEL_STACK_SIZE: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct ThreadId(pub u64);
+
+impl fmt::Display for ThreadId {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u8)]
+pub enum ThreadState {
+    Running,
+
+    Runnable,
+
+    Blocked,
+
+    Exited
+}
+
+impl ThreadState {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Self::Running,
+            1 => Self::Runnable,
+            2 => Self::Blocked,
+            _ => Self::Exited
+        }
+    }
+}
+
+pub struct KernelStack {
+    bottom: *mut u8,
+    size: usize
+}
+
+impl KernelStack {
+    pub fn new(size: usize) -> Self {
+        let layout = Layout::from_size_align(size, 4096).expect("Invalid kernel stack layout.");
+
+        let bottom = unsafe {
+            allocator::alloc(layout)
+        };
+
+        if bottom.is_null() {
+            allocator::handle_alloc_error(layout);
+        }
+
+        Self {
+            bottom,
+            size
+        }
+    }
+
+    #[inline]
+    pub fn top(&self) -> usize {
+        self.bottom as usize + self.size
+    }
+}
+
+impl Drop for KernelStack {
+    fn drop(&mut self) {
+        unsafe {
+            allocator::dealloc(self.bottom, Layout::from_size_align_unchecked(self.size, 4096));
+        }
+    }
+}
+
+pub struct Thread {
+    id: ThreadId,
+    name: String,
+    state: AtomicU8,
+    stack_pointer: UnsafeCell<usize>,
+    kernel_stack: Option<KernelStack>,
+    process: Option<Arc<Process>>,
+    // Keeps the tables behind `level_four_frame` alive while the thread can still be switched to.
+    _address_space: Option<Arc<Mutex<AddressSpace>>>,
+    level_four_frame: Option<PhysFrame>,
+    killed: AtomicBool
+}
+
+unsafe impl Send for Thread { }
+unsafe impl Sync for Thread { }
+
+impl Thread {
+    pub(in crate::scheduler) fn new(id: ThreadId, name: String, kernel_stack: Option<KernelStack>, process: Option<Arc<Process>>) -> Self {
+        let address_space = process.as_ref().and_then(|process| process.address_space());
+
+        let level_four_frame = address_space
+            .as_ref()
+            .map(|address_space| address_space.lock().level_four_frame());
+
+        Self {
+            id,
+            name,
+            state: AtomicU8::new(ThreadState::Runnable as u8),
+            stack_pointer: UnsafeCell::new(0),
+            kernel_stack,
+            process,
+            _address_space: address_space,
+            level_four_frame,
+            killed: AtomicBool::new(false)
+        }
+    }
+
+    #[inline]
+    pub fn id(&self) -> ThreadId {
+        self.id
+    }
+
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[inline]
+    pub fn state(&self) -> ThreadState {
+        ThreadState::from_u8(self.state.load(Ordering::Acquire))
+    }
+
+    #[inline]
+    pub fn set_state(&self, state: ThreadState) {
+        self.state.store(state as u8, Ordering::Release);
+    }
+
+    #[inline]
+    pub fn transition_state(&self, from: ThreadState, to: ThreadState) -> bool {
+        self.state.compare_exchange(from as u8, to as u8, Ordering::AcqRel, Ordering::Acquire).is_ok()
+    }
+
+    #[inline]
+    pub fn kernel_stack(&self) -> Option<&KernelStack> {
+        self.kernel_stack.as_ref()
+    }
+
+    #[inline]
+    pub fn process(&self) -> Option<&Arc<Process>> {
+        self.process.as_ref()
+    }
+
+    #[inline]
+    pub fn level_four_frame(&self) -> Option<PhysFrame> {
+        self.level_four_frame
+    }
+
+    #[inline]
+    pub fn is_killed(&self) -> bool {
+        self.killed.load(Ordering::Acquire)
+    }
+
+    #[inline]
+    pub(in crate::scheduler) fn kill(&self) {
+        self.killed.store(true, Ordering::Release);
+    }
+
+    #[inline]
+    pub(in crate::scheduler) fn stack_pointer(&self) -> *mut usize {
+        self.stack_pointer.get()
+    }
+
+    // The registers a switched-out thread will resume with, and the stack pointer after they are restored.
+    pub fn saved_context(&self) -> Option<(SavedContext, u64)> {
+        if self.state() == ThreadState::Running || self.state() == ThreadState::Exited {
+            return None;
+        }
+
+        let stack_pointer = unsafe {
+            *self.stack_pointer.get()
+        };
+
+        match stack_pointer {
+            0 => None,
+            stack_pointer => {
+                let context = unsafe {
+                    (stack_pointer as *const SavedContext).read()
+                };
+
+                Some((context, (stack_pointer + mem::size_of::<SavedContext>()) as u64))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Thread {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Thread")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("state", &self.state())
+            .finish()
+    }
+}
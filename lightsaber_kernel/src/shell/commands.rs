@@ -0,0 +1,687 @@
+use alloc::{
+    format,
+    string::{
+        String,
+        ToString
+    },
+    vec::Vec
+};
+
+use core::{
+    fmt::Write,
+    ptr
+};
+
+use x86_64::{
+    structures::paging::PageTableFlags,
+    VirtAddr
+};
+
+use lightsaber_bootloader::MemoryRegionType;
+
+use crate::{
+    acpi,
+    architecture::{
+        apic,
+        power,
+        processor
+    },
+    block,
+    cmdline,
+    console::ConsoleWriter,
+    drivers::ps2::keymap::{
+        self,
+        Keymap
+    },
+    fs::{
+        mount,
+        FsError
+    },
+    gdb,
+    input,
+    logger,
+    memory::{
+        self,
+        frame,
+        heap,
+        paging
+    },
+    pci,
+    process,
+    scheduler,
+    shell::{
+        Shell,
+        ShellCommand,
+        ShellError
+    },
+    time
+};
+
+const INPUT_DEFAULT_COUNT: u64 = 16;
+const PEEK_DEFAULT_LENGTH: u64 = 64;
+const PEEK_MAXIMUM_LENGTH: u64 = 4096;
+const PEEK_BYTES_PER_LINE: usize = 16;
+const PAGE_SIZE: u64 = 4096;
+
+pub const SHELL_COMMANDS: &[ShellCommand] = &[
+    ShellCommand {
+        name: "help",
+        usage: "help",
+        description: "List the commands.",
+        handler: lightsaber_kernel_shell_help
+    },
+    ShellCommand {
+        name: "meminfo",
+        usage: "meminfo",
+        description: "Show physical memory and kernel heap usage.",
+        handler: lightsaber_kernel_shell_meminfo
+    },
+    ShellCommand {
+        name: "regions",
+        usage: "regions",
+        description: "Dump the memory map the bootloader passed in.",
+        handler: lightsaber_kernel_shell_regions
+    },
+    ShellCommand {
+        name: "cpuinfo",
+        usage: "cpuinfo",
+        description: "Identify the processor and list its features.",
+        handler: lightsaber_kernel_shell_cpuinfo
+    },
+    ShellCommand {
+        name: "acpi",
+        usage: "acpi",
+        description: "List the ACPI tables.",
+        handler: lightsaber_kernel_shell_acpi
+    },
+    ShellCommand {
+        name: "pci",
+        usage: "pci",
+        description: "List the PCI devices and their drivers.",
+        handler: lightsaber_kernel_shell_pci
+    },
+    ShellCommand {
+        name: "disks",
+        usage: "disks",
+        description: "List the block devices and their I/O statistics.",
+        handler: lightsaber_kernel_shell_disks
+    },
+    ShellCommand {
+        name: "df",
+        usage: "df",
+        description: "List the mounted file systems and how full they are.",
+        handler: lightsaber_kernel_shell_df
+    },
+    ShellCommand {
+        name: "umount",
+        usage: "umount <path>",
+        description: "Unmount the file system mounted on a directory.",
+        handler: lightsaber_kernel_shell_umount
+    },
+    ShellCommand {
+        name: "sync",
+        usage: "sync",
+        description: "Write the file systems and disk caches back.",
+        handler: lightsaber_kernel_shell_sync
+    },
+    ShellCommand {
+        name: "dmesg",
+        usage: "dmesg",
+        description: "Print the kernel log.",
+        handler: lightsaber_kernel_shell_dmesg
+    },
+    ShellCommand {
+        name: "cmdline",
+        usage: "cmdline",
+        description: "Print the kernel command line.",
+        handler: lightsaber_kernel_shell_cmdline
+    },
+    ShellCommand {
+        name: "keymap",
+        usage: "keymap [layout | path]",
+        description: "Show the keyboard layout, or switch to a built-in one or one loaded from a file.",
+        handler: lightsaber_kernel_shell_keymap
+    },
+    ShellCommand {
+        name: "input",
+        usage: "input [count]",
+        description: "Print the next keyboard and mouse events.",
+        handler: lightsaber_kernel_shell_input
+    },
+    ShellCommand {
+        name: "threads",
+        usage: "threads",
+        description: "List the threads.",
+        handler: lightsaber_kernel_shell_threads
+    },
+    ShellCommand {
+        name: "ps",
+        usage: "ps",
+        description: "List the processes.",
+        handler: lightsaber_kernel_shell_ps
+    },
+    ShellCommand {
+        name: "peek",
+        usage: "peek <address> [length]",
+        description: "Dump kernel memory.",
+        handler: lightsaber_kernel_shell_peek
+    },
+    ShellCommand {
+        name: "poke",
+        usage: "poke <address> <byte>...",
+        description: "Write bytes to kernel memory.",
+        handler: lightsaber_kernel_shell_poke
+    },
+    ShellCommand {
+        name: "history",
+        usage: "history",
+        description: "List the previous commands.",
+        handler: lightsaber_kernel_shell_history
+    },
+    ShellCommand {
+        name: "gdb",
+        usage: "gdb",
+        description: "Stop the kernel and wait for GDB on the serial line.",
+        handler: lightsaber_kernel_shell_gdb
+    },
+    ShellCommand {
+        name: "uptime",
+        usage: "uptime",
+        description: "Show how long the kernel has been running.",
+        handler: lightsaber_kernel_shell_uptime
+    },
+    ShellCommand {
+        name: "reboot",
+        usage: "reboot",
+        description: "Restart the machine.",
+        handler: lightsaber_kernel_shell_reboot
+    },
+    ShellCommand {
+        name: "shutdown",
+        usage: "shutdown",
+        description: "Power the machine off.",
+        handler: lightsaber_kernel_shell_shutdown
+    }
+];
+
+// Accepts hexadecimal with a 0x prefix, and decimal otherwise.
+fn lightsaber_kernel_parse_number(text: &str) -> Result<u64, ShellError> {
+    let result = match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hexadecimal) => u64::from_str_radix(&hexadecimal.replace('_', ""), 16),
+        None => text.replace('_', "").parse()
+    };
+
+    result.map_err(|_| ShellError::InvalidNumber)
+}
+
+fn lightsaber_kernel_no_arguments(arguments: &[&str], usage: &'static str) -> Result<(), ShellError> {
+    match arguments.is_empty() {
+        true => Ok(()),
+        false => Err(ShellError::Usage(usage))
+    }
+}
+
+// File systems first, so that what they write back reaches the disks.
+fn lightsaber_kernel_write_back() -> Result<(), FsError> {
+    let result = mount::lightsaber_kernel_sync_all();
+
+    block::lightsaber_kernel_sync_block_devices();
+
+    result
+}
+
+// Every page in the range has to be mapped, and writable when writing, or the access would fault.
+fn lightsaber_kernel_check_range(start: u64, length: u64, write: bool) -> Result<VirtAddr, ShellError> {
+    let end = start.checked_add(length).ok_or(ShellError::NotMapped(start))?;
+    let address = VirtAddr::try_new(start).map_err(|_| ShellError::NotMapped(start))?;
+    let mut page = start & !(PAGE_SIZE - 1);
+
+    while page < end {
+        let page_address = VirtAddr::try_new(page).map_err(|_| ShellError::NotMapped(page.max(start)))?;
+
+        let flags = match paging::lightsaber_kernel_translate_flags(page_address) {
+            Some(flags) => flags,
+            None => return Err(ShellError::NotMapped(page.max(start)))
+        };
+
+        if write && !flags.contains(PageTableFlags::WRITABLE) {
+            return Err(ShellError::NotWritable(page.max(start)));
+        }
+
+        page += PAGE_SIZE;
+    }
+
+    Ok(address)
+}
+
+fn lightsaber_kernel_shell_help(_shell: &mut Shell, _arguments: &[&str]) -> Result<(), ShellError> {
+    let width = SHELL_COMMANDS.iter().map(|command| command.usage.len()).max().unwrap_or(0);
+
+    for command in SHELL_COMMANDS {
+        let _ = writeln!(ConsoleWriter, "  {:width$}  {}", command.usage, command.description, width = width);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_meminfo(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "meminfo")?;
+
+    let frames = frame::lightsaber_kernel_frame_statistics();
+    let heap = heap::lightsaber_kernel_heap_statistics();
+    let used_frames = frames.total_frames - frames.free_frames;
+
+    let _ = writeln!(
+        ConsoleWriter,
+        "Physical memory: {} KiB used of {} KiB ({} KiB free).",
+        used_frames * 4,
+        frames.total_frames * 4,
+        frames.free_frames * 4
+    );
+
+    let _ = writeln!(
+        ConsoleWriter,
+        "Kernel heap:     {} KiB used of {} KiB ({} KiB free).",
+        heap.used / 1024,
+        heap.size / 1024,
+        (heap.size - heap.used) / 1024
+    );
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_regions(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "regions")?;
+
+    let regions = memory::lightsaber_kernel_memory_regions();
+    let mut usable = 0;
+
+    for (index, region) in regions.iter().enumerate() {
+        let size = region.end - region.start;
+
+        if region.r#type == MemoryRegionType::Usable {
+            usable += size;
+        }
+
+        let _ = writeln!(
+            ConsoleWriter,
+            "{:3}  {:#014x}-{:#014x}  {:>10} KiB  {:?}",
+            index,
+            region.start,
+            region.end,
+            size / 1024,
+            region.r#type
+        );
+    }
+
+    let _ = writeln!(ConsoleWriter, "{} regions, {} KiB usable.", regions.len(), usable / 1024);
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_cpuinfo(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "cpuinfo")?;
+
+    let identity = processor::lightsaber_kernel_processor_identity();
+    let processors = acpi::lightsaber_kernel_processor_apic_ids();
+
+    let _ = writeln!(ConsoleWriter, "Vendor:     {}", identity.vendor);
+
+    if !identity.brand.is_empty() {
+        let _ = writeln!(ConsoleWriter, "Model name: {}", identity.brand);
+    }
+
+    let _ = writeln!(ConsoleWriter, "Family:     {:#x}, model {:#x}, stepping {}", identity.family, identity.model, identity.stepping);
+    let _ = writeln!(ConsoleWriter, "Features:   {}", identity.features.join(" "));
+    let _ = writeln!(ConsoleWriter, "Processors: {} (APIC IDs {:?})", processors.len().max(1), processors);
+    let _ = writeln!(ConsoleWriter, "Running on: APIC ID {}", apic::lightsaber_kernel_local_apic_id());
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_acpi(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "acpi")?;
+
+    let tables = acpi::lightsaber_kernel_acpi_tables();
+
+    if tables.is_empty() {
+        let _ = writeln!(ConsoleWriter, "No ACPI tables.");
+    }
+
+    for table in tables {
+        let _ = writeln!(
+            ConsoleWriter,
+            "{}  {:#012x}  {:>6} bytes  revision {}  OEM {}",
+            table.signature(),
+            table.address.as_u64(),
+            table.length,
+            table.revision,
+            table.oem_id()
+        );
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_pci(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "pci")?;
+
+    for device in pci::lightsaber_kernel_pci_devices() {
+        let _ = writeln!(
+            ConsoleWriter,
+            "{}  {:04x}:{:04x}  {:<32}  {}",
+            device.address,
+            device.vendor_id,
+            device.device_id,
+            device.class_name(),
+            device.driver().unwrap_or("-")
+        );
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_disks(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "disks")?;
+
+    let _ = writeln!(ConsoleWriter, "{:<12}  {:>10}  {:>8}  {:>8}  {:>6}  {:>10}  TYPE", "NAME", "SIZE (KiB)", "READS", "WRITES", "ERRORS", "CACHE HITS");
+
+    for device in block::lightsaber_kernel_block_devices() {
+        let statistics = device.statistics().snapshot();
+
+        let kind = match device.partition_kind() {
+            Some(kind) => format!("{}", kind),
+            None => String::from("disk")
+        };
+
+        let _ = writeln!(
+            ConsoleWriter,
+            "{:<12}  {:>10}  {:>8}  {:>8}  {:>6}  {:>10}  {}",
+            device.name(),
+            device.size() / 1024,
+            statistics.reads,
+            statistics.writes,
+            statistics.errors,
+            statistics.cache_hits,
+            kind
+        );
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_df(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "df")?;
+
+    let _ = writeln!(ConsoleWriter, "{:<6}  {:>10}  {:>10}  {:>10}  MOUNTED ON", "TYPE", "SIZE (KiB)", "USED", "FREE");
+
+    for mount in mount::lightsaber_kernel_mounts() {
+        let file_system = mount.file_system();
+
+        let (size, used, free) = match file_system.usage() {
+            Ok(usage) => ((usage.size / 1024).to_string(), ((usage.size - usage.free) / 1024).to_string(), (usage.free / 1024).to_string()),
+            Err(_) => ("-".into(), "-".into(), "-".into())
+        };
+
+        let _ = writeln!(ConsoleWriter, "{:<6}  {:>10}  {:>10}  {:>10}  {}", file_system.name(), size, used, free, mount.path());
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_umount(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    match arguments {
+        [path] => Ok(mount::lightsaber_kernel_unmount(path)?),
+        _ => Err(ShellError::Usage("umount <path>"))
+    }
+}
+
+fn lightsaber_kernel_shell_sync(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "sync")?;
+
+    Ok(lightsaber_kernel_write_back()?)
+}
+
+fn lightsaber_kernel_shell_dmesg(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "dmesg")?;
+
+    let log = logger::lightsaber_kernel_kernel_log();
+
+    let _ = write!(ConsoleWriter, "{}", String::from_utf8_lossy(&log));
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_cmdline(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "cmdline")?;
+
+    let _ = writeln!(ConsoleWriter, "{}", cmdline::lightsaber_kernel_command_line());
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_keymap(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    const USAGE: &str = "keymap [layout | path]";
+
+    match arguments {
+        [] => {
+            let built_in = keymap::lightsaber_kernel_built_in_keymaps().collect::<Vec<_>>();
+
+            let _ = writeln!(ConsoleWriter, "Using the {} layout; built in are {}.", keymap::lightsaber_kernel_keymap().name(), built_in.join(", "));
+        }
+        // Anything with a slash in it is a file rather than a layout name.
+        [path] if path.contains('/') => keymap::lightsaber_kernel_load_keymap(path)?,
+        [layout] => keymap::lightsaber_kernel_set_keymap(Keymap::built_in(layout)?),
+        _ => return Err(ShellError::Usage(USAGE))
+    }
+
+    Ok(())
+}
+
+// Keys typed meanwhile still reach the console as well.
+fn lightsaber_kernel_shell_input(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    let count = match arguments {
+        [] => INPUT_DEFAULT_COUNT,
+        [count] => lightsaber_kernel_parse_number(count)?,
+        _ => return Err(ShellError::Usage("input [count]"))
+    };
+
+    for _ in 0..count {
+        match input::lightsaber_kernel_wait_input_event() {
+            Ok(event) => {
+                let _ = writeln!(ConsoleWriter, "{:?}", event);
+            }
+            Err(_) => break
+        }
+    }
+
+    let dropped = input::lightsaber_kernel_dropped_input_events();
+
+    if dropped != 0 {
+        let _ = writeln!(ConsoleWriter, "{} events were dropped since boot.", dropped);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_threads(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "threads")?;
+
+    let _ = writeln!(ConsoleWriter, "{:>5}  {:<9}  {:>7}  NAME", "ID", "STATE", "PROCESS");
+
+    for thread in scheduler::lightsaber_kernel_threads() {
+        let process = match thread.process() {
+            Some(process) => process.id().0.to_string(),
+            None => "-".into()
+        };
+
+        let _ = writeln!(
+            ConsoleWriter,
+            "{:>5}  {:<9}  {:>7}  {}",
+            thread.id(),
+            format!("{:?}", thread.state()),
+            process,
+            thread.name()
+        );
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_ps(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "ps")?;
+
+    let _ = writeln!(ConsoleWriter, "{:>5}  {:>5}  {:>8}  {:>7}  {:>5}  {:<10}  NAME", "PID", "PPID", "CHILDREN", "THREADS", "FILES", "STATE");
+
+    for process in process::lightsaber_kernel_processes() {
+        let parent = match process.parent() {
+            Some(parent) => parent.id().0.to_string(),
+            None => "-".into()
+        };
+
+        let state = match process.exit_status() {
+            Some(status) => format!("{:?}", status),
+            None => "Running".into()
+        };
+
+        let _ = writeln!(
+            ConsoleWriter,
+            "{:>5}  {:>5}  {:>8}  {:>7}  {:>5}  {:<10}  {}",
+            process.id().0,
+            parent,
+            process.children().len(),
+            process.threads().len(),
+            process.files().lock().len(),
+            state,
+            process.name()
+        );
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_peek(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    const USAGE: &str = "peek <address> [length]";
+
+    let (start, length) = match arguments {
+        [address] => (lightsaber_kernel_parse_number(address)?, PEEK_DEFAULT_LENGTH),
+        [address, length] => (lightsaber_kernel_parse_number(address)?, lightsaber_kernel_parse_number(length)?.min(PEEK_MAXIMUM_LENGTH)),
+        _ => return Err(ShellError::Usage(USAGE))
+    };
+
+    let address = lightsaber_kernel_check_range(start, length, false)?;
+
+    let bytes: Vec<u8> = (0..length)
+        .map(|offset| unsafe {
+            ptr::read_volatile(address.as_ptr::<u8>().add(offset as usize))
+        })
+        .collect();
+
+    for (line, chunk) in bytes.chunks(PEEK_BYTES_PER_LINE).enumerate() {
+        let mut output = String::new();
+
+        let _ = write!(output, "{:016x}  ", start + (line * PEEK_BYTES_PER_LINE) as u64);
+
+        for index in 0..PEEK_BYTES_PER_LINE {
+            match chunk.get(index) {
+                Some(byte) => {
+                    let _ = write!(output, "{:02x} ", byte);
+                }
+                None => output.push_str("   ")
+            }
+        }
+
+        output.push(' ');
+
+        for byte in chunk {
+            output.push(match byte {
+                0x20..=0x7E => *byte as char,
+                _ => '.'
+            });
+        }
+
+        let _ = writeln!(ConsoleWriter, "{}", output);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_poke(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    const USAGE: &str = "poke <address> <byte>...";
+
+    if arguments.len() < 2 {
+        return Err(ShellError::Usage(USAGE));
+    }
+
+    let start = lightsaber_kernel_parse_number(arguments[0])?;
+    let mut bytes = Vec::new();
+
+    for argument in &arguments[1..] {
+        match lightsaber_kernel_parse_number(argument)? {
+            byte if byte <= u8::MAX as u64 => bytes.push(byte as u8),
+            _ => return Err(ShellError::InvalidNumber)
+        }
+    }
+
+    let address = lightsaber_kernel_check_range(start, bytes.len() as u64, true)?;
+
+    for (offset, byte) in bytes.iter().enumerate() {
+        unsafe {
+            ptr::write_volatile(address.as_mut_ptr::<u8>().add(offset), *byte);
+        }
+    }
+
+    let _ = writeln!(ConsoleWriter, "Wrote {} bytes at {:#x}.", bytes.len(), start);
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_history(shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "history")?;
+
+    for (index, line) in shell.editor().history().iter().enumerate() {
+        let _ = writeln!(ConsoleWriter, "{:4}  {}", index + 1, line);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_gdb(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "gdb")?;
+
+    if let Err(error) = gdb::lightsaber_kernel_gdb_attach() {
+        let _ = writeln!(ConsoleWriter, "{}", error);
+    }
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_uptime(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "uptime")?;
+
+    let milliseconds = time::lightsaber_kernel_uptime_milliseconds();
+
+    let _ = writeln!(ConsoleWriter, "Up {}.{:03} seconds.", milliseconds / 1000, milliseconds % 1000);
+
+    Ok(())
+}
+
+fn lightsaber_kernel_shell_reboot(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "reboot")?;
+
+    if let Err(error) = lightsaber_kernel_write_back() {
+        log::warn!("Failed to write the file systems back: {}", error);
+    }
+
+    power::lightsaber_kernel_reboot()
+}
+
+fn lightsaber_kernel_shell_shutdown(_shell: &mut Shell, arguments: &[&str]) -> Result<(), ShellError> {
+    lightsaber_kernel_no_arguments(arguments, "shutdown")?;
+
+    if let Err(error) = lightsaber_kernel_write_back() {
+        log::warn!("Failed to write the file systems back: {}", error);
+    }
+
+    power::lightsaber_kernel_shutdown()
+}
@@ -0,0 +1,327 @@
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use core::{
+    fmt::Write,
+    str
+};
+
+use crate::console::{
+    self,
+    ConsoleWriter
+};
+
+const HISTORY_CAPACITY: usize = 64;
+
+const CONTROL_A: u8 = 0x01;
+const CONTROL_C: u8 = 0x03;
+const CONTROL_E: u8 = 0x05;
+const CONTROL_K: u8 = 0x0B;
+const CONTROL_U: u8 = 0x15;
+const CONTROL_W: u8 = 0x17;
+const BACKSPACE: u8 = 0x08;
+const ESCAPE: u8 = 0x1B;
+const DELETE: u8 = 0x7F;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum EscapeState {
+    None,
+
+    Escape,
+
+    // Collecting the numeric parameter of a `CSI n ~` sequence.
+    Sequence(u8)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum EditorKey {
+    Character(char),
+
+    Enter,
+
+    Backspace,
+
+    Delete,
+
+    Left,
+
+    Right,
+
+    Home,
+
+    End,
+
+    Up,
+
+    Down,
+
+    Cancel,
+
+    KillToStart,
+
+    KillToEnd,
+
+    KillWord
+}
+
+// Reads lines from the console the way a VT100 terminal sends them, echoing edits back to it.
+pub struct LineEditor {
+    line: Vec<char>,
+    cursor: usize,
+    // How many characters the last redraw left on screen, so a shorter line can blank the rest.
+    drawn: usize,
+    history: Vec<String>,
+    // The history entry being shown and the line it replaced, while browsing.
+    browsing: Option<(usize, Vec<char>)>,
+    escape: EscapeState,
+    pending: Vec<u8>
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            line: Vec::new(),
+            cursor: 0,
+            drawn: 0,
+            history: Vec::new(),
+            browsing: None,
+            escape: EscapeState::None,
+            pending: Vec::new()
+        }
+    }
+
+    #[inline]
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    fn redraw(&mut self, prompt: &str) {
+        let line: String = self.line.iter().collect();
+        let blank = self.drawn.saturating_sub(self.line.len());
+        let mut output = String::new();
+
+        let _ = write!(output, "\r{}{}", prompt, line);
+
+        for _ in 0..blank {
+            output.push(' ');
+        }
+
+        for _ in 0..(blank + self.line.len() - self.cursor) {
+            output.push('\x08');
+        }
+
+        console::lightsaber_kernel_console_write(output.as_bytes());
+        self.drawn = self.line.len();
+    }
+
+    fn decode(&mut self, byte: u8) -> Option<EditorKey> {
+        match self.escape {
+            EscapeState::Escape => {
+                self.escape = match byte {
+                    b'[' | b'O' => EscapeState::Sequence(0),
+                    _ => EscapeState::None
+                };
+
+                return None;
+            }
+            EscapeState::Sequence(parameter) => {
+                if byte.is_ascii_digit() {
+                    self.escape = EscapeState::Sequence(parameter.saturating_mul(10).saturating_add(byte - b'0'));
+
+                    return None;
+                }
+
+                self.escape = EscapeState::None;
+
+                return match (byte, parameter) {
+                    (b'A', _) => Some(EditorKey::Up),
+                    (b'B', _) => Some(EditorKey::Down),
+                    (b'C', _) => Some(EditorKey::Right),
+                    (b'D', _) => Some(EditorKey::Left),
+                    (b'H', _) | (b'~', 1) | (b'~', 7) => Some(EditorKey::Home),
+                    (b'F', _) | (b'~', 4) | (b'~', 8) => Some(EditorKey::End),
+                    (b'~', 3) => Some(EditorKey::Delete),
+                    _ => None
+                };
+            }
+            EscapeState::None => { }
+        }
+
+        match byte {
+            ESCAPE => {
+                self.escape = EscapeState::Escape;
+
+                None
+            }
+            b'\n' | b'\r' => Some(EditorKey::Enter),
+            BACKSPACE | DELETE => Some(EditorKey::Backspace),
+            CONTROL_A => Some(EditorKey::Home),
+            CONTROL_E => Some(EditorKey::End),
+            CONTROL_C => Some(EditorKey::Cancel),
+            CONTROL_K => Some(EditorKey::KillToEnd),
+            CONTROL_U => Some(EditorKey::KillToStart),
+            CONTROL_W => Some(EditorKey::KillWord),
+            0x20..=0x7E => Some(EditorKey::Character(byte as char)),
+            // Anything else is either another control character or part of a UTF-8 sequence.
+            0x80..=0xFF => {
+                self.pending.push(byte);
+
+                match str::from_utf8(&self.pending) {
+                    Ok(string) => {
+                        let character = string.chars().next();
+
+                        self.pending.clear();
+                        character.map(EditorKey::Character)
+                    }
+                    Err(error) if error.error_len().is_some() => {
+                        self.pending.clear();
+
+                        None
+                    }
+                    Err(_) => None
+                }
+            }
+            _ => None
+        }
+    }
+
+    fn show_history(&mut self, index: usize) {
+        self.line = self.history[index].chars().collect();
+        self.cursor = self.line.len();
+    }
+
+    fn history_up(&mut self) {
+        let index = match &self.browsing {
+            Some((0, _)) => return,
+            Some((index, _)) => index - 1,
+            None if self.history.is_empty() => return,
+            None => {
+                self.browsing = Some((self.history.len() - 1, self.line.clone()));
+                self.history.len() - 1
+            }
+        };
+
+        if let Some((browsing, _)) = &mut self.browsing {
+            *browsing = index;
+        }
+
+        self.show_history(index);
+    }
+
+    fn history_down(&mut self) {
+        let (index, saved) = match self.browsing.take() {
+            Some(browsing) => browsing,
+            None => return
+        };
+
+        if index + 1 < self.history.len() {
+            self.browsing = Some((index + 1, saved));
+            self.show_history(index + 1);
+        }
+        else {
+            self.line = saved;
+            self.cursor = self.line.len();
+        }
+    }
+
+    fn reset(&mut self) {
+        self.line.clear();
+        self.cursor = 0;
+        self.drawn = 0;
+        self.browsing = None;
+    }
+
+    fn finish(&mut self) -> String {
+        let line: String = self.line.iter().collect();
+
+        self.reset();
+
+        let command = line.trim();
+
+        if !command.is_empty() && self.history.last().map(String::as_str) != Some(command) {
+            if self.history.len() == HISTORY_CAPACITY {
+                self.history.remove(0);
+            }
+
+            self.history.push(command.into());
+        }
+
+        line
+    }
+
+    // Returns None when the line is abandoned with Ctrl+C.
+    pub fn read_line(&mut self, prompt: &str) -> Option<String> {
+        let mut buffer = [0; 64];
+
+        self.redraw(prompt);
+
+        loop {
+            let count = match console::lightsaber_kernel_console_read(&mut buffer) {
+                Ok(count) => count,
+                Err(_) => return None
+            };
+
+            for byte in &buffer[..count] {
+                let key = match self.decode(*byte) {
+                    Some(key) => key,
+                    None => continue
+                };
+
+                match key {
+                    EditorKey::Character(character) => {
+                        self.line.insert(self.cursor, character);
+                        self.cursor += 1;
+                    }
+                    EditorKey::Enter => {
+                        console::lightsaber_kernel_console_write(b"\n");
+
+                        return Some(self.finish());
+                    }
+                    EditorKey::Cancel => {
+                        let _ = writeln!(ConsoleWriter, "^C");
+                        self.reset();
+
+                        return None;
+                    }
+                    EditorKey::Backspace if self.cursor > 0 => {
+                        self.cursor -= 1;
+                        self.line.remove(self.cursor);
+                    }
+                    EditorKey::Delete if self.cursor < self.line.len() => {
+                        self.line.remove(self.cursor);
+                    }
+                    EditorKey::Left => self.cursor = self.cursor.saturating_sub(1),
+                    EditorKey::Right => self.cursor = (self.cursor + 1).min(self.line.len()),
+                    EditorKey::Home => self.cursor = 0,
+                    EditorKey::End => self.cursor = self.line.len(),
+                    EditorKey::Up => self.history_up(),
+                    EditorKey::Down => self.history_down(),
+                    EditorKey::KillToStart => {
+                        self.line.drain(..self.cursor);
+                        self.cursor = 0;
+                    }
+                    EditorKey::KillToEnd => self.line.truncate(self.cursor),
+                    EditorKey::KillWord => {
+                        let mut start = self.cursor;
+
+                        while start > 0 && self.line[start - 1] == ' ' {
+                            start -= 1;
+                        }
+
+                        while start > 0 && self.line[start - 1] != ' ' {
+                            start -= 1;
+                        }
+
+                        self.line.drain(start..self.cursor);
+                        self.cursor = start;
+                    }
+                    _ => { }
+                }
+
+                self.redraw(prompt);
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+pub mod commands;
+pub mod editor;
+
+use alloc::vec::Vec;
+
+use core::fmt::{
+    self,
+    Write
+};
+
+use crate::{
+    console::ConsoleWriter,
+    drivers::ps2::keymap::KeymapError,
+    fs::FsError,
+    scheduler,
+    shell::{
+        commands::SHELL_COMMANDS,
+        editor::LineEditor
+    }
+};
+
+const SHELL_PROMPT: &str = "lightsaber> ";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ShellError {
+    Usage(&'static str),
+
+    InvalidNumber,
+
+    NotMapped(u64),
+
+    NotWritable(u64),
+
+    Keymap(KeymapError),
+
+    Fs(FsError)
+}
+
+impl fmt::Display for ShellError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(usage) => write!(formatter, "Usage: {}", usage),
+            Self::InvalidNumber => write!(formatter, "Not a number."),
+            Self::NotMapped(address) => write!(formatter, "{:#x} is not mapped.", address),
+            Self::NotWritable(address) => write!(formatter, "{:#x} is not writable.", address),
+            Self::Keymap(error) => write!(formatter, "{}", error),
+            Self::Fs(error) => write!(formatter, "{}", error)
+        }
+    }
+}
+
+impl From<KeymapError> for ShellError {
+    fn from(error: KeymapError) -> Self {
+        Self::Keymap(error)
+    }
+}
+
+impl From<FsError> for ShellError {
+    fn from(error: FsError) -> Self {
+        Self::Fs(error)
+    }
+}
+
+pub struct ShellCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub description: &'static str,
+    pub handler: fn(&mut Shell, &[&str]) -> Result<(), ShellError>
+}
+
+pub struct Shell {
+    editor: LineEditor
+}
+
+impl Shell {
+    pub fn new() -> Self {
+        Self {
+            editor: LineEditor::new()
+        }
+    }
+
+    #[inline]
+    pub fn editor(&self) -> &LineEditor {
+        &self.editor
+    }
+
+    pub fn execute(&mut self, line: &str) {
+        let arguments: Vec<&str> = line.split_whitespace().collect();
+
+        let name = match arguments.first() {
+            Some(name) => *name,
+            None => return
+        };
+
+        let command = match SHELL_COMMANDS.iter().find(|command| command.name == name) {
+            Some(command) => command,
+            None => {
+                let _ = writeln!(ConsoleWriter, "{}: no such command; try help.", name);
+
+                return;
+            }
+        };
+
+        if let Err(error) = (command.handler)(self, &arguments[1..]) {
+            let _ = writeln!(ConsoleWriter, "{}: {}", name, error);
+        }
+    }
+
+    pub fn run(&mut self) -> ! {
+        let _ = writeln!(ConsoleWriter, "\nLightsaber kernel debug shell. Type help for a list of commands.");
+
+        loop {
+            if let Some(line) = self.editor.read_line(SHELL_PROMPT) {
+                self.execute(&line);
+            }
+        }
+    }
+}
+
+// The shell reads whatever the keyboard and serial line feed into the console.
+pub fn lightsaber_kernel_start_shell() {
+    scheduler::lightsaber_kernel_spawn("shell", || {
+        Shell::new().run();
+    });
+}
@@ -0,0 +1,52 @@
+use crate::sync::{
+    mutex::MutexGuard,
+    wait_queue::WaitQueue
+};
+
+pub struct Condvar {
+    waiters: WaitQueue
+}
+
+// Kept with the other sleeping locks; nothing in the kernel waits on a condition under a mutex yet.
+#[allow(dead_code)]
+impl Condvar {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            waiters: WaitQueue::new()
+        }
+    }
+
+    pub fn wait<'mutex, T: ?Sized>(&self, guard: MutexGuard<'mutex, T>) -> MutexGuard<'mutex, T> {
+        let mutex = guard.mutex;
+
+        // The current thread is queued before the mutex is released so a notification in between is not lost.
+        self.waiters.sleep_after(|| drop(guard));
+
+        mutex.lock()
+    }
+
+    pub fn wait_while<'mutex, T: ?Sized, F>(&self, mut guard: MutexGuard<'mutex, T>, mut condition: F) -> MutexGuard<'mutex, T>
+    where
+        F: FnMut(&mut T) -> bool {
+        while condition(&mut *guard) {
+            guard = self.wait(guard);
+        }
+
+        guard
+    }
+
+    pub fn notify_one(&self) -> bool {
+        self.waiters.wake_one()
+    }
+
+    pub fn notify_all(&self) -> usize {
+        self.waiters.wake_all()
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,590 @@
+#[cfg(feature = "lockdep")]
+use core::{
+    cell::UnsafeCell,
+    hint,
+    sync::atomic::{
+        AtomicBool,
+        AtomicU64,
+        AtomicUsize,
+        Ordering
+    }
+};
+
+#[cfg(feature = "lockdep")]
+use crate::{
+    architecture::interrupts,
+    scheduler
+};
+
+#[cfg(feature = "lockdep")]
+const LOCKDEP_MAXIMUM_CLASSES: usize = 128;
+#[cfg(feature = "lockdep")]
+const LOCKDEP_MAXIMUM_HELD: usize = 32;
+#[cfg(feature = "lockdep")]
+const LOCKDEP_MAXIMUM_THREADS: usize = 64;
+#[cfg(feature = "lockdep")]
+const LOCKDEP_UNTRACKED: usize = usize::MAX;
+#[cfg(feature = "lockdep")]
+const LOCKDEP_NO_THREAD: u64 = u64::MAX;
+
+// The thread working on the lockdep state plus one, or zero while none is.
+#[cfg(feature = "lockdep")]
+static LOCKDEP_OWNER: AtomicU64 = AtomicU64::new(0);
+#[cfg(feature = "lockdep")]
+static LOCKDEP_REPORTING: AtomicBool = AtomicBool::new(false);
+// Reports that came up while another was being logged, counted so that they can be owned up to.
+#[cfg(feature = "lockdep")]
+static LOCKDEP_SUPPRESSED: AtomicUsize = AtomicUsize::new(0);
+// Events raised from inside lockdep itself, by a fault, which cannot touch the state.
+#[cfg(feature = "lockdep")]
+static LOCKDEP_MISSED: AtomicUsize = AtomicUsize::new(0);
+#[cfg(feature = "lockdep")]
+static LOCKDEP_STATE: LockdepCell<LockdepState> = LockdepCell(UnsafeCell::new(LockdepState::new()));
+// Only touched while a report is being logged, so the tests can see what was reported.
+#[cfg(all(test, feature = "lockdep"))]
+static LOCKDEP_LAST_REPORT: LockdepCell<Option<LockdepReport>> = LockdepCell(UnsafeCell::new(None));
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LockKind {
+    Spinning,
+
+    Sleeping,
+
+    // Taken more than once at a time, and given back by whichever thread is done with it.
+    Counting
+}
+
+// Every lock created under the same name shares a class, so ordering is learnt once for all of them
+// and the number of classes is bounded by the code rather than by how many locks exist.
+pub struct LockClass {
+    name: &'static str,
+    #[cfg(feature = "lockdep")]
+    kind: LockKind,
+    #[cfg(feature = "lockdep")]
+    id: AtomicUsize
+}
+
+impl LockClass {
+    #[inline]
+    pub const fn new(name: &'static str, kind: LockKind) -> Self {
+        #[cfg(not(feature = "lockdep"))]
+        let _ = kind;
+
+        Self {
+            name,
+            #[cfg(feature = "lockdep")]
+            kind,
+            #[cfg(feature = "lockdep")]
+            id: AtomicUsize::new(0)
+        }
+    }
+
+    #[inline]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    #[inline]
+    pub fn acquire(&self) {
+        #[cfg(feature = "lockdep")]
+        lightsaber_kernel_lockdep_acquire(self);
+    }
+
+    #[inline]
+    pub fn release(&self) {
+        #[cfg(feature = "lockdep")]
+        lightsaber_kernel_lockdep_release(self);
+    }
+
+    #[cfg(feature = "lockdep")]
+    fn id(&self) -> usize {
+        let id = self.id.load(Ordering::Relaxed);
+
+        if id != 0 {
+            return id;
+        }
+
+        let id = match lightsaber_kernel_lockdep_with_state(|state, _| state.class_id(self)) {
+            Some(id) => id,
+            None => return LOCKDEP_UNTRACKED
+        };
+
+        if id == LOCKDEP_UNTRACKED {
+            lightsaber_kernel_lockdep_report(LockdepReport::TooManyClasses(self.name));
+        }
+
+        self.id.store(id, Ordering::Relaxed);
+
+        id
+    }
+
+    #[cfg(feature = "lockdep")]
+    fn instance(&self) -> usize {
+        self as *const Self as usize
+    }
+}
+
+#[cfg(feature = "lockdep")]
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum LockdepReport {
+    Recursive(&'static str),
+
+    OrderViolation {
+        held: &'static str,
+        acquiring: &'static str
+    },
+
+    MightSleep {
+        sleeping: &'static str,
+        spinning: &'static str,
+        held: usize
+    },
+
+    TooManyClasses(&'static str),
+
+    TooManyThreads(&'static str),
+
+    TooManyHeld(&'static str),
+
+    Missed(usize)
+}
+
+// A lock one thread holds: its class, and the lock itself so that taking the same one twice can be told
+// apart from nesting two locks of a class.
+#[cfg(feature = "lockdep")]
+#[derive(Clone, Copy)]
+struct HeldLock {
+    class: usize,
+    instance: usize
+}
+
+// The locks one thread holds, in the order it took them.
+#[cfg(feature = "lockdep")]
+#[derive(Clone, Copy)]
+struct HeldLocks {
+    thread: u64,
+    held: [HeldLock; LOCKDEP_MAXIMUM_HELD],
+    depth: usize
+}
+
+#[cfg(feature = "lockdep")]
+impl HeldLocks {
+    const fn new() -> Self {
+        Self {
+            thread: LOCKDEP_NO_THREAD,
+            held: [HeldLock {
+                class: 0,
+                instance: 0
+            }; LOCKDEP_MAXIMUM_HELD],
+            depth: 0
+        }
+    }
+
+    fn remove(&mut self, instance: usize) -> bool {
+        match self.held[..self.depth].iter().rposition(|held| held.instance == instance) {
+            Some(position) => {
+                self.held.copy_within((position + 1)..self.depth, position);
+                self.depth -= 1;
+
+                if self.depth == 0 {
+                    self.thread = LOCKDEP_NO_THREAD;
+                }
+
+                true
+            }
+            None => false
+        }
+    }
+}
+
+#[cfg(feature = "lockdep")]
+struct LockdepState {
+    // Class zero is never handed out, so that an unset identifier reads as zero.
+    classes: usize,
+    names: [&'static str; LOCKDEP_MAXIMUM_CLASSES],
+    kinds: [LockKind; LOCKDEP_MAXIMUM_CLASSES],
+    before: [u128; LOCKDEP_MAXIMUM_CLASSES],
+    // Only threads holding a lock have a slot; it is given up when the last one is released.
+    threads: [HeldLocks; LOCKDEP_MAXIMUM_THREADS]
+}
+
+#[cfg(feature = "lockdep")]
+impl LockdepState {
+    const fn new() -> Self {
+        Self {
+            classes: 1,
+            names: [""; LOCKDEP_MAXIMUM_CLASSES],
+            kinds: [LockKind::Spinning; LOCKDEP_MAXIMUM_CLASSES],
+            before: [0; LOCKDEP_MAXIMUM_CLASSES],
+            threads: [HeldLocks::new(); LOCKDEP_MAXIMUM_THREADS]
+        }
+    }
+
+    fn class_id(&mut self, class: &LockClass) -> usize {
+        if let Some(id) = (1..self.classes).find(|id| self.names[*id] == class.name() && self.kinds[*id] == class.kind) {
+            return id;
+        }
+
+        if self.classes == LOCKDEP_MAXIMUM_CLASSES {
+            return LOCKDEP_UNTRACKED;
+        }
+
+        let id = self.classes;
+        self.names[id] = class.name();
+        self.kinds[id] = class.kind;
+        self.classes += 1;
+
+        id
+    }
+
+    fn ordered_before(&self, first: usize, second: usize) -> bool {
+        self.before[first] & (1 << second) != 0
+    }
+
+    fn held_locks(&mut self, thread: u64) -> Option<&mut HeldLocks> {
+        self.threads.iter_mut().find(|locks| locks.thread == thread)
+    }
+
+    fn claim_held_locks(&mut self, thread: u64) -> Option<usize> {
+        if let Some(slot) = self.threads.iter().position(|locks| locks.thread == thread) {
+            return Some(slot);
+        }
+
+        let slot = self.threads.iter().position(|locks| locks.thread == LOCKDEP_NO_THREAD)?;
+        self.threads[slot].thread = thread;
+
+        Some(slot)
+    }
+}
+
+#[cfg(feature = "lockdep")]
+struct LockdepCell<T>(UnsafeCell<T>);
+
+#[cfg(feature = "lockdep")]
+unsafe impl<T> Sync for LockdepCell<T> { }
+
+// Waits for the state rather than giving up on it, so that every acquire is matched by its release.
+#[cfg(feature = "lockdep")]
+fn lightsaber_kernel_lockdep_with_state<F, R>(function: F) -> Option<R>
+where
+    F: FnOnce(&mut LockdepState, u64) -> R {
+    let thread = scheduler::lightsaber_kernel_current_thread_id().0;
+    let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+
+    while let Err(owner) = LOCKDEP_OWNER.compare_exchange_weak(0, thread + 1, Ordering::Acquire, Ordering::Relaxed) {
+        // A fault taken inside lockdep would otherwise wait on itself.
+        if owner == thread + 1 {
+            interrupts::lightsaber_kernel_restore_interrupts(interrupts_enabled);
+            LOCKDEP_MISSED.fetch_add(1, Ordering::Relaxed);
+
+            return None;
+        }
+
+        hint::spin_loop();
+    }
+
+    let result = function(unsafe {
+        &mut *LOCKDEP_STATE.0.get()
+    }, thread);
+
+    LOCKDEP_OWNER.store(0, Ordering::Release);
+    interrupts::lightsaber_kernel_restore_interrupts(interrupts_enabled);
+
+    let missed = LOCKDEP_MISSED.swap(0, Ordering::Relaxed);
+
+    if missed != 0 {
+        lightsaber_kernel_lockdep_report(LockdepReport::Missed(missed));
+    }
+
+    Some(result)
+}
+
+// Logging takes locks of its own, so whatever comes up while a report is logged is counted and owned up to afterwards.
+#[cfg(feature = "lockdep")]
+fn lightsaber_kernel_lockdep_report(report: LockdepReport) {
+    if LOCKDEP_REPORTING.swap(true, Ordering::AcqRel) {
+        LOCKDEP_SUPPRESSED.fetch_add(1, Ordering::Relaxed);
+
+        return;
+    }
+
+    match report {
+        LockdepReport::Recursive(lock) => log::warn!("Lock `{}` is being acquired recursively.", lock),
+        LockdepReport::OrderViolation { held, acquiring } => {
+            log::warn!("Lock order violation: acquiring `{}` while holding `{}`, which was previously acquired after `{}`.", acquiring, held, acquiring)
+        }
+        LockdepReport::MightSleep { sleeping, spinning, held } => {
+            log::warn!("Sleeping lock `{}` acquired while holding {} spinning lock(s), the last of them `{}`.", sleeping, held, spinning)
+        }
+        LockdepReport::TooManyClasses(lock) => log::warn!("Lock `{}` is not tracked, as all {} lock classes are taken.", lock, LOCKDEP_MAXIMUM_CLASSES - 1),
+        LockdepReport::TooManyThreads(lock) => log::warn!("Lock `{}` is not tracked, as {} other threads already hold locks.", lock, LOCKDEP_MAXIMUM_THREADS),
+        LockdepReport::TooManyHeld(lock) => log::warn!("Lock `{}` is not tracked, as its thread already holds {} locks.", lock, LOCKDEP_MAXIMUM_HELD),
+        LockdepReport::Missed(count) => log::warn!("{} lock event(s) raised inside lockdep were not tracked; held locks may be misreported.", count)
+    }
+
+    #[cfg(test)]
+    unsafe {
+        *LOCKDEP_LAST_REPORT.0.get() = Some(report);
+    }
+
+    let suppressed = LOCKDEP_SUPPRESSED.swap(0, Ordering::Relaxed);
+
+    if suppressed != 0 {
+        log::warn!("{} further lockdep report(s) came up while logging the one above.", suppressed);
+    }
+
+    LOCKDEP_REPORTING.store(false, Ordering::Release);
+}
+
+#[cfg(feature = "lockdep")]
+fn lightsaber_kernel_lockdep_acquire(class: &LockClass) {
+    let id = class.id();
+
+    if id == LOCKDEP_UNTRACKED {
+        return;
+    }
+
+    let instance = class.instance();
+
+    let report = lightsaber_kernel_lockdep_with_state(|state, thread| {
+        let slot = match state.claim_held_locks(thread) {
+            Some(slot) => slot,
+            None => return Some(LockdepReport::TooManyThreads(class.name()))
+        };
+
+        let mut report = None;
+
+        for index in 0..state.threads[slot].depth {
+            let held = state.threads[slot].held[index];
+
+            // A counting lock may well be held more than once.
+            if held.instance == instance && class.kind != LockKind::Counting {
+                report = Some(LockdepReport::Recursive(class.name()));
+                break;
+            }
+
+            // Locks of one class nest in whatever order their users keep, such as a directory before its child.
+            if held.class == id {
+                continue;
+            }
+
+            if state.ordered_before(id, held.class) {
+                report = Some(LockdepReport::OrderViolation {
+                    held: state.names[held.class],
+                    acquiring: class.name()
+                });
+                break;
+            }
+
+            state.before[held.class] |= 1 << id;
+        }
+
+        let locks = &mut state.threads[slot];
+
+        if locks.depth == LOCKDEP_MAXIMUM_HELD {
+            return report.or_else(|| Some(LockdepReport::TooManyHeld(class.name())));
+        }
+
+        locks.held[locks.depth] = HeldLock {
+            class: id,
+            instance
+        };
+        locks.depth += 1;
+
+        report
+    }).flatten();
+
+    if let Some(report) = report {
+        lightsaber_kernel_lockdep_report(report);
+    }
+}
+
+#[cfg(feature = "lockdep")]
+fn lightsaber_kernel_lockdep_release(class: &LockClass) {
+    let id = class.id();
+
+    if id == LOCKDEP_UNTRACKED {
+        return;
+    }
+
+    let instance = class.instance();
+
+    // A lock that was not tracked when it was taken was reported then, so it is simply not found here. One
+    // given back by another thread than the one that took it, as counting locks are, is found wherever it is.
+    lightsaber_kernel_lockdep_with_state(|state, thread| {
+        if state.held_locks(thread).map_or(false, |locks| locks.remove(instance)) {
+            return;
+        }
+
+        for locks in state.threads.iter_mut().filter(|locks| locks.thread != LOCKDEP_NO_THREAD) {
+            if locks.remove(instance) {
+                return;
+            }
+        }
+    });
+}
+
+pub fn lightsaber_kernel_lockdep_might_sleep(name: &'static str) {
+    #[cfg(feature = "lockdep")]
+    {
+        // Only spinning locks make sleeping wrong; a sleeping lock held across another is fine.
+        let spinning = lightsaber_kernel_lockdep_with_state(|state, thread| {
+            let state = &*state;
+            let locks = state.threads.iter().find(|locks| locks.thread == thread)?;
+            let spinning = locks.held[..locks.depth].iter().filter(|held| state.kinds[held.class] == LockKind::Spinning);
+
+            spinning.clone().last().map(|last| (state.names[last.class], spinning.count()))
+        }).flatten();
+
+        if let Some((spinning, held)) = spinning {
+            lightsaber_kernel_lockdep_report(LockdepReport::MightSleep {
+                sleeping: name,
+                spinning,
+                held
+            });
+        }
+    }
+
+    #[cfg(not(feature = "lockdep"))]
+    let _ = name;
+}
+
+#[cfg(all(test, feature = "lockdep"))]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    use crate::sync::{
+        Mutex,
+        Semaphore,
+        Spinlock
+    };
+
+    // Takes the report the last test case caused, if any; the order graph is shared, so every test names its own locks.
+    fn lightsaber_kernel_test_last_report() -> Option<LockdepReport> {
+        while LOCKDEP_REPORTING.swap(true, Ordering::AcqRel) {
+            hint::spin_loop();
+        }
+
+        let report = unsafe {
+            (*LOCKDEP_LAST_REPORT.0.get()).take()
+        };
+
+        LOCKDEP_REPORTING.store(false, Ordering::Release);
+
+        report
+    }
+
+    #[test_case]
+    fn lockdep_reports_spinlocks_taken_in_both_orders() {
+        static FIRST: Spinlock<()> = Spinlock::named("lockdep_test_spinlock_first", ());
+        static SECOND: Spinlock<()> = Spinlock::named("lockdep_test_spinlock_second", ());
+
+        lightsaber_kernel_test_last_report();
+
+        {
+            let _first = FIRST.lock();
+            let _second = SECOND.lock();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), None);
+
+        {
+            let _second = SECOND.lock();
+            let _first = FIRST.lock();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), Some(LockdepReport::OrderViolation {
+            held: "lockdep_test_spinlock_second",
+            acquiring: "lockdep_test_spinlock_first"
+        }));
+    }
+
+    #[test_case]
+    fn lockdep_orders_sleeping_locks_too() {
+        static FIRST: Mutex<()> = Mutex::named("lockdep_test_mutex_first", ());
+        static SECOND: Semaphore = Semaphore::named("lockdep_test_semaphore_second", 1);
+
+        lightsaber_kernel_test_last_report();
+
+        {
+            let _first = FIRST.lock();
+            SECOND.acquire();
+            SECOND.release();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), None);
+
+        SECOND.acquire();
+        drop(FIRST.lock());
+        SECOND.release();
+
+        assert_eq!(lightsaber_kernel_test_last_report(), Some(LockdepReport::OrderViolation {
+            held: "lockdep_test_semaphore_second",
+            acquiring: "lockdep_test_mutex_first"
+        }));
+    }
+
+    #[test_case]
+    fn lockdep_reports_sleeping_under_a_spinlock() {
+        static OUTER: Mutex<()> = Mutex::named("lockdep_test_sleep_outer", ());
+        static SPINNING: Spinlock<()> = Spinlock::named("lockdep_test_sleep_spinning", ());
+        static SLEEPING: Mutex<()> = Mutex::named("lockdep_test_sleep_inner", ());
+
+        lightsaber_kernel_test_last_report();
+
+        // Sleeping with only sleeping locks held is fine.
+        {
+            let _outer = OUTER.lock();
+            let _sleeping = SLEEPING.lock();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), None);
+
+        {
+            let _outer = OUTER.lock();
+            let _spinning = SPINNING.lock();
+
+            // Trying does not sleep, so it is fine too.
+            assert!(SLEEPING.try_lock().is_some());
+            assert_eq!(lightsaber_kernel_test_last_report(), None);
+
+            let _sleeping = SLEEPING.lock();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), Some(LockdepReport::MightSleep {
+            sleeping: "lockdep_test_sleep_inner",
+            spinning: "lockdep_test_sleep_spinning",
+            held: 1
+        }));
+    }
+
+    #[test_case]
+    fn lockdep_shares_a_class_among_locks_of_one_name() {
+        let locks = (0..2 * LOCKDEP_MAXIMUM_CLASSES).map(|_| Spinlock::named("lockdep_test_shared", ())).collect::<Vec<Spinlock<()>>>();
+
+        lightsaber_kernel_test_last_report();
+
+        // Far more locks than there are classes, nested two at a time as a parent and its child would be.
+        for pair in locks.windows(2) {
+            let _parent = pair[0].lock();
+            let _child = pair[1].lock();
+        }
+
+        assert_eq!(lightsaber_kernel_test_last_report(), None);
+        assert_eq!(lightsaber_kernel_lockdep_with_state(|state, _| {
+            state.names[..state.classes].iter().filter(|name| **name == "lockdep_test_shared").count()
+        }), Some(1));
+
+        // A counting lock may be held more than once by the same thread.
+        let semaphore = Semaphore::named("lockdep_test_counting", 2);
+
+        semaphore.acquire();
+        semaphore.acquire();
+        semaphore.release();
+        semaphore.release();
+
+        assert_eq!(lightsaber_kernel_test_last_report(), None);
+    }
+}
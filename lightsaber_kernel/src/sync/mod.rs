@@ -0,0 +1,32 @@
+pub mod condvar;
+pub mod lockdep;
+pub mod mutex;
+pub mod rwlock;
+pub mod semaphore;
+pub mod spinlock;
+pub mod ticket;
+pub mod wait_queue;
+
+pub use condvar::Condvar;
+pub use mutex::{
+    Mutex,
+    MutexGuard
+};
+pub use rwlock::{
+    RwLock,
+    RwLockReadGuard,
+    RwLockWriteGuard
+};
+pub use semaphore::Semaphore;
+pub use spinlock::{
+    Spinlock,
+    SpinlockGuard
+};
+pub use ticket::{
+    TicketLock,
+    TicketLockGuard
+};
+pub use wait_queue::{
+    Interrupted,
+    WaitQueue
+};
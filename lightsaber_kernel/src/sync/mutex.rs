@@ -0,0 +1,132 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    ops::{
+        Deref,
+        DerefMut
+    },
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use crate::sync::{
+    lockdep::{
+        self,
+        LockClass,
+        LockKind
+    },
+    wait_queue::WaitQueue
+};
+
+pub struct Mutex<T: ?Sized> {
+    class: LockClass,
+    locked: AtomicBool,
+    waiters: WaitQueue,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for Mutex<T> { }
+unsafe impl<T: ?Sized + Send> Sync for Mutex<T> { }
+
+impl<T> Mutex<T> {
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self::named("<anonymous mutex>", data)
+    }
+
+    #[inline]
+    pub const fn named(name: &'static str, data: T) -> Self {
+        Self {
+            class: LockClass::new(name, LockKind::Sleeping),
+            locked: AtomicBool::new(false),
+            waiters: WaitQueue::new(),
+            data: UnsafeCell::new(data)
+        }
+    }
+}
+
+impl<T: ?Sized> Mutex<T> {
+    #[inline]
+    fn try_acquire(&self) -> bool {
+        self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_ok()
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        lockdep::lightsaber_kernel_lockdep_might_sleep(self.class.name());
+        self.class.acquire();
+
+        if !self.try_acquire() {
+            self.waiters.wait_until_uninterruptible(|| self.try_acquire());
+        }
+
+        MutexGuard {
+            mutex: self
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<MutexGuard<'_, T>> {
+        if !self.try_acquire() {
+            return None;
+        }
+
+        self.class.acquire();
+
+        Some(MutexGuard {
+            mutex: self
+        })
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut T {
+        self.data.get_mut()
+    }
+}
+
+impl<T: Default> Default for Mutex<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Mutex<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => formatter.debug_struct("Mutex").field("data", &&*guard).finish(),
+            None => formatter.write_str("Mutex { <locked> }")
+        }
+    }
+}
+
+pub struct MutexGuard<'mutex, T: ?Sized> {
+    pub(in crate::sync) mutex: &'mutex Mutex<T>
+}
+
+unsafe impl<'mutex, T: ?Sized + Sync> Sync for MutexGuard<'mutex, T> { }
+
+impl<'mutex, T: ?Sized> Deref for MutexGuard<'mutex, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.mutex.data.get()
+        }
+    }
+}
+
+impl<'mutex, T: ?Sized> DerefMut for MutexGuard<'mutex, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            &mut *self.mutex.data.get()
+        }
+    }
+}
+
+impl<'mutex, T: ?Sized> Drop for MutexGuard<'mutex, T> {
+    fn drop(&mut self) {
+        self.mutex.locked.store(false, Ordering::Release);
+        self.mutex.class.release();
+        self.mutex.waiters.wake_one();
+    }
+}
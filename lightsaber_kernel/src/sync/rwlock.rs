@@ -0,0 +1,163 @@
+use core::{
+    cell::UnsafeCell,
+    hint,
+    marker::PhantomData,
+    ops::{
+        Deref,
+        DerefMut
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::interrupts,
+    sync::lockdep::{
+        LockClass,
+        LockKind
+    }
+};
+
+const RWLOCK_WRITER: usize = 1;
+const RWLOCK_WRITER_WAITING: usize = 1 << 1;
+const RWLOCK_READER: usize = 1 << 2;
+
+pub struct RwLock<T: ?Sized> {
+    class: LockClass,
+    state: AtomicUsize,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for RwLock<T> { }
+unsafe impl<T: ?Sized + Send + Sync> Sync for RwLock<T> { }
+
+impl<T> RwLock<T> {
+    #[inline]
+    pub const fn named(name: &'static str, data: T) -> Self {
+        Self {
+            class: LockClass::new(name, LockKind::Spinning),
+            state: AtomicUsize::new(0),
+            data: UnsafeCell::new(data)
+        }
+    }
+}
+
+impl<T: ?Sized> RwLock<T> {
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+        self.class.acquire();
+
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+
+            // Readers back off while a writer holds or is waiting for the lock so writers cannot starve.
+            if state & (RWLOCK_WRITER | RWLOCK_WRITER_WAITING) != 0 {
+                hint::spin_loop();
+                continue;
+            }
+
+            if self.state.compare_exchange_weak(state, state + RWLOCK_READER, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                break;
+            }
+        }
+
+        RwLockReadGuard {
+            lock: self,
+            interrupts_enabled,
+            _not_send: PhantomData
+        }
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+        self.class.acquire();
+
+        loop {
+            let state = self.state.load(Ordering::Relaxed);
+
+            if state & !RWLOCK_WRITER_WAITING == 0 {
+                if self.state.compare_exchange_weak(state, RWLOCK_WRITER, Ordering::Acquire, Ordering::Relaxed).is_ok() {
+                    break;
+                }
+
+                continue;
+            }
+
+            if state & RWLOCK_WRITER_WAITING == 0 {
+                self.state.fetch_or(RWLOCK_WRITER_WAITING, Ordering::Relaxed);
+            }
+
+            hint::spin_loop();
+        }
+
+        RwLockWriteGuard {
+            lock: self,
+            interrupts_enabled,
+            _not_send: PhantomData
+        }
+    }
+}
+
+pub struct RwLockReadGuard<'lock, T: ?Sized> {
+    lock: &'lock RwLock<T>,
+    interrupts_enabled: bool,
+    _not_send: PhantomData<*mut ()>
+}
+
+unsafe impl<'lock, T: ?Sized + Sync> Sync for RwLockReadGuard<'lock, T> { }
+
+impl<'lock, T: ?Sized> Deref for RwLockReadGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> Drop for RwLockReadGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_sub(RWLOCK_READER, Ordering::Release);
+        self.lock.class.release();
+
+        interrupts::lightsaber_kernel_restore_interrupts(self.interrupts_enabled);
+    }
+}
+
+pub struct RwLockWriteGuard<'lock, T: ?Sized> {
+    lock: &'lock RwLock<T>,
+    interrupts_enabled: bool,
+    _not_send: PhantomData<*mut ()>
+}
+
+unsafe impl<'lock, T: ?Sized + Sync> Sync for RwLockWriteGuard<'lock, T> { }
+
+impl<'lock, T: ?Sized> Deref for RwLockWriteGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> DerefMut for RwLockWriteGuard<'lock, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            &mut *self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> Drop for RwLockWriteGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.state.fetch_and(!RWLOCK_WRITER, Ordering::Release);
+        self.lock.class.release();
+
+        interrupts::lightsaber_kernel_restore_interrupts(self.interrupts_enabled);
+    }
+}
@@ -0,0 +1,81 @@
+use core::sync::atomic::{
+    AtomicUsize,
+    Ordering
+};
+
+use crate::sync::{
+    lockdep::{
+        self,
+        LockClass,
+        LockKind
+    },
+    wait_queue::WaitQueue
+};
+
+// Kept with the other sleeping locks; nothing in the kernel hands out a bounded number of permits yet.
+#[allow(dead_code)]
+pub struct Semaphore {
+    class: LockClass,
+    permits: AtomicUsize,
+    waiters: WaitQueue
+}
+
+#[allow(dead_code)]
+impl Semaphore {
+    #[inline]
+    pub const fn new(permits: usize) -> Self {
+        Self::named("<anonymous semaphore>", permits)
+    }
+
+    #[inline]
+    pub const fn named(name: &'static str, permits: usize) -> Self {
+        Self {
+            class: LockClass::new(name, LockKind::Counting),
+            permits: AtomicUsize::new(permits),
+            waiters: WaitQueue::new()
+        }
+    }
+
+    fn take_permit(&self) -> bool {
+        let mut permits = self.permits.load(Ordering::Relaxed);
+
+        while permits != 0 {
+            match self.permits.compare_exchange_weak(permits, permits - 1, Ordering::Acquire, Ordering::Relaxed) {
+                Ok(_) => return true,
+                Err(current) => permits = current
+            }
+        }
+
+        false
+    }
+
+    pub fn try_acquire(&self) -> bool {
+        if !self.take_permit() {
+            return false;
+        }
+
+        self.class.acquire();
+
+        true
+    }
+
+    pub fn acquire(&self) {
+        lockdep::lightsaber_kernel_lockdep_might_sleep(self.class.name());
+        self.class.acquire();
+
+        if !self.take_permit() {
+            self.waiters.wait_until_uninterruptible(|| self.take_permit());
+        }
+    }
+
+    pub fn release(&self) {
+        self.class.release();
+        self.permits.fetch_add(1, Ordering::Release);
+        self.waiters.wake_one();
+    }
+
+    #[inline]
+    pub fn permits(&self) -> usize {
+        self.permits.load(Ordering::Relaxed)
+    }
+}
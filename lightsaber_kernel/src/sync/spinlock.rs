@@ -0,0 +1,138 @@
+use core::{
+    cell::UnsafeCell,
+    fmt,
+    hint,
+    marker::PhantomData,
+    ops::{
+        Deref,
+        DerefMut
+    },
+    sync::atomic::{
+        AtomicBool,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::interrupts,
+    sync::lockdep::{
+        LockClass,
+        LockKind
+    }
+};
+
+pub struct Spinlock<T: ?Sized> {
+    class: LockClass,
+    locked: AtomicBool,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for Spinlock<T> { }
+unsafe impl<T: ?Sized + Send> Sync for Spinlock<T> { }
+
+impl<T> Spinlock<T> {
+    #[inline]
+    pub const fn new(data: T) -> Self {
+        Self::named("<anonymous spinlock>", data)
+    }
+
+    #[inline]
+    pub const fn named(name: &'static str, data: T) -> Self {
+        Self {
+            class: LockClass::new(name, LockKind::Spinning),
+            locked: AtomicBool::new(false),
+            data: UnsafeCell::new(data)
+        }
+    }
+}
+
+impl<T: ?Sized> Spinlock<T> {
+    pub fn lock(&self) -> SpinlockGuard<'_, T> {
+        let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+        self.class.acquire();
+
+        while self.locked.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            while self.locked.load(Ordering::Relaxed) {
+                hint::spin_loop();
+            }
+        }
+
+        SpinlockGuard {
+            lock: self,
+            interrupts_enabled,
+            _not_send: PhantomData
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<SpinlockGuard<'_, T>> {
+        let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+
+        if self.locked.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+            interrupts::lightsaber_kernel_restore_interrupts(interrupts_enabled);
+
+            return None;
+        }
+
+        self.class.acquire();
+
+        Some(SpinlockGuard {
+            lock: self,
+            interrupts_enabled,
+            _not_send: PhantomData
+        })
+    }
+
+    pub unsafe fn force_unlock(&self) {
+        self.locked.store(false, Ordering::Release);
+    }
+}
+
+impl<T: Default> Default for Spinlock<T> {
+    fn default() -> Self {
+        Self::new(T::default())
+    }
+}
+
+impl<T: ?Sized + fmt::Debug> fmt::Debug for Spinlock<T> {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.try_lock() {
+            Some(guard) => formatter.debug_struct("Spinlock").field("data", &&*guard).finish(),
+            None => formatter.write_str("Spinlock { <locked> }")
+        }
+    }
+}
+
+pub struct SpinlockGuard<'lock, T: ?Sized> {
+    lock: &'lock Spinlock<T>,
+    interrupts_enabled: bool,
+    _not_send: PhantomData<*mut ()>
+}
+
+unsafe impl<'lock, T: ?Sized + Sync> Sync for SpinlockGuard<'lock, T> { }
+
+impl<'lock, T: ?Sized> Deref for SpinlockGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> DerefMut for SpinlockGuard<'lock, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            &mut *self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> Drop for SpinlockGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.locked.store(false, Ordering::Release);
+        self.lock.class.release();
+
+        interrupts::lightsaber_kernel_restore_interrupts(self.interrupts_enabled);
+    }
+}
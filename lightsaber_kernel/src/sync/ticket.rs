@@ -0,0 +1,97 @@
+use core::{
+    cell::UnsafeCell,
+    hint,
+    marker::PhantomData,
+    ops::{
+        Deref,
+        DerefMut
+    },
+    sync::atomic::{
+        AtomicUsize,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::interrupts,
+    sync::lockdep::{
+        LockClass,
+        LockKind
+    }
+};
+
+pub struct TicketLock<T: ?Sized> {
+    class: LockClass,
+    next_ticket: AtomicUsize,
+    now_serving: AtomicUsize,
+    data: UnsafeCell<T>
+}
+
+unsafe impl<T: ?Sized + Send> Send for TicketLock<T> { }
+unsafe impl<T: ?Sized + Send> Sync for TicketLock<T> { }
+
+impl<T> TicketLock<T> {
+    #[inline]
+    pub const fn named(name: &'static str, data: T) -> Self {
+        Self {
+            class: LockClass::new(name, LockKind::Spinning),
+            next_ticket: AtomicUsize::new(0),
+            now_serving: AtomicUsize::new(0),
+            data: UnsafeCell::new(data)
+        }
+    }
+}
+
+impl<T: ?Sized> TicketLock<T> {
+    pub fn lock(&self) -> TicketLockGuard<'_, T> {
+        let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+        self.class.acquire();
+
+        let ticket = self.next_ticket.fetch_add(1, Ordering::Relaxed);
+
+        while self.now_serving.load(Ordering::Acquire) != ticket {
+            hint::spin_loop();
+        }
+
+        TicketLockGuard {
+            lock: self,
+            interrupts_enabled,
+            _not_send: PhantomData
+        }
+    }
+}
+
+pub struct TicketLockGuard<'lock, T: ?Sized> {
+    lock: &'lock TicketLock<T>,
+    interrupts_enabled: bool,
+    _not_send: PhantomData<*mut ()>
+}
+
+unsafe impl<'lock, T: ?Sized + Sync> Sync for TicketLockGuard<'lock, T> { }
+
+impl<'lock, T: ?Sized> Deref for TicketLockGuard<'lock, T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe {
+            &*self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> DerefMut for TicketLockGuard<'lock, T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        unsafe {
+            &mut *self.lock.data.get()
+        }
+    }
+}
+
+impl<'lock, T: ?Sized> Drop for TicketLockGuard<'lock, T> {
+    fn drop(&mut self) {
+        self.lock.now_serving.fetch_add(1, Ordering::Release);
+        self.lock.class.release();
+
+        interrupts::lightsaber_kernel_restore_interrupts(self.interrupts_enabled);
+    }
+}
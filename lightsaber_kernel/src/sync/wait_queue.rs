@@ -0,0 +1,142 @@
+use alloc::{
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    mem
+};
+
+use crate::{
+    scheduler::{
+        self,
+        thread::{
+            Thread,
+            ThreadState
+        }
+    },
+    sync::Spinlock
+};
+
+// A wait given up on because the thread was killed before its condition came true.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Interrupted;
+
+pub struct WaitQueue {
+    waiters: Spinlock<Vec<Arc<Thread>>>
+}
+
+impl WaitQueue {
+    #[inline]
+    pub const fn new() -> Self {
+        Self {
+            waiters: Spinlock::named("wait_queue", Vec::new())
+        }
+    }
+
+    // For waits that may never end, such as for input; a killed thread gives up so that it can exit.
+    pub fn wait_until<F>(&self, condition: F) -> Result<(), Interrupted>
+    where
+        F: FnMut() -> bool {
+        self.wait(condition, true)
+    }
+
+    // For waits that end by themselves, such as for a lock or a device, which a killed thread still has to see through.
+    pub fn wait_until_uninterruptible<F>(&self, condition: F)
+    where
+        F: FnMut() -> bool {
+        let _ = self.wait(condition, false);
+    }
+
+    fn wait<F>(&self, mut condition: F, killable: bool) -> Result<(), Interrupted>
+    where
+        F: FnMut() -> bool {
+        loop {
+            {
+                let mut waiters = self.waiters.lock();
+
+                if condition() {
+                    return Ok(());
+                }
+
+                match scheduler::lightsaber_kernel_current_thread() {
+                    Some(current) if killable && current.is_killed() => return Err(Interrupted),
+                    Some(current) => {
+                        current.set_state(ThreadState::Blocked);
+                        waiters.push(current);
+                    }
+                    None => {
+                        drop(waiters);
+                        hint::spin_loop();
+
+                        continue;
+                    }
+                }
+            }
+
+            scheduler::lightsaber_kernel_schedule();
+        }
+    }
+
+    pub fn sleep_after<F>(&self, before_sleep: F)
+    where
+        F: FnOnce() {
+        let current = match scheduler::lightsaber_kernel_current_thread() {
+            Some(current) => current,
+            None => {
+                before_sleep();
+                hint::spin_loop();
+
+                return;
+            }
+        };
+
+        {
+            let mut waiters = self.waiters.lock();
+
+            current.set_state(ThreadState::Blocked);
+            waiters.push(current);
+        }
+
+        before_sleep();
+        scheduler::lightsaber_kernel_schedule();
+    }
+
+    pub fn wake_one(&self) -> bool {
+        loop {
+            let waiter = {
+                let mut waiters = self.waiters.lock();
+
+                match waiters.is_empty() {
+                    true => return false,
+                    false => waiters.remove(0)
+                }
+            };
+
+            // A waiter that was already woken some other way does not count.
+            if scheduler::lightsaber_kernel_wake(&waiter) {
+                return true;
+            }
+        }
+    }
+
+    pub fn wake_all(&self) -> usize {
+        let waiters = {
+            let mut waiters = self.waiters.lock();
+
+            mem::take(&mut *waiters)
+        };
+
+        waiters
+            .iter()
+            .filter(|waiter| scheduler::lightsaber_kernel_wake(waiter))
+            .count()
+    }
+}
+
+impl Default for WaitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,186 @@
+use alloc::{
+    string::String,
+    sync::Arc,
+    vec,
+    vec::Vec
+};
+
+use crate::{
+    architecture::syscall::SyscallFrame,
+    fs::{
+        file::{
+            self,
+            SeekWhence
+        },
+        inode::MODE_PERMISSION_MASK,
+        path,
+        Metadata
+    },
+    process::{
+        self,
+        files::FileDescription
+    },
+    syscall::{
+        user,
+        SyscallError,
+        SyscallResult
+    }
+};
+
+const READ_CHUNK_SIZE: usize = 4096;
+
+// The `linux_dirent64` header: inode, offset, record length and type, followed by the name.
+const DIRECTORY_RECORD_HEADER_SIZE: usize = 19;
+
+#[derive(Debug, Clone, Copy)]
+#[repr(C)]
+pub struct UserStat {
+    pub device: u64,
+    pub inode: u64,
+    pub mode: u32,
+    pub links: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub block_size: u64,
+    pub blocks: u64,
+    pub accessed: u64,
+    pub modified: u64,
+    pub changed: u64
+}
+
+impl From<Metadata> for UserStat {
+    fn from(metadata: Metadata) -> Self {
+        Self {
+            device: metadata.device,
+            inode: metadata.inode,
+            mode: metadata.full_mode(),
+            links: metadata.links,
+            uid: metadata.uid,
+            gid: metadata.gid,
+            size: metadata.size,
+            block_size: metadata.block_size,
+            blocks: metadata.blocks,
+            accessed: metadata.accessed,
+            modified: metadata.modified,
+            changed: metadata.changed
+        }
+    }
+}
+
+fn lightsaber_kernel_copy_path_from_user(address: u64) -> Result<String, SyscallError> {
+    user::lightsaber_kernel_copy_string_from_user(address, path::MAXIMUM_PATH_LENGTH)
+        .map_err(|error| match error {
+            SyscallError::InvalidArgument => SyscallError::NameTooLong,
+            error => error
+        })
+}
+
+fn lightsaber_kernel_file(descriptor: u64) -> Result<Arc<dyn FileDescription>, SyscallError> {
+    process::lightsaber_kernel_current_process()
+        .ok_or(SyscallError::BadDescriptor)?
+        .files()
+        .lock()
+        .get(descriptor as usize)
+}
+
+pub fn lightsaber_kernel_syscall_open(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let path = lightsaber_kernel_copy_path_from_user(arguments[0])?;
+    let open_file = file::lightsaber_kernel_open(&path, arguments[1], arguments[2] as u16 & MODE_PERMISSION_MASK)?;
+
+    let process = process::lightsaber_kernel_current_process().ok_or(SyscallError::BadDescriptor)?;
+    let descriptor = process.files().lock().install(open_file)?;
+
+    Ok(descriptor as u64)
+}
+
+pub fn lightsaber_kernel_syscall_read(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let file = lightsaber_kernel_file(arguments[0])?;
+    let address = arguments[1];
+    let length = arguments[2] as usize;
+
+    user::lightsaber_kernel_validate_user_range(address, length, true)?;
+
+    let mut buffer = vec![0; READ_CHUNK_SIZE.min(length)];
+    let mut read = 0;
+
+    while read < length {
+        let chunk = &mut buffer[..READ_CHUNK_SIZE.min(length - read)];
+        let count = file.read(chunk)?;
+
+        user::lightsaber_kernel_copy_to_user(address + read as u64, &chunk[..count])?;
+        read += count;
+
+        if count < chunk.len() {
+            break;
+        }
+    }
+
+    Ok(read as u64)
+}
+
+pub fn lightsaber_kernel_syscall_close(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let process = process::lightsaber_kernel_current_process().ok_or(SyscallError::BadDescriptor)?;
+    let file = process.files().lock().remove(arguments[0] as usize)?;
+
+    drop(file);
+
+    Ok(0)
+}
+
+pub fn lightsaber_kernel_syscall_lseek(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let file = lightsaber_kernel_file(arguments[0])?;
+    let whence = SeekWhence::from_u64(arguments[2]).ok_or(SyscallError::InvalidArgument)?;
+
+    file.seek(arguments[1] as i64, whence)
+}
+
+pub fn lightsaber_kernel_syscall_stat(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let path = lightsaber_kernel_copy_path_from_user(arguments[0])?;
+    let metadata = path::lightsaber_kernel_lookup(&path, true)?.inode().metadata()?;
+
+    user::lightsaber_kernel_write_user(arguments[1], &UserStat::from(metadata))?;
+
+    Ok(0)
+}
+
+pub fn lightsaber_kernel_syscall_readdir(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let file = lightsaber_kernel_file(arguments[0])?;
+    let address = arguments[1];
+    let length = arguments[2] as usize;
+
+    user::lightsaber_kernel_validate_user_range(address, length, true)?;
+
+    let mut records = Vec::new();
+    let mut truncated = false;
+
+    file.read_directory(&mut |entry| {
+        let record_length = (DIRECTORY_RECORD_HEADER_SIZE + entry.name.len() + 1 + 7) & !7;
+
+        if records.len() + record_length > length {
+            truncated = true;
+
+            return false;
+        }
+
+        let start = records.len();
+
+        records.extend_from_slice(&entry.inode.to_le_bytes());
+        records.extend_from_slice(&((start + record_length) as i64).to_le_bytes());
+        records.extend_from_slice(&(record_length as u16).to_le_bytes());
+        records.push(entry.inode_type.directory_entry_type());
+        records.extend_from_slice(entry.name.as_bytes());
+        records.resize(start + record_length, 0);
+
+        true
+    })?;
+
+    // A buffer too small for even one entry must not look like the end of the directory.
+    if records.is_empty() && truncated {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    user::lightsaber_kernel_copy_to_user(address, &records)?;
+
+    Ok(records.len() as u64)
+}
@@ -0,0 +1,44 @@
+use alloc::vec;
+
+use crate::{
+    architecture::syscall::SyscallFrame,
+    process,
+    syscall::{
+        user,
+        SyscallError,
+        SyscallResult
+    }
+};
+
+const WRITE_CHUNK_SIZE: usize = 4096;
+
+pub fn lightsaber_kernel_syscall_write(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let descriptor = arguments[0] as usize;
+    let address = arguments[1];
+    let length = arguments[2] as usize;
+
+    let file = process::lightsaber_kernel_current_process()
+        .ok_or(SyscallError::BadDescriptor)?
+        .files()
+        .lock()
+        .get(descriptor)?;
+
+    user::lightsaber_kernel_validate_user_range(address, length, false)?;
+
+    let mut buffer = vec![0; WRITE_CHUNK_SIZE.min(length)];
+    let mut written = 0;
+
+    while written < length {
+        let chunk = &mut buffer[..WRITE_CHUNK_SIZE.min(length - written)];
+        user::lightsaber_kernel_copy_from_user(chunk, address + written as u64)?;
+
+        let count = file.write(chunk)?;
+        written += count;
+
+        if count < chunk.len() {
+            break;
+        }
+    }
+
+    Ok(written as u64)
+}
@@ -0,0 +1,173 @@
+use x86_64::{
+    structures::paging::{
+        Page,
+        PageSize,
+        PageTableFlags,
+        Size4KiB
+    },
+    VirtAddr
+};
+
+use crate::{
+    architecture::syscall::SyscallFrame,
+    loader,
+    memory::address_space::{
+        AddressSpace,
+        PAGE_SHARED
+    },
+    process,
+    syscall::{
+        user,
+        SyscallError,
+        SyscallResult
+    }
+};
+
+pub const PROTECTION_READ: u64 = 0x01;
+pub const PROTECTION_WRITE: u64 = 0x02;
+pub const PROTECTION_EXECUTE: u64 = 0x04;
+
+pub const MAP_SHARED: u64 = 0x01;
+pub const MAP_PRIVATE: u64 = 0x02;
+pub const MAP_FIXED: u64 = 0x10;
+pub const MAP_ANONYMOUS: u64 = 0x20;
+
+pub const MEMORY_MAP_BASE: u64 = 0x0000_1000_0000_0000;
+pub const MEMORY_MAP_END: u64 = loader::INTERPRETER_BASE;
+
+fn lightsaber_kernel_protection_flags(protection: u64) -> Result<PageTableFlags, SyscallError> {
+    if protection & !(PROTECTION_READ | PROTECTION_WRITE | PROTECTION_EXECUTE) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    // Pages cannot be made unreadable without unmapping them, so `PROT_NONE` degrades to read-only.
+    let mut flags = PageTableFlags::empty();
+
+    if protection & PROTECTION_WRITE != 0 {
+        flags |= PageTableFlags::WRITABLE;
+    }
+
+    if protection & PROTECTION_EXECUTE == 0 {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    Ok(flags)
+}
+
+fn lightsaber_kernel_page_range(address: u64, length: u64) -> Result<(Page, u64), SyscallError> {
+    if length == 0 || address % Size4KiB::SIZE != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let count = (length + Size4KiB::SIZE - 1) / Size4KiB::SIZE;
+
+    address
+        .checked_add(count * Size4KiB::SIZE)
+        .filter(|end| *end <= user::USER_SPACE_END)
+        .ok_or(SyscallError::InvalidArgument)?;
+
+    Ok((Page::containing_address(VirtAddr::new(address)), count))
+}
+
+fn lightsaber_kernel_find_free_range(address_space: &AddressSpace, hint: u64, count: u64) -> Result<Page, SyscallError> {
+    let size = count * Size4KiB::SIZE;
+
+    if hint != 0 && hint % Size4KiB::SIZE == 0 && hint.checked_add(size).map(|end| end <= MEMORY_MAP_END).unwrap_or(false) {
+        let page = Page::containing_address(VirtAddr::new(hint));
+
+        if address_space.is_range_free(page, count) {
+            return Ok(page);
+        }
+    }
+
+    let mut candidate = MEMORY_MAP_BASE;
+
+    while candidate + size <= MEMORY_MAP_END {
+        let start = Page::containing_address(VirtAddr::new(candidate));
+
+        match Page::range(start, start + count).find(|page| address_space.flags(*page).is_some()) {
+            Some(mapped) => candidate = mapped.start_address().as_u64() + Size4KiB::SIZE,
+            None => return Ok(start)
+        }
+    }
+
+    Err(SyscallError::OutOfMemory)
+}
+
+pub fn lightsaber_kernel_syscall_mmap(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let (hint, length, protection, options) = (arguments[0], arguments[1], arguments[2], arguments[3]);
+
+    if length == 0 || length > MEMORY_MAP_END - MEMORY_MAP_BASE {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    let shared = match options & (MAP_SHARED | MAP_PRIVATE) {
+        MAP_SHARED => true,
+        MAP_PRIVATE => false,
+        _ => return Err(SyscallError::InvalidArgument)
+    };
+
+    if options & !(MAP_SHARED | MAP_PRIVATE | MAP_FIXED | MAP_ANONYMOUS) != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    // Only anonymous memory can be mapped until there are files to back a mapping.
+    if options & MAP_ANONYMOUS == 0 {
+        return Err(SyscallError::BadDescriptor);
+    }
+
+    let mut flags = lightsaber_kernel_protection_flags(protection)?;
+
+    if shared {
+        flags |= PAGE_SHARED;
+    }
+
+    let address_space = process::lightsaber_kernel_current_process()
+        .and_then(|process| process.address_space())
+        .ok_or(SyscallError::BadAddress)?;
+
+    let mut address_space = address_space.lock();
+    let count = (length + Size4KiB::SIZE - 1) / Size4KiB::SIZE;
+
+    let start_page = match options & MAP_FIXED {
+        0 => {
+            let start_page = lightsaber_kernel_find_free_range(&address_space, hint, count)?;
+            address_space.map_zeroed(start_page, count, flags)?;
+
+            start_page
+        }
+        _ => {
+            let (start_page, count) = lightsaber_kernel_page_range(hint, length)?;
+            address_space.replace_zeroed(start_page, count, flags)?;
+
+            start_page
+        }
+    };
+
+    Ok(start_page.start_address().as_u64())
+}
+
+pub fn lightsaber_kernel_syscall_munmap(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let (start_page, count) = lightsaber_kernel_page_range(arguments[0], arguments[1])?;
+
+    let address_space = process::lightsaber_kernel_current_process()
+        .and_then(|process| process.address_space())
+        .ok_or(SyscallError::BadAddress)?;
+
+    address_space.lock().unmap(start_page, count);
+
+    Ok(0)
+}
+
+pub fn lightsaber_kernel_syscall_mprotect(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let (start_page, count) = lightsaber_kernel_page_range(arguments[0], arguments[1])?;
+    let flags = lightsaber_kernel_protection_flags(arguments[2])?;
+
+    let address_space = process::lightsaber_kernel_current_process()
+        .and_then(|process| process.address_space())
+        .ok_or(SyscallError::BadAddress)?;
+
+    address_space.lock().protect(start_page, count, flags)?;
+
+    Ok(0)
+}
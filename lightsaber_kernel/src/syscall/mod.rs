@@ -0,0 +1,316 @@
+use core::fmt;
+
+use crate::{
+    architecture::{
+        interrupts,
+        syscall::SyscallFrame
+    },
+    scheduler,
+    sync::Interrupted
+};
+
+pub mod fs;
+pub mod io;
+pub mod memory;
+pub mod process;
+pub mod thread;
+pub mod user;
+
+pub const SYSCALL_EXIT: u64 = 0;
+pub const SYSCALL_WRITE: u64 = 1;
+pub const SYSCALL_YIELD: u64 = 2;
+pub const SYSCALL_GETPID: u64 = 3;
+pub const SYSCALL_SLEEP: u64 = 4;
+pub const SYSCALL_FORK: u64 = 5;
+pub const SYSCALL_SPAWN: u64 = 6;
+pub const SYSCALL_WAIT: u64 = 7;
+pub const SYSCALL_MMAP: u64 = 8;
+pub const SYSCALL_MUNMAP: u64 = 9;
+pub const SYSCALL_MPROTECT: u64 = 10;
+pub const SYSCALL_OPEN: u64 = 11;
+pub const SYSCALL_READ: u64 = 12;
+pub const SYSCALL_CLOSE: u64 = 13;
+pub const SYSCALL_LSEEK: u64 = 14;
+pub const SYSCALL_STAT: u64 = 15;
+pub const SYSCALL_READDIR: u64 = 16;
+
+pub type SyscallResult = Result<u64, SyscallError>;
+
+type SyscallHandler = fn(&mut SyscallFrame, &[u64; 6]) -> SyscallResult;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(i64)]
+pub enum SyscallError {
+    NoEntry = 2,
+
+    Interrupted = 4,
+
+    Io = 5,
+
+    TooBig = 7,
+
+    ExecFormat = 8,
+
+    BadDescriptor = 9,
+
+    NoChild = 10,
+
+    OutOfMemory = 12,
+
+    BadAddress = 14,
+
+    Busy = 16,
+
+    Exists = 17,
+
+    CrossDevice = 18,
+
+    NotDirectory = 20,
+
+    IsDirectory = 21,
+
+    InvalidArgument = 22,
+
+    TooManyFiles = 24,
+
+    FileTooLarge = 27,
+
+    NoSpace = 28,
+
+    IllegalSeek = 29,
+
+    ReadOnlyFilesystem = 30,
+
+    NameTooLong = 36,
+
+    NotImplemented = 38,
+
+    NotEmpty = 39,
+
+    SymlinkLoop = 40,
+
+    NotSupported = 95
+}
+
+impl SyscallError {
+    #[inline]
+    pub fn as_return_value(self) -> u64 {
+        (-(self as i64)) as u64
+    }
+}
+
+impl fmt::Display for SyscallError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            Self::NoEntry => "No such file or directory.",
+            Self::Interrupted => "Interrupted system call.",
+            Self::Io => "Input/output error.",
+            Self::TooBig => "Argument list too long.",
+            Self::ExecFormat => "Exec format error.",
+            Self::BadDescriptor => "Bad file descriptor.",
+            Self::NoChild => "No child processes.",
+            Self::OutOfMemory => "Out of memory.",
+            Self::BadAddress => "Bad address.",
+            Self::Busy => "Device or resource busy.",
+            Self::Exists => "File exists.",
+            Self::CrossDevice => "Invalid cross-device link.",
+            Self::NotDirectory => "Not a directory.",
+            Self::IsDirectory => "Is a directory.",
+            Self::InvalidArgument => "Invalid argument.",
+            Self::TooManyFiles => "Too many open files.",
+            Self::FileTooLarge => "File too large.",
+            Self::NoSpace => "No space left on device.",
+            Self::IllegalSeek => "Illegal seek.",
+            Self::ReadOnlyFilesystem => "Read-only file system.",
+            Self::NameTooLong => "File name too long.",
+            Self::NotImplemented => "Function not implemented.",
+            Self::NotEmpty => "Directory not empty.",
+            Self::SymlinkLoop => "Too many levels of symbolic links.",
+            Self::NotSupported => "Operation not supported."
+        };
+
+        formatter.write_str(description)
+    }
+}
+
+impl From<Interrupted> for SyscallError {
+    fn from(_: Interrupted) -> Self {
+        Self::Interrupted
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SyscallArgument {
+    Integer,
+
+    Descriptor,
+
+    UserPointer,
+
+    Length
+}
+
+impl SyscallArgument {
+    fn validate(self, value: u64) -> Result<(), SyscallError> {
+        match self {
+            Self::Integer => Ok(()),
+            Self::Descriptor if value <= i32::MAX as u64 => Ok(()),
+            Self::Descriptor => Err(SyscallError::BadDescriptor),
+            Self::UserPointer if value != 0 && user::lightsaber_kernel_is_user_address(value) => Ok(()),
+            Self::UserPointer => Err(SyscallError::BadAddress),
+            Self::Length if value <= isize::MAX as u64 => Ok(()),
+            Self::Length => Err(SyscallError::InvalidArgument)
+        }
+    }
+}
+
+struct SyscallDescriptor {
+    number: u64,
+    name: &'static str,
+    arguments: &'static [SyscallArgument],
+    handler: SyscallHandler
+}
+
+static SYSCALL_TABLE: [SyscallDescriptor; 17] = [
+    SyscallDescriptor {
+        number: SYSCALL_EXIT,
+        name: "exit",
+        arguments: &[SyscallArgument::Integer],
+        handler: process::lightsaber_kernel_syscall_exit
+    },
+    SyscallDescriptor {
+        number: SYSCALL_WRITE,
+        name: "write",
+        arguments: &[SyscallArgument::Descriptor, SyscallArgument::UserPointer, SyscallArgument::Length],
+        handler: io::lightsaber_kernel_syscall_write
+    },
+    SyscallDescriptor {
+        number: SYSCALL_YIELD,
+        name: "yield",
+        arguments: &[],
+        handler: thread::lightsaber_kernel_syscall_yield
+    },
+    SyscallDescriptor {
+        number: SYSCALL_GETPID,
+        name: "getpid",
+        arguments: &[],
+        handler: process::lightsaber_kernel_syscall_getpid
+    },
+    SyscallDescriptor {
+        number: SYSCALL_SLEEP,
+        name: "sleep",
+        arguments: &[SyscallArgument::Integer],
+        handler: thread::lightsaber_kernel_syscall_sleep
+    },
+    SyscallDescriptor {
+        number: SYSCALL_FORK,
+        name: "fork",
+        arguments: &[],
+        handler: process::lightsaber_kernel_syscall_fork
+    },
+    SyscallDescriptor {
+        number: SYSCALL_SPAWN,
+        name: "spawn",
+        arguments: &[SyscallArgument::UserPointer, SyscallArgument::Length, SyscallArgument::Integer, SyscallArgument::Integer],
+        handler: process::lightsaber_kernel_syscall_spawn
+    },
+    SyscallDescriptor {
+        number: SYSCALL_WAIT,
+        name: "wait",
+        arguments: &[SyscallArgument::Integer, SyscallArgument::Integer, SyscallArgument::Integer],
+        handler: process::lightsaber_kernel_syscall_wait
+    },
+    SyscallDescriptor {
+        number: SYSCALL_MMAP,
+        name: "mmap",
+        arguments: &[SyscallArgument::Integer, SyscallArgument::Length, SyscallArgument::Integer, SyscallArgument::Integer, SyscallArgument::Integer, SyscallArgument::Integer],
+        handler: memory::lightsaber_kernel_syscall_mmap
+    },
+    SyscallDescriptor {
+        number: SYSCALL_MUNMAP,
+        name: "munmap",
+        arguments: &[SyscallArgument::UserPointer, SyscallArgument::Length],
+        handler: memory::lightsaber_kernel_syscall_munmap
+    },
+    SyscallDescriptor {
+        number: SYSCALL_MPROTECT,
+        name: "mprotect",
+        arguments: &[SyscallArgument::UserPointer, SyscallArgument::Length, SyscallArgument::Integer],
+        handler: memory::lightsaber_kernel_syscall_mprotect
+    },
+    SyscallDescriptor {
+        number: SYSCALL_OPEN,
+        name: "open",
+        arguments: &[SyscallArgument::UserPointer, SyscallArgument::Integer, SyscallArgument::Integer],
+        handler: fs::lightsaber_kernel_syscall_open
+    },
+    SyscallDescriptor {
+        number: SYSCALL_READ,
+        name: "read",
+        arguments: &[SyscallArgument::Descriptor, SyscallArgument::UserPointer, SyscallArgument::Length],
+        handler: fs::lightsaber_kernel_syscall_read
+    },
+    SyscallDescriptor {
+        number: SYSCALL_CLOSE,
+        name: "close",
+        arguments: &[SyscallArgument::Descriptor],
+        handler: fs::lightsaber_kernel_syscall_close
+    },
+    SyscallDescriptor {
+        number: SYSCALL_LSEEK,
+        name: "lseek",
+        arguments: &[SyscallArgument::Descriptor, SyscallArgument::Integer, SyscallArgument::Integer],
+        handler: fs::lightsaber_kernel_syscall_lseek
+    },
+    SyscallDescriptor {
+        number: SYSCALL_STAT,
+        name: "stat",
+        arguments: &[SyscallArgument::UserPointer, SyscallArgument::UserPointer],
+        handler: fs::lightsaber_kernel_syscall_stat
+    },
+    SyscallDescriptor {
+        number: SYSCALL_READDIR,
+        name: "readdir",
+        arguments: &[SyscallArgument::Descriptor, SyscallArgument::UserPointer, SyscallArgument::Length],
+        handler: fs::lightsaber_kernel_syscall_readdir
+    }
+];
+
+fn lightsaber_kernel_syscall(frame: &mut SyscallFrame) -> SyscallResult {
+    let descriptor = SYSCALL_TABLE
+        .get(frame.number() as usize)
+        .ok_or(SyscallError::NotImplemented)?;
+
+    debug_assert_eq!(descriptor.number, frame.number());
+
+    let arguments = frame.arguments();
+
+    for (argument, value) in descriptor.arguments.iter().zip(arguments.iter()) {
+        argument.validate(*value)?;
+    }
+
+    log::trace!("System call `{}` with arguments {:#x?}.", descriptor.name, &arguments[..descriptor.arguments.len()]);
+
+    (descriptor.handler)(frame, &arguments)
+}
+
+pub extern "C" fn lightsaber_kernel_syscall_dispatch(frame: &mut SyscallFrame) {
+    unsafe {
+        interrupts::lightsaber_kernel_enable_interrupts();
+    }
+
+    let return_value = match lightsaber_kernel_syscall(frame) {
+        Ok(value) => value,
+        Err(error) => error.as_return_value()
+    };
+
+    frame.set_return_value(return_value);
+
+    // `sysret` to a non-canonical address faults in kernel mode, so never hand one back.
+    if !user::lightsaber_kernel_is_user_address(frame.instruction_pointer) {
+        log::error!("Refusing to return to user mode at {:#x}.", frame.instruction_pointer);
+        scheduler::lightsaber_kernel_exit_thread();
+    }
+
+    scheduler::lightsaber_kernel_exit_if_killed();
+}
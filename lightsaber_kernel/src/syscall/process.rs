@@ -0,0 +1,137 @@
+use alloc::{
+    string::String,
+    vec,
+    vec::Vec
+};
+
+use core::mem;
+
+use crate::{
+    architecture::syscall::SyscallFrame,
+    loader::ElfError,
+    memory::address_space::AddressSpaceError,
+    process::{
+        self,
+        ExitStatus,
+        ProcessId
+    },
+    syscall::{
+        user,
+        SyscallError,
+        SyscallResult
+    }
+};
+
+const MAXIMUM_IMAGE_SIZE: usize = 64 * 1024 * 1024;
+const MAXIMUM_VECTOR_LENGTH: usize = 1024;
+const MAXIMUM_STRING_LENGTH: usize = 4096;
+
+const WAIT_NO_HANG: u64 = 1;
+
+impl From<AddressSpaceError> for SyscallError {
+    fn from(error: AddressSpaceError) -> Self {
+        match error {
+            AddressSpaceError::OutOfMemory => Self::OutOfMemory,
+            AddressSpaceError::AlreadyMapped | AddressSpaceError::NotMapped => Self::BadAddress
+        }
+    }
+}
+
+impl From<ElfError> for SyscallError {
+    fn from(error: ElfError) -> Self {
+        match error {
+            ElfError::ArgumentsTooLarge => Self::TooBig,
+            ElfError::AddressSpace(error) => error.into(),
+            _ => Self::ExecFormat
+        }
+    }
+}
+
+fn lightsaber_kernel_copy_string_vector_from_user(address: u64) -> Result<Vec<String>, SyscallError> {
+    let mut strings = Vec::new();
+
+    if address == 0 {
+        return Ok(strings);
+    }
+
+    loop {
+        if strings.len() >= MAXIMUM_VECTOR_LENGTH {
+            return Err(SyscallError::TooBig);
+        }
+
+        let pointer = user::lightsaber_kernel_read_user::<u64>(address + (strings.len() * mem::size_of::<u64>()) as u64)?;
+
+        if pointer == 0 {
+            break;
+        }
+
+        strings.push(user::lightsaber_kernel_copy_string_from_user(pointer, MAXIMUM_STRING_LENGTH)?);
+    }
+
+    Ok(strings)
+}
+
+pub fn lightsaber_kernel_syscall_exit(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    process::lightsaber_kernel_exit_process(ExitStatus::Exited(arguments[0] as i32))
+}
+
+pub fn lightsaber_kernel_syscall_getpid(_frame: &mut SyscallFrame, _arguments: &[u64; 6]) -> SyscallResult {
+    Ok(process::lightsaber_kernel_current_process().map(|process| process.id().0).unwrap_or(0))
+}
+
+pub fn lightsaber_kernel_syscall_fork(frame: &mut SyscallFrame, _arguments: &[u64; 6]) -> SyscallResult {
+    // The child resumes from a copy of this frame, so the parent's return value must not be in it yet.
+    let child = process::lightsaber_kernel_fork(frame)?;
+
+    Ok(child.id().0)
+}
+
+pub fn lightsaber_kernel_syscall_spawn(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let (address, length) = (arguments[0], arguments[1] as usize);
+
+    if length > MAXIMUM_IMAGE_SIZE {
+        return Err(SyscallError::TooBig);
+    }
+
+    let mut image = vec![0; length];
+    user::lightsaber_kernel_copy_from_user(&mut image, address)?;
+
+    let argument_strings = lightsaber_kernel_copy_string_vector_from_user(arguments[2])?;
+    let environment_strings = lightsaber_kernel_copy_string_vector_from_user(arguments[3])?;
+
+    let argument_slices = argument_strings.iter().map(String::as_str).collect::<Vec<&str>>();
+    let environment_slices = environment_strings.iter().map(String::as_str).collect::<Vec<&str>>();
+
+    let parent = process::lightsaber_kernel_current_process();
+    let name = argument_slices.first().copied().unwrap_or("user");
+
+    let child = process::lightsaber_kernel_spawn_process(name, &image, &argument_slices, &environment_slices, parent.as_ref())?;
+
+    Ok(child.id().0)
+}
+
+pub fn lightsaber_kernel_syscall_wait(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    let id = match arguments[0] as i64 {
+        -1 => None,
+        id if id > 0 => Some(ProcessId(id as u64)),
+        _ => return Err(SyscallError::InvalidArgument)
+    };
+
+    let status_address = arguments[1];
+    let options = arguments[2];
+
+    if options & !WAIT_NO_HANG != 0 {
+        return Err(SyscallError::InvalidArgument);
+    }
+
+    match process::lightsaber_kernel_wait(id, options & WAIT_NO_HANG != 0)? {
+        Some((id, status)) => {
+            if status_address != 0 {
+                user::lightsaber_kernel_write_user(status_address, &status.as_wait_status())?;
+            }
+
+            Ok(id.0)
+        }
+        None => Ok(0)
+    }
+}
@@ -0,0 +1,18 @@
+use crate::{
+    architecture::syscall::SyscallFrame,
+    scheduler,
+    syscall::SyscallResult,
+    time
+};
+
+pub fn lightsaber_kernel_syscall_yield(_frame: &mut SyscallFrame, _arguments: &[u64; 6]) -> SyscallResult {
+    scheduler::lightsaber_kernel_yield();
+
+    Ok(0)
+}
+
+pub fn lightsaber_kernel_syscall_sleep(_frame: &mut SyscallFrame, arguments: &[u64; 6]) -> SyscallResult {
+    time::lightsaber_kernel_sleep(arguments[0]);
+
+    Ok(0)
+}
@@ -0,0 +1,183 @@
+use alloc::{
+    string::String,
+    vec::Vec
+};
+
+use core::mem::{
+    self,
+    MaybeUninit
+};
+
+use x86_64::{
+    structures::paging::PageTableFlags,
+    VirtAddr
+};
+
+use crate::{
+    architecture::processor,
+    memory::{
+        address_space::PAGE_COPY_ON_WRITE,
+        paging
+    },
+    process,
+    syscall::SyscallError
+};
+
+pub const USER_SPACE_END: u64 = 0x0000_8000_0000_0000;
+
+const PAGE_SIZE: u64 = 4096;
+
+// Every access to user memory goes through this one `rep movsb`. Validating the range first is not
+// enough, as a page can be unmapped before the copy reaches it; the page fault handler then resumes
+// at the fixup label, which returns how many bytes were left uncopied.
+global_asm!("
+    .intel_syntax noprefix
+    .section .text
+    .global lightsaber_kernel_user_copy
+    .global lightsaber_kernel_user_copy_fault
+    .global lightsaber_kernel_user_copy_fixup
+lightsaber_kernel_user_copy:
+    mov rcx, rdx
+lightsaber_kernel_user_copy_fault:
+    rep movsb
+lightsaber_kernel_user_copy_fixup:
+    mov rax, rcx
+    ret
+    .att_syntax
+");
+
+extern "C" {
+    fn lightsaber_kernel_user_copy(destination: *mut u8, source: *const u8, length: usize) -> usize;
+
+    // Labels rather than functions, declared only so that their addresses can be taken.
+    fn lightsaber_kernel_user_copy_fault();
+
+    fn lightsaber_kernel_user_copy_fixup();
+}
+
+// Where a kernel page fault at this instruction resumes, if it was raised copying user memory.
+pub fn lightsaber_kernel_user_access_fixup(instruction_pointer: u64) -> Option<u64> {
+    match instruction_pointer == lightsaber_kernel_user_copy_fault as usize as u64 {
+        true => Some(lightsaber_kernel_user_copy_fixup as usize as u64),
+        false => None
+    }
+}
+
+unsafe fn lightsaber_kernel_copy_user_bytes(destination: *mut u8, source: *const u8, length: usize) -> Result<(), SyscallError> {
+    match processor::lightsaber_kernel_with_user_access(|| lightsaber_kernel_user_copy(destination, source, length)) {
+        0 => Ok(()),
+        _ => Err(SyscallError::BadAddress)
+    }
+}
+
+#[inline]
+pub fn lightsaber_kernel_is_user_address(address: u64) -> bool {
+    address < USER_SPACE_END
+}
+
+pub fn lightsaber_kernel_validate_user_range(address: u64, length: usize, writable: bool) -> Result<(), SyscallError> {
+    if length == 0 {
+        return Ok(());
+    }
+
+    let end = address
+        .checked_add(length as u64)
+        .filter(|end| *end <= USER_SPACE_END)
+        .ok_or(SyscallError::BadAddress)?;
+
+    let mut required = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+
+    if writable {
+        required |= PageTableFlags::WRITABLE;
+    }
+
+    let mut page = address & !(PAGE_SIZE - 1);
+
+    while page < end {
+        match paging::lightsaber_kernel_translate_flags(VirtAddr::new(page)) {
+            Some(flags) if flags.contains(required) => { }
+            Some(flags) if writable && flags.contains(PAGE_COPY_ON_WRITE) && process::lightsaber_kernel_resolve_write_fault(VirtAddr::new(page)) => { }
+            _ => return Err(SyscallError::BadAddress)
+        }
+
+        page += PAGE_SIZE;
+    }
+
+    Ok(())
+}
+
+pub fn lightsaber_kernel_copy_from_user(destination: &mut [u8], source: u64) -> Result<(), SyscallError> {
+    lightsaber_kernel_validate_user_range(source, destination.len(), false)?;
+
+    unsafe {
+        lightsaber_kernel_copy_user_bytes(destination.as_mut_ptr(), source as *const u8, destination.len())
+    }
+}
+
+pub fn lightsaber_kernel_copy_to_user(destination: u64, source: &[u8]) -> Result<(), SyscallError> {
+    lightsaber_kernel_validate_user_range(destination, source.len(), true)?;
+
+    unsafe {
+        lightsaber_kernel_copy_user_bytes(destination as *mut u8, source.as_ptr(), source.len())
+    }
+}
+
+pub fn lightsaber_kernel_read_user<T: Copy>(source: u64) -> Result<T, SyscallError> {
+    lightsaber_kernel_validate_user_range(source, mem::size_of::<T>(), false)?;
+
+    let mut value = MaybeUninit::<T>::uninit();
+
+    unsafe {
+        lightsaber_kernel_copy_user_bytes(value.as_mut_ptr() as *mut u8, source as *const u8, mem::size_of::<T>())?;
+
+        Ok(value.assume_init())
+    }
+}
+
+pub fn lightsaber_kernel_write_user<T: Copy>(destination: u64, value: &T) -> Result<(), SyscallError> {
+    lightsaber_kernel_validate_user_range(destination, mem::size_of::<T>(), true)?;
+
+    unsafe {
+        lightsaber_kernel_copy_user_bytes(destination as *mut u8, value as *const T as *const u8, mem::size_of::<T>())
+    }
+}
+
+pub fn lightsaber_kernel_copy_string_from_user(source: u64, maximum_length: usize) -> Result<String, SyscallError> {
+    let mut bytes = Vec::new();
+    let mut address = source;
+
+    loop {
+        let page_end = (address & !(PAGE_SIZE - 1)) + PAGE_SIZE;
+        let available = (page_end - address) as usize;
+
+        lightsaber_kernel_validate_user_range(address, available, false)?;
+
+        // The rest of the page is copied too, as it is readable all the same, and cut at the terminator.
+        let start = bytes.len();
+        bytes.resize(start + available, 0);
+
+        unsafe {
+            lightsaber_kernel_copy_user_bytes(bytes[start..].as_mut_ptr(), address as *const u8, available)?;
+        }
+
+        let (length, terminated) = bytes[start..]
+            .iter()
+            .position(|byte| *byte == 0)
+            .map(|position| (position, true))
+            .unwrap_or((available, false));
+
+        bytes.truncate(start + length);
+
+        if bytes.len() > maximum_length {
+            return Err(SyscallError::InvalidArgument);
+        }
+
+        if terminated {
+            break;
+        }
+
+        address = page_end;
+    }
+
+    String::from_utf8(bytes).map_err(|_| SyscallError::InvalidArgument)
+}
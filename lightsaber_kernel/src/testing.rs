@@ -0,0 +1,222 @@
+use core::{
+    any,
+    fmt::Write,
+    panic::PanicInfo,
+    sync::atomic::{
+        AtomicBool,
+        AtomicU64,
+        Ordering
+    }
+};
+
+use x86_64::instructions::port::Port;
+
+use crate::{
+    architecture::interrupts,
+    console::ConsoleWriter,
+    scheduler,
+    sync::{
+        Spinlock,
+        WaitQueue
+    }
+};
+
+// QEMU's `isa-debug-exit` device, which has to be given on the command line as
+// `-device isa-debug-exit,iobase=0xf4,iosize=0x04`. QEMU exits with `(code << 1) | 1`.
+const QEMU_EXIT_PORT: u16 = 0xF4;
+
+const NO_TEST_THREAD: u64 = u64::MAX;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+
+    Failure = 0x11
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum TestOutcome {
+    Returned,
+
+    Panicked
+}
+
+pub trait Testable: Sync {
+    fn name(&self) -> &'static str;
+
+    fn should_panic(&self) -> bool {
+        false
+    }
+
+    fn run(&self);
+}
+
+impl<T> Testable for T
+where
+    T: Fn() + Sync {
+    fn name(&self) -> &'static str {
+        any::type_name::<T>()
+    }
+
+    fn run(&self) {
+        self()
+    }
+}
+
+// Declared through `should_panic!`, which names it after the test.
+pub struct ShouldPanic {
+    pub name: &'static str,
+    pub function: fn()
+}
+
+impl Testable for ShouldPanic {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn should_panic(&self) -> bool {
+        true
+    }
+
+    fn run(&self) {
+        (self.function)()
+    }
+}
+
+// `#[test_case]` cannot carry `#[should_panic]`, so tests that are meant to panic are written as
+// `should_panic!(name, { ... });` instead.
+pub macro should_panic {
+    ($name:ident, $body:block) => {
+        #[test_case]
+        #[allow(non_upper_case_globals)]
+        static $name: $crate::testing::ShouldPanic = $crate::testing::ShouldPanic {
+            name: concat!(module_path!(), "::", stringify!($name)),
+            function: || $body
+        };
+    }
+}
+
+// Each test runs on a thread of its own, so that one that panics can be retired without taking the runner down.
+static TEST_THREAD: AtomicU64 = AtomicU64::new(NO_TEST_THREAD);
+static TEST_SHOULD_PANIC: AtomicBool = AtomicBool::new(false);
+static TEST_OUTCOME: Spinlock<Option<TestOutcome>> = Spinlock::named("test_outcome", None);
+static TEST_FINISHED: WaitQueue = WaitQueue::new();
+
+pub fn lightsaber_kernel_exit_qemu(exit_code: QemuExitCode) -> ! {
+    unsafe {
+        Port::<u32>::new(QEMU_EXIT_PORT).write(exit_code as u32);
+    }
+
+    // Off QEMU the port goes nowhere.
+    unsafe {
+        interrupts::lightsaber_kernel_disable_interrupts();
+
+        loop {
+            interrupts::lightsaber_kernel_halt();
+        }
+    }
+}
+
+fn lightsaber_kernel_finish_test(outcome: TestOutcome) {
+    *TEST_OUTCOME.lock() = Some(outcome);
+    TEST_FINISHED.wake_all();
+}
+
+fn lightsaber_kernel_run_test(test: &'static dyn Testable) -> TestOutcome {
+    *TEST_OUTCOME.lock() = None;
+    TEST_SHOULD_PANIC.store(test.should_panic(), Ordering::Release);
+
+    // The thread has to be known as the test before the timer can switch to it.
+    let interrupts_enabled = interrupts::lightsaber_kernel_save_and_disable_interrupts();
+
+    let thread = scheduler::lightsaber_kernel_spawn("test", move || {
+        test.run();
+        lightsaber_kernel_finish_test(TestOutcome::Returned);
+    });
+
+    TEST_THREAD.store(thread.id().0, Ordering::Release);
+    interrupts::lightsaber_kernel_restore_interrupts(interrupts_enabled);
+
+    let mut outcome = None;
+
+    TEST_FINISHED.wait_until_uninterruptible(|| {
+        outcome = *TEST_OUTCOME.lock();
+        outcome.is_some()
+    });
+
+    TEST_THREAD.store(NO_TEST_THREAD, Ordering::Release);
+
+    outcome.expect("Woke up without a test outcome.")
+}
+
+pub fn lightsaber_kernel_test_runner(tests: &[&'static dyn Testable]) {
+    let _ = writeln!(ConsoleWriter, "Running {} tests.", tests.len());
+
+    let mut failed = 0;
+
+    for test in tests {
+        let _ = write!(ConsoleWriter, "{} ... ", test.name());
+
+        let outcome = lightsaber_kernel_run_test(*test);
+
+        let passed = match (outcome, test.should_panic()) {
+            (TestOutcome::Returned, false) | (TestOutcome::Panicked, true) => true,
+            (TestOutcome::Returned, true) => {
+                let _ = write!(ConsoleWriter, "did not panic ... ");
+
+                false
+            }
+            (TestOutcome::Panicked, false) => false
+        };
+
+        if !passed {
+            failed += 1;
+        }
+
+        let _ = writeln!(ConsoleWriter, "{}", if passed { "ok" } else { "FAILED" });
+    }
+
+    let _ = writeln!(ConsoleWriter, "{} passed, {} failed.", tests.len() - failed, failed);
+
+    lightsaber_kernel_exit_qemu(if failed == 0 { QemuExitCode::Success } else { QemuExitCode::Failure })
+}
+
+// Called by the panic handler, which fails the whole run once this returns. Only a test meant to panic
+// is retired on its own so that the runner goes on, and only if it held nothing that disables interrupts,
+// such as a spinlock, since that would never be released.
+pub fn lightsaber_kernel_test_panicked(panic_info: &PanicInfo<'_>, interrupts_were_enabled: bool) {
+    let test_thread = TEST_THREAD.load(Ordering::Acquire);
+
+    // Going through the scheduler lock could deadlock on a panic taken while it is held.
+    if test_thread == NO_TEST_THREAD || scheduler::lightsaber_kernel_current_thread_id().0 != test_thread {
+        return;
+    }
+
+    if !TEST_SHOULD_PANIC.load(Ordering::Acquire) {
+        let _ = writeln!(ConsoleWriter, "FAILED");
+
+        return;
+    }
+
+    if !interrupts_were_enabled {
+        let _ = writeln!(ConsoleWriter, "panicked with interrupts disabled ... FAILED");
+
+        return;
+    }
+
+    let _ = write!(ConsoleWriter, "panicked ({}) ... ", panic_info);
+
+    lightsaber_kernel_finish_test(TestOutcome::Panicked);
+    scheduler::lightsaber_kernel_exit_thread();
+}
+
+#[test_case]
+fn test_runner_runs_each_test_on_its_own_thread() {
+    assert_eq!(scheduler::lightsaber_kernel_current_thread_id().0, TEST_THREAD.load(Ordering::Acquire));
+    assert!(interrupts::lightsaber_kernel_interrupts_enabled());
+}
+
+should_panic!(test_runner_catches_panics, {
+    panic!("This test is expected to panic.");
+});
@@ -0,0 +1,99 @@
+use alloc::{
+    sync::Arc,
+    vec::Vec
+};
+
+use core::{
+    hint,
+    sync::atomic::{
+        AtomicU64,
+        Ordering
+    }
+};
+
+use crate::{
+    architecture::{
+        interrupts::irq::{
+            self,
+            IRQ_TIMER
+        },
+        pit
+    },
+    scheduler::{
+        self,
+        thread::{
+            Thread,
+            ThreadState
+        }
+    },
+    sync::Spinlock
+};
+
+pub const TIMER_FREQUENCY: u64 = 100;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+static SLEEPING_THREADS: Spinlock<Vec<(u64, Arc<Thread>)>> = Spinlock::named("sleeping_threads", Vec::new());
+
+pub fn lightsaber_kernel_initialize_timer() {
+    pit::lightsaber_kernel_initialize_pit(TIMER_FREQUENCY as u32);
+    irq::lightsaber_kernel_register_irq_handler(IRQ_TIMER, lightsaber_kernel_timer_interrupt);
+
+    log::info!("Initialized timer at {} Hz.", TIMER_FREQUENCY);
+}
+
+fn lightsaber_kernel_timer_interrupt() {
+    let now = TICKS.fetch_add(1, Ordering::AcqRel) + 1;
+
+    {
+        let mut sleeping_threads = SLEEPING_THREADS.lock();
+
+        while let Some(index) = sleeping_threads.iter().position(|(deadline, _)| *deadline <= now) {
+            let (_, thread) = sleeping_threads.swap_remove(index);
+            scheduler::lightsaber_kernel_wake(&thread);
+        }
+    }
+
+    scheduler::lightsaber_kernel_tick();
+}
+
+#[inline]
+pub fn lightsaber_kernel_ticks() -> u64 {
+    TICKS.load(Ordering::Acquire)
+}
+
+#[inline]
+pub fn lightsaber_kernel_milliseconds_to_ticks(milliseconds: u64) -> u64 {
+    milliseconds.saturating_mul(TIMER_FREQUENCY).saturating_add(999) / 1000
+}
+
+#[inline]
+pub fn lightsaber_kernel_uptime_milliseconds() -> u64 {
+    lightsaber_kernel_ticks() * 1000 / TIMER_FREQUENCY
+}
+
+pub fn lightsaber_kernel_sleep(milliseconds: u64) {
+    let deadline = lightsaber_kernel_ticks().saturating_add(lightsaber_kernel_milliseconds_to_ticks(milliseconds));
+
+    while lightsaber_kernel_ticks() < deadline {
+        let current = match scheduler::lightsaber_kernel_current_thread() {
+            Some(current) => current,
+            None => {
+                hint::spin_loop();
+                continue;
+            }
+        };
+
+        {
+            let mut sleeping_threads = SLEEPING_THREADS.lock();
+
+            if lightsaber_kernel_ticks() >= deadline {
+                break;
+            }
+
+            current.set_state(ThreadState::Blocked);
+            sleeping_threads.push((deadline, current));
+        }
+
+        scheduler::lightsaber_kernel_schedule();
+    }
+}
@@ -1,16 +1,38 @@
 use core::panic::PanicInfo;
 
-use crate::architecture::interrupts;
+use crate::{
+    architecture::interrupts,
+    renderer
+};
+
+#[cfg(test)]
+use crate::testing;
 
 #[panic_handler]
 pub extern "C" fn rust_begin_unwind(panic_info: &PanicInfo<'_>) -> ! {
     let default_panic_message = &format_args!("");
     let panic_message = panic_info.message().unwrap_or(default_panic_message);
 
+    #[cfg(test)]
+    let interrupts_were_enabled = interrupts::lightsaber_kernel_interrupts_enabled();
+
+    unsafe {
+        interrupts::lightsaber_kernel_disable_interrupts();
+        renderer::lightsaber_kernel_force_unlock_renderer();
+    }
+
+    #[cfg(test)]
+    testing::lightsaber_kernel_test_panicked(panic_info, interrupts_were_enabled);
+
     log::error!("Unexpected Kernel Panic");
     log::error!("{}", panic_info.location().unwrap());
     log::error!("{}", panic_message);
 
+    // Any panic but an expected one on a test thread fails the whole run.
+    #[cfg(test)]
+    testing::lightsaber_kernel_exit_qemu(testing::QemuExitCode::Failure);
+
+    #[cfg(not(test))]
     unsafe {
         interrupts::lightsaber_kernel_disable_interrupts();
 
@@ -0,0 +1,147 @@
+use std::{
+    env,
+    ffi::OsString,
+    path::{
+        Path,
+        PathBuf
+    },
+    process::{
+        Command,
+        Stdio
+    }
+};
+
+use crate::XtaskError;
+
+const BOOTLOADER_TARGET: &str = "x86_64-unknown-uefi";
+const KERNEL_TARGET: &str = "x86_64-unknown-lightsaber";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Profile {
+    Debug,
+
+    Release
+}
+
+impl Profile {
+    fn directory(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release"
+        }
+    }
+}
+
+pub fn lightsaber_xtask_project_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .expect("The xtask crate is not inside the project.")
+        .to_path_buf()
+}
+
+// The cargo that runs the xtask, so that the same toolchain builds everything.
+fn lightsaber_xtask_cargo() -> Command {
+    Command::new(env::var_os("CARGO").unwrap_or_else(|| OsString::from("cargo")))
+}
+
+// Both crates are built for bare targets with the standard library built from source.
+fn lightsaber_xtask_cargo_command(root: &Path, subcommand: &str, package: &str, target: &Path, profile: Profile) -> Command {
+    let mut command = lightsaber_xtask_cargo();
+
+    command
+        .current_dir(root)
+        .arg(subcommand)
+        .arg("--manifest-path")
+        .arg(root.join(package).join("Cargo.toml"))
+        .arg("--target")
+        .arg(target)
+        .arg("--target-dir")
+        .arg(root.join("target"))
+        .args(["-Z", "build-std=core,alloc"]);
+
+    if profile == Profile::Release {
+        command.arg("--release");
+    }
+
+    command
+}
+
+pub fn lightsaber_xtask_run(command: &mut Command) -> Result<(), XtaskError> {
+    let status = command.status()?;
+
+    if !status.success() {
+        return Err(XtaskError::CommandFailed {
+            command: format!("{:?}", command),
+            status
+        });
+    }
+
+    Ok(())
+}
+
+pub fn lightsaber_xtask_build_bootloader(root: &Path, profile: Profile) -> Result<PathBuf, XtaskError> {
+    lightsaber_xtask_run(&mut lightsaber_xtask_cargo_command(root, "build", "lightsaber_bootloader", Path::new(BOOTLOADER_TARGET), profile))?;
+
+    Ok(root
+        .join("target")
+        .join(BOOTLOADER_TARGET)
+        .join(profile.directory())
+        .join("lightsaber_bootloader.efi"))
+}
+
+pub fn lightsaber_xtask_build_kernel(root: &Path, profile: Profile) -> Result<PathBuf, XtaskError> {
+    let target = root.join(format!("{}.json", KERNEL_TARGET));
+
+    lightsaber_xtask_run(&mut lightsaber_xtask_cargo_command(root, "build", "lightsaber_kernel", &target, profile))?;
+
+    Ok(root
+        .join("target")
+        .join(KERNEL_TARGET)
+        .join(profile.directory())
+        .join("lightsaber_kernel"))
+}
+
+// The test kernel's file name carries a hash, so it is read back from cargo's JSON messages.
+pub fn lightsaber_xtask_build_kernel_tests(root: &Path, profile: Profile) -> Result<PathBuf, XtaskError> {
+    let target = root.join(format!("{}.json", KERNEL_TARGET));
+
+    // Without panic-abort-tests, cargo builds a second, unwinding core for the test profile.
+    let mut command = lightsaber_xtask_cargo_command(root, "test", "lightsaber_kernel", &target, profile);
+    command
+        .args(["-Z", "panic-abort-tests", "--no-run", "--message-format=json"])
+        .stdout(Stdio::piped());
+
+    let output = command.output()?;
+
+    if !output.status.success() {
+        return Err(XtaskError::CommandFailed {
+            command: format!("{:?}", command),
+            status: output.status
+        });
+    }
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|message| message.contains("\"test\":true"))
+        .filter_map(lightsaber_xtask_json_executable)
+        .next_back()
+        .ok_or_else(|| XtaskError::MissingArtifact(String::from("kernel test executable")))
+}
+
+// Pulls `"executable":"..."` out of one of cargo's messages without a JSON parser; only `\\` and
+// `\"` can appear in a path.
+fn lightsaber_xtask_json_executable(message: &str) -> Option<PathBuf> {
+    const KEY: &str = "\"executable\":\"";
+
+    let start = message.find(KEY)? + KEY.len();
+    let mut path = String::new();
+    let mut characters = message[start..].chars();
+
+    loop {
+        match characters.next()? {
+            '"' => return Some(PathBuf::from(path)),
+            '\\' => path.push(characters.next()?),
+            character => path.push(character)
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::{
+    fs::{
+        self,
+        File,
+        OpenOptions
+    },
+    io,
+    path::{
+        Path,
+        PathBuf
+    }
+};
+
+use fatfs::{
+    FileSystem,
+    FormatVolumeOptions,
+    FsOptions
+};
+
+// Where the firmware looks for a bootloader on removable media.
+const ESP_BOOTLOADER_PATH: &str = "efi/boot/bootx64.efi";
+
+// These have to match the bootloader's defaults in `lightsaber_bootloader::config` and
+// `PROJECT_LIGHTSABER_SYSTEM_BOOT_CONFIG_PATH`.
+const ESP_KERNEL_PATH: &str = "efi/kernel/lightsaber.elf";
+const ESP_INITIAL_RAMDISK_PATH: &str = "efi/kernel/initrd";
+const ESP_BOOT_CONFIG_PATH: &str = "efi/lightsaber/boot.cfg";
+
+const MEBIBYTE: u64 = 1024 * 1024;
+
+// Room for the FAT structures and directories on top of the files themselves.
+const ESP_IMAGE_HEADROOM: u64 = 4 * MEBIBYTE;
+
+pub struct EspContents {
+    pub bootloader: PathBuf,
+    pub kernel: PathBuf,
+    pub initial_ramdisk: Option<PathBuf>,
+    pub boot_config: Option<PathBuf>
+}
+
+impl EspContents {
+    fn files(&self) -> Vec<(&'static str, &Path)> {
+        let mut files = vec![
+            (ESP_BOOTLOADER_PATH, self.bootloader.as_path()),
+            (ESP_KERNEL_PATH, self.kernel.as_path())
+        ];
+
+        if let Some(initial_ramdisk) = &self.initial_ramdisk {
+            files.push((ESP_INITIAL_RAMDISK_PATH, initial_ramdisk.as_path()));
+        }
+
+        if let Some(boot_config) = &self.boot_config {
+            files.push((ESP_BOOT_CONFIG_PATH, boot_config.as_path()));
+        }
+
+        files
+    }
+}
+
+// A directory QEMU can present to the firmware as a FAT drive.
+pub fn lightsaber_xtask_write_esp_directory(contents: &EspContents, directory: &Path) -> io::Result<()> {
+    if directory.exists() {
+        fs::remove_dir_all(directory)?;
+    }
+
+    for (destination, source) in contents.files() {
+        let destination = directory.join(destination);
+
+        fs::create_dir_all(destination.parent().expect("ESP paths are never at the root."))?;
+        fs::copy(source, &destination)?;
+    }
+
+    Ok(())
+}
+
+// A FAT image of the ESP, for QEMU or for writing to a USB stick.
+pub fn lightsaber_xtask_write_esp_image(contents: &EspContents, image: &Path) -> io::Result<()> {
+    let files = contents.files();
+
+    let mut total = 0;
+
+    for (_, source) in &files {
+        total += fs::metadata(source)?.len();
+    }
+
+    // Whole mebibytes, as partitioning tools expect.
+    let size = total + ESP_IMAGE_HEADROOM;
+    let size = size + (MEBIBYTE - size % MEBIBYTE) % MEBIBYTE;
+
+    let mut disk = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(image)?;
+
+    disk.set_len(size)?;
+    fatfs::format_volume(&mut disk, FormatVolumeOptions::new().volume_label(*b"LIGHTSABER "))?;
+
+    let filesystem = FileSystem::new(&mut disk, FsOptions::new())?;
+    let root = filesystem.root_dir();
+
+    for (destination, source) in files {
+        let components: Vec<&str> = destination.split('/').collect();
+        let (name, parents) = components.split_last().expect("ESP paths are never empty.");
+
+        let mut directory = root.clone();
+
+        for parent in parents {
+            directory = directory.create_dir(parent)?;
+        }
+
+        let mut file = directory.create_file(name)?;
+        file.truncate()?;
+
+        io::copy(&mut File::open(source)?, &mut file)?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs::{
+        self,
+        OpenOptions
+    },
+    io::{
+        self,
+        Write
+    },
+    path::Path,
+    process::Command
+};
+
+use fatfs::{
+    Dir,
+    FatType,
+    FileSystem,
+    FormatVolumeOptions,
+    FsOptions,
+    ReadWriteSeek
+};
+
+use crate::{
+    cargo,
+    XtaskError
+};
+
+// Where the kernel tests `include_bytes!` the images from.
+const TEST_IMAGE_DIRECTORY: &str = "lightsaber_kernel/testdata";
+
+// Fixed, so that regenerating the images only changes what the tree changed.
+const TEST_IMAGE_UUID: &str = "6c696768-7473-6162-6572-000000000001";
+const TEST_IMAGE_HASH_SEED: &str = "6c696768-7473-6162-6572-000000000002";
+const TEST_IMAGE_TIME: &str = "1620648000";
+
+const PATTERN_SIZE: usize = 20000;
+
+const SECTOR_SIZE: u64 = 512;
+const MEBIBYTE: u64 = 1024 * 1024;
+
+// Clusters of one sector, so that little data already makes long chains.
+const FAT_CLUSTER_SIZE: u32 = 512;
+const FAT_MANY_FILES: usize = 40;
+const FAT_FRAGMENTS: u8 = 8;
+
+// File name, `mke2fs` options and size in blocks.
+const EXT2_IMAGES: [(&str, &[&str], &str); 2] = [
+    // One kibibyte blocks in two groups, with the features `mke2fs -t ext2` enables by default.
+    ("ext2-1k.img", &["-b", "1024", "-g", "256", "-N", "64", "-L", "lightsaber"], "512"),
+    // The original revision, whose directory entries carry no file types.
+    ("ext2-rev0.img", &["-r", "0", "-b", "4096", "-N", "32"], "64")
+];
+
+// File name, FAT width, size in mebibytes and root directory entries; the smallest sizes that give each width.
+const FAT_IMAGES: [(&str, FatType, u64, u16); 3] = [
+    // A root directory of two sectors, which fills up quickly.
+    ("fat12.img", FatType::Fat12, 1, 32),
+    ("fat16.img", FatType::Fat16, 4, 512),
+    ("fat32.img", FatType::Fat32, 34, 0)
+];
+
+fn lightsaber_xtask_pattern() -> Vec<u8> {
+    (0..PATTERN_SIZE).map(|index| (index * 7 % 251) as u8).collect()
+}
+
+#[cfg(unix)]
+fn lightsaber_xtask_symlink(target: &str, link: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(not(unix))]
+fn lightsaber_xtask_symlink(_target: &str, _link: &Path) -> io::Result<()> {
+    Err(io::Error::new(io::ErrorKind::Other, "The test images need a host that supports symbolic links"))
+}
+
+// The tree every ext2 image is made from; the kernel tests check for exactly this.
+fn lightsaber_xtask_write_test_tree(tree: &Path) -> io::Result<()> {
+    if tree.exists() {
+        fs::remove_dir_all(tree)?;
+    }
+
+    fs::create_dir_all(tree.join("docs/nested"))?;
+
+    fs::write(tree.join("hello.txt"), "Hello, ext2!\n")?;
+    fs::write(tree.join("docs/nested/note.txt"), "nested\n")?;
+    fs::write(tree.join("docs/pattern.bin"), lightsaber_xtask_pattern())?;
+    fs::hard_link(tree.join("hello.txt"), tree.join("docs/hello-again.txt"))?;
+    lightsaber_xtask_symlink("hello.txt", &tree.join("link"))?;
+
+    // Too long to fit in the inode, so the target gets a block of its own.
+    lightsaber_xtask_symlink(&format!("docs/nested/note.txt/../../../{}", "x".repeat(70)), &tree.join("long-link"))
+}
+
+// Every path in the tree, as the image will have it.
+fn lightsaber_xtask_tree_paths(directory: &Path, prefix: &str, paths: &mut Vec<String>) -> io::Result<()> {
+    let mut entries = fs::read_dir(directory)?.collect::<io::Result<Vec<_>>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let path = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+
+        if entry.file_type()?.is_dir() {
+            lightsaber_xtask_tree_paths(&entry.path(), &path, paths)?;
+        }
+
+        paths.push(path);
+    }
+
+    Ok(())
+}
+
+// What every FAT image holds; the kernel tests check for exactly this.
+fn lightsaber_xtask_write_fat_tree<T: ReadWriteSeek>(root: &Dir<'_, T>) -> io::Result<()> {
+    root.create_file("HELLO.TXT")?.write_all(b"Hello, FAT!\n")?;
+    root.create_file("A long file name.txt")?.write_all(b"long\n")?;
+    root.create_file("\u{DC}n\u{EF}c\u{F6}d\u{E9}.txt")?.write_all(b"unicode\n")?;
+
+    let docs = root.create_dir("Docs")?;
+    docs.create_file("pattern.bin")?.write_all(&lightsaber_xtask_pattern())?;
+    docs.create_dir("Nested")?.create_file("note.txt")?.write_all(b"nested\n")?;
+
+    // Enough entries for the directory to take several clusters.
+    let many = root.create_dir("Many")?;
+
+    for index in 0..FAT_MANY_FILES {
+        let name = format!("file-{:02}.txt", index);
+        many.create_file(&name)?.write_all(name.as_bytes())?;
+    }
+
+    // Written a cluster at a time in turn, so that neither chain is contiguous.
+    let mut first = root.create_file("frag-a.bin")?;
+    let mut second = root.create_file("frag-b.bin")?;
+
+    for fragment in 0..FAT_FRAGMENTS {
+        first.write_all(&[fragment; FAT_CLUSTER_SIZE as usize])?;
+        second.write_all(&[0x80 | fragment; FAT_CLUSTER_SIZE as usize])?;
+    }
+
+    Ok(())
+}
+
+// The images are zeroes past their data, so that tail is left off; the tests put it back from the size in the boot sector.
+fn lightsaber_xtask_trim_image(image: &Path) -> io::Result<()> {
+    let data = fs::read(image)?;
+    let end = data.iter().rposition(|byte| *byte != 0).map_or(0, |last| last as u64 + 1);
+
+    OpenOptions::new().write(true).open(image)?.set_len((end + SECTOR_SIZE - 1) / SECTOR_SIZE * SECTOR_SIZE)
+}
+
+fn lightsaber_xtask_write_fat_image(image: &Path, fat_type: FatType, size: u64, root_entries: u16) -> io::Result<()> {
+    let mut disk = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(image)?;
+
+    disk.set_len(size * MEBIBYTE)?;
+
+    let options = FormatVolumeOptions::new()
+        .fat_type(fat_type)
+        .bytes_per_cluster(FAT_CLUSTER_SIZE)
+        .max_root_dir_entries(root_entries)
+        .volume_label(*b"LIGHTSABER ");
+
+    fatfs::format_volume(&mut disk, options)?;
+
+    let filesystem = FileSystem::new(&mut disk, FsOptions::new())?;
+    lightsaber_xtask_write_fat_tree(&filesystem.root_dir())?;
+    filesystem.unmount()?;
+
+    lightsaber_xtask_trim_image(image)
+}
+
+pub fn lightsaber_xtask_write_test_images(root: &Path) -> Result<(), XtaskError> {
+    let tree = root.join("target/test-images");
+    let directory = root.join(TEST_IMAGE_DIRECTORY);
+
+    lightsaber_xtask_write_test_tree(&tree)?;
+
+    let mut paths = Vec::new();
+    lightsaber_xtask_tree_paths(&tree, "", &mut paths)?;
+
+    // mke2fs copies the times of what it is given, and nothing on the host can set a change time.
+    let times = root.join("target/test-images.debugfs");
+    fs::write(&times, paths.iter().flat_map(|path| {
+        ["atime", "mtime", "ctime"].iter().map(move |field| format!("set_inode_field {} {} @{}\n", path, field, TEST_IMAGE_TIME))
+    }).collect::<String>())?;
+
+    for (name, options, blocks) in EXT2_IMAGES.iter() {
+        let image = directory.join(name);
+
+        // mke2fs keeps the size of an existing file.
+        if image.exists() {
+            fs::remove_file(&image)?;
+        }
+
+        cargo::lightsaber_xtask_run(Command::new(env::var_os("MKE2FS").unwrap_or_else(|| OsString::from("mke2fs")))
+            .env("E2FSPROGS_FAKE_TIME", TEST_IMAGE_TIME)
+            .args(["-q", "-F", "-t", "ext2", "-m", "0", "-U", TEST_IMAGE_UUID])
+            .arg("-E")
+            .arg(format!("hash_seed={}", TEST_IMAGE_HASH_SEED))
+            .arg("-d")
+            .arg(&tree)
+            .args(*options)
+            .arg(&image)
+            .arg(blocks))?;
+
+        cargo::lightsaber_xtask_run(Command::new(env::var_os("DEBUGFS").unwrap_or_else(|| OsString::from("debugfs")))
+            .arg("-w")
+            .arg("-f")
+            .arg(&times)
+            .arg(&image))?;
+
+        println!("Wrote {}.", image.display());
+    }
+
+    for (name, fat_type, size, root_entries) in FAT_IMAGES.iter() {
+        let image = directory.join(name);
+
+        lightsaber_xtask_write_fat_image(&image, *fat_type, *size, *root_entries)?;
+        println!("Wrote {}.", image.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,221 @@
+use std::{
+    env,
+    fmt,
+    io,
+    path::PathBuf,
+    process::{
+        self,
+        ExitStatus
+    },
+    time::Duration
+};
+
+mod cargo;
+mod esp;
+mod images;
+mod qemu;
+
+use cargo::Profile;
+use esp::EspContents;
+use qemu::{
+    QemuDisk,
+    QemuOptions
+};
+
+const USAGE: &str = "\
+Usage: cargo xtask <command> [options]
+
+Commands:
+    build       Build the bootloader and kernel and lay out the ESP in target/esp.
+    run         Build, then boot the ESP in QEMU.
+    test        Build the kernel tests and run them in QEMU.
+    images      Remake the file system images the kernel tests read, in lightsaber_kernel/testdata.
+
+Options:
+    --release           Build with the release profile.
+    --image             Also write the ESP as a FAT image, target/lightsaber.img, and boot from that.
+    --initrd <path>     Put the file at the initial ramdisk path on the ESP.
+    --config <path>     Put the file on the ESP as the bootloader's configuration.
+    --serial <path>     Capture the serial line to a file instead of the terminal.
+    --gdb               Start QEMU's GDB server on localhost:1234 and wait for a debugger.
+    --timeout <seconds> Kill QEMU if the tests have not finished by then; 300 by default, and never with --gdb.";
+
+#[derive(Debug)]
+pub enum XtaskError {
+    Usage(String),
+
+    Io(io::Error),
+
+    CommandFailed {
+        command: String,
+        status: ExitStatus
+    },
+
+    MissingArtifact(String),
+
+    TestsFailed(Option<i32>),
+
+    TimedOut(Duration)
+}
+
+impl fmt::Display for XtaskError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Usage(message) => write!(formatter, "{}\n\n{}", message, USAGE),
+            Self::Io(error) => write!(formatter, "{}.", error),
+            Self::CommandFailed { command, status } => write!(formatter, "`{}` failed with {}.", command, status),
+            Self::MissingArtifact(artifact) => write!(formatter, "Cargo did not report the {}.", artifact),
+            Self::TestsFailed(Some(code)) => write!(formatter, "The kernel tests failed; QEMU exited with {}.", code),
+            Self::TestsFailed(None) => write!(formatter, "The kernel tests failed; QEMU was terminated by a signal."),
+            Self::TimedOut(timeout) => write!(formatter, "The kernel tests did not finish within {} seconds; QEMU was killed.", timeout.as_secs())
+        }
+    }
+}
+
+impl From<io::Error> for XtaskError {
+    fn from(error: io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Command {
+    Build,
+
+    Run,
+
+    Test,
+
+    Images
+}
+
+#[derive(Debug, Default)]
+struct Options {
+    release: bool,
+    image: bool,
+    gdb: bool,
+    initial_ramdisk: Option<PathBuf>,
+    boot_config: Option<PathBuf>,
+    serial: Option<PathBuf>,
+    timeout: Option<Duration>
+}
+
+fn lightsaber_xtask_parse_arguments() -> Result<(Command, Options), XtaskError> {
+    let mut arguments = env::args().skip(1);
+
+    let command = match arguments.next().as_deref() {
+        Some("build") => Command::Build,
+        Some("run") => Command::Run,
+        Some("test") => Command::Test,
+        Some("images") => Command::Images,
+        Some(other) => return Err(XtaskError::Usage(format!("Unknown command `{}`.", other))),
+        None => return Err(XtaskError::Usage(String::from("No command given.")))
+    };
+
+    let mut options = Options::default();
+
+    while let Some(argument) = arguments.next() {
+        let mut path = |option: &str| {
+            arguments
+                .next()
+                .map(PathBuf::from)
+                .ok_or_else(|| XtaskError::Usage(format!("`{}` needs a path.", option)))
+        };
+
+        match argument.as_str() {
+            "--release" => options.release = true,
+            "--image" => options.image = true,
+            "--gdb" => options.gdb = true,
+            "--initrd" => options.initial_ramdisk = Some(path("--initrd")?),
+            "--config" => options.boot_config = Some(path("--config")?),
+            "--serial" => options.serial = Some(path("--serial")?),
+            "--timeout" => {
+                let seconds = arguments
+                    .next()
+                    .and_then(|seconds| seconds.parse().ok())
+                    .ok_or_else(|| XtaskError::Usage(String::from("`--timeout` needs a number of seconds.")))?;
+
+                options.timeout = Some(Duration::from_secs(seconds));
+            }
+            other => return Err(XtaskError::Usage(format!("Unknown option `{}`.", other)))
+        }
+    }
+
+    Ok((command, options))
+}
+
+fn lightsaber_xtask_main() -> Result<(), XtaskError> {
+    let (command, options) = lightsaber_xtask_parse_arguments()?;
+
+    let root = cargo::lightsaber_xtask_project_root();
+    let target = root.join("target");
+    let profile = if options.release { Profile::Release } else { Profile::Debug };
+
+    if command == Command::Images {
+        return images::lightsaber_xtask_write_test_images(&root);
+    }
+
+    let bootloader = cargo::lightsaber_xtask_build_bootloader(&root, profile)?;
+    let kernel = match command {
+        Command::Build | Command::Run => cargo::lightsaber_xtask_build_kernel(&root, profile)?,
+        Command::Test => cargo::lightsaber_xtask_build_kernel_tests(&root, profile)?,
+        Command::Images => unreachable!("The test images are made before anything is built.")
+    };
+
+    let contents = EspContents {
+        bootloader,
+        kernel,
+        initial_ramdisk: options.initial_ramdisk.clone(),
+        boot_config: options.boot_config.clone()
+    };
+
+    // Tests get an ESP of their own, so a test kernel is never left behind for `run` to boot.
+    let (directory, image) = match command {
+        Command::Build | Command::Run => (target.join("esp"), target.join("lightsaber.img")),
+        Command::Test => (target.join("esp-test"), target.join("lightsaber-test.img")),
+        Command::Images => unreachable!("The test images are made before anything is built.")
+    };
+
+    esp::lightsaber_xtask_write_esp_directory(&contents, &directory)?;
+    println!("Wrote the ESP to {}.", directory.display());
+
+    if options.image {
+        esp::lightsaber_xtask_write_esp_image(&contents, &image)?;
+        println!("Wrote the ESP image to {}.", image.display());
+    }
+
+    if command == Command::Build {
+        return Ok(());
+    }
+
+    let qemu_options = QemuOptions {
+        disk: if options.image { QemuDisk::Image(&image) } else { QemuDisk::Directory(&directory) },
+        gdb: options.gdb,
+        serial: options.serial.as_deref(),
+        test: command == Command::Test,
+        // A debugger can hold the kernel for as long as it likes.
+        timeout: match command {
+            Command::Test if !options.gdb => Some(options.timeout.unwrap_or(qemu::QEMU_TEST_TIMEOUT)),
+            _ => None
+        }
+    };
+
+    let status = qemu::lightsaber_xtask_run_qemu(&root, &qemu_options)?;
+
+    match command {
+        Command::Test if status.code() == Some(qemu::QEMU_TESTS_PASSED) => {
+            println!("The kernel tests passed.");
+
+            Ok(())
+        }
+        Command::Test => Err(XtaskError::TestsFailed(status.code())),
+        _ => Ok(())
+    }
+}
+
+fn main() {
+    if let Err(error) = lightsaber_xtask_main() {
+        eprintln!("error: {}", error);
+        process::exit(1);
+    }
+}
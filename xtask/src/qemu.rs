@@ -0,0 +1,115 @@
+use std::{
+    env,
+    ffi::OsString,
+    fs,
+    path::Path,
+    process::{
+        Command,
+        ExitStatus
+    },
+    thread,
+    time::{
+        Duration,
+        Instant
+    }
+};
+
+use crate::XtaskError;
+
+// The kernel writes 0x10 to `isa-debug-exit` when every test passed, which QEMU turns into `(0x10 << 1) | 1`.
+pub const QEMU_TESTS_PASSED: i32 = 33;
+
+// How long a test kernel gets before it is taken to have hung.
+pub const QEMU_TEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+const QEMU_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+pub enum QemuDisk<'a> {
+    Directory(&'a Path),
+
+    Image(&'a Path)
+}
+
+pub struct QemuOptions<'a> {
+    pub disk: QemuDisk<'a>,
+    pub gdb: bool,
+    pub serial: Option<&'a Path>,
+    pub test: bool,
+    // QEMU is killed once this has passed, if given.
+    pub timeout: Option<Duration>
+}
+
+pub fn lightsaber_xtask_run_qemu(root: &Path, options: &QemuOptions<'_>) -> Result<ExitStatus, XtaskError> {
+    let firmware = root.join("bundled").join("ovmf");
+
+    // The firmware writes its variables back, so it gets a copy rather than the one in the repository.
+    let variables = root.join("target").join("OVMF_VARS.fd");
+    fs::copy(firmware.join("OVMF_VARS.fd"), &variables)?;
+
+    let mut command = Command::new(env::var_os("QEMU").unwrap_or_else(|| OsString::from("qemu-system-x86_64")));
+
+    command
+        .arg("-drive")
+        .arg(lightsaber_xtask_drive("if=pflash,format=raw,readonly=on,file=", &firmware.join("OVMF_CODE.fd")))
+        .arg("-drive")
+        .arg(lightsaber_xtask_drive("if=pflash,format=raw,file=", &variables))
+        .arg("-drive")
+        .arg(match options.disk {
+            QemuDisk::Directory(directory) => lightsaber_xtask_drive("format=raw,file=fat:rw:", directory),
+            QemuDisk::Image(image) => lightsaber_xtask_drive("format=raw,file=", image)
+        })
+        .args(["-m", "512M", "-no-reboot"]);
+
+    match options.serial {
+        Some(path) => {
+            let mut serial = OsString::from("file:");
+            serial.push(path);
+
+            command.arg("-serial").arg(serial);
+        }
+        None => {
+            command.args(["-serial", "stdio"]);
+        }
+    }
+
+    if options.gdb {
+        println!("Waiting for GDB on localhost:1234.");
+
+        command.args(["-s", "-S"]);
+    }
+
+    if options.test {
+        command.args(["-device", "isa-debug-exit,iobase=0xf4,iosize=0x04", "-display", "none"]);
+    }
+
+    let mut child = command.spawn()?;
+
+    let timeout = match options.timeout {
+        Some(timeout) => timeout,
+        None => return Ok(child.wait()?)
+    };
+
+    let deadline = Instant::now() + timeout;
+
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(status);
+        }
+
+        if Instant::now() >= deadline {
+            child.kill()?;
+            child.wait()?;
+
+            return Err(XtaskError::TimedOut(timeout));
+        }
+
+        thread::sleep(QEMU_POLL_INTERVAL);
+    }
+}
+
+fn lightsaber_xtask_drive(prefix: &str, path: &Path) -> OsString {
+    let mut drive = OsString::from(prefix);
+    drive.push(path);
+
+    drive
+}